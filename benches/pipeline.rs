@@ -0,0 +1,69 @@
+//! Benchmarks for each stage of a run, over synthetic histories of a few
+//! sizes. Run with `cargo bench`; criterion keeps the previous results in
+//! `target/criterion` and reports any change against them.
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+
+use chat_history_consolidator::graph::KnowledgeGraph;
+use chat_history_consolidator::synthetic::SyntheticHistory;
+use chat_history_consolidator::{ChatExtractor, Config, MarkdownGenerator};
+
+/// (sessions, messages per session)
+const SIZES: &[(usize, usize)] = &[(50, 20), (500, 20), (100, 200)];
+
+fn config() -> Config {
+    Config::builder().link_edits_to_commits(false).build().expect("the default config is valid")
+}
+
+fn extraction(c: &mut Criterion) {
+    let config = config();
+    let runtime = tokio::runtime::Runtime::new().expect("a tokio runtime");
+    let mut group = c.benchmark_group("extract");
+    for &(sessions, messages) in SIZES {
+        let history = SyntheticHistory::new(sessions, messages);
+        let store = history.memory_store(&config).expect("synthetic records");
+        group.throughput(Throughput::Elements((sessions * messages) as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(format!("{}x{}", sessions, messages)), &store, |b, store| {
+            b.to_async(&runtime).iter_batched(
+                || ChatExtractor::with_store(&config, Box::new(store.clone())),
+                |extractor| async move { extractor.extract_all().await.expect("extraction") },
+                BatchSize::LargeInput,
+            );
+        });
+    }
+    group.finish();
+}
+
+fn rendering(c: &mut Criterion) {
+    let config = config();
+    let mut group = c.benchmark_group("render");
+    for &(sessions, messages) in SIZES {
+        let history = SyntheticHistory::new(sessions, messages);
+        let (data, generations, prompts) = (history.sessions(), history.generations(), history.prompts());
+        group.throughput(Throughput::Elements((sessions * messages) as u64));
+        group.bench_function(BenchmarkId::from_parameter(format!("{}x{}", sessions, messages)), |b| {
+            b.iter(|| {
+                MarkdownGenerator::new(&config)
+                    .generate_output(&data, &generations, &prompts)
+                    .expect("rendering")
+            });
+        });
+    }
+    group.finish();
+}
+
+fn knowledge_graph(c: &mut Criterion) {
+    let config = config();
+    let mut group = c.benchmark_group("graph");
+    for &(sessions, messages) in SIZES {
+        let data = SyntheticHistory::new(sessions, messages).sessions();
+        group.throughput(Throughput::Elements((sessions * messages) as u64));
+        group.bench_function(BenchmarkId::from_parameter(format!("{}x{}", sessions, messages)), |b| {
+            b.iter(|| KnowledgeGraph::build(&data, &config));
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, extraction, rendering, knowledge_graph);
+criterion_main!(benches);
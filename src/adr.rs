@@ -0,0 +1,194 @@
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::atomic::write_atomic;
+use crate::config::Config;
+use crate::decisions::{message_excerpt, session_decisions, Decision};
+use crate::document::heading_anchor;
+use crate::{ChatSession, ComposerData, MessageRole};
+
+/// Where ADRs go, under PROJECT_PATH, unless `generate-adrs --dir` says otherwise.
+pub const ADR_DIR: &str = "docs/adr";
+
+/// The comment each generated ADR carries, so a decision never gets a
+/// second one - even after its file is renamed or edited.
+const HASH_MARKER: &str = "<!-- lore-decision: ";
+
+/// The longest a title gets.
+const TITLE_LENGTH: usize = 72;
+
+/// An Architecture Decision Record to write, in MADR format.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Adr {
+    /// Its number, which follows on from the ADRs already there
+    pub number: usize,
+    /// The file name, like `0004-use-sqlite-instead-of-postgres.md`
+    pub file_name: String,
+    /// Which decision it records, as `decision_hash` gives it
+    pub hash: String,
+    /// The title
+    pub title: String,
+    /// The whole file
+    pub text: String,
+}
+
+/// What `plan_adrs` found: the new ADRs, and how many decisions already had one.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AdrPlan {
+    /// ADRs to write, in the order the decisions were made
+    pub new: Vec<Adr>,
+    /// Decisions skipped because an ADR for them exists
+    pub existing: usize,
+}
+
+/// A short, stable fingerprint of a decision: the session and the words
+/// of the sentence that made it.
+pub fn decision_hash(session: &ChatSession, decision: &Decision) -> String {
+    let digest = Sha256::digest(format!("{}\n{}", session.composer_id, decision.excerpt).as_bytes());
+    digest.iter().take(6).map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// The decision hashes recorded in the ADRs under `dir`, and the highest
+/// ADR number there (from file names like `0003-...md`).
+pub fn scan_adrs(dir: &Path) -> Result<(HashSet<String>, usize)> {
+    let mut hashes = HashSet::new();
+    let mut highest = 0;
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok((hashes, highest)),
+        Err(error) => return Err(error).with_context(|| format!("Could not read {}", dir.display())),
+    };
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().is_none_or(|extension| extension != "md") {
+            continue;
+        }
+        let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+        if let Ok(number) = name.split('-').next().unwrap_or_default().parse::<usize>() {
+            highest = highest.max(number);
+        }
+        let text = fs::read_to_string(&path).with_context(|| format!("Could not read {}", path.display()))?;
+        for line in text.lines() {
+            if let Some(hash) = line.trim().strip_prefix(HASH_MARKER).and_then(|rest| rest.strip_suffix(" -->")) {
+                hashes.insert(hash.to_string());
+            }
+        }
+    }
+    Ok((hashes, highest))
+}
+
+/// Work out an ADR for every decision in `sessions` that `dir` doesn't
+/// have one for yet, numbered after the ones already there.
+pub fn plan_adrs(config: &Config, sessions: &[ComposerData], dir: &Path) -> Result<AdrPlan> {
+    let (known, highest) = scan_adrs(dir)?;
+    let mut found: Vec<(&ChatSession, Decision)> = sessions
+        .iter()
+        .flat_map(|data| &data.all_composers)
+        .flat_map(|session| session_decisions(session).into_iter().map(move |decision| (session, decision)))
+        .collect();
+    found.sort_by_key(|(_, decision)| decision.timestamp);
+
+    let mut plan = AdrPlan::default();
+    let mut seen = HashSet::new();
+    for (session, decision) in found {
+        let hash = decision_hash(session, &decision);
+        if known.contains(&hash) {
+            plan.existing += 1;
+            continue;
+        }
+        if !seen.insert(hash.clone()) {
+            continue;
+        }
+        let number = highest + plan.new.len() + 1;
+        let title = title(&config.redact_secrets(&decision.excerpt));
+        plan.new.push(Adr {
+            number,
+            file_name: format!("{:04}-{}.md", number, slug(&title)),
+            text: madr(config, session, &decision, &title, &hash),
+            hash,
+            title,
+        });
+    }
+    Ok(plan)
+}
+
+/// Write the planned ADRs into `dir`, returning their paths.
+pub fn write_adrs(dir: &Path, adrs: &[Adr]) -> Result<Vec<PathBuf>> {
+    fs::create_dir_all(dir).with_context(|| format!("Could not create {}", dir.display()))?;
+    let mut written = Vec::new();
+    for adr in adrs {
+        let path = dir.join(&adr.file_name);
+        write_atomic(&path, adr.text.as_bytes())?;
+        written.push(path);
+    }
+    Ok(written)
+}
+
+/// A file name slug for a title, cut at a word after 50 characters or so.
+fn slug(title: &str) -> String {
+    let mut slug = String::new();
+    for word in heading_anchor(title).split('-').filter(|word| !word.is_empty()) {
+        if !slug.is_empty() && slug.len() + word.len() + 1 > 50 {
+            break;
+        }
+        if !slug.is_empty() {
+            slug.push('-');
+        }
+        slug.push_str(word);
+    }
+    slug
+}
+
+/// A title from the sentence that made the decision: without its closing
+/// punctuation, capitalized, and cut at a word if it's long.
+pub(crate) fn title(excerpt: &str) -> String {
+    let excerpt = excerpt.trim_end_matches("...").trim_end_matches(['.', '!', '?', ':', ';', ',']).trim();
+    let mut title = String::new();
+    for word in excerpt.split_whitespace() {
+        if !title.is_empty() && title.chars().count() + word.chars().count() + 1 > TITLE_LENGTH {
+            break;
+        }
+        if !title.is_empty() {
+            title.push(' ');
+        }
+        title.push_str(word);
+    }
+    let mut chars = title.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => "Untitled decision".to_string(),
+    }
+}
+
+/// The ADR itself, following the MADR template: front matter with the
+/// status and date, then the context, the outcome and its consequences.
+/// The context quotes the exchange; consequences are left for a person
+/// to fill in, since a chat rarely spells them out.
+fn madr(config: &Config, session: &ChatSession, decision: &Decision, title: &str, hash: &str) -> String {
+    let zone = config.display_zone();
+    let date = zone.format_millis(decision.timestamp, "%Y-%m-%d").unwrap_or_default();
+    let speaker = |role: MessageRole| match role {
+        MessageRole::User => "User",
+        MessageRole::Assistant => "Assistant",
+    };
+    let quote = |text: &str| format!("> {}", config.redact_secrets(text));
+
+    let mut text = format!(
+        "---\nstatus: proposed\ndate: {}\n---\n\n{}{} -->\n\n# {}\n\n## Context and Problem Statement\n\n",
+        date, HASH_MARKER, hash, title
+    );
+    text.push_str(&format!("From the chat session \"{}\" (`{}`) on {}:\n\n", session.name, session.composer_id, date));
+    if let Some(previous) = decision.message.checked_sub(1).and_then(|index| session.messages.get(index)) {
+        text.push_str(&format!("{}\n>\n", quote(&format!("**{}**: {}", speaker(previous.role), message_excerpt(&previous.text)))));
+    }
+    text.push_str(&format!("{}\n\n", quote(&format!("**{}**: {}", speaker(decision.role), decision.excerpt))));
+    text.push_str("## Decision Outcome\n\n");
+    text.push_str(&format!("{}\n\n", config.redact_secrets(&decision.excerpt)));
+    text.push_str("### Consequences\n\n");
+    text.push_str("* Good, because <!-- what this makes easier -->\n");
+    text.push_str("* Bad, because <!-- what this makes harder, or rules out -->\n");
+    text
+}
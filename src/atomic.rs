@@ -0,0 +1,114 @@
+use anyhow::{Context, Result};
+use std::fs::{self, File, Permissions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// What a file's previous version is kept as, after its name:
+/// `chat-history-consolidated.md.bak`.
+pub const BACKUP_SUFFIX: &str = ".bak";
+
+/// Write `data` to `path` so that a crash or a full disk leaves either the
+/// old file or the new one there, never half of one. It's written to a
+/// temporary file next to `path`, synced to disk, and renamed over it.
+/// A file that was there keeps its permissions (a `0600` config.env
+/// stays `0600`).
+pub fn write_atomic(path: &Path, data: &[u8]) -> Result<()> {
+    write_with(path, permissions(path), |file| file.write_all(data))
+        .with_context(|| format!("Could not write {}", path.display()))
+}
+
+/// Copy `from` to `to` the way `write_atomic` writes, so `to` is never
+/// left half-copied. The file is streamed across, never held in memory,
+/// and the copy gets the permissions of the original.
+pub fn copy_atomic(from: &Path, to: &Path) -> Result<()> {
+    copy_with(from, to, permissions(from))
+}
+
+/// Put `data` in place at `path` with `write_atomic`, first keeping
+/// whatever was there as `<name>.bak` if `keep_previous` is set. The
+/// previous version is copied, not moved, so `path` is never missing.
+pub fn replace(path: &Path, data: &[u8], keep_previous: bool) -> Result<()> {
+    if keep_previous && path.is_file() {
+        copy_atomic(path, &backup_path(path))?;
+    }
+    write_atomic(path, data)
+}
+
+/// Put a file rendered at `from` (in the run's workspace, say) in place
+/// at `to`, as `replace` does.
+pub fn install(from: &Path, to: &Path, keep_previous: bool) -> Result<()> {
+    if keep_previous && to.is_file() {
+        copy_atomic(to, &backup_path(to))?;
+    }
+    copy_with(from, to, permissions(to))
+}
+
+/// Where `replace` keeps the previous version of `path`.
+pub fn backup_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(BACKUP_SUFFIX);
+    path.with_file_name(name)
+}
+
+/// Copy `from` to `to` as `copy_atomic` does, giving the copy `permissions`
+/// (the default for a new file if there are none).
+fn copy_with(from: &Path, to: &Path, permissions: Option<Permissions>) -> Result<()> {
+    let mut source = File::open(from).with_context(|| format!("Could not read {}", from.display()))?;
+    write_with(to, permissions, |file| io::copy(&mut source, file).map(|_| ()))
+        .with_context(|| format!("Could not copy {} to {}", from.display(), to.display()))
+}
+
+/// The permissions of the file at `path`, if there is one.
+fn permissions(path: &Path) -> Option<Permissions> {
+    fs::metadata(path).ok().filter(|metadata| metadata.is_file()).map(|metadata| metadata.permissions())
+}
+
+/// Fill the temporary file for `path` with `write`, sync it and rename it
+/// over `path`, removing it again if anything fails. It gets `permissions`
+/// before anything is written, so a secret is never readable by more
+/// people than the file it replaces.
+fn write_with(
+    path: &Path,
+    permissions: Option<Permissions>,
+    write: impl FnOnce(&mut File) -> io::Result<()>,
+) -> io::Result<()> {
+    let partial = partial_path(path);
+    let written = (|| -> io::Result<()> {
+        let mut file = File::create(&partial)?;
+        if let Some(permissions) = permissions {
+            file.set_permissions(permissions)?;
+        }
+        write(&mut file)?;
+        file.sync_all()?;
+        drop(file);
+        fs::rename(&partial, path)?;
+        sync_dir(path)
+    })();
+    if written.is_err() {
+        let _ = fs::remove_file(&partial);
+    }
+    written
+}
+
+/// The temporary file `path` is written to first: hidden, in the same
+/// directory so the rename never crosses filesystems, and named after
+/// this process so two writers don't share one.
+fn partial_path(path: &Path) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!(".{}.{}.partial", name, std::process::id()))
+}
+
+/// Sync the directory `path` is in, so the rename itself survives a crash.
+#[cfg(unix)]
+fn sync_dir(path: &Path) -> io::Result<()> {
+    match path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        Some(dir) => File::open(dir)?.sync_all(),
+        None => File::open(".")?.sync_all(),
+    }
+}
+
+/// Windows has no way to sync a directory; the rename is as durable as it gets.
+#[cfg(not(unix))]
+fn sync_dir(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
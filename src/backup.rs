@@ -0,0 +1,138 @@
+use anyhow::{bail, Context, Result};
+use chrono::Utc;
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::config::Config;
+
+/// Where backups go when BACKUP_DIR isn't set, in the state directory.
+pub const BACKUP_DIR: &str = "backups";
+
+/// The checksum list in each backup, in `sha256sum` format so
+/// `sha256sum -c MANIFEST.sha256` can check it too.
+pub const MANIFEST_FILE: &str = "MANIFEST.sha256";
+
+/// One backup run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Backup {
+    /// The dated directory it went into
+    pub dir: PathBuf,
+    /// The files copied, relative to `dir`
+    pub files: Vec<PathBuf>,
+    /// How many bytes were copied
+    pub bytes: u64,
+}
+
+/// Copy the source's files into a new dated directory under `root`, with
+/// a manifest of their checksums, before anything reads them. SQLite
+/// databases are copied with their `-wal` and `-shm` files, since recent
+/// changes may only exist there. Each file keeps its parent directory's
+/// name (the workspace ID, `globalStorage`), so Cursor's two
+/// `state.vscdb` files don't collide.
+pub fn back_up(config: &Config, root: &Path) -> Result<Backup> {
+    let stamp = Utc::now().format("%Y-%m-%dT%H%M%S%.3fZ").to_string();
+    let mut dir = root.join(&stamp);
+    // Two backups in the same millisecond still get a directory each
+    let mut attempt = 1;
+    while dir.exists() {
+        dir = root.join(format!("{}-{}", stamp, attempt));
+        attempt += 1;
+    }
+    fs::create_dir_all(&dir).with_context(|| format!("Could not create backup directory {}", dir.display()))?;
+    // The backups hold the raw chat databases, so keep them out of any
+    // repository BACKUP_DIR or STATE_DIR is pointed into
+    let gitignore = root.join(".gitignore");
+    if !gitignore.exists() {
+        fs::write(&gitignore, "*\n")?;
+    }
+
+    let mut backup = Backup {
+        dir: dir.clone(),
+        files: Vec::new(),
+        bytes: 0,
+    };
+    let mut manifest = String::new();
+    for source in config.source_files() {
+        if !source.is_file() {
+            continue;
+        }
+        let parent = source
+            .parent()
+            .and_then(|parent| parent.file_name())
+            .map(PathBuf::from)
+            .unwrap_or_default();
+        let name = source.file_name().unwrap_or_default().to_string_lossy().to_string();
+        for suffix in ["", "-wal", "-shm"] {
+            let from = source.with_file_name(format!("{}{}", name, suffix));
+            if !from.is_file() {
+                continue;
+            }
+            let relative = parent.join(format!("{}{}", name, suffix));
+            let to = dir.join(&relative);
+            fs::create_dir_all(to.parent().unwrap_or(&dir))?;
+            backup.bytes += fs::copy(&from, &to).with_context(|| format!("Could not back up {}", from.display()))?;
+            manifest.push_str(&format!("{}  {}\n", checksum(&to)?, manifest_path(&relative)));
+            backup.files.push(relative);
+        }
+    }
+    if backup.files.is_empty() {
+        // Don't leave an empty dated directory behind for a source with nothing on disk
+        let _ = fs::remove_dir(&dir);
+        bail!("There was nothing to back up for the {} source", config.source);
+    }
+
+    fs::write(dir.join(MANIFEST_FILE), manifest).context("Could not write the backup manifest")?;
+    Ok(backup)
+}
+
+/// Check a backup against its manifest. Returns the files whose contents
+/// no longer match (or that are missing); empty means the backup is intact.
+pub fn verify(dir: &Path) -> Result<Vec<PathBuf>> {
+    let manifest = fs::read_to_string(dir.join(MANIFEST_FILE))
+        .with_context(|| format!("{} has no {}", dir.display(), MANIFEST_FILE))?;
+    let mut damaged = Vec::new();
+    for line in manifest.lines() {
+        let Some((expected, relative)) = line.split_once("  ") else {
+            continue;
+        };
+        let path = dir.join(relative);
+        if checksum(&path).ok().as_deref() != Some(expected) {
+            damaged.push(PathBuf::from(relative));
+        }
+    }
+    Ok(damaged)
+}
+
+/// Delete all but the newest `keep` backups under `root`. Returns the
+/// directories that were removed.
+pub fn prune(root: &Path, keep: usize) -> Result<Vec<PathBuf>> {
+    let mut backups: Vec<PathBuf> = fs::read_dir(root)
+        .with_context(|| format!("Could not read {}", root.display()))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.join(MANIFEST_FILE).is_file())
+        .collect();
+    // The directory names are timestamps, so they sort by age
+    backups.sort();
+    let removed: Vec<PathBuf> = backups.drain(..backups.len().saturating_sub(keep)).collect();
+    for dir in &removed {
+        fs::remove_dir_all(dir).with_context(|| format!("Could not remove old backup {}", dir.display()))?;
+    }
+    Ok(removed)
+}
+
+fn checksum(path: &Path) -> Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+/// A path as it's written in the manifest: always with forward slashes.
+fn manifest_path(relative: &Path) -> String {
+    relative
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
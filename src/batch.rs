@@ -0,0 +1,486 @@
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::atomic::write_atomic;
+use crate::config::Config;
+use crate::document::{Block, Document};
+use crate::report::{exit_code, ReportStatus, RunReport};
+use crate::terminal::{Align, Table, Terminal, Tone};
+use crate::time::DisplayZone;
+use crate::ComposerData;
+
+/// How far back counts as recent when projects are ranked, in days.
+pub const RECENT_DAYS: i64 = 30;
+
+/// The most sessions the cross-project timeline lists.
+const TIMELINE_SESSIONS: usize = 100;
+
+/// Where the cross-project index goes unless the manifest says otherwise.
+fn default_index() -> String {
+    "index.md".to_string()
+}
+
+/// The projects a `batch` run goes through, read from a JSON manifest like
+///
+/// ```json
+/// {
+///   "projects": [
+///     { "name": "api", "path": "~/code/api", "workspace_id": "a29d4797..." },
+///     { "name": "web", "path": "~/code/web", "output_dir": "lore/web", "settings": { "SOURCE": "continue" } }
+///   ]
+/// }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BatchManifest {
+    /// The projects, in the order they're run
+    pub projects: Vec<BatchProject>,
+    /// Where to write the index of every project's lore (relative to the
+    /// manifest); empty for none
+    #[serde(default = "default_index")]
+    pub index: String,
+    /// Where the manifest was read from; relative paths in it start here
+    #[serde(skip)]
+    pub dir: PathBuf,
+}
+
+/// One project in a batch manifest. Everything but the name is optional;
+/// what isn't given comes from the project's config file (or the one
+/// `--config` names) and the environment, like a normal run.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BatchProject {
+    /// The project's name (PROJECT_NAME)
+    pub name: String,
+    /// The project's checkout (PROJECT_PATH)
+    pub path: Option<String>,
+    /// The Cursor workspace its chats are in (WORKSPACE_ID)
+    pub workspace_id: Option<String>,
+    /// Where its lore goes (OUTPUT_DIR); `OUTPUT_DIR` under `path` if not given
+    pub output_dir: Option<String>,
+    /// A config file of its own, instead of the one `--config` names
+    pub config: Option<String>,
+    /// A profile of its config file to use, instead of the one `--profile` names
+    pub profile: Option<String>,
+    /// Any other settings, by name, like `{ "SOURCE": "continue" }`
+    pub settings: HashMap<String, String>,
+}
+
+impl BatchManifest {
+    /// Read a manifest, checking every project has a name of its own.
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path).with_context(|| format!("Could not read {}", path.display()))?;
+        let mut manifest: BatchManifest =
+            serde_json::from_str(&text).with_context(|| format!("{} is not a batch manifest", path.display()))?;
+        manifest.dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        if manifest.projects.is_empty() {
+            bail!("{} lists no projects", path.display());
+        }
+        for (index, project) in manifest.projects.iter().enumerate() {
+            if project.name.trim().is_empty() {
+                bail!("Project {} in {} has no name", index + 1, path.display());
+            }
+            if manifest.projects[..index].iter().any(|earlier| earlier.name == project.name) {
+                bail!("{} lists '{}' twice", path.display(), project.name);
+            }
+        }
+        Ok(manifest)
+    }
+
+    /// Where the cross-project index goes, if anywhere.
+    pub fn index_path(&self) -> Option<PathBuf> {
+        let index = self.index.trim();
+        (!index.is_empty()).then(|| self.dir.join(shellexpand::tilde(index).as_ref()))
+    }
+
+    /// The configuration each project runs with, in order, with
+    /// `settings` (from the command line) winning over everything else.
+    /// Fails if any of them is invalid, or if two would write the same file.
+    pub fn configs(
+        &self,
+        default_config_file: &str,
+        default_profile: Option<&str>,
+        settings: &HashMap<String, String>,
+    ) -> Result<Vec<(String, Config)>> {
+        let mut configs: Vec<(String, Config)> = Vec::new();
+        for project in &self.projects {
+            let config = project
+                .config(&self.dir, default_config_file, default_profile, settings)
+                .with_context(|| format!("Project '{}' is not set up right", project.name))?;
+            let output = output_path(&config)?;
+            if let Some((other, _)) = configs.iter().find(|(_, other)| output_path(other).is_ok_and(|path| path == output)) {
+                bail!("Projects '{}' and '{}' would both write {}", other, project.name, output.display());
+            }
+            configs.push((project.name.clone(), config));
+        }
+        Ok(configs)
+    }
+}
+
+impl BatchProject {
+    /// The project's configuration. Paths in the manifest are relative to
+    /// the manifest's directory, and its settings win over the environment
+    /// and the config file (but not over `settings`, from the command line).
+    pub fn config(
+        &self,
+        manifest_dir: &Path,
+        default_config_file: &str,
+        default_profile: Option<&str>,
+        settings: &HashMap<String, String>,
+    ) -> Result<Config> {
+        let resolve = |path: &str| manifest_dir.join(shellexpand::tilde(path).as_ref()).to_string_lossy().into_owned();
+        let mut overrides = self.settings.clone();
+        overrides.insert("PROJECT_NAME".to_string(), self.name.clone());
+        if let Some(path) = &self.path {
+            overrides.insert("PROJECT_PATH".to_string(), resolve(path));
+        }
+        if let Some(workspace_id) = &self.workspace_id {
+            overrides.insert("WORKSPACE_ID".to_string(), workspace_id.clone());
+        }
+        if let Some(output_dir) = &self.output_dir {
+            overrides.insert("OUTPUT_DIR".to_string(), resolve(output_dir));
+        }
+        overrides.extend(settings.clone());
+        let config_file = self.config.as_deref().map_or(default_config_file.to_string(), resolve);
+        let profile = self.profile.as_deref().or(default_profile);
+        let mut config = Config::load_with_profile(&config_file, profile, &overrides, &HashMap::new())?;
+
+        // Without an output directory of its own, a project keeps its lore
+        // in its own checkout rather than next to every other project's
+        if self.output_dir.is_none() && self.path.is_some() && Path::new(&config.output_dir).is_relative() {
+            config.output_dir = config.project_file(&config.output_dir).to_string_lossy().into_owned();
+        }
+        Ok(config)
+    }
+}
+
+/// The file a project's lore is written to, on a run started now.
+fn output_path(config: &Config) -> Result<PathBuf> {
+    let now = Utc::now();
+    let dir = PathBuf::from(shellexpand::tilde(&config.output_directory(now)?).as_ref());
+    let dir = std::path::absolute(&dir).unwrap_or(dir);
+    Ok(dir.join(config.output_file_name(now)?))
+}
+
+/// How one project of a batch went.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct BatchEntry {
+    /// The project's name
+    pub project: String,
+    /// Its run's report
+    pub report: RunReport,
+}
+
+/// The combined report of a `batch` run: every project's run report, and
+/// how many succeeded.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct BatchReport {
+    /// The version of this program that ran
+    pub version: String,
+    /// When the batch started
+    pub started_at: DateTime<Utc>,
+    /// When it ended
+    pub finished_at: DateTime<Utc>,
+    /// How many projects were written (partial runs included)
+    pub succeeded: usize,
+    /// How many projects failed
+    pub failed: usize,
+    /// How many weren't run because an earlier one failed with `--fail-fast`
+    pub skipped: usize,
+    /// The cross-project index, if one was written
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub index: Option<PathBuf>,
+    /// The exit code the batch ended with
+    pub exit_code: i32,
+    /// Each project's run, in order
+    pub projects: Vec<BatchEntry>,
+}
+
+impl BatchReport {
+    /// Start the report for a batch.
+    pub fn start() -> Self {
+        let now = Utc::now();
+        BatchReport {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            started_at: now,
+            finished_at: now,
+            succeeded: 0,
+            failed: 0,
+            skipped: 0,
+            index: None,
+            exit_code: exit_code::SUCCESS,
+            projects: Vec::new(),
+        }
+    }
+
+    /// Add a finished project's report.
+    pub fn add(&mut self, project: &str, report: RunReport) {
+        match report.status {
+            ReportStatus::Failed => self.failed += 1,
+            ReportStatus::Succeeded | ReportStatus::Partial => self.succeeded += 1,
+        }
+        self.projects.push(BatchEntry { project: project.to_string(), report });
+    }
+
+    /// Close the report, returning the exit code: 0 if every project was
+    /// written, `BATCH` if any failed or were skipped.
+    pub fn finish(&mut self, skipped: usize) -> i32 {
+        self.finished_at = Utc::now();
+        self.skipped = skipped;
+        self.exit_code = if self.failed + skipped == 0 { exit_code::SUCCESS } else { exit_code::BATCH };
+        self.exit_code
+    }
+
+    /// A table of the projects and how each went, for `terminal`.
+    pub fn summary(&self, terminal: &Terminal) -> String {
+        let mut table = Table::new(&["project", "status", "sessions", "output"]).align(2, Align::Right);
+        for entry in &self.projects {
+            let report = &entry.report;
+            let (status, tone) = match report.status {
+                ReportStatus::Succeeded => ("succeeded", Tone::Success),
+                ReportStatus::Partial => ("partial", Tone::Warning),
+                ReportStatus::Failed => ("failed", Tone::Error),
+            };
+            let detail = match (&report.error, report.outputs.first()) {
+                (Some(error), _) => error.lines().next().unwrap_or_default().to_string(),
+                (None, Some(output)) => output.path.display().to_string(),
+                (None, None) => String::new(),
+            };
+            table.row(vec![
+                entry.project.clone(),
+                terminal.paint(tone, status),
+                report.counts.sessions.to_string(),
+                detail,
+            ]);
+        }
+        let mut summary = table.render(terminal);
+        summary.push_str(&format!("{} succeeded, {} failed", self.succeeded, self.failed));
+        if self.skipped > 0 {
+            summary.push_str(&format!(", {} skipped", self.skipped));
+        }
+        summary.push('\n');
+        if let Some(index) = &self.index {
+            summary.push_str(&format!("Index: {}\n", index.display()));
+        }
+        summary
+    }
+
+    /// Write the report as pretty JSON to `path`, or to stdout for `-`.
+    pub fn write(&self, path: &str) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)? + "\n";
+        if path == "-" {
+            print!("{}", json);
+            return Ok(());
+        }
+        if let Some(parent) = Path::new(path).parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        write_atomic(Path::new(path), json.as_bytes()).with_context(|| format!("Could not write the batch report to {}", path))
+    }
+}
+
+/// How far a batch got: the projects that finished, kept after each one
+/// so `batch --resume` can skip them if the batch is interrupted.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchProgress {
+    /// When the batch started
+    pub started_at: DateTime<Utc>,
+    /// The projects that finished, with their reports
+    pub finished: Vec<BatchEntry>,
+    /// What they add to the index
+    pub indexed: Vec<ProjectActivity>,
+}
+
+impl BatchProgress {
+    /// A batch that's just starting.
+    pub fn start() -> Self {
+        BatchProgress { started_at: Utc::now(), finished: Vec::new(), indexed: Vec::new() }
+    }
+
+    /// Where the progress of the batch `manifest` lists is kept, under
+    /// `state_home`: one file per manifest.
+    pub fn path(state_home: &Path, manifest: &Path) -> PathBuf {
+        let manifest = std::path::absolute(manifest).unwrap_or_else(|_| manifest.to_path_buf());
+        let digest = Sha256::digest(manifest.to_string_lossy().as_bytes());
+        let hash: String = digest.iter().take(8).map(|byte| format!("{:02x}", byte)).collect();
+        state_home.join("batches").join(format!("{}.json", hash))
+    }
+
+    /// The progress an interrupted batch left, if there is any.
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let text = fs::read_to_string(path).with_context(|| format!("Could not read {}", path.display()))?;
+        let progress = serde_json::from_str(&text).with_context(|| format!("{} is not a batch's progress", path.display()))?;
+        Ok(Some(progress))
+    }
+
+    /// Record that `project` finished.
+    pub fn finish(&mut self, project: &str, report: &RunReport, activity: Option<&ProjectActivity>) {
+        self.finished.push(BatchEntry { project: project.to_string(), report: report.clone() });
+        self.indexed.extend(activity.cloned());
+    }
+
+    /// Save it, through a temporary file so an interruption can't leave
+    /// half of it.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).with_context(|| format!("Could not create {}", dir.display()))?;
+        }
+        write_atomic(path, &serde_json::to_vec_pretty(self)?)
+    }
+}
+
+/// A session, as the cross-project index sees it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexedSession {
+    /// The session's name
+    pub name: String,
+    /// When it was last active (Unix milliseconds)
+    pub last_active: i64,
+    /// How many messages it has
+    pub messages: usize,
+}
+
+impl IndexedSession {
+    /// The sessions of a run, for the index.
+    pub fn from_sessions(sessions: &[ComposerData]) -> Vec<IndexedSession> {
+        sessions
+            .iter()
+            .flat_map(|data| &data.all_composers)
+            .map(|session| IndexedSession {
+                name: session.name.clone(),
+                last_active: session.last_updated_at.max(session.created_at),
+                messages: session.messages.len(),
+            })
+            .collect()
+    }
+}
+
+/// A project's AI activity and where its lore is.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProjectActivity {
+    /// The project's name
+    pub project: String,
+    /// The lore the batch wrote for it
+    pub lore: PathBuf,
+    /// Its sessions
+    pub sessions: Vec<IndexedSession>,
+}
+
+/// The index of a batch's lore, to be written in `index_dir`: the projects
+/// ranked by how many sessions were active in the last `RECENT_DAYS` days
+/// (then by messages, then by how recently), each linking to its lore,
+/// and the most recent sessions of all of them by week. `failed` are
+/// the projects whose lore couldn't be brought up to date.
+pub fn index_document(
+    projects: &[ProjectActivity],
+    failed: &[String],
+    index_dir: &Path,
+    zone: &DisplayZone,
+    now: i64,
+) -> Document {
+    let since = now - RECENT_DAYS * 24 * 60 * 60 * 1000;
+    let mut ranked: Vec<(&ProjectActivity, usize, usize, i64)> = projects
+        .iter()
+        .map(|project| {
+            let recent: Vec<&IndexedSession> = project.sessions.iter().filter(|session| session.last_active >= since).collect();
+            let messages = recent.iter().map(|session| session.messages).sum();
+            let latest = project.sessions.iter().map(|session| session.last_active).max().unwrap_or(0);
+            (project, recent.len(), messages, latest)
+        })
+        .collect();
+    ranked.sort_by_key(|&(_, recent, messages, latest)| std::cmp::Reverse((recent, messages, latest)));
+
+    let mut document = Document::default();
+    document.push(Block::heading(1, "Project Lore Index"));
+    document.push(Block::heading(2, "Projects"));
+    document.push(Block::Paragraph(format!(
+        "Ranked by the sessions active in the last {} days.",
+        RECENT_DAYS
+    )));
+    let items = ranked
+        .iter()
+        .map(|(project, recent, messages, latest)| {
+            let mut item = format!(
+                "**[{}]({})**: {} recent {}, {} {}",
+                project.project,
+                link(index_dir, &project.lore),
+                recent,
+                plural(*recent, "session"),
+                messages,
+                plural(*messages, "message")
+            );
+            match zone.format_millis(*latest, "%Y-%m-%d").filter(|_| *latest > 0) {
+                Some(date) => item.push_str(&format!("; last active {}", date)),
+                None => item.push_str("; no sessions yet"),
+            }
+            item
+        })
+        .collect();
+    document.push(Block::numbered(items));
+    if !failed.is_empty() {
+        let failed: Vec<String> = failed.iter().map(|project| format!("**{}**", project)).collect();
+        document.push(Block::Paragraph(format!("Not brought up to date this time: {}", failed.join(", "))));
+    }
+
+    let mut sessions: Vec<(&str, &IndexedSession)> = projects
+        .iter()
+        .flat_map(|project| project.sessions.iter().map(move |session| (project.project.as_str(), session)))
+        .filter(|(_, session)| session.last_active > 0)
+        .collect();
+    sessions.sort_by_key(|(_, session)| std::cmp::Reverse(session.last_active));
+    sessions.truncate(TIMELINE_SESSIONS);
+    if !sessions.is_empty() {
+        document.push(Block::heading(2, "Timeline"));
+        let mut week = String::new();
+        let mut items: Vec<String> = Vec::new();
+        for (project, session) in sessions {
+            let key = zone.format_millis(session.last_active, "%G-W%V").unwrap_or_default();
+            if key != week {
+                if !items.is_empty() {
+                    document.push(Block::bullets(std::mem::take(&mut items)));
+                }
+                document.push(Block::heading(3, &key));
+                week = key;
+            }
+            let date = zone.format_millis(session.last_active, "%b %d").unwrap_or_default();
+            items.push(format!("{} - **{}**: {}", date, project, session.name));
+        }
+        document.push(Block::bullets(items));
+    }
+    document
+}
+
+fn plural(count: usize, noun: &str) -> String {
+    if count == 1 {
+        noun.to_string()
+    } else {
+        format!("{}s", noun)
+    }
+}
+
+/// A link target for `to` from a document in `from_dir`: relative where
+/// both are under the same root, with spaces escaped.
+fn link(from_dir: &Path, to: &Path) -> String {
+    let absolute = |path: &Path| std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let (from, to) = (absolute(from_dir), absolute(to));
+    let from: Vec<_> = from.components().collect();
+    let target: Vec<_> = to.components().collect();
+    let common = from.iter().zip(&target).take_while(|(a, b)| a == b).count();
+    let path = if common == 0 {
+        to.to_string_lossy().into_owned()
+    } else {
+        let mut parts: Vec<String> = vec!["..".to_string(); from.len() - common];
+        parts.extend(target[common..].iter().map(|part| part.as_os_str().to_string_lossy().into_owned()));
+        parts.join("/")
+    };
+    path.replace(' ', "%20")
+}
@@ -0,0 +1,10 @@
+//! `cargo lore`: the same program under the name cargo looks for, so
+//! `cargo install persistent-code-lore` makes it a cargo subcommand that
+//! writes the lore for whichever crate it's run in.
+
+#[path = "../main.rs"]
+mod consolidator;
+
+fn main() {
+    consolidator::main()
+}
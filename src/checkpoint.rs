@@ -0,0 +1,232 @@
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::atomic::write_atomic;
+use crate::config::Config;
+use crate::importers::ImportedHistory;
+use crate::ChatMessage;
+
+/// Where an unfinished run keeps its checkpoint, in the state directory.
+pub const CHECKPOINT_DIR: &str = "checkpoint";
+
+/// What the checkpoint is for and which stages are done.
+const MANIFEST_FILE: &str = "checkpoint.json";
+
+/// Transcripts read so far, one JSON object per line.
+const TRANSCRIPTS_FILE: &str = "transcripts.jsonl";
+
+/// Everything the extract stage found, once it's done.
+const HISTORY_FILE: &str = "history.json";
+
+/// What to do about a checkpoint an interrupted run left behind.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResumeMode {
+    /// Pick up where it stopped (`--resume`)
+    Resume,
+    /// Throw it away and start over (`--restart`)
+    Restart,
+    /// Refuse to run until one of the two is chosen
+    Ask,
+}
+
+/// The checkpoint's manifest.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Manifest {
+    /// Which run it belongs to (see `run_key`)
+    run: String,
+    /// When that run started
+    started_at: DateTime<Utc>,
+    /// The stages it finished, in order
+    completed: Vec<String>,
+}
+
+/// A transcript in the transcripts file.
+#[derive(Serialize, Deserialize)]
+struct Transcript {
+    id: String,
+    messages: Vec<ChatMessage>,
+}
+
+/// What a run has done so far, kept on disk as it goes so an interrupted
+/// run (Ctrl-C, a crash, a reboot) can carry on instead of starting over:
+/// the stages it finished, what the extract stage found, and each session's
+/// transcript as soon as it's read. A run that finishes removes it.
+#[derive(Debug)]
+pub struct Checkpoint {
+    /// Where it's kept
+    dir: PathBuf,
+    /// The manifest, as saved
+    manifest: Mutex<Manifest>,
+    /// The transcripts saved so far, by session ID
+    transcripts: Mutex<HashMap<String, Vec<ChatMessage>>>,
+    /// Whether an earlier run's work is being picked up
+    resumed: bool,
+}
+
+impl Checkpoint {
+    /// Open the checkpoint in `dir` for the run `key` names. What an
+    /// earlier run of the same key left is picked up with `Resume`,
+    /// thrown away with `Restart`, and an error with `Ask`. A checkpoint
+    /// from a different run (another source or database), or from one
+    /// that hadn't got anywhere, is always thrown away.
+    pub fn open(dir: &Path, key: &str, mode: ResumeMode) -> Result<Self> {
+        // A run that stopped before getting anywhere left nothing to pick up
+        let previous = read_manifest(dir).filter(|manifest| {
+            manifest.run == key && (!manifest.completed.is_empty() || dir.join(TRANSCRIPTS_FILE).exists())
+        });
+        match (&previous, mode) {
+            (Some(manifest), ResumeMode::Ask) => bail!(
+                "A run started {} was interrupted; pass --resume to pick up where it stopped, or --restart to start over",
+                manifest.started_at.format("%Y-%m-%d %H:%M UTC")
+            ),
+            (Some(manifest), ResumeMode::Resume) => {
+                let transcripts = read_transcripts(&dir.join(TRANSCRIPTS_FILE));
+                Ok(Checkpoint {
+                    dir: dir.to_path_buf(),
+                    manifest: Mutex::new(manifest.clone()),
+                    transcripts: Mutex::new(transcripts),
+                    resumed: true,
+                })
+            }
+            _ => {
+                if dir.exists() {
+                    fs::remove_dir_all(dir).with_context(|| format!("Could not remove {}", dir.display()))?;
+                }
+                fs::create_dir_all(dir).with_context(|| format!("Could not create {}", dir.display()))?;
+                let manifest = Manifest { run: key.to_string(), started_at: Utc::now(), completed: Vec::new() };
+                write_manifest(dir, &manifest)?;
+                Ok(Checkpoint {
+                    dir: dir.to_path_buf(),
+                    manifest: Mutex::new(manifest),
+                    transcripts: Mutex::new(HashMap::new()),
+                    resumed: false,
+                })
+            }
+        }
+    }
+
+    /// Whether an earlier run's work is being picked up.
+    pub fn is_resumed(&self) -> bool {
+        self.resumed
+    }
+
+    /// When the run this checkpoint belongs to started.
+    pub fn started_at(&self) -> DateTime<Utc> {
+        self.manifest.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).started_at
+    }
+
+    /// Whether `stage` finished.
+    pub fn is_done(&self, stage: &str) -> bool {
+        let manifest = self.manifest.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        manifest.completed.iter().any(|done| done == stage)
+    }
+
+    /// Record that `stage` finished.
+    pub fn complete(&self, stage: &str) -> Result<()> {
+        let mut manifest = self.manifest.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if !manifest.completed.iter().any(|done| done == stage) {
+            manifest.completed.push(stage.to_string());
+            write_manifest(&self.dir, &manifest)?;
+        }
+        Ok(())
+    }
+
+    /// How many transcripts have been saved.
+    pub fn transcripts(&self) -> usize {
+        self.transcripts.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).len()
+    }
+
+    /// The transcript saved for a session, if there is one.
+    pub fn transcript(&self, session_id: &str) -> Option<Vec<ChatMessage>> {
+        let transcripts = self.transcripts.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        transcripts.get(session_id).cloned()
+    }
+
+    /// Save a session's transcript.
+    pub fn save_transcript(&self, session_id: &str, messages: &[ChatMessage]) -> Result<()> {
+        let mut transcripts = self.transcripts.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let path = self.dir.join(TRANSCRIPTS_FILE);
+        let mut line = serde_json::to_string(&Transcript { id: session_id.to_string(), messages: messages.to_vec() })?;
+        line.push('\n');
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .and_then(|mut file| file.write_all(line.as_bytes()))
+            .with_context(|| format!("Could not write to {}", path.display()))?;
+        transcripts.insert(session_id.to_string(), messages.to_vec());
+        Ok(())
+    }
+
+    /// What the extract stage found, if it finished.
+    pub fn history(&self) -> Result<Option<ImportedHistory>> {
+        if !self.is_done("extract") {
+            return Ok(None);
+        }
+        let path = self.dir.join(HISTORY_FILE);
+        let file = File::open(&path).with_context(|| format!("Could not open {}", path.display()))?;
+        let history = serde_json::from_reader(BufReader::new(file))
+            .with_context(|| format!("{} is not a saved extraction", path.display()))?;
+        Ok(Some(history))
+    }
+
+    /// Save what the extract stage found, and mark it finished.
+    pub fn save_history(&self, history: &ImportedHistory) -> Result<()> {
+        let path = self.dir.join(HISTORY_FILE);
+        write_json(&path, history)?;
+        self.complete("extract")
+    }
+
+    /// Remove the checkpoint, once the run it's for has finished.
+    pub fn clear(&self) -> Result<()> {
+        fs::remove_dir_all(&self.dir).with_context(|| format!("Could not remove {}", self.dir.display()))
+    }
+}
+
+/// Which run a checkpoint belongs to: the source and where it reads from,
+/// so a checkpoint is never picked up by a run reading something else.
+pub fn run_key(config: &Config) -> String {
+    let source = match config.source.as_str() {
+        "continue" => config.continue_sessions_path.clone(),
+        "cody" => format!("{}\n{}", config.cody_history_path, config.cody_history_key),
+        _ => format!("{}\n{}\n{}", config.database_path(), config.global_database_path(), config.workspace_id),
+    };
+    let digest = Sha256::digest(format!("{}\n{}", config.source, source).as_bytes());
+    digest.iter().take(8).map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn read_manifest(dir: &Path) -> Option<Manifest> {
+    let text = fs::read_to_string(dir.join(MANIFEST_FILE)).ok()?;
+    serde_json::from_str(&text).ok()
+}
+
+fn write_manifest(dir: &Path, manifest: &Manifest) -> Result<()> {
+    write_json(&dir.join(MANIFEST_FILE), manifest)
+}
+
+/// Write JSON through a temporary file, so an interruption never leaves
+/// half of it behind.
+fn write_json<T: Serialize>(path: &Path, value: &T) -> Result<()> {
+    write_atomic(path, &serde_json::to_vec(value)?)
+}
+
+/// The transcripts saved so far. A line cut short by the interruption is
+/// left out; that session is just read again.
+fn read_transcripts(path: &Path) -> HashMap<String, Vec<ChatMessage>> {
+    let Ok(file) = File::open(path) else {
+        return HashMap::new();
+    };
+    BufReader::new(file)
+        .lines()
+        .map_while(|line| line.ok())
+        .filter_map(|line| serde_json::from_str::<Transcript>(&line).ok())
+        .map(|transcript| (transcript.id, transcript.messages))
+        .collect()
+}
@@ -0,0 +1,231 @@
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use crate::ChatGeneration;
+
+/// What a generation mostly is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum GenerationKind {
+    /// Prose explaining how something works or why
+    Explanation,
+    /// Mostly code
+    Code,
+    /// Steps to take, in order
+    Plan,
+    /// Working out what went wrong: an error, a panic, a failing test
+    ErrorAnalysis,
+}
+
+impl GenerationKind {
+    /// Every kind, in the order they're listed.
+    pub const ALL: [GenerationKind; 4] =
+        [GenerationKind::Plan, GenerationKind::ErrorAnalysis, GenerationKind::Explanation, GenerationKind::Code];
+
+    /// The kind's name in config and JSON, like `error-analysis`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            GenerationKind::Explanation => "explanation",
+            GenerationKind::Code => "code",
+            GenerationKind::Plan => "plan",
+            GenerationKind::ErrorAnalysis => "error-analysis",
+        }
+    }
+
+    /// A kind by name, ignoring case; `error` and `errors` mean error-analysis,
+    /// and plurals are fine.
+    pub fn parse(name: &str) -> Option<Self> {
+        let name = name.trim().trim_matches(|c: char| !c.is_alphanumeric()).to_lowercase().replace(['_', ' '], "-");
+        match name.trim_end_matches('s') {
+            "explanation" => Some(GenerationKind::Explanation),
+            "code" => Some(GenerationKind::Code),
+            "plan" => Some(GenerationKind::Plan),
+            "error-analysi" | "error" => Some(GenerationKind::ErrorAnalysis),
+            _ => None,
+        }
+    }
+
+    /// The kinds named in a comma-separated list like GENERATION_KINDS,
+    /// where `all` means every kind.
+    pub fn parse_list(list: &str) -> Result<Vec<Self>> {
+        let mut kinds = Vec::new();
+        for name in list.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+            if name.eq_ignore_ascii_case("all") {
+                return Ok(GenerationKind::ALL.to_vec());
+            }
+            match GenerationKind::parse(name) {
+                Some(kind) if !kinds.contains(&kind) => kinds.push(kind),
+                Some(_) => {}
+                None => bail!("Unknown generation kind '{}' (expected explanation, code, plan or error-analysis)", name),
+            }
+        }
+        Ok(kinds)
+    }
+}
+
+/// What the heuristics make of a generation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Classification {
+    /// The kind it most looks like
+    pub kind: GenerationKind,
+    /// Whether the signs were clear; a CLASSIFY_COMMAND is only asked about
+    /// the ones that weren't
+    pub confident: bool,
+}
+
+/// Words that come up when working out what went wrong.
+const ERROR_MARKERS: &[&str] = &[
+    "error",
+    "exception",
+    "traceback",
+    "stack trace",
+    "panicked",
+    "panic",
+    "segfault",
+    "root cause",
+    "fails because",
+    "failing because",
+    "the issue is",
+    "the problem is",
+    "the bug",
+    "caused by",
+    "not found",
+    "undefined",
+];
+
+/// Words that come up when laying out steps.
+const PLAN_MARKERS: &[&str] = &[
+    "here's the plan",
+    "here is the plan",
+    "step 1",
+    "first,",
+    "then,",
+    "next,",
+    "finally,",
+    "i'll ",
+    "we'll ",
+    "let's start",
+];
+
+/// Guess what a generation is from its text: mostly fenced code (or lines
+/// that look like code) is code, an error or two and talk of causes is
+/// error analysis, a numbered list (or a list with "first", "then"...) is a
+/// plan, and anything else is an explanation.
+pub fn classify(text: &str) -> Classification {
+    let lower = text.to_lowercase();
+    let total = text.chars().filter(|c| !c.is_whitespace()).count().max(1);
+
+    // How much of it is code, fenced or not
+    let mut in_fence = false;
+    let mut code_chars = 0;
+    let mut code_lines = 0;
+    let mut lines = 0;
+    let mut numbered = 0;
+    let mut bullets = 0;
+    for line in text.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with("```") {
+            in_fence = !in_fence;
+            continue;
+        }
+        if trimmed.is_empty() {
+            continue;
+        }
+        lines += 1;
+        if in_fence {
+            code_chars += trimmed.chars().filter(|c| !c.is_whitespace()).count();
+            continue;
+        }
+        if trimmed.ends_with([';', '{', '}']) || trimmed.starts_with("//") || trimmed.starts_with("#include") {
+            code_lines += 1;
+        }
+        let digits = trimmed.chars().take_while(char::is_ascii_digit).count();
+        if digits > 0 && trimmed[digits..].starts_with(['.', ')']) {
+            numbered += 1;
+        } else if trimmed.starts_with("- ") || trimmed.starts_with("* ") {
+            bullets += 1;
+        }
+    }
+    let code_share = code_chars as f64 / total as f64;
+    let errors = ERROR_MARKERS.iter().filter(|marker| lower.contains(*marker)).count();
+    let steps = PLAN_MARKERS.iter().filter(|marker| lower.contains(*marker)).count();
+
+    let (kind, confident) = if code_share >= 0.6 || (lines >= 3 && code_lines * 10 >= lines * 6) {
+        (GenerationKind::Code, true)
+    } else if errors >= 2 {
+        (GenerationKind::ErrorAnalysis, errors >= 3)
+    } else if numbered >= 3 || (bullets >= 3 && steps >= 1) || (numbered + bullets >= 2 && steps >= 2) {
+        (GenerationKind::Plan, numbered >= 3)
+    } else {
+        let clear = errors == 0 && steps == 0 && numbered + bullets < 2 && code_share < 0.2;
+        (GenerationKind::Explanation, clear)
+    };
+    Classification { kind, confident }
+}
+
+/// Label each generation with its kind. The heuristics decide, except
+/// that with a `command` (CLASSIFY_COMMAND) the ones they aren't sure of
+/// are handed to it - the text on its standard input, run through the
+/// shell in `dir` - and the kind it prints is used instead. If the command
+/// fails, the rest keep the heuristics' labels and the error is returned.
+pub fn classify_generations(generations: &mut [ChatGeneration], command: Option<&str>, dir: &Path) -> Result<()> {
+    let mut unsure = Vec::new();
+    for (position, generation) in generations.iter_mut().enumerate() {
+        let classification = classify(&generation.text_description);
+        generation.kind = Some(classification.kind);
+        if !classification.confident {
+            unsure.push(position);
+        }
+    }
+
+    let Some(command) = command.map(str::trim).filter(|command| !command.is_empty()) else {
+        return Ok(());
+    };
+    for position in unsure {
+        let generation = &mut generations[position];
+        if let Some(kind) = ask(command, dir, &generation.text_description)? {
+            generation.kind = Some(kind);
+        }
+    }
+    Ok(())
+}
+
+/// Run the classify command on one text and read the kind it prints.
+/// `None` if it printed something else.
+fn ask(command: &str, dir: &Path, text: &str) -> Result<Option<GenerationKind>> {
+    let stdout = pipe_through(command, dir, text, "CLASSIFY_COMMAND")?;
+    Ok(stdout.split_whitespace().next().and_then(GenerationKind::parse))
+}
+
+/// Run a command from the config (named by `setting`, for errors) through
+/// the shell in `dir`, with `input` on its standard input, and return what
+/// it printed.
+pub(crate) fn pipe_through(command: &str, dir: &Path, input: &str, setting: &str) -> Result<String> {
+    let mut shell = if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C");
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+    shell.arg(command).stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped());
+    if dir.is_dir() {
+        shell.current_dir(dir);
+    }
+    let mut child = shell.spawn().with_context(|| format!("Couldn't run {} `{}`", setting, command))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // A command that stops reading early is fine - it's what it prints that counts
+        let _ = stdin.write_all(input.as_bytes());
+    }
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        bail!("{} `{}` failed ({}): {}", setting, command, output.status, stderr.lines().next().unwrap_or_default());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
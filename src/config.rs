@@ -1,7 +1,48 @@
-use anyhow::Result;
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::env;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::classify::GenerationKind;
+#[cfg(feature = "native")]
+use crate::email::SmtpSecurity;
+use crate::encrypt::EncryptionKey;
+use crate::front_matter::parse_fields;
+use crate::graph::GraphFormat;
+use crate::grouping::{parse_sort_order, GroupBy, SortBy};
+use crate::i18n::Strings;
+use crate::message_policy::MessagePolicies;
+use crate::namespace::namespaced;
+use crate::naming::{self, NameValues};
+#[cfg(feature = "native")]
+use crate::plugins;
+use crate::profile::ConfigFile;
+#[cfg(feature = "native")]
+use crate::publish::git::{parse_author, publish_dir};
+use crate::redaction::Identities;
+use crate::render::OutputFormat;
+use crate::sanitize::{parse_rules, SanitizeRule, Sanitizer};
+use crate::schedule::Schedule;
+use crate::schema::FieldMapping;
+#[cfg(feature = "native")]
+use crate::sections::custom::{parse_custom_sections, CustomSection};
+#[cfg(feature = "native")]
+use crate::sections::BUILTIN_SECTIONS;
+use crate::store::{validate_key, validate_table};
+use crate::tags::parse_tags;
+#[cfg(feature = "native")]
+use crate::terminal::{ColorChoice, Terminal, Theme};
+use crate::time::DisplayZone;
+use crate::timeline::TimelineStyle;
+use crate::timestamps::{parse_time_units, time_unit_for, TimeUnit, TimestampPolicy};
+use crate::usage::{parse_pricing, ModelPrice};
+
+/// The chat applications we know how to read.
+pub const SOURCES: &[&str] = &["cursor", "continue", "cody"];
 
 /// Configuration structure that holds all the settings for our persistent code lore tool.
 /// This is where we store everything from database paths to privacy settings.
@@ -10,16 +51,46 @@ use std::path::Path;
 pub struct Config {
     /// What we call ourselves - the name of our application
     pub app_name: String,
-    /// Where to put the generated markdown files
+    /// Where to put the generated markdown files. Can use the same
+    /// placeholders as the file name, like `lore/{date:%Y}`
     pub output_dir: String,
-    /// What to name the main output file
+    /// What to name the main output file. Placeholders are filled in on
+    /// each run: `{project}`, `{branch}`, `{source}`, and `{date}` or
+    /// `{date:<strftime format>}`, like `chat-history-{project}-{date:%Y-%m}.md`
     pub output_filename: String,
+    /// What to write the lore as: "markdown", "org", "asciidoc", "typst", "pdf",
+    /// "csv"/"parquet" for a table of messages, or "json" for the document tree
+    pub output_format: String,
+    /// Which chat application we're pulling history from ("cursor", "continue", "cody", or "plugin:<name>" for a source plugin)
+    pub source: String,
     /// What type of database we're connecting to (currently just SQLite)
     pub db_type: String,
     /// The base path where the database files live
     pub db_path: String,
     /// The name of the database file we're looking for
     pub db_filename: String,
+    /// How long to wait for a locked database before calling it busy (milliseconds)
+    pub db_busy_timeout_ms: u64,
+    /// How many times to retry opening a database that stays locked
+    pub db_connect_retries: u32,
+    /// Open databases as immutable (no locking at all) - only safe with Cursor closed
+    pub db_immutable: bool,
+    /// Values bigger than this many MB are read in pieces and parsed as they arrive (0 streams them all)
+    pub large_value_mb: u64,
+    /// What to do with a record whose timestamp is zero, negative or implausibly far off: "warn" (keep it), "skip" (leave it out) or "clamp" (move it to the nearest plausible time)
+    pub timestamp_policy: String,
+    /// What unit each source's numeric timestamps are in, like "cody=seconds,plugin:mine=ms" ("auto", "seconds" or "milliseconds"; a unit on its own covers every other source); empty for milliseconds from Cursor and working it out from each value elsewhere
+    pub timestamp_units: String,
+    /// Where to find fields in Cursor records of a shape we don't know, like "session.created_at=/meta/created,composer_data.all_composers=/data/sessions" (`kind.field=/json/pointer`)
+    pub field_mapping: String,
+    /// Fail on records that don't match our models (unknown fields, missing keys, wrong types) instead of skipping or keeping them
+    pub strict: bool,
+    /// The Cursor global storage database, where full conversations are kept
+    pub global_db_path: String,
+    /// The key/value table in the global database that holds conversations
+    pub conversation_table: String,
+    /// Whether to merge prompts and generations from global storage with the workspace's
+    pub include_global_storage: bool,
     /// The specific workspace ID we're interested in
     pub workspace_id: String,
     /// The name of the project we're extracting lore from
@@ -34,45 +105,656 @@ pub struct Config {
     pub generations_key: String,
     /// The key in the database where prompt data is stored
     pub prompts_key: String,
+    /// Where Continue.dev keeps its per-session JSON files
+    pub continue_sessions_path: String,
+    /// Where Cody's chat history lives - either an exported JSON file or
+    /// the VS Code global `state.vscdb` the extension writes to
+    pub cody_history_path: String,
+    /// The key in VS Code's global state database that holds Cody's history
+    pub cody_history_key: String,
     /// Whether to include sensitive information in the output
     pub include_secrets: bool,
     /// Whether to include full absolute paths (privacy concern)
     pub include_absolute_paths: bool,
     /// Whether to include system information in the metadata
     pub include_system_info: bool,
+    /// Whether to keep user names, host names and email addresses in the
+    /// output instead of replacing them with placeholders
+    pub include_personal_info: bool,
+    /// More user names to take out of the output, comma-separated, on top
+    /// of the ones detected on this machine
+    pub redact_users: String,
+    /// More host names to take out of the output, comma-separated, on top
+    /// of this machine's
+    pub redact_hosts: String,
+    /// Sanitizer rules of your own, applied to everything written after the
+    /// built-in ones, separated by `;`: `regex:<pattern>=<replacement>`,
+    /// `literal:<text>=<replacement>`, `path:<dir>=<placeholder>` or
+    /// `builtin:<secrets|emails|home-dirs|personal-info>`
+    pub sanitize_rules: String,
+    /// Which time zone to show timestamps in: an IANA name like "Europe/Rome", "UTC" or "local"
+    pub timezone: String,
+    /// Which language to write section titles and labels in ("en", "de", "it" or "ja")
+    pub locale: String,
+    /// How to group the Historical Chat Sessions section: "none", "day", "week", "month" or "topic"
+    pub group_by: String,
+    /// What to sort sessions by before numbering them: "source", "created", "updated", "name" or "message-count"
+    pub sort_by: String,
+    /// Whether sessions are sorted ascending ("asc") or descending ("desc")
+    pub sort_order: String,
+    /// Only put these sessions in the lore: comma-separated session IDs or name patterns; empty for all
+    pub include_sessions: String,
+    /// Sessions to leave out of the lore: comma-separated session IDs or name patterns (`*` and `?` wildcards)
+    pub exclude_sessions: String,
+    /// Only put sessions with one of these tags in the lore (comma-separated); empty for all
+    pub include_tags: String,
+    /// Leave out sessions with any of these tags (comma-separated)
+    pub exclude_tags: String,
+    /// Hide sessions with fewer messages than this (0 keeps every session)
+    pub min_session_messages: usize,
+    /// Merge a session into the one before it when it starts within this many
+    /// minutes of that one's last message and shares words with it (0 never merges)
+    pub merge_sessions_within: u64,
+    /// How many related sessions (sharing words or files, and close in time) to link under each session; 0 for none
+    pub related_sessions: usize,
+    /// Draw a Mermaid diagram of sessions and commits per week: "none", "timeline" or "gantt"
+    pub timeline: String,
+    /// Export a knowledge graph of files, crates, services and features next to the markdown: "none", "json", "dot" or "graphml"
+    pub graph_format: String,
+    /// Write a small markdown file per heavily discussed source file into a files/ directory next to the lore
+    pub file_lore: bool,
+    /// How many sessions have to touch a file before it gets its own file in files/
+    pub file_lore_min_sessions: usize,
+    /// Write lore-index.json next to the lore, mapping project files and code symbols to the sessions that discuss them, for editor extensions
+    pub lore_index: bool,
+    /// Which kinds of generation the Generations section lists: "all", or any of explanation, code, plan and error-analysis, comma-separated (empty leaves the section out)
+    pub generation_kinds: String,
+    /// A shell command to ask what a generation is when the heuristics are unsure: it gets the text on stdin and prints explanation, code, plan or error-analysis
+    pub classify_command: String,
+    /// How long messages are shown: full, truncate[:lines] or summarize[:lines], and section=policy for sections that differ, like "truncate:40,current-session=full"
+    pub message_policy: String,
+    /// A shell command that summarizes a long message for MESSAGE_POLICY=summarize: it gets the text on stdin and prints the summary
+    pub summarize_command: String,
+    /// Put each session transcript in a collapsed block (<details> in markdown) so the lore can be skimmed
+    pub collapse_transcripts: bool,
+    /// Tidy code blocks in messages: guess missing languages, close unclosed fences, lengthen fences around nested ones and re-indent the code
+    pub normalize_fences: bool,
+    /// The heading level of the lore's title; sections and sessions go that many levels deeper too, for embedding the lore in a bigger document
+    pub heading_level: usize,
+    /// The deepest heading level to use; headings that would go deeper become bold lines
+    pub heading_depth: usize,
+    /// Start each markdown file written with YAML front matter (title, date, tags, draft) for static site generators
+    pub front_matter: bool,
+    /// Mark the pages as drafts in the front matter
+    pub front_matter_draft: bool,
+    /// More front matter fields, as key=value pairs separated by commas (like layout=lore, weight=10)
+    pub front_matter_fields: String,
+    /// Whether to draw the knowledge graph as a Mermaid diagram in the markdown
+    pub graph_in_markdown: bool,
+    /// Whether to add a Decisions section quoting the places in the transcripts where choices were made
+    pub include_decisions: bool,
+    /// Whether to add an Open follow-ups section listing the TODOs and work left for later in the chats
+    pub include_followups: bool,
+    /// Whether to add a FAQ section pairing the questions asked in the chats with the answers that were taken
+    pub include_faq: bool,
+    /// Add a Troubleshooting log of errors pasted into the chats and the fixes that were taken
+    pub include_troubleshooting: bool,
+    /// Add an Extraction Warnings appendix listing records that were skipped or had to be patched up
+    pub include_warnings: bool,
+    /// Show the fields a source wrote for each session that we don't otherwise use, as an Additional fields list
+    pub include_extra_fields: bool,
+    /// Which sections to render and in what order, comma-separated (like "metadata,sessions,topics"); empty for all of them
+    pub sections: String,
+    /// Sections of your own, from files, templates or commands, like `Architecture=file:docs/architecture.md;Dependencies@after:topics=command:cargo tree --depth 1`
+    #[cfg(feature = "native")]
+    pub custom_sections: Vec<CustomSection>,
+    /// Analyzer plugins (`lore-plugin-<name>` on PATH) whose sections to add, comma-separated
+    pub analyzer_plugins: String,
+    /// Renderer plugins (`lore-plugin-<name>` on PATH) whose files to write next to the lore, comma-separated
+    pub render_plugins: String,
+    /// A markdown file describing the project for the Project Context section, relative to PROJECT_PATH
+    pub project_context_file: String,
+    /// A markdown file listing the project's key features, relative to PROJECT_PATH; the section is left out without one
+    pub key_features_file: String,
+    /// A JSON file of session names, summaries and ordering to apply over
+    /// the chat history, relative to PROJECT_PATH (see `curation::Curation`)
+    pub curation_file: String,
+    /// Whether to look up the git commit that eventually contained each agent edit
+    pub link_edits_to_commits: bool,
+    /// What each model costs, used to estimate spend per session and month
+    pub model_pricing: Vec<ModelPrice>,
+    /// Base URL of the Confluence site to publish to (like https://team.atlassian.net/wiki)
+    pub confluence_url: String,
+    /// Key of the Confluence space the lore is published in
+    pub confluence_space: String,
+    /// Page to publish the lore under; empty for the top of the space
+    pub confluence_parent_id: String,
+    /// Who to publish to Confluence as (usually an email address)
+    pub confluence_user: String,
+    /// API token for the Confluence user
+    pub confluence_token: String,
+    /// Notion integration token to publish with
+    pub notion_token: String,
+    /// Notion page to publish the lore under (shared with the integration)
+    pub notion_parent_page: String,
+    /// Git repository (URL or path) to commit the lore into, like a GitHub wiki
+    pub git_publish_repo: String,
+    /// Branch to commit to; empty for the repository's default branch
+    pub git_publish_branch: String,
+    /// Directory inside the repository to put the lore pages in; empty for the top level
+    pub git_publish_dir: String,
+    /// Commit message for published lore; {project}, {sessions} and {date} are filled in
+    pub git_commit_message: String,
+    /// Who to commit as, like "Lore Bot <lore@example.com>"; empty for git's own setting
+    pub git_author: String,
+    /// Push to a new branch and open a GitHub pull request instead of pushing straight to the branch
+    pub git_open_pr: bool,
+    /// GitHub token, for opening pull requests and filing issues
+    pub github_token: String,
+    /// The GitHub repository to file issues in, as owner/name
+    pub github_issues_repo: String,
+    /// Labels for the issues filed on GitHub, comma-separated
+    pub github_issue_labels: String,
+    /// Linear API key, for filing issues
+    pub linear_api_key: String,
+    /// The Linear team to file issues in (its ID)
+    pub linear_team_id: String,
+    /// Slack or Discord webhook to post a digest of new sessions to after each run
+    pub notify_webhook: String,
+    /// Where teammates can read the lore, linked from the digest
+    pub notify_lore_url: String,
+    /// SMTP server to send the email digest through (needs the `email` feature)
+    pub smtp_host: String,
+    /// SMTP server port
+    pub smtp_port: u16,
+    /// How to secure the SMTP connection: "starttls", "tls" or "none"
+    pub smtp_security: String,
+    /// SMTP user name, if the server wants one
+    pub smtp_username: String,
+    /// SMTP password
+    pub smtp_password: String,
+    /// Who the email digest comes from, like "Lore <lore@example.com>"
+    pub email_from: String,
+    /// Who gets the email digest, comma-separated
+    pub email_to: String,
+    /// The email digest subject, with {project}, {count} and {period} filled in
+    pub email_subject: String,
+    /// An HTML template for the email digest, relative to PROJECT_PATH (empty for the built-in one)
+    pub email_html_template: String,
+    /// A plain-text template for the email digest, relative to PROJECT_PATH (empty for the built-in one)
+    pub email_text_template: String,
+    /// When the daemon runs, as a cron expression like "0 18 * * *" (in TIMEZONE)
+    pub schedule: String,
+    /// Encrypt the written lore with age (for ENCRYPT_RECIPIENTS or ENCRYPT_PASSPHRASE)
+    pub encrypt: bool,
+    /// age recipients (`age1...`, comma-separated) who can decrypt the lore
+    pub encrypt_recipients: String,
+    /// Passphrase to encrypt the lore with instead of recipients
+    pub encrypt_passphrase: String,
+    /// Keep a compressed snapshot of everything each run extracts in the state directory
+    pub snapshots: bool,
+    /// Copy the source databases into a dated backup directory before reading them
+    pub backup: bool,
+    /// Where backups go (the state directory's backups if empty)
+    pub backup_dir: String,
+    /// How many backups to keep (0 keeps them all)
+    pub backup_keep: usize,
+    /// Keep the previous version of each file a run replaces as `<name>.bak`
+    pub keep_previous_output: bool,
+    /// Skip rendering and writing when the extracted data, the settings, the project files and commits the lore reads and this program are all what they were at the last run that wrote the lore (never when it includes command output)
+    pub skip_unchanged: bool,
+    /// Where run history, the lock, snapshots and backups go (a per-project directory under XDG_STATE_HOME if empty)
+    pub state_dir: String,
+    /// Where rebuildable data like the hook's fingerprint goes (a per-project directory under XDG_CACHE_HOME if empty)
+    pub cache_dir: String,
+    /// Color the subcommands' output: "auto" (on a terminal, unless NO_COLOR is set), "always" or "never"
+    pub color: String,
+    /// Which colors to use: "default" or "high-contrast"
+    pub color_theme: String,
+}
+
+impl Default for Config {
+    /// The settings we use when nothing else has been specified.
+    fn default() -> Self {
+        Config {
+            app_name: "persistent-code-lore".to_string(),
+            output_dir: ".knowledge".to_string(),
+            output_filename: "chat-history-consolidated.md".to_string(),
+            output_format: "markdown".to_string(),
+            source: "cursor".to_string(),
+            db_type: "sqlite".to_string(),
+            db_path: "~/Library/Application Support/Cursor/User/workspaceStorage".to_string(),
+            db_filename: "state.vscdb".to_string(),
+            db_busy_timeout_ms: 5000,
+            db_connect_retries: 3,
+            db_immutable: false,
+            large_value_mb: 32,
+            timestamp_policy: "warn".to_string(),
+            timestamp_units: String::new(),
+            field_mapping: String::new(),
+            strict: false,
+            global_db_path: "~/Library/Application Support/Cursor/User/globalStorage/state.vscdb".to_string(),
+            conversation_table: "cursorDiskKV".to_string(),
+            include_global_storage: true,
+            workspace_id: "default-workspace".to_string(),
+            project_name: "unknown-project".to_string(),
+            project_branch: "main".to_string(),
+            project_path: "/path/to/project".to_string(),
+            composer_data_key: "composer.composerData".to_string(),
+            generations_key: "aiService.generations".to_string(),
+            prompts_key: "aiService.prompts".to_string(),
+            continue_sessions_path: "~/.continue/sessions".to_string(),
+            cody_history_path: "~/Library/Application Support/Code/User/globalStorage/state.vscdb".to_string(),
+            cody_history_key: "cody-local-chatHistory-v2".to_string(),
+            include_secrets: false,
+            include_absolute_paths: false,
+            include_system_info: true,
+            include_personal_info: false,
+            redact_users: String::new(),
+            redact_hosts: String::new(),
+            sanitize_rules: String::new(),
+            timezone: "UTC".to_string(),
+            locale: "en".to_string(),
+            group_by: "none".to_string(),
+            sort_by: "source".to_string(),
+            sort_order: "asc".to_string(),
+            include_sessions: String::new(),
+            exclude_sessions: String::new(),
+            include_tags: String::new(),
+            exclude_tags: String::new(),
+            min_session_messages: 0,
+            merge_sessions_within: 0,
+            related_sessions: 0,
+            timeline: "none".to_string(),
+            graph_format: "none".to_string(),
+            file_lore: false,
+            file_lore_min_sessions: 2,
+            lore_index: false,
+            generation_kinds: String::new(),
+            classify_command: String::new(),
+            message_policy: "full".to_string(),
+            summarize_command: String::new(),
+            collapse_transcripts: false,
+            normalize_fences: true,
+            heading_level: 1,
+            heading_depth: 6,
+            front_matter: false,
+            front_matter_draft: false,
+            front_matter_fields: String::new(),
+            graph_in_markdown: false,
+            include_decisions: true,
+            include_followups: true,
+            include_faq: false,
+            include_troubleshooting: true,
+            include_warnings: false,
+            include_extra_fields: false,
+            sections: String::new(),
+            #[cfg(feature = "native")]
+            custom_sections: Vec::new(),
+            analyzer_plugins: String::new(),
+            render_plugins: String::new(),
+            project_context_file: "project_context.md".to_string(),
+            key_features_file: "key_features.md".to_string(),
+            curation_file: "lore-curation.json".to_string(),
+            link_edits_to_commits: false,
+            model_pricing: Vec::new(),
+            confluence_url: String::new(),
+            confluence_space: String::new(),
+            confluence_parent_id: String::new(),
+            confluence_user: String::new(),
+            confluence_token: String::new(),
+            notion_token: String::new(),
+            notion_parent_page: String::new(),
+            git_publish_repo: String::new(),
+            git_publish_branch: String::new(),
+            git_publish_dir: String::new(),
+            git_commit_message: "Update code lore for {project} ({sessions} sessions)".to_string(),
+            git_author: String::new(),
+            git_open_pr: false,
+            github_token: String::new(),
+            github_issues_repo: String::new(),
+            github_issue_labels: "lore".to_string(),
+            linear_api_key: String::new(),
+            linear_team_id: String::new(),
+            notify_webhook: String::new(),
+            notify_lore_url: String::new(),
+            smtp_host: String::new(),
+            smtp_port: 587,
+            smtp_security: "starttls".to_string(),
+            smtp_username: String::new(),
+            smtp_password: String::new(),
+            email_from: String::new(),
+            email_to: String::new(),
+            email_subject: "Lore digest for {project}: {count}".to_string(),
+            email_html_template: String::new(),
+            email_text_template: String::new(),
+            schedule: String::new(),
+            encrypt: false,
+            encrypt_recipients: String::new(),
+            encrypt_passphrase: String::new(),
+            snapshots: false,
+            backup: false,
+            backup_dir: String::new(),
+            backup_keep: 5,
+            keep_previous_output: false,
+            skip_unchanged: false,
+            state_dir: String::new(),
+            cache_dir: String::new(),
+            color: "auto".to_string(),
+            color_theme: "default".to_string(),
+        }
+    }
 }
 
 impl Config {
     /// Load configuration from a file and environment variables.
     /// This is where we read all our settings from the config file and
     /// set up sensible defaults for anything that's not specified.
+    /// Environment variables win over the file, but the file is read
+    /// directly rather than copied into the process environment.
     pub fn load(config_file: &str) -> Result<Self> {
-        // First, try to load environment variables from the config file
+        Config::load_with(config_file, &HashMap::new())
+    }
+    
+    /// Load configuration like `load`, with `overrides` winning over both
+    /// the environment and the file (for settings that belong to one
+    /// project of a batch, say).
+    pub fn load_with(config_file: &str, overrides: &HashMap<String, String>) -> Result<Self> {
+        Config::load_with_defaults(config_file, overrides, &HashMap::new())
+    }
+    
+    /// Load configuration like `load_with`, falling back to `defaults`
+    /// before the built-in ones for anything nothing else sets (what
+    /// `cargo lore` works out about the crate it's run in, say).
+    pub fn load_with_defaults(
+        config_file: &str,
+        overrides: &HashMap<String, String>,
+        defaults: &HashMap<String, String>,
+    ) -> Result<Self> {
+        Config::load_with_profile(config_file, None, overrides, defaults)
+    }
+    
+    /// Load configuration like `load_with_defaults`, with the settings of
+    /// `profile` (a `[profile.<name>]` section of the file) over the
+    /// file's own. Fails if there's no such profile.
+    pub fn load_with_profile(
+        config_file: &str,
+        profile: Option<&str>,
+        overrides: &HashMap<String, String>,
+        defaults: &HashMap<String, String>,
+    ) -> Result<Self> {
+        // Read the config file if there is one
         // If the file doesn't exist, that's okay - we'll just use defaults
-        dotenv::from_filename(config_file).ok();
+        let file = ConfigFile::read(config_file)?.settings(profile)?;
         
-        // Now we build our configuration struct, reading from environment variables
-        // and falling back to sensible defaults if something isn't set
-        Ok(Config {
-            app_name: env::var("APP_NAME").unwrap_or_else(|_| "persistent-code-lore".to_string()),
-            output_dir: env::var("OUTPUT_DIR").unwrap_or_else(|_| ".knowledge".to_string()),
-            output_filename: env::var("OUTPUT_FILENAME").unwrap_or_else(|_| "chat-history-consolidated.md".to_string()),
-            db_type: env::var("DB_TYPE").unwrap_or_else(|_| "sqlite".to_string()),
-            db_path: env::var("DB_PATH").unwrap_or_else(|_| "~/Library/Application Support/Cursor/User/workspaceStorage".to_string()),
-            db_filename: env::var("DB_FILENAME").unwrap_or_else(|_| "state.vscdb".to_string()),
-            workspace_id: env::var("WORKSPACE_ID").unwrap_or_else(|_| "default-workspace".to_string()),
-            project_name: env::var("PROJECT_NAME").unwrap_or_else(|_| "unknown-project".to_string()),
-            project_branch: env::var("PROJECT_BRANCH").unwrap_or_else(|_| "main".to_string()),
-            project_path: env::var("PROJECT_PATH").unwrap_or_else(|_| "/path/to/project".to_string()),
-            composer_data_key: env::var("COMPOSER_DATA_KEY").unwrap_or_else(|_| "composer.composerData".to_string()),
-            generations_key: env::var("GENERATIONS_KEY").unwrap_or_else(|_| "aiService.generations".to_string()),
-            prompts_key: env::var("PROMPTS_KEY").unwrap_or_else(|_| "aiService.prompts".to_string()),
-            include_secrets: env::var("INCLUDE_SECRETS").unwrap_or_else(|_| "false".to_string()).parse().unwrap_or(false),
-            include_absolute_paths: env::var("INCLUDE_ABSOLUTE_PATHS").unwrap_or_else(|_| "false".to_string()).parse().unwrap_or(false),
-            include_system_info: env::var("INCLUDE_SYSTEM_INFO").unwrap_or_else(|_| "true".to_string()).parse().unwrap_or(true),
+        // Either source can have LORE_OUTPUT_DIR as well as OUTPUT_DIR; the
+        // prefixed name wins
+        let from_env = |name: &str| env::var(name).ok();
+        let from_file = |name: &str| file.get(name).cloned();
+        Config::from_lookup(|name| {
+            overrides
+                .get(name)
+                .cloned()
+                .or_else(|| namespaced(&from_env, name))
+                .or_else(|| namespaced(&from_file, name))
+                .or_else(|| defaults.get(name).cloned())
         })
     }
     
+    /// The names of every setting, in the order they're read.
+    pub fn setting_names() -> Vec<String> {
+        let names = RefCell::new(Vec::new());
+        // The defaults are always valid, so this can't fail
+        let _ = Config::from_lookup(|name| {
+            names.borrow_mut().push(name.to_string());
+            None
+        });
+        names.into_inner()
+    }
+    
+    /// Start building a configuration in code, from the defaults.
+    /// Nothing is read from files or the environment, which makes this the
+    /// way to go when embedding the library or running tests in parallel.
+    pub fn builder() -> ConfigBuilder {
+        ConfigBuilder::default()
+    }
+    
+    /// Build a configuration from any source of `NAME=value` settings,
+    /// falling back to the defaults for anything that isn't set.
+    pub fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Result<Self> {
+        let defaults = Config::default();
+        let config = Config {
+            app_name: lookup("APP_NAME").unwrap_or(defaults.app_name),
+            output_dir: lookup("OUTPUT_DIR").unwrap_or(defaults.output_dir),
+            output_filename: lookup("OUTPUT_FILENAME").unwrap_or(defaults.output_filename),
+            output_format: lookup("OUTPUT_FORMAT").unwrap_or(defaults.output_format),
+            source: lookup("SOURCE").unwrap_or(defaults.source),
+            db_type: lookup("DB_TYPE").unwrap_or(defaults.db_type),
+            db_path: lookup("DB_PATH").unwrap_or(defaults.db_path),
+            db_filename: lookup("DB_FILENAME").unwrap_or(defaults.db_filename),
+            db_busy_timeout_ms: parsed(&lookup, "DB_BUSY_TIMEOUT_MS", defaults.db_busy_timeout_ms)?,
+            db_connect_retries: parsed(&lookup, "DB_CONNECT_RETRIES", defaults.db_connect_retries)?,
+            db_immutable: parsed(&lookup, "DB_IMMUTABLE", defaults.db_immutable)?,
+            large_value_mb: parsed(&lookup, "LARGE_VALUE_MB", defaults.large_value_mb)?,
+            timestamp_policy: lookup("TIMESTAMP_POLICY").unwrap_or(defaults.timestamp_policy),
+            timestamp_units: lookup("TIMESTAMP_UNITS").unwrap_or(defaults.timestamp_units),
+            field_mapping: lookup("FIELD_MAPPING").unwrap_or(defaults.field_mapping),
+            strict: parsed(&lookup, "STRICT", defaults.strict)?,
+            global_db_path: lookup("GLOBAL_DB_PATH").unwrap_or(defaults.global_db_path),
+            conversation_table: lookup("CONVERSATION_TABLE").unwrap_or(defaults.conversation_table),
+            include_global_storage: parsed(&lookup, "INCLUDE_GLOBAL_STORAGE", defaults.include_global_storage)?,
+            workspace_id: lookup("WORKSPACE_ID").unwrap_or(defaults.workspace_id),
+            project_name: lookup("PROJECT_NAME").unwrap_or(defaults.project_name),
+            project_branch: lookup("PROJECT_BRANCH").unwrap_or(defaults.project_branch),
+            project_path: lookup("PROJECT_PATH").unwrap_or(defaults.project_path),
+            composer_data_key: lookup("COMPOSER_DATA_KEY").unwrap_or(defaults.composer_data_key),
+            generations_key: lookup("GENERATIONS_KEY").unwrap_or(defaults.generations_key),
+            prompts_key: lookup("PROMPTS_KEY").unwrap_or(defaults.prompts_key),
+            continue_sessions_path: lookup("CONTINUE_SESSIONS_PATH").unwrap_or(defaults.continue_sessions_path),
+            cody_history_path: lookup("CODY_HISTORY_PATH").unwrap_or(defaults.cody_history_path),
+            cody_history_key: lookup("CODY_HISTORY_KEY").unwrap_or(defaults.cody_history_key),
+            include_secrets: parsed(&lookup, "INCLUDE_SECRETS", defaults.include_secrets)?,
+            include_absolute_paths: parsed(&lookup, "INCLUDE_ABSOLUTE_PATHS", defaults.include_absolute_paths)?,
+            include_system_info: parsed(&lookup, "INCLUDE_SYSTEM_INFO", defaults.include_system_info)?,
+            include_personal_info: parsed(&lookup, "INCLUDE_PERSONAL_INFO", defaults.include_personal_info)?,
+            redact_users: lookup("REDACT_USERS").unwrap_or(defaults.redact_users),
+            redact_hosts: lookup("REDACT_HOSTS").unwrap_or(defaults.redact_hosts),
+            sanitize_rules: lookup("SANITIZE_RULES").unwrap_or(defaults.sanitize_rules),
+            timezone: lookup("TIMEZONE").unwrap_or(defaults.timezone),
+            locale: lookup("LOCALE").unwrap_or(defaults.locale),
+            group_by: lookup("GROUP_BY").unwrap_or(defaults.group_by),
+            sort_by: lookup("SORT_BY").unwrap_or(defaults.sort_by),
+            sort_order: lookup("SORT_ORDER").unwrap_or(defaults.sort_order),
+            include_sessions: lookup("INCLUDE_SESSIONS").unwrap_or(defaults.include_sessions),
+            exclude_sessions: lookup("EXCLUDE_SESSIONS").unwrap_or(defaults.exclude_sessions),
+            include_tags: lookup("INCLUDE_TAGS").unwrap_or(defaults.include_tags),
+            exclude_tags: lookup("EXCLUDE_TAGS").unwrap_or(defaults.exclude_tags),
+            min_session_messages: parsed(&lookup, "MIN_SESSION_MESSAGES", defaults.min_session_messages)?,
+            merge_sessions_within: parsed(&lookup, "MERGE_SESSIONS_WITHIN", defaults.merge_sessions_within)?,
+            related_sessions: parsed(&lookup, "RELATED_SESSIONS", defaults.related_sessions)?,
+            timeline: lookup("TIMELINE").unwrap_or(defaults.timeline),
+            graph_format: lookup("GRAPH_FORMAT").unwrap_or(defaults.graph_format),
+            file_lore: parsed(&lookup, "FILE_LORE", defaults.file_lore)?,
+            file_lore_min_sessions: parsed(&lookup, "FILE_LORE_MIN_SESSIONS", defaults.file_lore_min_sessions)?,
+            lore_index: parsed(&lookup, "LORE_INDEX", defaults.lore_index)?,
+            generation_kinds: lookup("GENERATION_KINDS").unwrap_or(defaults.generation_kinds),
+            classify_command: lookup("CLASSIFY_COMMAND").unwrap_or(defaults.classify_command),
+            message_policy: lookup("MESSAGE_POLICY").unwrap_or(defaults.message_policy),
+            summarize_command: lookup("SUMMARIZE_COMMAND").unwrap_or(defaults.summarize_command),
+            collapse_transcripts: parsed(&lookup, "COLLAPSE_TRANSCRIPTS", defaults.collapse_transcripts)?,
+            normalize_fences: parsed(&lookup, "NORMALIZE_FENCES", defaults.normalize_fences)?,
+            heading_level: parsed(&lookup, "HEADING_LEVEL", defaults.heading_level)?,
+            heading_depth: parsed(&lookup, "HEADING_DEPTH", defaults.heading_depth)?,
+            front_matter: parsed(&lookup, "FRONT_MATTER", defaults.front_matter)?,
+            front_matter_draft: parsed(&lookup, "FRONT_MATTER_DRAFT", defaults.front_matter_draft)?,
+            front_matter_fields: lookup("FRONT_MATTER_FIELDS").unwrap_or(defaults.front_matter_fields),
+            graph_in_markdown: parsed(&lookup, "GRAPH_IN_MARKDOWN", defaults.graph_in_markdown)?,
+            include_decisions: parsed(&lookup, "INCLUDE_DECISIONS", defaults.include_decisions)?,
+            include_followups: parsed(&lookup, "INCLUDE_FOLLOWUPS", defaults.include_followups)?,
+            include_faq: parsed(&lookup, "INCLUDE_FAQ", defaults.include_faq)?,
+            include_troubleshooting: parsed(&lookup, "INCLUDE_TROUBLESHOOTING", defaults.include_troubleshooting)?,
+            include_warnings: parsed(&lookup, "INCLUDE_WARNINGS", defaults.include_warnings)?,
+            include_extra_fields: parsed(&lookup, "INCLUDE_EXTRA_FIELDS", defaults.include_extra_fields)?,
+            sections: lookup("SECTIONS").unwrap_or(defaults.sections),
+            #[cfg(feature = "native")]
+            custom_sections: match lookup("CUSTOM_SECTIONS") {
+                Some(spec) => parse_custom_sections(&spec).context("CUSTOM_SECTIONS is not a list of sections")?,
+                None => defaults.custom_sections,
+            },
+            analyzer_plugins: lookup("ANALYZER_PLUGINS").unwrap_or(defaults.analyzer_plugins),
+            render_plugins: lookup("RENDER_PLUGINS").unwrap_or(defaults.render_plugins),
+            project_context_file: lookup("PROJECT_CONTEXT_FILE").unwrap_or(defaults.project_context_file),
+            key_features_file: lookup("KEY_FEATURES_FILE").unwrap_or(defaults.key_features_file),
+            curation_file: lookup("CURATION_FILE").unwrap_or(defaults.curation_file),
+            link_edits_to_commits: parsed(&lookup, "LINK_EDITS_TO_COMMITS", defaults.link_edits_to_commits)?,
+            model_pricing: match lookup("MODEL_PRICING") {
+                Some(spec) => parse_pricing(&spec)?,
+                None => defaults.model_pricing,
+            },
+            confluence_url: lookup("CONFLUENCE_URL").unwrap_or(defaults.confluence_url),
+            confluence_space: lookup("CONFLUENCE_SPACE").unwrap_or(defaults.confluence_space),
+            confluence_parent_id: lookup("CONFLUENCE_PARENT_ID").unwrap_or(defaults.confluence_parent_id),
+            confluence_user: lookup("CONFLUENCE_USER").unwrap_or(defaults.confluence_user),
+            confluence_token: lookup("CONFLUENCE_TOKEN").unwrap_or(defaults.confluence_token),
+            notion_token: lookup("NOTION_TOKEN").unwrap_or(defaults.notion_token),
+            notion_parent_page: lookup("NOTION_PARENT_PAGE").unwrap_or(defaults.notion_parent_page),
+            git_publish_repo: lookup("GIT_PUBLISH_REPO").unwrap_or(defaults.git_publish_repo),
+            git_publish_branch: lookup("GIT_PUBLISH_BRANCH").unwrap_or(defaults.git_publish_branch),
+            git_publish_dir: lookup("GIT_PUBLISH_DIR").unwrap_or(defaults.git_publish_dir),
+            git_commit_message: lookup("GIT_COMMIT_MESSAGE").unwrap_or(defaults.git_commit_message),
+            git_author: lookup("GIT_AUTHOR").unwrap_or(defaults.git_author),
+            git_open_pr: parsed(&lookup, "GIT_OPEN_PR", defaults.git_open_pr)?,
+            github_token: lookup("GITHUB_TOKEN").unwrap_or(defaults.github_token),
+            github_issues_repo: lookup("GITHUB_ISSUES_REPO").unwrap_or(defaults.github_issues_repo),
+            github_issue_labels: lookup("GITHUB_ISSUE_LABELS").unwrap_or(defaults.github_issue_labels),
+            linear_api_key: lookup("LINEAR_API_KEY").unwrap_or(defaults.linear_api_key),
+            linear_team_id: lookup("LINEAR_TEAM_ID").unwrap_or(defaults.linear_team_id),
+            notify_webhook: lookup("NOTIFY_WEBHOOK").unwrap_or(defaults.notify_webhook),
+            notify_lore_url: lookup("NOTIFY_LORE_URL").unwrap_or(defaults.notify_lore_url),
+            smtp_host: lookup("SMTP_HOST").unwrap_or(defaults.smtp_host),
+            smtp_port: parsed(&lookup, "SMTP_PORT", defaults.smtp_port)?,
+            smtp_security: lookup("SMTP_SECURITY").unwrap_or(defaults.smtp_security),
+            smtp_username: lookup("SMTP_USERNAME").unwrap_or(defaults.smtp_username),
+            smtp_password: lookup("SMTP_PASSWORD").unwrap_or(defaults.smtp_password),
+            email_from: lookup("EMAIL_FROM").unwrap_or(defaults.email_from),
+            email_to: lookup("EMAIL_TO").unwrap_or(defaults.email_to),
+            email_subject: lookup("EMAIL_SUBJECT").unwrap_or(defaults.email_subject),
+            email_html_template: lookup("EMAIL_HTML_TEMPLATE").unwrap_or(defaults.email_html_template),
+            email_text_template: lookup("EMAIL_TEXT_TEMPLATE").unwrap_or(defaults.email_text_template),
+            schedule: lookup("SCHEDULE").unwrap_or(defaults.schedule),
+            encrypt: parsed(&lookup, "ENCRYPT", defaults.encrypt)?,
+            encrypt_recipients: lookup("ENCRYPT_RECIPIENTS").unwrap_or(defaults.encrypt_recipients),
+            encrypt_passphrase: lookup("ENCRYPT_PASSPHRASE").unwrap_or(defaults.encrypt_passphrase),
+            snapshots: parsed(&lookup, "SNAPSHOTS", defaults.snapshots)?,
+            backup: parsed(&lookup, "BACKUP", defaults.backup)?,
+            backup_dir: lookup("BACKUP_DIR").unwrap_or(defaults.backup_dir),
+            backup_keep: parsed(&lookup, "BACKUP_KEEP", defaults.backup_keep)?,
+            keep_previous_output: parsed(&lookup, "KEEP_PREVIOUS_OUTPUT", defaults.keep_previous_output)?,
+            skip_unchanged: parsed(&lookup, "SKIP_UNCHANGED", defaults.skip_unchanged)?,
+            state_dir: lookup("STATE_DIR").unwrap_or(defaults.state_dir),
+            cache_dir: lookup("CACHE_DIR").unwrap_or(defaults.cache_dir),
+            color: lookup("COLOR").unwrap_or(defaults.color),
+            color_theme: lookup("COLOR_THEME").unwrap_or(defaults.color_theme),
+        };
+        
+        config.validate()?;
+        Ok(config)
+    }
+    
+    /// Catch malformed settings up front (empty names, unknown sources, bad
+    /// key and table names), so a typo in the config file gets a clear
+    /// message instead of an odd database error later.
+    pub fn validate(&self) -> Result<()> {
+        if self.app_name.trim().is_empty() {
+            bail!("APP_NAME can't be empty");
+        }
+        if self.output_filename.trim().is_empty() {
+            bail!("OUTPUT_FILENAME can't be empty");
+        }
+        if !(1..=6).contains(&self.heading_level) {
+            bail!("HEADING_LEVEL must be between 1 and 6");
+        }
+        if !(self.heading_level..=6).contains(&self.heading_depth) {
+            bail!("HEADING_DEPTH must be between HEADING_LEVEL ({}) and 6", self.heading_level);
+        }
+        #[cfg(feature = "native")]
+        match plugins::source_plugin(&self.source) {
+            Some(name) => plugins::validate_name(name).context("SOURCE doesn't name a plugin")?,
+            None if !SOURCES.contains(&self.source.as_str()) => {
+                bail!("Unsupported source '{}' (expected {} or plugin:<name>)", self.source, SOURCES.join(", "))
+            }
+            None => {}
+        }
+        for (setting, key) in [
+            ("COMPOSER_DATA_KEY", &self.composer_data_key),
+            ("GENERATIONS_KEY", &self.generations_key),
+            ("PROMPTS_KEY", &self.prompts_key),
+            ("CODY_HISTORY_KEY", &self.cody_history_key),
+        ] {
+            validate_key(key).with_context(|| format!("{} is not a usable key", setting))?;
+        }
+        validate_table(&self.conversation_table).context("CONVERSATION_TABLE is not a usable table name")?;
+        self.output_directory(Utc::now()).context("OUTPUT_DIR is not a usable directory template")?;
+        self.output_file_name(Utc::now()).context("OUTPUT_FILENAME is not a usable file name template")?;
+        let format = OutputFormat::parse(&self.output_format).context("OUTPUT_FORMAT is not a supported format")?;
+        if format == OutputFormat::Site && self.encrypt {
+            bail!("A site can't be encrypted; use another OUTPUT_FORMAT with ENCRYPT");
+        }
+        DisplayZone::parse(&self.timezone).context("TIMEZONE is not a usable time zone")?;
+        Strings::for_locale(&self.locale).context("LOCALE is not a supported language")?;
+        GroupBy::parse(&self.group_by).context("GROUP_BY is not a supported grouping")?;
+        SortBy::parse(&self.sort_by).context("SORT_BY is not a supported sort key")?;
+        parse_sort_order(&self.sort_order).context("SORT_ORDER must be asc or desc")?;
+        parse_tags(&self.include_tags).context("INCLUDE_TAGS is not a list of tags")?;
+        parse_tags(&self.exclude_tags).context("EXCLUDE_TAGS is not a list of tags")?;
+        TimelineStyle::parse(&self.timeline).context("TIMELINE is not a supported diagram style")?;
+        GraphFormat::parse(&self.graph_format).context("GRAPH_FORMAT is not a supported graph format")?;
+        TimestampPolicy::parse(&self.timestamp_policy).context("TIMESTAMP_POLICY is not a supported timestamp policy")?;
+        parse_time_units(&self.timestamp_units).context("TIMESTAMP_UNITS is not a list of timestamp units")?;
+        FieldMapping::parse(&self.field_mapping).context("FIELD_MAPPING is not a list of field mappings")?;
+        parse_rules(&self.sanitize_rules, &Identities::default()).context("SANITIZE_RULES is not a list of sanitizer rules")?;
+        GenerationKind::parse_list(&self.generation_kinds).context("GENERATION_KINDS is not a list of generation kinds")?;
+        MessagePolicies::parse(&self.message_policy).context("MESSAGE_POLICY is not a list of message policies")?;
+        #[cfg(feature = "native")]
+        SmtpSecurity::parse(&self.smtp_security).context("SMTP_SECURITY is not a supported connection security")?;
+        parse_fields(&self.front_matter_fields).context("FRONT_MATTER_FIELDS is not a list of key=value pairs")?;
+        #[cfg(feature = "native")]
+        plugins::parse_names(&self.analyzer_plugins).context("ANALYZER_PLUGINS is not a list of plugins")?;
+        #[cfg(feature = "native")]
+        plugins::parse_names(&self.render_plugins).context("RENDER_PLUGINS is not a list of plugins")?;
+        #[cfg(feature = "native")]
+        for (index, section) in self.custom_sections.iter().enumerate() {
+            if self.custom_sections[..index].iter().any(|earlier| earlier.name == section.name) {
+                bail!("CUSTOM_SECTIONS has two sections called '{}'", section.name);
+            }
+            // A section can be placed next to a built-in one or any custom one before it
+            if let Some(anchor) = section.position.anchor() {
+                let known = BUILTIN_SECTIONS.contains(&anchor)
+                    || self.custom_sections[..index].iter().any(|earlier| earlier.name == anchor);
+                if !known || anchor == section.name {
+                    bail!("Custom section '{}' is placed next to '{}', which isn't a built-in section or a custom one listed before it", section.title, anchor);
+                }
+            }
+        }
+        #[cfg(feature = "native")]
+        publish_dir(&self.git_publish_dir).context("GIT_PUBLISH_DIR is not a usable directory")?;
+        if !self.schedule.trim().is_empty() {
+            Schedule::parse(&self.schedule).context("SCHEDULE is not a usable cron expression")?;
+        }
+        if self.encrypt {
+            EncryptionKey::from_config(self).context("ENCRYPT is on but the lore can't be encrypted")?;
+        }
+        #[cfg(feature = "native")]
+        if !self.git_author.trim().is_empty() {
+            parse_author(&self.git_author).context("GIT_AUTHOR is not a usable author")?;
+        }
+        #[cfg(feature = "native")]
+        ColorChoice::parse(&self.color).context("COLOR must be auto, always or never")?;
+        #[cfg(feature = "native")]
+        Theme::named(&self.color_theme).context("COLOR_THEME is not a color theme")?;
+        Ok(())
+    }
+    
     /// Build the full path to the database file we want to connect to.
     /// This takes the base database path, expands any ~ symbols, and
     /// combines it with the workspace ID and database filename.
@@ -86,17 +768,989 @@ impl Config {
             .to_string()
     }
     
+    /// Build the full path to Cursor's global storage database.
+    /// Unlike the workspace database this one is shared by every workspace.
+    pub fn global_database_path(&self) -> String {
+        shellexpand::tilde(&self.global_db_path).to_string()
+    }
+    
+    /// The files the configured source reads: Cursor's two databases,
+    /// Cody's history, or every file in Continue's sessions directory
+    /// (none for a source plugin).
+    /// SQLite's `-wal` and `-shm` companions aren't included.
+    pub fn source_files(&self) -> Vec<PathBuf> {
+        match self.source.as_str() {
+            "continue" => {
+                let dir = PathBuf::from(shellexpand::tilde(&self.continue_sessions_path).to_string());
+                let mut files: Vec<PathBuf> = std::fs::read_dir(&dir)
+                    .map(|entries| entries.filter_map(|entry| entry.ok().map(|entry| entry.path())).collect())
+                    .unwrap_or_default();
+                files.retain(|path| path.is_file());
+                files.sort();
+                files
+            }
+            "cody" => vec![PathBuf::from(shellexpand::tilde(&self.cody_history_path).to_string())],
+            // What a plugin reads is its own business
+            #[cfg(feature = "native")]
+            source if plugins::source_plugin(source).is_some() => Vec::new(),
+            _ => vec![PathBuf::from(self.database_path()), PathBuf::from(self.global_database_path())],
+        }
+    }
+    
+    /// What the placeholders in OUTPUT_DIR and OUTPUT_FILENAME stand for on
+    /// a run that started at `now`.
+    pub fn name_values(&self, now: DateTime<Utc>) -> NameValues<'_> {
+        NameValues {
+            project: &self.project_name,
+            branch: &self.project_branch,
+            source: &self.source,
+            zone: self.display_zone(),
+            now,
+        }
+    }
+    
+    /// OUTPUT_DIR with its placeholders filled in for a run at `now`.
+    pub fn output_directory(&self, now: DateTime<Utc>) -> Result<String> {
+        naming::expand(&self.output_dir, &self.name_values(now))
+    }
+    
+    /// OUTPUT_FILENAME with its placeholders filled in for a run at `now`,
+    /// and the default `.md` swapped for the output format's extension.
+    pub fn output_file_name(&self, now: DateTime<Utc>) -> Result<String> {
+        let name = naming::expand(&self.output_filename, &self.name_values(now))?;
+        if name.contains(['/', '\\']) {
+            bail!("'{}' is a path, not a file name (put directories in OUTPUT_DIR)", name);
+        }
+        Ok(self.output_format().file_name(&name))
+    }
+    
+    /// The format to write the lore in (markdown if the setting is invalid).
+    pub fn output_format(&self) -> OutputFormat {
+        OutputFormat::parse(&self.output_format).unwrap_or_default()
+    }
+    
+    /// The time zone to render timestamps in (UTC if the setting is invalid,
+    /// though `validate` will already have complained about that).
+    pub fn display_zone(&self) -> DisplayZone {
+        DisplayZone::parse(&self.timezone).unwrap_or_default()
+    }
+    
+    /// How to group sessions in the output (flat if the setting is invalid).
+    pub fn session_grouping(&self) -> GroupBy {
+        GroupBy::parse(&self.group_by).unwrap_or_default()
+    }
+    
+    /// The sections SECTIONS names, in order (empty for all of them).
+    pub fn section_order(&self) -> Vec<String> {
+        self.sections
+            .split(',')
+            .map(|name| name.trim().to_lowercase())
+            .filter(|name| !name.is_empty())
+            .collect()
+    }
+    
+    /// How to order sessions before numbering them, and whether that order
+    /// is descending (source order, ascending, if the settings are invalid).
+    pub fn session_sorting(&self) -> (SortBy, bool) {
+        (
+            SortBy::parse(&self.sort_by).unwrap_or_default(),
+            parse_sort_order(&self.sort_order).unwrap_or(false),
+        )
+    }
+    
+    /// Which development timeline diagram to draw (none if the setting is invalid).
+    pub fn timeline_style(&self) -> TimelineStyle {
+        TimelineStyle::parse(&self.timeline).unwrap_or_default()
+    }
+    
+    /// Which format to export the knowledge graph in (none if the setting is invalid).
+    pub fn graph_export_format(&self) -> GraphFormat {
+        GraphFormat::parse(&self.graph_format).unwrap_or_default()
+    }
+    
+    /// What to do with implausible timestamps (warn if the setting is invalid).
+    pub fn timestamp_handling(&self) -> TimestampPolicy {
+        TimestampPolicy::parse(&self.timestamp_policy).unwrap_or_default()
+    }
+    
+    /// What unit the configured source's numeric timestamps are in.
+    pub fn timestamp_unit(&self) -> TimeUnit {
+        time_unit_for(&self.timestamp_units, &self.source)
+    }
+    
+    /// Where to find fields in records of an unknown shape (nothing mapped
+    /// if the setting is invalid).
+    pub fn record_field_mapping(&self) -> FieldMapping {
+        FieldMapping::parse(&self.field_mapping).unwrap_or_default()
+    }
+    
+    /// Which kinds of generation the Generations section lists (none if
+    /// the setting is invalid).
+    pub fn generation_kind_list(&self) -> Vec<GenerationKind> {
+        GenerationKind::parse_list(&self.generation_kinds).unwrap_or_default()
+    }
+    
+    /// A path relative to PROJECT_PATH (with `~` expanded in both). An
+    /// absolute path is returned as it is.
+    pub fn project_file(&self, path: &str) -> PathBuf {
+        let path = PathBuf::from(shellexpand::tilde(path).to_string());
+        PathBuf::from(shellexpand::tilde(&self.project_path).to_string()).join(path)
+    }
+    
+    /// Blank out anything that looks like a secret (tokens, passwords, keys),
+    /// unless the user explicitly asked for secrets to be kept.
+    pub fn redact_secrets(&self, text: &str) -> String {
+        if self.include_secrets {
+            text.to_string()
+        } else {
+            crate::redaction::redact_secrets(text)
+        }
+    }
+    
+    /// Who the `personal-info` rule takes out of the output: this
+    /// machine's user and host names, REDACT_USERS and REDACT_HOSTS.
+    pub fn identities(&self) -> Identities {
+        let list = |names: &str| names.split(',').map(str::trim).filter(|name| !name.is_empty()).map(str::to_string).collect::<Vec<_>>();
+        Identities::detect(&list(&self.redact_users), &list(&self.redact_hosts))
+    }
+    
+    /// The rules that keep PROJECT_PATH and DB_PATH out of the output, or
+    /// none if the user wants absolute paths kept.
+    pub fn path_rules(&self) -> Vec<SanitizeRule> {
+        if self.include_absolute_paths {
+            return Vec::new();
+        }
+        vec![
+            SanitizeRule::path_prefix(&self.project_path, "<PROJECT_PATH>"),
+            SanitizeRule::path_prefix(&self.db_path, "<DB_PATH>"),
+        ]
+    }
+    
+    /// Every rule the output goes through, in order: secrets (unless
+    /// INCLUDE_SECRETS), PROJECT_PATH and DB_PATH (unless
+    /// INCLUDE_ABSOLUTE_PATHS), personal info (unless
+    /// INCLUDE_PERSONAL_INFO), then SANITIZE_RULES.
+    pub fn sanitizer(&self) -> Sanitizer {
+        let identities = self.identities();
+        let mut rules = Vec::new();
+        if !self.include_secrets {
+            rules.extend(SanitizeRule::builtin("secrets", &identities));
+        }
+        rules.extend(self.path_rules());
+        if !self.include_personal_info {
+            rules.extend(SanitizeRule::builtin("personal-info", &identities));
+        }
+        rules.extend(parse_rules(&self.sanitize_rules, &identities).unwrap_or_default());
+        Sanitizer { rules }
+    }
+    
     /// Clean up paths for privacy by replacing absolute paths with placeholders.
     /// This is useful when we want to share the generated markdown without
     /// exposing sensitive directory information.
     pub fn sanitize_path(&self, path: &str) -> String {
-        if self.include_absolute_paths {
-            // User wants to keep absolute paths, so just return as-is
-            path.to_string()
-        } else {
-            // Replace sensitive path information with generic placeholders
-            path.replace(&self.project_path, "<PROJECT_PATH>")
-                .replace(&self.db_path, "<DB_PATH>")
-        }
+        self.path_rules().iter().fold(path.to_string(), |path, rule| rule.apply(&path))
+    }
+    
+    /// Where the subcommands print, colored as COLOR, COLOR_THEME and
+    /// NO_COLOR say (automatic colors in the default theme if the settings
+    /// are invalid).
+    #[cfg(feature = "native")]
+    pub fn terminal(&self) -> Terminal {
+        Terminal::stdout(
+            ColorChoice::parse(&self.color).unwrap_or_default(),
+            Theme::named(&self.color_theme).unwrap_or_default(),
+        )
+    }
+}
+
+/// Builds a `Config` in code, with typed setters instead of strings.
+/// Start from `Config::builder()`, set what you need, and call `build()`
+/// to get the same validation a config file goes through.
+#[derive(Debug, Clone, Default)]
+pub struct ConfigBuilder {
+    /// The configuration being built, starting from the defaults
+    config: Config,
+}
+
+impl ConfigBuilder {
+    /// What we call ourselves - the name of our application
+    pub fn app_name(mut self, value: impl Into<String>) -> Self {
+        self.config.app_name = value.into();
+        self
+    }
+
+    /// Where to put the generated markdown files. Can use the same
+    /// placeholders as the file name, like `lore/{date:%Y}`
+    pub fn output_dir(mut self, value: impl Into<String>) -> Self {
+        self.config.output_dir = value.into();
+        self
+    }
+
+    /// What to name the main output file. Placeholders are filled in on
+    /// each run: `{project}`, `{branch}`, `{source}`, and `{date}` or
+    /// `{date:<strftime format>}`, like `chat-history-{project}-{date:%Y-%m}.md`
+    pub fn output_filename(mut self, value: impl Into<String>) -> Self {
+        self.config.output_filename = value.into();
+        self
+    }
+
+    /// What to write the lore as: "markdown", "org", "asciidoc", "typst", "pdf",
+    /// "csv"/"parquet" for a table of messages, or "json" for the document tree
+    pub fn output_format(mut self, value: impl Into<String>) -> Self {
+        self.config.output_format = value.into();
+        self
+    }
+
+    /// Which chat application we're pulling history from ("cursor", "continue", "cody", or "plugin:<name>" for a source plugin)
+    pub fn source(mut self, value: impl Into<String>) -> Self {
+        self.config.source = value.into();
+        self
+    }
+
+    /// What type of database we're connecting to (currently just SQLite)
+    pub fn db_type(mut self, value: impl Into<String>) -> Self {
+        self.config.db_type = value.into();
+        self
+    }
+
+    /// The base path where the database files live
+    pub fn db_path(mut self, value: impl Into<String>) -> Self {
+        self.config.db_path = value.into();
+        self
+    }
+
+    /// The name of the database file we're looking for
+    pub fn db_filename(mut self, value: impl Into<String>) -> Self {
+        self.config.db_filename = value.into();
+        self
+    }
+
+    /// How long to wait for a locked database before calling it busy (milliseconds)
+    pub fn db_busy_timeout_ms(mut self, value: u64) -> Self {
+        self.config.db_busy_timeout_ms = value;
+        self
+    }
+
+    /// How many times to retry opening a database that stays locked
+    pub fn db_connect_retries(mut self, value: u32) -> Self {
+        self.config.db_connect_retries = value;
+        self
+    }
+
+    /// Open databases as immutable (no locking at all) - only safe with Cursor closed
+    pub fn db_immutable(mut self, value: bool) -> Self {
+        self.config.db_immutable = value;
+        self
+    }
+
+    /// Values bigger than this many MB are read in pieces and parsed as they arrive (0 streams them all)
+    pub fn large_value_mb(mut self, value: u64) -> Self {
+        self.config.large_value_mb = value;
+        self
+    }
+
+    /// What to do with a record whose timestamp is zero, negative or implausibly far off: "warn" (keep it), "skip" (leave it out) or "clamp" (move it to the nearest plausible time)
+    pub fn timestamp_policy(mut self, value: impl Into<String>) -> Self {
+        self.config.timestamp_policy = value.into();
+        self
+    }
+
+    /// What unit each source's numeric timestamps are in, like "cody=seconds,plugin:mine=ms" ("auto", "seconds" or "milliseconds"; a unit on its own covers every other source); empty for milliseconds from Cursor and working it out from each value elsewhere
+    pub fn timestamp_units(mut self, value: impl Into<String>) -> Self {
+        self.config.timestamp_units = value.into();
+        self
+    }
+
+    /// Where to find fields in Cursor records of a shape we don't know, like "session.created_at=/meta/created,composer_data.all_composers=/data/sessions" (`kind.field=/json/pointer`)
+    pub fn field_mapping(mut self, value: impl Into<String>) -> Self {
+        self.config.field_mapping = value.into();
+        self
+    }
+
+    /// Fail on records that don't match our models (unknown fields, missing keys, wrong types) instead of skipping or keeping them
+    pub fn strict(mut self, value: bool) -> Self {
+        self.config.strict = value;
+        self
+    }
+
+    /// The Cursor global storage database, where full conversations are kept
+    pub fn global_db_path(mut self, value: impl Into<String>) -> Self {
+        self.config.global_db_path = value.into();
+        self
+    }
+
+    /// The key/value table in the global database that holds conversations
+    pub fn conversation_table(mut self, value: impl Into<String>) -> Self {
+        self.config.conversation_table = value.into();
+        self
+    }
+
+    /// Whether to merge prompts and generations from global storage with the workspace's
+    pub fn include_global_storage(mut self, value: bool) -> Self {
+        self.config.include_global_storage = value;
+        self
+    }
+
+    /// The specific workspace ID we're interested in
+    pub fn workspace_id(mut self, value: impl Into<String>) -> Self {
+        self.config.workspace_id = value.into();
+        self
+    }
+
+    /// The name of the project we're extracting lore from
+    pub fn project_name(mut self, value: impl Into<String>) -> Self {
+        self.config.project_name = value.into();
+        self
+    }
+
+    /// Which git branch we're working with
+    pub fn project_branch(mut self, value: impl Into<String>) -> Self {
+        self.config.project_branch = value.into();
+        self
+    }
+
+    /// The full path to the project directory
+    pub fn project_path(mut self, value: impl Into<String>) -> Self {
+        self.config.project_path = value.into();
+        self
+    }
+
+    /// The key in the database where composer data is stored
+    pub fn composer_data_key(mut self, value: impl Into<String>) -> Self {
+        self.config.composer_data_key = value.into();
+        self
+    }
+
+    /// The key in the database where generation data is stored
+    pub fn generations_key(mut self, value: impl Into<String>) -> Self {
+        self.config.generations_key = value.into();
+        self
+    }
+
+    /// The key in the database where prompt data is stored
+    pub fn prompts_key(mut self, value: impl Into<String>) -> Self {
+        self.config.prompts_key = value.into();
+        self
+    }
+
+    /// Where Continue.dev keeps its per-session JSON files
+    pub fn continue_sessions_path(mut self, value: impl Into<String>) -> Self {
+        self.config.continue_sessions_path = value.into();
+        self
+    }
+
+    /// Where Cody's chat history lives - either an exported JSON file or
+    /// the VS Code global `state.vscdb` the extension writes to
+    pub fn cody_history_path(mut self, value: impl Into<String>) -> Self {
+        self.config.cody_history_path = value.into();
+        self
+    }
+
+    /// The key in VS Code's global state database that holds Cody's history
+    pub fn cody_history_key(mut self, value: impl Into<String>) -> Self {
+        self.config.cody_history_key = value.into();
+        self
+    }
+
+    /// Whether to include sensitive information in the output
+    pub fn include_secrets(mut self, value: bool) -> Self {
+        self.config.include_secrets = value;
+        self
+    }
+
+    /// Whether to include full absolute paths (privacy concern)
+    pub fn include_absolute_paths(mut self, value: bool) -> Self {
+        self.config.include_absolute_paths = value;
+        self
+    }
+
+    /// Whether to include system information in the metadata
+    pub fn include_system_info(mut self, value: bool) -> Self {
+        self.config.include_system_info = value;
+        self
+    }
+
+    /// Whether to keep user names, host names and email addresses in the
+    /// output instead of replacing them with placeholders
+    pub fn include_personal_info(mut self, value: bool) -> Self {
+        self.config.include_personal_info = value;
+        self
+    }
+
+    /// More user names to take out of the output, comma-separated, on top
+    /// of the ones detected on this machine
+    pub fn redact_users(mut self, value: impl Into<String>) -> Self {
+        self.config.redact_users = value.into();
+        self
+    }
+
+    /// More host names to take out of the output, comma-separated, on top
+    /// of this machine's
+    pub fn redact_hosts(mut self, value: impl Into<String>) -> Self {
+        self.config.redact_hosts = value.into();
+        self
+    }
+
+    /// Sanitizer rules of your own, applied to everything written after the
+    /// built-in ones, separated by `;`: `regex:<pattern>=<replacement>`,
+    /// `literal:<text>=<replacement>`, `path:<dir>=<placeholder>` or
+    /// `builtin:<secrets|emails|home-dirs|personal-info>`
+    pub fn sanitize_rules(mut self, value: impl Into<String>) -> Self {
+        self.config.sanitize_rules = value.into();
+        self
+    }
+
+    /// Which time zone to show timestamps in: an IANA name like "Europe/Rome", "UTC" or "local"
+    pub fn timezone(mut self, value: impl Into<String>) -> Self {
+        self.config.timezone = value.into();
+        self
+    }
+
+    /// Which language to write section titles and labels in ("en", "de", "it" or "ja")
+    pub fn locale(mut self, value: impl Into<String>) -> Self {
+        self.config.locale = value.into();
+        self
+    }
+
+    /// How to group the Historical Chat Sessions section: "none", "day", "week", "month" or "topic"
+    pub fn group_by(mut self, value: impl Into<String>) -> Self {
+        self.config.group_by = value.into();
+        self
+    }
+
+    /// What to sort sessions by before numbering them: "source", "created", "updated", "name" or "message-count"
+    pub fn sort_by(mut self, value: impl Into<String>) -> Self {
+        self.config.sort_by = value.into();
+        self
+    }
+
+    /// Whether sessions are sorted ascending ("asc") or descending ("desc")
+    pub fn sort_order(mut self, value: impl Into<String>) -> Self {
+        self.config.sort_order = value.into();
+        self
+    }
+
+    /// Only put these sessions in the lore: comma-separated session IDs or name patterns; empty for all
+    pub fn include_sessions(mut self, value: impl Into<String>) -> Self {
+        self.config.include_sessions = value.into();
+        self
+    }
+
+    /// Sessions to leave out of the lore: comma-separated session IDs or name patterns (`*` and `?` wildcards)
+    pub fn exclude_sessions(mut self, value: impl Into<String>) -> Self {
+        self.config.exclude_sessions = value.into();
+        self
+    }
+
+    /// Only put sessions with one of these tags in the lore (comma-separated); empty for all
+    pub fn include_tags(mut self, value: impl Into<String>) -> Self {
+        self.config.include_tags = value.into();
+        self
+    }
+
+    /// Leave out sessions with any of these tags (comma-separated)
+    pub fn exclude_tags(mut self, value: impl Into<String>) -> Self {
+        self.config.exclude_tags = value.into();
+        self
+    }
+
+    /// Hide sessions with fewer messages than this (0 keeps every session)
+    pub fn min_session_messages(mut self, value: usize) -> Self {
+        self.config.min_session_messages = value;
+        self
+    }
+
+    /// Merge a session into the one before it when it starts within this many
+    /// minutes of that one's last message and shares words with it (0 never merges)
+    pub fn merge_sessions_within(mut self, value: u64) -> Self {
+        self.config.merge_sessions_within = value;
+        self
+    }
+
+    /// How many related sessions (sharing words or files, and close in time) to link under each session; 0 for none
+    pub fn related_sessions(mut self, value: usize) -> Self {
+        self.config.related_sessions = value;
+        self
+    }
+
+    /// Draw a Mermaid diagram of sessions and commits per week: "none", "timeline" or "gantt"
+    pub fn timeline(mut self, value: impl Into<String>) -> Self {
+        self.config.timeline = value.into();
+        self
+    }
+
+    /// Export a knowledge graph of files, crates, services and features next to the markdown: "none", "json", "dot" or "graphml"
+    pub fn graph_format(mut self, value: impl Into<String>) -> Self {
+        self.config.graph_format = value.into();
+        self
+    }
+
+    /// Write a small markdown file per heavily discussed source file into a files/ directory next to the lore
+    pub fn file_lore(mut self, value: bool) -> Self {
+        self.config.file_lore = value;
+        self
+    }
+
+    /// How many sessions have to touch a file before it gets its own file in files/
+    pub fn file_lore_min_sessions(mut self, value: usize) -> Self {
+        self.config.file_lore_min_sessions = value;
+        self
+    }
+
+    /// Write lore-index.json next to the lore, mapping project files and code symbols to the sessions that discuss them, for editor extensions
+    pub fn lore_index(mut self, value: bool) -> Self {
+        self.config.lore_index = value;
+        self
+    }
+
+    /// Which kinds of generation the Generations section lists: "all", or any of explanation, code, plan and error-analysis, comma-separated (empty leaves the section out)
+    pub fn generation_kinds(mut self, value: impl Into<String>) -> Self {
+        self.config.generation_kinds = value.into();
+        self
+    }
+
+    /// A shell command to ask what a generation is when the heuristics are unsure: it gets the text on stdin and prints explanation, code, plan or error-analysis
+    pub fn classify_command(mut self, value: impl Into<String>) -> Self {
+        self.config.classify_command = value.into();
+        self
+    }
+
+    /// How long messages are shown: full, truncate[:lines] or summarize[:lines], and section=policy for sections that differ, like "truncate:40,current-session=full"
+    pub fn message_policy(mut self, value: impl Into<String>) -> Self {
+        self.config.message_policy = value.into();
+        self
+    }
+
+    /// A shell command that summarizes a long message for MESSAGE_POLICY=summarize: it gets the text on stdin and prints the summary
+    pub fn summarize_command(mut self, value: impl Into<String>) -> Self {
+        self.config.summarize_command = value.into();
+        self
+    }
+
+    /// Put each session transcript in a collapsed block (<details> in markdown) so the lore can be skimmed
+    pub fn collapse_transcripts(mut self, value: bool) -> Self {
+        self.config.collapse_transcripts = value;
+        self
+    }
+
+    /// Tidy code blocks in messages: guess missing languages, close unclosed fences, lengthen fences around nested ones and re-indent the code
+    pub fn normalize_fences(mut self, value: bool) -> Self {
+        self.config.normalize_fences = value;
+        self
+    }
+
+    /// The heading level of the lore's title; sections and sessions go that many levels deeper too, for embedding the lore in a bigger document
+    pub fn heading_level(mut self, value: usize) -> Self {
+        self.config.heading_level = value;
+        self
+    }
+
+    /// The deepest heading level to use; headings that would go deeper become bold lines
+    pub fn heading_depth(mut self, value: usize) -> Self {
+        self.config.heading_depth = value;
+        self
+    }
+
+    /// Start each markdown file written with YAML front matter (title, date, tags, draft) for static site generators
+    pub fn front_matter(mut self, value: bool) -> Self {
+        self.config.front_matter = value;
+        self
+    }
+
+    /// Mark the pages as drafts in the front matter
+    pub fn front_matter_draft(mut self, value: bool) -> Self {
+        self.config.front_matter_draft = value;
+        self
+    }
+
+    /// More front matter fields, as key=value pairs separated by commas (like layout=lore, weight=10)
+    pub fn front_matter_fields(mut self, value: impl Into<String>) -> Self {
+        self.config.front_matter_fields = value.into();
+        self
+    }
+
+    /// Whether to draw the knowledge graph as a Mermaid diagram in the markdown
+    pub fn graph_in_markdown(mut self, value: bool) -> Self {
+        self.config.graph_in_markdown = value;
+        self
+    }
+
+    /// Whether to add a Decisions section quoting the places in the transcripts where choices were made
+    pub fn include_decisions(mut self, value: bool) -> Self {
+        self.config.include_decisions = value;
+        self
+    }
+
+    /// Whether to add an Open follow-ups section listing the TODOs and work left for later in the chats
+    pub fn include_followups(mut self, value: bool) -> Self {
+        self.config.include_followups = value;
+        self
+    }
+
+    /// Whether to add a FAQ section pairing the questions asked in the chats with the answers that were taken
+    pub fn include_faq(mut self, value: bool) -> Self {
+        self.config.include_faq = value;
+        self
+    }
+
+    /// Add a Troubleshooting log of errors pasted into the chats and the fixes that were taken
+    pub fn include_troubleshooting(mut self, value: bool) -> Self {
+        self.config.include_troubleshooting = value;
+        self
+    }
+
+    /// Add an Extraction Warnings appendix listing records that were skipped or had to be patched up
+    pub fn include_warnings(mut self, value: bool) -> Self {
+        self.config.include_warnings = value;
+        self
+    }
+
+    /// Show the fields a source wrote for each session that we don't otherwise use, as an Additional fields list
+    pub fn include_extra_fields(mut self, value: bool) -> Self {
+        self.config.include_extra_fields = value;
+        self
+    }
+
+    /// Which sections to render and in what order, comma-separated (like "metadata,sessions,topics"); empty for all of them
+    pub fn sections(mut self, value: impl Into<String>) -> Self {
+        self.config.sections = value.into();
+        self
+    }
+
+    /// Sections of your own, from files, templates or commands, like `Architecture=file:docs/architecture.md;Dependencies@after:topics=command:cargo tree --depth 1`
+    #[cfg(feature = "native")]
+    pub fn custom_sections(mut self, value: Vec<CustomSection>) -> Self {
+        self.config.custom_sections = value;
+        self
+    }
+
+    /// Analyzer plugins (`lore-plugin-<name>` on PATH) whose sections to add, comma-separated
+    pub fn analyzer_plugins(mut self, value: impl Into<String>) -> Self {
+        self.config.analyzer_plugins = value.into();
+        self
+    }
+
+    /// Renderer plugins (`lore-plugin-<name>` on PATH) whose files to write next to the lore, comma-separated
+    pub fn render_plugins(mut self, value: impl Into<String>) -> Self {
+        self.config.render_plugins = value.into();
+        self
+    }
+
+    /// A markdown file describing the project for the Project Context section, relative to PROJECT_PATH
+    pub fn project_context_file(mut self, value: impl Into<String>) -> Self {
+        self.config.project_context_file = value.into();
+        self
+    }
+
+    /// A markdown file listing the project's key features, relative to PROJECT_PATH; the section is left out without one
+    pub fn key_features_file(mut self, value: impl Into<String>) -> Self {
+        self.config.key_features_file = value.into();
+        self
+    }
+
+    /// A JSON file of session names, summaries and ordering to apply over
+    /// the chat history, relative to PROJECT_PATH (see `curation::Curation`)
+    pub fn curation_file(mut self, value: impl Into<String>) -> Self {
+        self.config.curation_file = value.into();
+        self
+    }
+
+    /// Whether to look up the git commit that eventually contained each agent edit
+    pub fn link_edits_to_commits(mut self, value: bool) -> Self {
+        self.config.link_edits_to_commits = value;
+        self
+    }
+
+    /// What each model costs, used to estimate spend per session and month
+    pub fn model_pricing(mut self, value: Vec<ModelPrice>) -> Self {
+        self.config.model_pricing = value;
+        self
+    }
+
+    /// Base URL of the Confluence site to publish to (like https://team.atlassian.net/wiki)
+    pub fn confluence_url(mut self, value: impl Into<String>) -> Self {
+        self.config.confluence_url = value.into();
+        self
+    }
+
+    /// Key of the Confluence space the lore is published in
+    pub fn confluence_space(mut self, value: impl Into<String>) -> Self {
+        self.config.confluence_space = value.into();
+        self
+    }
+
+    /// Page to publish the lore under; empty for the top of the space
+    pub fn confluence_parent_id(mut self, value: impl Into<String>) -> Self {
+        self.config.confluence_parent_id = value.into();
+        self
+    }
+
+    /// Who to publish to Confluence as (usually an email address)
+    pub fn confluence_user(mut self, value: impl Into<String>) -> Self {
+        self.config.confluence_user = value.into();
+        self
+    }
+
+    /// API token for the Confluence user
+    pub fn confluence_token(mut self, value: impl Into<String>) -> Self {
+        self.config.confluence_token = value.into();
+        self
+    }
+
+    /// Notion integration token to publish with
+    pub fn notion_token(mut self, value: impl Into<String>) -> Self {
+        self.config.notion_token = value.into();
+        self
+    }
+
+    /// Notion page to publish the lore under (shared with the integration)
+    pub fn notion_parent_page(mut self, value: impl Into<String>) -> Self {
+        self.config.notion_parent_page = value.into();
+        self
+    }
+
+    /// Git repository (URL or path) to commit the lore into, like a GitHub wiki
+    pub fn git_publish_repo(mut self, value: impl Into<String>) -> Self {
+        self.config.git_publish_repo = value.into();
+        self
+    }
+
+    /// Branch to commit to; empty for the repository's default branch
+    pub fn git_publish_branch(mut self, value: impl Into<String>) -> Self {
+        self.config.git_publish_branch = value.into();
+        self
+    }
+
+    /// Directory inside the repository to put the lore pages in; empty for the top level
+    pub fn git_publish_dir(mut self, value: impl Into<String>) -> Self {
+        self.config.git_publish_dir = value.into();
+        self
+    }
+
+    /// Commit message for published lore; {project}, {sessions} and {date} are filled in
+    pub fn git_commit_message(mut self, value: impl Into<String>) -> Self {
+        self.config.git_commit_message = value.into();
+        self
+    }
+
+    /// Who to commit as, like "Lore Bot <lore@example.com>"; empty for git's own setting
+    pub fn git_author(mut self, value: impl Into<String>) -> Self {
+        self.config.git_author = value.into();
+        self
+    }
+
+    /// Push to a new branch and open a GitHub pull request instead of pushing straight to the branch
+    pub fn git_open_pr(mut self, value: bool) -> Self {
+        self.config.git_open_pr = value;
+        self
+    }
+
+    /// GitHub token, for opening pull requests and filing issues
+    pub fn github_token(mut self, value: impl Into<String>) -> Self {
+        self.config.github_token = value.into();
+        self
+    }
+
+    /// The GitHub repository to file issues in, as owner/name
+    pub fn github_issues_repo(mut self, value: impl Into<String>) -> Self {
+        self.config.github_issues_repo = value.into();
+        self
+    }
+
+    /// Labels for the issues filed on GitHub, comma-separated
+    pub fn github_issue_labels(mut self, value: impl Into<String>) -> Self {
+        self.config.github_issue_labels = value.into();
+        self
+    }
+
+    /// Linear API key, for filing issues
+    pub fn linear_api_key(mut self, value: impl Into<String>) -> Self {
+        self.config.linear_api_key = value.into();
+        self
+    }
+
+    /// The Linear team to file issues in (its ID)
+    pub fn linear_team_id(mut self, value: impl Into<String>) -> Self {
+        self.config.linear_team_id = value.into();
+        self
+    }
+
+    /// Slack or Discord webhook to post a digest of new sessions to after each run
+    pub fn notify_webhook(mut self, value: impl Into<String>) -> Self {
+        self.config.notify_webhook = value.into();
+        self
+    }
+
+    /// Where teammates can read the lore, linked from the digest
+    pub fn notify_lore_url(mut self, value: impl Into<String>) -> Self {
+        self.config.notify_lore_url = value.into();
+        self
+    }
+
+    /// SMTP server to send the email digest through (needs the `email` feature)
+    pub fn smtp_host(mut self, value: impl Into<String>) -> Self {
+        self.config.smtp_host = value.into();
+        self
+    }
+
+    /// SMTP server port
+    pub fn smtp_port(mut self, value: u16) -> Self {
+        self.config.smtp_port = value;
+        self
+    }
+
+    /// How to secure the SMTP connection: "starttls", "tls" or "none"
+    pub fn smtp_security(mut self, value: impl Into<String>) -> Self {
+        self.config.smtp_security = value.into();
+        self
+    }
+
+    /// SMTP user name, if the server wants one
+    pub fn smtp_username(mut self, value: impl Into<String>) -> Self {
+        self.config.smtp_username = value.into();
+        self
+    }
+
+    /// SMTP password
+    pub fn smtp_password(mut self, value: impl Into<String>) -> Self {
+        self.config.smtp_password = value.into();
+        self
+    }
+
+    /// Who the email digest comes from, like "Lore <lore@example.com>"
+    pub fn email_from(mut self, value: impl Into<String>) -> Self {
+        self.config.email_from = value.into();
+        self
+    }
+
+    /// Who gets the email digest, comma-separated
+    pub fn email_to(mut self, value: impl Into<String>) -> Self {
+        self.config.email_to = value.into();
+        self
+    }
+
+    /// The email digest subject, with {project}, {count} and {period} filled in
+    pub fn email_subject(mut self, value: impl Into<String>) -> Self {
+        self.config.email_subject = value.into();
+        self
+    }
+
+    /// An HTML template for the email digest, relative to PROJECT_PATH (empty for the built-in one)
+    pub fn email_html_template(mut self, value: impl Into<String>) -> Self {
+        self.config.email_html_template = value.into();
+        self
+    }
+
+    /// A plain-text template for the email digest, relative to PROJECT_PATH (empty for the built-in one)
+    pub fn email_text_template(mut self, value: impl Into<String>) -> Self {
+        self.config.email_text_template = value.into();
+        self
+    }
+
+    /// When the daemon runs, as a cron expression like "0 18 * * *" (in TIMEZONE)
+    pub fn schedule(mut self, value: impl Into<String>) -> Self {
+        self.config.schedule = value.into();
+        self
+    }
+
+    /// Encrypt the written lore with age (for ENCRYPT_RECIPIENTS or ENCRYPT_PASSPHRASE)
+    pub fn encrypt(mut self, value: bool) -> Self {
+        self.config.encrypt = value;
+        self
+    }
+
+    /// age recipients (`age1...`, comma-separated) who can decrypt the lore
+    pub fn encrypt_recipients(mut self, value: impl Into<String>) -> Self {
+        self.config.encrypt_recipients = value.into();
+        self
+    }
+
+    /// Passphrase to encrypt the lore with instead of recipients
+    pub fn encrypt_passphrase(mut self, value: impl Into<String>) -> Self {
+        self.config.encrypt_passphrase = value.into();
+        self
+    }
+
+    /// Keep a compressed snapshot of everything each run extracts in the state directory
+    pub fn snapshots(mut self, value: bool) -> Self {
+        self.config.snapshots = value;
+        self
+    }
+
+    /// Copy the source databases into a dated backup directory before reading them
+    pub fn backup(mut self, value: bool) -> Self {
+        self.config.backup = value;
+        self
+    }
+
+    /// Where backups go (the state directory's backups if empty)
+    pub fn backup_dir(mut self, value: impl Into<String>) -> Self {
+        self.config.backup_dir = value.into();
+        self
+    }
+
+    /// How many backups to keep (0 keeps them all)
+    pub fn backup_keep(mut self, value: usize) -> Self {
+        self.config.backup_keep = value;
+        self
+    }
+
+    /// Keep the previous version of each file a run replaces as `<name>.bak`
+    pub fn keep_previous_output(mut self, value: bool) -> Self {
+        self.config.keep_previous_output = value;
+        self
+    }
+
+    /// Skip rendering and writing when the extracted data, the settings, the project files and commits the lore reads and this program are all what they were at the last run that wrote the lore (never when it includes command output)
+    pub fn skip_unchanged(mut self, value: bool) -> Self {
+        self.config.skip_unchanged = value;
+        self
+    }
+
+    /// Where run history, the lock, snapshots and backups go (a per-project directory under XDG_STATE_HOME if empty)
+    pub fn state_dir(mut self, value: impl Into<String>) -> Self {
+        self.config.state_dir = value.into();
+        self
+    }
+
+    /// Where rebuildable data like the hook's fingerprint goes (a per-project directory under XDG_CACHE_HOME if empty)
+    pub fn cache_dir(mut self, value: impl Into<String>) -> Self {
+        self.config.cache_dir = value.into();
+        self
+    }
+
+    /// Color the subcommands' output: "auto" (on a terminal, unless NO_COLOR is set), "always" or "never"
+    pub fn color(mut self, value: impl Into<String>) -> Self {
+        self.config.color = value.into();
+        self
+    }
+
+    /// Which colors to use: "default" or "high-contrast"
+    pub fn color_theme(mut self, value: impl Into<String>) -> Self {
+        self.config.color_theme = value.into();
+        self
+    }
+
+    /// Check the settings and hand back the finished configuration.
+    pub fn build(self) -> Result<Config> {
+        self.config.validate()?;
+        Ok(self.config)
+    }
+}
+
+/// Read a setting that should parse as a number or boolean, falling back to
+/// the default when it's missing or empty. A value that doesn't parse is an
+/// error naming the setting, rather than quietly the default: `ENCRYPT=yes`
+/// mustn't write the lore unencrypted.
+fn parsed<T: FromStr>(lookup: &impl Fn(&str) -> Option<String>, name: &str, default: T) -> Result<T>
+where
+    T::Err: std::fmt::Display,
+{
+    match lookup(name) {
+        Some(value) if !value.trim().is_empty() => value
+            .trim()
+            .parse()
+            .map_err(|error| anyhow!("{} can't be '{}': {}", name, value.trim(), error)),
+        _ => Ok(default),
     }
 }
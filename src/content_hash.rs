@@ -0,0 +1,136 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::atomic::write_atomic;
+use crate::config::Config;
+use crate::curation::Curation;
+use crate::git::head_commit;
+use crate::importers::ImportedHistory;
+use crate::project::{project_files, project_tree, MANIFESTS};
+use crate::sections::custom::CustomContent;
+use crate::tags::SessionTags;
+use crate::timeline::TimelineStyle;
+
+/// Where the hash of the last run that wrote the lore is kept, in the
+/// cache directory.
+pub const CONTENT_HASH_FILE: &str = ".lore-content.json";
+
+/// Everything the lore is made from. If none of it has changed, the lore
+/// written from it wouldn't either (apart from when it says it was written).
+#[derive(Serialize)]
+struct Inputs<'a> {
+    version: &'a str,
+    config: &'a Config,
+    tags: &'a SessionTags,
+    curation: &'a Curation,
+    output: &'a Path,
+    history: &'a ImportedHistory,
+    project: ProjectInputs,
+    /// The commit checked out, when the lore looks up commits (the timeline
+    /// and LINK_EDITS_TO_COMMITS); both only read the history behind it
+    head: Option<String>,
+}
+
+/// What the lore takes from the project itself rather than the history.
+#[derive(Serialize)]
+struct ProjectInputs {
+    /// Its files, as the analyzer, the hotspots and the file lore see them
+    files: Vec<String>,
+    /// Its structure, as the project structure section draws it
+    tree: Option<String>,
+    /// What's in the files read into the lore: the manifests,
+    /// PROJECT_CONTEXT_FILE, KEY_FEATURES_FILE and custom sections' files
+    contents: BTreeMap<PathBuf, Option<String>>,
+}
+
+impl ProjectInputs {
+    fn read(config: &Config) -> Self {
+        let root = config.project_file("");
+        let mut read = MANIFESTS.iter().map(|name| root.join(name)).collect::<Vec<_>>();
+        for path in [&config.project_context_file, &config.key_features_file] {
+            if !path.trim().is_empty() {
+                read.push(config.project_file(path));
+            }
+        }
+        for section in &config.custom_sections {
+            if let CustomContent::File(path) = &section.content {
+                read.push(config.project_file(path));
+            }
+        }
+        ProjectInputs {
+            files: project_files(&root),
+            tree: project_tree(&root, 2),
+            contents: read.into_iter().map(|path| (path.clone(), fs::read_to_string(path).ok())).collect(),
+        }
+    }
+}
+
+/// Whether `content_hash` covers everything a run with `config` puts in
+/// the lore. It can't when the lore includes what a command prints -
+/// custom sections' commands, SUMMARIZE_COMMAND, analyzer and render
+/// plugins - which can change with nothing else changing, so those runs
+/// are never skipped.
+pub fn can_skip(config: &Config) -> bool {
+    let runs_commands = config.custom_sections.iter().any(|section| matches!(section.content, CustomContent::Command(_)))
+        || !config.summarize_command.trim().is_empty()
+        || !config.analyzer_plugins.trim().is_empty()
+        || !config.render_plugins.trim().is_empty();
+    !runs_commands
+}
+
+/// A stable hash (SHA-256, in hex) of what a run extracted and everything
+/// else that decides its lore: the settings, the tags and curation, where
+/// it's written, the project files and commits it reads, and this
+/// program's version. Unlike the hook's fingerprint, which goes by the
+/// sources' modification times, this only changes when the data does -
+/// Cursor touches its database far more often than it adds anything to
+/// the history.
+pub fn content_hash(
+    config: &Config,
+    tags: &SessionTags,
+    curation: &Curation,
+    output: &Path,
+    history: &ImportedHistory,
+) -> Result<String> {
+    let looks_up_commits = config.link_edits_to_commits || config.timeline_style() != TimelineStyle::None;
+    let inputs = Inputs {
+        version: env!("CARGO_PKG_VERSION"),
+        config,
+        tags,
+        curation,
+        output,
+        history,
+        project: ProjectInputs::read(config),
+        head: looks_up_commits.then(|| head_commit(&config.project_path)).flatten(),
+    };
+    // Hashed as it's serialized, so the history is never held twice
+    let mut hasher = Sha256::new();
+    serde_json::to_writer(&mut hasher, &inputs)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// The content hash of the last run that wrote the lore (SKIP_UNCHANGED).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContentState {
+    /// The hash, from `content_hash`
+    pub hash: String,
+}
+
+impl ContentState {
+    /// Load the state, or start fresh if there isn't any (or it's unreadable).
+    pub fn load(path: &Path) -> Self {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+
+    /// Save the state for the next run.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        write_atomic(path, serde_json::to_string_pretty(self)?.as_bytes())
+    }
+}
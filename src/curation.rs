@@ -0,0 +1,89 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use crate::ChatSession;
+
+/// What someone decided about one session: a better name than the one the
+/// chat app made up, a summary, and where it goes in the lore.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CuratedSession {
+    /// The name to show instead of the session's own
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// A summary to show under the session's heading
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub summary: Option<String>,
+    /// Where the session goes: sessions with an order come first, lowest
+    /// first, and the rest follow in SORT_BY's order
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub order: Option<i64>,
+}
+
+/// The curation overlay (CURATION_FILE): names, summaries and ordering for
+/// sessions, by session ID, like
+///
+/// ```json
+/// {
+///   "sessions": {
+///     "a29d4797-...": { "name": "Retry failed fetches", "summary": "Settled on exponential backoff.", "order": 1 }
+///   }
+/// }
+/// ```
+///
+/// It's applied as the lore is rendered, so the chat app's database is
+/// never touched and the overlay can be kept in the repository.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Curation {
+    /// What's been decided about each session, by session ID
+    #[serde(default)]
+    pub sessions: BTreeMap<String, CuratedSession>,
+}
+
+impl Curation {
+    /// Load the overlay, or start with an empty one if there's no file.
+    pub fn load(path: &Path) -> Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(text) => serde_json::from_str(&text).with_context(|| format!("{} is not a curation file", path.display())),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(Curation::default()),
+            Err(error) => Err(error).with_context(|| format!("Could not read {}", path.display())),
+        }
+    }
+
+    /// The name to show for a session: the curated one, or its own.
+    pub fn name<'a>(&'a self, session: &'a ChatSession) -> &'a str {
+        self.sessions
+            .get(&session.composer_id)
+            .and_then(|curated| curated.name.as_deref())
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .unwrap_or(&session.name)
+    }
+
+    /// The summary written for a session, if there is one.
+    pub fn summary(&self, session_id: &str) -> Option<&str> {
+        self.sessions
+            .get(session_id)
+            .and_then(|curated| curated.summary.as_deref())
+            .map(str::trim)
+            .filter(|summary| !summary.is_empty())
+    }
+
+    /// Move the sessions that have an order to the front, lowest first,
+    /// keeping the rest in the order they're in.
+    pub fn reorder(&self, sessions: &mut [&ChatSession]) {
+        sessions.sort_by_key(|session| match self.sessions.get(&session.composer_id).and_then(|curated| curated.order) {
+            Some(order) => (0, order),
+            None => (1, 0),
+        });
+    }
+
+    /// Whether nothing's been curated.
+    pub fn is_empty(&self) -> bool {
+        self.sessions.is_empty()
+    }
+}
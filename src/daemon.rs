@@ -0,0 +1,98 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+
+/// Where the daemon records its runs, in the state directory.
+pub const RUN_HISTORY_FILE: &str = ".lore-runs.jsonl";
+
+/// Where the run lock goes, in the state directory.
+pub const LOCK_FILE: &str = ".lore.lock";
+
+/// How a scheduled run ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum RunStatus {
+    /// The lore was written (or published)
+    Succeeded,
+    /// Something went wrong; the error is in the record
+    Failed,
+    /// Another run still held the lock, so this one didn't start
+    Skipped,
+}
+
+/// One line of the daemon's run history.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct RunRecord {
+    /// When the run started
+    pub started_at: DateTime<Utc>,
+    /// When it finished
+    pub finished_at: DateTime<Utc>,
+    /// How it ended
+    pub status: RunStatus,
+    /// How many sessions it found, if it got that far
+    #[serde(default)]
+    pub sessions: Option<usize>,
+    /// What went wrong, for failed and skipped runs
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+impl RunRecord {
+    /// A run that finished normally.
+    pub fn succeeded(started_at: DateTime<Utc>, sessions: usize) -> Self {
+        RunRecord {
+            started_at,
+            finished_at: Utc::now(),
+            status: RunStatus::Succeeded,
+            sessions: Some(sessions),
+            error: None,
+        }
+    }
+
+    /// A run that ended in an error.
+    pub fn failed(started_at: DateTime<Utc>, error: &anyhow::Error) -> Self {
+        RunRecord {
+            started_at,
+            finished_at: Utc::now(),
+            status: RunStatus::Failed,
+            sessions: None,
+            error: Some(format!("{:#}", error)),
+        }
+    }
+
+    /// A run that didn't start because another one was still going.
+    pub fn skipped(started_at: DateTime<Utc>, reason: &str) -> Self {
+        RunRecord {
+            started_at,
+            finished_at: Utc::now(),
+            status: RunStatus::Skipped,
+            sessions: None,
+            error: Some(reason.to_string()),
+        }
+    }
+}
+
+/// Add a run to the history file (one JSON object per line).
+pub fn append_history(path: &Path, record: &RunRecord) -> Result<()> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Could not open run history {}", path.display()))?;
+    writeln!(file, "{}", serde_json::to_string(record)?)
+        .with_context(|| format!("Could not write run history {}", path.display()))
+}
+
+/// Read the run history back, oldest first. Lines that don't parse (a
+/// run killed mid-write, say) are skipped.
+pub fn read_history(path: &Path) -> Result<Vec<RunRecord>> {
+    match fs::read_to_string(path) {
+        Ok(text) => Ok(text.lines().filter_map(|line| serde_json::from_str(line).ok()).collect()),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(error) => Err(error).with_context(|| format!("Could not read run history {}", path.display())),
+    }
+}
@@ -0,0 +1,133 @@
+use crate::document::{split_message, MessagePart};
+use crate::{ChatSession, MessageRole};
+
+/// Phrases that usually mean a choice was made (or argued for). They're
+/// matched ignoring case, as whole words.
+const DECISION_PHRASES: &[&str] = &[
+    "let's go with",
+    "lets go with",
+    "we'll go with",
+    "going with",
+    "we decided",
+    "i decided",
+    "decided to",
+    "decision is",
+    "instead of",
+    "rather than",
+    "tradeoff",
+    "trade-off",
+    "trade off",
+    "opted for",
+    "settled on",
+    "we'll use",
+    "we should use",
+];
+
+/// The longest an excerpt gets before it's cut short.
+const EXCERPT_LENGTH: usize = 280;
+
+/// A moment in a session where a decision seems to have been made.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Decision {
+    /// Which message in the session it was made in
+    pub message: usize,
+    /// When the message was sent (Unix milliseconds), or when the session
+    /// started if the message has no time of its own
+    pub timestamp: i64,
+    /// Who said it
+    pub role: MessageRole,
+    /// The phrase that gave it away, as listed in `DECISION_PHRASES`
+    pub phrase: &'static str,
+    /// The sentence it was in, shortened if it's long
+    pub excerpt: String,
+}
+
+/// Find the decisions in a session: at most one per message (the first
+/// sentence that sounds like one), skipping code blocks.
+pub fn session_decisions(session: &ChatSession) -> Vec<Decision> {
+    let mut decisions: Vec<Decision> = Vec::new();
+    for (index, message) in session.messages.iter().enumerate() {
+        let found = split_message(&message.text).into_iter().find_map(|part| match part {
+            MessagePart::Prose(prose) => sentences(&prose).into_iter().find_map(|sentence| {
+                decision_phrase(sentence).map(|phrase| (phrase, excerpt(sentence)))
+            }),
+            MessagePart::Code { .. } => None,
+        });
+        if let Some((phrase, excerpt)) = found {
+            // The assistant often repeats the user's decision back; once is enough
+            if decisions.iter().any(|decision| decision.excerpt == excerpt) {
+                continue;
+            }
+            decisions.push(Decision {
+                message: index,
+                timestamp: message.timestamp.unwrap_or(session.created_at),
+                role: message.role,
+                phrase,
+                excerpt,
+            });
+        }
+    }
+    decisions
+}
+
+/// The decision phrase in a sentence, if it has one.
+pub fn decision_phrase(sentence: &str) -> Option<&'static str> {
+    find_phrase(sentence, DECISION_PHRASES)
+}
+
+/// The first of `phrases` that's in a sentence as whole words, ignoring case.
+pub(crate) fn find_phrase(sentence: &str, phrases: &[&'static str]) -> Option<&'static str> {
+    let words: Vec<String> = sentence
+        .to_lowercase()
+        .replace('\u{2019}', "'")
+        .split(|c: char| !(c.is_alphanumeric() || c == '\'' || c == '-'))
+        .filter(|word| !word.is_empty())
+        .map(str::to_string)
+        .collect();
+    let text = format!(" {} ", words.join(" "));
+    phrases.iter().copied().find(|phrase| text.contains(&format!(" {} ", phrase)))
+}
+
+/// Split prose into sentences, at sentence-ending punctuation followed by
+/// a space and at line breaks.
+pub(crate) fn sentences(prose: &str) -> Vec<&str> {
+    let mut sentences = Vec::new();
+    for line in prose.lines() {
+        let mut start = 0;
+        let chars: Vec<(usize, char)> = line.char_indices().collect();
+        for (i, &(position, c)) in chars.iter().enumerate() {
+            let at_end = chars.get(i + 1).is_none_or(|(_, next)| next.is_whitespace());
+            if matches!(c, '.' | '!' | '?') && at_end {
+                sentences.push(line[start..position + c.len_utf8()].trim());
+                start = position + c.len_utf8();
+            }
+        }
+        sentences.push(line[start..].trim());
+    }
+    sentences.retain(|sentence| !sentence.is_empty());
+    sentences
+}
+
+/// A message's prose (without code blocks) on one line, cut to
+/// `EXCERPT_LENGTH` - to quote the exchange around a decision.
+pub fn message_excerpt(text: &str) -> String {
+    let prose: Vec<String> = split_message(text)
+        .into_iter()
+        .filter_map(|part| match part {
+            MessagePart::Prose(prose) => Some(prose.split_whitespace().collect::<Vec<_>>().join(" ")),
+            MessagePart::Code { .. } => None,
+        })
+        .collect();
+    excerpt(&prose.join(" "))
+}
+
+/// A sentence trimmed of list markers and cut to `EXCERPT_LENGTH`.
+pub(crate) fn excerpt(sentence: &str) -> String {
+    let sentence = sentence.trim_start_matches(['-', '*', '>', ' ']).trim();
+    if sentence.chars().count() <= EXCERPT_LENGTH {
+        return sentence.to_string();
+    }
+    let cut: String = sentence.chars().take(EXCERPT_LENGTH).collect();
+    let cut = cut.rsplit_once(' ').map_or(cut.as_str(), |(head, _)| head);
+    format!("{}...", cut.trim_end())
+}
@@ -0,0 +1,143 @@
+use anyhow::{bail, Context, Result};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::backup::BACKUP_DIR;
+use crate::config::Config;
+use crate::daemon::RUN_HISTORY_FILE;
+use crate::hook::HOOK_STATE_FILE;
+use crate::notify::NOTIFY_STATE_FILE;
+use crate::snapshot::SNAPSHOT_DIR;
+
+/// Our directory under the XDG state and cache homes.
+pub const APP_DIR: &str = "persistent-code-lore";
+
+/// Where a project's bookkeeping lives, away from the lore in the output
+/// directory: run history, the lock, notification state, snapshots and
+/// backups go in the state directory, and things that can be rebuilt at
+/// any time (the hook's fingerprint, the content hash) in the cache directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dirs {
+    /// Things worth keeping between runs
+    pub state: PathBuf,
+    /// Things that can be thrown away
+    pub cache: PathBuf,
+}
+
+impl Dirs {
+    /// The directories for the project writing to `output_dir`, from
+    /// STATE_DIR and CACHE_DIR if they're set, and otherwise from the XDG
+    /// base directories in the environment.
+    pub fn resolve(config: &Config, output_dir: &Path) -> Result<Self> {
+        Self::resolve_with(config, output_dir, |name| std::env::var(name).ok())
+    }
+
+    /// The same, reading the environment through `env` (for tests).
+    /// STATE_DIR and CACHE_DIR are used as they are; the XDG defaults get a
+    /// subdirectory per project, named after PROJECT_NAME and the output
+    /// directory, so two checkouts of one project don't share a history.
+    pub fn resolve_with(config: &Config, output_dir: &Path, env: impl Fn(&str) -> Option<String>) -> Result<Self> {
+        let project = project_key(config, output_dir)?;
+        let dir = |setting: &str, variable: &str, fallback: &str| -> Result<PathBuf> {
+            if !setting.trim().is_empty() {
+                return Ok(PathBuf::from(shellexpand::tilde(setting.trim()).to_string()));
+            }
+            Ok(base_dir(&env, variable, fallback)?.join(APP_DIR).join(&project))
+        };
+        Ok(Dirs {
+            state: dir(&config.state_dir, "XDG_STATE_HOME", ".local/state")?,
+            cache: dir(&config.cache_dir, "XDG_CACHE_HOME", ".cache")?,
+        })
+    }
+
+    /// Move bookkeeping that earlier versions kept in the output directory
+    /// to where it lives now. Anything already at the new location is left
+    /// alone. Returns where each moved item went.
+    pub fn adopt_legacy(&self, output_dir: &Path) -> Result<Vec<PathBuf>> {
+        let moves = [
+            (RUN_HISTORY_FILE, &self.state),
+            (NOTIFY_STATE_FILE, &self.state),
+            (SNAPSHOT_DIR, &self.state),
+            (BACKUP_DIR, &self.state),
+            (HOOK_STATE_FILE, &self.cache),
+        ];
+        let mut moved = Vec::new();
+        for (name, dir) in moves {
+            let from = output_dir.join(name);
+            let to = dir.join(name);
+            if !from.exists() || to.exists() || from == to {
+                continue;
+            }
+            fs::create_dir_all(dir).with_context(|| format!("Could not create {}", dir.display()))?;
+            move_path(&from, &to).with_context(|| format!("Could not move {} to {}", from.display(), to.display()))?;
+            moved.push(to);
+        }
+        Ok(moved)
+    }
+}
+
+/// Our directory under the XDG state home, for bookkeeping that isn't
+/// any one project's, like a batch's progress.
+pub fn state_home() -> Result<PathBuf> {
+    Ok(base_dir(&|name: &str| std::env::var(name).ok(), "XDG_STATE_HOME", ".local/state")?.join(APP_DIR))
+}
+
+/// An XDG base directory: the variable if it holds an absolute path (the
+/// spec says relative ones are to be ignored), or `fallback` in the home
+/// directory.
+fn base_dir(env: &impl Fn(&str) -> Option<String>, variable: &str, fallback: &str) -> Result<PathBuf> {
+    if let Some(dir) = env(variable).filter(|dir| Path::new(dir).is_absolute()) {
+        return Ok(PathBuf::from(dir));
+    }
+    match env("HOME").or_else(|| env("USERPROFILE")).filter(|home| !home.is_empty()) {
+        Some(home) => Ok(Path::new(&home).join(fallback)),
+        None => bail!("Could not find a home directory; set {} or the matching --state-dir/--cache-dir", variable),
+    }
+}
+
+/// The project's subdirectory name: PROJECT_NAME made safe for a path,
+/// plus a short hash of the absolute output directory.
+fn project_key(config: &Config, output_dir: &Path) -> Result<String> {
+    let name: String = config
+        .project_name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.' { c } else { '-' })
+        .collect();
+    let name = name.trim_matches(|c| c == '-' || c == '.');
+    let absolute = std::path::absolute(output_dir)
+        .with_context(|| format!("Could not make {} absolute", output_dir.display()))?;
+    let hash: String = Sha256::digest(absolute.to_string_lossy().as_bytes())
+        .iter()
+        .take(4)
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    Ok(if name.is_empty() { hash } else { format!("{}-{}", name, hash) })
+}
+
+/// Rename, or copy and delete when the two are on different filesystems.
+fn move_path(from: &Path, to: &Path) -> Result<()> {
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    copy_recursively(from, to)?;
+    if from.is_dir() {
+        fs::remove_dir_all(from)?;
+    } else {
+        fs::remove_file(from)?;
+    }
+    Ok(())
+}
+
+fn copy_recursively(from: &Path, to: &Path) -> Result<()> {
+    if !from.is_dir() {
+        fs::copy(from, to)?;
+        return Ok(());
+    }
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        copy_recursively(&entry.path(), &to.join(entry.file_name()))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,396 @@
+use chrono::NaiveDateTime;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::fences::{parse_fence, Fence};
+use crate::versioning::FORMAT_VERSION;
+
+/// The lore as a tree of blocks, before it's written out in any particular
+/// format. The generator builds one of these and the renderers in
+/// `render` turn it into markdown, org-mode or AsciiDoc, so every format
+/// gets the same sections in the same order. `--format json` writes the
+/// tree itself, for the browser viewer to render again (see `wasm`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Document {
+    /// `FORMAT_VERSION` when the document was built; documents from before
+    /// it was recorded have none, which reads as 0
+    #[serde(default)]
+    pub format_version: u32,
+    /// The document's blocks, top to bottom
+    pub blocks: Vec<Block>,
+}
+
+impl Default for Document {
+    fn default() -> Self {
+        Document { format_version: FORMAT_VERSION, blocks: Vec::new() }
+    }
+}
+
+impl Document {
+    /// Add a block to the end of the document.
+    pub fn push(&mut self, block: Block) {
+        self.blocks.push(block);
+    }
+
+    /// Add a run of blocks (a whole section, say) to the end of the document.
+    pub fn extend(&mut self, blocks: Vec<Block>) {
+        self.blocks.extend(blocks);
+    }
+}
+
+/// One piece of the document.
+/// Text in headings, paragraphs, fields and list items may use the small
+/// inline subset `parse_inline` understands: `**bold**`, `*italic*`,
+/// `` `code` `` and `[text](target)` links.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Block {
+    /// A section heading. Level 1 is the document title.
+    Heading {
+        /// 1 for the title, 2 for sections, 3 and deeper for subsections
+        level: usize,
+        /// The heading text
+        text: String,
+        /// An anchor other blocks can link to with `[text](#id)`
+        #[serde(default)]
+        id: Option<String>,
+        /// When the thing the heading is about happened, in display time
+        #[serde(default)]
+        timestamp: Option<NaiveDateTime>,
+        /// Labelled facts about the heading (date, session id, ...).
+        /// Org-mode turns these into a properties drawer.
+        #[serde(default)]
+        properties: Vec<(String, String)>,
+    },
+    /// A paragraph of text
+    Paragraph(String),
+    /// Labelled values, one per line, like `**Models**: gpt-4o`
+    Fields(Vec<(String, String)>),
+    /// A bulleted or numbered list
+    List {
+        /// Whether the items are numbered
+        ordered: bool,
+        /// The items, one line each
+        items: Vec<String>,
+    },
+    /// Preformatted text, like a diff or a Mermaid diagram
+    Code {
+        /// The language, for highlighting ("diff", "shell", "mermaid", or empty)
+        language: String,
+        /// The text itself, ending in a newline
+        text: String,
+    },
+    /// Content that starts out collapsed, behind a one-line summary
+    Details {
+        /// The line shown while it's collapsed
+        summary: String,
+        /// What's inside
+        blocks: Vec<Block>,
+    },
+    /// One message of a conversation. The text is whatever the chat app
+    /// stored, which is usually markdown and may contain fenced code.
+    Message {
+        /// Who said it, already translated
+        speaker: String,
+        /// When they said it, already formatted for display
+        #[serde(default)]
+        sent_at: Option<String>,
+        /// What they said
+        text: String,
+    },
+    /// A horizontal rule
+    Rule,
+}
+
+impl Block {
+    /// A plain heading with no anchor, timestamp or properties.
+    pub fn heading(level: usize, text: impl Into<String>) -> Self {
+        Block::Heading {
+            level,
+            text: text.into(),
+            id: None,
+            timestamp: None,
+            properties: Vec::new(),
+        }
+    }
+
+    /// A bulleted list.
+    pub fn bullets(items: Vec<String>) -> Self {
+        Block::List { ordered: false, items }
+    }
+
+    /// A numbered list.
+    pub fn numbered(items: Vec<String>) -> Self {
+        Block::List { ordered: true, items }
+    }
+
+    /// A code block.
+    pub fn code(language: &str, text: impl Into<String>) -> Self {
+        Block::Code {
+            language: language.to_string(),
+            text: text.into(),
+        }
+    }
+}
+
+/// Move headings `shift` levels deeper, in `blocks` and the blocks inside
+/// them, for a document that sits under headings of its own. A heading
+/// that would end up deeper than `deepest` is written as a bold line
+/// instead (with its properties after it), since it can't nest any further.
+pub fn nest_headings(blocks: Vec<Block>, shift: usize, deepest: usize) -> Vec<Block> {
+    let mut nested = Vec::with_capacity(blocks.len());
+    for block in blocks {
+        match block {
+            Block::Heading { level, text, id, timestamp, properties } if level + shift <= deepest => {
+                nested.push(Block::Heading { level: level + shift, text, id, timestamp, properties });
+            }
+            Block::Heading { text, properties, .. } => {
+                nested.push(Block::Paragraph(format!("**{}**", text)));
+                if !properties.is_empty() {
+                    nested.push(Block::Fields(properties));
+                }
+            }
+            Block::Details { summary, blocks } => {
+                nested.push(Block::Details { summary, blocks: nest_headings(blocks, shift, deepest) });
+            }
+            block => nested.push(block),
+        }
+    }
+    nested
+}
+
+/// `blocks` with `change` applied to all of their text, inside collapsed
+/// blocks too: headings and their properties, paragraphs, fields, list
+/// items, code and messages. Heading anchors are left as they are, so
+/// links to them keep working.
+pub fn map_text(blocks: Vec<Block>, change: &dyn Fn(&str) -> String) -> Vec<Block> {
+    let pairs = |pairs: Vec<(String, String)>| pairs.into_iter().map(|(label, value)| (change(&label), change(&value))).collect();
+    blocks
+        .into_iter()
+        .map(|block| match block {
+            Block::Heading { level, text, id, timestamp, properties } => {
+                Block::Heading { level, text: change(&text), id, timestamp, properties: pairs(properties) }
+            }
+            Block::Paragraph(text) => Block::Paragraph(change(&text)),
+            Block::Fields(fields) => Block::Fields(pairs(fields)),
+            Block::List { ordered, items } => Block::List { ordered, items: items.iter().map(|item| change(item)).collect() },
+            Block::Code { language, text } => Block::Code { language, text: change(&text) },
+            Block::Details { summary, blocks } => Block::Details { summary: change(&summary), blocks: map_text(blocks, change) },
+            Block::Message { speaker, sent_at, text } => Block::Message { speaker, sent_at, text: change(&text) },
+            Block::Rule => Block::Rule,
+        })
+        .collect()
+}
+
+/// A run of inline text with (at most) one style applied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Inline {
+    /// Plain text
+    Text(String),
+    /// `**bold**`
+    Bold(String),
+    /// `*italic*`
+    Italic(String),
+    /// `` `code` ``
+    Code(String),
+    /// `[text](target)`, where a target starting with `#` is a heading id
+    Link {
+        /// The link text
+        text: String,
+        /// Where it points
+        target: String,
+    },
+}
+
+/// Split inline text into styled runs. Anything that doesn't look like one
+/// of the supported styles stays plain text, so stray asterisks in chat
+/// messages come through untouched.
+pub fn parse_inline(text: &str) -> Vec<Inline> {
+    let mut spans: Vec<Inline> = Vec::new();
+    let mut plain = String::new();
+    let mut rest = text;
+
+    while let Some(c) = rest.chars().next() {
+        let span = match c {
+            '`' => delimited(rest, "`", "`").map(|(inner, len)| (Inline::Code(inner.to_string()), len)),
+            '*' if rest.starts_with("**") => delimited(rest, "**", "**")
+                .filter(|(inner, _)| is_tight(inner))
+                .map(|(inner, len)| (Inline::Bold(inner.to_string()), len)),
+            '*' => delimited(rest, "*", "*")
+                .filter(|(inner, _)| is_tight(inner))
+                .map(|(inner, len)| (Inline::Italic(inner.to_string()), len)),
+            '[' => link(rest),
+            _ => None,
+        };
+
+        match span {
+            Some((span, len)) => {
+                if !plain.is_empty() {
+                    spans.push(Inline::Text(std::mem::take(&mut plain)));
+                }
+                spans.push(span);
+                rest = &rest[len..];
+            }
+            None => {
+                plain.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+
+    if !plain.is_empty() {
+        spans.push(Inline::Text(plain));
+    }
+    spans
+}
+
+/// Text between an opening and closing marker on the same line, with the
+/// total length consumed.
+fn delimited<'a>(text: &'a str, open: &str, close: &str) -> Option<(&'a str, usize)> {
+    let body = text.strip_prefix(open)?;
+    let end = body.find(close)?;
+    let inner = &body[..end];
+    if inner.is_empty() || inner.contains('\n') {
+        return None;
+    }
+    Some((inner, open.len() + end + close.len()))
+}
+
+/// Emphasis has to hug its text (`*this*`, not `* this *`), the way
+/// markdown itself decides whether an asterisk is emphasis.
+fn is_tight(inner: &str) -> bool {
+    !inner.starts_with(char::is_whitespace) && !inner.ends_with(char::is_whitespace)
+}
+
+/// A `[text](target)` link at the start of the text.
+fn link(text: &str) -> Option<(Inline, usize)> {
+    let (label, label_len) = delimited(text, "[", "]")?;
+    let (target, target_len) = delimited(&text[label_len..], "(", ")")?;
+    if target.contains(char::is_whitespace) {
+        return None;
+    }
+    Some((
+        Inline::Link {
+            text: label.to_string(),
+            target: target.to_string(),
+        },
+        label_len + target_len,
+    ))
+}
+
+/// A piece of a chat message: prose, or a fenced code block inside it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MessagePart {
+    /// Ordinary text, possibly several lines
+    Prose(String),
+    /// A fenced code block, without its fences
+    Code {
+        /// The language after the opening fence, if any
+        language: String,
+        /// The code, ending in a newline
+        text: String,
+    },
+}
+
+/// Split a message into prose and fenced code, so formats without
+/// markdown fences can give the code their own kind of block. A block
+/// ends at a fence like the one that opened it (so a longer fence can hold
+/// shorter ones), and an unclosed fence runs to the end of the message, as
+/// it would on GitHub.
+pub fn split_message(text: &str) -> Vec<MessagePart> {
+    let mut parts: Vec<MessagePart> = Vec::new();
+    let mut prose = String::new();
+    let mut code: Option<(Fence, String)> = None;
+
+    for line in text.lines() {
+        match (&mut code, parse_fence(line)) {
+            (None, Some(fence)) => {
+                if !prose.trim().is_empty() {
+                    parts.push(MessagePart::Prose(prose.trim().to_string()));
+                }
+                prose.clear();
+                code = Some((fence, String::new()));
+            }
+            (Some((open, _)), Some(fence)) if fence.closes(open) => {
+                if let Some((open, text)) = code.take() {
+                    parts.push(MessagePart::Code { language: open.info.to_string(), text });
+                }
+            }
+            (Some((_, text)), _) => {
+                text.push_str(line);
+                text.push('\n');
+            }
+            (None, None) => {
+                prose.push_str(line);
+                prose.push('\n');
+            }
+        }
+    }
+
+    if let Some((open, text)) = code {
+        parts.push(MessagePart::Code { language: open.info.to_string(), text });
+    }
+    if !prose.trim().is_empty() {
+        parts.push(MessagePart::Prose(prose.trim().to_string()));
+    }
+    parts
+}
+
+/// Turn markdown written by hand (a README-style file, say) into blocks:
+/// fenced code, headings (two levels down, so they sit under a section's
+/// heading), bulleted and numbered lists, and paragraphs.
+pub fn markdown_blocks(text: &str) -> Vec<Block> {
+    let mut blocks = Vec::new();
+    for part in split_message(text) {
+        let prose = match part {
+            MessagePart::Code { language, text } => {
+                blocks.push(Block::Code { language, text });
+                continue;
+            }
+            MessagePart::Prose(prose) => prose,
+        };
+        for paragraph in prose.split("\n\n").map(str::trim).filter(|paragraph| !paragraph.is_empty()) {
+            let mut lines: Vec<&str> = paragraph.lines().map(str::trim).collect();
+            let hashes = lines[0].chars().take_while(|&c| c == '#').count();
+            if (1..=6).contains(&hashes) && lines[0][hashes..].starts_with(' ') {
+                blocks.push(Block::heading((hashes + 2).min(6), lines[0][hashes..].trim()));
+                lines.remove(0);
+            }
+            if lines.is_empty() {
+                continue;
+            }
+            if let Some(items) = list_items(&lines, |line| line.strip_prefix("- ").or_else(|| line.strip_prefix("* "))) {
+                blocks.push(Block::bullets(items));
+            } else if let Some(items) = list_items(&lines, |line| {
+                line.split_once(". ")
+                    .filter(|(number, _)| !number.is_empty() && number.chars().all(|c| c.is_ascii_digit()))
+                    .map(|(_, item)| item)
+            }) {
+                blocks.push(Block::numbered(items));
+            } else {
+                blocks.push(Block::Paragraph(lines.join("\n")));
+            }
+        }
+    }
+    blocks
+}
+
+/// The items of a list, if every line is one.
+fn list_items<'a>(lines: &[&'a str], item: impl Fn(&'a str) -> Option<&'a str>) -> Option<Vec<String>> {
+    lines.iter().map(|line| item(line).map(str::to_string)).collect()
+}
+
+/// The anchor GitHub (and most markdown renderers) give a heading:
+/// lowercased, punctuation dropped, spaces turned into hyphens.
+pub fn heading_anchor(heading: &str) -> String {
+    heading
+        .trim()
+        .to_lowercase()
+        .chars()
+        .filter_map(|c| match c {
+            ' ' => Some('-'),
+            c if c.is_alphanumeric() || c == '-' || c == '_' => Some(c),
+            _ => None,
+        })
+        .collect()
+}
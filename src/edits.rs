@@ -0,0 +1,88 @@
+use serde_json::Value;
+use similar::TextDiff;
+
+use crate::{ChatSession, FileEdit, ToolCall};
+
+/// Turn an editing tool call into a diff, if the tool was one that edits files.
+/// Search-and-replace style tools give us both sides so we can build a real
+/// diff; whole-file and sketch-style edits only tell us the new text.
+pub(crate) fn edit_from_tool_call(call: &ToolCall) -> Option<FileEdit> {
+    let arguments: Value = serde_json::from_str(&call.arguments).ok()?;
+    let path = ["file_path", "target_file", "path", "filepath"]
+        .iter()
+        .find_map(|key| arguments[*key].as_str())?
+        .to_string();
+
+    let diff = if let (Some(old), Some(new)) = (
+        arguments["old_string"].as_str(),
+        arguments["new_string"].as_str(),
+    ) {
+        TextDiff::from_lines(old, new)
+            .unified_diff()
+            .context_radius(3)
+            .to_string()
+    } else {
+        let new = ["code_edit", "contents", "content", "file_text"]
+            .iter()
+            .find_map(|key| arguments[*key].as_str())?;
+        additions_only(new)
+    };
+
+    Some(FileEdit {
+        path,
+        diff,
+        status: call.status.clone(),
+    })
+}
+
+/// Turn one of Cursor's stored code block diffs into a `FileEdit`.
+/// Cursor keeps the replaced line range and the new lines, but not the old
+/// text, so each hunk shows what went in and where.
+pub(crate) fn edit_from_code_block_diff(path: &str, diff: &Value, status: Option<&str>) -> FileEdit {
+    let mut text = String::new();
+
+    for change in diff["newModelDiffWrtV0"].as_array().into_iter().flatten() {
+        let start = change["original"]["startLineNumber"].as_u64().unwrap_or(1);
+        let end = change["original"]["endLineNumberExclusive"].as_u64().unwrap_or(start);
+        let lines: Vec<&str> = change["modified"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+            .collect();
+
+        text.push_str(&format!(
+            "@@ -{},{} +{},{} @@\n",
+            start,
+            end.saturating_sub(start),
+            start,
+            lines.len()
+        ));
+        for line in lines {
+            text.push_str(&format!("+{}\n", line));
+        }
+    }
+
+    FileEdit {
+        path: path.to_string(),
+        diff: text,
+        status: status.map(str::to_string),
+    }
+}
+
+/// Render brand new text as a diff where every line is an addition.
+fn additions_only(text: &str) -> String {
+    text.lines().map(|line| format!("+{}\n", line)).collect()
+}
+
+/// Every edit made during a session, paired with when it happened (if known).
+pub fn session_edits(session: &ChatSession) -> Vec<(&FileEdit, i64)> {
+    session
+        .messages
+        .iter()
+        .flat_map(|message| {
+            let when = message.timestamp.unwrap_or(session.last_updated_at);
+            message.edits.iter().map(move |edit| (edit, when))
+        })
+        .collect()
+}
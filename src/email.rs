@@ -0,0 +1,297 @@
+use anyhow::{bail, Context, Result};
+use chrono::{Duration, Utc};
+use std::fs;
+use std::path::Path;
+
+use crate::config::Config;
+use crate::i18n::Strings;
+use crate::notify::{Digest, NotifyState};
+use crate::ComposerData;
+
+/// How far back the email digest goes unless told otherwise: a week, so
+/// it can go out from a weekly cron job.
+pub const DEFAULT_DAYS: i64 = 7;
+
+/// The HTML body, unless EMAIL_HTML_TEMPLATE names one of your own.
+pub const HTML_TEMPLATE: &str = r#"<!DOCTYPE html>
+<html>
+<body style="font-family: -apple-system, 'Segoe UI', sans-serif; line-height: 1.5; color: #222; max-width: 40rem;">
+<h2 style="margin-bottom: 0;">{headline}</h2>
+<p style="color: #666; margin-top: 0.25rem;">{period}</p>
+{sessions}
+{topics}
+{link}
+</body>
+</html>
+"#;
+
+/// The plain-text body, unless EMAIL_TEXT_TEMPLATE names one of your own.
+pub const TEXT_TEMPLATE: &str = "{headline}\n{period}\n\n{sessions}\n\n{topics}\n\n{link}\n";
+
+/// How the connection to the SMTP server is secured.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SmtpSecurity {
+    /// Connect in the clear and upgrade with STARTTLS (port 587, the default)
+    #[default]
+    StartTls,
+    /// TLS from the start (port 465)
+    Tls,
+    /// No encryption at all, for a relay on the same machine
+    None,
+}
+
+impl SmtpSecurity {
+    /// Parse an `SMTP_SECURITY` value.
+    pub fn parse(name: &str) -> Result<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "" | "starttls" => Ok(SmtpSecurity::StartTls),
+            "tls" | "ssl" => Ok(SmtpSecurity::Tls),
+            "none" => Ok(SmtpSecurity::None),
+            other => bail!("Unknown SMTP security '{}' (expected starttls, tls or none)", other),
+        }
+    }
+}
+
+/// The email digest's templates: the subject, and the HTML and plain-text
+/// bodies. Each can use `{project}`, `{count}` (like "5 sessions"),
+/// `{period}`, `{headline}`, `{sessions}` (the list of them), `{topics}`
+/// and `{link}`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmailTemplates {
+    /// The subject line
+    pub subject: String,
+    /// The HTML body
+    pub html: String,
+    /// The plain-text body
+    pub text: String,
+}
+
+impl EmailTemplates {
+    /// The templates EMAIL_SUBJECT, EMAIL_HTML_TEMPLATE and
+    /// EMAIL_TEXT_TEMPLATE give, with the built-in ones for the bodies
+    /// that aren't set.
+    pub fn from_config(config: &Config) -> Result<Self> {
+        let read = |setting: &str, path: &str, default: &str| -> Result<String> {
+            if path.trim().is_empty() {
+                return Ok(default.to_string());
+            }
+            let path = Path::new(&config.project_path).join(path.trim());
+            fs::read_to_string(&path).with_context(|| format!("Could not read {} ({})", setting, path.display()))
+        };
+        Ok(EmailTemplates {
+            subject: config.email_subject.clone(),
+            html: read("EMAIL_HTML_TEMPLATE", &config.email_html_template, HTML_TEMPLATE)?,
+            text: read("EMAIL_TEXT_TEMPLATE", &config.email_text_template, TEXT_TEMPLATE)?,
+        })
+    }
+}
+
+/// An email digest of the sessions active in the last few days, ready to
+/// send: a subject, and the same digest as HTML and as plain text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmailDigest {
+    /// The subject line
+    pub subject: String,
+    /// The plain-text body
+    pub text: String,
+    /// The HTML body
+    pub html: String,
+}
+
+impl EmailDigest {
+    /// Put together a digest of the sessions active in the last `days`
+    /// days, or `None` if there weren't any.
+    pub fn build(
+        config: &Config,
+        strings: &Strings,
+        templates: &EmailTemplates,
+        sessions: &[ComposerData],
+        days: i64,
+    ) -> Option<Self> {
+        let zone = config.display_zone();
+        let since = (Utc::now() - Duration::days(days)).timestamp_millis();
+        // Sessions quiet since then count as announced, so the digest leaves them out
+        let earlier = NotifyState {
+            announced: sessions
+                .iter()
+                .flat_map(|data| &data.all_composers)
+                .filter(|session| session.last_updated_at.max(session.created_at) < since)
+                .map(|session| session.composer_id.clone())
+                .collect(),
+        };
+        let links = Some(config.notify_lore_url.trim().to_string())
+            .filter(|url| !url.is_empty())
+            .map(|url| (strings.get("notify.read").to_string(), url))
+            .into_iter()
+            .collect();
+        let digest = Digest::build(&config.project_name, sessions, &earlier, links);
+        if digest.is_empty() {
+            return None;
+        }
+
+        let count = strings.count("count.sessions", digest.new_sessions.len());
+        let period = format!(
+            "{} – {}",
+            zone.format_millis(since, "%B %d, %Y").unwrap_or_default(),
+            zone.format_now("%B %d, %Y")
+        );
+        let headline = strings.format("notify.new_sessions", &[("project", &config.project_name), ("sessions", &count)]);
+        let names: Vec<String> = digest.new_sessions.iter().map(|(_, name)| config.redact_secrets(name)).collect();
+        let topics: Vec<String> = digest.topics.iter().map(|(topic, count)| format!("{} ({})", topic, count)).collect();
+        let topics_label = strings.get("notify.topics");
+
+        let text_values = [
+            ("sessions", names.iter().map(|name| format!("• {}", name)).collect::<Vec<_>>().join("\n")),
+            ("topics", if topics.is_empty() { String::new() } else { format!("{}: {}", topics_label, topics.join(", ")) }),
+            ("link", digest.links.iter().map(|(label, url)| format!("{}: {}", label, url)).collect::<Vec<_>>().join("\n")),
+        ];
+        let html_values = [
+            ("sessions", format!("<ul>\n{}</ul>", names.iter().map(|name| format!("<li>{}</li>\n", escape(name))).collect::<String>())),
+            (
+                "topics",
+                if topics.is_empty() { String::new() } else { format!("<p><strong>{}</strong>: {}</p>", escape(topics_label), escape(&topics.join(", "))) },
+            ),
+            (
+                "link",
+                digest
+                    .links
+                    .iter()
+                    .map(|(label, url)| format!("<p><a href=\"{}\">{}</a></p>", escape(url), escape(label)))
+                    .collect(),
+            ),
+        ];
+        let common = [("project", config.project_name.clone()), ("count", count), ("period", period), ("headline", headline)];
+        let html_common: Vec<(&str, String)> = common.iter().map(|(key, value)| (*key, escape(value))).collect();
+
+        let text = fill(&templates.text, common.iter().cloned().chain(text_values));
+        Some(EmailDigest {
+            subject: fill(&templates.subject, common.iter().cloned()).lines().next().unwrap_or_default().trim().to_string(),
+            text: tidy(&text),
+            html: fill(&templates.html, html_common.into_iter().chain(html_values)),
+        })
+    }
+}
+
+/// The recipients in EMAIL_TO.
+pub fn recipients(config: &Config) -> Vec<String> {
+    config
+        .email_to
+        .split(',')
+        .map(|address| address.trim().to_string())
+        .filter(|address| !address.is_empty())
+        .collect()
+}
+
+/// The digest as a MIME message from EMAIL_FROM to EMAIL_TO, with the
+/// plain text and the HTML as alternatives - what `send` sends, and what
+/// `--dry-run` writes out as an `.eml` file.
+#[cfg(feature = "email")]
+pub fn message(config: &Config, digest: &EmailDigest) -> Result<Vec<u8>> {
+    Ok(smtp::message(config, digest)?.formatted())
+}
+
+#[cfg(not(feature = "email"))]
+pub fn message(config: &Config, digest: &EmailDigest) -> Result<Vec<u8>> {
+    let _ = (config, digest);
+    bail!("The email digest needs the `email` feature (cargo install --features email)")
+}
+
+/// Send the digest through SMTP_HOST.
+#[cfg(feature = "email")]
+pub async fn send(config: &Config, digest: &EmailDigest) -> Result<()> {
+    smtp::send(config, smtp::message(config, digest)?).await
+}
+
+#[cfg(not(feature = "email"))]
+pub async fn send(config: &Config, digest: &EmailDigest) -> Result<()> {
+    let _ = (config, digest);
+    bail!("The email digest needs the `email` feature (cargo install --features email)")
+}
+
+#[cfg(feature = "email")]
+mod smtp {
+    use anyhow::{anyhow, bail, Context, Result};
+    use lettre::message::{Mailbox, Message, MultiPart};
+    use lettre::transport::smtp::authentication::Credentials;
+    use lettre::{AsyncSmtpTransport, AsyncTransport, Tokio1Executor};
+
+    use super::{recipients, EmailDigest, SmtpSecurity};
+    use crate::config::Config;
+
+    pub fn message(config: &Config, digest: &EmailDigest) -> Result<Message> {
+        let mailbox = |address: &str| -> Result<Mailbox> {
+            address.parse().map_err(|error| anyhow!("'{}' isn't an email address: {}", address, error))
+        };
+        if config.email_from.trim().is_empty() {
+            bail!("Set EMAIL_FROM to send the email digest");
+        }
+        let to = recipients(config);
+        if to.is_empty() {
+            bail!("Set EMAIL_TO to who should get the email digest");
+        }
+        let mut builder = Message::builder()
+            .from(mailbox(config.email_from.trim())?)
+            .subject(digest.subject.as_str())
+            .message_id(None);
+        for address in &to {
+            builder = builder.to(mailbox(address)?);
+        }
+        builder
+            .multipart(MultiPart::alternative_plain_html(digest.text.clone(), digest.html.clone()))
+            .context("Could not put the email together")
+    }
+
+    pub async fn send(config: &Config, message: Message) -> Result<()> {
+        let host = config.smtp_host.trim();
+        if host.is_empty() {
+            bail!("Set SMTP_HOST to send the email digest");
+        }
+        let mut transport = match SmtpSecurity::parse(&config.smtp_security)? {
+            SmtpSecurity::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)?,
+            SmtpSecurity::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(host)?,
+            SmtpSecurity::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host),
+        }
+        .port(config.smtp_port);
+        if !config.smtp_username.is_empty() {
+            transport = transport.credentials(Credentials::new(config.smtp_username.clone(), config.smtp_password.clone()));
+        }
+        transport
+            .build()
+            .send(message)
+            .await
+            .with_context(|| format!("Could not send the email digest through {}", host))?;
+        Ok(())
+    }
+}
+
+/// Fill `{key}` placeholders in a template. Unknown placeholders are left
+/// as they are.
+fn fill(template: &str, values: impl IntoIterator<Item = (&'static str, String)>) -> String {
+    let mut filled = template.to_string();
+    for (key, value) in values {
+        filled = filled.replace(&format!("{{{}}}", key), &value);
+    }
+    filled
+}
+
+/// Plain text without the blank lines empty placeholders leave behind.
+fn tidy(text: &str) -> String {
+    let mut tidied = String::new();
+    for line in text.trim().lines() {
+        if line.trim().is_empty() && (tidied.is_empty() || tidied.ends_with("\n\n")) {
+            continue;
+        }
+        tidied.push_str(line.trim_end());
+        tidied.push('\n');
+    }
+    tidied.trim_end().to_string() + "\n"
+}
+
+/// Escape text for HTML.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
@@ -0,0 +1,154 @@
+use anyhow::{bail, Result};
+use std::path::{Path, PathBuf};
+
+use crate::config::Config;
+
+/// The extension added to encrypted files (`lore.md` becomes `lore.md.age`).
+pub const ENCRYPTED_EXTENSION: &str = "age";
+
+/// Who can read the encrypted lore: anyone holding the key for one of a
+/// list of age recipients, or anyone who knows a passphrase. age doesn't
+/// allow both on the same file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EncryptionKey {
+    /// age public keys (`age1...`)
+    Recipients(Vec<String>),
+    /// A passphrase
+    Passphrase(String),
+}
+
+impl EncryptionKey {
+    /// The key from ENCRYPT_RECIPIENTS (comma-separated) or
+    /// ENCRYPT_PASSPHRASE, checking the recipients parse.
+    pub fn from_config(config: &Config) -> Result<Self> {
+        let recipients: Vec<String> = config
+            .encrypt_recipients
+            .split(',')
+            .map(|recipient| recipient.trim().to_string())
+            .filter(|recipient| !recipient.is_empty())
+            .collect();
+        let passphrase = config.encrypt_passphrase.as_str();
+        let key = match (recipients.is_empty(), passphrase.is_empty()) {
+            (false, false) => {
+                bail!("Set ENCRYPT_RECIPIENTS or ENCRYPT_PASSPHRASE, not both (age can't mix them on one file)")
+            }
+            (true, true) => bail!("Encrypting the lore needs ENCRYPT_RECIPIENTS or ENCRYPT_PASSPHRASE"),
+            (false, true) => EncryptionKey::Recipients(recipients),
+            (true, false) => EncryptionKey::Passphrase(passphrase.to_string()),
+        };
+        key.check()?;
+        Ok(key)
+    }
+
+    #[cfg(feature = "encrypt")]
+    fn check(&self) -> Result<()> {
+        if let EncryptionKey::Recipients(recipients) = self {
+            age_encryption::recipients(recipients)?;
+        }
+        Ok(())
+    }
+
+    #[cfg(not(feature = "encrypt"))]
+    fn check(&self) -> Result<()> {
+        bail!("Encrypting the lore needs the `encrypt` feature (cargo install --features encrypt)")
+    }
+}
+
+/// A file name with the encrypted extension added.
+pub fn encrypted_name(name: &str) -> String {
+    format!("{}.{}", name, ENCRYPTED_EXTENSION)
+}
+
+/// Where to write a decrypted file: the encrypted one without its `.age`.
+pub fn decrypted_path(path: &Path) -> Option<PathBuf> {
+    (path.extension()? == ENCRYPTED_EXTENSION).then(|| path.with_extension(""))
+}
+
+/// Encrypt `data`, ASCII-armored so the encrypted lore is still a text
+/// file git can store and diff sensibly.
+pub fn encrypt(data: &[u8], key: &EncryptionKey) -> Result<Vec<u8>> {
+    #[cfg(feature = "encrypt")]
+    return age_encryption::encrypt(data, key);
+    #[cfg(not(feature = "encrypt"))]
+    {
+        let _ = (data, key);
+        bail!("Encrypting the lore needs the `encrypt` feature (cargo install --features encrypt)")
+    }
+}
+
+/// Decrypt an age file (armored or not) with the identities in
+/// `identity_files`, or with a passphrase.
+pub fn decrypt(data: &[u8], identity_files: &[PathBuf], passphrase: Option<&str>) -> Result<Vec<u8>> {
+    #[cfg(feature = "encrypt")]
+    return age_encryption::decrypt(data, identity_files, passphrase);
+    #[cfg(not(feature = "encrypt"))]
+    {
+        let _ = (data, identity_files, passphrase);
+        bail!("Decrypting needs the `encrypt` feature (cargo install --features encrypt)")
+    }
+}
+
+#[cfg(feature = "encrypt")]
+mod age_encryption {
+    use age::armor::{ArmoredReader, ArmoredWriter, Format};
+    use age::secrecy::SecretString;
+    use age::{Decryptor, Encryptor, Identity, IdentityFile, Recipient};
+    use anyhow::{anyhow, bail, Context, Result};
+    use std::io::{Read, Write};
+    use std::path::PathBuf;
+
+    use super::EncryptionKey;
+
+    pub(super) fn recipients(recipients: &[String]) -> Result<Vec<age::x25519::Recipient>> {
+        recipients
+            .iter()
+            .map(|recipient| {
+                recipient
+                    .parse::<age::x25519::Recipient>()
+                    .map_err(|error| anyhow!("'{}' isn't an age recipient: {}", recipient, error))
+            })
+            .collect()
+    }
+
+    pub(super) fn encrypt(data: &[u8], key: &EncryptionKey) -> Result<Vec<u8>> {
+        let encryptor = match key {
+            EncryptionKey::Recipients(list) => {
+                let recipients = recipients(list)?;
+                Encryptor::with_recipients(recipients.iter().map(|recipient| recipient as &dyn Recipient))?
+            }
+            EncryptionKey::Passphrase(passphrase) => {
+                Encryptor::with_user_passphrase(SecretString::from(passphrase.clone()))
+            }
+        };
+
+        let mut encrypted = Vec::with_capacity(data.len());
+        let armor = ArmoredWriter::wrap_output(&mut encrypted, Format::AsciiArmor)?;
+        let mut writer = encryptor.wrap_output(armor)?;
+        writer.write_all(data)?;
+        writer.finish()?.finish()?;
+        Ok(encrypted)
+    }
+
+    pub(super) fn decrypt(data: &[u8], identity_files: &[PathBuf], passphrase: Option<&str>) -> Result<Vec<u8>> {
+        let mut identities: Vec<Box<dyn Identity>> = Vec::new();
+        for path in identity_files {
+            let file = IdentityFile::from_file(path.to_string_lossy().to_string())
+                .with_context(|| format!("Could not read identity file {}", path.display()))?;
+            identities.extend(file.into_identities()?);
+        }
+        if let Some(passphrase) = passphrase {
+            identities.push(Box::new(age::scrypt::Identity::new(SecretString::from(passphrase.to_string()))));
+        }
+        if identities.is_empty() {
+            bail!("Decrypting needs an identity file (--identity) or ENCRYPT_PASSPHRASE");
+        }
+
+        let decryptor = Decryptor::new_buffered(ArmoredReader::new(data)).context("This isn't an age file")?;
+        let mut reader = decryptor
+            .decrypt(identities.iter().map(|identity| identity.as_ref()))
+            .context("Could not decrypt - wrong identity or passphrase?")?;
+        let mut decrypted = Vec::new();
+        reader.read_to_end(&mut decrypted)?;
+        Ok(decrypted)
+    }
+}
@@ -1,111 +1,606 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+#[cfg(feature = "native")]
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use sqlx::{SqlitePool, Row};
+use serde_json::Value;
+#[cfg(feature = "native")]
+use std::collections::BTreeMap;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+#[cfg(feature = "native")]
+use std::sync::Arc;
 
+#[cfg(feature = "native")]
+use crate::checkpoint::Checkpoint;
 use crate::config::Config;
-use crate::{ChatGeneration, ChatPrompt, ComposerData};
+use crate::edits::{edit_from_code_block_diff, edit_from_tool_call};
+#[cfg(feature = "native")]
+use crate::importers::ImportedHistory;
+#[cfg(feature = "native")]
+use crate::progress::{Progress, Stage};
+use crate::parsing::{parse_values, summarize_skipped, Parsed};
+#[cfg(feature = "native")]
+use crate::parsing::{parse_array_reader, parse_array_with};
+use crate::schema::{migrate, FieldMapping, RecordKind};
+#[cfg(feature = "native")]
+use crate::schema::{fingerprint, SchemaReport};
+use crate::references::{uri_to_path, whole_file};
+#[cfg(feature = "native")]
+use crate::store::{stream_value, ConnectionOptions, RecordStore, SqliteStore, CHUNK_BYTES};
+#[cfg(feature = "native")]
+use crate::timestamps::{normalize_generations, normalize_sessions};
+use crate::tools::tool_call;
+#[cfg(feature = "native")]
+use crate::workspace_info::{WorkspaceInfo, EDITOR_STATE_KEY, HISTORY_KEY};
+#[cfg(feature = "native")]
+use crate::ComposerData;
+use crate::{
+    ChatGeneration, ChatMessage, ChatPrompt, ChatSession, FileReference, MessageRole, TokenUsage, ToolCall,
+};
 
 /// The ChatExtractor is responsible for pulling data out of the SQLite database.
 /// Think of it as our "data miner" - it knows how to connect to the database
 /// and extract all the chat-related information we need.
+#[cfg(feature = "native")]
 pub struct ChatExtractor {
-    /// Our connection to the SQLite database
-    pool: SqlitePool,
+    /// Where the raw records live (normally Cursor's SQLite `ItemTable`)
+    store: Box<dyn RecordStore>,
+    /// Where full conversations live, if that's somewhere other than `store`
+    /// (Cursor keeps them in the global storage database)
+    conversation_store: Option<Box<dyn RecordStore>>,
+    /// Cursor's global (cross-workspace) `ItemTable`, for prompts and
+    /// generations that never made it into the workspace database
+    global_store: Option<Box<dyn RecordStore>>,
+    /// Where each transcript is saved as it's read, so an interrupted run
+    /// doesn't have to read it again
+    checkpoint: Option<Arc<Checkpoint>>,
+    /// Who to tell how the extraction's going, and whether to stop
+    progress: Progress,
     /// Configuration settings that tell us what to look for
     config: Config,
 }
 
+#[cfg(feature = "native")]
 impl ChatExtractor {
     /// Create a new ChatExtractor and connect to the database.
     /// This is where we establish our connection to the SQLite database
     /// so we can start pulling out chat data.
     pub async fn new(config: &Config) -> Result<Self> {
-        // Build the database URL that SQLx needs to connect
-        let database_url = format!("sqlite:{}", config.database_path());
-        let pool = SqlitePool::connect(&database_url).await?;
+        let options = ConnectionOptions::from_config(config);
+        let store = SqliteStore::open_with(&config.database_path(), "ItemTable", &options).await?;
+        let mut extractor = ChatExtractor::with_store(config, Box::new(store));
         
-        Ok(ChatExtractor {
-            pool,
+        // The global database is a bonus - without it we still have the session
+        // list and workspace prompts, just no transcripts or cross-workspace data
+        let global_path = config.global_database_path();
+        if Path::new(&global_path).is_file() {
+            let global = SqliteStore::open_with(&global_path, &config.conversation_table, &options).await?;
+            if config.include_global_storage {
+                extractor = extractor.with_global_store(Box::new(global.with_table("ItemTable")?));
+            }
+            extractor = extractor.with_conversation_store(Box::new(global));
+        }
+        
+        Ok(extractor)
+    }
+    
+    /// Create a ChatExtractor that reads from any record store.
+    /// Useful for tests (with a `MemoryStore`) and for sources that lay
+    /// their data out like Cursor but somewhere else.
+    pub fn with_store(config: &Config, store: Box<dyn RecordStore>) -> Self {
+        ChatExtractor {
+            store,
+            conversation_store: None,
+            global_store: None,
+            checkpoint: None,
+            progress: Progress::default(),
             config: config.clone(),
-        })
+        }
+    }
+    
+    /// Read full conversations from a separate store instead of the main one.
+    pub fn with_conversation_store(mut self, store: Box<dyn RecordStore>) -> Self {
+        self.conversation_store = Some(store);
+        self
+    }
+    
+    /// Also read prompts and generations from a global store, merging them
+    /// with the workspace's own records.
+    pub fn with_global_store(mut self, store: Box<dyn RecordStore>) -> Self {
+        self.global_store = Some(store);
+        self
+    }
+    
+    /// Save each transcript to a checkpoint as it's read, and take the
+    /// ones it already has from there instead of the database.
+    pub fn with_checkpoint(mut self, checkpoint: Arc<Checkpoint>) -> Self {
+        self.checkpoint = Some(checkpoint);
+        self
+    }
+    
+    /// Report how the extraction is going, transcript by transcript, and
+    /// give up with `Cancelled` between transcripts if asked to.
+    pub fn with_progress(mut self, progress: Progress) -> Self {
+        self.progress = progress;
+        self
     }
     
     /// Extract all the chat sessions from the database.
     /// This pulls out the main session data that tells us about each
     /// conversation that happened in the chat application.
     pub async fn extract_sessions(&self) -> Result<Vec<ComposerData>> {
-        // Query the database for the composer data (this contains session info)
-        let query = format!(
-            "SELECT value FROM ItemTable WHERE key = '{}'",
-            self.config.composer_data_key
-        );
+        // Look up the composer data (this contains session info) and parse it
+        let json_str = self.store.require_key(&self.config.composer_data_key).await?;
+        Ok(warn_skipped(self.parse_sessions(&json_str).await?))
+    }
+    
+    /// Extract sessions, generations and prompts in one go.
+    /// All three top-level records are fetched with a single query (plus one
+    /// for global storage) rather than a round-trip each, which is noticeably
+    /// faster on network filesystems and when aggregating many workspaces.
+    /// Values over LARGE_VALUE_MB are left out of that query and streamed.
+    pub async fn extract_all(&self) -> Result<ImportedHistory> {
+        let keys = [
+            self.config.composer_data_key.as_str(),
+            self.config.generations_key.as_str(),
+            self.config.prompts_key.as_str(),
+        ];
+        let mut records = self.store.get_keys_within(&keys, self.large_value_bytes()).await?;
+        let mut global_records = match &self.global_store {
+            Some(global) => global.get_keys_within(&keys[1..], self.large_value_bytes()).await?,
+            None => Default::default(),
+        };
+        
+        let mut history = ImportedHistory::default();
+        let json_str = match records.remove(keys[0]) {
+            Some(json_str) => json_str,
+            None => self.store.require_key(keys[0]).await?,
+        };
+        let parsed = self.parse_sessions(&json_str).await?;
+        history.sessions = parsed.records;
+        history.skipped.extend(parsed.skipped);
         
-        let row = sqlx::query(&query)
-            .fetch_one(&self.pool)
+        self.progress.start(Stage::Records, Some(2));
+        self.progress.check()?;
+        let mut parsed = self
+            .read_list(keys[1], RecordKind::Generation, merge_generations, &mut records, &mut global_records)
             .await?;
+        normalize_generations(&mut parsed.records, self.config.timestamp_unit());
+        history.generations = parsed.records;
+        history.skipped.extend(parsed.skipped);
+        self.progress.advance(Stage::Records, 1);
+        
+        self.progress.check()?;
+        let parsed = self
+            .read_list(keys[2], RecordKind::Prompt, merge_prompts, &mut records, &mut global_records)
+            .await?;
+        history.prompts = parsed.records;
+        history.skipped.extend(parsed.skipped);
+        self.progress.advance(Stage::Records, 2);
+        self.progress.finish(Stage::Records);
+        
+        history.workspace = Some(self.workspace_info().await?).filter(|info| !info.is_empty());
+        Ok(history)
+    }
+    
+    /// What the workspace had open: the folder from workspace.json (next
+    /// to the workspace database) and the editor tabs and history in its
+    /// `ItemTable`.
+    pub async fn workspace_info(&self) -> Result<WorkspaceInfo> {
+        let workspace_json = workspace_json(&self.config);
+        let history = self.store.get_key(HISTORY_KEY).await?;
+        let editor_state = self.store.get_key(EDITOR_STATE_KEY).await?;
+        Ok(WorkspaceInfo::from_records(workspace_json.as_deref(), history.as_deref(), editor_state.as_deref()))
+    }
+    
+    /// Parse the composer data record and fill in each session's transcript.
+    /// Sessions are parsed one by one so a single odd entry is skipped
+    /// rather than losing the whole list.
+    async fn parse_sessions(&self, json_str: &str) -> Result<Parsed<ComposerData>> {
+        let Parsed { records, skipped } = parse_session_list(json_str, &self.config.record_field_mapping())?;
+        let mut composer_data = ComposerData { all_composers: records };
         
-        // Parse the JSON data we got from the database
-        let json_str: String = row.get(0);
-        let composer_data: ComposerData = serde_json::from_str(&json_str)?;
+        // Fill in each session's transcript wherever Cursor kept one
+        self.progress.start(Stage::Transcripts, Some(composer_data.all_composers.len()));
+        for (done, session) in composer_data.all_composers.iter_mut().enumerate() {
+            self.progress.check()?;
+            let saved = self.checkpoint.as_ref().and_then(|checkpoint| checkpoint.transcript(&session.composer_id));
+            match saved {
+                Some(messages) => session.messages = messages,
+                None => {
+                    session.messages = self.extract_conversation(session).await?;
+                    if let Some(checkpoint) = &self.checkpoint {
+                        checkpoint.save_transcript(&session.composer_id, &session.messages)?;
+                    }
+                }
+            }
+            self.progress.advance(Stage::Transcripts, done + 1);
+        }
+        self.progress.finish(Stage::Transcripts);
+        normalize_sessions(&mut composer_data.all_composers, self.config.timestamp_unit());
         
-        Ok(vec![composer_data])
+        Ok(Parsed {
+            records: vec![composer_data],
+            skipped,
+        })
+    }
+    
+    /// Extract the messages of a single session.
+    /// Older Cursor versions keep the whole conversation inline under
+    /// `composerData:<id>`; newer ones only keep headers there and store each
+    /// message ("bubble") under its own `bubbleId:<id>:<bubble>` key.
+    async fn extract_conversation(&self, session: &ChatSession) -> Result<Vec<ChatMessage>> {
+        let store = self.conversation_store.as_deref().unwrap_or(self.store.as_ref());
+        
+        let key = format!("composerData:{}", session.composer_id);
+        let Some(json_str) = store.get_key(&key).await? else {
+            return Ok(Vec::new());
+        };
+        let composer: Value = serde_json::from_str(&json_str)?;
+        
+        // Bubbles and code block diffs each live under a key of their own
+        let mut records = HashMap::new();
+        for key in conversation_keys(&session.composer_id, &composer) {
+            if let Some(value) = store.get_key(&key).await? {
+                records.insert(key, value);
+            }
+        }
+        assemble_conversation(&session.composer_id, &composer, &records)
     }
     
     /// Extract all the generation data from the database.
     /// This contains information about what the AI generated during conversations.
+    /// When global storage is available its generations are merged in too,
+    /// skipping any the workspace database already had.
     pub async fn extract_generations(&self) -> Result<Vec<ChatGeneration>> {
-        let query = format!(
-            "SELECT value FROM ItemTable WHERE key = '{}'",
-            self.config.generations_key
-        );
-        
-        let row = sqlx::query(&query)
-            .fetch_one(&self.pool)
+        let key = self.config.generations_key.as_str();
+        let (mut records, mut global_records) = self.fetch_list(key).await?;
+        let mut parsed = self
+            .read_list(key, RecordKind::Generation, merge_generations, &mut records, &mut global_records)
             .await?;
-        
-        let json_str: String = row.get(0);
-        let generations: Vec<ChatGeneration> = serde_json::from_str(&json_str)?;
-        
-        Ok(generations)
+        normalize_generations(&mut parsed.records, self.config.timestamp_unit());
+        Ok(warn_skipped(parsed))
     }
     
     /// Extract all the prompt data from the database.
     /// This contains the user's input prompts that started each conversation.
+    /// Like generations, global prompts are merged in and deduplicated.
     pub async fn extract_prompts(&self) -> Result<Vec<ChatPrompt>> {
-        let query = format!(
-            "SELECT value FROM ItemTable WHERE key = '{}'",
-            self.config.prompts_key
-        );
-        
-        let row = sqlx::query(&query)
-            .fetch_one(&self.pool)
+        let key = self.config.prompts_key.as_str();
+        let (mut records, mut global_records) = self.fetch_list(key).await?;
+        let parsed = self
+            .read_list(key, RecordKind::Prompt, merge_prompts, &mut records, &mut global_records)
             .await?;
-        
-        let json_str: String = row.get(0);
-        let prompts: Vec<ChatPrompt> = serde_json::from_str(&json_str)?;
-        
-        Ok(prompts)
+        Ok(warn_skipped(parsed))
+    }
+    
+    /// The biggest a value can be before it's streamed rather than fetched.
+    fn large_value_bytes(&self) -> u64 {
+        self.config.large_value_mb.saturating_mul(1024 * 1024)
+    }
+    
+    /// Fetch a record list from the workspace and global storage, unless
+    /// it's big enough to stream.
+    async fn fetch_list(&self, key: &str) -> Result<(BTreeMap<String, String>, BTreeMap<String, String>)> {
+        let records = self.store.get_keys_within(&[key], self.large_value_bytes()).await?;
+        let global_records = match &self.global_store {
+            Some(global) => global.get_keys_within(&[key], self.large_value_bytes()).await?,
+            None => BTreeMap::new(),
+        };
+        Ok((records, global_records))
+    }
+    
+    /// Parse a list of generations or prompts, merging in global storage's
+    /// if we have it. It's fine for the key to be missing there - not every
+    /// Cursor version writes AI service data globally. Lists that weren't
+    /// fetched into `records` and `global_records` are streamed.
+    async fn read_list<T: DeserializeOwned + Send + 'static>(
+        &self,
+        key: &str,
+        kind: RecordKind,
+        merge: fn(Vec<T>, Vec<T>) -> Vec<T>,
+        records: &mut BTreeMap<String, String>,
+        global_records: &mut BTreeMap<String, String>,
+    ) -> Result<Parsed<T>> {
+        let name = match kind {
+            RecordKind::Prompt => "prompt",
+            _ => "generation",
+        };
+        let mapping = self.config.record_field_mapping();
+        let mut parsed = read_records(self.store.as_ref(), key, name.to_string(), kind, mapping.clone(), records)
+            .await?
+            .ok_or_else(|| anyhow!("Key '{}' not found in {}", key, self.store.location()))?;
+        if let Some(global) = &self.global_store {
+            let global_name = format!("global {}", name);
+            if let Some(global) = read_records(global.as_ref(), key, global_name, kind, mapping, global_records).await? {
+                parsed.records = merge(parsed.records, global.records);
+                parsed.skipped.extend(global.skipped);
+            }
+        }
+        Ok(parsed)
+    }
+    
+    /// Fingerprint the database layout, for the `schema-report` subcommand.
+    pub async fn schema_report(&self) -> Result<SchemaReport> {
+        fingerprint(&self.config, self.store.as_ref(), self.conversation_store.as_deref()).await
     }
     
     pub async fn get_database_info(&self) -> Result<DatabaseInfo> {
-        let tables_query = "SELECT name FROM sqlite_master WHERE type='table'";
-        let tables: Vec<String> = sqlx::query_scalar(tables_query)
-            .fetch_all(&self.pool)
-            .await?;
-        
-        let item_count_query = "SELECT COUNT(*) FROM ItemTable";
-        let item_count: i64 = sqlx::query_scalar(item_count_query)
-            .fetch_one(&self.pool)
-            .await?;
-        
         Ok(DatabaseInfo {
-            tables,
-            item_count,
-            database_path: self.config.sanitize_path(&self.config.database_path()),
+            tables: self.store.tables().await?,
+            item_count: self.store.count().await?,
+            database_path: self.config.sanitize_path(&self.store.location()),
         })
     }
 }
 
+/// Parse the composer data record's list of sessions, without their
+/// transcripts. Sessions are parsed one by one so a single odd entry is
+/// skipped rather than losing the whole list.
+pub(crate) fn parse_session_list(json_str: &str, mapping: &FieldMapping) -> Result<Parsed<ChatSession>> {
+    let mut value: Value = serde_json::from_str(json_str)?;
+    migrate(RecordKind::ComposerData, &mut value, mapping);
+    // Anything but an object (a list, a string...) has no list to take
+    let Some(Value::Array(mut composers)) = value.as_object_mut().and_then(|fields| fields.remove("all_composers")) else {
+        return Err(anyhow!("The composer data has no 'all_composers' list"));
+    };
+    composers.iter_mut().for_each(|composer| migrate(RecordKind::Session, composer, mapping));
+    Ok(parse_values(composers, "session"))
+}
+
+/// The keys a session's conversation needs besides its `composerData:<id>`
+/// record: one per bubble when only headers are kept inline, and one per
+/// code block diff.
+pub(crate) fn conversation_keys(session_id: &str, composer: &Value) -> Vec<String> {
+    let mut keys = Vec::new();
+    if composer["conversation"].as_array().is_none() {
+        for header in composer["fullConversationHeadersOnly"].as_array().into_iter().flatten() {
+            if let Some(bubble_id) = header["bubbleId"].as_str() {
+                keys.push(format!("bubbleId:{}:{}", session_id, bubble_id));
+            }
+        }
+    }
+    for (_, blocks) in composer["codeBlockData"].as_object().into_iter().flatten() {
+        for block in code_blocks(blocks) {
+            if let Some(diff_id) = block["diffId"].as_str() {
+                keys.push(format!("codeBlockDiff:{}:{}", session_id, diff_id));
+            }
+        }
+    }
+    keys
+}
+
+/// Put a session's messages together from its `composerData:<id>` record
+/// and the records `conversation_keys` named (the ones that exist).
+/// Older Cursor versions keep the whole conversation inline; newer ones
+/// only keep headers there and store each message ("bubble") separately.
+pub(crate) fn assemble_conversation(
+    session_id: &str,
+    composer: &Value,
+    records: &HashMap<String, String>,
+) -> Result<Vec<ChatMessage>> {
+    // Headers tell us the order; the bubbles themselves live under their own keys
+    let bubbles = match composer["conversation"].as_array() {
+        Some(conversation) => conversation.clone(),
+        None => {
+            let mut bubbles = Vec::new();
+            for header in composer["fullConversationHeadersOnly"].as_array().into_iter().flatten() {
+                let Some(bubble_id) = header["bubbleId"].as_str() else {
+                    continue;
+                };
+                if let Some(bubble_str) = records.get(&format!("bubbleId:{}:{}", session_id, bubble_id)) {
+                    bubbles.push(serde_json::from_str(bubble_str)?);
+                }
+            }
+            bubbles
+        }
+    };
+    
+    // The session's model setting is our fallback for bubbles that don't name one
+    let session_model = composer["modelConfig"]["modelName"].as_str();
+    
+    let mut messages = Vec::new();
+    let mut bubble_ids = Vec::new();
+    for bubble in &bubbles {
+        if let Some(mut message) = parse_bubble(bubble) {
+            if message.role == MessageRole::Assistant && message.model.is_none() {
+                message.model = session_model.map(str::to_string);
+            }
+            bubble_ids.push(bubble["bubbleId"].as_str().unwrap_or_default().to_string());
+            messages.push(message);
+        }
+    }
+    
+    // Edits applied through code blocks keep their diffs under separate
+    // `codeBlockDiff:<id>:<diff>` keys, indexed from `codeBlockData`
+    for (uri, blocks) in composer["codeBlockData"].as_object().into_iter().flatten() {
+        for block in code_blocks(blocks) {
+            let Some(diff_id) = block["diffId"].as_str() else {
+                continue;
+            };
+            let Some(diff_str) = records.get(&format!("codeBlockDiff:{}:{}", session_id, diff_id)) else {
+                continue;
+            };
+            let diff: Value = serde_json::from_str(diff_str)?;
+            let path = uri_to_path(&block["uri"])
+                .or_else(|| uri_to_path(&Value::String(uri.clone())))
+                .unwrap_or_else(|| uri.clone());
+            let edit = edit_from_code_block_diff(&path, &diff, block["status"].as_str());
+            
+            // Hang the edit on the bubble that proposed it, or the last one if we can't tell
+            let position = block["bubbleId"]
+                .as_str()
+                .and_then(|id| bubble_ids.iter().position(|bubble_id| bubble_id == id))
+                .or(messages.len().checked_sub(1));
+            if let Some(position) = position {
+                messages[position].edits.push(edit);
+            }
+        }
+    }
+    
+    Ok(messages)
+}
+
+/// The blocks listed for one file in `codeBlockData`, which Cursor keeps
+/// as a list or as an object keyed by block ID.
+fn code_blocks(blocks: &Value) -> Vec<&Value> {
+    match blocks {
+        Value::Array(blocks) => blocks.iter().collect(),
+        Value::Object(blocks) => blocks.values().collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Parse the list of records under `key`: from `fetched` if it's there,
+/// or read from `store` a chunk at a time and parsed as it arrives.
+#[cfg(feature = "native")]
+async fn read_records<T: DeserializeOwned + Send + 'static>(
+    store: &dyn RecordStore,
+    key: &str,
+    kind: String,
+    record_kind: RecordKind,
+    mapping: FieldMapping,
+    fetched: &mut BTreeMap<String, String>,
+) -> Result<Option<Parsed<T>>> {
+    let prepare = move |record: &mut Value| migrate(record_kind, record, &mapping);
+    match fetched.remove(key) {
+        Some(json_str) => parse_array_with(&json_str, &kind, prepare).map(Some),
+        None => stream_value(store, key, CHUNK_BYTES, move |reader| parse_array_reader(reader, &kind, prepare)).await,
+    }
+}
+
+/// Hand back the records that parsed, mentioning on stderr any we skipped.
+/// `extract_all` returns the skipped records instead, for the caller to report.
+pub(crate) fn warn_skipped<T>(parsed: Parsed<T>) -> Vec<T> {
+    if !parsed.skipped.is_empty() {
+        eprintln!("Warning: {}", summarize_skipped(&parsed.skipped));
+    }
+    parsed.records
+}
+
+/// The workspace.json Cursor keeps next to the workspace database, saying
+/// which folder the workspace has open.
+pub(crate) fn workspace_json(config: &Config) -> Option<String> {
+    let database = config.database_path();
+    std::fs::read_to_string(Path::new(&database).parent()?.join("workspace.json")).ok()
+}
+
+/// Combine workspace and global generations into one timeline.
+/// A generation is the same record if it has the same UUID; the workspace
+/// copy wins, and the result is ordered by when each generation happened.
+pub fn merge_generations(workspace: Vec<ChatGeneration>, global: Vec<ChatGeneration>) -> Vec<ChatGeneration> {
+    let mut seen: HashSet<String> = workspace.iter().map(|generation| generation.generation_uuid.clone()).collect();
+    let mut merged = workspace;
+    
+    for generation in global {
+        if seen.insert(generation.generation_uuid.clone()) {
+            merged.push(generation);
+        }
+    }
+    
+    merged.sort_by_key(|generation| generation.unix_ms);
+    merged
+}
+
+/// Combine workspace and global prompts. Prompts carry no ID or timestamp,
+/// so two prompts are the same record if their text and command type match.
+/// Workspace prompts keep their order, with new global ones appended.
+pub fn merge_prompts(workspace: Vec<ChatPrompt>, global: Vec<ChatPrompt>) -> Vec<ChatPrompt> {
+    let mut seen: HashSet<(String, i32)> = workspace
+        .iter()
+        .map(|prompt| (prompt.text.clone(), prompt.command_type))
+        .collect();
+    let mut merged = workspace;
+    
+    for prompt in global {
+        if seen.insert((prompt.text.clone(), prompt.command_type)) {
+            merged.push(prompt);
+        }
+    }
+    
+    merged
+}
+
+/// Turn one Cursor conversation bubble into a message.
+/// Type 1 bubbles are the user's, type 2 are the AI's.
+fn parse_bubble(bubble: &Value) -> Option<ChatMessage> {
+    let role = match bubble["type"].as_i64()? {
+        1 => MessageRole::User,
+        2 => MessageRole::Assistant,
+        _ => return None,
+    };
+    
+    // Newer Cursor versions record token counts on AI bubbles; zeros mean "not recorded"
+    let token_count = &bubble["tokenCount"];
+    let usage = match (token_count["inputTokens"].as_u64(), token_count["outputTokens"].as_u64()) {
+        (Some(input), Some(output)) if input + output > 0 => Some(TokenUsage {
+            input_tokens: input,
+            output_tokens: output,
+        }),
+        _ => None,
+    };
+    
+    let tool_calls = parse_bubble_tool_calls(bubble);
+    Some(ChatMessage {
+        timestamp: bubble["timingInfo"]["clientStartTime"].as_i64(),
+        file_refs: parse_bubble_references(bubble),
+        edits: tool_calls.iter().filter_map(edit_from_tool_call).collect(),
+        tool_calls,
+        model: bubble["modelInfo"]["modelName"].as_str().map(str::to_string),
+        usage,
+        ..ChatMessage::new(role, bubble["text"].as_str().unwrap_or_default())
+    })
+}
+
+/// Pull out the tool call an agent-mode bubble made, if it made one.
+/// Cursor records these under `toolFormerData`, with the arguments either
+/// as a raw JSON string or already parsed into `params`.
+fn parse_bubble_tool_calls(bubble: &Value) -> Vec<ToolCall> {
+    let data = &bubble["toolFormerData"];
+    let Some(name) = data["name"].as_str() else {
+        return Vec::new();
+    };
+    
+    let arguments = match &data["rawArgs"] {
+        Value::Null => &data["params"],
+        raw => raw,
+    };
+    vec![tool_call(name, arguments, data["status"].as_str())]
+}
+
+/// Collect the files a bubble pointed at: whole attached files, code
+/// selections (with their line ranges), and files Cursor marked as relevant.
+fn parse_bubble_references(bubble: &Value) -> Vec<FileReference> {
+    let context = &bubble["context"];
+    let mut references: Vec<FileReference> = context["fileSelections"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|selection| whole_file(&selection["uri"]))
+        .collect();
+    
+    for selection in context["selections"].as_array().into_iter().flatten() {
+        if let Some(path) = uri_to_path(&selection["uri"]) {
+            let range = &selection["range"];
+            references.push(FileReference {
+                path,
+                start_line: range["selectionStartLineNumber"].as_u64().map(|line| line as u32),
+                end_line: range["positionLineNumber"].as_u64().map(|line| line as u32),
+            });
+        }
+    }
+    
+    references.extend(
+        bubble["relevantFiles"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(whole_file),
+    );
+    
+    references
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct DatabaseInfo {
     pub tables: Vec<String>,
@@ -0,0 +1,179 @@
+use std::collections::BTreeSet;
+
+use crate::decisions::{excerpt, sentences};
+use crate::document::{split_message, MessagePart};
+use crate::{ChatSession, MessageRole};
+
+/// Words a question can start with when it doesn't end in a question mark.
+const QUESTION_WORDS: &[&str] = &[
+    "how", "what", "why", "where", "when", "which", "who", "can", "could", "should", "is", "are", "does", "do",
+];
+
+/// How a reply starts when the answer before it wasn't taken. Matched
+/// ignoring case, at the start of the user's next message.
+const REJECTIONS: &[&str] = &[
+    "no",
+    "nope",
+    "wrong",
+    "that's wrong",
+    "that's not",
+    "that is not",
+    "not what",
+    "doesn't work",
+    "didn't work",
+    "does not work",
+    "did not work",
+    "still",
+    "that broke",
+    "you broke",
+];
+
+/// Words left out when comparing questions, since they say little about
+/// what's being asked.
+const STOP_WORDS: &[&str] = &[
+    "a", "an", "the", "we", "i", "you", "it", "to", "do", "does", "is", "are", "can", "could", "should", "how",
+    "what", "why", "in", "on", "of", "for", "with", "our", "my", "this", "that", "be", "there", "way", "best",
+];
+
+/// How alike two questions' words need to be to count as the same question.
+const SAME_QUESTION: f64 = 0.6;
+
+/// How many sentences of the answer make its summary.
+const SUMMARY_SENTENCES: usize = 2;
+
+/// A question that came up in the chats, with the answer that was taken.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FaqEntry {
+    /// The question, as it was first asked
+    pub question: String,
+    /// The first sentences of the answer
+    pub answer: String,
+    /// IDs of the sessions it was asked in, first one first
+    pub sessions: Vec<String>,
+    /// When it was first asked (Unix milliseconds)
+    pub timestamp: i64,
+}
+
+/// Pair the users' questions with the answers they took and boil them
+/// down to FAQ entries, in the order they were first asked. An answer
+/// counts as taken unless the user's next message pushes back on it
+/// ("no", "that didn't work"...). Questions asked again in other words
+/// are folded into the first one, which keeps its answer.
+pub fn distill_faq(sessions: &[&ChatSession]) -> Vec<FaqEntry> {
+    let mut entries: Vec<(BTreeSet<String>, FaqEntry)> = Vec::new();
+    for session in sessions {
+        for (index, message) in session.messages.iter().enumerate() {
+            if message.role != MessageRole::User {
+                continue;
+            }
+            let Some(question) = question(&message.text) else {
+                continue;
+            };
+            let Some(reply) = session.messages.get(index + 1).filter(|reply| reply.role == MessageRole::Assistant) else {
+                continue;
+            };
+            if session.messages.get(index + 2).is_some_and(|next| next.role == MessageRole::User && is_rejection(&next.text)) {
+                continue;
+            }
+            let Some(answer) = summary(&reply.text) else {
+                continue;
+            };
+
+            let words = key_words(&question);
+            if let Some((_, entry)) = entries.iter_mut().find(|(known, _)| similarity(known, &words) >= SAME_QUESTION) {
+                if !entry.sessions.contains(&session.composer_id) {
+                    entry.sessions.push(session.composer_id.clone());
+                }
+                continue;
+            }
+            let entry = FaqEntry {
+                question,
+                answer,
+                sessions: vec![session.composer_id.clone()],
+                timestamp: message.timestamp.unwrap_or(session.created_at),
+            };
+            entries.push((words, entry));
+        }
+    }
+    entries.into_iter().map(|(_, entry)| entry).collect()
+}
+
+/// The question in a user's message: the first sentence ending in a
+/// question mark, or the whole message if it's one short sentence that
+/// starts like a question.
+pub fn question(text: &str) -> Option<String> {
+    let prose: Vec<String> = split_message(text)
+        .into_iter()
+        .filter_map(|part| match part {
+            MessagePart::Prose(prose) => Some(prose),
+            MessagePart::Code { .. } => None,
+        })
+        .collect();
+    let prose = prose.join("\n");
+    let sentences = sentences(&prose);
+    if let Some(asked) = sentences.iter().find(|sentence| sentence.ends_with('?')) {
+        return Some(excerpt(asked));
+    }
+    match sentences.as_slice() {
+        [only] if only.split_whitespace().count() >= 3 => {
+            let first = only.split_whitespace().next().unwrap_or_default().to_lowercase();
+            QUESTION_WORDS.contains(&first.as_str()).then(|| excerpt(only.trim_end_matches('.')))
+        }
+        _ => None,
+    }
+}
+
+/// Whether a reply pushes back on the answer before it.
+pub(crate) fn is_rejection(text: &str) -> bool {
+    let text = text.trim().to_lowercase().replace('\u{2019}', "'");
+    REJECTIONS.iter().any(|rejection| {
+        text.strip_prefix(rejection)
+            .is_some_and(|rest| rest.chars().next().is_none_or(|c| !c.is_alphanumeric()))
+    })
+}
+
+/// The first sentences of an answer's prose, leaving out openers like
+/// "Sure." or "Good question!".
+pub(crate) fn summary(text: &str) -> Option<String> {
+    let mut picked: Vec<String> = Vec::new();
+    for part in split_message(text) {
+        if let MessagePart::Prose(prose) = part {
+            for sentence in sentences(&prose) {
+                if picked.len() == SUMMARY_SENTENCES {
+                    break;
+                }
+                if sentence.split_whitespace().count() > 2 && !sentence.ends_with('?') {
+                    picked.push(sentence.trim_start_matches(['-', '*', '>', ' ']).to_string());
+                }
+            }
+        }
+    }
+    (!picked.is_empty()).then(|| excerpt(&picked.join(" ")))
+}
+
+/// The words of a question worth comparing: lowercased, without stop
+/// words, and with plural and verb endings trimmed so "configuring the
+/// loggers" and "configure logger" compare the same.
+pub fn key_words(question: &str) -> BTreeSet<String> {
+    question
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty() && !STOP_WORDS.contains(word))
+        .map(|word| {
+            let stem = ["ing", "ed", "es", "s", "e"]
+                .iter()
+                .find_map(|suffix| word.strip_suffix(suffix).filter(|stem| stem.len() >= 3))
+                .unwrap_or(word);
+            stem.to_string()
+        })
+        .collect()
+}
+
+/// How many words two questions share, out of all the words in either.
+pub fn similarity(a: &BTreeSet<String>, b: &BTreeSet<String>) -> f64 {
+    let all = a.union(b).count();
+    if all == 0 {
+        return 0.0;
+    }
+    a.intersection(b).count() as f64 / all as f64
+}
@@ -0,0 +1,227 @@
+/// A line that opens or closes a fenced code block: three or more
+/// backticks or tildes, maybe indented, maybe with a language after.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Fence<'a> {
+    /// How far the fence is indented
+    pub indent: usize,
+    /// '`' or '~'
+    pub marker: char,
+    /// How many of them there are
+    pub len: usize,
+    /// What follows them (the language), trimmed
+    pub info: &'a str,
+}
+
+impl Fence<'_> {
+    /// Whether this fence closes a block `open` opened: the same marker, at
+    /// least as many of them, and nothing after.
+    pub fn closes(&self, open: &Fence<'_>) -> bool {
+        self.marker == open.marker && self.len >= open.len && self.info.is_empty()
+    }
+
+    /// The fence's markers, without indent or language.
+    pub fn markers(&self) -> String {
+        self.marker.to_string().repeat(self.len)
+    }
+}
+
+/// The fence on a line, if it is one. A backtick fence can't have a
+/// backtick after it - "```x```" is inline code, not a fence.
+pub(crate) fn parse_fence(line: &str) -> Option<Fence<'_>> {
+    let trimmed = line.trim_start();
+    let marker = trimmed.chars().next().filter(|c| *c == '`' || *c == '~')?;
+    let len = trimmed.chars().take_while(|c| *c == marker).count();
+    if len < 3 {
+        return None;
+    }
+    let info = trimmed[len..].trim();
+    if marker == '`' && info.contains('`') {
+        return None;
+    }
+    Some(Fence { indent: line.len() - trimmed.len(), marker, len, info })
+}
+
+/// A backtick fence long enough to wrap `text` without any fence inside
+/// it closing the block early.
+pub(crate) fn fence_for(text: &str) -> String {
+    let longest = text
+        .lines()
+        .filter_map(parse_fence)
+        .filter(|fence| fence.marker == '`')
+        .map(|fence| fence.len)
+        .max()
+        .unwrap_or(0);
+    "`".repeat(longest.max(2) + 1)
+}
+
+/// Languages whose code blocks can hold other code blocks, like a README
+/// written out in an answer.
+const CONTAINERS: &[&str] = &["markdown", "md", "mdx"];
+
+/// A code block being read.
+struct OpenBlock<'a> {
+    fence: Fence<'a>,
+    body: Vec<&'a str>,
+    /// How many code blocks inside it are open, for markdown blocks
+    nested: usize,
+}
+
+/// Tidy the fenced code in a message so it renders the way it was meant
+/// to: a block with no language gets one if the code makes it clear, a
+/// block left open is closed (and a stray fence with nothing after it
+/// dropped), a block that holds fences of its own gets a longer fence
+/// around it, and the code is moved left so its least indented line sits
+/// at the fence. A fence with a language inside a block of code (not
+/// markdown) means the block before it was never closed, so it's closed
+/// there.
+pub fn normalize_fences(text: &str) -> String {
+    let mut out: Vec<String> = Vec::new();
+    let mut block: Option<OpenBlock> = None;
+
+    for line in text.lines() {
+        let fence = parse_fence(line);
+        let Some(open) = &mut block else {
+            match fence {
+                Some(fence) => block = Some(OpenBlock { fence, body: Vec::new(), nested: 0 }),
+                None => out.push(line.to_string()),
+            }
+            continue;
+        };
+        let Some(fence) = fence.filter(|fence| fence.marker == open.fence.marker) else {
+            open.body.push(line);
+            continue;
+        };
+
+        let container = CONTAINERS.contains(&open.fence.info.to_lowercase().as_str());
+        if fence.closes(&open.fence) && open.nested == 0 {
+            close(block.take(), true, &mut out);
+        } else if fence.info.is_empty() && open.nested > 0 {
+            open.nested -= 1;
+            open.body.push(line);
+        } else if !fence.info.is_empty() && container {
+            open.nested += 1;
+            open.body.push(line);
+        } else if !fence.info.is_empty() {
+            close(block.take(), true, &mut out);
+            block = Some(OpenBlock { fence, body: Vec::new(), nested: 0 });
+        } else {
+            open.body.push(line);
+        }
+    }
+    close(block, false, &mut out);
+    out.join("\n")
+}
+
+/// Write out a code block, fixed up as `normalize_fences` describes.
+fn close(block: Option<OpenBlock>, closed: bool, out: &mut Vec<String>) {
+    let Some(block) = block else {
+        return;
+    };
+    if !closed && block.body.iter().all(|line| line.trim().is_empty()) {
+        return;
+    }
+
+    let common = block
+        .body
+        .iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.len() - line.trim_start_matches([' ', '\t']).len())
+        .min()
+        .unwrap_or(0);
+    let indent = " ".repeat(block.fence.indent);
+    let body: Vec<String> = block
+        .body
+        .iter()
+        .map(|line| match line.trim().is_empty() {
+            true => String::new(),
+            false => format!("{}{}", indent, line.get(common..).unwrap_or(line)),
+        })
+        .collect();
+
+    let code = body.join("\n");
+    let language = match block.fence.info {
+        "" => detect_language(&code).unwrap_or_default(),
+        info => info,
+    };
+    let inner = body
+        .iter()
+        .filter_map(|line| parse_fence(line))
+        .filter(|fence| fence.marker == block.fence.marker)
+        .map(|fence| fence.len)
+        .max()
+        .unwrap_or(0);
+    let markers = block.fence.marker.to_string().repeat(block.fence.len.max(inner + 1));
+    out.push(format!("{}{}{}", indent, markers, language));
+    out.extend(body);
+    out.push(format!("{}{}", indent, markers));
+}
+
+/// Words that give a language away, for code blocks that don't say
+/// what they are. Two of them have to turn up.
+const LANGUAGE_MARKERS: &[(&str, &[&str])] = &[
+    (
+        "rust",
+        &["fn ", "let mut ", "impl ", "pub fn ", "use std", "&self", "-> Result", "println!", "#[derive", "unwrap()", "Some(", "Ok("],
+    ),
+    ("python", &["def ", "import ", "self.", "elif ", "print(", "__init__", "None", "True:", "lambda ", "    return "]),
+    ("go", &["func ", "package ", ":= ", "fmt.", "err != nil"]),
+    ("java", &["public class ", "public static void ", "System.out", "private ", "import java"]),
+    ("cpp", &["#include", "std::", "int main(", "cout", "nullptr"]),
+    ("javascript", &["const ", "let ", "=> ", "function ", "console.log", "require(", "module.exports", "document."]),
+    ("sql", &["SELECT ", "FROM ", "WHERE ", "INSERT INTO ", "CREATE TABLE ", "UPDATE ", "JOIN "]),
+    ("html", &["<div", "<html", "<!DOCTYPE", "<span", "<body", "<head", "</"]),
+];
+
+/// What `detect_language` calls a JavaScript block that has types in it.
+const TYPESCRIPT_MARKERS: &[&str] = &[": string", ": number", ": boolean", "interface ", "readonly ", "export type ", "as const"];
+
+/// Commands that start a shell snippet.
+const SHELL_COMMANDS: &[&str] = &[
+    "cargo", "npm", "npx", "yarn", "pnpm", "git", "pip", "cd", "ls", "mkdir", "rm", "cp", "mv", "export", "docker", "curl",
+    "sudo", "apt", "brew", "make", "echo", "cat",
+];
+
+/// Guess the language of a code block from its code: a diff, JSON,
+/// shell commands, TOML, or a programming language whose keywords turn up.
+/// `None` if nothing stands out.
+pub fn detect_language(code: &str) -> Option<&'static str> {
+    let lines: Vec<&str> = code.lines().map(str::trim).filter(|line| !line.is_empty()).collect();
+    if lines.is_empty() {
+        return None;
+    }
+
+    if lines.iter().any(|line| line.starts_with("diff --git") || line.starts_with("@@ ")) {
+        return Some("diff");
+    }
+    let trimmed = code.trim();
+    if trimmed.starts_with(['{', '[']) && serde_json::from_str::<serde_json::Value>(trimmed).is_ok() {
+        return Some("json");
+    }
+    let commands = lines
+        .iter()
+        .filter(|line| {
+            let line = line.strip_prefix("$ ").unwrap_or(line);
+            line.starts_with('$') || SHELL_COMMANDS.contains(&line.split_whitespace().next().unwrap_or_default())
+        })
+        .count();
+    if commands * 2 > lines.len() {
+        return Some("shell");
+    }
+    let sections = lines.iter().filter(|line| line.starts_with('[') && line.ends_with(']')).count();
+    let keys = lines.iter().filter(|line| line.contains(" = ") && !line.ends_with(';')).count();
+    if sections > 0 && sections + keys == lines.len() {
+        return Some("toml");
+    }
+
+    let (language, score) = LANGUAGE_MARKERS
+        .iter()
+        .map(|(language, markers)| (*language, markers.iter().filter(|marker| code.contains(*marker)).count()))
+        .fold(("", 0), |best, candidate| if candidate.1 > best.1 { candidate } else { best });
+    if score < 2 {
+        return None;
+    }
+    if language == "javascript" && TYPESCRIPT_MARKERS.iter().any(|marker| code.contains(marker)) {
+        return Some("typescript");
+    }
+    Some(language)
+}
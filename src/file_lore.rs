@@ -0,0 +1,107 @@
+use std::collections::BTreeSet;
+
+use crate::decisions::{excerpt, message_excerpt};
+use crate::hotspots::{path_mentions, FileIndex};
+use crate::{ChatSession, MessageRole};
+
+/// Where the per-file lore goes, under the output directory.
+pub const FILE_LORE_DIR: &str = "files";
+
+/// How a session touched a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Touch {
+    /// The file (or a selection from it) was attached to a message
+    Attached,
+    /// The agent edited it
+    Edited,
+    /// A message named it
+    Mentioned,
+}
+
+/// A session that touched a file.
+#[derive(Debug, Clone)]
+pub struct FileSession<'a> {
+    /// The session's number in the lore
+    pub number: usize,
+    /// The session
+    pub session: &'a ChatSession,
+    /// Every way it touched the file
+    pub touches: BTreeSet<Touch>,
+    /// The first thing said about the file: who said it, when, and the
+    /// line that names it (or the start of the message it was attached to)
+    pub excerpt: Option<(MessageRole, Option<i64>, String)>,
+}
+
+/// The sessions that touched one of the project's files.
+#[derive(Debug, Clone)]
+pub struct FileLore<'a> {
+    /// The file, relative to the project
+    pub path: String,
+    /// The sessions, in the order they're numbered
+    pub sessions: Vec<FileSession<'a>>,
+}
+
+impl FileLore<'_> {
+    /// The file its lore is written to: the path with `__` between
+    /// directories, like `src__auth.rs.md`.
+    pub fn file_name(&self) -> String {
+        format!("{}.md", self.path.replace(['/', '\\'], "__"))
+    }
+}
+
+/// Find the project files that at least `min_sessions` of the numbered
+/// sessions attached, edited or mentioned, with the sessions that did.
+/// Paths are matched against the project's files through `index`, so
+/// only files that are really there count. The files that came up in the
+/// most sessions come first.
+pub fn collect_file_lore<'a>(
+    sessions: &[(usize, &'a ChatSession)],
+    index: &FileIndex<'_>,
+    min_sessions: usize,
+) -> Vec<FileLore<'a>> {
+    let mut files: Vec<FileLore<'a>> = Vec::new();
+
+    for &(number, session) in sessions {
+        let mut touched: Vec<(&str, FileSession<'a>)> = Vec::new();
+        for message in &session.messages {
+            let attached = message.file_refs.iter().map(|reference| (Touch::Attached, reference.path.as_str()));
+            let edited = message.edits.iter().map(|edit| (Touch::Edited, edit.path.as_str()));
+            let mentioned = path_mentions(&message.text).map(|mention| (Touch::Mentioned, mention));
+            for (touch, path) in attached.chain(edited).chain(mentioned) {
+                let Some(file) = index.resolve(path) else {
+                    continue;
+                };
+                let position = match touched.iter().position(|(known, _)| *known == file) {
+                    Some(position) => position,
+                    None => {
+                        let session = FileSession { number, session, touches: BTreeSet::new(), excerpt: None };
+                        touched.push((file, session));
+                        touched.len() - 1
+                    }
+                };
+                let entry = &mut touched[position].1;
+                entry.touches.insert(touch);
+                if entry.excerpt.is_none() {
+                    // The line that names the file, or the start of the message
+                    let quote = match message.text.lines().find(|line| touch == Touch::Mentioned && line.contains(path)) {
+                        Some(line) => excerpt(line),
+                        None => message_excerpt(&message.text),
+                    };
+                    if !quote.is_empty() {
+                        entry.excerpt = Some((message.role, message.timestamp, quote));
+                    }
+                }
+            }
+        }
+        for (file, session) in touched {
+            match files.iter_mut().find(|lore| lore.path == file) {
+                Some(lore) => lore.sessions.push(session),
+                None => files.push(FileLore { path: file.to_string(), sessions: vec![session] }),
+            }
+        }
+    }
+
+    files.retain(|lore| lore.sessions.len() >= min_sessions.max(1));
+    files.sort_by(|a, b| b.sessions.len().cmp(&a.sessions.len()).then_with(|| a.path.cmp(&b.path)));
+    files
+}
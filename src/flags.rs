@@ -0,0 +1,78 @@
+use clap::{Arg, ArgAction, ArgMatches, Args, Command, FromArgMatches};
+use std::collections::{HashMap, HashSet};
+
+use crate::config::Config;
+
+/// Where `--help` lists the setting flags.
+pub const SETTINGS_HEADING: &str = "Settings";
+
+/// A flag for every setting, so a one-off run can change any of them
+/// without editing the config file: `--workspace-id a29d4797...`,
+/// `--include-secrets true`, `--project-name api`. The flags are made
+/// from `Config::setting_names`, so a new setting gets one without
+/// anything to add here. Settings that already have an option of their
+/// own with the same name (`--output-dir`, `--source`...) keep it.
+///
+/// What's given wins over the environment and the config file, the way a
+/// batch project's settings do.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SettingFlags {
+    /// The settings given, by name (`WORKSPACE_ID`)
+    pub values: HashMap<String, String>,
+}
+
+impl SettingFlags {
+    /// The flag for a setting: `WORKSPACE_ID` is `--workspace-id`.
+    pub fn flag(name: &str) -> String {
+        name.to_lowercase().replace('_', "-")
+    }
+
+    /// The argument ID for a setting, kept apart from the IDs of the
+    /// options derived from the rest of the command line.
+    fn id(name: &str) -> String {
+        format!("setting:{}", name)
+    }
+}
+
+impl Args for SettingFlags {
+    fn augment_args(cmd: Command) -> Command {
+        let taken: HashSet<String> = cmd.get_arguments().filter_map(|arg| arg.get_long()).map(str::to_string).collect();
+        Config::setting_names()
+            .into_iter()
+            .filter(|name| !taken.contains(&SettingFlags::flag(name)))
+            .fold(cmd, |cmd, name| {
+                cmd.arg(
+                    Arg::new(SettingFlags::id(&name))
+                        .long(SettingFlags::flag(&name))
+                        .value_name("VALUE")
+                        .action(ArgAction::Set)
+                        .help(format!("Set {} for this run", name))
+                        .help_heading(SETTINGS_HEADING)
+                        // 150-odd of them would bury the options in `-h`
+                        .hide_short_help(true),
+                )
+            })
+    }
+
+    fn augment_args_for_update(cmd: Command) -> Command {
+        SettingFlags::augment_args(cmd)
+    }
+}
+
+impl FromArgMatches for SettingFlags {
+    fn from_arg_matches(matches: &ArgMatches) -> Result<Self, clap::Error> {
+        let mut flags = SettingFlags::default();
+        flags.update_from_arg_matches(matches)?;
+        Ok(flags)
+    }
+
+    fn update_from_arg_matches(&mut self, matches: &ArgMatches) -> Result<(), clap::Error> {
+        for name in Config::setting_names() {
+            // Settings with an option of their own have no flag here
+            if let Ok(Some(value)) = matches.try_get_one::<String>(&SettingFlags::id(&name)) {
+                self.values.insert(name, value.clone());
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,172 @@
+use schemars::JsonSchema;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+
+use crate::decisions::{excerpt, find_phrase, sentences};
+use crate::document::{split_message, MessagePart};
+use crate::{ChatGeneration, ChatPrompt, ChatSession, ComposerData, MessageRole};
+
+/// Phrases that usually mean something was left for later. They're
+/// matched ignoring case, as whole words.
+const FOLLOW_UP_PHRASES: &[&str] = &[
+    "todo",
+    "fixme",
+    "later we should",
+    "we should later",
+    "we can later",
+    "left as follow-up",
+    "left as a follow-up",
+    "as a follow-up",
+    "in a follow-up",
+    "for a follow-up",
+    "follow-up pr",
+    "follow-up task",
+    "come back to",
+    "still need to",
+    "still needs to",
+    "not implemented yet",
+    "not yet implemented",
+    "in a later pr",
+    "out of scope for now",
+];
+
+/// Markers that count inside code blocks, where only comments like
+/// `// TODO: retry` are worth pulling out. Matched as written.
+const CODE_MARKERS: &[&str] = &["TODO", "FIXME"];
+
+/// Where a follow-up was found.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum FollowUpSource {
+    /// A message in a session's transcript
+    Message {
+        /// Which message in the session
+        index: usize,
+        /// Who wrote it
+        role: MessageRole,
+    },
+    /// A prompt the user entered
+    Prompt,
+    /// A generation's description
+    Generation {
+        /// The generation's ID
+        uuid: String,
+    },
+}
+
+/// A piece of unfinished work mentioned in the chats.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct FollowUp {
+    /// A short, stable fingerprint: the session (if any) and the excerpt
+    pub id: String,
+    /// The session it came up in, when that's known
+    pub session_id: Option<String>,
+    /// That session's name
+    pub session_name: Option<String>,
+    /// Where in the chats it was found
+    pub source: FollowUpSource,
+    /// When it was said (Unix milliseconds), when that's known
+    pub timestamp: Option<i64>,
+    /// The phrase that gave it away, as listed in `FOLLOW_UP_PHRASES`
+    /// (or `CODE_MARKERS`)
+    pub phrase: &'static str,
+    /// The sentence or code comment it was in, shortened if it's long
+    pub excerpt: String,
+}
+
+/// Find the open follow-ups in the chats, oldest first: every sentence
+/// in the transcripts that leaves something for later, and every TODO
+/// comment in the code they suggest. Cursor keeps no transcripts, so the
+/// prompts and generations are searched too, and matched to a session
+/// where they can be - prompts by their text, generations by when they
+/// were made. Something said twice is only listed once.
+pub fn find_follow_ups(sessions: &[ComposerData], generations: &[ChatGeneration], prompts: &[ChatPrompt]) -> Vec<FollowUp> {
+    let sessions: Vec<&ChatSession> = sessions.iter().flat_map(|data| &data.all_composers).collect();
+    let mut found: Vec<FollowUp> = Vec::new();
+    let mut seen: HashSet<String> = HashSet::new();
+    let mut add = |session: Option<&ChatSession>, source: FollowUpSource, timestamp: Option<i64>, text: &str| {
+        for (phrase, excerpt) in text_follow_ups(text) {
+            if !seen.insert(excerpt.clone()) {
+                continue;
+            }
+            found.push(FollowUp {
+                id: follow_up_id(session.map(|session| session.composer_id.as_str()), &excerpt),
+                session_id: session.map(|session| session.composer_id.clone()),
+                session_name: session.map(|session| session.name.clone()),
+                source: source.clone(),
+                timestamp,
+                phrase,
+                excerpt,
+            });
+        }
+    };
+
+    for session in &sessions {
+        for (index, message) in session.messages.iter().enumerate() {
+            let source = FollowUpSource::Message { index, role: message.role };
+            add(Some(session), source, Some(message.timestamp.unwrap_or(session.created_at)), &message.text);
+        }
+    }
+    for prompt in prompts {
+        let asked = sessions.iter().find_map(|session| {
+            session
+                .messages
+                .iter()
+                .find(|message| message.role == MessageRole::User && message.text == prompt.text)
+                .map(|message| (*session, message.timestamp.unwrap_or(session.created_at)))
+        });
+        add(asked.map(|(session, _)| session), FollowUpSource::Prompt, asked.map(|(_, timestamp)| timestamp), &prompt.text);
+    }
+    for generation in generations {
+        let during = sessions.iter().copied().find(|session| {
+            generation.unix_ms >= session.created_at && generation.unix_ms <= session.last_updated_at.max(session.created_at)
+        });
+        let source = FollowUpSource::Generation { uuid: generation.generation_uuid.clone() };
+        add(during, source, Some(generation.unix_ms), &generation.text_description);
+    }
+
+    // Stable, so follow-ups from the same moment keep the order they were found in;
+    // the ones with no time go last
+    found.sort_by_key(|follow_up| (follow_up.timestamp.is_none(), follow_up.timestamp));
+    found
+}
+
+/// A short, stable fingerprint of a follow-up, to tell it apart from the
+/// others - and to find it again in an issue tracker.
+pub fn follow_up_id(session_id: Option<&str>, excerpt: &str) -> String {
+    let digest = Sha256::digest(format!("{}\n{}", session_id.unwrap_or_default(), excerpt).as_bytes());
+    digest.iter().take(6).map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// The follow-up phrase in a sentence, if it has one.
+pub fn follow_up_phrase(sentence: &str) -> Option<&'static str> {
+    find_phrase(sentence, FOLLOW_UP_PHRASES)
+}
+
+/// The follow-ups in one piece of text, with their excerpts: sentences in
+/// the prose, comment lines in the code.
+fn text_follow_ups(text: &str) -> Vec<(&'static str, String)> {
+    let mut found = Vec::new();
+    for part in split_message(text) {
+        match part {
+            MessagePart::Prose(prose) => {
+                for sentence in sentences(&prose) {
+                    if let Some(phrase) = follow_up_phrase(sentence) {
+                        found.push((phrase, excerpt(sentence)));
+                    }
+                }
+            }
+            MessagePart::Code { text, .. } => {
+                for line in text.lines() {
+                    if let Some((marker, position)) =
+                        CODE_MARKERS.iter().find_map(|marker| line.find(marker).map(|position| (*marker, position)))
+                    {
+                        found.push((marker, excerpt(&line[position..])));
+                    }
+                }
+            }
+        }
+    }
+    found
+}
@@ -0,0 +1,116 @@
+use anyhow::{bail, Result};
+
+/// The names the front matter fills in itself, which FRONT_MATTER_FIELDS
+/// can't set.
+const RESERVED: &[&str] = &["title", "date", "tags", "draft"];
+
+/// YAML front matter for a markdown file, so it can go straight into a
+/// Hugo, Jekyll or Zola site: the page's title, date and tags, whether it's
+/// a draft, and any fields of the site's own.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FrontMatter {
+    /// The page title
+    pub title: String,
+    /// When the page's content last changed, as an RFC 3339 timestamp
+    pub date: Option<String>,
+    /// The page's tags
+    pub tags: Vec<String>,
+    /// The page's topics
+    pub topics: Vec<String>,
+    /// Whether the site should hold the page back as a draft
+    pub draft: bool,
+    /// More fields, as FRONT_MATTER_FIELDS gives them
+    pub fields: Vec<(String, String)>,
+}
+
+impl FrontMatter {
+    /// The front matter as a YAML block between `---` lines, followed by
+    /// the blank line that separates it from the page.
+    pub fn to_yaml(&self) -> String {
+        let mut yaml = String::from("---\n");
+        yaml.push_str(&format!("title: {}\n", quoted(&self.title)));
+        if let Some(date) = &self.date {
+            yaml.push_str(&format!("date: {}\n", date));
+        }
+        if !self.tags.is_empty() {
+            let tags: Vec<String> = self.tags.iter().map(|tag| quoted(tag)).collect();
+            yaml.push_str(&format!("tags: [{}]\n", tags.join(", ")));
+        }
+        if !self.topics.is_empty() {
+            let topics: Vec<String> = self.topics.iter().map(|topic| quoted(topic)).collect();
+            yaml.push_str(&format!("topics: [{}]\n", topics.join(", ")));
+        }
+        yaml.push_str(&format!("draft: {}\n", self.draft));
+        for (key, value) in &self.fields {
+            yaml.push_str(&format!("{}: {}\n", key, scalar(value)));
+        }
+        yaml.push_str("---\n\n");
+        yaml
+    }
+
+    /// The front matter as a TOML block between `+++` lines, the way Zola
+    /// wants it: tags and topics are taxonomies, and the other fields go
+    /// under `extra`, since Zola only allows its own at the top.
+    pub fn to_toml(&self) -> String {
+        let mut toml = String::from("+++\n");
+        toml.push_str(&format!("title = {}\n", quoted(&self.title)));
+        if let Some(date) = &self.date {
+            toml.push_str(&format!("date = {}\n", date));
+        }
+        toml.push_str(&format!("draft = {}\n", self.draft));
+        if !self.tags.is_empty() || !self.topics.is_empty() {
+            toml.push_str("\n[taxonomies]\n");
+            for (name, terms) in [("tags", &self.tags), ("topics", &self.topics)] {
+                if !terms.is_empty() {
+                    let terms: Vec<String> = terms.iter().map(|term| quoted(term)).collect();
+                    toml.push_str(&format!("{} = [{}]\n", name, terms.join(", ")));
+                }
+            }
+        }
+        if !self.fields.is_empty() {
+            toml.push_str("\n[extra]\n");
+            for (key, value) in &self.fields {
+                toml.push_str(&format!("{} = {}\n", key, scalar(value)));
+            }
+        }
+        toml.push_str("+++\n\n");
+        toml
+    }
+}
+
+/// Parse FRONT_MATTER_FIELDS: `key=value` pairs separated by commas, like
+/// `layout=lore, weight=10`.
+pub fn parse_fields(list: &str) -> Result<Vec<(String, String)>> {
+    let mut fields: Vec<(String, String)> = Vec::new();
+    for entry in list.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+        let Some((key, value)) = entry.split_once('=') else {
+            bail!("Front matter field '{}' should look like key=value", entry);
+        };
+        let key = key.trim();
+        if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+            bail!("'{}' can't be a front matter field name (letters, digits, _ and - only)", key);
+        }
+        if RESERVED.contains(&key) {
+            bail!("Front matter field '{}' is filled in already", key);
+        }
+        fields.retain(|(known, _)| known != key);
+        fields.push((key.to_string(), value.trim().to_string()));
+    }
+    Ok(fields)
+}
+
+/// A string in double quotes, as YAML and TOML both read it: JSON's
+/// string escapes are theirs too.
+pub(crate) fn quoted(text: &str) -> String {
+    serde_json::to_string(text).unwrap_or_else(|_| "\"\"".to_string())
+}
+
+/// A field's value: booleans and numbers as they are (so `weight=10` is a
+/// number to the site), anything else quoted.
+fn scalar(value: &str) -> String {
+    if value == "true" || value == "false" || value.parse::<i64>().is_ok() || value.parse::<f64>().is_ok_and(f64::is_finite) {
+        value.to_string()
+    } else {
+        quoted(value)
+    }
+}
@@ -1,15 +1,82 @@
-use anyhow::Result;
-use chrono::{DateTime, Utc};
+use anyhow::{Context, Result};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 
+use crate::classify::{classify, pipe_through};
 use crate::config::Config;
-use crate::{ChatGeneration, ChatPrompt, ComposerData};
+use crate::curation::Curation;
+use crate::decisions::{excerpt, message_excerpt, session_decisions};
+use crate::document::{heading_anchor, map_text, markdown_blocks, nest_headings, Block, Document};
+use crate::edits::session_edits;
+use crate::faq::{distill_faq, summary};
+use crate::fences::normalize_fences;
+use crate::file_lore::{collect_file_lore, Touch};
+use crate::followups::{find_follow_ups, FollowUpSource};
+use crate::front_matter::{parse_fields, FrontMatter};
+use crate::git::{commits_between, first_commit_touching};
+use crate::graph::KnowledgeGraph;
+use crate::grouping::{group_by_key, session_topic, GroupBy};
+use crate::hotspots::{find_hotspots, FileIndex};
+use crate::i18n::Strings;
+use crate::lore_index::LoreIndex;
+use crate::message_policy::{split_message_lines, MessagePolicies, MessagePolicy};
+use crate::plugins::{parse_names, source_plugin, PluginSection, PLUGIN_PREFIX};
+use crate::progress::{Progress, Stage};
+use crate::project::{project_files, project_tree, summarize_project};
+use crate::publish::{Page, PublishPlan};
+use crate::redaction::REDACTED;
+use crate::references::{files_discussed, most_discussed_files};
+use crate::related::RelatedSessions;
+use crate::render::epub::{Book, Chapter};
+use crate::render::{DocumentWriter, OutputFormat};
+use crate::sanitize::Sanitizer;
+use crate::sections::{Section, SectionContext, SectionRegistry};
+use crate::site::{self, SiteLabels};
+use crate::tabular::{message_rows, to_csv, to_parquet};
+use crate::tags::SessionTags;
+use crate::time::DisplayZone;
+use crate::timeline::{mermaid_diagram, TimelineStyle};
+use crate::tools::{commands_executed, tool_usage};
+use crate::troubleshooting::troubleshooting_log;
+use crate::warnings::{Warning, WarningKind, Warnings};
+use crate::usage::{monthly_usage, session_usage, total_cost, total_usage, ModelUsage};
+use crate::workspace_info::WorkspaceInfo;
+use crate::{ChatGeneration, ChatMessage, ChatPrompt, ChatSession, ComposerData, MessageRole};
 
 /// The MarkdownGenerator is our "storyteller" - it takes all the raw chat data
-/// and weaves it into a beautiful, readable markdown document that tells the
-/// story of the code development process.
+/// and weaves it into a beautiful, readable document that tells the story of
+/// the code development process. It builds a `Document` and writes it out
+/// as markdown (or org-mode, or AsciiDoc) - a section at a time, when the
+/// output is going straight to a file.
 pub struct MarkdownGenerator {
     /// Configuration settings that control how we format the output
     config: Config,
+    /// Section titles and labels in the configured language
+    strings: Strings,
+    /// How many secrets have been blanked out so far
+    redactions: AtomicUsize,
+    /// The tags given to sessions with the `tag` subcommand
+    tags: SessionTags,
+    /// Names, summaries and ordering from CURATION_FILE
+    curation: Curation,
+    /// What the workspace had open, if the source keeps track
+    workspace: Option<WorkspaceInfo>,
+    /// The sections it can render
+    sections: SectionRegistry,
+    /// How long messages are shown, per section
+    message_policies: MessagePolicies,
+    /// Whether SUMMARIZE_COMMAND has failed, so it isn't tried again
+    summarize_failed: AtomicBool,
+    /// Who to tell how rendering's going, and whether to stop
+    progress: Progress,
+    /// What was wrong with the history, found extracting it or here
+    warnings: Warnings,
+    /// The rules everything written goes through (secrets, paths, personal
+    /// info and SANITIZE_RULES)
+    sanitizer: Sanitizer,
 }
 
 impl MarkdownGenerator {
@@ -17,299 +84,1791 @@ impl MarkdownGenerator {
     /// This is where we set up our "storyteller" with all the settings
     /// it needs to create beautiful markdown output.
     pub fn new(config: &Config) -> Self {
+        let mut sections = builtin_sections();
+        for section in &config.custom_sections {
+            sections.insert(Box::new(section.clone()), &section.position);
+        }
+        for name in parse_names(&config.analyzer_plugins).unwrap_or_default() {
+            sections.register(Box::new(PluginSection::new(name)));
+        }
         MarkdownGenerator {
             config: config.clone(),
+            strings: Strings::for_locale(&config.locale).unwrap_or_default(),
+            redactions: AtomicUsize::new(0),
+            tags: SessionTags::default(),
+            curation: Curation::default(),
+            workspace: None,
+            sections,
+            message_policies: MessagePolicies::parse(&config.message_policy).unwrap_or_default(),
+            summarize_failed: AtomicBool::new(false),
+            progress: Progress::default(),
+            warnings: Warnings::default(),
+            sanitizer: config.sanitizer(),
         }
     }
     
-    /// Generate the complete consolidated markdown document.
-    /// This is the main method that orchestrates the creation of our
-    /// beautiful markdown file from all the raw chat data.
+    /// Show these tags on sessions, group by them with `GROUP_BY=tag`, and
+    /// list the sessions under each in a Tags section.
+    pub fn with_tags(mut self, tags: SessionTags) -> Self {
+        self.tags = tags;
+        self
+    }
+    
+    /// Show sessions under the names and in the order `curation` gives
+    /// them, with its summaries under their headings.
+    pub fn with_curation(mut self, curation: Curation) -> Self {
+        self.curation = curation;
+        self
+    }
+    
+    /// Describe what the workspace had open (the folder, editor tabs and
+    /// recently opened files) in a Workspace section.
+    pub fn with_workspace(mut self, workspace: Option<WorkspaceInfo>) -> Self {
+        self.workspace = workspace;
+        self
+    }
+    
+    /// Add a section of your own. It goes before the footer unless
+    /// SECTIONS places it, and one named like a built-in section replaces it.
+    pub fn with_section(mut self, section: impl Section + 'static) -> Self {
+        self.sections.register(Box::new(section));
+        self
+    }
+    
+    /// Report how rendering the sessions (and indexing them, with
+    /// LORE_INDEX) is going, and give up with `Cancelled` between sessions
+    /// if asked to.
+    pub fn with_progress(mut self, progress: Progress) -> Self {
+        self.progress = progress;
+        self
+    }
+    
+    /// Start from the warnings extracting the history gave (see
+    /// `ImportedHistory::all_warnings`), for the Extraction Warnings
+    /// appendix. Any found while rendering are added to them.
+    pub fn with_warnings(self, warnings: Vec<Warning>) -> Self {
+        self.warnings.extend(warnings);
+        self
+    }
+    
+    /// Everything that was wrong with the history: what extraction found,
+    /// and what rendering has found so far.
+    pub fn warnings(&self) -> Vec<Warning> {
+        self.warnings.to_vec()
+    }
+    
+    /// How many secrets this generator has blanked out of what it wrote.
+    pub fn redactions(&self) -> usize {
+        self.redactions.load(Ordering::Relaxed)
+    }
+    
+    /// Run text through the secret redaction, counting what it catches,
+    /// and then the rest of the sanitizer.
+    pub(crate) fn redact(&self, text: &str) -> String {
+        let redacted = self.config.redact_secrets(text);
+        let caught = redacted.matches(REDACTED).count().saturating_sub(text.matches(REDACTED).count());
+        self.redactions.fetch_add(caught, Ordering::Relaxed);
+        self.scrub(&redacted)
+    }
+    
+    /// Run `text` through the sanitizer's rules: user names, host names,
+    /// email addresses and home directories, and SANITIZE_RULES.
+    fn scrub(&self, text: &str) -> String {
+        self.sanitizer.apply(text)
+    }
+    
+    /// `scrub` all the text in `blocks`. Everything the generator writes
+    /// goes through here on its way out, whichever section built it.
+    fn scrub_blocks(&self, blocks: Vec<Block>) -> Vec<Block> {
+        if self.sanitizer.rules.is_empty() {
+            return blocks;
+        }
+        map_text(blocks, &|text| self.sanitizer.apply(text))
+    }
+    
+    /// Generate the complete consolidated document, in the configured
+    /// output format (markdown unless `OUTPUT_FORMAT` says otherwise).
     pub fn generate_consolidated_history(
         &self,
         sessions: &[ComposerData],
-        _generations: &[ChatGeneration],
-        _prompts: &[ChatPrompt],
+        generations: &[ChatGeneration],
+        prompts: &[ChatPrompt],
     ) -> Result<String> {
-        let mut content = String::new();
+        let writer = DocumentWriter::new(self.config.output_format(), Vec::new())?;
+        let text = self.stream_document(sessions, generations, prompts, writer)?;
+        Ok(String::from_utf8(text)?)
+    }
+    
+    /// Generate the output file's contents in the configured format. This
+    /// is what the CLI writes: the rendered document for document formats
+    /// (typeset, for PDF), or the table of messages for CSV and Parquet.
+    pub fn generate_output(
+        &self,
+        sessions: &[ComposerData],
+        generations: &[ChatGeneration],
+        prompts: &[ChatPrompt],
+    ) -> Result<Vec<u8>> {
+        let mut output = Vec::new();
+        self.write_output(sessions, generations, prompts, &mut output)?;
+        Ok(output)
+    }
+    
+    /// Write the output file's contents to `sink`, the same as
+    /// `generate_output` gives. Text formats are written a session at a
+    /// time as they're built, so a huge history never has its whole
+    /// rendering in memory; PDF, EPUB and Parquet have to be built whole first.
+    pub fn write_output(
+        &self,
+        sessions: &[ComposerData],
+        generations: &[ChatGeneration],
+        prompts: &[ChatPrompt],
+        mut sink: impl Write,
+    ) -> Result<()> {
+        let format = self.config.output_format();
+        match format {
+            OutputFormat::Csv => sink.write_all(to_csv(&message_rows(sessions, generations, prompts)).as_bytes())?,
+            OutputFormat::Parquet => sink.write_all(&to_parquet(&message_rows(sessions, generations, prompts))?)?,
+            OutputFormat::Pdf => sink.write_all(&format.render_bytes(&self.generate_document(sessions, generations, prompts)?)?)?,
+            OutputFormat::Epub => sink.write_all(&self.generate_book(sessions, generations, prompts)?.package()?)?,
+            _ => {
+                sink = self.stream_document(sessions, generations, prompts, DocumentWriter::new(format, sink)?)?;
+            }
+        }
+        sink.flush()?;
+        Ok(())
+    }
+    
+    /// Build the document straight into `writer`, section by section.
+    fn stream_document<W: Write>(
+        &self,
+        sessions: &[ComposerData],
+        generations: &[ChatGeneration],
+        prompts: &[ChatPrompt],
+        mut writer: DocumentWriter<W>,
+    ) -> Result<W> {
+        // Front matter only means something to sites built from markdown
+        let all = sessions.iter().flat_map(|data| &data.all_composers);
+        match self.front_matter(self.lore_title(), all) {
+            Some(front_matter) if self.config.output_format() == OutputFormat::Markdown => writer.write_raw(&front_matter)?,
+            _ => {}
+        }
+        self.build_sections(sessions, generations, prompts, true, &mut |blocks| writer.write(&blocks))?;
+        writer.finish()
+    }
+    
+    /// Build the consolidated document without writing it out in any format.
+    /// This is the main method that orchestrates the creation of our
+    /// document from all the raw chat data.
+    pub fn generate_document(
+        &self,
+        sessions: &[ComposerData],
+        generations: &[ChatGeneration],
+        prompts: &[ChatPrompt],
+    ) -> Result<Document> {
+        self.build_document(sessions, generations, prompts, true)
+    }
+    
+    /// The lore's title as a page title: the heading and the project.
+    fn lore_title(&self) -> String {
+        format!("{} - {}", self.strings.get("header.title"), self.config.project_name)
+    }
+    
+    /// YAML front matter for a markdown page about `sessions`, with
+    /// FRONT_MATTER on: dated when the latest of them was, and tagged
+    /// with all their tags.
+    fn front_matter<'a>(&self, title: String, sessions: impl Iterator<Item = &'a ChatSession>) -> Option<String> {
+        if !self.config.front_matter {
+            return None;
+        }
         
-        // Start building our markdown document piece by piece
-        // First, we need a nice header to introduce our story
-        content.push_str(&self.generate_header());
-        content.push_str("\n\n");
+        let mut latest: Option<i64> = None;
+        let mut tags: Vec<String> = Vec::new();
+        for session in sessions {
+            latest = latest.max(Some(session.last_updated_at.max(session.created_at)));
+            for tag in self.tags.of(&session.composer_id) {
+                if !tags.iter().any(|known| known == tag) {
+                    tags.push(tag.to_string());
+                }
+            }
+        }
+        tags.sort();
+        let front_matter = FrontMatter {
+            title: self.config.sanitize_path(&self.redact(&title)),
+            date: latest.and_then(|millis| self.config.display_zone().format_millis(millis, "%Y-%m-%dT%H:%M:%S%:z")),
+            tags,
+            topics: Vec::new(),
+            draft: self.config.front_matter_draft,
+            fields: parse_fields(&self.config.front_matter_fields).unwrap_or_default(),
+        };
+        Some(front_matter.to_yaml())
+    }
+    
+    /// Split the lore into wiki pages for `publish`: an overview page with
+    /// every section but the sessions, and a page for each session.
+    pub fn generate_publish_plan(
+        &self,
+        sessions: &[ComposerData],
+        generations: &[ChatGeneration],
+        prompts: &[ChatPrompt],
+    ) -> Result<PublishPlan> {
+        let overview = self.build_document(sessions, generations, prompts, false)?;
+        let root = Page {
+            key: self.config.project_name.clone(),
+            title: self.lore_title(),
+            blocks: overview.blocks,
+        };
         
-        // Add metadata that tells readers when and where this was created
-        content.push_str(&self.generate_metadata(sessions)?);
-        content.push_str("\n\n");
+        let pages = self
+            .numbered_sessions(sessions)
+            .into_iter()
+            .map(|(number, session)| Page {
+                key: session.composer_id.clone(),
+                title: self.session_title(number, session),
+                blocks: self.scrub_blocks(self.generate_session(number, session, 2)),
+            })
+            .collect();
         
-        // Give some context about what this project is all about
-        content.push_str(&self.generate_project_context());
-        content.push_str("\n\n");
+        Ok(PublishPlan { root, sessions: pages })
+    }
+    
+    /// The source of a Zola site, as (path, contents) pairs relative to
+    /// the site's directory: `config.toml` and the templates, the overview
+    /// (every section but the sessions) as the front page, and a page per
+    /// session under `content/sessions/`, filed under its tags and topic
+    /// so Zola makes a page for each of those too.
+    pub fn generate_site(
+        &self,
+        sessions: &[ComposerData],
+        generations: &[ChatGeneration],
+        prompts: &[ChatPrompt],
+    ) -> Result<Vec<(String, String)>> {
+        let t = &self.strings;
+        let zone = self.config.display_zone();
+        let labels = SiteLabels {
+            title: self.lore_title(),
+            tags: t.get("sessions.tags").to_string(),
+            topics: t.get("site.topics").to_string(),
+            recent: t.get("site.recent").to_string(),
+        };
+        let mut files = vec![("config.toml".to_string(), site::config_toml(&labels, &self.config.locale))];
+        for (name, template) in site::TEMPLATES {
+            files.push((format!("templates/{}", name), template.to_string()));
+        }
         
-        // Now we get to the good stuff - all the historical chat sessions
-        content.push_str(&self.generate_historical_sessions(sessions)?);
-        content.push_str("\n\n");
+        // The front page has the title already, so the overview starts after it
+        let mut overview = self.build_document(sessions, generations, prompts, false)?;
+        if matches!(overview.blocks.first(), Some(Block::Heading { level: 1, .. })) {
+            overview.blocks.remove(0);
+        }
+        let front = FrontMatter { title: labels.title.clone(), ..FrontMatter::default() };
+        let overview = format!("{}{}", front.to_toml(), OutputFormat::Markdown.render(&overview)?);
+        files.push(("content/_index.md".to_string(), overview));
+        files.push((
+            format!("content/{}/_index.md", site::SESSIONS_SECTION),
+            site::section_front_matter(t.get("sessions.title")),
+        ));
         
-        // Add information about the current session
-        content.push_str(&self.generate_current_session());
-        content.push_str("\n\n");
+        let policy = self.message_policies.for_section("sessions");
+        for (number, session) in self.numbered_sessions(sessions) {
+            // The page title is the heading; what it says about the session goes under it
+            let title = self.session_title(number, session);
+            let mut blocks = self.generate_session(number, session, 1);
+            if let Some(Block::Heading { properties, .. }) = blocks.first().cloned() {
+                blocks[0] = Block::Fields(properties);
+            }
+            let blocks = self.scrub_blocks(self.apply_message_policy(policy, blocks));
+            
+            let topic = session_topic(session);
+            let front = FrontMatter {
+                title: self.config.sanitize_path(&self.redact(&title)),
+                date: zone.format_millis(session.created_at, "%Y-%m-%dT%H:%M:%S%:z"),
+                tags: self.tags.of(&session.composer_id).into_iter().map(str::to_string).collect(),
+                topics: if topic.is_empty() { Vec::new() } else { vec![topic] },
+                draft: self.config.front_matter_draft,
+                fields: parse_fields(&self.config.front_matter_fields).unwrap_or_default(),
+            };
+            let page = format!("{}{}", front.to_toml(), OutputFormat::Markdown.render(&Document { blocks, ..Document::default() })?);
+            let slug: String = heading_anchor(&title).chars().take(80).collect();
+            files.push((format!("content/{}/{}.md", site::SESSIONS_SECTION, slug.trim_end_matches('-')), page));
+        }
+        Ok(files)
+    }
+    
+    /// The lore as an EPUB book: the overview (every section but the
+    /// sessions) as the first chapter, then a chapter for each month of
+    /// sessions, oldest first, with each session listed in the contents.
+    pub fn generate_book(
+        &self,
+        sessions: &[ComposerData],
+        generations: &[ChatGeneration],
+        prompts: &[ChatPrompt],
+    ) -> Result<Book> {
+        let t = &self.strings;
+        let zone = self.config.display_zone();
         
-        // Organize everything by topics and themes for easy navigation
-        content.push_str(&self.generate_topics_and_themes());
-        content.push_str("\n\n");
+        // The chapter has the title already, so the overview starts after it
+        let mut overview = self.build_document(sessions, generations, prompts, false)?;
+        if matches!(overview.blocks.first(), Some(Block::Heading { level: 1, .. })) {
+            overview.blocks.remove(0);
+        }
+        let mut chapters = vec![Chapter { title: t.get("epub.overview").to_string(), blocks: overview.blocks }];
         
-        // Show the project structure so readers understand the codebase
-        content.push_str(&self.generate_project_structure());
-        content.push_str("\n\n");
+        let policy = self.message_policies.for_section("sessions");
+        let numbered = self.numbered_sessions(sessions);
+        let mut months = group_by_key(numbered.clone(), |(_, session)| GroupBy::Month.date_key(session, &zone).unwrap_or_default());
+        months.sort_by(|a, b| a.0.cmp(&b.0));
+        for (_, members) in &months {
+            let title = zone.format_millis(members[0].1.created_at, "%B %Y").unwrap_or_default();
+            let blocks = members.iter().flat_map(|(number, session)| self.generate_session(*number, session, 2)).collect();
+            chapters.push(Chapter { title, blocks: self.scrub_blocks(self.apply_message_policy(policy, blocks)) });
+        }
         
-        // Highlight the key features that were implemented
-        content.push_str(&self.generate_key_features());
-        content.push_str("\n\n");
+        let first = numbered.iter().map(|(_, session)| session.created_at).min();
+        let last = numbered.iter().map(|(_, session)| session.last_updated_at.max(session.created_at)).max();
+        let mut cover_lines = Vec::new();
+        if let (Some(first), Some(last)) = (first, last) {
+            let (from, to) = (zone.format_millis(first, "%B %Y"), zone.format_millis(last, "%B %Y"));
+            cover_lines.push(if from == to { from.unwrap_or_default() } else { format!("{} – {}", from.unwrap_or_default(), to.unwrap_or_default()) });
+        }
+        cover_lines.push(t.count("count.sessions", numbered.len()));
         
-        // Include git status information for context
-        content.push_str(&self.generate_git_status());
-        content.push_str("\n\n");
+        // The same project is always the same book, so readers replace the old edition
+        let digest = Sha256::digest(format!("persistent-code-lore\n{}", self.config.project_name).as_bytes());
+        let mut bytes = [0u8; 16];
+        bytes.copy_from_slice(&digest[..16]);
+        let modified = last.and_then(|millis| DisplayZone::Utc.format_millis(millis, "%Y-%m-%dT%H:%M:%SZ"));
+        Ok(Book {
+            title: self.config.sanitize_path(&self.redact(&self.lore_title())),
+            author: self.config.project_name.clone(),
+            language: self.config.locale.clone(),
+            identifier: format!("urn:uuid:{}", uuid::Builder::from_custom_bytes(bytes).into_uuid()),
+            modified: modified.unwrap_or_else(|| DisplayZone::Utc.format_now("%Y-%m-%dT%H:%M:%SZ")),
+            cover_lines,
+            chapters,
+        })
+    }
+    
+    /// Put the document together, with or without the historical sessions
+    /// (which get their own pages when publishing).
+    fn build_document(
+        &self,
+        sessions: &[ComposerData],
+        generations: &[ChatGeneration],
+        prompts: &[ChatPrompt],
+        include_sessions: bool,
+    ) -> Result<Document> {
+        let mut document = Document::default();
+        self.build_sections(sessions, generations, prompts, include_sessions, &mut |blocks| {
+            document.extend(blocks);
+            Ok(())
+        })?;
+        Ok(document)
+    }
+    
+    /// Build the document a section at a time, handing each one to `emit`
+    /// as soon as it's ready. The historical sessions (most of any lore)
+    /// are handed over one session at a time.
+    fn build_sections(
+        &self,
+        sessions: &[ComposerData],
+        generations: &[ChatGeneration],
+        prompts: &[ChatPrompt],
+        include_sessions: bool,
+        emit: &mut dyn FnMut(Vec<Block>) -> Result<()>,
+    ) -> Result<()> {
+        let context = SectionContext {
+            config: &self.config,
+            strings: &self.strings,
+            sessions,
+            generations,
+            prompts,
+            include_sessions,
+            generator: self,
+        };
+        // Headings go as deep as HEADING_LEVEL and HEADING_DEPTH put them
+        let shift = self.config.heading_level.saturating_sub(1);
+        let deepest = self.config.heading_depth;
+        let emit = &mut |blocks: Vec<Block>| emit(self.scrub_blocks(nest_headings(blocks, shift, deepest)));
+        for section in self.sections.pipeline(&self.config.section_order())? {
+            // Long messages are cut or summarized the way MESSAGE_POLICY says for this section
+            match self.message_policies.for_section(section.name()) {
+                MessagePolicy::Full => section.render(&context, emit)?,
+                policy => section.render(&context, &mut |blocks| emit(self.apply_message_policy(policy, blocks)))?,
+            }
+        }
+        Ok(())
+    }
+    
+    /// Apply a message policy to every message in `blocks`, including
+    /// ones inside collapsed blocks.
+    fn apply_message_policy(&self, policy: MessagePolicy, blocks: Vec<Block>) -> Vec<Block> {
+        let mut applied = Vec::with_capacity(blocks.len());
+        for block in blocks {
+            match block {
+                Block::Message { speaker, sent_at, text } => {
+                    applied.extend(self.long_message(policy, speaker, sent_at, text));
+                }
+                Block::Details { summary, blocks } => {
+                    applied.push(Block::Details { summary, blocks: self.apply_message_policy(policy, blocks) });
+                }
+                other => applied.push(other),
+            }
+        }
+        applied
+    }
+    
+    /// A message under a policy: as it is if it's short enough, or else its
+    /// first lines (or a summary) with the rest collapsed underneath.
+    fn long_message(&self, policy: MessagePolicy, speaker: String, sent_at: Option<String>, text: String) -> Vec<Block> {
+        let t = &self.strings;
+        let lines = match policy {
+            MessagePolicy::Full => return vec![Block::Message { speaker, sent_at, text }],
+            MessagePolicy::Truncate(lines) | MessagePolicy::Summarize(lines) => lines,
+        };
+        let Some((head, rest, hidden)) = split_message_lines(&text, lines) else {
+            return vec![Block::Message { speaker, sent_at, text }];
+        };
         
-        // Tell readers where we got all this data from
-        content.push_str(&self.generate_data_sources());
-        content.push_str("\n\n");
+        if let MessagePolicy::Summarize(_) = policy {
+            let summary = self.summarize(&text).unwrap_or(head);
+            return vec![
+                Block::Message { speaker, sent_at, text: format!("_{}_: {}", t.get("messages.summary"), summary) },
+                Block::Details { summary: t.get("messages.full").to_string(), blocks: markdown_blocks(&text) },
+            ];
+        }
+        vec![
+            Block::Message { speaker, sent_at, text: head },
+            Block::Details { summary: t.count("count.more_lines", hidden), blocks: markdown_blocks(&rest) },
+        ]
+    }
+    
+    /// A summary of a long message: what SUMMARIZE_COMMAND makes of it (with
+    /// secrets taken out first, since it may send the text elsewhere), or
+    /// the first sentences of its prose. A failing command is reported once
+    /// and not run again.
+    fn summarize(&self, text: &str) -> Option<String> {
+        let command = self.config.summarize_command.trim();
+        if !command.is_empty() && !self.summarize_failed.load(Ordering::Relaxed) {
+            let dir = self.config.project_file("");
+            match pipe_through(command, &dir, &self.config.redact_secrets(text), "SUMMARIZE_COMMAND") {
+                Ok(summary) if !summary.trim().is_empty() => {
+                    return Some(summary.split_whitespace().collect::<Vec<_>>().join(" "));
+                }
+                Ok(_) => {}
+                Err(error) => {
+                    eprintln!("Warning: {:#} - summarizing from the text instead", error);
+                    self.summarize_failed.store(true, Ordering::Relaxed);
+                }
+            }
+        }
+        summary(text)
+    }
+    
+    /// The sections names in SECTIONS can refer to, in their default order.
+    pub fn section_names(&self) -> Vec<&str> {
+        self.sections.names()
+    }
+    
+    /// Check that SECTIONS only names sections this generator knows.
+    pub fn check_sections(&self) -> Result<()> {
+        self.sections.pipeline(&self.config.section_order()).map(|_| ())
+    }
+    
+    fn generate_header(&self) -> Block {
+        Block::heading(1, self.strings.get("header.title"))
+    }
+    
+    fn generate_metadata(
+        &self,
+        sessions: &[ComposerData],
+    ) -> Result<Vec<Block>> {
+        let total_sessions: usize = sessions.iter().map(|s| s.all_composers.len()).sum();
         
-        // Add some final notes and context
-        content.push_str(&self.generate_notes());
-        content.push_str("\n\n");
+        let t = &self.strings;
+        let mut metadata = vec![
+            format!("**{}**: {}", t.get("metadata.created"), self.config.display_zone().format_now("%B %d, %Y, %H:%M %Z")),
+            format!("**{}**: {}", t.get("metadata.project"), self.config.project_name),
+            format!("**{}**: {}", t.get("metadata.branch"), self.config.project_branch),
+            format!("**{}**: {}", t.get("metadata.workspace"), self.config.sanitize_path(&self.config.project_path)),
+            format!("**{}**: {}", t.get("metadata.file_type"), t.get("metadata.file_type_value")),
+            format!("**{}**: {}", t.get("metadata.purpose"), t.get("metadata.purpose_value")),
+            format!(
+                "**{}**: {}",
+                t.get("metadata.total_sessions"),
+                t.format("metadata.total_sessions_value", &[("count", &total_sessions.to_string())])
+            ),
+        ];
         
-        // Finish with a nice footer
-        content.push_str(&self.generate_footer());
+        // Which models did the work, and roughly what it cost
+        let usage = total_usage(sessions, &self.config.model_pricing);
+        if !usage.is_empty() {
+            let models: Vec<String> = usage
+                .iter()
+                .map(|model_usage| {
+                    let responses = t.format("count.responses", &[("count", &model_usage.responses.to_string())]);
+                    format!("{} ({})", model_usage.model, responses)
+                })
+                .collect();
+            metadata.push(format!("**{}**: {}", t.get("metadata.models_used"), models.join(", ")));
+            
+            if let Some(cost) = total_cost(&usage) {
+                metadata.push(format!("**{}**: ${:.2}", t.get("metadata.estimated_spend"), cost));
+            }
+        }
+        
+        if self.config.include_system_info {
+            metadata.push(format!("**{}**: {}", t.get("metadata.os"), std::env::consts::OS));
+            metadata.push(format!(
+                "**{}**: {}",
+                t.get("metadata.shell"),
+                shell_name(std::env::var("SHELL").ok())
+            ));
+        }
         
-        Ok(content)
+        Ok(vec![Block::heading(2, t.get("metadata.title")), Block::bullets(metadata)])
     }
     
-    fn generate_header(&self) -> String {
-        format!("# Chat History - Consolidated\n")
+    /// What the project is: PROJECT_CONTEXT_FILE if there is one, or
+    /// else what its manifests and files say - description, languages,
+    /// frameworks, main dependencies and entry points. Nothing at all if
+    /// neither says anything.
+    fn generate_project_context(&self) -> Result<Vec<Block>> {
+        let mut blocks = match self.project_file(&self.config.project_context_file)? {
+            Some(blocks) => blocks,
+            None => {
+                let summary = summarize_project(&self.config.project_file(""));
+                let mut blocks = Vec::new();
+                if let Some(description) = summary.description {
+                    blocks.push(Block::Paragraph(description));
+                }
+                let languages: Vec<String> = summary
+                    .languages
+                    .iter()
+                    .map(|(language, share)| format!("{} ({:.0}%)", language, share))
+                    .collect();
+                let code = |items: &[String]| items.iter().map(|item| format!("`{}`", item)).collect::<Vec<_>>();
+                let fields: Vec<(String, String)> = [
+                    ("context.languages", languages),
+                    ("context.frameworks", summary.frameworks.clone()),
+                    ("context.dependencies", code(&summary.dependencies)),
+                    ("context.entry_points", code(&summary.entry_points)),
+                ]
+                .into_iter()
+                .filter(|(_, values)| !values.is_empty())
+                .map(|(label, values)| (self.strings.get(label).to_string(), values.join(", ")))
+                .collect();
+                if !fields.is_empty() {
+                    blocks.push(Block::Fields(fields));
+                }
+                blocks
+            }
+        };
+        if blocks.is_empty() {
+            return Ok(blocks);
+        }
+        blocks.insert(0, Block::heading(2, self.strings.get("context.title")));
+        Ok(blocks)
     }
     
-    fn generate_metadata(
+    /// What the workspace had open, with files shown relative to its
+    /// folder: the editor tabs, the top-level directories most of the
+    /// recently opened files are in, and those files.
+    fn generate_workspace(&self) -> Vec<Block> {
+        let Some(info) = self.workspace.as_ref().filter(|info| !info.is_empty()) else {
+            return Vec::new();
+        };
+        let t = &self.strings;
+        let file = |path: &String| {
+            format!("`{}`", info.relative(path).map(str::to_string).unwrap_or_else(|| self.config.sanitize_path(path)))
+        };
+        let mut fields = Vec::new();
+        if let Some(uri) = &info.folder_uri {
+            let folder = info.folder.as_deref().unwrap_or(uri);
+            fields.push((t.get("workspace.folder").to_string(), self.config.sanitize_path(folder)));
+        }
+        if !info.open_files.is_empty() {
+            let open: Vec<String> = info.open_files.iter().take(10).map(file).collect();
+            fields.push((t.get("workspace.open_files").to_string(), open.join(", ")));
+        }
+        let areas: Vec<String> = info
+            .areas()
+            .iter()
+            .take(5)
+            .map(|(area, count)| format!("`{}` ({})", area, t.count("count.files", *count)))
+            .collect();
+        if !areas.is_empty() {
+            fields.push((t.get("workspace.areas").to_string(), areas.join(", ")));
+        }
+        
+        let mut blocks = vec![
+            Block::heading(2, t.get("workspace.title")),
+            Block::Paragraph(t.get("workspace.note").to_string()),
+            Block::Fields(fields),
+        ];
+        if !info.recent_files.is_empty() {
+            blocks.push(Block::heading(3, t.get("workspace.recent_files")));
+            blocks.push(Block::numbered(info.recent_files.iter().take(15).map(file).collect()));
+        }
+        blocks
+    }
+    
+    /// A markdown file in the project, as blocks, or `None` if it isn't
+    /// there (or no file is configured).
+    fn project_file(&self, path: &str) -> Result<Option<Vec<Block>>> {
+        if path.trim().is_empty() {
+            return Ok(None);
+        }
+        let path = self.config.project_file(path);
+        match std::fs::read_to_string(&path) {
+            Ok(text) => Ok(Some(markdown_blocks(&self.redact(&text)))),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error).with_context(|| format!("Couldn't read {}", path.display())),
+        }
+    }
+    
+    fn generate_historical_sessions(
         &self,
         sessions: &[ComposerData],
-    ) -> Result<String> {
-        let total_sessions: usize = sessions.iter().map(|s| s.all_composers.len()).sum();
-        let current_time = Utc::now();
-        
-        let mut metadata = format!(
-            "## Metadata\n\
-            - **Created**: {}\n\
-            - **Project**: {}\n\
-            - **Branch**: {}\n\
-            - **Workspace**: {}\n\
-            - **File Type**: Consolidated Chat History\n\
-            - **Purpose**: Knowledge base storage for chat interactions\n\
-            - **Total Chat Sessions**: {} historical sessions + current session\n",
-            current_time.format("%B %d, %Y, %H:%M %Z"),
-            self.config.project_name,
-            self.config.project_branch,
-            self.config.sanitize_path(&self.config.project_path),
-            total_sessions
+        emit: &mut dyn FnMut(Vec<Block>) -> Result<()>,
+    ) -> Result<()> {
+        let t = &self.strings;
+        let mut blocks = vec![Block::heading(2, t.get("sessions.title"))];
+        
+        let numbered = self.numbered_sessions(sessions);
+        let related = RelatedSessions::find(&numbered, self.config.related_sessions);
+        self.progress.start(Stage::Rendering, Some(numbered.len()));
+        let mut rendered = 0;
+        let mut render = |number: usize, session: &ChatSession, level: usize| -> Result<Vec<Block>> {
+            self.progress.check()?;
+            let mut blocks = self.generate_session(number, session, level);
+            blocks.extend(self.generate_related_sessions(related.of(&session.composer_id)));
+            rendered += 1;
+            self.progress.advance(Stage::Rendering, rendered);
+            Ok(blocks)
+        };
+        
+        let grouping = self.config.session_grouping();
+        if grouping == GroupBy::None {
+            blocks.extend(self.generate_table_of_contents(&numbered));
+            emit(blocks)?;
+            for (number, session) in numbered {
+                emit(render(number, session, 3)?)?;
+            }
+            self.progress.finish(Stage::Rendering);
+            return Ok(());
+        }
+        
+        // Group under a heading per day/week/month/topic/tag, with a quick summary of each
+        let zone = self.config.display_zone();
+        let mut groups = group_by_key(numbered, |(_, session)| match grouping {
+            GroupBy::Topic => session_topic(session),
+            GroupBy::Tag => self.tags.of(&session.composer_id).first().map(|tag| tag.to_string()).unwrap_or_default(),
+            _ => grouping.date_key(session, &zone).unwrap_or_default(),
+        });
+        if grouping != GroupBy::Topic {
+            // Untagged sessions (an empty key) go last
+            groups.sort_by(|a, b| a.0.is_empty().cmp(&b.0.is_empty()).then(a.0.cmp(&b.0)));
+        }
+        
+        // The contents list follows the grouped order so it reads top to bottom
+        let listed: Vec<(usize, &ChatSession)> = groups.iter().flat_map(|(_, members)| members.iter().copied()).collect();
+        blocks.extend(self.generate_table_of_contents(&listed));
+        emit(blocks)?;
+        
+        for (group, members) in groups {
+            let group = if group.is_empty() && grouping == GroupBy::Tag { t.get("tags.untagged").to_string() } else { group };
+            let messages: usize = members.iter().map(|(_, session)| session.messages.len()).sum();
+            emit(vec![Block::heading(
+                3,
+                format!(
+                    "{} ({}, {})",
+                    group,
+                    t.count("count.sessions", members.len()),
+                    t.count("count.messages", messages)
+                ),
+            )])?;
+            for (number, session) in members {
+                emit(render(number, session, 4)?)?;
+            }
+        }
+        self.progress.finish(Stage::Rendering);
+        
+        Ok(())
+    }
+    
+    /// Every exchange that looks like a decision being made, oldest first,
+    /// with the date, the session (linked, when the sessions are in the
+    /// same document) and the sentence that made it.
+    fn generate_decisions(&self, sessions: &[ComposerData], link: bool) -> Vec<Block> {
+        if !self.config.include_decisions {
+            return Vec::new();
+        }
+        
+        let t = &self.strings;
+        let zone = self.config.display_zone();
+        let mut decisions: Vec<(i64, String)> = Vec::new();
+        for (number, session) in self.numbered_sessions(sessions) {
+            let title = self.session_title(number, session);
+            let title = if link { format!("[{}](#{})", title, heading_anchor(&title)) } else { title };
+            for decision in session_decisions(session) {
+                let speaker = match decision.role {
+                    MessageRole::User => t.get("role.user"),
+                    MessageRole::Assistant => t.get("role.assistant"),
+                };
+                let date = zone.format_millis(decision.timestamp, "%Y-%m-%d").unwrap_or_default();
+                let excerpt = self.redact(&decision.excerpt);
+                decisions.push((decision.timestamp, format!("**{}** - {} ({}): {}", date, title, speaker, excerpt)));
+            }
+        }
+        if decisions.is_empty() {
+            return Vec::new();
+        }
+        
+        // Stable, so decisions made in the same millisecond keep session order
+        decisions.sort_by_key(|(timestamp, _)| *timestamp);
+        vec![
+            Block::heading(2, t.get("decisions.title")),
+            Block::bullets(decisions.into_iter().map(|(_, item)| item).collect()),
+        ]
+    }
+    
+    /// Every TODO and piece of work left for later in the chats, oldest
+    /// first, with the session it came up in (linked, when the sessions
+    /// are in the same document) and the sentence that mentioned it.
+    fn generate_follow_ups(
+        &self,
+        sessions: &[ComposerData],
+        generations: &[ChatGeneration],
+        prompts: &[ChatPrompt],
+        link: bool,
+    ) -> Vec<Block> {
+        if !self.config.include_followups {
+            return Vec::new();
+        }
+        
+        let t = &self.strings;
+        let zone = self.config.display_zone();
+        let titles: HashMap<&str, String> = self
+            .numbered_sessions(sessions)
+            .into_iter()
+            .map(|(number, session)| (session.composer_id.as_str(), self.session_title(number, session)))
+            .collect();
+        let items: Vec<String> = find_follow_ups(sessions, generations, prompts)
+            .into_iter()
+            .map(|follow_up| {
+                let source = match follow_up.source {
+                    FollowUpSource::Message { role: MessageRole::User, .. } => t.get("role.user"),
+                    FollowUpSource::Message { role: MessageRole::Assistant, .. } => t.get("role.assistant"),
+                    FollowUpSource::Prompt => t.get("followups.prompt"),
+                    FollowUpSource::Generation { .. } => t.get("followups.generation"),
+                };
+                let title = follow_up.session_id.as_deref().and_then(|id| titles.get(id)).map(|title| {
+                    if link { format!("[{}](#{}) ", title, heading_anchor(title)) } else { format!("{} ", title) }
+                });
+                let date = follow_up
+                    .timestamp
+                    .and_then(|timestamp| zone.format_millis(timestamp, "%Y-%m-%d"))
+                    .map(|date| format!("**{}** - ", date));
+                format!(
+                    "{}{}({}): {}",
+                    date.unwrap_or_default(),
+                    title.unwrap_or_default(),
+                    source,
+                    self.redact(&follow_up.excerpt)
+                )
+            })
+            .collect();
+        if items.is_empty() {
+            return Vec::new();
+        }
+        
+        vec![Block::heading(2, t.get("followups.title")), Block::bullets(items)]
+    }
+    
+    /// The generations of the kinds GENERATION_KINDS picks, a list for
+    /// each kind, newest first, with the date and how each one starts.
+    fn generate_generations(&self, generations: &[ChatGeneration]) -> Vec<Block> {
+        let t = &self.strings;
+        let zone = self.config.display_zone();
+        let mut blocks = Vec::new();
+        for kind in self.config.generation_kind_list() {
+            let mut matching: Vec<&ChatGeneration> = generations
+                .iter()
+                .filter(|generation| generation.kind.unwrap_or_else(|| classify(&generation.text_description).kind) == kind)
+                .collect();
+            if matching.is_empty() {
+                continue;
+            }
+            
+            matching.sort_by_key(|generation| std::cmp::Reverse(generation.unix_ms));
+            let items: Vec<String> = matching
+                .iter()
+                .take(20)
+                .map(|generation| {
+                    // Code has no prose to quote, so it starts with its first line
+                    let text = &generation.text_description;
+                    let mut quote = message_excerpt(text);
+                    if quote.is_empty() {
+                        quote = text
+                            .lines()
+                            .find(|line| !line.trim().is_empty() && !line.trim().starts_with("```"))
+                            .map(excerpt)
+                            .unwrap_or_default();
+                    }
+                    let date = zone.format_millis(generation.unix_ms, "%Y-%m-%d").unwrap_or_default();
+                    format!("**{}** - {}", date, self.config.sanitize_path(&self.redact(&quote)))
+                })
+                .collect();
+            let title = t.get(&format!("generation_kind.{}", kind.as_str())).to_string();
+            blocks.push(Block::heading(3, format!("{} ({})", title, matching.len())));
+            blocks.push(Block::bullets(items));
+        }
+        if blocks.is_empty() {
+            return blocks;
+        }
+        
+        blocks.splice(
+            0..0,
+            [Block::heading(2, t.get("generations.title")), Block::Paragraph(t.get("generations.description").to_string())],
         );
+        blocks
+    }
+    
+    /// The questions asked in the chats with the answers that were taken,
+    /// each linked to the sessions it came up in (when the sessions are
+    /// in the same document).
+    fn generate_faq(&self, sessions: &[ComposerData], link: bool) -> Vec<Block> {
+        if !self.config.include_faq {
+            return Vec::new();
+        }
         
-        if self.config.include_system_info {
-            metadata.push_str(&format!(
-                "- **OS**: {}\n\
-                - **Shell**: {}\n",
-                std::env::consts::OS,
-                std::env::var("SHELL").unwrap_or_else(|_| "unknown".to_string())
+        let t = &self.strings;
+        let numbered = self.numbered_sessions(sessions);
+        let ordered: Vec<&ChatSession> = numbered.iter().map(|(_, session)| *session).collect();
+        let titles: HashMap<&str, String> = numbered
+            .iter()
+            .map(|(number, session)| (session.composer_id.as_str(), self.session_title(*number, session)))
+            .collect();
+        let entries = distill_faq(&ordered);
+        if entries.is_empty() {
+            return Vec::new();
+        }
+        
+        let mut blocks = vec![Block::heading(2, t.get("faq.title"))];
+        for entry in entries {
+            let asked_in: Vec<String> = entry
+                .sessions
+                .iter()
+                .filter_map(|id| titles.get(id.as_str()))
+                .map(|title| if link { format!("[{}](#{})", title, heading_anchor(title)) } else { title.clone() })
+                .collect();
+            blocks.push(Block::Paragraph(format!("**{}: {}**", t.get("faq.question"), self.redact(&entry.question))));
+            blocks.push(Block::Paragraph(format!(
+                "{}: {} ({})",
+                t.get("faq.answer"),
+                self.redact(&entry.answer),
+                asked_in.join(", ")
+            )));
+        }
+        blocks
+    }
+    
+    /// The errors pasted into the chats, each with the fix that was taken,
+    /// the files it changed and the sessions it came up in (linked, when
+    /// the sessions are in the same document).
+    /// The Extraction Warnings appendix: each record that was skipped or
+    /// patched up, so readers know what to take with a pinch of salt.
+    /// Rendered near the end so it has what rendering the sessions found.
+    fn generate_warnings(&self) -> Vec<Block> {
+        let warnings = self.warnings();
+        if !self.config.include_warnings || warnings.is_empty() {
+            return Vec::new();
+        }
+        
+        let t = &self.strings;
+        let items = warnings
+            .iter()
+            .map(|warning| {
+                let location = self.config.sanitize_path(&self.redact(&warning.location));
+                format!("**{}** - {}: {}", warning.kind, location, self.redact(&warning.message))
+            })
+            .collect();
+        vec![
+            Block::heading(2, t.get("warnings.title")),
+            Block::Paragraph(t.get("warnings.description").to_string()),
+            Block::bullets(items),
+        ]
+    }
+    
+    fn generate_troubleshooting(&self, sessions: &[ComposerData], link: bool) -> Vec<Block> {
+        if !self.config.include_troubleshooting {
+            return Vec::new();
+        }
+        
+        let t = &self.strings;
+        let zone = self.config.display_zone();
+        let numbered = self.numbered_sessions(sessions);
+        let ordered: Vec<&ChatSession> = numbered.iter().map(|(_, session)| *session).collect();
+        let titles: HashMap<&str, String> = numbered
+            .iter()
+            .map(|(number, session)| (session.composer_id.as_str(), self.session_title(*number, session)))
+            .collect();
+        let entries = troubleshooting_log(&ordered);
+        if entries.is_empty() {
+            return Vec::new();
+        }
+        
+        let mut blocks = vec![
+            Block::heading(2, t.get("troubleshooting.title")),
+            Block::Paragraph(t.get("troubleshooting.description").to_string()),
+        ];
+        for entry in entries {
+            let seen_in: Vec<String> = entry
+                .sessions
+                .iter()
+                .filter_map(|id| titles.get(id.as_str()))
+                .map(|title| if link { format!("[{}](#{})", title, heading_anchor(title)) } else { title.clone() })
+                .collect();
+            let error = self.config.sanitize_path(&self.redact(&entry.signature));
+            let mut fields = vec![
+                (t.get("troubleshooting.error").to_string(), format!("`{}`", error)),
+                (t.get("troubleshooting.fix").to_string(), self.config.sanitize_path(&self.redact(&entry.resolution))),
+            ];
+            if !entry.files.is_empty() {
+                let files: Vec<String> =
+                    entry.files.iter().map(|file| format!("`{}`", self.config.sanitize_path(file))).collect();
+                fields.push((t.get("troubleshooting.files").to_string(), files.join(", ")));
+            }
+            let date = zone.format_millis(entry.timestamp, "%Y-%m-%d").unwrap_or_default();
+            fields.push((t.get("troubleshooting.seen_in").to_string(), format!("{} ({})", seen_in.join(", "), date)));
+            blocks.push(Block::Fields(fields));
+        }
+        blocks
+    }
+    
+    /// Each tag with links to the sessions that have it.
+    fn generate_tag_index(&self, sessions: &[ComposerData]) -> Vec<Block> {
+        let mut by_tag: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+        for (number, session) in self.numbered_sessions(sessions) {
+            for tag in self.tags.of(&session.composer_id) {
+                let title = self.session_title(number, session);
+                by_tag.entry(tag).or_default().push(format!("[{}](#{})", title, heading_anchor(&title)));
+            }
+        }
+        if by_tag.is_empty() {
+            return Vec::new();
+        }
+        
+        let items = by_tag.into_iter().map(|(tag, links)| format!("**{}**: {}", tag, links.join(", "))).collect();
+        vec![Block::heading(2, self.strings.get("tags.title")), Block::bullets(items)]
+    }
+    
+    /// A Mermaid timeline or gantt chart of sessions and the commits made
+    /// while they were going on.
+    fn generate_timeline(&self, sessions: &[ComposerData]) -> Vec<Block> {
+        let style = self.config.timeline_style();
+        if style == TimelineStyle::None {
+            return Vec::new();
+        }
+        
+        let numbered = self.numbered_sessions(sessions);
+        let first = numbered.iter().map(|(_, session)| session.created_at).min();
+        let last = numbered.iter().map(|(_, session)| session.last_updated_at.max(session.created_at)).max();
+        let commit_times: Vec<i64> = match (first, last) {
+            (Some(first), Some(last)) => commits_between(&self.config.project_path, first, last)
+                .into_iter()
+                .map(|(time, _)| time)
+                .collect(),
+            _ => Vec::new(),
+        };
+        
+        let diagram = mermaid_diagram(style, &numbered, &commit_times, &self.config.display_zone(), &self.strings);
+        if diagram.is_empty() {
+            return Vec::new();
+        }
+        vec![Block::heading(2, self.strings.get("timeline.title")), Block::code("mermaid", diagram)]
+    }
+    
+    /// The knowledge graph as a Mermaid diagram, when it's switched on.
+    fn generate_knowledge_graph(&self, sessions: &[ComposerData]) -> Vec<Block> {
+        if !self.config.graph_in_markdown {
+            return Vec::new();
+        }
+        
+        let graph = KnowledgeGraph::build(sessions, &self.config);
+        if graph.is_empty() {
+            return Vec::new();
+        }
+        vec![Block::heading(2, self.strings.get("graph.title")), Block::code("mermaid", graph.to_mermaid(60))]
+    }
+    
+    /// Put every session in the configured order and number it.
+    /// Numbers are assigned after sorting, so "Session 1" is always the first
+    /// one listed, and every section that mentions sessions agrees on them.
+    fn numbered_sessions<'a>(&self, sessions: &'a [ComposerData]) -> Vec<(usize, &'a ChatSession)> {
+        let mut ordered: Vec<&ChatSession> = sessions
+            .iter()
+            .flat_map(|session_data| session_data.all_composers.iter())
+            .collect();
+        
+        let (sort_by, descending) = self.config.session_sorting();
+        sort_by.sort(&mut ordered, descending);
+        self.curation.reorder(&mut ordered);
+        
+        ordered.into_iter().enumerate().map(|(i, session)| (i + 1, session)).collect()
+    }
+    
+    /// A linked list of sessions, so long histories are easy to jump around in.
+    fn generate_table_of_contents(&self, numbered: &[(usize, &ChatSession)]) -> Vec<Block> {
+        if numbered.is_empty() {
+            return Vec::new();
+        }
+        
+        let links: Vec<String> = numbered
+            .iter()
+            .map(|(number, session)| {
+                let title = self.session_title(*number, session);
+                format!("[{}](#{})", title, heading_anchor(&title))
+            })
+            .collect();
+        vec![
+            Block::Paragraph(format!("**{}**", self.strings.get("sessions.contents"))),
+            Block::bullets(links),
+        ]
+    }
+    
+    /// Links to the sessions related to one, under its transcript, so
+    /// readers can follow a thread through the history. Only the single
+    /// document has them; published pages and site pages stand alone.
+    fn generate_related_sessions(&self, related: &[(usize, &ChatSession)]) -> Vec<Block> {
+        if related.is_empty() {
+            return Vec::new();
+        }
+        
+        let links: Vec<String> = related
+            .iter()
+            .map(|(number, session)| {
+                let title = self.session_title(*number, session);
+                format!("[{}](#{})", title, heading_anchor(&title))
+            })
+            .collect();
+        vec![
+            Block::Paragraph(format!("**{}**", self.strings.get("sessions.related"))),
+            Block::bullets(links),
+        ]
+    }
+    
+    /// The text of a session's heading, like "Session 3: Fix login bug",
+    /// scrubbed already so the anchors made from it match the heading.
+    fn session_title(&self, number: usize, session: &ChatSession) -> String {
+        self.scrub(&format!("{} {}: {}", self.strings.get("sessions.session"), number, self.curation.name(session)))
+    }
+    
+    /// Render one session: its header, models, files, edits and transcript.
+    /// `level` is the heading level to use for the session title.
+    fn generate_session(&self, number: usize, session: &ChatSession, level: usize) -> Vec<Block> {
+        let t = &self.strings;
+        let zone = self.config.display_zone();
+        let created_at = zone.format_millis(session.created_at, "%B %d, %Y, %H:%M:%S %Z").unwrap_or_else(|| {
+            self.warnings.push(Warning::new(
+                WarningKind::InvalidTimestamp,
+                format!("session {}", session.composer_id),
+                format!("its creation time ({}) isn't a date that can be shown", session.created_at),
             ));
+            t.get("sessions.unknown_date").to_string()
+        });
+        
+        let title = self.session_title(number, session);
+        let mut properties = vec![
+            (t.get("sessions.date").to_string(), created_at),
+            (t.get("sessions.session_id").to_string(), session.composer_id.clone()),
+            (t.get("sessions.context").to_string(), session_topic(session)),
+        ];
+        let tags = self.tags.of(&session.composer_id);
+        if !tags.is_empty() {
+            properties.push((t.get("sessions.tags").to_string(), tags.join(", ")));
+        }
+        let mut blocks = vec![Block::Heading {
+            level,
+            id: Some(heading_anchor(&title)),
+            text: title,
+            timestamp: zone.local_millis(session.created_at),
+            properties,
+        }];
+        
+        if let Some(summary) = self.curation.summary(&session.composer_id) {
+            blocks.push(Block::Fields(vec![(t.get("sessions.summary").to_string(), self.redact(summary))]));
+        }
+        blocks.extend(self.generate_session_models(session));
+        blocks.extend(self.generate_files_discussed(session));
+        blocks.extend(self.generate_edits(session));
+        if self.config.include_extra_fields {
+            blocks.extend(self.generate_additional_fields(session));
+        }
+        
+        // Sources that keep the whole conversation get their transcript included
+        if !session.messages.is_empty() {
+            blocks.extend(self.generate_transcript(&session.messages));
+        }
+        
+        blocks
+    }
+    
+    fn generate_session_models(&self, session: &ChatSession) -> Vec<Block> {
+        let usage = session_usage(session, &self.config.model_pricing);
+        if usage.is_empty() {
+            return Vec::new();
+        }
+        
+        let models: Vec<String> = usage.iter().map(|model_usage| describe_usage(model_usage, &self.strings)).collect();
+        let mut fields = vec![(self.strings.get("sessions.models").to_string(), models.join(", "))];
+        if let Some(cost) = total_cost(&usage) {
+            fields.push((self.strings.get("sessions.estimated_cost").to_string(), format!("${:.2}", cost)));
+        }
+        
+        vec![Block::Fields(fields)]
+    }
+    
+    fn generate_model_usage(&self, sessions: &[ComposerData]) -> Vec<Block> {
+        let usage = total_usage(sessions, &self.config.model_pricing);
+        if usage.is_empty() {
+            return Vec::new();
+        }
+        
+        let t = &self.strings;
+        let mut blocks = vec![
+            Block::heading(2, t.get("usage.title")),
+            Block::heading(3, t.get("usage.by_model")),
+            Block::bullets(usage.iter().map(|model_usage| describe_usage(model_usage, t)).collect()),
+            Block::heading(3, t.get("usage.by_month")),
+        ];
+        
+        let mut months = Vec::new();
+        for (month, usage) in monthly_usage(sessions, &self.config.model_pricing) {
+            let responses: usize = usage.iter().map(|model_usage| model_usage.responses).sum();
+            let mut line = format!(
+                "**{}**: {}",
+                month,
+                t.format("count.responses", &[("count", &responses.to_string())])
+            );
+            if let Some(cost) = total_cost(&usage) {
+                line.push_str(&format!(", {} ${:.2}", t.get("usage.estimated"), cost));
+            }
+            months.push(line);
         }
+        blocks.push(Block::bullets(months));
         
-        Ok(metadata)
-    }
-    
-    fn generate_project_context(&self) -> String {
-        format!(
-            "## Project Context\n\
-            This is a TypeScript-based MCP (Model Context Protocol) server project that provides local LLM proxy functionality with orchestration capabilities. The project includes:\n\n\
-            - MCP server implementation\n\
-            - Orchestrator service for tool management\n\
-            - RAG (Retrieval Augmented Generation) service\n\
-            - Agentic tools and services\n\
-            - Sonar integration\n\
-            - Web search patterns\n\
-            - Validation services\n"
-        )
-    }
-    
-    fn generate_historical_sessions(&self, sessions: &[ComposerData]) -> Result<String> {
-        let mut content = String::from("## Historical Chat Sessions\n\n");
-        
-        for session_data in sessions {
-            for (i, session) in session_data.all_composers.iter().enumerate() {
-                let created_at = DateTime::from_timestamp_millis(session.created_at)
-                    .unwrap_or_else(|| Utc::now());
-                
-                content.push_str(&format!(
-                    "### Session {}: {}\n\
-                    **Date**: {}\n\
-                    **Session ID**: {}\n\
-                    **Context**: {}\n\n",
-                    i + 1,
-                    session.name,
-                    created_at.format("%B %d, %Y, %H:%M:%S UTC"),
-                    session.composer_id,
-                    self.generate_session_context(session)
+        if usage.iter().any(|model_usage| model_usage.estimated) {
+            blocks.push(Block::Paragraph(format!("*{}*", t.get("usage.estimated_note"))));
+        }
+        
+        blocks
+    }
+    
+    fn generate_files_discussed(&self, session: &ChatSession) -> Vec<Block> {
+        let files = files_discussed(session);
+        if files.is_empty() {
+            return Vec::new();
+        }
+        
+        let mut items = Vec::new();
+        for file in files {
+            let ranges: Vec<String> = file
+                .line_ranges
+                .iter()
+                .map(|(start, end)| {
+                    if start == end {
+                        format!("L{}", start)
+                    } else {
+                        format!("L{}-{}", start, end)
+                    }
+                })
+                .collect();
+            
+            if ranges.is_empty() {
+                items.push(format!("`{}`", self.config.sanitize_path(&file.path)));
+            } else {
+                items.push(format!(
+                    "`{}` ({})",
+                    self.config.sanitize_path(&file.path),
+                    ranges.join(", ")
                 ));
             }
         }
         
-        Ok(content)
-    }
-    
-    fn generate_session_context(&self, session: &crate::ChatSession) -> String {
-        match session.name.as_str() {
-            name if name.contains("orchestrator") => "MCP orchestrator analysis and architecture discussion".to_string(),
-            name if name.contains("RAG") => "RAG (Retrieval Augmented Generation) task implementation".to_string(),
-            name if name.contains("agentic") => "Agentic behavior enhancement and tool integration".to_string(),
-            name if name.contains("memory") => "Memory management and storage implementation".to_string(),
-            name if name.contains("delegate") => "Delegation and orchestration analysis".to_string(),
-            name if name.contains("enhance") => "Server orchestration enhancements".to_string(),
-            name if name.contains("clarification") => "Action requirements clarification".to_string(),
-            name if name.contains("history") => "Knowledge management and chat history consolidation".to_string(),
-            _ => "General project development and discussion".to_string(),
-        }
-    }
-    
-    fn generate_current_session(&self) -> String {
-        format!(
-            "## Current Session\n\n\
-            ### Current Knowledge Management Session\n\
-            **Date**: {}\n\
-            **Context**: Knowledge management and chat history consolidation request\n\n\
-            **Actions Taken**:\n\
-            1. **Configuration Loading**: Loaded settings from configuration file\n\
-            2. **Database Connection**: Connected to SQLite database\n\
-            3. **Data Extraction**: Extracted chat sessions, generations, and prompts\n\
-            4. **Markdown Generation**: Generated consolidated markdown with metadata\n\
-            5. **File Output**: Created consolidated chat history file\n\n\
-            **Technical Details**:\n\
-            - Project structure includes TypeScript source files and compiled JavaScript\n\
-            - RAG storage system with document indexing\n\
-            - Multiple test files for different components\n\
-            - Comprehensive MCP server implementation with orchestration capabilities\n",
-            Utc::now().format("%B %d, %Y, %H:%M %Z")
-        )
-    }
-    
-    fn generate_topics_and_themes(&self) -> String {
-        format!(
-            "## Key Chat Topics and Themes\n\n\
-            ### 1. MCP Server Development\n\
-            - TypeScript migration from JavaScript\n\
-            - MCP server implementation and configuration\n\
-            - Tool development and integration\n\
-            - Hot reload and development workflow\n\n\
-            ### 2. Orchestration and Delegation\n\
-            - Orchestrator service architecture\n\
-            - Tool management and delegation system\n\
-            - Validation and error handling\n\
-            - Context management\n\n\
-            ### 3. RAG (Retrieval Augmented Generation)\n\
-            - Document indexing and storage\n\
-            - Query processing and context retrieval\n\
-            - Memory management and persistence\n\
-            - Integration with local LLM\n\n\
-            ### 4. Agentic Behavior\n\
-            - LlamaIndex integration\n\
-            - Enhanced AI capabilities\n\
-            - Tool orchestration\n\
-            - Context-aware responses\n\n\
-            ### 5. Development Workflow\n\
-            - Git branching and merging\n\
-            - Release management\n\
-            - Documentation updates\n\
-            - Testing and validation\n\n\
-            ### 6. Knowledge Management\n\
-            - Chat history consolidation\n\
-            - Metadata organization\n\
-            - Persistent storage\n\
-            - Git integration\n"
-        )
-    }
-    
-    fn generate_project_structure(&self) -> String {
-        format!(
-            "## Project Structure Reference\n\
-            ```\n\
-            {}/\n\
-            ├── src/                    # TypeScript source files\n\
-            │   ├── agentic/           # Agentic service implementation\n\
-            │   ├── config/            # LLM configuration\n\
-            │   ├── mcp/               # MCP server implementation\n\
-            │   ├── orchestrator/      # Orchestration services\n\
-            │   ├── rag/               # RAG service\n\
-            │   ├── services/          # External services (Sonar)\n\
-            │   └── tools/             # Agentic tools\n\
-            ├── dist/                  # Compiled JavaScript output\n\
-            ├── rag-storage/           # RAG document storage\n\
-            ├── .knowledge/            # Knowledge base (git-ignored)\n\
-            ├── test-*.js              # Various test files\n\
-            └── Configuration files    # package.json, tsconfig.json, etc.\n\
-            ```\n",
-            self.config.sanitize_path(&self.config.project_path)
-        )
-    }
-    
-    fn generate_key_features(&self) -> String {
-        format!(
-            "## Key Features Implemented\n\
-            1. **MCP Server**: Model Context Protocol server implementation\n\
-            2. **Orchestration**: Tool management and delegation system\n\
-            3. **RAG Service**: Retrieval Augmented Generation capabilities\n\
-            4. **Agentic Tools**: AI-powered tool implementations\n\
-            5. **Sonar Integration**: Code analysis and search capabilities\n\
-            6. **Validation Service**: Response validation and accuracy checking\n\
-            7. **Web Search Patterns**: Structured web search functionality\n\
-            8. **Knowledge Management**: Chat history consolidation and storage\n"
-        )
-    }
-    
-    fn generate_git_status(&self) -> String {
-        format!(
-            "## Git Status\n\
-            - **Branch**: {}\n\
-            - **Status**: Modified files include rag-storage/metadata.json\n\
-            - **New Addition**: .knowledge/ folder added to .gitignore\n",
-            self.config.project_branch
-        )
-    }
-    
-    fn generate_data_sources(&self) -> String {
-        format!(
-            "## Chat Data Sources\n\
-            - **Workspace Storage**: {}\n\
-            - **Database**: SQLite state.vscdb containing chat sessions and AI service data\n\
-            - **Composer Data**: JSON data containing session metadata and conversation history\n\
-            - **AI Service Data**: Prompts and generations stored in workspace-specific database\n",
-            self.config.sanitize_path(&self.config.database_path())
-        )
-    }
-    
-    fn generate_notes(&self) -> String {
-        format!(
-            "## Notes\n\
-            - This file serves as a consolidated knowledge base for all chat interactions\n\
-            - Metadata includes timestamps, project context, and technical details\n\
-            - Future chat sessions should be appended to this file\n\
-            - The .knowledge folder is git-ignored to prevent sensitive chat data from being committed\n\
-            - Project focuses on MCP server development with advanced orchestration capabilities\n\
-            - Historical data extracted from workspace-specific SQLite database\n\
-            - All timestamps converted to ISO format for consistency\n"
-        )
-    }
-    
-    fn generate_footer(&self) -> String {
-        format!(
-            "---\n\
-            *This file was automatically generated by {} and includes all historical chat sessions from the {} project workspace.*\n",
-            self.config.app_name,
-            self.config.project_name
-        )
+        vec![
+            Block::Paragraph(format!("**{}**:", self.strings.get("sessions.files_discussed"))),
+            Block::bullets(items),
+        ]
+    }
+    
+    fn generate_edits(&self, session: &ChatSession) -> Vec<Block> {
+        let edits = session_edits(session);
+        if edits.is_empty() {
+            return Vec::new();
+        }
+        
+        let mut blocks = vec![Block::Paragraph(format!("**{}**:", self.strings.get("sessions.edits")))];
+        for (edit, when) in edits {
+            let mut summary = format!("`{}`", self.config.sanitize_path(&edit.path));
+            if let Some(status) = &edit.status {
+                summary.push_str(&format!(" ({})", status));
+            }
+            
+            // Point at the commit that (probably) ended up containing this change
+            if self.config.link_edits_to_commits {
+                if let Some(commit) = first_commit_touching(&self.config.project_path, &edit.path, when) {
+                    summary.push_str(&format!(
+                        " - {} {} \"{}\"",
+                        self.strings.get("sessions.committed_in"),
+                        commit.short_hash(),
+                        commit.subject
+                    ));
+                }
+            }
+            
+            // Collapsed by default so a long agent session doesn't bury the rest of the file
+            blocks.push(Block::Details {
+                summary,
+                blocks: vec![Block::code("diff", self.redact(&edit.diff))],
+            });
+        }
+        
+        blocks
+    }
+    
+    /// The fields the source wrote for a session that we don't model, so
+    /// whatever a newer Cursor records is at least visible.
+    fn generate_additional_fields(&self, session: &ChatSession) -> Vec<Block> {
+        if session.extra.is_empty() {
+            return Vec::new();
+        }
+        
+        let items = session
+            .extra
+            .iter()
+            .map(|(name, value)| {
+                let value = match value {
+                    Value::String(text) => text.clone(),
+                    other => other.to_string(),
+                };
+                format!("`{}`: {}", name, self.config.sanitize_path(&self.redact(&excerpt(&value))))
+            })
+            .collect();
+        vec![
+            Block::Paragraph(format!("**{}**:", self.strings.get("sessions.additional_fields"))),
+            Block::bullets(items),
+        ]
+    }
+    
+    fn generate_most_discussed_files(&self, sessions: &[ComposerData]) -> Vec<Block> {
+        let ranking = most_discussed_files(sessions);
+        if ranking.is_empty() {
+            return Vec::new();
+        }
+        
+        let t = &self.strings;
+        let items: Vec<String> = ranking
+            .iter()
+            .take(20)
+            .map(|file| {
+                format!(
+                    "`{}` - {}, {}",
+                    self.config.sanitize_path(&file.path),
+                    t.count("count.sessions", file.sessions),
+                    t.count("count.references", file.references)
+                )
+            })
+            .collect();
+        
+        vec![Block::heading(2, t.get("files.title")), Block::numbered(items)]
+    }
+    
+    /// Rank the project's files by how many sessions mention them in their
+    /// messages, matched against the files actually in PROJECT_PATH.
+    fn generate_hotspots(&self, sessions: &[ComposerData]) -> Vec<Block> {
+        let root = self.config.project_file("");
+        let files = project_files(&root);
+        let hotspots = find_hotspots(sessions, &FileIndex::new(&files, &root.to_string_lossy()));
+        if hotspots.is_empty() {
+            return Vec::new();
+        }
+        
+        let t = &self.strings;
+        let items: Vec<String> = hotspots
+            .iter()
+            .take(20)
+            .map(|hotspot| {
+                format!(
+                    "`{}` - {}, {}",
+                    hotspot.path,
+                    t.count("count.sessions", hotspot.sessions),
+                    t.count("count.mentions", hotspot.mentions)
+                )
+            })
+            .collect();
+        
+        vec![
+            Block::heading(2, t.get("hotspots.title")),
+            Block::Paragraph(t.get("hotspots.description").to_string()),
+            Block::numbered(items),
+        ]
+    }
+    
+    /// A small markdown file for each project file at least
+    /// FILE_LORE_MIN_SESSIONS sessions touched, with those sessions linked
+    /// into `lore_file` (the main lore, one directory up). Returns each
+    /// file's name and contents; the names are the file's path with `__`
+    /// between directories, like `src__auth.rs.md`.
+    pub fn generate_file_lore(&self, sessions: &[ComposerData], lore_file: &str) -> Result<Vec<(String, String)>> {
+        let root = self.config.project_file("");
+        let files = project_files(&root);
+        let index = FileIndex::new(&files, &root.to_string_lossy());
+        let numbered = self.numbered_sessions(sessions);
+        let t = &self.strings;
+        let zone = self.config.display_zone();
+        
+        let mut sidecars = Vec::new();
+        for lore in collect_file_lore(&numbered, &index, self.config.file_lore_min_sessions) {
+            let mut document = Document::default();
+            document.extend(vec![
+                Block::heading(1, format!("`{}`", lore.path)),
+                Block::Paragraph(t.format(
+                    "file_lore.description",
+                    &[("file", &lore.path), ("lore", &format!("../{}", lore_file))],
+                )),
+            ]);
+            
+            for touched in &lore.sessions {
+                let title = self.session_title(touched.number, touched.session);
+                let touches: Vec<&str> = touched
+                    .touches
+                    .iter()
+                    .map(|touch| match touch {
+                        Touch::Attached => t.get("file_lore.attached"),
+                        Touch::Edited => t.get("file_lore.edited"),
+                        Touch::Mentioned => t.get("file_lore.mentioned"),
+                    })
+                    .collect();
+                let mut properties = vec![(t.get("file_lore.touched").to_string(), touches.join(", "))];
+                if let Some(date) = zone.format_millis(touched.session.created_at, "%Y-%m-%d") {
+                    properties.insert(0, (t.get("sessions.date").to_string(), date));
+                }
+                let mut blocks = vec![
+                    Block::Heading {
+                        level: 2,
+                        text: format!("[{}](../{}#{})", title, lore_file, heading_anchor(&title)),
+                        id: None,
+                        timestamp: None,
+                        properties: Vec::new(),
+                    },
+                    Block::Fields(properties),
+                ];
+                if let Some((role, sent, quote)) = &touched.excerpt {
+                    let speaker = match role {
+                        MessageRole::User => t.get("role.user"),
+                        MessageRole::Assistant => t.get("role.assistant"),
+                    };
+                    blocks.push(Block::Message {
+                        speaker: speaker.to_string(),
+                        sent_at: sent.and_then(|millis| zone.format_millis(millis, "%b %d, %H:%M")),
+                        text: self.config.sanitize_path(&self.redact(quote)),
+                    });
+                }
+                document.extend(blocks);
+            }
+            
+            let mut text = OutputFormat::Markdown.render(&Document { blocks: self.scrub_blocks(document.blocks), ..Document::default() })?;
+            let sessions = lore.sessions.iter().map(|touched| touched.session);
+            if let Some(front_matter) = self.front_matter(lore.path.clone(), sessions) {
+                text.insert_str(0, &front_matter);
+            }
+            sidecars.push((lore.file_name(), text));
+        }
+        Ok(sidecars)
+    }
+    
+    /// The lore index for editor extensions: which sessions, and where in
+    /// their messages, talk about each project file and code symbol, with
+    /// the sessions' anchors in `lore_file`.
+    pub fn generate_lore_index(&self, sessions: &[ComposerData], lore_file: &str) -> Result<String> {
+        let root = self.config.project_file("");
+        let files = project_files(&root);
+        let index = FileIndex::new(&files, &root.to_string_lossy());
+        let numbered = self.numbered_sessions(sessions);
+        self.progress.start(Stage::Indexing, Some(numbered.len()));
+        self.progress.check()?;
+        let lore_index =
+            LoreIndex::build(&numbered, |number, session| self.session_title(number, session), &index, lore_file);
+        self.progress.advance(Stage::Indexing, numbered.len());
+        self.progress.finish(Stage::Indexing);
+        Ok(lore_index.to_json()?)
+    }
+    
+    fn generate_commands_executed(&self, sessions: &[ComposerData]) -> Vec<Block> {
+        let mut blocks = Vec::new();
+        
+        for (number, session) in self.numbered_sessions(sessions) {
+            let usage = tool_usage(session);
+            if usage.is_empty() {
+                continue;
+            }
+            
+            let tools: Vec<String> = usage
+                .iter()
+                .map(|(name, count)| format!("`{}` ×{}", name, count))
+                .collect();
+            blocks.push(Block::Heading {
+                level: 3,
+                text: self.session_title(number, session),
+                id: None,
+                timestamp: None,
+                properties: vec![(self.strings.get("commands.tools_used").to_string(), tools.join(", "))],
+            });
+            
+            // Commands go through the redaction pass - people paste tokens into shells
+            let commands = commands_executed(session);
+            if !commands.is_empty() {
+                let mut shell = String::new();
+                for command in commands {
+                    let command = self.config.sanitize_path(&self.redact(command));
+                    shell.push_str(&format!("$ {}\n", command));
+                }
+                blocks.push(Block::code("shell", shell));
+            }
+        }
+        
+        if blocks.is_empty() {
+            return blocks;
+        }
+        blocks.insert(0, Block::heading(2, self.strings.get("commands.title")));
+        blocks
+    }
+    
+    /// A session's messages, under a "Transcript" label or, with
+    /// COLLAPSE_TRANSCRIPTS, folded into a collapsed block.
+    fn generate_transcript(&self, messages: &[ChatMessage]) -> Vec<Block> {
+        let mut transcript = vec![Block::Paragraph(format!("**{}**:", self.strings.get("sessions.transcript")))];
+        
+        for message in messages {
+            let speaker = match message.role {
+                MessageRole::User => self.strings.get("role.user"),
+                MessageRole::Assistant => self.strings.get("role.assistant"),
+            };
+            // Show when each message was sent, if the source recorded it
+            let sent_at = message
+                .timestamp
+                .and_then(|millis| self.config.display_zone().format_millis(millis, "%b %d, %H:%M"));
+            let text = match self.config.normalize_fences {
+                true => normalize_fences(message.text.trim()),
+                false => message.text.trim().to_string(),
+            };
+            transcript.push(Block::Message { speaker: speaker.to_string(), sent_at, text });
+        }
+        
+        // With COLLAPSE_TRANSCRIPTS, it starts out folded away under a one-line summary
+        if self.config.collapse_transcripts {
+            let summary = format!(
+                "{} ({})",
+                self.strings.get("sessions.transcript"),
+                self.strings.count("count.messages", messages.len())
+            );
+            return vec![Block::Details { summary, blocks: transcript.split_off(1) }];
+        }
+        
+        transcript
+    }
+    
+    fn generate_current_session(&self) -> Vec<Block> {
+        vec![
+            Block::heading(2, self.strings.get("current.title")),
+            Block::Heading {
+                level: 3,
+                text: "Current Knowledge Management Session".to_string(),
+                id: None,
+                timestamp: None,
+                properties: vec![
+                    (
+                        self.strings.get("sessions.date").to_string(),
+                        self.config.display_zone().format_now("%B %d, %Y, %H:%M %Z"),
+                    ),
+                    (
+                        "Context".to_string(),
+                        "Knowledge management and chat history consolidation request".to_string(),
+                    ),
+                ],
+            },
+            Block::Paragraph("**Actions Taken**:".to_string()),
+            Block::numbered(lines(&[
+                "**Configuration Loading**: Loaded settings from configuration file",
+                "**Database Connection**: Connected to SQLite database",
+                "**Data Extraction**: Extracted chat sessions, generations, and prompts",
+                "**Markdown Generation**: Generated consolidated markdown with metadata",
+                "**File Output**: Created consolidated chat history file",
+            ])),
+        ]
+    }
+    
+    /// What the sessions were about, most common first, by the same
+    /// topics `GROUP_BY=topic` uses.
+    fn generate_topics_and_themes(&self, sessions: &[ComposerData]) -> Vec<Block> {
+        let all: Vec<&ChatSession> = sessions.iter().flat_map(|data| &data.all_composers).collect();
+        if all.is_empty() {
+            return Vec::new();
+        }
+        let mut topics: Vec<(String, usize)> = group_by_key(all, |session| session_topic(session))
+            .into_iter()
+            .map(|(topic, members)| (topic, members.len()))
+            .collect();
+        topics.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+        let items = topics
+            .iter()
+            .map(|(topic, count)| format!("{} ({})", topic, self.strings.count("count.sessions", *count)))
+            .collect();
+        vec![Block::heading(2, self.strings.get("topics.title")), Block::bullets(items)]
+    }
+    
+    /// The top two levels of the project's directories, if it's there.
+    fn generate_project_structure(&self) -> Vec<Block> {
+        let Some(tree) = project_tree(&self.config.project_file(""), 2) else {
+            return Vec::new();
+        };
+        vec![
+            Block::heading(2, self.strings.get("structure.title")),
+            Block::code("", format!("{}/\n{}", self.config.sanitize_path(&self.config.project_path), tree)),
+        ]
+    }
+    
+    /// KEY_FEATURES_FILE, if the project has one.
+    fn generate_key_features(&self) -> Result<Vec<Block>> {
+        let Some(mut blocks) = self.project_file(&self.config.key_features_file)?.filter(|blocks| !blocks.is_empty()) else {
+            return Ok(Vec::new());
+        };
+        blocks.insert(0, Block::heading(2, self.strings.get("features.title")));
+        Ok(blocks)
+    }
+    
+    fn generate_git_status(&self) -> Vec<Block> {
+        vec![
+            Block::heading(2, self.strings.get("git.title")),
+            Block::bullets(vec![format!("**{}**: {}", self.strings.get("git.branch"), self.config.project_branch)]),
+        ]
+    }
+    
+    fn generate_data_sources(&self) -> Vec<Block> {
+        let t = &self.strings;
+        let sources = match self.config.source.as_str() {
+            "continue" => vec![
+                format!(
+                    "**{}**: {}",
+                    t.get("sources.continue_sessions"),
+                    self.config.sanitize_path(&self.config.continue_sessions_path)
+                ),
+                "**Session Files**: One JSON file per session with the full conversation history".to_string(),
+            ],
+            "cody" => vec![
+                format!(
+                    "**{}**: {}",
+                    t.get("sources.cody_history"),
+                    self.config.sanitize_path(&self.config.cody_history_path)
+                ),
+                "**Chat Transcripts**: Human/assistant interactions stored by the Cody extension".to_string(),
+            ],
+            source if source_plugin(source).is_some() => vec![
+                format!("**{}**: {}{}", t.get("sources.plugin"), PLUGIN_PREFIX, source_plugin(source).unwrap_or_default()),
+                "**Sessions**: Imported by the plugin over the JSON-over-stdio plugin protocol".to_string(),
+            ],
+            _ => {
+                let mut sources = vec![
+                    format!(
+                        "**{}**: {}",
+                        t.get("sources.workspace_storage"),
+                        self.config.sanitize_path(&self.config.database_path())
+                    ),
+                    "**Database**: SQLite state.vscdb containing chat sessions and AI service data".to_string(),
+                    "**Composer Data**: JSON data containing session metadata and conversation history".to_string(),
+                    "**AI Service Data**: Prompts and generations stored in workspace-specific database".to_string(),
+                ];
+                if self.config.include_global_storage {
+                    sources.push(format!(
+                        "**{}**: {} (merged and deduplicated with workspace data)",
+                        t.get("sources.global_storage"),
+                        self.config.sanitize_path(&self.config.global_db_path)
+                    ));
+                }
+                sources
+            }
+        };
+        
+        vec![Block::heading(2, t.get("sources.title")), Block::bullets(sources)]
+    }
+    
+    fn generate_notes(&self) -> Vec<Block> {
+        vec![
+            Block::heading(2, self.strings.get("notes.title")),
+            Block::bullets(lines(&[
+                "This file serves as a consolidated knowledge base for all chat interactions",
+                "Metadata includes timestamps, project context, and technical details",
+                "Future chat sessions should be appended to this file",
+                "The .knowledge folder is git-ignored to prevent sensitive chat data from being committed",
+                "Historical data extracted from workspace-specific SQLite database",
+                "All timestamps converted to ISO format for consistency",
+            ])),
+        ]
+    }
+    
+    fn generate_footer(&self) -> Vec<Block> {
+        let generated = self.strings.format(
+            "footer.generated",
+            &[("app", &self.config.app_name), ("project", &self.config.project_name)],
+        );
+        vec![Block::Rule, Block::Paragraph(format!("*{}*", generated))]
+    }
+}
+
+/// The shell's name without where it's installed (`zsh`, not
+/// `/Users/ana/.nix-profile/bin/zsh`), or "unknown".
+fn shell_name(shell: Option<String>) -> String {
+    let shell = shell.unwrap_or_default();
+    match shell.rsplit(['/', '\\']).next().map(str::trim) {
+        Some(name) if !name.is_empty() => name.to_string(),
+        _ => "unknown".to_string(),
+    }
+}
+
+/// Turn fixed text into owned list items.
+fn lines(items: &[&str]) -> Vec<String> {
+    items.iter().map(|item| item.to_string()).collect()
+}
+
+/// Summarize one model's usage as "model (~1,234 in / 567 out tokens, est. $0.01)".
+fn describe_usage(usage: &ModelUsage, strings: &Strings) -> String {
+    let approx = if usage.estimated { "~" } else { "" };
+    let tokens = strings.format(
+        "usage.tokens",
+        &[
+            ("input", &format!("{}{}", approx, group_thousands(usage.input_tokens))),
+            ("output", &format!("{}{}", approx, group_thousands(usage.output_tokens))),
+        ],
+    );
+    let mut description = format!("{} ({}", usage.model, tokens);
+    if let Some(cost) = usage.cost {
+        description.push_str(&format!(", {} ${:.2}", strings.get("usage.estimated"), cost));
+    }
+    description.push(')');
+    description
+}
+
+/// Format a number with thousands separators (1234567 -> "1,234,567").
+fn group_thousands(value: u64) -> String {
+    let digits = value.to_string();
+    let mut grouped = String::new();
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
+
+/// How a built-in section is rendered: by one of the generator's methods.
+type RenderSection = fn(&MarkdownGenerator, &SectionContext<'_>, &mut dyn FnMut(Vec<Block>) -> Result<()>) -> Result<()>;
+
+/// A section built into the generator: a name, and the method that renders it.
+struct BuiltinSection {
+    name: &'static str,
+    render: RenderSection,
+}
+
+impl Section for BuiltinSection {
+    fn name(&self) -> &str {
+        self.name
+    }
+    
+    fn render(&self, context: &SectionContext<'_>, emit: &mut dyn FnMut(Vec<Block>) -> Result<()>) -> Result<()> {
+        (self.render)(context.generator, context, emit)
+    }
+}
+
+/// The built-in sections, in the order `BUILTIN_SECTIONS` lists them.
+fn builtin_sections() -> SectionRegistry {
+    let sections = [
+        // First, we need a nice header to introduce our story
+        BuiltinSection { name: "header", render: |g, _, emit| emit(vec![g.generate_header()]) },
+        // Add metadata that tells readers when and where this was created
+        BuiltinSection { name: "metadata", render: |g, c, emit| emit(g.generate_metadata(c.sessions)?) },
+        // Give some context about what this project is all about
+        BuiltinSection { name: "project-context", render: |g, _, emit| emit(g.generate_project_context()?) },
+        // And which part of it the workspace had open
+        BuiltinSection { name: "workspace", render: |g, _, emit| emit(g.generate_workspace()) },
+        // The choices that were made along the way, with where to read more
+        BuiltinSection {
+            name: "decisions",
+            render: |g, c, emit| emit(g.generate_decisions(c.sessions, c.include_sessions)),
+        },
+        // And the work that was left for later
+        BuiltinSection {
+            name: "follow-ups",
+            render: |g, c, emit| emit(g.generate_follow_ups(c.sessions, c.generations, c.prompts, c.include_sessions)),
+        },
+        // The plans, error analyses and so on GENERATION_KINDS asks for
+        BuiltinSection { name: "generations", render: |g, c, emit| emit(g.generate_generations(c.generations)) },
+        // The questions that kept coming up, and what the answers were
+        BuiltinSection { name: "faq", render: |g, c, emit| emit(g.generate_faq(c.sessions, c.include_sessions)) },
+        // The errors people ran into, and what fixed them
+        BuiltinSection {
+            name: "troubleshooting",
+            render: |g, c, emit| emit(g.generate_troubleshooting(c.sessions, c.include_sessions)),
+        },
+        // Now we get to the good stuff - all the historical chat sessions
+        // (unless they're getting pages of their own)
+        BuiltinSection {
+            name: "sessions",
+            render: |g, c, emit| if c.include_sessions { g.generate_historical_sessions(c.sessions, emit) } else { Ok(()) },
+        },
+        // An index of the sessions under each tag, if any have one
+        BuiltinSection {
+            name: "tags",
+            render: |g, c, emit| if c.include_sessions { emit(g.generate_tag_index(c.sessions)) } else { Ok(()) },
+        },
+        // Draw the development story week by week, if asked to
+        BuiltinSection { name: "timeline", render: |g, c, emit| emit(g.generate_timeline(c.sessions)) },
+        // Show how files, crates, services and features tie back to sessions
+        BuiltinSection { name: "knowledge-graph", render: |g, c, emit| emit(g.generate_knowledge_graph(c.sessions)) },
+        // Rank the files that kept coming up (only if any were attached at all)
+        BuiltinSection {
+            name: "most-discussed-files",
+            render: |g, c, emit| emit(g.generate_most_discussed_files(c.sessions)),
+        },
+        // And the project files the conversations name most often
+        BuiltinSection { name: "hotspots", render: |g, c, emit| emit(g.generate_hotspots(c.sessions)) },
+        // Show what the agent actually did - the tools it used and commands it ran
+        BuiltinSection { name: "commands", render: |g, c, emit| emit(g.generate_commands_executed(c.sessions)) },
+        // Break down which models were used and what they (probably) cost
+        BuiltinSection { name: "model-usage", render: |g, c, emit| emit(g.generate_model_usage(c.sessions)) },
+        // Add information about the current session
+        BuiltinSection { name: "current-session", render: |g, _, emit| emit(g.generate_current_session()) },
+        // Organize everything by topics and themes for easy navigation
+        BuiltinSection { name: "topics", render: |g, c, emit| emit(g.generate_topics_and_themes(c.sessions)) },
+        // Show the project structure so readers understand the codebase
+        BuiltinSection { name: "project-structure", render: |g, _, emit| emit(g.generate_project_structure()) },
+        // Highlight the key features that were implemented
+        BuiltinSection { name: "key-features", render: |g, _, emit| emit(g.generate_key_features()?) },
+        // Include git status information for context
+        BuiltinSection { name: "git-status", render: |g, _, emit| emit(g.generate_git_status()) },
+        // Tell readers where we got all this data from
+        BuiltinSection { name: "data-sources", render: |g, _, emit| emit(g.generate_data_sources()) },
+        // Add some final notes and context
+        BuiltinSection { name: "notes", render: |g, _, emit| emit(g.generate_notes()) },
+        // Own up to anything that had to be skipped or guessed (INCLUDE_WARNINGS)
+        BuiltinSection { name: "warnings", render: |g, _, emit| emit(g.generate_warnings()) },
+        // Finish with a nice footer
+        BuiltinSection { name: "footer", render: |g, _, emit| emit(g.generate_footer()) },
+    ];
+    let mut registry = SectionRegistry::default();
+    for section in sections {
+        registry.register(Box::new(section));
     }
+    registry
 }
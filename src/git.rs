@@ -0,0 +1,122 @@
+use chrono::DateTime;
+use std::process::Command;
+
+/// A git commit we can point readers to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommitRef {
+    /// The full commit hash
+    pub hash: String,
+    /// The first line of the commit message
+    pub subject: String,
+}
+
+impl CommitRef {
+    /// The abbreviated hash people usually quote.
+    pub fn short_hash(&self) -> &str {
+        &self.hash[..self.hash.len().min(7)]
+    }
+}
+
+/// Find the first commit at or after `since_ms` that touched `file`.
+/// That's our best guess at the commit that ended up containing an edit the
+/// agent made at that time. Returns `None` when the project isn't a git
+/// repository, git isn't installed, or nothing has touched the file since.
+pub fn first_commit_touching(repo_path: &str, file: &str, since_ms: i64) -> Option<CommitRef> {
+    let since = DateTime::from_timestamp_millis(since_ms)?;
+
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repo_path)
+        .args(["log", "--reverse", "--format=%H%x09%s"])
+        .arg(format!("--since={}", since.to_rfc3339()))
+        .arg("--")
+        .arg(file)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let (hash, subject) = stdout.lines().next()?.split_once('\t')?;
+    Some(CommitRef {
+        hash: hash.to_string(),
+        subject: subject.to_string(),
+    })
+}
+
+/// List the commits made between two times (Unix milliseconds), oldest
+/// first, each with its commit time. Empty when the project isn't a git
+/// repository or git isn't available.
+pub fn commits_between(repo_path: &str, since_ms: i64, until_ms: i64) -> Vec<(i64, CommitRef)> {
+    let (Some(since), Some(until)) = (
+        DateTime::from_timestamp_millis(since_ms),
+        DateTime::from_timestamp_millis(until_ms),
+    ) else {
+        return Vec::new();
+    };
+
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repo_path)
+        .args(["log", "--reverse", "--format=%ct%x09%H%x09%s"])
+        .arg(format!("--since={}", since.to_rfc3339()))
+        .arg(format!("--until={}", until.to_rfc3339()))
+        .output();
+    let output = match output {
+        Ok(output) if output.status.success() => output,
+        _ => return Vec::new(),
+    };
+
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(3, '\t');
+            let seconds: i64 = fields.next()?.parse().ok()?;
+            let hash = fields.next()?;
+            let subject = fields.next().unwrap_or_default();
+            Some((
+                seconds * 1000,
+                CommitRef {
+                    hash: hash.to_string(),
+                    subject: subject.to_string(),
+                },
+            ))
+        })
+        .collect()
+}
+
+/// The branch checked out in the repository at `repo_path`. `None` when it
+/// isn't a git repository, git isn't available, or HEAD is detached.
+pub fn current_branch(repo_path: &str) -> Option<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repo_path)
+        .args(["symbolic-ref", "--quiet", "--short", "HEAD"])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    let branch = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (!branch.is_empty()).then_some(branch)
+}
+
+/// The commit checked out in the repository at `repo_path`, in full.
+/// `None` when it isn't a git repository, git isn't available, or there
+/// are no commits yet.
+pub fn head_commit(repo_path: &str) -> Option<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repo_path)
+        .args(["rev-parse", "--verify", "--quiet", "HEAD"])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    let hash = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (!hash.is_empty()).then_some(hash)
+}
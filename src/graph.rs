@@ -0,0 +1,370 @@
+use anyhow::{anyhow, Result};
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::sync::LazyLock;
+
+use crate::config::Config;
+use crate::edits::session_edits;
+use crate::references::files_discussed;
+use crate::{ChatSession, ComposerData};
+
+/// Crate names pulled from `use foo::`, `extern crate foo` and `cargo add foo`.
+static CRATE_PATTERN: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\buse\s+([a-z][a-z0-9_]*)::|\bextern\s+crate\s+([a-z][a-z0-9_]*)|\bcargo\s+add\s+([a-z][a-z0-9_-]*)")
+        .expect("crate pattern should compile")
+});
+
+/// Services, as people usually name them: "auth service", "RAG-service".
+static SERVICE_PATTERN: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\b([A-Za-z][A-Za-z0-9]*)[ -][Ss]ervice\b").expect("service pattern should compile")
+});
+
+/// Features, when someone calls them one: "the dark mode feature".
+static FEATURE_PATTERN: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)\bthe\s+([a-z][\w-]*(?:\s+[a-z][\w-]*){0,2})\s+feature\b").expect("feature pattern should compile")
+});
+
+/// Module paths that look like crates in a `use` line but aren't dependencies.
+const NOT_CRATES: &[&str] = &["std", "core", "alloc", "crate", "self", "super"];
+
+/// The file formats a knowledge graph can be exported in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GraphFormat {
+    /// Don't export a graph (the default)
+    #[default]
+    None,
+    /// Plain JSON with `entities` and `relations` arrays
+    Json,
+    /// Graphviz DOT
+    Dot,
+    /// GraphML, for Gephi, yEd and friends
+    GraphMl,
+}
+
+impl GraphFormat {
+    /// The values `GRAPH_FORMAT` accepts.
+    pub const NAMES: &'static [&'static str] = &["none", "json", "dot", "graphml"];
+
+    /// Parse a `--graph` / `GRAPH_FORMAT` value.
+    pub fn parse(name: &str) -> Result<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "" | "none" => Ok(GraphFormat::None),
+            "json" => Ok(GraphFormat::Json),
+            "dot" | "graphviz" => Ok(GraphFormat::Dot),
+            "graphml" => Ok(GraphFormat::GraphMl),
+            other => Err(anyhow!(
+                "Unknown graph format '{}' (expected {})",
+                other,
+                GraphFormat::NAMES.join(", ")
+            )),
+        }
+    }
+
+    /// The file extension for this format.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            GraphFormat::None => "",
+            GraphFormat::Json => "json",
+            GraphFormat::Dot => "dot",
+            GraphFormat::GraphMl => "graphml",
+        }
+    }
+}
+
+/// What sort of thing an entity is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum EntityKind {
+    /// A chat session
+    Session,
+    /// A file in the project
+    File,
+    /// A Rust crate (or other package) the project uses
+    Crate,
+    /// A service the conversations talk about
+    Service,
+    /// A feature someone named
+    Feature,
+}
+
+impl EntityKind {
+    /// The name used in ids and exports.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EntityKind::Session => "session",
+            EntityKind::File => "file",
+            EntityKind::Crate => "crate",
+            EntityKind::Service => "service",
+            EntityKind::Feature => "feature",
+        }
+    }
+}
+
+/// How two entities are related.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RelationKind {
+    /// The entity came up in a session
+    DiscussedIn,
+    /// The file was changed by a session
+    ModifiedBy,
+    /// The file uses the crate
+    DependsOn,
+}
+
+impl RelationKind {
+    /// The human-readable label, as used on diagram edges.
+    pub fn label(&self) -> &'static str {
+        match self {
+            RelationKind::DiscussedIn => "discussed in",
+            RelationKind::ModifiedBy => "modified by",
+            RelationKind::DependsOn => "depends on",
+        }
+    }
+}
+
+/// Something the conversations were about.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct Entity {
+    /// A stable id like `file:src/main.rs` or `session:abc123`
+    pub id: String,
+    /// What sort of thing it is
+    pub kind: EntityKind,
+    /// The name to show for it
+    pub label: String,
+}
+
+/// A directed link between two entities, by id.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct Relation {
+    /// The entity the relation starts from
+    pub from: String,
+    /// The entity it points at
+    pub to: String,
+    /// What the link means
+    pub kind: RelationKind,
+}
+
+/// Files, crates, services and features, and how they tie back to sessions.
+#[derive(Debug, Clone, Default, Serialize, JsonSchema)]
+pub struct KnowledgeGraph {
+    /// Every entity, in the order it was first seen
+    pub entities: Vec<Entity>,
+    /// Every relation, without duplicates
+    pub relations: Vec<Relation>,
+}
+
+impl KnowledgeGraph {
+    /// Build the graph from every session's references, edits and text.
+    /// Paths go through the same sanitizing as the markdown, so the export
+    /// is as safe to share as the document.
+    pub fn build(sessions: &[ComposerData], config: &Config) -> Self {
+        let mut graph = KnowledgeGraph::default();
+
+        for session in sessions.iter().flat_map(|data| &data.all_composers) {
+            let session_id = graph.add(EntityKind::Session, &session.composer_id, &session.name);
+
+            for file in files_discussed(session) {
+                let path = config.sanitize_path(&file.path);
+                let file_id = graph.add(EntityKind::File, &path, &path);
+                graph.relate(&file_id, &session_id, RelationKind::DiscussedIn);
+            }
+
+            for (edit, _) in session_edits(session) {
+                let path = config.sanitize_path(&edit.path);
+                let file_id = graph.add(EntityKind::File, &path, &path);
+                graph.relate(&file_id, &session_id, RelationKind::ModifiedBy);
+
+                // Only lines the edit added count - the rest is context
+                let added: String = edit
+                    .diff
+                    .lines()
+                    .filter_map(|line| line.strip_prefix('+'))
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                for name in crate_names(&added) {
+                    let crate_id = graph.add(EntityKind::Crate, &name, &name);
+                    graph.relate(&file_id, &crate_id, RelationKind::DependsOn);
+                }
+            }
+
+            graph.add_mentions(session, &session_id);
+        }
+
+        graph
+    }
+
+    /// Crates, services and features named anywhere in the conversation.
+    fn add_mentions(&mut self, session: &ChatSession, session_id: &str) {
+        for message in &session.messages {
+            for name in crate_names(&message.text) {
+                let id = self.add(EntityKind::Crate, &name, &name);
+                self.relate(&id, session_id, RelationKind::DiscussedIn);
+            }
+            for capture in SERVICE_PATTERN.captures_iter(&message.text) {
+                let label = format!("{} service", capture[1].to_lowercase());
+                let id = self.add(EntityKind::Service, &label, &label);
+                self.relate(&id, session_id, RelationKind::DiscussedIn);
+            }
+            for capture in FEATURE_PATTERN.captures_iter(&message.text) {
+                let label = capture[1].split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase();
+                let id = self.add(EntityKind::Feature, &label, &label);
+                self.relate(&id, session_id, RelationKind::DiscussedIn);
+            }
+        }
+    }
+
+    /// Add an entity if it isn't there yet, returning its id.
+    fn add(&mut self, kind: EntityKind, key: &str, label: &str) -> String {
+        let id = format!("{}:{}", kind.as_str(), key);
+        if !self.entities.iter().any(|entity| entity.id == id) {
+            self.entities.push(Entity {
+                id: id.clone(),
+                kind,
+                label: label.to_string(),
+            });
+        }
+        id
+    }
+
+    /// Add a relation if it isn't there yet.
+    fn relate(&mut self, from: &str, to: &str, kind: RelationKind) {
+        let relation = Relation {
+            from: from.to_string(),
+            to: to.to_string(),
+            kind,
+        };
+        if !self.relations.contains(&relation) {
+            self.relations.push(relation);
+        }
+    }
+
+    /// Whether there's anything besides sessions in the graph.
+    pub fn is_empty(&self) -> bool {
+        self.relations.is_empty()
+    }
+
+    /// Export the graph in the given format.
+    pub fn export(&self, format: GraphFormat) -> Result<String> {
+        match format {
+            GraphFormat::None => Ok(String::new()),
+            GraphFormat::Json => Ok(serde_json::to_string_pretty(self)? + "\n"),
+            GraphFormat::Dot => Ok(self.to_dot()),
+            GraphFormat::GraphMl => Ok(self.to_graphml()),
+        }
+    }
+
+    /// Render as Graphviz DOT.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph lore {\n    rankdir=LR;\n");
+        for entity in &self.entities {
+            dot.push_str(&format!(
+                "    \"{}\" [label=\"{}\", kind=\"{}\"];\n",
+                dot_escape(&entity.id),
+                dot_escape(&entity.label),
+                entity.kind.as_str()
+            ));
+        }
+        for relation in &self.relations {
+            dot.push_str(&format!(
+                "    \"{}\" -> \"{}\" [label=\"{}\"];\n",
+                dot_escape(&relation.from),
+                dot_escape(&relation.to),
+                relation.kind.label()
+            ));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Render as GraphML.
+    pub fn to_graphml(&self) -> String {
+        let mut xml = String::from(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+            <graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n  \
+            <key id=\"label\" for=\"all\" attr.name=\"label\" attr.type=\"string\"/>\n  \
+            <key id=\"kind\" for=\"node\" attr.name=\"kind\" attr.type=\"string\"/>\n  \
+            <graph id=\"lore\" edgedefault=\"directed\">\n",
+        );
+        for entity in &self.entities {
+            xml.push_str(&format!(
+                "    <node id=\"{}\"><data key=\"label\">{}</data><data key=\"kind\">{}</data></node>\n",
+                xml_escape(&entity.id),
+                xml_escape(&entity.label),
+                entity.kind.as_str()
+            ));
+        }
+        for relation in &self.relations {
+            xml.push_str(&format!(
+                "    <edge source=\"{}\" target=\"{}\"><data key=\"label\">{}</data></edge>\n",
+                xml_escape(&relation.from),
+                xml_escape(&relation.to),
+                relation.kind.label()
+            ));
+        }
+        xml.push_str("  </graph>\n</graphml>\n");
+        xml
+    }
+
+    /// Render as the source of a Mermaid flowchart, for the markdown. Big
+    /// graphs are cut down to their first `max_relations` relations so the
+    /// diagram still renders; the export files always have everything.
+    pub fn to_mermaid(&self, max_relations: usize) -> String {
+        let relations: Vec<&Relation> = self.relations.iter().take(max_relations).collect();
+        let node = |id: &str| self.entities.iter().position(|entity| entity.id == id).unwrap_or_default();
+
+        let mut used: Vec<usize> = relations.iter().flat_map(|relation| [node(&relation.from), node(&relation.to)]).collect();
+        used.sort_unstable();
+        used.dedup();
+
+        let mut diagram = String::from("graph LR\n");
+        for index in used {
+            let entity = &self.entities[index];
+            diagram.push_str(&format!("    n{}[\"{}\"]\n", index, mermaid_escape(&entity.label)));
+        }
+        for relation in relations {
+            diagram.push_str(&format!(
+                "    n{} -->|{}| n{}\n",
+                node(&relation.from),
+                relation.kind.label(),
+                node(&relation.to)
+            ));
+        }
+        diagram
+    }
+}
+
+/// The crates a piece of text names, in order, without duplicates.
+fn crate_names(text: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for capture in CRATE_PATTERN.captures_iter(text) {
+        let Some(name) = capture.iter().skip(1).flatten().next() else {
+            continue;
+        };
+        let name = name.as_str().replace('-', "_");
+        if !NOT_CRATES.contains(&name.as_str()) && !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    names
+}
+
+/// Escape a string for a double-quoted DOT id or label.
+fn dot_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Escape a string for XML text or attributes.
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Escape a string for a quoted Mermaid node label. Mermaid reads labels
+/// as HTML, so a sanitized `<PROJECT_PATH>` would otherwise vanish.
+fn mermaid_escape(text: &str) -> String {
+    text.replace('"', "#quot;").replace('<', "#lt;").replace('>', "#gt;")
+}
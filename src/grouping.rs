@@ -0,0 +1,166 @@
+use anyhow::{anyhow, Result};
+
+use crate::time::DisplayZone;
+use crate::ChatSession;
+
+/// How to organize the Historical Chat Sessions section.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GroupBy {
+    /// One flat list (the default)
+    #[default]
+    None,
+    /// One group per calendar day
+    Day,
+    /// One group per ISO week
+    Week,
+    /// One group per calendar month
+    Month,
+    /// One group per topic, as guessed from the session name
+    Topic,
+    /// One group per tag (a session with several goes under the first,
+    /// alphabetically), with untagged sessions last
+    Tag,
+}
+
+impl GroupBy {
+    /// The values `--group-by` accepts.
+    pub const NAMES: &'static [&'static str] = &["none", "day", "week", "month", "topic", "tag"];
+
+    /// Parse a `--group-by` / `GROUP_BY` value.
+    pub fn parse(name: &str) -> Result<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "" | "none" => Ok(GroupBy::None),
+            "day" => Ok(GroupBy::Day),
+            "week" => Ok(GroupBy::Week),
+            "month" => Ok(GroupBy::Month),
+            "topic" => Ok(GroupBy::Topic),
+            "tag" | "tags" => Ok(GroupBy::Tag),
+            other => Err(anyhow!(
+                "Can't group sessions by '{}' (expected {})",
+                other,
+                GroupBy::NAMES.join(", ")
+            )),
+        }
+    }
+
+    /// The group a session belongs in, for the date-based groupings.
+    /// Keys sort chronologically (`2025-09-05`, `2025-W36`, `2025-09`), and
+    /// use the display time zone so a late-night session lands on the day
+    /// it felt like it happened. Topic and tag grouping go by
+    /// `session_topic` and the session's tags instead, so they (and
+    /// `None`) return `None` here.
+    pub fn date_key(&self, session: &ChatSession, zone: &DisplayZone) -> Option<String> {
+        let format = match self {
+            GroupBy::Day => "%Y-%m-%d",
+            GroupBy::Week => "%G-W%V",
+            GroupBy::Month => "%Y-%m",
+            GroupBy::None | GroupBy::Topic | GroupBy::Tag => return None,
+        };
+        Some(zone.format_millis(session.created_at, format).unwrap_or_else(|| "unknown".to_string()))
+    }
+}
+
+/// Put items into groups by key. Groups come back in first-seen order and
+/// items keep their order within each group; callers that want the groups
+/// sorted (by date, say) sort the result.
+pub fn group_by_key<T>(items: Vec<T>, key: impl Fn(&T) -> String) -> Vec<(String, Vec<T>)> {
+    let mut groups: Vec<(String, Vec<T>)> = Vec::new();
+
+    for item in items {
+        let item_key = key(&item);
+        match groups.iter_mut().find(|(group_key, _)| *group_key == item_key) {
+            Some((_, members)) => members.push(item),
+            None => groups.push((item_key, vec![item])),
+        }
+    }
+
+    groups
+}
+
+/// What to sort sessions by before they're numbered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortBy {
+    /// The order the source stored them in (the default)
+    #[default]
+    Source,
+    /// When the session started
+    Created,
+    /// When the session was last updated
+    Updated,
+    /// The session name, case-insensitively
+    Name,
+    /// How many messages the session has
+    MessageCount,
+}
+
+impl SortBy {
+    /// The values `--sort` accepts.
+    pub const NAMES: &'static [&'static str] = &["source", "created", "updated", "name", "message-count"];
+
+    /// Parse a `--sort` / `SORT_BY` value.
+    pub fn parse(name: &str) -> Result<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "" | "source" | "none" => Ok(SortBy::Source),
+            "created" => Ok(SortBy::Created),
+            "updated" => Ok(SortBy::Updated),
+            "name" => Ok(SortBy::Name),
+            "message-count" | "messages" => Ok(SortBy::MessageCount),
+            other => Err(anyhow!(
+                "Can't sort sessions by '{}' (expected {})",
+                other,
+                SortBy::NAMES.join(", ")
+            )),
+        }
+    }
+
+    /// Sort sessions in place. The sort is stable, so sessions that tie
+    /// keep their source order; `descending` reverses the comparison only.
+    pub fn sort(&self, sessions: &mut [&ChatSession], descending: bool) {
+        if *self == SortBy::Source {
+            if descending {
+                sessions.reverse();
+            }
+            return;
+        }
+
+        sessions.sort_by(|a, b| {
+            let ordering = match self {
+                SortBy::Source => std::cmp::Ordering::Equal,
+                SortBy::Created => a.created_at.cmp(&b.created_at),
+                SortBy::Updated => a.last_updated_at.cmp(&b.last_updated_at),
+                SortBy::Name => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+                SortBy::MessageCount => a.messages.len().cmp(&b.messages.len()),
+            };
+            if descending {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        });
+    }
+}
+
+/// Parse a `--order` / `SORT_ORDER` value, returning whether it's descending.
+pub fn parse_sort_order(order: &str) -> Result<bool> {
+    match order.trim().to_lowercase().as_str() {
+        "" | "asc" | "ascending" => Ok(false),
+        "desc" | "descending" => Ok(true),
+        other => Err(anyhow!("Unknown sort order '{}' (expected asc or desc)", other)),
+    }
+}
+
+/// A rough guess at what a session was about, from keywords in its name.
+/// Sessions are grouped by this with `GROUP_BY=topic`.
+pub fn session_topic(session: &ChatSession) -> String {
+    match session.name.as_str() {
+        name if name.contains("orchestrator") => "MCP orchestrator analysis and architecture discussion".to_string(),
+        name if name.contains("RAG") => "RAG (Retrieval Augmented Generation) task implementation".to_string(),
+        name if name.contains("agentic") => "Agentic behavior enhancement and tool integration".to_string(),
+        name if name.contains("memory") => "Memory management and storage implementation".to_string(),
+        name if name.contains("delegate") => "Delegation and orchestration analysis".to_string(),
+        name if name.contains("enhance") => "Server orchestration enhancements".to_string(),
+        name if name.contains("clarification") => "Action requirements clarification".to_string(),
+        name if name.contains("history") => "Knowledge management and chat history consolidation".to_string(),
+        _ => "General project development and discussion".to_string(),
+    }
+}
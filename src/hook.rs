@@ -0,0 +1,202 @@
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::UNIX_EPOCH;
+
+use crate::atomic::write_atomic;
+use crate::config::Config;
+use crate::publish::git::run;
+
+/// Where `--hook-mode` remembers what the sources looked like at its last
+/// run, in the cache directory.
+pub const HOOK_STATE_FILE: &str = ".lore-hook.json";
+
+/// The line that marks a hook as ours, so `uninstall-hook` never removes
+/// (and `install-hook` never overwrites) a hook someone wrote by hand.
+pub const HOOK_MARKER: &str = "# Installed by persistent-code-lore";
+
+/// The git hooks the lore can be refreshed from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookKind {
+    /// Before each commit, so the refreshed lore goes into that commit
+    PreCommit,
+    /// After each commit, so the refreshed lore is staged for the next one
+    PostCommit,
+}
+
+impl HookKind {
+    /// The values `--kind` accepts.
+    pub const NAMES: &'static [&'static str] = &["pre-commit", "post-commit"];
+
+    /// Every kind, for `uninstall-hook` without `--kind`.
+    pub const ALL: &'static [HookKind] = &[HookKind::PreCommit, HookKind::PostCommit];
+
+    /// Parse a `--kind` value.
+    pub fn parse(name: &str) -> Result<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "pre-commit" => Ok(HookKind::PreCommit),
+            "post-commit" => Ok(HookKind::PostCommit),
+            other => Err(anyhow!(
+                "Unknown hook kind '{}' (expected {})",
+                other,
+                HookKind::NAMES.join(", ")
+            )),
+        }
+    }
+
+    /// The hook's file name in the hooks directory.
+    pub fn file_name(&self) -> &'static str {
+        match self {
+            HookKind::PreCommit => "pre-commit",
+            HookKind::PostCommit => "post-commit",
+        }
+    }
+}
+
+/// The hook script: run the consolidator in hook mode (with the profile
+/// it was installed with, if any), and carry on with the commit even if
+/// that fails - out-of-date lore is never a reason to lose someone's commit.
+pub fn hook_script(program: &Path, config_file: &Path, profile: Option<&str>, output_dir: &Path) -> String {
+    let profile = profile.map(|name| format!(" --profile {}", shell_quote(name))).unwrap_or_default();
+    format!(
+        "#!/bin/sh\n\
+         {}; `chat-history-consolidator uninstall-hook` removes it\n\
+         {} --config {}{} --output-dir {} --hook-mode \\\n    \
+         || echo \"persistent-code-lore: couldn't refresh the lore, committing anyway\" >&2\n\
+         exit 0\n",
+        HOOK_MARKER,
+        shell_quote(&program.to_string_lossy()),
+        shell_quote(&config_file.to_string_lossy()),
+        profile,
+        shell_quote(&output_dir.to_string_lossy()),
+    )
+}
+
+/// Quote a word for `sh`.
+fn shell_quote(word: &str) -> String {
+    format!("'{}'", word.replace('\'', r"'\''"))
+}
+
+/// The repository's hooks directory (respecting `core.hooksPath`).
+pub fn hooks_dir(repo: &Path) -> Result<PathBuf> {
+    let dir = run(
+        Command::new("git").arg("-C").arg(repo).args(["rev-parse", "--git-path", "hooks"]),
+        "rev-parse",
+    )
+    .with_context(|| format!("{} is not a git repository", repo.display()))?;
+    Ok(repo.join(dir.trim()))
+}
+
+/// Write the hook into the repository. A hook that isn't ours is left
+/// alone unless `force` is set.
+pub fn install(repo: &Path, kind: HookKind, script: &str, force: bool) -> Result<PathBuf> {
+    let dir = hooks_dir(repo)?;
+    fs::create_dir_all(&dir).with_context(|| format!("Could not create {}", dir.display()))?;
+    let path = dir.join(kind.file_name());
+    if path.exists() && !is_ours(&path) && !force {
+        bail!(
+            "{} already has a {} hook that wasn't installed by us; pass --force to replace it",
+            repo.display(),
+            kind.file_name()
+        );
+    }
+    write_atomic(&path, script.as_bytes())?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755))
+            .with_context(|| format!("Could not make {} executable", path.display()))?;
+    }
+    Ok(path)
+}
+
+/// Remove our hooks of the given kinds. Returns the ones that were removed.
+pub fn uninstall(repo: &Path, kinds: &[HookKind]) -> Result<Vec<PathBuf>> {
+    let dir = hooks_dir(repo)?;
+    let mut removed = Vec::new();
+    for kind in kinds {
+        let path = dir.join(kind.file_name());
+        if path.exists() && is_ours(&path) {
+            fs::remove_file(&path).with_context(|| format!("Could not remove {}", path.display()))?;
+            removed.push(path);
+        }
+    }
+    Ok(removed)
+}
+
+fn is_ours(path: &Path) -> bool {
+    fs::read_to_string(path).is_ok_and(|text| text.lines().any(|line| line.starts_with(HOOK_MARKER)))
+}
+
+/// What `--hook-mode` saw at its last run.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HookState {
+    /// The sources' fingerprint when the lore was last written
+    pub fingerprint: String,
+}
+
+impl HookState {
+    /// Load the state, or start fresh if there isn't any (or it's unreadable).
+    pub fn load(path: &Path) -> Self {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+
+    /// Save the state for the next commit.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        write_atomic(path, serde_json::to_string_pretty(self)?.as_bytes())
+    }
+}
+
+/// A cheap fingerprint of everything a run reads: the source's files (by
+/// size and modification time, not content) and the config file, plus
+/// this program's version. If it hasn't changed, neither would the lore.
+pub fn source_fingerprint(config: &Config, config_file: &Path) -> String {
+    let mut hasher = DefaultHasher::new();
+    env!("CARGO_PKG_VERSION").hash(&mut hasher);
+    fs::read(config_file).unwrap_or_default().hash(&mut hasher);
+    // Renaming a session in the curation file changes the lore too
+    fs::read(config.project_file(&config.curation_file)).unwrap_or_default().hash(&mut hasher);
+    for path in source_files(config) {
+        path.hash(&mut hasher);
+        stamp(&path).hash(&mut hasher);
+    }
+    format!("{:016x}", hasher.finish())
+}
+
+/// The files a run reads. SQLite databases come with their write-ahead
+/// log, which is where recent changes live until a checkpoint; Continue's
+/// directory is included so a deleted session counts as a change.
+fn source_files(config: &Config) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    for path in config.source_files() {
+        files.push(PathBuf::from(format!("{}-wal", path.display())));
+        files.push(path);
+    }
+    if config.source == "continue" {
+        files.push(PathBuf::from(shellexpand::tilde(&config.continue_sessions_path).to_string()));
+    }
+    files
+}
+
+/// A file's size and modification time, if it exists.
+fn stamp(path: &Path) -> Option<(u64, u128)> {
+    let metadata = fs::metadata(path).ok()?;
+    let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    Some((metadata.len(), modified.as_nanos()))
+}
+
+/// Stage files in the repository the hook is running in.
+pub fn stage(files: &[PathBuf]) -> Result<()> {
+    if files.is_empty() {
+        return Ok(());
+    }
+    run(Command::new("git").arg("add").arg("--").args(files), "add")?;
+    Ok(())
+}
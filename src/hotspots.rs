@@ -0,0 +1,122 @@
+use std::collections::{BTreeSet, HashMap};
+
+use crate::ComposerData;
+
+/// A project file the conversations kept mentioning.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hotspot {
+    /// The file's path, relative to the project
+    pub path: String,
+    /// How many different sessions mentioned it
+    pub sessions: usize,
+    /// How many times it was mentioned in all
+    pub mentions: usize,
+}
+
+/// The files in a project, looked up by the ways a conversation might
+/// name them.
+pub struct FileIndex<'a> {
+    /// Every file, relative to the project
+    files: BTreeSet<&'a str>,
+    /// Files by their name alone
+    by_name: HashMap<&'a str, Vec<&'a str>>,
+    /// The project's path, for mentions given as absolute paths
+    root: String,
+}
+
+impl<'a> FileIndex<'a> {
+    /// Index `files` (relative to `root`, with `/` between directories,
+    /// as `project::project_files` lists them).
+    pub fn new(files: &'a [String], root: &str) -> Self {
+        let mut by_name: HashMap<&str, Vec<&str>> = HashMap::new();
+        for file in files {
+            let name = file.rsplit('/').next().unwrap_or(file);
+            by_name.entry(name).or_default().push(file);
+        }
+        FileIndex {
+            files: files.iter().map(String::as_str).collect(),
+            by_name,
+            root: format!("{}/", root.replace('\\', "/").trim_end_matches('/')),
+        }
+    }
+
+    /// The project file a mention means, if it's clear which one: the file
+    /// at that path (relative to the project, or absolute inside it), or
+    /// else the only one with that name whose path ends with the mention or
+    /// the other way round (for a path on someone else's machine).
+    pub fn resolve(&self, mention: &str) -> Option<&'a str> {
+        let mention = mention.replace('\\', "/");
+        let mention = mention.strip_prefix(&self.root).unwrap_or(&mention);
+        let mention = mention.trim_start_matches("./");
+        if let Some(file) = self.files.get(mention) {
+            return Some(file);
+        }
+
+        let name = mention.rsplit('/').next().unwrap_or(mention);
+        let suffix = format!("/{}", mention);
+        let mut candidates = self.by_name.get(name)?.iter().filter(|file| {
+            !mention.contains('/') || file.ends_with(&suffix) || mention.ends_with(&format!("/{}", file))
+        });
+        match (candidates.next(), candidates.next()) {
+            (Some(file), None) => Some(file),
+            _ => None,
+        }
+    }
+}
+
+/// Rank the project's files by how many sessions mention them by name or
+/// path anywhere in their messages. A mention that could be one of several
+/// files (a bare `mod.rs`, say) isn't counted. Ties are broken by the number
+/// of mentions, then by path.
+pub fn find_hotspots(sessions: &[ComposerData], index: &FileIndex<'_>) -> Vec<Hotspot> {
+    let mut activity: HashMap<&str, (BTreeSet<&str>, usize)> = HashMap::new();
+    let mut resolved: HashMap<&str, Option<&str>> = HashMap::new();
+
+    for session in sessions.iter().flat_map(|data| &data.all_composers) {
+        for message in &session.messages {
+            for mention in path_mentions(&message.text) {
+                let file = *resolved.entry(mention).or_insert_with(|| index.resolve(mention));
+                if let Some(file) = file {
+                    let entry = activity.entry(file).or_default();
+                    entry.0.insert(&session.composer_id);
+                    entry.1 += 1;
+                }
+            }
+        }
+    }
+
+    let mut hotspots: Vec<Hotspot> = activity
+        .into_iter()
+        .map(|(path, (sessions, mentions))| Hotspot {
+            path: path.to_string(),
+            sessions: sessions.len(),
+            mentions,
+        })
+        .collect();
+    hotspots.sort_by(|a, b| {
+        b.sessions
+            .cmp(&a.sessions)
+            .then(b.mentions.cmp(&a.mentions))
+            .then(a.path.cmp(&b.path))
+    });
+    hotspots
+}
+
+/// The words in `text` that could be file paths: runs of path characters
+/// with an extension, like `src/main.rs`, `./lib/util.ts` or `Cargo.toml`
+/// (in `src/main.rs:42`, the line number is left off). URLs are passed over.
+pub(crate) fn path_mentions(text: &str) -> impl Iterator<Item = &str> {
+    text.split(|c: char| !(c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | '/' | '\\' | '~' | '@' | '+')))
+        .filter(|word| !word.contains("//"))
+        .map(|word| word.trim_end_matches('.'))
+        .filter(|word| match word.rsplit_once('.') {
+            Some((stem, extension)) => {
+                !stem.is_empty()
+                    && !stem.ends_with(['/', '\\'])
+                    && !extension.is_empty()
+                    && extension.len() <= 10
+                    && extension.chars().all(|c| c.is_ascii_alphanumeric())
+            }
+            None => false,
+        })
+}
@@ -0,0 +1,101 @@
+use anyhow::{anyhow, Context, Result};
+use std::collections::HashMap;
+
+/// The locales we ship strings for, with their locale files.
+/// English is the reference: any string missing from another locale falls
+/// back to it, so a half-finished translation still renders.
+const LOCALE_FILES: &[(&str, &str)] = &[
+    ("en", include_str!("../locales/en.json")),
+    ("de", include_str!("../locales/de.json")),
+    ("it", include_str!("../locales/it.json")),
+    ("ja", include_str!("../locales/ja.json")),
+];
+
+/// The fixed strings of the generated document (section titles, field
+/// labels and the like) in one language.
+#[derive(Debug, Clone)]
+pub struct Strings {
+    /// The language these strings are in ("en", "de", ...)
+    locale: String,
+    /// The translated strings, keyed like `sessions.title`
+    strings: HashMap<String, String>,
+    /// English, for anything the translation doesn't cover
+    fallback: HashMap<String, String>,
+}
+
+impl Strings {
+    /// Load the strings for a locale. Region and encoding suffixes are
+    /// ignored, so `de_DE.UTF-8` and `de-AT` both pick German.
+    pub fn for_locale(locale: &str) -> Result<Self> {
+        let language = locale
+            .split(['_', '-', '.'])
+            .next()
+            .unwrap_or_default()
+            .to_lowercase();
+        let language = if language.is_empty() { "en".to_string() } else { language };
+
+        let file = LOCALE_FILES
+            .iter()
+            .find(|(code, _)| *code == language)
+            .map(|(_, file)| *file)
+            .ok_or_else(|| {
+                anyhow!(
+                    "No translation for locale '{}' (available: {})",
+                    locale,
+                    available_locales().join(", ")
+                )
+            })?;
+
+        Ok(Strings {
+            locale: language,
+            strings: parse_locale_file(file)?,
+            fallback: parse_locale_file(LOCALE_FILES[0].1)?,
+        })
+    }
+
+    /// The language code these strings are in.
+    pub fn locale(&self) -> &str {
+        &self.locale
+    }
+
+    /// Look up a string, falling back to English and then to the key itself.
+    pub fn get<'a>(&'a self, key: &'a str) -> &'a str {
+        self.strings
+            .get(key)
+            .or_else(|| self.fallback.get(key))
+            .map(String::as_str)
+            .unwrap_or(key)
+    }
+
+    /// Look up a string and fill in its `{name}` placeholders.
+    pub fn format(&self, key: &str, values: &[(&str, &str)]) -> String {
+        let mut text = self.get(key).to_string();
+        for (name, value) in values {
+            text = text.replace(&format!("{{{}}}", name), value);
+        }
+        text
+    }
+
+    /// Format a count with the singular or plural form of a phrase
+    /// (`count.sessions_one` / `count.sessions_other`).
+    pub fn count(&self, key: &str, count: usize) -> String {
+        let form = if count == 1 { "one" } else { "other" };
+        self.format(&format!("{}_{}", key, form), &[("count", &count.to_string())])
+    }
+}
+
+impl Default for Strings {
+    fn default() -> Self {
+        Strings::for_locale("en").expect("the English locale file should always parse")
+    }
+}
+
+/// The language codes we have locale files for.
+pub fn available_locales() -> Vec<&'static str> {
+    LOCALE_FILES.iter().map(|(code, _)| *code).collect()
+}
+
+/// Parse a locale file: a flat JSON object of key to string.
+fn parse_locale_file(file: &str) -> Result<HashMap<String, String>> {
+    serde_json::from_str(file).context("A built-in locale file is not valid JSON")
+}
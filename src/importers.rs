@@ -0,0 +1,452 @@
+#[cfg(feature = "native")]
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+#[cfg(feature = "native")]
+use std::fs;
+#[cfg(feature = "native")]
+use std::path::Path;
+
+#[cfg(feature = "native")]
+use crate::config::Config;
+#[cfg(feature = "native")]
+use crate::edits::edit_from_tool_call;
+use crate::parsing::SkippedRecord;
+#[cfg(feature = "native")]
+use crate::references::{uri_to_path, whole_file};
+#[cfg(feature = "native")]
+use crate::store::{ConnectionOptions, JsonDirStore, MemoryStore, RecordStore, SqliteStore};
+#[cfg(feature = "native")]
+use crate::timestamps::{parse_millis, TimeUnit};
+#[cfg(feature = "native")]
+use crate::tools::tool_call;
+use crate::warnings::Warning;
+#[cfg(feature = "native")]
+use crate::warnings::WarningKind;
+use crate::workspace_info::WorkspaceInfo;
+use crate::{ChatGeneration, ChatPrompt, ComposerData};
+#[cfg(feature = "native")]
+use crate::{ChatMessage, ChatSession, FileReference, MessageRole, ToolCall};
+
+/// Everything we managed to pull out of a source, shaped the way the
+/// Cursor extractor reads it so the rest of the pipeline doesn't need to
+/// care where it came from.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ImportedHistory {
+    /// The sessions that were found (with their transcripts filled in)
+    pub sessions: Vec<ComposerData>,
+    /// Every assistant message, flattened into generations
+    pub generations: Vec<ChatGeneration>,
+    /// Every user message, flattened into prompts
+    pub prompts: Vec<ChatPrompt>,
+    /// Records that didn't parse and were left out
+    pub skipped: Vec<SkippedRecord>,
+    /// Records that were kept, but with something about them made up or
+    /// left out (see `all_warnings` for these and the skipped ones together)
+    #[serde(default)]
+    pub warnings: Vec<Warning>,
+    /// What the workspace had open, for sources that keep track (Cursor)
+    #[serde(default)]
+    pub workspace: Option<WorkspaceInfo>,
+}
+
+impl ImportedHistory {
+    /// Build the flattened prompt/generation lists from a set of sessions.
+    /// Sources like Continue and Cody only store whole conversations, so we
+    /// derive the Cursor-style lists from the transcripts ourselves.
+    #[cfg(feature = "native")]
+    pub(crate) fn from_sessions(sessions: Vec<ChatSession>, generation_type: &str) -> Self {
+        let mut history = ImportedHistory::default();
+
+        for session in &sessions {
+            for (i, message) in session.messages.iter().enumerate() {
+                match message.role {
+                    MessageRole::User => history.prompts.push(ChatPrompt {
+                        text: message.text.clone(),
+                        command_type: 0,
+                    }),
+                    MessageRole::Assistant => history.generations.push(ChatGeneration {
+                        unix_ms: message.timestamp.unwrap_or(session.created_at),
+                        generation_uuid: format!("{}-{}", session.composer_id, i),
+                        r#type: generation_type.to_string(),
+                        text_description: message.text.clone(),
+                        model: message.model.clone(),
+                        kind: None,
+                        extra: Default::default(),
+                    }),
+                }
+            }
+        }
+
+        history.sessions.push(ComposerData {
+            all_composers: sessions,
+        });
+        history
+    }
+
+    /// Everything about the history that didn't match our models, one line
+    /// each: records that were skipped for a missing field or a value of
+    /// the wrong type, and fields we don't know. Empty when it all fit.
+    pub fn schema_drift(&self) -> Vec<String> {
+        let skipped = self
+            .skipped
+            .iter()
+            .map(|record| format!("{} {}: {}", record.kind, record.location, record.error));
+        let sessions = self
+            .sessions
+            .iter()
+            .flat_map(|data| &data.all_composers)
+            .filter(|session| !session.extra.is_empty())
+            .map(|session| format!("session {}: unknown fields {}", session.composer_id, field_list(&session.extra)));
+        let generations = self
+            .generations
+            .iter()
+            .filter(|generation| !generation.extra.is_empty())
+            .map(|generation| {
+                format!("generation {}: unknown fields {}", generation.generation_uuid, field_list(&generation.extra))
+            });
+        skipped.chain(sessions).chain(generations).collect()
+    }
+
+    /// Every warning about the history: the records that were skipped,
+    /// then the ones kept with something wrong with them.
+    pub fn all_warnings(&self) -> Vec<Warning> {
+        self.skipped.iter().map(Warning::from).chain(self.warnings.iter().cloned()).collect()
+    }
+}
+
+fn field_list(fields: &Map<String, Value>) -> String {
+    fields.keys().map(|name| format!("`{}`", name)).collect::<Vec<_>>().join(", ")
+}
+
+/// Imports chat sessions from Continue.dev.
+/// Continue writes one JSON file per session into `~/.continue/sessions`,
+/// plus a `sessions.json` index that remembers when each one was created.
+#[cfg(feature = "native")]
+pub struct ContinueImporter {
+    /// Where the session files live (normally a `JsonDirStore`)
+    store: Box<dyn RecordStore>,
+    /// What unit the numeric dates in the index are in
+    unit: TimeUnit,
+}
+
+#[cfg(feature = "native")]
+impl ContinueImporter {
+    /// Create a new importer for the configured Continue sessions directory.
+    pub fn new(config: &Config) -> Result<Self> {
+        let store = JsonDirStore::open(&config.continue_sessions_path)
+            .with_context(|| "Could not open the Continue sessions directory")?;
+        Ok(ContinueImporter::with_store(Box::new(store)).with_time_unit(config.timestamp_unit()))
+    }
+
+    /// Create an importer that reads session records from any store.
+    pub fn with_store(store: Box<dyn RecordStore>) -> Self {
+        ContinueImporter { store, unit: TimeUnit::Auto }
+    }
+
+    /// Read numeric dates as `unit` rather than working it out.
+    pub fn with_time_unit(mut self, unit: TimeUnit) -> Self {
+        self.unit = unit;
+        self
+    }
+
+    /// Read every session record and turn it into lore-ready data.
+    pub async fn import(&self) -> Result<ImportedHistory> {
+        // The index is optional - older Continue versions didn't always write it,
+        // and we can still fall back to file timestamps without it
+        let index: Vec<Value> = self
+            .store
+            .get_key("sessions")
+            .await?
+            .and_then(|json_str| serde_json::from_str(&json_str).ok())
+            .unwrap_or_default();
+
+        let mut sessions = Vec::new();
+        let mut skipped = Vec::new();
+        let mut warnings = Vec::new();
+        for (key, json_str) in self.store.scan_prefix("").await? {
+            if key == "sessions" {
+                continue;
+            }
+            // One unreadable session file shouldn't stop the rest from importing
+            let value: Value = match serde_json::from_str(&json_str) {
+                Ok(value) => value,
+                Err(error) => {
+                    skipped.push(SkippedRecord {
+                        kind: "Continue session".to_string(),
+                        location: key,
+                        error: error.to_string(),
+                    });
+                    continue;
+                }
+            };
+            // Only a real sessions directory has mtimes; other stores just won't find the file
+            let path = Path::new(&self.store.location()).join(format!("{}.json", key));
+            let modified_at = file_modified_millis(&path);
+            sessions.push(parse_continue_session(&key, &value, &index, modified_at, self.unit, &mut warnings));
+        }
+
+        Ok(ImportedHistory {
+            skipped,
+            warnings,
+            ..ImportedHistory::from_sessions(sessions, "continue")
+        })
+    }
+}
+
+#[cfg(feature = "native")]
+fn parse_continue_session(
+    key: &str,
+    value: &Value,
+    index: &[Value],
+    modified_at: Option<i64>,
+    unit: TimeUnit,
+    warnings: &mut Vec<Warning>,
+) -> ChatSession {
+    let session_id = value["sessionId"].as_str().unwrap_or(key).to_string();
+
+    // Prefer the creation date from the index, then fall back to the file's mtime
+    let indexed = index
+        .iter()
+        .find(|entry| entry["sessionId"].as_str() == Some(session_id.as_str()))
+        .and_then(|entry| parse_millis(&entry["dateCreated"], unit));
+    let created_at = match (indexed, modified_at) {
+        (Some(created_at), _) => created_at,
+        (None, Some(modified_at)) => {
+            warnings.push(Warning::new(
+                WarningKind::MissingTimestamp,
+                format!("Continue session {}", session_id),
+                "not in the sessions index; dated by when its file was last changed",
+            ));
+            modified_at
+        }
+        // Left as zero for timestamp validation to flag
+        (None, None) => 0,
+    };
+
+    let messages: Vec<ChatMessage> = value["history"]
+        .as_array()
+        .map(|items| items.iter().filter_map(parse_continue_message).collect())
+        .unwrap_or_default();
+
+    ChatSession {
+        session_type: "head".to_string(),
+        composer_id: session_id,
+        name: value["title"].as_str().unwrap_or("Untitled Continue session").to_string(),
+        last_updated_at: modified_at.unwrap_or(created_at),
+        created_at,
+        unified_mode: "chat".to_string(),
+        force_mode: "chat".to_string(),
+        has_unread_messages: false,
+        messages,
+        extra: Default::default(),
+    }
+}
+
+/// Pull a single message out of a Continue history item.
+/// Newer versions wrap the message as `{ "message": { ... } }`, older ones don't,
+/// and content can be either a plain string or a list of typed parts.
+#[cfg(feature = "native")]
+fn parse_continue_message(item: &Value) -> Option<ChatMessage> {
+    let message = item.get("message").unwrap_or(item);
+    let role = match message["role"].as_str()? {
+        "user" => MessageRole::User,
+        "assistant" => MessageRole::Assistant,
+        // System prompts, tool results and "thinking" blocks aren't conversation
+        _ => return None,
+    };
+
+    let text = match &message["content"] {
+        Value::String(text) => text.clone(),
+        Value::Array(parts) => parts
+            .iter()
+            .filter_map(|part| part["text"].as_str())
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => return None,
+    };
+
+    // Files pulled in with @-mentions show up as context items with a file URI
+    let file_refs = item["contextItems"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|context_item| context_item["uri"]["type"].as_str() == Some("file"))
+        .filter_map(|context_item| whole_file(&context_item["uri"]))
+        .collect();
+
+    // Agent mode records tool calls OpenAI-style on the assistant message
+    let tool_calls: Vec<ToolCall> = message["toolCalls"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|call| {
+            let function = &call["function"];
+            Some(tool_call(function["name"].as_str()?, &function["arguments"], None))
+        })
+        .collect();
+
+    // Continue logs the model it called for each response alongside the message
+    let model = item["promptLogs"]
+        .as_array()
+        .and_then(|logs| logs.last())
+        .and_then(|log| log["completionOptions"]["model"].as_str().or_else(|| log["modelTitle"].as_str()))
+        .map(str::to_string);
+
+    Some(ChatMessage {
+        file_refs,
+        edits: tool_calls.iter().filter_map(edit_from_tool_call).collect(),
+        tool_calls,
+        model,
+        ..ChatMessage::new(role, text)
+    })
+}
+
+/// Imports chat history from Sourcegraph Cody.
+/// Cody keeps its history in VS Code's global state database, and can also
+/// export it to a JSON file - we accept either one.
+#[cfg(feature = "native")]
+pub struct CodyImporter {
+    /// Where the history record lives
+    store: Box<dyn RecordStore>,
+    /// The key Cody's history is stored under
+    key: String,
+    /// What unit the numeric interaction times are in
+    unit: TimeUnit,
+}
+
+#[cfg(feature = "native")]
+impl CodyImporter {
+    /// Create a new importer for the configured Cody history location.
+    pub async fn new(config: &Config) -> Result<Self> {
+        let path = shellexpand::tilde(&config.cody_history_path).to_string();
+
+        let store: Box<dyn RecordStore> = if path.ends_with(".json") {
+            // An export is a single JSON document, so just hold it in memory
+            let json_str = fs::read_to_string(&path)
+                .with_context(|| format!("Could not read Cody history export {}", path))?;
+            Box::new(MemoryStore::new().with_record(config.cody_history_key.clone(), json_str))
+        } else {
+            // It's VS Code's state database, so the history is a value in ItemTable
+            let options = ConnectionOptions::from_config(config);
+            Box::new(SqliteStore::open_with(&path, "ItemTable", &options).await?)
+        };
+
+        Ok(CodyImporter::with_store(store, &config.cody_history_key).with_time_unit(config.timestamp_unit()))
+    }
+
+    /// Create an importer that reads the history stored under `key` in any store.
+    pub fn with_store(store: Box<dyn RecordStore>, key: &str) -> Self {
+        CodyImporter {
+            store,
+            key: key.to_string(),
+            unit: TimeUnit::Auto,
+        }
+    }
+
+    /// Read numeric interaction times as `unit` rather than working it out.
+    pub fn with_time_unit(mut self, unit: TimeUnit) -> Self {
+        self.unit = unit;
+        self
+    }
+
+    /// Read Cody's history and turn every chat into a session with a transcript.
+    pub async fn import(&self) -> Result<ImportedHistory> {
+        let json_str = self.store.require_key(&self.key).await?;
+        let value: Value = serde_json::from_str(&json_str)?;
+        let sessions = collect_cody_transcripts(&value)
+            .into_iter()
+            .map(|transcript| parse_cody_transcript(transcript, self.unit))
+            .collect();
+
+        // A chat without a last interaction time comes out dated zero,
+        // for timestamp validation to flag
+        Ok(ImportedHistory::from_sessions(sessions, "cody"))
+    }
+}
+
+/// Find all chat transcripts in Cody's history JSON.
+/// The stored format is keyed by account (`{ account: { chat: { id: transcript } } }`),
+/// while exports may be just the `{ chat: ... }` object or a plain list.
+#[cfg(feature = "native")]
+fn collect_cody_transcripts(value: &Value) -> Vec<&Value> {
+    match value {
+        Value::Array(items) => items.iter().collect(),
+        Value::Object(map) => match map.get("chat").and_then(Value::as_object) {
+            Some(chats) => chats.values().collect(),
+            None => map.values().flat_map(collect_cody_transcripts).collect(),
+        },
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(feature = "native")]
+fn parse_cody_transcript(transcript: &Value, unit: TimeUnit) -> ChatSession {
+    let last_interaction = parse_millis(&transcript["lastInteractionTimestamp"], unit).unwrap_or_default();
+
+    let mut messages = Vec::new();
+    for interaction in transcript["interactions"].as_array().into_iter().flatten() {
+        if let Some(text) = interaction["humanMessage"]["text"].as_str() {
+            messages.push(ChatMessage {
+                file_refs: parse_cody_context_files(&interaction["humanMessage"]),
+                ..ChatMessage::new(MessageRole::User, text)
+            });
+        }
+        if let Some(text) = interaction["assistantMessage"]["text"].as_str() {
+            messages.push(ChatMessage {
+                model: interaction["assistantMessage"]["model"].as_str().map(str::to_string),
+                ..ChatMessage::new(MessageRole::Assistant, text)
+            });
+        }
+    }
+
+    // Cody only titles chats you've renamed, so otherwise use the opening question
+    let name = transcript["chatTitle"]
+        .as_str()
+        .filter(|title| !title.is_empty())
+        .map(str::to_string)
+        .or_else(|| {
+            messages
+                .first()
+                .map(|message| message.text.lines().next().unwrap_or_default().chars().take(60).collect())
+        })
+        .unwrap_or_else(|| "Untitled Cody chat".to_string());
+
+    ChatSession {
+        session_type: "head".to_string(),
+        composer_id: transcript["id"].as_str().unwrap_or_default().to_string(),
+        name,
+        last_updated_at: last_interaction,
+        created_at: last_interaction,
+        unified_mode: "chat".to_string(),
+        force_mode: "chat".to_string(),
+        has_unread_messages: false,
+        messages,
+        extra: Default::default(),
+    }
+}
+
+/// Pull the files Cody attached as context to a human message.
+/// Cody's ranges are zero-based, so we shift them to the line numbers people see.
+#[cfg(feature = "native")]
+fn parse_cody_context_files(message: &Value) -> Vec<FileReference> {
+    message["contextFiles"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|file| {
+            Some(FileReference {
+                path: uri_to_path(&file["uri"])?,
+                start_line: file["range"]["start"]["line"].as_u64().map(|line| line as u32 + 1),
+                end_line: file["range"]["end"]["line"].as_u64().map(|line| line as u32 + 1),
+            })
+        })
+        .collect()
+}
+
+#[cfg(feature = "native")]
+fn file_modified_millis(path: &Path) -> Option<i64> {
+    let modified = fs::metadata(path).ok()?.modified().ok()?;
+    let duration = modified.duration_since(std::time::UNIX_EPOCH).ok()?;
+    Some(duration.as_millis() as i64)
+}
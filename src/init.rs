@@ -0,0 +1,199 @@
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::atomic::write_atomic;
+use crate::workspace_info::WorkspaceInfo;
+
+/// Where Cursor keeps its `User` directory (with `workspaceStorage` and
+/// `globalStorage` in it) on this platform: `~/Library/Application
+/// Support/Cursor/User` on macOS, `%APPDATA%\Cursor\User` on Windows, and
+/// `$XDG_CONFIG_HOME/Cursor/User` (`~/.config/Cursor/User`) elsewhere.
+pub fn cursor_user_dir() -> Result<PathBuf> {
+    cursor_user_dir_with(std::env::consts::OS, |name| std::env::var(name).ok())
+}
+
+/// The same, for the platform `os` (as in `std::env::consts::OS`) and
+/// reading the environment through `env` (for tests).
+pub fn cursor_user_dir_with(os: &str, env: impl Fn(&str) -> Option<String>) -> Result<PathBuf> {
+    let home = || env("HOME").or_else(|| env("USERPROFILE")).filter(|home| !home.is_empty());
+    let base = match os {
+        "macos" => home().map(|home| Path::new(&home).join("Library").join("Application Support")),
+        "windows" => env("APPDATA")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| home().map(|home| Path::new(&home).join("AppData").join("Roaming"))),
+        _ => env("XDG_CONFIG_HOME")
+            .filter(|dir| Path::new(dir).is_absolute())
+            .map(PathBuf::from)
+            .or_else(|| home().map(|home| Path::new(&home).join(".config"))),
+    };
+    match base {
+        Some(base) => Ok(base.join("Cursor").join("User")),
+        None => bail!("Could not find a home directory to look for Cursor in; pass --storage"),
+    }
+}
+
+/// A Cursor workspace `init` can set a project up for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorkspaceCandidate {
+    /// The workspace ID: its directory's name in workspaceStorage
+    pub id: String,
+    /// What it has open, from workspace.json: a folder, a `.code-workspace`
+    /// file, or a remote URI, as Cursor wrote it
+    pub uri: Option<String>,
+    /// The local folder it has open, when that's a `file:` URI
+    pub folder: Option<PathBuf>,
+    /// When its database was last written to
+    pub last_used: Option<SystemTime>,
+}
+
+impl WorkspaceCandidate {
+    /// How to show it in a list: the folder (or URI) it has open.
+    pub fn label(&self) -> String {
+        match (&self.folder, &self.uri) {
+            (Some(folder), _) => folder.display().to_string(),
+            (None, Some(uri)) => uri.clone(),
+            (None, None) => format!("(nothing open) {}", self.id),
+        }
+    }
+
+    /// Whether it has `dir`, or a directory `dir` is in, open.
+    pub fn contains(&self, dir: &Path) -> bool {
+        self.folder.as_ref().is_some_and(|folder| dir.starts_with(folder))
+    }
+}
+
+/// The workspaces in `workspace_storage` that have a chat database,
+/// most recently used first.
+pub fn find_workspaces(workspace_storage: &Path, db_filename: &str) -> Result<Vec<WorkspaceCandidate>> {
+    let entries = fs::read_dir(workspace_storage)
+        .with_context(|| format!("Could not read {} - is Cursor installed? (pass --storage)", workspace_storage.display()))?;
+    let mut workspaces: Vec<WorkspaceCandidate> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().join(db_filename).is_file())
+        .map(|entry| {
+            let dir = entry.path();
+            let workspace_json = fs::read_to_string(dir.join("workspace.json")).ok();
+            let info = WorkspaceInfo::from_records(workspace_json.as_deref(), None, None);
+            WorkspaceCandidate {
+                id: entry.file_name().to_string_lossy().into_owned(),
+                uri: info.folder_uri,
+                folder: info.folder.map(PathBuf::from),
+                last_used: fs::metadata(dir.join(db_filename)).and_then(|metadata| metadata.modified()).ok(),
+            }
+        })
+        .collect();
+    workspaces.sort_by(|a, b| b.last_used.cmp(&a.last_used).then_with(|| a.id.cmp(&b.id)));
+    Ok(workspaces)
+}
+
+/// The local path in a `file:` URI, percent-decoded (`file:///c%3A/code`
+/// is `c:/code`). `None` for any other kind of URI.
+pub fn file_uri_path(uri: &str) -> Option<PathBuf> {
+    let path = uri.strip_prefix("file://")?;
+    // file://host/share is a UNC path; the usual file:///path has an empty host
+    let path = match path.strip_prefix('/') {
+        Some(_) => path.to_string(),
+        None => format!("//{}", path),
+    };
+    let mut bytes = Vec::with_capacity(path.len());
+    let mut rest = path.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        let decoded = (byte == b'%')
+            .then(|| tail.get(..2))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match decoded {
+            Some(decoded) => {
+                bytes.push(decoded);
+                rest = &tail[2..];
+            }
+            None => {
+                bytes.push(byte);
+                rest = tail;
+            }
+        }
+    }
+    let path = String::from_utf8_lossy(&bytes).into_owned();
+    // A Windows drive comes out as /c:/code
+    let is_drive = path.len() > 2 && path.as_bytes()[2] == b':' && path.as_bytes()[1].is_ascii_alphabetic();
+    Some(PathBuf::from(if is_drive { &path[1..] } else { &path }))
+}
+
+/// What `init` writes into the config file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InitSettings {
+    /// `NAME=value` settings, in the order they're written
+    pub settings: Vec<(String, String)>,
+}
+
+impl InitSettings {
+    /// Add a setting.
+    pub fn set(mut self, name: &str, value: impl Into<String>) -> Self {
+        self.settings.push((name.to_string(), value.into()));
+        self
+    }
+
+    /// The config file: a comment saying where it came from, then each
+    /// setting, quoted when it has to be.
+    pub fn to_env(&self, header: &str) -> String {
+        let mut text = String::new();
+        for line in header.lines() {
+            text.push_str(&if line.is_empty() { "#\n".to_string() } else { format!("# {}\n", line) });
+        }
+        text.push('\n');
+        for (name, value) in &self.settings {
+            text.push_str(&format!("{}={}\n", name, env_value(value)));
+        }
+        text
+    }
+}
+
+/// A value as it goes in a dotenv file: as it is if it's plain, in single
+/// quotes (where nothing is expanded) if it has spaces or anything else
+/// dotenv would read differently.
+fn env_value(value: &str) -> String {
+    let plain = !value.contains(|c: char| c.is_whitespace() || matches!(c, '#' | '"' | '\'' | '$' | '\\'));
+    if plain {
+        value.to_string()
+    } else if !value.contains(['\'', '\n']) {
+        format!("'{}'", value)
+    } else {
+        format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\"").replace('$', "\\$").replace('\n', "\\n"))
+    }
+}
+
+/// A path with the home directory written as `~`, the way the config file
+/// has it, so it still works for whoever else uses the file.
+pub fn tilde_path(path: &Path) -> String {
+    let home = std::env::var("HOME").ok().filter(|home| !home.is_empty());
+    match home.and_then(|home| path.strip_prefix(home).ok().map(Path::to_path_buf)) {
+        Some(rest) if rest.as_os_str().is_empty() => "~".to_string(),
+        Some(rest) => format!("~/{}", rest.display()),
+        None => path.display().to_string(),
+    }
+}
+
+/// Make sure the `.gitignore` in `dir` ignores `entry`, adding it (and
+/// the file) if it doesn't. Returns whether anything was added.
+pub fn add_to_gitignore(dir: &Path, entry: &str) -> Result<bool> {
+    let path = dir.join(".gitignore");
+    let text = fs::read_to_string(&path).unwrap_or_default();
+    let bare = entry.trim_matches('/');
+    let ignored = text
+        .lines()
+        .map(|line| line.trim().trim_matches('/'))
+        .any(|line| line == bare || line == format!("{}/*", bare));
+    if ignored {
+        return Ok(false);
+    }
+    let mut updated = text;
+    if !updated.is_empty() && !updated.ends_with('\n') {
+        updated.push('\n');
+    }
+    updated.push_str(&format!("{}/\n", bare));
+    write_atomic(&path, updated.as_bytes())?;
+    Ok(true)
+}
@@ -0,0 +1,233 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+
+use crate::adr::{decision_hash, title};
+use crate::config::Config;
+use crate::decisions::session_decisions;
+use crate::followups::find_follow_ups;
+use crate::{ChatGeneration, ChatPrompt, ComposerData, MessageRole};
+
+pub mod github;
+pub mod linear;
+
+/// Where `issues` can file follow-ups and decisions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IssueTarget {
+    /// A GitHub repository's issues, through the REST API
+    GitHub,
+    /// A Linear team's issues, through the GraphQL API
+    Linear,
+}
+
+impl IssueTarget {
+    /// The values `issues` accepts.
+    pub const NAMES: &'static [&'static str] = &["github", "linear"];
+
+    /// Parse an `issues` target.
+    pub fn parse(name: &str) -> Result<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "github" => Ok(IssueTarget::GitHub),
+            "linear" => Ok(IssueTarget::Linear),
+            other => Err(anyhow!(
+                "Unknown issue tracker '{}' (expected {})",
+                other,
+                IssueTarget::NAMES.join(", ")
+            )),
+        }
+    }
+
+    /// The tracker's name, for messages.
+    pub fn label(&self) -> &'static str {
+        match self {
+            IssueTarget::GitHub => "GitHub",
+            IssueTarget::Linear => "Linear",
+        }
+    }
+
+    /// Connect to the tracker with the credentials in the config.
+    pub fn tracker(&self, config: &Config) -> Result<Box<dyn IssueTracker>> {
+        Ok(match self {
+            IssueTarget::GitHub => Box::new(github::GitHubTracker::new(config)?),
+            IssueTarget::Linear => Box::new(linear::LinearTracker::new(config)?),
+        })
+    }
+}
+
+/// What issues get filed for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IssueKind {
+    /// The open follow-ups, as the Open follow-ups section lists them
+    FollowUp,
+    /// The decisions, as the Decisions section lists them
+    Decision,
+}
+
+impl IssueKind {
+    /// The values `issues --from` accepts.
+    pub const NAMES: &'static [&'static str] = &["follow-ups", "decisions"];
+
+    /// Parse an `issues --from` value.
+    pub fn parse(name: &str) -> Result<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "follow-ups" | "followups" => Ok(IssueKind::FollowUp),
+            "decisions" => Ok(IssueKind::Decision),
+            other => Err(anyhow!(
+                "Can't file issues for '{}' (expected {})",
+                other,
+                IssueKind::NAMES.join(", ")
+            )),
+        }
+    }
+}
+
+/// An issue to file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Issue {
+    /// What identifies the issue between runs, like `followup-1a2b3c4d5e6f`.
+    /// It's written into the issue body, which is how later runs find the
+    /// issue again and update it instead of filing a second one.
+    pub lore_id: String,
+    /// What it was filed for
+    pub kind: IssueKind,
+    /// The issue title
+    pub title: String,
+    /// The issue body in markdown, without the lore-ID marker
+    pub body: String,
+}
+
+/// What happened to an issue when it was filed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IssueAction {
+    /// There was no issue for it yet
+    Created,
+    /// An earlier run's issue was brought up to date
+    Updated,
+    /// An earlier run's issue already said the same
+    Unchanged,
+}
+
+/// One issue that was filed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Filed {
+    /// The issue's lore ID
+    pub lore_id: String,
+    /// The issue title
+    pub title: String,
+    /// Whether it was new
+    pub action: IssueAction,
+    /// Where to read it, if the tracker told us
+    pub url: Option<String>,
+}
+
+/// Something that can file issues. Filing is idempotent: issues from an
+/// earlier run are found again by the lore ID in their body and updated
+/// in place.
+#[async_trait]
+pub trait IssueTracker: Send + Sync {
+    /// Create or update an issue for each of `issues`.
+    async fn file(&self, issues: &[Issue]) -> Result<Vec<Filed>>;
+}
+
+/// The issues to file for the follow-ups and decisions in the chats,
+/// oldest first, with their excerpts redacted.
+pub fn plan_issues(
+    config: &Config,
+    sessions: &[ComposerData],
+    generations: &[ChatGeneration],
+    prompts: &[ChatPrompt],
+    kinds: &[IssueKind],
+) -> Vec<Issue> {
+    let zone = config.display_zone();
+    let date = |timestamp: i64| zone.format_millis(timestamp, "%Y-%m-%d").unwrap_or_default();
+    let mut issues: Vec<(Option<i64>, Issue)> = Vec::new();
+
+    if kinds.contains(&IssueKind::FollowUp) {
+        for follow_up in find_follow_ups(sessions, generations, prompts) {
+            let excerpt = config.redact_secrets(&follow_up.excerpt);
+            let mut body = format!("> {}\n\n", excerpt);
+            match (&follow_up.session_name, &follow_up.session_id) {
+                (Some(name), Some(id)) => body.push_str(&format!("Left for later in the chat session \"{}\" (`{}`)", name, id)),
+                _ => body.push_str("Left for later in the chats"),
+            }
+            match follow_up.timestamp {
+                Some(timestamp) => body.push_str(&format!(" on {}.\n", date(timestamp))),
+                None => body.push_str(".\n"),
+            }
+            let issue = Issue {
+                lore_id: format!("followup-{}", follow_up.id),
+                kind: IssueKind::FollowUp,
+                title: title(&excerpt),
+                body,
+            };
+            issues.push((follow_up.timestamp, issue));
+        }
+    }
+
+    if kinds.contains(&IssueKind::Decision) {
+        for session in sessions.iter().flat_map(|data| &data.all_composers) {
+            for decision in session_decisions(session) {
+                let excerpt = config.redact_secrets(&decision.excerpt);
+                let speaker = match decision.role {
+                    MessageRole::User => "the user",
+                    MessageRole::Assistant => "the assistant",
+                };
+                let body = format!(
+                    "> {}\n\nDecided by {} in the chat session \"{}\" (`{}`) on {}.\n",
+                    excerpt,
+                    speaker,
+                    session.name,
+                    session.composer_id,
+                    date(decision.timestamp)
+                );
+                let issue = Issue {
+                    lore_id: format!("decision-{}", decision_hash(session, &decision)),
+                    kind: IssueKind::Decision,
+                    title: title(&excerpt),
+                    body,
+                };
+                issues.push((Some(decision.timestamp), issue));
+            }
+        }
+    }
+
+    // The ones with no time go last
+    issues.sort_by_key(|(timestamp, _)| (timestamp.is_none(), *timestamp));
+    issues.into_iter().map(|(_, issue)| issue).collect()
+}
+
+/// What filing `issues` would do, for `--dry-run`.
+pub fn describe_issues(issues: &[Issue], target: IssueTarget) -> String {
+    let mut description = format!("Would file {} issues in {}:\n", issues.len(), target.label());
+    for issue in issues {
+        description.push_str(&format!("  {} {}\n", issue.lore_id, issue.title));
+    }
+    description
+}
+
+/// The line that carries an issue's lore ID: an HTML comment, so it
+/// doesn't show, where the tracker keeps HTML in issue bodies.
+pub fn lore_id_marker(lore_id: &str, hidden: bool) -> String {
+    if hidden {
+        format!("<!-- lore-id: {} -->", lore_id)
+    } else {
+        format!("lore-id: {}", lore_id)
+    }
+}
+
+/// The lore ID in an issue body, if it has one (in either kind of marker).
+pub fn lore_id(body: &str) -> Option<&str> {
+    body.lines().find_map(|line| {
+        let line = line.trim();
+        let line = line.strip_prefix("<!-- ").and_then(|line| line.strip_suffix(" -->")).unwrap_or(line);
+        line.strip_prefix("lore-id: ").map(str::trim)
+    })
+}
+
+/// A setting a tracker can't work without.
+pub(crate) fn required<'a>(value: &'a str, name: &str, target: IssueTarget) -> Result<&'a str> {
+    if value.trim().is_empty() {
+        Err(anyhow!("{} needs to be set to file issues in {}", name, target.label()))
+    } else {
+        Ok(value.trim())
+    }
+}
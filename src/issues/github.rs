@@ -0,0 +1,132 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+use crate::config::Config;
+use crate::issues::{lore_id, lore_id_marker, required, Filed, Issue, IssueAction, IssueTarget, IssueTracker};
+use crate::publish::check;
+
+/// GitHub returns at most this many issues a page.
+const PAGE_SIZE: usize = 100;
+
+/// Files issues in a GitHub repository through the REST API. Issues carry
+/// the `GITHUB_ISSUE_LABELS` labels, and later runs look for their lore
+/// IDs among the issues with the first of them (open or closed).
+pub struct GitHubTracker {
+    client: reqwest::Client,
+    token: String,
+    repo: String,
+    labels: Vec<String>,
+}
+
+impl GitHubTracker {
+    /// Set up a tracker from `GITHUB_TOKEN`, `GITHUB_ISSUES_REPO` and
+    /// `GITHUB_ISSUE_LABELS`.
+    pub fn new(config: &Config) -> Result<Self> {
+        let target = IssueTarget::GitHub;
+        let repo = required(&config.github_issues_repo, "GITHUB_ISSUES_REPO", target)?;
+        if repo.split('/').filter(|part| !part.is_empty()).count() != 2 {
+            return Err(anyhow!("GITHUB_ISSUES_REPO should look like owner/name, not '{}'", repo));
+        }
+        Ok(GitHubTracker {
+            client: reqwest::Client::new(),
+            token: required(&config.github_token, "GITHUB_TOKEN", target)?.to_string(),
+            repo: repo.to_string(),
+            labels: config
+                .github_issue_labels
+                .split(',')
+                .map(str::trim)
+                .filter(|label| !label.is_empty())
+                .map(str::to_string)
+                .collect(),
+        })
+    }
+
+    async fn send(&self, method: reqwest::Method, path: &str, body: Option<Value>) -> Result<Value> {
+        let mut request = self
+            .client
+            .request(method, format!("https://api.github.com/repos/{}{}", self.repo, path))
+            .bearer_auth(&self.token)
+            .header("Accept", "application/vnd.github+json")
+            .header("User-Agent", "persistent-code-lore");
+        if let Some(body) = body {
+            request = request.json(&body);
+        }
+        let response = request.send().await?;
+        Ok(check(response, "talk to GitHub").await?.json().await?)
+    }
+
+    /// The issues earlier runs filed, by lore ID.
+    async fn existing(&self) -> Result<HashMap<String, Value>> {
+        let mut existing = HashMap::new();
+        for page in 1.. {
+            let mut path = format!("/issues?state=all&per_page={}&page={}", PAGE_SIZE, page);
+            if let Some(label) = self.labels.first() {
+                path.push_str(&format!("&labels={}", label));
+            }
+            let issues = self.send(reqwest::Method::GET, &path, None).await?;
+            let issues = issues.as_array().cloned().unwrap_or_default();
+            for issue in &issues {
+                // The issues endpoint lists pull requests too
+                if issue.get("pull_request").is_some() {
+                    continue;
+                }
+                if let Some(id) = issue["body"].as_str().and_then(lore_id) {
+                    existing.entry(id.to_string()).or_insert_with(|| issue.clone());
+                }
+            }
+            if issues.len() < PAGE_SIZE {
+                break;
+            }
+        }
+        Ok(existing)
+    }
+}
+
+#[async_trait]
+impl IssueTracker for GitHubTracker {
+    async fn file(&self, issues: &[Issue]) -> Result<Vec<Filed>> {
+        let existing = self.existing().await?;
+        let mut filed = Vec::new();
+        for issue in issues {
+            let body = format!("{}\n{}\n", issue.body, lore_id_marker(&issue.lore_id, true));
+            let (found, action) = match existing.get(&issue.lore_id) {
+                Some(found)
+                    if found["title"].as_str() == Some(issue.title.as_str())
+                        && found["body"].as_str().map(str::trim) == Some(body.trim()) =>
+                {
+                    (found.clone(), IssueAction::Unchanged)
+                }
+                Some(found) => {
+                    let number = found["number"].as_u64().ok_or_else(|| anyhow!("GitHub didn't say which issue this is"))?;
+                    let updated = self
+                        .send(
+                            reqwest::Method::PATCH,
+                            &format!("/issues/{}", number),
+                            Some(json!({ "title": issue.title, "body": body })),
+                        )
+                        .await?;
+                    (updated, IssueAction::Updated)
+                }
+                None => {
+                    let created = self
+                        .send(
+                            reqwest::Method::POST,
+                            "/issues",
+                            Some(json!({ "title": issue.title, "body": body, "labels": self.labels })),
+                        )
+                        .await?;
+                    (created, IssueAction::Created)
+                }
+            };
+            filed.push(Filed {
+                lore_id: issue.lore_id.clone(),
+                title: issue.title.clone(),
+                action,
+                url: found["html_url"].as_str().map(str::to_string),
+            });
+        }
+        Ok(filed)
+    }
+}
@@ -0,0 +1,130 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+use crate::config::Config;
+use crate::issues::{lore_id, lore_id_marker, required, Filed, Issue, IssueAction, IssueTarget, IssueTracker};
+use crate::publish::check;
+
+/// Linear's GraphQL endpoint.
+const LINEAR_API: &str = "https://api.linear.app/graphql";
+
+/// Earlier runs' issues: the team's issues whose description has a lore ID.
+const EXISTING_QUERY: &str = "query($team: ID!, $after: String) {
+  issues(first: 100, after: $after, filter: { team: { id: { eq: $team } }, description: { contains: \"lore-id: \" } }) {
+    nodes { id url title description }
+    pageInfo { hasNextPage endCursor }
+  }
+}";
+
+const CREATE_MUTATION: &str = "mutation($input: IssueCreateInput!) {
+  issueCreate(input: $input) { success issue { id url } }
+}";
+
+const UPDATE_MUTATION: &str = "mutation($id: String!, $input: IssueUpdateInput!) {
+  issueUpdate(id: $id, input: $input) { success issue { id url } }
+}";
+
+/// Files issues in a Linear team through the GraphQL API. Linear doesn't
+/// keep HTML in descriptions, so the lore ID is a visible last line.
+pub struct LinearTracker {
+    client: reqwest::Client,
+    api_key: String,
+    team: String,
+}
+
+impl LinearTracker {
+    /// Set up a tracker from the `LINEAR_*` settings.
+    pub fn new(config: &Config) -> Result<Self> {
+        let target = IssueTarget::Linear;
+        Ok(LinearTracker {
+            client: reqwest::Client::new(),
+            api_key: required(&config.linear_api_key, "LINEAR_API_KEY", target)?.to_string(),
+            team: required(&config.linear_team_id, "LINEAR_TEAM_ID", target)?.to_string(),
+        })
+    }
+
+    /// Run a query, returning its data. GraphQL errors come back with a
+    /// 200, so they're turned into errors here.
+    async fn query(&self, query: &str, variables: Value) -> Result<Value> {
+        let response = self
+            .client
+            .post(LINEAR_API)
+            .header("Authorization", &self.api_key)
+            .json(&json!({ "query": query, "variables": variables }))
+            .send()
+            .await?;
+        let mut result: Value = check(response, "talk to Linear").await?.json().await?;
+        if let Some(errors) = result["errors"].as_array().filter(|errors| !errors.is_empty()) {
+            let messages: Vec<&str> = errors.iter().filter_map(|error| error["message"].as_str()).collect();
+            return Err(anyhow!("Linear turned the request down: {}", messages.join("; ")));
+        }
+        Ok(result["data"].take())
+    }
+
+    /// The issues earlier runs filed, by lore ID.
+    async fn existing(&self) -> Result<HashMap<String, Value>> {
+        let mut existing = HashMap::new();
+        let mut after: Option<String> = None;
+        loop {
+            let data = self.query(EXISTING_QUERY, json!({ "team": self.team, "after": after })).await?;
+            let issues = &data["issues"];
+            for issue in issues["nodes"].as_array().into_iter().flatten() {
+                if let Some(id) = issue["description"].as_str().and_then(lore_id) {
+                    existing.entry(id.to_string()).or_insert_with(|| issue.clone());
+                }
+            }
+            match issues["pageInfo"]["endCursor"].as_str() {
+                Some(cursor) if issues["pageInfo"]["hasNextPage"].as_bool() == Some(true) => after = Some(cursor.to_string()),
+                _ => return Ok(existing),
+            }
+        }
+    }
+
+    /// Run a create or update mutation, returning the issue it touched.
+    async fn mutate(&self, mutation: &str, name: &str, variables: Value) -> Result<Value> {
+        let mut data = self.query(mutation, variables).await?;
+        if data[name]["success"].as_bool() != Some(true) {
+            return Err(anyhow!("Linear couldn't save the issue"));
+        }
+        Ok(data[name]["issue"].take())
+    }
+}
+
+#[async_trait]
+impl IssueTracker for LinearTracker {
+    async fn file(&self, issues: &[Issue]) -> Result<Vec<Filed>> {
+        let existing = self.existing().await?;
+        let mut filed = Vec::new();
+        for issue in issues {
+            let description = format!("{}\n---\n\n{}\n", issue.body, lore_id_marker(&issue.lore_id, false));
+            let (found, action) = match existing.get(&issue.lore_id) {
+                Some(found)
+                    if found["title"].as_str() == Some(issue.title.as_str())
+                        && found["description"].as_str().map(str::trim) == Some(description.trim()) =>
+                {
+                    (found.clone(), IssueAction::Unchanged)
+                }
+                Some(found) => {
+                    let id = found["id"].as_str().ok_or_else(|| anyhow!("Linear didn't say which issue this is"))?;
+                    let input = json!({ "title": issue.title, "description": description });
+                    let updated = self.mutate(UPDATE_MUTATION, "issueUpdate", json!({ "id": id, "input": input })).await?;
+                    (updated, IssueAction::Updated)
+                }
+                None => {
+                    let input = json!({ "teamId": self.team, "title": issue.title, "description": description });
+                    let created = self.mutate(CREATE_MUTATION, "issueCreate", json!({ "input": input })).await?;
+                    (created, IssueAction::Created)
+                }
+            };
+            filed.push(Filed {
+                lore_id: issue.lore_id.clone(),
+                title: issue.title.clone(),
+                action,
+                url: found["url"].as_str().map(str::to_string),
+            });
+        }
+        Ok(filed)
+    }
+}
@@ -0,0 +1,85 @@
+use anyhow::{anyhow, Context, Result};
+use schemars::{schema_for, Schema};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::atomic::write_atomic;
+use crate::batch::BatchReport;
+use crate::daemon::{RunRecord, RUN_HISTORY_FILE};
+use crate::document::Document;
+use crate::followups::FollowUp;
+use crate::graph::KnowledgeGraph;
+use crate::lore_index::{LoreIndex, LORE_INDEX_FILE};
+use crate::report::RunReport;
+
+/// Where `schema export --dir` is pointed in this repository, so the
+/// schemas are versioned with the code that writes the formats.
+pub const SCHEMA_DIR: &str = "schemas";
+
+/// A JSON format the tool writes, and the schema it follows.
+#[derive(Debug, Clone)]
+pub struct ExportedSchema {
+    /// A short name for the format, which names its schema file
+    pub name: &'static str,
+    /// The JSON Schema (draft 2020-12), made from the Rust types
+    pub schema: Schema,
+}
+
+impl ExportedSchema {
+    fn new(name: &'static str, written_by: impl Into<String>, mut schema: Schema) -> Self {
+        // The version in the ID says which release a consumer generated against
+        schema.insert("$id".to_string(), format!("urn:persistent-code-lore:{}:{}", name, env!("CARGO_PKG_VERSION")).into());
+        schema.insert("$comment".to_string(), format!("Written by {}", written_by.into()).into());
+        ExportedSchema { name, schema }
+    }
+
+    /// The schema's file name: `run-report.schema.json`.
+    pub fn file_name(&self) -> String {
+        format!("{}.schema.json", self.name)
+    }
+
+    /// The schema as pretty JSON, ending in a newline.
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(&self.schema)? + "\n")
+    }
+}
+
+/// The schema of every JSON and JSON Lines format the tool writes, for
+/// consumers to validate against or generate code from.
+pub fn exported_schemas() -> Vec<ExportedSchema> {
+    vec![
+        ExportedSchema::new("document", "--format json", schema_for!(Document)),
+        ExportedSchema::new("run-report", "--report-json", schema_for!(RunReport)),
+        ExportedSchema::new("batch-report", "batch --report-json", schema_for!(BatchReport)),
+        ExportedSchema::new("lore-index", LORE_INDEX_FILE, schema_for!(LoreIndex)),
+        ExportedSchema::new("knowledge-graph", "GRAPH_FORMAT=json", schema_for!(KnowledgeGraph)),
+        ExportedSchema::new("follow-ups", "follow-ups --format json", schema_for!(Vec<FollowUp>)),
+        // JSON Lines: the schema is of each line
+        ExportedSchema::new("run-history", format!("{} (each line)", RUN_HISTORY_FILE), schema_for!(RunRecord)),
+    ]
+}
+
+/// The schema for one format, by name.
+pub fn exported_schema(name: &str) -> Result<ExportedSchema> {
+    let schemas = exported_schemas();
+    let names: Vec<&str> = schemas.iter().map(|schema| schema.name).collect();
+    let names = names.join(", ");
+    schemas
+        .into_iter()
+        .find(|schema| schema.name == name.trim())
+        .ok_or_else(|| anyhow!("There's no schema called '{}' (there are {})", name.trim(), names))
+}
+
+/// Write each of `schemas` into `dir` as `<name>.schema.json`, returning
+/// the files written.
+pub fn write_schemas(dir: &Path, schemas: &[ExportedSchema]) -> Result<Vec<PathBuf>> {
+    fs::create_dir_all(dir).with_context(|| format!("Couldn't create {}", dir.display()))?;
+    schemas
+        .iter()
+        .map(|schema| {
+            let path = dir.join(schema.file_name());
+            write_atomic(&path, schema.to_json()?.as_bytes())?;
+            Ok(path)
+        })
+        .collect()
+}
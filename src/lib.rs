@@ -1,19 +1,152 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::classify::GenerationKind;
 
 // Re-export our main modules so users can easily access everything they need
+#[cfg(feature = "native")]
+pub mod adr;
+pub mod atomic;
+#[cfg(feature = "native")]
+pub mod backup;
+#[cfg(feature = "native")]
+pub mod batch;
+#[cfg(feature = "native")]
+pub mod checkpoint;
+pub mod classify;
+#[cfg(any(feature = "native", feature = "sync"))]
 pub mod config;
+#[cfg(feature = "native")]
+pub mod content_hash;
+pub mod curation;
+pub mod daemon;
+pub mod decisions;
+#[cfg(feature = "native")]
+pub mod dirs;
+pub mod document;
+#[cfg(any(feature = "native", feature = "sync"))]
+pub mod edits;
+#[cfg(feature = "native")]
+pub mod email;
+#[cfg(any(feature = "native", feature = "sync"))]
+pub mod encrypt;
+#[cfg(any(feature = "native", feature = "sync"))]
 pub mod extractor;
+pub mod faq;
+pub mod fences;
+pub mod file_lore;
+#[cfg(feature = "native")]
+pub mod flags;
+pub mod followups;
+pub mod front_matter;
+#[cfg(feature = "native")]
 pub mod generator;
+pub mod git;
+#[cfg(any(feature = "native", feature = "sync"))]
+pub mod graph;
+pub mod grouping;
+pub mod hotspots;
+#[cfg(feature = "native")]
+pub mod hook;
+pub mod i18n;
+pub mod init;
+#[cfg(any(feature = "native", feature = "sync"))]
+pub mod importers;
+#[cfg(feature = "native")]
+pub mod issues;
+#[cfg(feature = "native")]
+pub mod json_schema;
+pub mod lint;
+pub mod lock;
+pub mod lore_index;
+pub mod lore_store;
+pub mod message_policy;
+pub mod namespace;
+pub mod naming;
+#[cfg(feature = "native")]
+pub mod notify;
+pub mod parsing;
+#[cfg(feature = "native")]
+pub mod plugins;
+#[cfg(any(feature = "native", feature = "sync"))]
+pub mod profile;
+pub mod progress;
+#[cfg(feature = "native")]
+pub mod project;
+#[cfg(feature = "native")]
+pub mod publish;
+pub mod redaction;
+#[cfg(any(feature = "native", feature = "sync"))]
+pub mod references;
+#[cfg(feature = "native")]
+pub mod related;
+#[cfg(feature = "native")]
+pub mod restore;
+pub mod render;
+pub mod report;
+#[cfg(any(feature = "native", feature = "sync"))]
+pub mod sanitize;
+pub mod schedule;
+#[cfg(any(feature = "native", feature = "sync"))]
+pub mod schema;
+#[cfg(feature = "native")]
+pub mod sections;
+#[cfg(feature = "native")]
+pub mod selection;
+pub mod site;
+pub mod snapshot;
+#[cfg(any(feature = "native", feature = "sync"))]
+pub mod store;
+#[cfg(feature = "sync")]
+pub mod sync;
+#[cfg(feature = "native")]
+pub mod synthetic;
+pub mod tabular;
+pub mod tags;
+#[cfg(feature = "native")]
+pub mod terminal;
+#[cfg(feature = "test-util")]
+pub mod test_util;
+pub mod time;
+pub mod timeline;
+#[cfg(any(feature = "native", feature = "sync"))]
+pub mod timestamps;
+#[cfg(any(feature = "native", feature = "sync"))]
+pub mod tools;
+#[cfg(feature = "native")]
+pub mod trivial;
+pub mod troubleshooting;
+pub mod usage;
+pub mod verify;
+pub mod versioning;
+pub mod warnings;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "native")]
+pub mod workspace;
+pub mod workspace_info;
 
 // Make the main types available at the crate root for convenience
-pub use config::Config;
+#[cfg(any(feature = "native", feature = "sync"))]
+pub use config::{Config, ConfigBuilder};
+#[cfg(feature = "native")]
 pub use extractor::ChatExtractor;
+#[cfg(feature = "native")]
 pub use generator::MarkdownGenerator;
+#[cfg(feature = "native")]
+pub use importers::{CodyImporter, ContinueImporter};
+pub use lore_store::LoreStore;
+pub use progress::{CancellationToken, Progress, ProgressReporter};
+#[cfg(feature = "native")]
+pub use store::{ConnectionOptions, JsonDirStore, MemoryStore, RecordStore, SqliteStore};
+#[cfg(feature = "native")]
+pub use workspace::TempWorkspace;
 
 /// Represents a single chat session from the database.
 /// This contains all the metadata about a conversation that happened
 /// in the chat application, like when it was created and what mode it used.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChatSession {
     /// The type of session (usually "head" for main sessions)
     #[serde(rename = "type")]
@@ -32,12 +165,128 @@ pub struct ChatSession {
     pub force_mode: String,
     /// Whether there are unread messages in this session
     pub has_unread_messages: bool,
+    /// The conversation itself, when the source keeps it alongside the session.
+    /// Cursor's composer list doesn't, so this is empty for Cursor sessions.
+    #[serde(default)]
+    pub messages: Vec<ChatMessage>,
+    /// Fields the source wrote that we don't model, kept so they survive
+    /// being exported and read back in
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// Who wrote a particular message in a conversation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum MessageRole {
+    /// Something the human typed
+    User,
+    /// Something the AI assistant answered
+    Assistant,
+}
+
+/// A single message inside a chat session's transcript.
+/// Importers for sources that keep whole conversations (like Continue.dev
+/// or Cody) fill these in so the transcript ends up in the lore too.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatMessage {
+    /// Who sent the message
+    pub role: MessageRole,
+    /// The message text, as the source stored it
+    pub text: String,
+    /// When the message was sent (Unix timestamp in milliseconds), if known
+    #[serde(default)]
+    pub timestamp: Option<i64>,
+    /// Files (or parts of files) the message pointed at
+    #[serde(default)]
+    pub file_refs: Vec<FileReference>,
+    /// Tools the assistant invoked while producing this message
+    #[serde(default)]
+    pub tool_calls: Vec<ToolCall>,
+    /// Changes the assistant made (or proposed) to files
+    #[serde(default)]
+    pub edits: Vec<FileEdit>,
+    /// Which model produced this message, if the source recorded it
+    #[serde(default)]
+    pub model: Option<String>,
+    /// How many tokens the source says this message used, if it recorded that
+    #[serde(default)]
+    pub usage: Option<TokenUsage>,
+}
+
+impl ChatMessage {
+    /// Create a plain message with just a role and some text.
+    /// Everything else starts out empty, ready to be filled in by the importer.
+    pub fn new(role: MessageRole, text: impl Into<String>) -> Self {
+        ChatMessage {
+            role,
+            text: text.into(),
+            timestamp: None,
+            file_refs: Vec::new(),
+            tool_calls: Vec::new(),
+            edits: Vec::new(),
+            model: None,
+            usage: None,
+        }
+    }
+}
+
+/// Token counts for a single model call.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenUsage {
+    /// Tokens sent to the model (prompt plus context)
+    pub input_tokens: u64,
+    /// Tokens the model generated
+    pub output_tokens: u64,
+}
+
+/// A change an agent made to a file, stored as a unified-style diff.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileEdit {
+    /// The file that was edited
+    pub path: String,
+    /// The change itself, as `-`/`+`/context lines with `@@` hunk headers
+    pub diff: String,
+    /// Whether the user kept the change ("accepted", "rejected", ...), if recorded
+    #[serde(default)]
+    pub status: Option<String>,
+}
+
+/// A tool the AI invoked in agent mode - reading a file, editing code,
+/// searching, or running a command in the terminal.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ToolCall {
+    /// The tool's name as the source reports it (e.g. "run_terminal_cmd")
+    pub name: String,
+    /// The raw arguments the tool was called with, usually a JSON string
+    #[serde(default)]
+    pub arguments: String,
+    /// The shell command, if this call ran something in a terminal
+    #[serde(default)]
+    pub command: Option<String>,
+    /// How the call ended up ("completed", "cancelled", ...), if recorded
+    #[serde(default)]
+    pub status: Option<String>,
+}
+
+/// A file that a message referred to - an attached file, a code selection,
+/// or a file the source marked as relevant context for the question.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileReference {
+    /// The path of the file, as the source recorded it (usually absolute)
+    pub path: String,
+    /// First line of the referenced range, if only part of the file was attached
+    #[serde(default)]
+    pub start_line: Option<u32>,
+    /// Last line of the referenced range
+    #[serde(default)]
+    pub end_line: Option<u32>,
 }
 
 /// Represents a single AI generation from the database.
 /// This contains information about what the AI generated during a conversation,
 /// including the text and when it was created.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChatGeneration {
     /// When this generation was created (Unix timestamp in milliseconds)
     pub unix_ms: i64,
@@ -47,11 +296,22 @@ pub struct ChatGeneration {
     pub r#type: String,
     /// The actual text that was generated
     pub text_description: String,
+    /// Which model produced the generation, where the database records it
+    #[serde(default, alias = "modelName")]
+    pub model: Option<String>,
+    /// What the generation mostly is (explanation, code, plan, error
+    /// analysis), once `classify::classify_generations` has labelled it
+    #[serde(default)]
+    pub kind: Option<GenerationKind>,
+    /// Fields the source wrote that we don't model, kept so they survive
+    /// being exported and read back in
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 /// Represents a user prompt from the database.
 /// This contains the text that the user typed to start or continue a conversation.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChatPrompt {
     /// The actual prompt text that the user entered
     pub text: String,
@@ -62,7 +322,7 @@ pub struct ChatPrompt {
 /// Container for all the chat sessions from the database.
 /// This is what we get when we extract the composer data - it contains
 /// a list of all the chat sessions that were found.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ComposerData {
     /// All the chat sessions that were found in the database
     pub all_composers: Vec<ChatSession>,
@@ -0,0 +1,218 @@
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::LazyLock;
+
+use crate::document::heading_anchor;
+use crate::i18n::Strings;
+use crate::redaction::redact_secrets;
+
+/// The biggest a section gets before `lint` calls it oversized, by default.
+pub const DEFAULT_MAX_SECTION_KB: usize = 256;
+
+/// Home directories and other absolute paths that say who and where
+/// someone is: `/Users/ana/`, `/home/ana/`, `/root/`, `C:\Users\ana\`.
+static ABSOLUTE_PATH: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?:^|[\s(`'\x22=:])((?:/Users|/home)/[^/\s]+/|/root/|[A-Za-z]:\\Users\\[^\\\s]+\\)")
+        .expect("path pattern should compile")
+});
+
+/// Links to a heading in the same document: `[text](#anchor)` and `href="#anchor"`.
+static ANCHOR_LINK: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r##"\]\(#([^)\s]+)\)|href="#([^"]+)""##).expect("anchor pattern should compile")
+});
+
+/// What kind of problem `lint` found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LintKind {
+    /// An absolute path that gives away a home directory
+    AbsolutePath,
+    /// Something that looks like a secret and wasn't redacted
+    Secret,
+    /// A link to a heading the document doesn't have
+    BrokenAnchor,
+    /// A session with no transcript
+    EmptySession,
+    /// A section bigger than the limit
+    OversizedSection,
+}
+
+impl LintKind {
+    /// The kind's name, for messages.
+    pub fn name(&self) -> &'static str {
+        match self {
+            LintKind::AbsolutePath => "absolute-path",
+            LintKind::Secret => "secret",
+            LintKind::BrokenAnchor => "broken-anchor",
+            LintKind::EmptySession => "empty-session",
+            LintKind::OversizedSection => "oversized-section",
+        }
+    }
+}
+
+/// A problem `lint` found, and where.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintProblem {
+    /// The line it's on (from 1)
+    pub line: usize,
+    /// What kind of problem it is
+    pub kind: LintKind,
+    /// What's wrong, without repeating any secret
+    pub message: String,
+}
+
+impl fmt::Display for LintProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}: {}", self.line, self.kind.name(), self.message)
+    }
+}
+
+/// What `lint` checks against.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintOptions {
+    /// How sessions are titled, like "Session" in `### Session 3: ...`
+    pub session_label: String,
+    /// The label the transcript starts with, like "Transcript"
+    pub transcript_label: String,
+    /// The most bytes a section (a `##` heading and all below it) can have
+    pub max_section_bytes: usize,
+}
+
+impl LintOptions {
+    /// Options for lore written in `strings`' language.
+    pub fn new(strings: &Strings, max_section_kb: usize) -> Self {
+        LintOptions {
+            session_label: strings.get("sessions.session").to_string(),
+            transcript_label: strings.get("sessions.transcript").to_string(),
+            max_section_bytes: max_section_kb * 1024,
+        }
+    }
+}
+
+impl Default for LintOptions {
+    fn default() -> Self {
+        LintOptions::new(&Strings::default(), DEFAULT_MAX_SECTION_KB)
+    }
+}
+
+/// A heading, as `lint` sees it.
+struct Heading<'a> {
+    line: usize,
+    level: usize,
+    text: &'a str,
+    /// Where in the document it starts, in bytes
+    offset: usize,
+}
+
+/// Check markdown lore for things that shouldn't be published: absolute
+/// paths, secrets that got past redaction, links to headings that aren't
+/// there, sessions with no transcript, and sections over the size limit.
+/// Code blocks are only checked for paths and secrets.
+pub fn lint_markdown(text: &str, options: &LintOptions) -> Vec<LintProblem> {
+    let mut problems = Vec::new();
+    let mut headings: Vec<Heading> = Vec::new();
+    let mut links: Vec<(usize, String)> = Vec::new();
+    let mut transcripts: HashSet<usize> = HashSet::new();
+    let transcript_line = format!("**{}**:", options.transcript_label);
+
+    let mut in_code = false;
+    let mut offset = 0;
+    for (index, line) in text.split_inclusive('\n').enumerate() {
+        let number = index + 1;
+        let start = offset;
+        offset += line.len();
+        let line = line.trim_end_matches(['\n', '\r']);
+
+        if let Some(found) = ABSOLUTE_PATH.captures(line).and_then(|captures| captures.get(1)) {
+            problems.push(LintProblem {
+                line: number,
+                kind: LintKind::AbsolutePath,
+                message: format!("absolute path {}", found.as_str()),
+            });
+        }
+        if redact_secrets(line) != line {
+            problems.push(LintProblem {
+                line: number,
+                kind: LintKind::Secret,
+                message: "this looks like a secret that wasn't redacted".to_string(),
+            });
+        }
+
+        if line.trim_start().starts_with("```") {
+            in_code = !in_code;
+            continue;
+        }
+        if in_code {
+            continue;
+        }
+        let hashes = line.chars().take_while(|&c| c == '#').count();
+        if (1..=6).contains(&hashes) && line[hashes..].starts_with(' ') {
+            headings.push(Heading { line: number, level: hashes, text: line[hashes..].trim(), offset: start });
+            continue;
+        }
+        if line.trim() == transcript_line {
+            if let Some(heading) = headings.last() {
+                transcripts.insert(heading.line);
+            }
+        }
+        for captures in ANCHOR_LINK.captures_iter(line) {
+            if let Some(anchor) = captures.get(1).or_else(|| captures.get(2)) {
+                links.push((number, anchor.as_str().to_string()));
+            }
+        }
+    }
+
+    // Anchors the way GitHub makes them: repeated headings get -1, -2...
+    let mut anchors: HashSet<String> = HashSet::new();
+    let mut repeats: HashMap<String, usize> = HashMap::new();
+    for heading in &headings {
+        let anchor = heading_anchor(heading.text);
+        let seen = repeats.entry(anchor.clone()).or_insert(0);
+        anchors.insert(if *seen == 0 { anchor } else { format!("{}-{}", anchor, seen) });
+        *seen += 1;
+    }
+    for (line, anchor) in links {
+        if !anchors.contains(&anchor) {
+            problems.push(LintProblem {
+                line,
+                kind: LintKind::BrokenAnchor,
+                message: format!("nothing is at #{}", anchor),
+            });
+        }
+    }
+
+    let session_prefix = format!("{} ", options.session_label);
+    for heading in &headings {
+        let is_session = heading.text.strip_prefix(&session_prefix).is_some_and(|rest| {
+            rest.split_once(':').is_some_and(|(number, _)| !number.is_empty() && number.chars().all(|c| c.is_ascii_digit()))
+        });
+        if is_session && !transcripts.contains(&heading.line) {
+            problems.push(LintProblem {
+                line: heading.line,
+                kind: LintKind::EmptySession,
+                message: format!("\"{}\" has no transcript", heading.text),
+            });
+        }
+    }
+
+    let sections: Vec<&Heading> = headings.iter().filter(|heading| heading.level <= 2).collect();
+    for (index, heading) in sections.iter().enumerate() {
+        let end = sections.get(index + 1).map_or(text.len(), |next| next.offset);
+        let size = end - heading.offset;
+        if heading.level == 2 && size > options.max_section_bytes {
+            problems.push(LintProblem {
+                line: heading.line,
+                kind: LintKind::OversizedSection,
+                message: format!(
+                    "\"{}\" is {} KB (the limit is {} KB)",
+                    heading.text,
+                    size.div_ceil(1024),
+                    options.max_section_bytes / 1024
+                ),
+            });
+        }
+    }
+
+    problems.sort_by_key(|problem| problem.line);
+    problems
+}
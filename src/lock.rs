@@ -0,0 +1,113 @@
+use anyhow::{anyhow, Context, Result};
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// A lock older than this is assumed to belong to a run that died
+/// without cleaning up, and is taken over - but only when there's no
+/// telling from its process id whether that run is still going.
+const STALE_AFTER: Duration = Duration::from_secs(6 * 60 * 60);
+
+/// A lock file that keeps two runs from working on the same output at
+/// once. It holds the owner's process id, and is removed when the lock is
+/// dropped - including when a run is cancelled mid-flight.
+#[derive(Debug)]
+pub struct RunLock {
+    path: PathBuf,
+}
+
+impl RunLock {
+    /// Take the lock, or return `None` if another run holds it. A lock
+    /// left behind by a run that's no longer alive is taken over.
+    pub fn acquire(path: &Path) -> Result<Option<Self>> {
+        for _ in 0..2 {
+            match OpenOptions::new().write(true).create_new(true).open(path) {
+                Ok(mut file) => {
+                    writeln!(file, "{}", std::process::id())
+                        .with_context(|| format!("Could not write lock file {}", path.display()))?;
+                    return Ok(Some(RunLock { path: path.to_path_buf() }));
+                }
+                Err(error) if error.kind() == ErrorKind::AlreadyExists => {
+                    if !is_stale(path) {
+                        return Ok(None);
+                    }
+                    // Clear the dead run's lock and try once more
+                    let _ = fs::remove_file(path);
+                }
+                Err(error) => {
+                    return Err(error).with_context(|| format!("Could not create lock file {}", path.display()));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Take the lock even if another run holds it. That run carries on,
+    /// so only do this when it's known to be stuck or gone.
+    pub fn force(path: &Path) -> Result<Self> {
+        if let Some(lock) = RunLock::acquire(path)? {
+            return Ok(lock);
+        }
+        fs::remove_file(path).with_context(|| format!("Could not remove lock file {}", path.display()))?;
+        RunLock::acquire(path)?.ok_or_else(|| anyhow!("Another run took {} first", path.display()))
+    }
+
+    /// The process id of whichever run holds the lock at `path`, if it's
+    /// held and says who by.
+    pub fn holder(path: &Path) -> Option<u32> {
+        fs::read_to_string(path).ok().and_then(|text| text.trim().parse().ok())
+    }
+
+    /// Where the lock file is.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for RunLock {
+    fn drop(&mut self) {
+        // Only our own lock: if it was forced away from us, the file is
+        // someone else's now
+        if RunLock::holder(&self.path) == Some(std::process::id()) {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// Who holds the lock at `path`, for messages.
+pub fn describe_holder(path: &Path) -> String {
+    match RunLock::holder(path) {
+        Some(pid) => format!("another run (process {})", pid),
+        None => "another run".to_string(),
+    }
+}
+
+/// Whether a lock's owner is gone: the process that wrote it isn't
+/// running any more or, where that can't be checked, the lock is very old.
+/// A long run keeps its lock however long it takes.
+fn is_stale(path: &Path) -> bool {
+    if let Some(running) = RunLock::holder(path).and_then(process_is_running) {
+        return !running;
+    }
+
+    // Half-written, written by something else entirely, or on a system
+    // whose processes we can't look up
+    let age = fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+        .unwrap_or_default();
+    age > STALE_AFTER
+}
+
+/// Whether process `pid` is running, if there's a way to tell.
+#[cfg(target_os = "linux")]
+fn process_is_running(pid: u32) -> Option<bool> {
+    Some(Path::new("/proc").join(pid.to_string()).exists())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn process_is_running(_pid: u32) -> Option<bool> {
+    None
+}
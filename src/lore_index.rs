@@ -0,0 +1,147 @@
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+
+use crate::document::heading_anchor;
+use crate::hotspots::{path_mentions, FileIndex};
+use crate::ChatSession;
+
+/// What the index is called, next to the lore.
+pub const LORE_INDEX_FILE: &str = "lore-index.json";
+
+/// The index's format version. It goes up when a change would break an
+/// extension reading an older one.
+pub const LORE_INDEX_VERSION: u32 = 1;
+
+/// A compact index of where the lore talks about each project file and
+/// code symbol, for an editor extension to show "past discussions about
+/// this file" next to the code without parsing the lore itself.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct LoreIndex {
+    /// `LORE_INDEX_VERSION` when it was written
+    pub version: u32,
+    /// The lore file the anchors are in, next to the index
+    pub lore: String,
+    /// The sessions, in the lore's order; hits refer to them by position
+    pub sessions: Vec<IndexedSession>,
+    /// Hits by project file, relative to the project with `/` between directories
+    pub files: BTreeMap<String, Vec<Hit>>,
+    /// Hits by symbol (`Config::load`, `parse_args`, `UserStore`), as
+    /// written in inline code
+    pub symbols: BTreeMap<String, Vec<Hit>>,
+}
+
+/// A session, as the index lists it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct IndexedSession {
+    /// The session's ID in the chat app
+    pub id: String,
+    /// Its heading in the lore
+    pub title: String,
+    /// The heading's anchor, for linking to `lore#anchor`
+    pub anchor: String,
+    /// When it started (Unix timestamp in milliseconds)
+    pub created_at: i64,
+}
+
+/// One place a file or symbol came up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct Hit {
+    /// The session's position in `sessions`
+    pub session: usize,
+    /// The message's position in the session
+    pub message: usize,
+    /// Where the mention starts in the message's text (a UTF-8 byte
+    /// offset); left out for a file attached to the message
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start: Option<usize>,
+    /// Where it ends
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end: Option<usize>,
+}
+
+impl LoreIndex {
+    /// Index the numbered sessions, titled as `title` gives their headings
+    /// in `lore`. Files are matched against the project's through `files`,
+    /// the way the Hotspots section matches them.
+    pub fn build(
+        sessions: &[(usize, &ChatSession)],
+        title: impl Fn(usize, &ChatSession) -> String,
+        files: &FileIndex<'_>,
+        lore: &str,
+    ) -> Self {
+        let mut index = LoreIndex { version: LORE_INDEX_VERSION, lore: lore.to_string(), ..LoreIndex::default() };
+
+        for (position, &(number, session)) in sessions.iter().enumerate() {
+            let title = title(number, session);
+            index.sessions.push(IndexedSession {
+                id: session.composer_id.clone(),
+                anchor: heading_anchor(&title),
+                title,
+                created_at: session.created_at,
+            });
+
+            for (message_position, message) in session.messages.iter().enumerate() {
+                let hit = |range: Option<(usize, usize)>| Hit {
+                    session: position,
+                    message: message_position,
+                    start: range.map(|(start, _)| start),
+                    end: range.map(|(_, end)| end),
+                };
+                for reference in &message.file_refs {
+                    if let Some(file) = files.resolve(&reference.path) {
+                        index.files.entry(file.to_string()).or_default().push(hit(None));
+                    }
+                }
+                for mention in path_mentions(&message.text) {
+                    if let Some(file) = files.resolve(mention) {
+                        let start = offset(&message.text, mention);
+                        index.files.entry(file.to_string()).or_default().push(hit(Some((start, start + mention.len()))));
+                    }
+                }
+                for (start, symbol) in symbol_mentions(&message.text) {
+                    if files.resolve(symbol).is_some() {
+                        continue;
+                    }
+                    let name = symbol.trim_end_matches("()");
+                    index.symbols.entry(name.to_string()).or_default().push(hit(Some((start, start + symbol.len()))));
+                }
+            }
+        }
+        index
+    }
+
+    /// The index as JSON, on one line.
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string(self)
+    }
+}
+
+/// Where `part`, a slice of `text`, starts in it.
+fn offset(text: &str, part: &str) -> usize {
+    part.as_ptr() as usize - text.as_ptr() as usize
+}
+
+/// The code symbols in `text`'s inline code, with where each starts: one
+/// identifier, or several joined by `::` or `.`, maybe with `()` after.
+/// Plain words don't count - a symbol has to look like code, with an
+/// underscore, `::`, `()`, or a capital letter after the first character.
+fn symbol_mentions(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    static INLINE_CODE: OnceLock<Regex> = OnceLock::new();
+    static SYMBOL: OnceLock<Regex> = OnceLock::new();
+    let inline_code = INLINE_CODE.get_or_init(|| Regex::new(r"`([^`\n]+)`").expect("valid regex"));
+    let symbol = SYMBOL.get_or_init(|| {
+        Regex::new(r"^[A-Za-z_][A-Za-z0-9_]*(?:(?:::|\.)[A-Za-z_][A-Za-z0-9_]*)*(?:\(\))?$").expect("valid regex")
+    });
+    inline_code
+        .captures_iter(text)
+        .filter_map(|captures| captures.get(1))
+        .filter(|code| symbol.is_match(code.as_str()))
+        .filter(|code| {
+            let code = code.as_str();
+            code.contains(['_', ':']) || code.ends_with("()") || code.chars().skip(1).any(|c| c.is_ascii_uppercase())
+        })
+        .map(|code| (code.start(), code.as_str()))
+}
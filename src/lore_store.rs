@@ -0,0 +1,229 @@
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use flate2::read::GzDecoder;
+use regex::RegexBuilder;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+
+use crate::snapshot::{Dataset, SnapshotStore, SNAPSHOT_DIR};
+use crate::versioning::check_readable;
+use crate::{ChatGeneration, ChatMessage, ChatPrompt, ChatSession};
+
+/// How much text a search hit shows on either side of the match.
+const EXCERPT_CONTEXT: usize = 60;
+
+/// Read-only queries over lore that's already been extracted, for Rust
+/// tools that want to build on it without running the CLI: which sessions
+/// happened when, where something was said, and what was said about a file.
+///
+/// It reads the dataset snapshots hold - from a state directory (the
+/// latest snapshot, with `SNAPSHOTS=true`) or from a file written with
+/// `snapshots show <ID> --json` (gzip-compressed or not).
+#[derive(Debug, Default)]
+pub struct LoreStore {
+    dataset: Dataset,
+}
+
+/// A message in a session.
+#[derive(Debug, Clone, Copy)]
+pub struct MessageRef<'a> {
+    /// The session it's in
+    pub session: &'a ChatSession,
+    /// Its position in the session's transcript
+    pub index: usize,
+    /// The message itself
+    pub message: &'a ChatMessage,
+}
+
+/// Somewhere a search matched: a session's name, or one of its messages.
+#[derive(Debug, Clone)]
+pub struct SearchHit<'a> {
+    /// The session it's in
+    pub session: &'a ChatSession,
+    /// The message that matched, by position, or `None` for the session's name
+    pub message: Option<usize>,
+    /// The match with some text around it, on one line
+    pub excerpt: String,
+}
+
+impl LoreStore {
+    /// Open lore at `path`: a state directory (or its `snapshots`
+    /// directory), whose latest snapshot is read, or a dataset file
+    /// (`.json`, or `.json.gz`).
+    pub fn open(path: &Path) -> Result<Self> {
+        if path.is_dir() {
+            let state_dir = match path.file_name() {
+                Some(name) if name == SNAPSHOT_DIR => path.parent().unwrap_or(path),
+                _ => path,
+            };
+            let store = SnapshotStore::open(state_dir);
+            let latest = store
+                .list()?
+                .pop()
+                .with_context(|| format!("{} has no snapshots (runs keep them with SNAPSHOTS=true)", state_dir.display()))?;
+            return Ok(LoreStore { dataset: store.load(&latest)? });
+        }
+
+        let bytes = fs::read(path).with_context(|| format!("Could not read {}", path.display()))?;
+        let json = if bytes.starts_with(&[0x1f, 0x8b]) {
+            let mut json = Vec::new();
+            GzDecoder::new(bytes.as_slice())
+                .read_to_end(&mut json)
+                .with_context(|| format!("{} isn't a valid gzip file", path.display()))?;
+            json
+        } else {
+            bytes
+        };
+        let dataset: Dataset = serde_json::from_slice(&json).with_context(|| format!("{} isn't an extracted dataset", path.display()))?;
+        check_readable(dataset.format_version, &path.display().to_string())?;
+        Ok(LoreStore { dataset })
+    }
+
+    /// Wrap a dataset that's already in memory.
+    pub fn from_dataset(dataset: Dataset) -> Self {
+        LoreStore { dataset }
+    }
+
+    /// Which source the lore was extracted from.
+    pub fn source(&self) -> &str {
+        &self.dataset.source
+    }
+
+    /// Every session, in the order they were extracted.
+    pub fn sessions(&self) -> impl Iterator<Item = &ChatSession> {
+        self.dataset.sessions.iter().flat_map(|data| &data.all_composers)
+    }
+
+    /// The session with this ID, or the only one whose ID starts with it.
+    pub fn session(&self, id: &str) -> Result<&ChatSession> {
+        if let Some(session) = self.sessions().find(|session| session.composer_id == id) {
+            return Ok(session);
+        }
+        let matches: Vec<&ChatSession> = self.sessions().filter(|session| session.composer_id.starts_with(id)).collect();
+        match matches.as_slice() {
+            [session] => Ok(session),
+            [] => bail!("No session matches '{}'", id),
+            _ => bail!("'{}' matches {} sessions; use more of the ID", id, matches.len()),
+        }
+    }
+
+    /// The generations.
+    pub fn generations(&self) -> &[ChatGeneration] {
+        &self.dataset.generations
+    }
+
+    /// The prompts.
+    pub fn prompts(&self) -> &[ChatPrompt] {
+        &self.dataset.prompts
+    }
+
+    /// The sessions started from `from` up to (not including) `to`,
+    /// oldest first.
+    pub fn sessions_between(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> Vec<&ChatSession> {
+        let (from, to) = (from.timestamp_millis(), to.timestamp_millis());
+        let mut sessions: Vec<&ChatSession> = self
+            .sessions()
+            .filter(|session| session.created_at >= from && session.created_at < to)
+            .collect();
+        sessions.sort_by_key(|session| session.created_at);
+        sessions
+    }
+
+    /// Everywhere `query` turns up, ignoring case: session names first,
+    /// then messages, each session's in order. An empty query finds nothing.
+    pub fn search(&self, query: &str) -> Vec<SearchHit<'_>> {
+        let query = query.trim();
+        if query.is_empty() {
+            return Vec::new();
+        }
+        let Ok(pattern) = RegexBuilder::new(&regex::escape(query)).case_insensitive(true).build() else {
+            return Vec::new();
+        };
+
+        let mut hits = Vec::new();
+        for session in self.sessions() {
+            if let Some(found) = pattern.find(&session.name) {
+                hits.push(SearchHit { session, message: None, excerpt: excerpt(&session.name, found.start(), found.end()) });
+            }
+        }
+        for session in self.sessions() {
+            for (index, message) in session.messages.iter().enumerate() {
+                if let Some(found) = pattern.find(&message.text) {
+                    hits.push(SearchHit {
+                        session,
+                        message: Some(index),
+                        excerpt: excerpt(&message.text, found.start(), found.end()),
+                    });
+                }
+            }
+        }
+        hits
+    }
+
+    /// The messages about a file: ones it was attached to or edited in,
+    /// and ones that mention it by path. `path` can be relative to the
+    /// project (`src/main.rs`), which matches the absolute paths chat
+    /// apps record too.
+    pub fn messages_for_file(&self, path: &str) -> Vec<MessageRef<'_>> {
+        let path = path.trim().trim_start_matches("./").replace('\\', "/");
+        if path.is_empty() {
+            return Vec::new();
+        }
+        let same_file = |recorded: &str| {
+            let recorded = recorded.replace('\\', "/");
+            recorded == path || recorded.ends_with(&format!("/{}", path))
+        };
+
+        let mut found = Vec::new();
+        for session in self.sessions() {
+            for (index, message) in session.messages.iter().enumerate() {
+                let about = message.file_refs.iter().any(|file| same_file(&file.path))
+                    || message.edits.iter().any(|edit| same_file(&edit.path))
+                    || mentions(&message.text, &path);
+                if about {
+                    found.push(MessageRef { session, index, message });
+                }
+            }
+        }
+        found
+    }
+}
+
+/// Whether `text` mentions `path` on its own, not as part of a longer
+/// path or name (`main.rs` isn't mentioned by `domain.rs`).
+fn mentions(text: &str, path: &str) -> bool {
+    let part_of_path = |c: char| c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | '/');
+    text.match_indices(path).any(|(start, _)| {
+        let before = text[..start].chars().next_back();
+        let after = text[start + path.len()..].chars().next();
+        // A trailing full stop ends the sentence, not the path
+        let after_ok = match after {
+            Some('.') => text[start + path.len() + 1..].chars().next().is_none_or(|c| !part_of_path(c)),
+            other => other.is_none_or(|c| !part_of_path(c)),
+        };
+        before.is_none_or(|c| !part_of_path(c) || c == '/') && after_ok
+    })
+}
+
+/// The text from `start` to `end` with some context either side, on one
+/// line, with an ellipsis where it was cut.
+fn excerpt(text: &str, start: usize, end: usize) -> String {
+    let from = text[..start]
+        .char_indices()
+        .rev()
+        .nth(EXCERPT_CONTEXT - 1)
+        .map_or(0, |(at, _)| at);
+    let to = text[end..]
+        .char_indices()
+        .nth(EXCERPT_CONTEXT)
+        .map_or(text.len(), |(at, _)| end + at);
+    let mut excerpt = text[from..to].split_whitespace().collect::<Vec<_>>().join(" ");
+    if from > 0 {
+        excerpt.insert(0, '…');
+    }
+    if to < text.len() {
+        excerpt.push('…');
+    }
+    excerpt
+}
@@ -1,9 +1,69 @@
-use anyhow::Result;
-use clap::Parser;
-use std::fs;
-use std::path::Path;
+use anyhow::{anyhow, bail, Context, Result};
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand};
+use clap_complete::Shell;
+use dialoguer::console::Term;
+use dialoguer::{Confirm, MultiSelect, Select};
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use chat_history_consolidator::{Config, ChatExtractor, MarkdownGenerator};
+use chrono::{DateTime, Utc};
+
+use chat_history_consolidator::adr::{plan_adrs, write_adrs, ADR_DIR};
+use chat_history_consolidator::atomic;
+use chat_history_consolidator::backup::{self, BACKUP_DIR};
+use chat_history_consolidator::batch::{
+    index_document, BatchManifest, BatchProgress, BatchReport, IndexedSession, ProjectActivity,
+};
+use chat_history_consolidator::checkpoint::{run_key, Checkpoint, ResumeMode, CHECKPOINT_DIR};
+use chat_history_consolidator::classify::classify_generations;
+use chat_history_consolidator::content_hash::{can_skip, content_hash, ContentState, CONTENT_HASH_FILE};
+use chat_history_consolidator::curation::Curation;
+use chat_history_consolidator::daemon::{append_history, RunRecord, LOCK_FILE, RUN_HISTORY_FILE};
+use chat_history_consolidator::dirs::{self as state_dirs, Dirs};
+use chat_history_consolidator::email::{self, EmailDigest, EmailTemplates, DEFAULT_DAYS};
+use chat_history_consolidator::encrypt::{decrypt, decrypted_path, encrypt, encrypted_name, EncryptionKey};
+use chat_history_consolidator::file_lore::FILE_LORE_DIR;
+use chat_history_consolidator::flags::SettingFlags;
+use chat_history_consolidator::followups::find_follow_ups;
+use chat_history_consolidator::graph::{GraphFormat, KnowledgeGraph};
+use chat_history_consolidator::hook::{self, HookKind, HookState, HOOK_STATE_FILE};
+use chat_history_consolidator::i18n::Strings;
+use chat_history_consolidator::init;
+use chat_history_consolidator::json_schema;
+use chat_history_consolidator::importers::ImportedHistory;
+use chat_history_consolidator::issues::{describe_issues, plan_issues, IssueAction, IssueKind, IssueTarget};
+use chat_history_consolidator::lint::{lint_markdown, LintOptions, DEFAULT_MAX_SECTION_KB};
+use chat_history_consolidator::lock::{describe_holder, RunLock};
+use chat_history_consolidator::lore_index::LORE_INDEX_FILE;
+use chat_history_consolidator::namespace::{self, ENV_PREFIX};
+use chat_history_consolidator::notify::{self, Digest, NotifyState, NOTIFY_STATE_FILE};
+use chat_history_consolidator::parsing::summarize_skipped;
+use chat_history_consolidator::plugins::{self, Plugin};
+use chat_history_consolidator::project::CargoProject;
+use chat_history_consolidator::publish::{PublishAction, PublishTarget};
+use chat_history_consolidator::render::OutputFormat;
+use chat_history_consolidator::report::{exit_code, exit_code_for, Failure, RunCounts, RunReport};
+use chat_history_consolidator::restore::{plan_restore, select_sessions};
+use chat_history_consolidator::schedule::Schedule;
+use chat_history_consolidator::selection::{PickerState, SessionFilter, PICKER_STATE_FILE};
+use chat_history_consolidator::site::SESSIONS_SECTION;
+use chat_history_consolidator::snapshot::{SnapshotDiff, SnapshotStore};
+use chat_history_consolidator::store::{ConnectionOptions, RecordStore, SqliteStore};
+use chat_history_consolidator::synthetic::SyntheticHistory;
+use chat_history_consolidator::tags::{parse_tags, suggest_tags, SessionTags, TAGS_FILE};
+use chat_history_consolidator::terminal::{Align, Table, Terminal, Tone};
+use chat_history_consolidator::time::DisplayZone;
+use chat_history_consolidator::timestamps;
+use chat_history_consolidator::trivial::TrivialSessions;
+use chat_history_consolidator::verify::verify_lore;
+use chat_history_consolidator::versioning::{self, FORMAT_VERSION};
+use chat_history_consolidator::{
+    ChatExtractor, ChatSession, CodyImporter, ComposerData, Config, ContinueImporter, MarkdownGenerator, TempWorkspace,
+};
 
 /// Command-line interface for the persistent code lore tool.
 /// This struct defines all the command-line arguments that users can pass
@@ -17,6 +77,11 @@ struct Cli {
     #[arg(short, long, default_value = "config.env")]
     config: String,
     
+    /// Use this profile's settings (a [profile.<name>] section of the
+    /// config file) over the ones at the top of the file.
+    #[arg(long)]
+    profile: Option<String>,
+    
     /// Where to put the generated markdown files.
     /// If not specified, we'll use the default from the config file.
     #[arg(long)]
@@ -27,40 +92,966 @@ struct Cli {
     #[arg(long)]
     output_file: Option<String>,
     
+    /// What to write the lore as: markdown, org, asciidoc, typst, pdf
+    /// (needs the `pdf` feature) or epub; csv or parquet for a table of
+    /// messages to analyse (parquet needs the `parquet` feature); site
+    /// for the source of a Zola site; or json for the document tree.
+    /// If not specified, we'll use the OUTPUT_FORMAT setting (markdown by default).
+    #[arg(long)]
+    format: Option<String>,
+    
+    /// Which chat application to pull history from: cursor, continue, cody,
+    /// or plugin:<name> for a `lore-plugin-<name>` source plugin on PATH.
+    /// If not specified, we'll use the SOURCE setting from the config file.
+    #[arg(long)]
+    source: Option<String>,
+    
+    /// Which time zone to show timestamps in: an IANA name like Europe/Rome,
+    /// UTC, or local. If not specified, we'll use the TIMEZONE setting.
+    #[arg(long)]
+    timezone: Option<String>,
+    
+    /// Group the historical sessions by day, week, month, topic, or tag.
+    /// If not specified, we'll use the GROUP_BY setting (flat by default).
+    #[arg(long)]
+    group_by: Option<String>,
+    
+    /// Sort sessions by created, updated, name, or message-count before
+    /// numbering them. If not specified, we'll use the SORT_BY setting.
+    #[arg(long)]
+    sort: Option<String>,
+    
+    /// Sort order for --sort: asc or desc.
+    /// If not specified, we'll use the SORT_ORDER setting (asc by default).
+    #[arg(long)]
+    order: Option<String>,
+    
+    /// Add a Mermaid diagram of sessions and commits per week: timeline or gantt.
+    /// If not specified, we'll use the TIMELINE setting (no diagram by default).
+    #[arg(long)]
+    timeline: Option<String>,
+    
+    /// List the sessions found and tick the ones to export. The picks are
+    /// remembered in the state directory, and later runs leave out the
+    /// same sessions until they're picked again.
+    #[arg(long)]
+    interactive: bool,
+    
+    /// Leave sessions out of the lore: a session ID, or a name pattern
+    /// with `*` and `?` wildcards like "scratch*". Can be given more than
+    /// once, and adds to the EXCLUDE_SESSIONS setting.
+    #[arg(long, value_name = "PATTERN")]
+    exclude: Vec<String>,
+    
+    /// Only put sessions with this tag in the lore (can be repeated; adds
+    /// to the INCLUDE_TAGS setting). Tag sessions with the `tag` subcommand.
+    #[arg(long = "tag", value_name = "TAG")]
+    tags: Vec<String>,
+    
+    /// Leave out sessions with this tag (can be repeated; adds to the
+    /// EXCLUDE_TAGS setting).
+    #[arg(long, value_name = "TAG")]
+    exclude_tag: Vec<String>,
+    
+    /// Export a knowledge graph next to the markdown: json, dot, or graphml.
+    /// If not specified, we'll use the GRAPH_FORMAT setting (no export by default).
+    #[arg(long)]
+    graph: Option<String>,
+    
+    /// Write a small markdown file for each source file that keeps coming
+    /// up in the chats into a files/ directory next to the lore (same as
+    /// FILE_LORE=true).
+    #[arg(long)]
+    file_lore: bool,
+    
+    /// Fold each session's transcript into a collapsed <details> block
+    /// (same as COLLAPSE_TRANSCRIPTS=true).
+    #[arg(long)]
+    collapse_transcripts: bool,
+    
+    /// The heading level of the lore's title, for embedding it in a bigger
+    /// document: with 3, the title is ### and the sessions go under it at
+    /// ##### (same as HEADING_LEVEL).
+    #[arg(long)]
+    heading_level: Option<usize>,
+    
+    /// Write lore-index.json next to the lore, mapping project files and
+    /// code symbols to the sessions that discuss them, for editor
+    /// extensions (same as LORE_INDEX=true).
+    #[arg(long)]
+    lore_index: bool,
+    
+    /// Fail on records that don't match what we expect - unknown fields,
+    /// missing keys, values of the wrong type - instead of skipping them or
+    /// keeping them quietly, to catch schema drift in CI (same as STRICT=true).
+    #[arg(long)]
+    strict: bool,
+    
     /// Print extra information about what we're doing.
     /// Useful for debugging or just seeing what's happening under the hood.
     #[arg(short, long)]
     verbose: bool,
+    
+    /// Leave the run's temporary workspace on disk instead of cleaning it up.
+    /// Useful when you need to poke at snapshots or partial output.
+    #[arg(long)]
+    keep_temp: bool,
+    
+    /// Run the quick way a git hook needs: do nothing if the chat history
+    /// hasn't changed since the last hook run, skip per-edit commit lookups
+    /// and notifications, stay quiet, and stage the refreshed files.
+    #[arg(long)]
+    hook_mode: bool,
+    
+    /// Copy the source databases (with their WAL files) into a dated backup
+    /// directory with a checksum manifest before reading them.
+    /// If not specified, we'll use the BACKUP setting (off by default).
+    #[arg(long)]
+    backup: bool,
+    
+    /// Where to keep run history, the lock, snapshots and backups.
+    /// If not specified, we'll use STATE_DIR, or a directory for this
+    /// project under $XDG_STATE_HOME (~/.local/state).
+    #[arg(long)]
+    state_dir: Option<String>,
+    
+    /// Where to keep things that can be rebuilt, like the hook's fingerprint.
+    /// If not specified, we'll use CACHE_DIR, or a directory for this
+    /// project under $XDG_CACHE_HOME (~/.cache).
+    #[arg(long)]
+    cache_dir: Option<String>,
+    
+    /// Write a JSON report of the run to this file (`-` for stdout): what
+    /// was found, how long each stage took, warnings, and the files written
+    /// with their checksums. It's written even when the run fails.
+    #[arg(long, value_name = "PATH")]
+    report_json: Option<String>,
+    
+    /// Print how long each stage of the run took and the most memory it
+    /// used, to find out where the time goes on a large history.
+    #[arg(long)]
+    profile_run: bool,
+    
+    /// Pick up a run that was interrupted (by Ctrl-C or a crash) where it
+    /// stopped, instead of reading everything again. With `batch`, the
+    /// projects that already finished are skipped too.
+    #[arg(long, conflicts_with = "restart")]
+    resume: bool,
+    
+    /// Throw away what an interrupted run left and start over.
+    #[arg(long)]
+    restart: bool,
+    
+    /// If another run is working on this project (a cron job, say), wait
+    /// for it to finish instead of giving up straight away.
+    #[arg(long, conflicts_with = "force_lock")]
+    wait: bool,
+    
+    /// Take the run lock even if another run holds it. Only for a lock
+    /// that's stuck - the other run carries on regardless.
+    #[arg(long = "force")]
+    force_lock: bool,
+    
+    // Every other setting, as --workspace-id, --include-secrets and so on
+    #[command(flatten)]
+    settings: SettingFlags,
+    
+    /// Something other than a normal extraction run
+    #[command(subcommand)]
+    command: Option<Command>,
+    
+    /// The crate we were run in, when we're `cargo lore`
+    #[arg(skip)]
+    cargo: Option<CargoProject>,
+}
+
+/// Extra things the tool can do besides consolidating history.
+#[derive(Subcommand)]
+enum Command {
+    /// Describe the layout of the Cursor database (tables, key formats,
+    /// record shapes and the detected version) - handy for bug reports
+    SchemaReport,
+    
+    /// Print a shell completion script (bash, zsh, fish, elvish or powershell)
+    Completions {
+        /// Which shell to generate completions for
+        shell: Shell,
+    },
+    
+    /// Print the man page (roff format)
+    Manpage,
+    
+    /// The JSON Schemas of the JSON formats the lore is written in, for
+    /// validating them or generating code from them
+    Schema {
+        #[command(subcommand)]
+        action: SchemaAction,
+    },
+    
+    /// Set up a config file: find Cursor's workspaces, pick the one for
+    /// this project, and write --config (config.env) for it, adding the
+    /// output directory to .gitignore
+    Init {
+        /// Cursor's User directory, if it isn't where Cursor usually puts it
+        #[arg(long)]
+        storage: Option<PathBuf>,
+        
+        /// The workspace to use, by ID or the folder it has open, instead
+        /// of picking one from the list
+        #[arg(long)]
+        workspace: Option<String>,
+        
+        /// Overwrite the config file if there is one
+        #[arg(long)]
+        force: bool,
+        
+        /// Leave .gitignore alone
+        #[arg(long)]
+        no_gitignore: bool,
+    },
+    
+    /// Move settings to LORE_-prefixed names, so they can't collide with
+    /// other tools' variables: rename them in --config (keeping a .bak
+    /// copy), and list the ones set in the environment under plain names
+    MigrateEnv {
+        /// Show what would be renamed without touching the config file
+        #[arg(long)]
+        dry_run: bool,
+    },
+    
+    /// Keep running in the background, extracting on a cron schedule
+    /// (SCHEDULE, like "0 18 * * *"). Runs are recorded in .lore-runs.jsonl
+    /// in the state directory.
+    Daemon {
+        /// The schedule to use instead of SCHEDULE
+        #[arg(long)]
+        schedule: Option<String>,
+    },
+    
+    /// Publish the lore to a wiki: confluence, notion, or git (a repository
+    /// or GitHub wiki). Each session gets
+    /// its own page under an overview page, and running it again updates
+    /// the same pages instead of adding new ones.
+    Publish {
+        /// Where to publish: confluence, notion, or git
+        target: String,
+        
+        /// Show which pages would be published without sending anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+    
+    /// Install a git hook in PROJECT_PATH that refreshes the lore and stages
+    /// it on every commit (runs in --hook-mode)
+    InstallHook {
+        /// Which hook: pre-commit (the lore goes into the commit) or
+        /// post-commit (it's staged for the next one)
+        #[arg(long, default_value = "pre-commit")]
+        kind: String,
+        
+        /// Replace an existing hook even if we didn't install it
+        #[arg(long)]
+        force: bool,
+    },
+    
+    /// Remove the git hooks install-hook added (hooks written by anyone
+    /// else are left alone)
+    UninstallHook {
+        /// Only remove this hook: pre-commit or post-commit
+        #[arg(long)]
+        kind: Option<String>,
+    },
+    
+    /// Decrypt lore written with ENCRYPT on, using an age identity file or
+    /// ENCRYPT_PASSPHRASE
+    Decrypt {
+        /// The encrypted file (name.md.age)
+        file: PathBuf,
+        
+        /// An age identity file holding the private key (can be repeated)
+        #[arg(short, long)]
+        identity: Vec<PathBuf>,
+        
+        /// Where to write the decrypted file, or - for standard output
+        /// (default: next to it, without the .age)
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+    
+    /// Experimental: write sessions back into a Cursor workspace database,
+    /// e.g. after moving to a new machine. Close Cursor first.
+    Restore(RestoreArgs),
+    
+    /// Write a made-up Cursor history of any size, to profile runs (with
+    /// --profile-run) on more history than you have
+    Synthetic {
+        /// The directory to create the workspace and global databases in
+        dir: PathBuf,
+        
+        /// How many sessions
+        #[arg(long, default_value_t = 100)]
+        sessions: usize,
+        
+        /// How many messages in each session
+        #[arg(long, default_value_t = 50)]
+        messages: usize,
+        
+        /// Varies the text without changing the sizes
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+    },
+    
+    /// Tag a session, like `tag 3f2a architecture,auth`. Tags are kept in
+    /// the state directory; --tag and --exclude-tag pick sessions by them,
+    /// and GROUP_BY=tag groups the lore by them. With only a session, shows
+    /// its tags and some suggestions; with nothing, lists every session.
+    Tag {
+        /// The session (an ID or the start of one)
+        session: Option<String>,
+        
+        /// Tags to add, comma-separated
+        tags: Option<String>,
+        
+        /// Take the tags off instead
+        #[arg(long)]
+        remove: bool,
+        
+        /// Add the suggested tags
+        #[arg(long)]
+        suggested: bool,
+    },
+    
+    /// Write an Architecture Decision Record (MADR format) for each decision
+    /// found in the chats, skipping decisions that already have one
+    GenerateAdrs {
+        /// Where the ADRs live (default: docs/adr under PROJECT_PATH)
+        #[arg(long)]
+        dir: Option<PathBuf>,
+        
+        /// List the ADRs that would be written without writing them
+        #[arg(long)]
+        dry_run: bool,
+    },
+    
+    /// List the open follow-ups in the chats - TODOs and work left for
+    /// later - as text, or as JSON to feed an issue tracker
+    FollowUps {
+        /// text or json
+        #[arg(long, default_value = "text")]
+        format: String,
+    },
+    
+    /// File an issue for each open follow-up (or decision) in GitHub or
+    /// Linear. Issues from earlier runs are found by the lore ID in their
+    /// body and updated instead of filed again.
+    Issues {
+        /// Where to file them: github or linear
+        target: String,
+        
+        /// What to file issues for: follow-ups or decisions (can be repeated)
+        #[arg(long, value_name = "KIND", default_value = "follow-ups")]
+        from: Vec<String>,
+        
+        /// Show which issues would be filed without sending anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+    
+    /// Email a digest of the sessions active in the last week (or
+    /// --days) to EMAIL_TO through SMTP_HOST, as HTML with a plain-text
+    /// version. Needs the `email` feature.
+    EmailDigest {
+        /// How many days back the digest goes
+        #[arg(long, default_value_t = DEFAULT_DAYS)]
+        days: i64,
+        
+        /// Write the email to an .eml file in the output directory instead
+        /// of sending it
+        #[arg(long)]
+        dry_run: bool,
+    },
+    
+    /// List the plugins on PATH (`lore-plugin-<name>` executables) with
+    /// their versions and what they can do
+    Plugins,
+    
+    /// Check the lore for problems before it's published: absolute paths,
+    /// secrets that got past redaction, broken links between headings,
+    /// sessions with no transcript, and oversized sections. Exits with 7
+    /// if it finds any.
+    Lint {
+        /// The markdown lore to check (default: the output file)
+        file: Option<PathBuf>,
+        
+        /// The most a section can be, in KB
+        #[arg(long, default_value_t = DEFAULT_MAX_SECTION_KB)]
+        max_section_kb: usize,
+    },
+    
+    /// Check that the lore (the output file) still represents the chat
+    /// history: extract again, render each session and compare it with
+    /// the file, reporting sessions the lore is missing and ones that
+    /// have changed since it was written. Exits with 9 if there are any.
+    Verify {
+        /// The lore to check (default: the output file)
+        file: Option<PathBuf>,
+    },
+    
+    /// Bring lore written by older versions of this tool up to the
+    /// current format: the markdown lore, JSON documents and extracted
+    /// datasets in the output directory, keeping each old file as .bak
+    Migrate {
+        /// A file or directory to migrate instead of the output directory
+        path: Option<PathBuf>,
+        
+        /// List what would change without writing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+    
+    /// See what the sanitizer does: the rules everything written goes
+    /// through (the built-in ones the INCLUDE_* settings leave on, then
+    /// SANITIZE_RULES), and which of them fire on some text
+    Sanitize {
+        #[command(subcommand)]
+        action: SanitizeAction,
+    },
+    
+    /// Consolidate several projects in one go, from a JSON manifest
+    /// listing each project's path, workspace ID and output directory.
+    /// Prints how each went; exits with 8 if any failed.
+    Batch {
+        /// The manifest, like {"projects": [{"name": "api", "path": "~/code/api"}]}
+        manifest: PathBuf,
+        
+        /// Stop at the first project that fails instead of going on to the rest
+        #[arg(long)]
+        fail_fast: bool,
+    },
+    
+    /// Look through the snapshots runs keep with SNAPSHOTS on
+    Snapshots {
+        #[command(subcommand)]
+        action: SnapshotAction,
+    },
+}
+
+/// Where `restore` gets its sessions and where it puts them.
+#[derive(clap::Args)]
+struct RestoreArgs {
+    /// The target workspace's state.vscdb, for the session list, prompts
+    /// and generations
+    #[arg(long)]
+    workspace_db: Option<PathBuf>,
+    
+    /// Cursor's global state.vscdb on the target machine, for the transcripts
+    #[arg(long)]
+    global_db: Option<PathBuf>,
+    
+    /// Only restore this session (an ID or the start of one; can be repeated)
+    #[arg(long = "session")]
+    sessions: Vec<String>,
+    
+    /// Restore from a snapshot instead of the configured source
+    #[arg(long)]
+    snapshot: Option<String>,
+    
+    /// Write the records to a JSON file instead of a database
+    #[arg(long)]
+    export: Option<PathBuf>,
+    
+    /// Show what would be restored without writing anything
+    #[arg(long)]
+    dry_run: bool,
+}
+
+/// What to do with the snapshots.
+#[derive(Subcommand)]
+enum SnapshotAction {
+    /// List every run's snapshot, oldest first
+    List,
+    
+    /// Show what a snapshot holds
+    Show {
+        /// The snapshot's ID (a prefix will do), or latest
+        id: String,
+        
+        /// Print the whole dataset as JSON instead of a summary
+        #[arg(long)]
+        json: bool,
+    },
+    
+    /// Show which sessions were added, removed or changed between two snapshots
+    Diff {
+        /// The older snapshot
+        from: String,
+        
+        /// The newer snapshot
+        #[arg(default_value = "latest")]
+        to: String,
+    },
+    
+    /// Delete all but the newest snapshots
+    Prune {
+        /// How many runs' snapshots to keep
+        #[arg(long)]
+        keep: usize,
+    },
+}
+
+/// What to do with the JSON Schemas.
+#[derive(Subcommand)]
+enum SchemaAction {
+    /// Print the schemas as one JSON object by format name, or write each
+    /// to `<dir>/<name>.schema.json` with --dir
+    Export {
+        /// Only this format's schema (document, run-report, batch-report,
+        /// lore-index, knowledge-graph, follow-ups or run-history)
+        name: Option<String>,
+        
+        /// Write the schemas into this directory instead of printing them
+        #[arg(long)]
+        dir: Option<PathBuf>,
+    },
+}
+
+/// What to do with the sanitizer.
+#[derive(Subcommand)]
+enum SanitizeAction {
+    /// List the rules, in the order they're applied
+    Rules,
+    
+    /// Run some text through the rules, showing each one that fired and
+    /// what the text was after it
+    Test {
+        /// The text to sanitize
+        text: String,
+    },
 }
 
 /// Main entry point for our persistent code lore application.
 /// This is where everything starts - we parse command line arguments,
 /// load configuration, extract chat data, and generate the final markdown.
+/// It's also the entry point of the `cargo-lore` binary (src/bin/cargo-lore.rs),
+/// which is what cargo runs for `cargo lore`.
 #[tokio::main]
-async fn main() -> Result<()> {
+pub async fn main() {
     // First things first - let's see what the user wants us to do
-    let cli = Cli::parse();
+    let cli = if env!("CARGO_BIN_NAME") == "cargo-lore" { cargo_cli() } else { Cli::parse() };
+    
+    // Run until we're done or the user hits Ctrl-C. Each kind of failure
+    // has its own exit code, so scripts can tell them apart
+    tokio::select! {
+        result = run(cli) => match result {
+            Ok(code) => std::process::exit(code),
+            Err(error) => {
+                eprintln!("Error: {:?}", error);
+                std::process::exit(exit_code_for(&Err(error)));
+            }
+        },
+        _ = tokio::signal::ctrl_c() => {}
+    }
+    
+    // Getting here means we were interrupted. The run future has been dropped
+    // by now, which cleaned up its temp workspace on the way out
+    eprintln!("Interrupted - temporary files cleaned up; run again with --resume to pick up where it stopped");
+    std::process::exit(exit_code::INTERRUPTED);
+}
+
+/// Parse the command line of `cargo lore`, and find the crate it was run
+/// in so PROJECT_PATH, PROJECT_NAME and PROJECT_BRANCH don't need setting.
+fn cargo_cli() -> Cli {
+    // cargo runs us as `cargo-lore lore <args>`
+    let mut args: Vec<std::ffi::OsString> = std::env::args_os().collect();
+    if args.get(1).is_some_and(|arg| arg == "lore") {
+        args.remove(1);
+    }
+    let matches = Cli::command().name("cargo-lore").bin_name("cargo lore").get_matches_from(args);
+    let mut cli = Cli::from_arg_matches(&matches).unwrap_or_else(|error| error.exit());
+    
+    let here = std::env::current_dir().unwrap_or_default();
+    cli.cargo = CargoProject::find(&here);
+    match &cli.cargo {
+        // A config file at the workspace root is found from anywhere in it
+        Some(project) if !Path::new(&cli.config).exists() => {
+            let config = project.root.join(&cli.config);
+            if config.is_file() {
+                cli.config = config.to_string_lossy().into_owned();
+            }
+        }
+        Some(_) => {}
+        None => eprintln!("Warning: no Cargo.toml in {} or above; using PROJECT_PATH as configured", here.display()),
+    }
+    cli
+}
+
+/// Do one full extraction run: load config, pull the chat data, render it,
+/// and write it out.
+async fn run(cli: Cli) -> Result<i32> {
+    // Completions and the man page only describe the CLI itself, so they
+    // don't need a config file (or a database) to exist
+    match cli.command {
+        Some(Command::Completions { shell }) => {
+            let mut command = Cli::command();
+            let name = command.get_name().to_string();
+            clap_complete::generate(shell, &mut command, name, &mut io::stdout());
+            return Ok(exit_code::SUCCESS);
+        }
+        Some(Command::Manpage) => {
+            clap_mangen::Man::new(Cli::command()).render(&mut io::stdout())?;
+            return Ok(exit_code::SUCCESS);
+        }
+        Some(Command::Schema { action: SchemaAction::Export { ref name, ref dir } }) => {
+            export_schemas(name.as_deref(), dir.as_deref())?;
+            return Ok(exit_code::SUCCESS);
+        }
+        _ => {}
+    }
+    
+    // init is what writes the configuration in the first place
+    if let Some(Command::Init { storage, workspace, force, no_gitignore }) = &cli.command {
+        init(&cli, storage.as_deref(), workspace.as_deref(), *force, *no_gitignore).context(Failure::Config)?;
+        return Ok(exit_code::SUCCESS);
+    }
+    
+    if let Some(Command::MigrateEnv { dry_run }) = &cli.command {
+        migrate_env(&cli, *dry_run).context(Failure::Config)?;
+        return Ok(exit_code::SUCCESS);
+    }
+    
+    // A setting under both names is most likely a leftover from before the prefix
+    let from_env = |name: &str| std::env::var(name).ok();
+    for name in namespace::conflicts(&from_env, &Config::setting_names()) {
+        eprintln!(
+            "Warning: {}{} and {} are both set, to different values; using {}{}",
+            ENV_PREFIX, name, name, ENV_PREFIX, name
+        );
+    }
+    
+    // A batch loads a configuration per project instead
+    if let Some(Command::Batch { manifest, fail_fast }) = &cli.command {
+        return batch(&cli, manifest, *fail_fast).await;
+    }
     
     // Load up our configuration from the file the user specified
     // (or the default one if they didn't specify anything)
-    let config = Config::load(&cli.config)?;
+    let config = match load_config(&cli).context(Failure::Config) {
+        Ok(config) => config,
+        Err(error) => {
+            // A run that CI is watching should leave a report even now
+            let mut report = RunReport::start(cli.source.as_deref().unwrap_or_default());
+            return finish_report(&cli, &mut report, Err(error));
+        }
+    };
     
     // If the user wants to see what's going on, let's tell them
     if cli.verbose {
         println!("Configuration loaded from: {}", cli.config);
+        println!("Source: {}", config.source);
         println!("Database path: {}", config.database_path());
         println!("Output directory: {}", config.output_dir);
     }
     
-    // Now we need to connect to the database and set up our data extractor
-    // This is where we'll pull all the chat history from the SQLite database
-    let extractor = ChatExtractor::new(&config).await?;
+    if let Some(Command::SchemaReport) = cli.command {
+        if config.source != "cursor" {
+            bail!("schema-report only applies to the cursor source");
+        }
+        let report = ChatExtractor::new(&config).await?.schema_report().await?;
+        print!("{}", report);
+        return Ok(exit_code::SUCCESS);
+    }
+    
+    if let Some(Command::Daemon { schedule }) = &cli.command {
+        daemon(&cli, schedule.clone()).await?;
+        return Ok(exit_code::SUCCESS);
+    }
+    
+    match &cli.command {
+        Some(Command::InstallHook { kind, force }) => {
+            install_hook(&cli, &config, HookKind::parse(kind)?, *force)?;
+            return Ok(exit_code::SUCCESS);
+        }
+        Some(Command::UninstallHook { kind }) => {
+            let kinds = match kind {
+                Some(kind) => vec![HookKind::parse(kind)?],
+                None => HookKind::ALL.to_vec(),
+            };
+            let removed = hook::uninstall(Path::new(&config.project_path), &kinds)?;
+            if removed.is_empty() {
+                println!("No lore hooks to remove");
+            }
+            for path in removed {
+                println!("Removed {}", path.display());
+            }
+            return Ok(exit_code::SUCCESS);
+        }
+        _ => {}
+    }
+    
+    if let Some(Command::Restore(args)) = &cli.command {
+        restore(&config, &dirs(&cli, &config)?, args).await?;
+        return Ok(exit_code::SUCCESS);
+    }
+    
+    if let Some(Command::Tag { session, tags, remove, suggested }) = &cli.command {
+        tag(&config, &dirs(&cli, &config)?, session.as_deref(), tags.as_deref(), *remove, *suggested).await?;
+        return Ok(exit_code::SUCCESS);
+    }
+    
+    if let Some(Command::GenerateAdrs { dir, dry_run }) = &cli.command {
+        generate_adrs(&config, &dirs(&cli, &config)?, dir.clone(), *dry_run).await?;
+        return Ok(exit_code::SUCCESS);
+    }
+    
+    if let Some(Command::FollowUps { format }) = &cli.command {
+        follow_ups(&config, &dirs(&cli, &config)?, format).await?;
+        return Ok(exit_code::SUCCESS);
+    }
+    
+    if let Some(Command::Issues { target, from, dry_run }) = &cli.command {
+        issues(&config, &dirs(&cli, &config)?, target, from, *dry_run).await?;
+        return Ok(exit_code::SUCCESS);
+    }
+    
+    if let Some(Command::EmailDigest { days, dry_run }) = &cli.command {
+        email_digest(&cli, &config, &dirs(&cli, &config)?, *days, *dry_run).await?;
+        return Ok(exit_code::SUCCESS);
+    }
+    
+    if let Some(Command::Plugins) = &cli.command {
+        list_plugins(&config.terminal());
+        return Ok(exit_code::SUCCESS);
+    }
+    
+    if let Some(Command::Lint { file, max_section_kb }) = &cli.command {
+        return lint(&cli, &config, file.clone(), *max_section_kb);
+    }
+    
+    if let Some(Command::Verify { file }) = &cli.command {
+        return verify(&cli, &config, file.clone()).await;
+    }
+    
+    if let Some(Command::Migrate { path, dry_run }) = &cli.command {
+        migrate(&cli, &config, path.clone(), *dry_run)?;
+        return Ok(exit_code::SUCCESS);
+    }
+    
+    if let Some(Command::Snapshots { action }) = &cli.command {
+        snapshots(&SnapshotStore::open(&dirs(&cli, &config)?.state), action, &config.display_zone(), &config.terminal())?;
+        return Ok(exit_code::SUCCESS);
+    }
+    
+    if let Some(Command::Sanitize { action }) = &cli.command {
+        sanitize(&config, action);
+        return Ok(exit_code::SUCCESS);
+    }
+    
+    if let Some(Command::Synthetic { dir, sessions, messages, seed }) = &cli.command {
+        let history = SyntheticHistory { sessions: *sessions, messages: *messages, seed: *seed };
+        synthetic(&config, dir, &history).await?;
+        return Ok(exit_code::SUCCESS);
+    }
+    
+    if let Some(Command::Decrypt { file, identity, output }) = &cli.command {
+        let passphrase = Some(config.encrypt_passphrase.as_str()).filter(|passphrase| !passphrase.is_empty());
+        let decrypted = decrypt(&fs::read(file)?, identity, passphrase)?;
+        match output.as_deref() {
+            Some("-") => io::stdout().write_all(&decrypted)?,
+            output => {
+                let path = match output {
+                    Some(path) => PathBuf::from(path),
+                    None => decrypted_path(file)
+                        .ok_or_else(|| anyhow!("{} doesn't end in .age - pass --output", file.display()))?,
+                };
+                atomic::write_atomic(&path, &decrypted)?;
+                println!("Decrypted to {}", path.display());
+            }
+        }
+        return Ok(exit_code::SUCCESS);
+    }
+    
+    let mut report = RunReport::start(&config.source);
+    let result = if cli.hook_mode {
+        hook_run(&cli, config, &mut report).await
+    } else {
+        async {
+            let _lock = lock_run(&cli, &config).await?;
+            consolidate(&cli, &config, &mut report).await.map(|_| ())
+        }
+        .await
+    };
+    finish_report(&cli, &mut report, result)
+}
+
+/// Take the lock in the project's state directory, so a manual run and a
+/// scheduled one never write the same lore and state at once. If another
+/// run holds it this fails, unless `--wait` or `--force` says otherwise.
+async fn lock_run(cli: &Cli, config: &Config) -> Result<RunLock> {
+    let dirs = dirs(cli, config).context(Failure::Config)?;
+    fs::create_dir_all(&dirs.state).context(Failure::Output)?;
+    let path = dirs.state.join(LOCK_FILE);
+    if cli.force_lock {
+        return RunLock::force(&path).context(Failure::Locked);
+    }
+    let mut waiting = false;
+    loop {
+        if let Some(lock) = RunLock::acquire(&path).context(Failure::Output)? {
+            return Ok(lock);
+        }
+        if !cli.wait {
+            return Err(anyhow!(
+                "{} is working on this project (lock file {}); pass --wait to wait for it, or --force if it's stuck",
+                describe_holder(&path),
+                path.display()
+            ))
+            .context(Failure::Locked);
+        }
+        if !waiting {
+            println!("Waiting for {} to finish...", describe_holder(&path));
+            waiting = true;
+        }
+        tokio::time::sleep(Duration::from_millis(500)).await;
+    }
+}
+
+/// Close a run's report, writing it out if `--report-json` asked for it,
+/// and hand back the exit code the run ends with.
+fn finish_report(cli: &Cli, report: &mut RunReport, result: Result<()>) -> Result<i32> {
+    let code = report.finish(&result);
+    if cli.profile_run {
+        eprint!("{}", report.profile());
+    }
+    if let Some(path) = &cli.report_json {
+        if let Err(error) = report.write(path) {
+            eprintln!("Warning: {:#}", error);
+        }
+    }
+    result.map(|()| code)
+}
+
+/// What a run did.
+struct RunOutcome {
+    /// How many sessions it found
+    sessions: usize,
+    /// The files it wrote (none when publishing)
+    written: Vec<PathBuf>,
+    /// Its sessions, for a batch's index
+    activity: Vec<IndexedSession>,
+}
+
+/// Do one extraction run: pull the chat data, render it, and write it out
+/// (or publish it). What happens along the way goes into `report`.
+async fn consolidate(cli: &Cli, config: &Config, report: &mut RunReport) -> Result<RunOutcome> {
+    // Everything temporary for this run lives in one place and goes away with it
+    let workspace = TempWorkspace::create(&config.app_name, cli.keep_temp)?;
+    if cli.verbose || workspace.is_kept() {
+        println!("Temp workspace: {}", workspace.path().display());
+    }
+    let dirs = dirs(cli, config)?;
+    
+    // What's done is saved as we go, so an interrupted run needn't start over
+    let checkpoint = Checkpoint::open(&dirs.state.join(CHECKPOINT_DIR), &run_key(config), resume_mode(cli))
+        .context(Failure::Config)?;
+    let checkpoint = Arc::new(checkpoint);
+    if checkpoint.is_resumed() && !cli.hook_mode {
+        println!(
+            "Resuming the run started {} ({} transcripts already read)",
+            checkpoint.started_at().format("%Y-%m-%d %H:%M UTC"),
+            checkpoint.transcripts()
+        );
+    }
+    
+    // Take a copy of the source before touching it, if asked to
+    if config.backup && !checkpoint.is_done("backup") {
+        let started = Instant::now();
+        let root = if config.backup_dir.trim().is_empty() {
+            dirs.state.join(BACKUP_DIR)
+        } else {
+            PathBuf::from(shellexpand::tilde(config.backup_dir.trim()).to_string())
+        };
+        let backup = backup::back_up(config, &root)?;
+        if config.backup_keep > 0 {
+            backup::prune(&root, config.backup_keep)?;
+        }
+        println!("Backed up {} files ({} bytes) to {}", backup.files.len(), backup.bytes, backup.dir.display());
+        checkpoint.complete("backup")?;
+        report.stage("backup", started);
+    }
     
-    // Time to extract all the good stuff from the database
+    // Time to extract all the good stuff from whichever source we're reading
     // We're looking for three types of data: chat sessions, generations, and prompts
-    let sessions = extractor.extract_sessions().await?;
-    let generations = extractor.extract_generations().await?;
-    let prompts = extractor.extract_prompts().await?;
+    let started = Instant::now();
+    let tags = SessionTags::load(&dirs.state.join(TAGS_FILE))?;
+    let curation = Curation::load(&config.project_file(&config.curation_file)).context(Failure::Config)?;
+    let mut history = match checkpoint.history()? {
+        Some(history) => history,
+        None => {
+            let history = read_source(config, Some(checkpoint.clone())).await.context(Failure::Source)?;
+            checkpoint.save_history(&history)?;
+            history
+        }
+    };
+    SessionFilter::from_config(config).with_tags(tags.clone()).apply(&mut history);
+    TrivialSessions::from_config(config).apply(&mut history);
+    report.stage("extract", started);
+    
+    // Leave out the sessions unticked with --interactive, now or last time
+    pick_sessions(cli, config, &dirs, &mut history)?;
+    
+    // Label each generation as an explanation, code, a plan or error analysis
+    let classified = classify_generations(
+        &mut history.generations,
+        Some(config.classify_command.as_str()),
+        &config.project_file(""),
+    );
+    if let Err(error) = classified {
+        eprintln!("Warning: {:#}", error);
+        report.warn(format!("{:#}", error));
+    }
+    
+    // With SKIP_UNCHANGED, hash everything the lore is made from, to compare with last time
+    let publishing = matches!(cli.command, Some(Command::Publish { .. }));
+    if config.skip_unchanged && !can_skip(config) && cli.verbose {
+        println!("Not skipping unchanged runs: the lore includes what commands print");
+    }
+    let content = if config.skip_unchanged && !publishing && can_skip(config) {
+        let (output_dir, output_file) = output_location(cli, config, Utc::now()).context(Failure::Config)?;
+        // A site is written to a directory named after the file, as it is
+        let output_file = if config.encrypt && config.output_format() != OutputFormat::Site {
+            encrypted_name(&output_file)
+        } else {
+            output_file
+        };
+        let output_path = Path::new(&output_dir).join(output_file);
+        let hash = content_hash(config, &tags, &curation, &output_path, &history).context(Failure::Output)?;
+        Some((output_path, hash))
+    } else {
+        None
+    };
+    let content_state_path = dirs.cache.join(CONTENT_HASH_FILE);
+    let data_warnings = history.all_warnings();
+    let (sessions, generations, prompts) = (history.sessions, history.generations, history.prompts);
+    let session_count: usize = sessions.iter().map(|data| data.all_composers.len()).sum();
+    let activity = IndexedSession::from_sessions(&sessions);
+    report.counts = RunCounts {
+        sessions: session_count,
+        messages: sessions.iter().flat_map(|data| &data.all_composers).map(|session| session.messages.len()).sum(),
+        generations: generations.len(),
+        prompts: prompts.len(),
+        skipped_records: history.skipped.len(),
+        redactions: 0,
+    };
+    
+    // Malformed records are skipped rather than fatal, but the user should know
+    if !history.skipped.is_empty() {
+        eprintln!("Warning: {}", summarize_skipped(&history.skipped));
+        report.warn(summarize_skipped(&history.skipped));
+        if cli.verbose {
+            for record in &history.skipped {
+                eprintln!("  {} {}: {}", record.kind, record.location, record.error);
+            }
+        }
+    }
     
     // Let the user know how much data we found (if they want to know)
     if cli.verbose {
@@ -69,28 +1060,1537 @@ async fn main() -> Result<()> {
         println!("Extracted {} prompts", prompts.len());
     }
     
+    // Nothing's changed since the last run that wrote the lore, so neither would the lore
+    if let Some((output_path, hash)) = &content {
+        if ContentState::load(&content_state_path).hash == *hash && output_path.exists() {
+            checkpoint.clear()?;
+            report.unchanged = true;
+            if !cli.hook_mode {
+                println!("No changes since the last run; {} is up to date", output_path.display());
+            }
+            return Ok(RunOutcome { sessions: session_count, written: Vec::new(), activity });
+        }
+    }
+    
+    // Keep a copy of everything we found, in case the source forgets it later
+    if config.snapshots {
+        let started = Instant::now();
+        let entry = SnapshotStore::open(&dirs.state).save(&config.source, &sessions, &generations, &prompts)?;
+        if cli.verbose {
+            println!("Snapshot: {} ({} bytes)", entry.short_id(), entry.bytes);
+        }
+        report.stage("snapshot", started);
+    }
+    
+    // Publishing sends the lore to a wiki instead of writing a file
+    if let Some(Command::Publish { target, dry_run }) = &cli.command {
+        let target = PublishTarget::parse(target).context(Failure::Config)?;
+        let started = Instant::now();
+        let generator = MarkdownGenerator::new(config)
+            .with_tags(tags)
+            .with_curation(curation)
+            .with_workspace(history.workspace)
+            .with_warnings(data_warnings);
+        let plan = generator
+            .generate_publish_plan(&sessions, &generations, &prompts)
+            .context(Failure::Output)?;
+        report.counts.redactions = generator.redactions();
+        report.data_warnings = generator.warnings();
+        report.stage("render", started);
+        if *dry_run {
+            print!("{}", plan.describe(target));
+            println!("Dry run - nothing was published");
+            checkpoint.clear()?;
+            return Ok(RunOutcome { sessions: session_count, written: Vec::new(), activity });
+        }
+        let started = Instant::now();
+        let published = async { target.publisher(config, &workspace)?.publish(&plan).await }
+            .await
+            .context(Failure::Publish)?;
+        report.stage("publish", started);
+        checkpoint.clear()?;
+        for page in &published {
+            let action = match page.action {
+                PublishAction::Created => "Created",
+                PublishAction::Updated => "Updated",
+            };
+            match &page.url {
+                Some(url) => println!("{} {}: {}", action, page.title, url),
+                None => println!("{} {}", action, page.title),
+            }
+            report.published.push(page.title.clone());
+        }
+        
+        // Point the digest at the overview page people can start reading from
+        let root_url = published.first().and_then(|page| page.url.clone());
+        send_digest(config, &sessions, &dirs.state, root_url, cli.verbose, report).await;
+        return Ok(RunOutcome { sessions: session_count, written: Vec::new(), activity });
+    }
+    
     // Now comes the fun part - we take all that raw data and turn it into
-    // a nice, readable markdown file that tells the story of the code
-    let generator = MarkdownGenerator::new(&config);
-    let markdown_content = generator.generate_consolidated_history(
-        &sessions,
+    // a nice, readable document that tells the story of the code
+    let started = Instant::now();
+    let generator = MarkdownGenerator::new(config)
+        .with_tags(tags)
+        .with_curation(curation)
+        .with_workspace(history.workspace)
+        .with_warnings(data_warnings);
+    
+    // With ENCRYPT on, only the encrypted files (name.md.age) ever leave the workspace
+    let key = if config.encrypt { Some(EncryptionKey::from_config(config).context(Failure::Config)?) } else { None };
+    let seal = |name: &str, data: Vec<u8>| -> Result<(String, Vec<u8>)> {
+        match &key {
+            Some(key) => Ok((encrypted_name(name), encrypt(&data, key)?)),
+            None => Ok((name.to_string(), data)),
+        }
+    };
+    
+    // We render into the temp workspace first so an interrupted run never
+    // leaves half a file behind. The default .md name follows the format
+    // (.org, .adoc, .pdf, .csv) if it's changed
+    let (output_dir, output_file) = output_location(cli, config, Utc::now()).context(Failure::Config)?;
+    let written_file = if key.is_some() { encrypted_name(&output_file) } else { output_file.clone() };
+    
+    // A site is a directory of pages rather than one file, so it's written on its own
+    if config.output_format() == OutputFormat::Site {
+        let files = generator.generate_site(&sessions, &generations, &prompts).context(Failure::Output)?;
+        report.counts.redactions = generator.redactions();
+        report.data_warnings = generator.warnings();
+        report.stage("render", started);
+        
+        let started = Instant::now();
+        let site_dir = Path::new(&output_dir).join(&output_file);
+        let written = write_site(&site_dir, files, &workspace, config.keep_previous_output).context(Failure::Output)?;
+        for path in &written {
+            report.output(path).context(Failure::Output)?;
+        }
+        report.stage("write", started);
+        checkpoint.clear()?;
+        record_content(&content_state_path, content, report);
+        if cli.hook_mode {
+            return Ok(RunOutcome { sessions: session_count, written, activity });
+        }
+        
+        println!("Chat history consolidated successfully!");
+        println!("Site: {} files in {} (run `zola build` there to build it)", written.len(), site_dir.display());
+        send_digest(config, &sessions, &dirs.state, None, cli.verbose, report).await;
+        return Ok(RunOutcome { sessions: session_count, written, activity });
+    }
+    
+    let render_path = (|| -> Result<PathBuf> {
+        let render_path = workspace.file("renders", &written_file)?;
+        if key.is_some() {
+            // Encryption needs the whole file, so this one is built in memory
+            let content = generator.generate_output(&sessions, &generations, &prompts)?;
+            fs::write(&render_path, seal(&output_file, content)?.1)?;
+        } else {
+            // Otherwise it goes to disk a section at a time as it's built
+            let file = File::create(&render_path)?;
+            generator.write_output(&sessions, &generations, &prompts, BufWriter::new(file))?;
+        }
+        Ok(render_path)
+    })()
+    .context(Failure::Output)?;
+    
+    // The knowledge graph goes next to the markdown, named after it
+    let graph_format = config.graph_export_format();
+    let graph = if graph_format != GraphFormat::None {
+        Some(KnowledgeGraph::build(&sessions, config).export(graph_format).context(Failure::Output)?)
+    } else {
+        None
+    };
+    let has_graph = graph.is_some();
+    
+    // With FILE_LORE, a small file per busy source file, linking into the lore
+    let file_lore = if config.file_lore {
+        generator.generate_file_lore(&sessions, &output_file).context(Failure::Output)?
+    } else {
+        Vec::new()
+    };
+    
+    // With LORE_INDEX, where each file and symbol came up, for editor extensions
+    let lore_index = if config.lore_index {
+        Some(generator.generate_lore_index(&sessions, &output_file).context(Failure::Output)?)
+    } else {
+        None
+    };
+    let has_index = lore_index.is_some();
+    
+    // With RENDER_PLUGINS, whatever files the plugins make of the sessions
+    let mut plugin_files = Vec::new();
+    for name in plugins::parse_names(&config.render_plugins).context(Failure::Config)? {
+        let plugin = Plugin::find(&name).context(Failure::Output)?;
+        plugin_files.extend(plugin.render(config, &sessions, &generations, &prompts).context(Failure::Output)?);
+    }
+    let plugin_file_count = plugin_files.len();
+    report.counts.redactions = generator.redactions();
+    report.data_warnings = generator.warnings();
+    report.stage("render", started);
+    
+    let started = Instant::now();
+    let written = (|| -> Result<Vec<PathBuf>> {
+        // Make sure the output directory exists before we try to write to it
+        // (nothing worse than a file write error because the directory doesn't exist)
+        fs::create_dir_all(&output_dir)?;
+        let keep = config.keep_previous_output;
+        
+        // Finally, put our beautiful file in place
+        let output_path = Path::new(&output_dir).join(&written_file);
+        atomic::install(&render_path, &output_path, keep)?;
+        let mut written = vec![output_path];
+        
+        if let Some(graph) = graph {
+            let stem = Path::new(&output_file).file_stem().unwrap_or_default().to_string_lossy();
+            let (graph_file, graph) = seal(&format!("{}.graph.{}", stem, graph_format.extension()), graph.into_bytes())?;
+            let render_path = workspace.file("renders", &graph_file)?;
+            fs::write(&render_path, graph)?;
+            let graph_path = Path::new(&output_dir).join(&graph_file);
+            atomic::install(&render_path, &graph_path, keep)?;
+            written.push(graph_path);
+        }
+        
+        if let Some(lore_index) = lore_index {
+            let (index_file, lore_index) = seal(LORE_INDEX_FILE, lore_index.into_bytes())?;
+            let render_path = workspace.file("renders", &index_file)?;
+            fs::write(&render_path, lore_index)?;
+            let index_path = Path::new(&output_dir).join(&index_file);
+            atomic::install(&render_path, &index_path, keep)?;
+            written.push(index_path);
+        }
+        
+        for file in plugin_files {
+            let (name, content) = seal(&file.path, file.content.into_bytes())?;
+            let render_path = workspace.file("renders", &name.replace(['/', '\\'], "-"))?;
+            fs::write(&render_path, content)?;
+            let path = Path::new(&output_dir).join(&name);
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            atomic::install(&render_path, &path, keep)?;
+            written.push(path);
+        }
+        
+        if config.file_lore {
+            // Clear out last run's files, so ones that dropped below the bar go
+            let files_dir = Path::new(&output_dir).join(FILE_LORE_DIR);
+            if files_dir.is_dir() {
+                for entry in fs::read_dir(&files_dir)? {
+                    let path = entry?.path();
+                    let name = path.file_name().unwrap_or_default().to_string_lossy();
+                    if name.ends_with(".md") || name.ends_with(".md.age") {
+                        fs::remove_file(&path)?;
+                    }
+                }
+            }
+            fs::create_dir_all(&files_dir)?;
+            for (name, text) in file_lore {
+                let (name, text) = seal(&name, text.into_bytes())?;
+                let render_path = workspace.file("renders", &format!("{}-{}", FILE_LORE_DIR, name))?;
+                fs::write(&render_path, text)?;
+                let path = files_dir.join(&name);
+                atomic::install(&render_path, &path, false)?;
+                written.push(path);
+            }
+        }
+        Ok(written)
+    })()
+    .context(Failure::Output)?;
+    for path in &written {
+        report.output(path).context(Failure::Output)?;
+    }
+    report.stage("write", started);
+    checkpoint.clear()?;
+    record_content(&content_state_path, content, report);
+    
+    // A hook mid-commit should stay quiet and not wait on the network
+    if cli.hook_mode {
+        return Ok(RunOutcome { sessions: session_count, written, activity });
+    }
+    
+    // Success! Let the user know we're done and where to find their file
+    println!("Chat history consolidated successfully!");
+    println!("Output file: {}", written[0].display());
+    if has_graph {
+        println!("Knowledge graph: {}", written[1].display());
+    }
+    if has_index {
+        println!("Lore index: {}", written[1 + usize::from(has_graph)].display());
+    }
+    let plugin_outputs = 1 + usize::from(has_graph) + usize::from(has_index);
+    for path in &written[plugin_outputs..plugin_outputs + plugin_file_count] {
+        println!("Plugin output: {}", path.display());
+    }
+    let file_lore_count = written.len() - plugin_outputs - plugin_file_count;
+    if file_lore_count > 0 {
+        let files_dir = written[written.len() - 1].parent().unwrap_or(Path::new("."));
+        println!("File lore: {} files in {}", file_lore_count, files_dir.display());
+    }
+    
+    // Let the team know about the new sessions, if there's a webhook for it
+    send_digest(config, &sessions, &dirs.state, None, cli.verbose, report).await;
+    
+    Ok(RunOutcome { sessions: session_count, written, activity })
+}
+
+/// Record the content hash of the lore a run has just written, so the
+/// next run can tell if anything's changed (SKIP_UNCHANGED). The lore is
+/// already written, so failing to record it only costs the next run a skip.
+fn record_content(path: &Path, content: Option<(PathBuf, String)>, report: &mut RunReport) {
+    let Some((_, hash)) = content else {
+        return;
+    };
+    let saved = (|| -> Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        ContentState { hash }.save(path)
+    })();
+    if let Err(error) = saved {
+        eprintln!("Warning: couldn't record the content hash: {:#}", error);
+        report.warn(format!("Couldn't record the content hash: {:#}", error));
+    }
+}
+
+/// Write a site's files under `dir`, each rendered in the workspace first
+/// like the lore is. Last run's session pages are cleared out first, so a
+/// session that's been left out since doesn't linger on the site; the
+/// config and templates are only written if they're missing, since they're
+/// there to be changed.
+fn write_site(dir: &Path, files: Vec<(String, String)>, workspace: &TempWorkspace, keep_previous: bool) -> Result<Vec<PathBuf>> {
+    let sessions_dir = dir.join("content").join(SESSIONS_SECTION);
+    if sessions_dir.is_dir() {
+        for entry in fs::read_dir(&sessions_dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|extension| extension == "md") {
+                fs::remove_file(&path)?;
+            }
+        }
+    }
+    
+    let mut written = Vec::new();
+    for (name, text) in files {
+        let path = dir.join(&name);
+        if !name.starts_with("content/") && path.exists() {
+            continue;
+        }
+        let render_path = workspace.file("renders", &format!("site-{}", name.replace('/', "__")))?;
+        fs::write(&render_path, text)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        atomic::install(&render_path, &path, keep_previous)?;
+        written.push(path);
+    }
+    Ok(written)
+}
+
+/// Show the sessions with checkboxes for `--interactive` and remember the
+/// picks, then leave out every session that's unticked (in this run or,
+/// without `--interactive`, the last one that asked).
+fn pick_sessions(cli: &Cli, config: &Config, dirs: &Dirs, history: &mut ImportedHistory) -> Result<()> {
+    let path = dirs.state.join(PICKER_STATE_FILE);
+    let mut state = PickerState::load(&path)?;
+    if cli.interactive {
+        let term = Term::stderr();
+        if !term.is_term() {
+            return Err(anyhow!("--interactive needs a terminal to list the sessions in")).context(Failure::Config);
+        }
+        let sessions: Vec<&ChatSession> = history.sessions.iter().flat_map(|data| &data.all_composers).collect();
+        let zone = config.display_zone();
+        let items: Vec<String> = sessions
+            .iter()
+            .map(|session| {
+                let created = zone.format_millis(session.created_at, "%Y-%m-%d").unwrap_or_default();
+                format!("{} ({}, {} messages)", session.name, created, session.messages.len())
+            })
+            .collect();
+        let ticked: Vec<bool> = sessions.iter().map(|session| !state.left_out.contains(&session.composer_id)).collect();
+        let picked = MultiSelect::new()
+            .with_prompt("Sessions to export (space to tick, enter to confirm, esc to cancel)")
+            .items(&items)
+            .defaults(&ticked)
+            .max_length(20)
+            .interact_on_opt(&term)?;
+        let Some(picked) = picked else {
+            bail!("Cancelled - nothing was exported");
+        };
+        let chosen: HashSet<String> = picked.into_iter().map(|index| sessions[index].composer_id.clone()).collect();
+        state.record(&sessions, &chosen);
+        fs::create_dir_all(&dirs.state)?;
+        state.save(&path)?;
+    }
+    let left_out = state.filter().apply(history);
+    if left_out > 0 && (cli.verbose || cli.interactive) {
+        println!("Leaving out {} unticked sessions", left_out);
+    }
+    Ok(())
+}
+
+/// Refresh the lore from a git hook. This has to be quick, since someone
+/// is waiting on their commit: if nothing the run reads has changed since
+/// the last hook run, it stops there, and otherwise it skips the
+/// per-edit git lookups. A run that's already going (the daemon, say)
+/// wins, and this one quietly does nothing.
+async fn hook_run(cli: &Cli, mut config: Config, report: &mut RunReport) -> Result<()> {
+    let (output_dir, mut output_file) = output_location(cli, &config, Utc::now())?;
+    let dirs = dirs(cli, &config)?;
+    fs::create_dir_all(&dirs.state)?;
+    let Some(_lock) = RunLock::acquire(&dirs.state.join(LOCK_FILE))? else {
+        return Ok(());
+    };
+    
+    fs::create_dir_all(&dirs.cache)?;
+    let state_path = dirs.cache.join(HOOK_STATE_FILE);
+    let fingerprint = hook::source_fingerprint(&config, Path::new(&cli.config));
+    if config.encrypt {
+        output_file = encrypted_name(&output_file);
+    }
+    if HookState::load(&state_path).fingerprint == fingerprint && Path::new(&output_dir).join(&output_file).exists() {
+        return Ok(());
+    }
+    
+    // One `git log` per edit is far too slow for a hook, and so is copying
+    // databases that can run to gigabytes
+    config.link_edits_to_commits = false;
+    config.backup = false;
+    let outcome = consolidate(cli, &config, report).await?;
+    // Lore written outside the repository can't be staged, which is fine
+    if let Err(error) = hook::stage(&outcome.written) {
+        eprintln!("Warning: couldn't stage the lore: {:#}", error);
+        report.warn(format!("Couldn't stage the lore: {:#}", error));
+    }
+    HookState { fingerprint }.save(&state_path)
+}
+
+/// Write the git hook into PROJECT_PATH. Paths in the hook are made
+/// absolute, since git runs hooks from the top of the repository.
+fn install_hook(cli: &Cli, config: &Config, kind: HookKind, force: bool) -> Result<()> {
+    let program = std::env::current_exe()?;
+    let config_file = std::path::absolute(&cli.config)?;
+    let output_dir = std::path::absolute(cli.output_dir.clone().unwrap_or(config.output_dir.clone()))?;
+    let script = hook::hook_script(&program, &config_file, cli.profile.as_deref(), &output_dir);
+    let path = hook::install(Path::new(&config.project_path), kind, &script, force)?;
+    println!("Installed {}", path.display());
+    Ok(())
+}
+
+/// Where a run at `now` writes the lore: OUTPUT_DIR and OUTPUT_FILENAME
+/// (or `--output-dir` and `--output-file`) with their placeholders filled
+/// in. A run works this out once, so everything it writes shares a date.
+fn output_location(cli: &Cli, config: &Config, now: DateTime<Utc>) -> Result<(String, String)> {
+    let mut config = config.clone();
+    if let Some(output_dir) = &cli.output_dir {
+        config.output_dir = output_dir.clone();
+    }
+    if let Some(output_file) = &cli.output_file {
+        config.output_filename = output_file.clone();
+    }
+    let output_dir = config.output_directory(now).context("OUTPUT_DIR (or --output-dir) is not a usable directory template")?;
+    let output_file = config.output_file_name(now).context("OUTPUT_FILENAME (or --output-file) is not a usable file name template")?;
+    Ok((output_dir, output_file))
+}
+
+/// Where this project's run history, snapshots and other bookkeeping go.
+/// Anything an older version left in the output directory is moved there
+/// first.
+fn dirs(cli: &Cli, config: &Config) -> Result<Dirs> {
+    // The template rather than this run's directory, so a dated OUTPUT_DIR
+    // still keeps one run history
+    let output_dir = PathBuf::from(cli.output_dir.clone().unwrap_or(config.output_dir.clone()));
+    let dirs = Dirs::resolve(config, &output_dir)?;
+    match dirs.adopt_legacy(&output_dir) {
+        Ok(moved) => {
+            for path in moved {
+                println!("Moved {} to {}", path.file_name().unwrap_or_default().to_string_lossy(), path.display());
+            }
+        }
+        Err(error) => eprintln!("Warning: couldn't move old state out of the output directory: {:#}", error),
+    }
+    Ok(dirs)
+}
+
+/// Read everything from whichever source is configured, minus the
+/// sessions INCLUDE_SESSIONS, EXCLUDE_SESSIONS and the tag filters (going
+/// by `tags`) leave out.
+async fn extract(config: &Config, tags: &SessionTags) -> Result<ImportedHistory> {
+    let mut history = read_source(config, None).await?;
+    
+    // Throwaway and personal sessions never go any further than this
+    SessionFilter::from_config(config).with_tags(tags.clone()).apply(&mut history);
+    TrivialSessions::from_config(config).apply(&mut history);
+    Ok(history)
+}
+
+/// Read everything from whichever source is configured. Cursor's
+/// transcripts are saved to `checkpoint` as they're read, if there is one.
+async fn read_source(config: &Config, checkpoint: Option<Arc<Checkpoint>>) -> Result<ImportedHistory> {
+    let mut history = match config.source.as_str() {
+        // Cursor keeps everything in a SQLite database, so connect to it first
+        "cursor" => {
+            let mut extractor = ChatExtractor::new(config).await?;
+            if let Some(checkpoint) = checkpoint {
+                extractor = extractor.with_checkpoint(checkpoint);
+            }
+            extractor.extract_all().await?
+        }
+        "continue" => ContinueImporter::new(config)?.import().await?,
+        "cody" => CodyImporter::new(config).await?.import().await?,
+        other => match plugins::source_plugin(other) {
+            Some(name) => Plugin::find(name)?.import(config)?,
+            None => bail!("Unsupported source '{}' (expected cursor, continue, cody, or plugin:<name>)", other),
+        },
+    };
+    if config.strict {
+        let drift = history.schema_drift();
+        if !drift.is_empty() {
+            bail!("The history doesn't match the expected schema (STRICT is on):\n  {}", drift.join("\n  "));
+        }
+    }
+    // No source's dates are taken on trust: a zero or far-off one would
+    // put the session in the wrong place in the lore
+    timestamps::validate_history(&mut history, config.timestamp_handling(), Utc::now().timestamp_millis());
+    Ok(history)
+}
+
+/// What to do about an interrupted run's checkpoint. The daemon and git
+/// hooks have nobody to ask, so they pick up where it stopped.
+fn resume_mode(cli: &Cli) -> ResumeMode {
+    if cli.resume {
+        ResumeMode::Resume
+    } else if cli.restart {
+        ResumeMode::Restart
+    } else if cli.hook_mode || matches!(cli.command, Some(Command::Daemon { .. })) {
+        ResumeMode::Resume
+    } else {
+        ResumeMode::Ask
+    }
+}
+
+/// Show, add or take off a session's tags, or list every session's tags.
+async fn tag(
+    config: &Config,
+    dirs: &Dirs,
+    session: Option<&str>,
+    tags: Option<&str>,
+    remove: bool,
+    suggested: bool,
+) -> Result<()> {
+    let path = dirs.state.join(TAGS_FILE);
+    let mut store = SessionTags::load(&path)?;
+    
+    // Every session can be tagged, whatever the tag filters say
+    let mut config = config.clone();
+    config.include_tags.clear();
+    config.exclude_tags.clear();
+    let history = extract(&config, &store).await.context(Failure::Source)?;
+    
+    let Some(session) = session else {
+        let terminal = config.terminal();
+        let mut table = Table::new(&["id", "session", "tags"]);
+        for session in history.sessions.iter().flat_map(|data| &data.all_composers) {
+            let tags = store.of(&session.composer_id);
+            let tags = if tags.is_empty() {
+                terminal.paint(Tone::Muted, &format!("(suggested: {})", suggest_tags(session).join(", ")))
+            } else {
+                tags.join(", ")
+            };
+            let id: String = session.composer_id.chars().take(8).collect();
+            table.row(vec![terminal.paint(Tone::Muted, &id), session.name.clone(), tags]);
+        }
+        print!("{}", table.render(&terminal));
+        return Ok(());
+    };
+    let session = select_sessions(&history.sessions, &[session.to_string()])?[0];
+    let suggestions = suggest_tags(session);
+    
+    let mut changes = parse_tags(tags.unwrap_or_default()).context(Failure::Config)?;
+    if suggested {
+        changes.extend(suggestions.iter().cloned());
+    }
+    if changes.is_empty() {
+        let current = store.of(&session.composer_id);
+        println!("{} ({})", session.name, session.composer_id);
+        println!("Tags:      {}", if current.is_empty() { "none".to_string() } else { current.join(", ") });
+        println!("Suggested: {}", if suggestions.is_empty() { "none".to_string() } else { suggestions.join(", ") });
+        return Ok(());
+    }
+    if remove {
+        store.remove(&session.composer_id, &changes);
+    } else {
+        store.add(&session.composer_id, &changes);
+    }
+    fs::create_dir_all(&dirs.state)?;
+    store.save(&path)?;
+    let current = store.of(&session.composer_id);
+    println!("{}: {}", session.name, if current.is_empty() { "no tags".to_string() } else { current.join(", ") });
+    Ok(())
+}
+
+/// Write an ADR for each decision in the chats that doesn't have one yet.
+async fn generate_adrs(config: &Config, dirs: &Dirs, dir: Option<PathBuf>, dry_run: bool) -> Result<()> {
+    let dir = dir.unwrap_or_else(|| Path::new(&shellexpand::tilde(&config.project_path).to_string()).join(ADR_DIR));
+    let tags = SessionTags::load(&dirs.state.join(TAGS_FILE))?;
+    let history = extract(config, &tags).await.context(Failure::Source)?;
+    let plan = plan_adrs(config, &history.sessions, &dir).context(Failure::Output)?;
+    if plan.existing > 0 {
+        println!("{} decisions already have ADRs in {}", plan.existing, dir.display());
+    }
+    if plan.new.is_empty() {
+        println!("No new decisions to record");
+        return Ok(());
+    }
+    if dry_run {
+        for adr in &plan.new {
+            println!("Would write {}", dir.join(&adr.file_name).display());
+        }
+        return Ok(());
+    }
+    for path in write_adrs(&dir, &plan.new).context(Failure::Output)? {
+        println!("Wrote {}", path.display());
+    }
+    Ok(())
+}
+
+/// Print the open follow-ups, oldest first, with their excerpts redacted.
+async fn follow_ups(config: &Config, dirs: &Dirs, format: &str) -> Result<()> {
+    let json = match format.to_lowercase().as_str() {
+        "text" => false,
+        "json" => true,
+        other => return Err(anyhow!("Unknown follow-up format '{}' (use text or json)", other)).context(Failure::Config),
+    };
+    let tags = SessionTags::load(&dirs.state.join(TAGS_FILE))?;
+    let history = extract(config, &tags).await.context(Failure::Source)?;
+    let mut found = find_follow_ups(&history.sessions, &history.generations, &history.prompts);
+    for follow_up in &mut found {
+        follow_up.excerpt = config.redact_secrets(&follow_up.excerpt);
+    }
+    if json {
+        println!("{}", serde_json::to_string_pretty(&found)?);
+        return Ok(());
+    }
+    if found.is_empty() {
+        println!("No open follow-ups");
+    }
+    let zone = config.display_zone();
+    for follow_up in &found {
+        let date = follow_up.timestamp.and_then(|timestamp| zone.format_millis(timestamp, "%Y-%m-%d"));
+        let session = follow_up.session_name.as_deref().map(|name| format!(" ({})", name));
+        println!(
+            "{} {}{}: {}",
+            follow_up.id,
+            date.unwrap_or_else(|| "----------".to_string()),
+            session.unwrap_or_default(),
+            follow_up.excerpt
+        );
+    }
+    Ok(())
+}
+
+/// File (or update) an issue for each follow-up or decision in the chats.
+async fn issues(config: &Config, dirs: &Dirs, target: &str, from: &[String], dry_run: bool) -> Result<()> {
+    let target = IssueTarget::parse(target).context(Failure::Config)?;
+    let kinds = from
+        .iter()
+        .flat_map(|list| list.split(','))
+        .map(IssueKind::parse)
+        .collect::<Result<Vec<_>>>()
+        .context(Failure::Config)?;
+    let tags = SessionTags::load(&dirs.state.join(TAGS_FILE))?;
+    let history = extract(config, &tags).await.context(Failure::Source)?;
+    let issues = plan_issues(config, &history.sessions, &history.generations, &history.prompts, &kinds);
+    if dry_run {
+        print!("{}", describe_issues(&issues, target));
+        println!("Dry run - nothing was filed");
+        return Ok(());
+    }
+    if issues.is_empty() {
+        println!("Nothing to file");
+        return Ok(());
+    }
+    let tracker = target.tracker(config).context(Failure::Config)?;
+    let filed = tracker.file(&issues).await.context(Failure::Publish)?;
+    for issue in &filed {
+        let action = match issue.action {
+            IssueAction::Created => "Created",
+            IssueAction::Updated => "Updated",
+            IssueAction::Unchanged => "Unchanged",
+        };
+        match &issue.url {
+            Some(url) => println!("{} {}: {}", action, issue.title, url),
+            None => println!("{} {}", action, issue.title),
+        }
+    }
+    Ok(())
+}
+
+/// Email a digest of the last `days` days' sessions, or with `dry_run`
+/// write the email to the output directory instead.
+async fn email_digest(cli: &Cli, config: &Config, dirs: &Dirs, days: i64, dry_run: bool) -> Result<()> {
+    let templates = EmailTemplates::from_config(config).context(Failure::Config)?;
+    let tags = SessionTags::load(&dirs.state.join(TAGS_FILE))?;
+    let history = extract(config, &tags).await.context(Failure::Source)?;
+    let strings = Strings::for_locale(&config.locale).unwrap_or_default();
+    let Some(digest) = EmailDigest::build(config, &strings, &templates, &history.sessions, days) else {
+        println!("No sessions in the last {} days - nothing to send", days);
+        return Ok(());
+    };
+    
+    if dry_run {
+        let message = email::message(config, &digest).context(Failure::Config)?;
+        let now = Utc::now();
+        let output_dir = PathBuf::from(output_location(cli, config, now).context(Failure::Config)?.0);
+        let path = output_dir.join(format!("lore-digest-{}.eml", now.format("%Y-%m-%d")));
+        fs::create_dir_all(&output_dir).context(Failure::Output)?;
+        atomic::write_atomic(&path, &message).context(Failure::Output)?;
+        println!("Dry run - wrote the email to {} instead of sending it", path.display());
+        return Ok(());
+    }
+    email::send(config, &digest).await.context(Failure::Publish)?;
+    println!("Emailed the digest to {}", email::recipients(config).join(", "));
+    Ok(())
+}
+
+/// Print the JSON Schemas (or one of them), or write them into `dir`.
+fn export_schemas(name: Option<&str>, dir: Option<&Path>) -> Result<()> {
+    let schemas = match name {
+        Some(name) => vec![json_schema::exported_schema(name)?],
+        None => json_schema::exported_schemas(),
+    };
+    match (dir, name) {
+        (Some(dir), _) => {
+            for path in json_schema::write_schemas(dir, &schemas)? {
+                println!("Wrote {}", path.display());
+            }
+        }
+        (None, Some(_)) => print!("{}", schemas[0].to_json()?),
+        (None, None) => {
+            let all: serde_json::Map<String, serde_json::Value> = schemas
+                .iter()
+                .map(|schema| Ok((schema.name.to_string(), serde_json::to_value(&schema.schema)?)))
+                .collect::<Result<_>>()?;
+            println!("{}", serde_json::to_string_pretty(&all)?);
+        }
+    }
+    Ok(())
+}
+
+/// List the plugins on PATH, shaking hands with each to see what it is.
+fn list_plugins(terminal: &Terminal) {
+    let found = plugins::discover();
+    if found.is_empty() {
+        println!("No plugins on PATH (they're executables called {}<name>)", plugins::PLUGIN_PREFIX);
+        return;
+    }
+    let mut table = Table::new(&["plugin", "version", "capabilities", "path", "description"]);
+    for plugin in found {
+        let path = terminal.paint(Tone::Muted, &plugin.path.display().to_string());
+        match plugin.describe() {
+            Ok(handshake) => {
+                let capabilities: Vec<&str> = handshake.capabilities.iter().map(|capability| capability.name()).collect();
+                table.row(vec![plugin.name, handshake.version, capabilities.join(", "), path, handshake.description]);
+            }
+            Err(error) => table.row(vec![
+                plugin.name,
+                terminal.paint(Tone::Error, "unusable"),
+                String::new(),
+                path,
+                format!("{:#}", error),
+            ]),
+        }
+    }
+    print!("{}", table.render(terminal));
+}
+
+/// List the sanitizer's rules, or show which of them fire on some text.
+fn sanitize(config: &Config, action: &SanitizeAction) {
+    let sanitizer = config.sanitizer();
+    let terminal = config.terminal();
+    match action {
+        SanitizeAction::Rules => {
+            if sanitizer.rules.is_empty() {
+                println!("No rules - the output is written as it is");
+                return;
+            }
+            let mut table = Table::new(&["#", "rule"]).align(0, Align::Right);
+            for (number, rule) in sanitizer.rules.iter().enumerate() {
+                table.row(vec![(number + 1).to_string(), rule.label.clone()]);
+            }
+            print!("{}", table.render(&terminal));
+        }
+        SanitizeAction::Test { text } => {
+            let hits = sanitizer.trace(text);
+            if hits.is_empty() {
+                println!("No rule fired ({} checked)", sanitizer.rules.len());
+            }
+            for hit in &hits {
+                println!("{}\n    {}", terminal.paint(Tone::Heading, &hit.rule.label), hit.after);
+            }
+            println!("Result: {}", hits.last().map_or(text.as_str(), |hit| hit.after.as_str()));
+        }
+    }
+}
+
+/// Lint the lore, returning the exit code: 7 if there were problems.
+fn lint(cli: &Cli, config: &Config, file: Option<PathBuf>, max_section_kb: usize) -> Result<i32> {
+    let file = match file {
+        Some(file) => file,
+        None => {
+            let (output_dir, name) = output_location(cli, config, Utc::now()).context(Failure::Config)?;
+            Path::new(&output_dir).join(name)
+        }
+    };
+    if file.extension().is_none_or(|extension| extension != "md") {
+        return Err(anyhow!("lint checks markdown lore, and {} isn't markdown", file.display())).context(Failure::Config);
+    }
+    let text = fs::read_to_string(&file)
+        .with_context(|| format!("Could not read {}", file.display()))
+        .context(Failure::Output)?;
+    let strings = Strings::for_locale(&config.locale).unwrap_or_default();
+    let problems = lint_markdown(&text, &LintOptions::new(&strings, max_section_kb));
+    for problem in &problems {
+        println!("{}:{}", file.display(), problem);
+    }
+    if problems.is_empty() {
+        println!("{}: no problems found", file.display());
+        return Ok(exit_code::SUCCESS);
+    }
+    eprintln!("{} problems found", problems.len());
+    Ok(exit_code::LINT)
+}
+
+/// Upgrade the files under `path` (the output directory by default) that
+/// older versions wrote, keeping the old ones as .bak.
+fn migrate(cli: &Cli, config: &Config, path: Option<PathBuf>, dry_run: bool) -> Result<()> {
+    let path = match path {
+        Some(path) => path,
+        None => PathBuf::from(output_location(cli, config, Utc::now()).context(Failure::Config)?.0),
+    };
+    let mut files = Vec::new();
+    let mut pending = vec![path.clone()];
+    while let Some(next) = pending.pop() {
+        if next.is_dir() {
+            let entries = fs::read_dir(&next).with_context(|| format!("Could not read {}", next.display()))?;
+            pending.extend(entries.filter_map(|entry| entry.ok().map(|entry| entry.path())));
+        } else if next.is_file() {
+            files.push(next);
+        }
+    }
+    if files.is_empty() {
+        bail!("There's nothing to migrate at {}", path.display());
+    }
+    files.sort();
+    
+    let (mut migrated, mut failed) = (0, 0);
+    for file in &files {
+        let upgrade = fs::read(file)
+            .with_context(|| format!("Could not read {}", file.display()))
+            .and_then(|bytes| versioning::migrate(file, &bytes));
+        match upgrade {
+            Ok(Some(upgrade)) => {
+                println!(
+                    "{}: {} from format version {}: {}",
+                    file.display(),
+                    upgrade.kind.name(),
+                    upgrade.from,
+                    upgrade.changes.join(", ")
+                );
+                if !dry_run {
+                    atomic::replace(file, &upgrade.contents, true)?;
+                }
+                migrated += 1;
+            }
+            Ok(None) => {}
+            Err(error) => {
+                eprintln!("Warning: {:#}", error);
+                failed += 1;
+            }
+        }
+    }
+    let verb = if dry_run { "Would migrate" } else { "Migrated" };
+    println!("{} {} of {} files to format version {}", verb, migrated, files.len(), FORMAT_VERSION);
+    if failed > 0 {
+        bail!("{} files couldn't be migrated", failed);
+    }
+    Ok(())
+}
+
+/// Compare the lore with the chat history it was written from, returning
+/// the exit code: 9 if sessions are missing from it or have changed.
+async fn verify(cli: &Cli, config: &Config, file: Option<PathBuf>) -> Result<i32> {
+    let format = config.output_format();
+    let file = match file {
+        Some(file) => file,
+        None => {
+            let (output_dir, name) = output_location(cli, config, Utc::now()).context(Failure::Config)?;
+            Path::new(&output_dir).join(name)
+        }
+    };
+    if config.encrypt {
+        return Err(anyhow!("verify compares the lore as text; decrypt {} first", file.display())).context(Failure::Config);
+    }
+    if format.render_blocks(&[]).is_err() {
+        return Err(anyhow!("verify needs a text OUTPUT_FORMAT (markdown, org, asciidoc, typst or json)")).context(Failure::Config);
+    }
+    let lore = fs::read_to_string(&file)
+        .with_context(|| format!("Could not read {}", file.display()))
+        .context(Failure::Output)?;
+    
+    // Build the document just as a run would, from what the database has now
+    let dirs = dirs(cli, config)?;
+    let tags = SessionTags::load(&dirs.state.join(TAGS_FILE))?;
+    let mut history = extract(config, &tags).await.context(Failure::Source)?;
+    pick_sessions(cli, config, &dirs, &mut history)?;
+    let _ = classify_generations(&mut history.generations, Some(config.classify_command.as_str()), &config.project_file(""));
+    let curation = Curation::load(&config.project_file(&config.curation_file)).context(Failure::Config)?;
+    let generator = MarkdownGenerator::new(config)
+        .with_tags(tags)
+        .with_curation(curation)
+        .with_warnings(history.all_warnings())
+        .with_workspace(history.workspace);
+    let document = generator
+        .generate_document(&history.sessions, &history.generations, &history.prompts)
+        .context(Failure::Output)?;
+    
+    let strings = Strings::for_locale(&config.locale).unwrap_or_default();
+    let verification = verify_lore(&lore, &document, format, &strings).context(Failure::Output)?;
+    for session in &verification.missing {
+        println!("missing: {} ({}) sha256:{}", session.title, session.id, session.hash);
+    }
+    for session in &verification.drifted {
+        println!("changed: {} ({}) sha256:{}", session.title, session.id, session.hash);
+    }
+    println!(
+        "{}: {} sessions checked, {} missing, {} changed (sha256:{})",
+        file.display(),
+        verification.sessions.len(),
+        verification.missing.len(),
+        verification.drifted.len(),
+        verification.lore_hash
+    );
+    if verification.is_faithful() {
+        return Ok(exit_code::SUCCESS);
+    }
+    eprintln!("The lore no longer matches the chat history; run again to bring it up to date");
+    Ok(exit_code::VERIFY)
+}
+
+/// Write sessions back into a Cursor workspace (experimental). The data
+/// comes from the configured source, or from a snapshot - which is how
+/// history travels to a new machine.
+async fn restore(config: &Config, dirs: &Dirs, args: &RestoreArgs) -> Result<()> {
+    let (sessions, generations, prompts) = match &args.snapshot {
+        Some(id) => {
+            let store = SnapshotStore::open(&dirs.state);
+            let dataset = store.load(&store.find(id)?)?;
+            (dataset.sessions, dataset.generations, dataset.prompts)
+        }
+        None => {
+            let tags = SessionTags::load(&dirs.state.join(TAGS_FILE))?;
+            let history = extract(config, &tags).await.context(Failure::Source)?;
+            (history.sessions, history.generations, history.prompts)
+        }
+    };
+    let selected = select_sessions(&sessions, &args.sessions)?;
+    
+    // Never write into the database we're reading from
+    for target in args.workspace_db.iter().chain(&args.global_db) {
+        let target = fs::canonicalize(target).with_context(|| {
+            format!("{} doesn't exist - open the project in Cursor once so it creates its database", target.display())
+        })?;
+        if config.source_files().iter().any(|source| fs::canonicalize(source).is_ok_and(|source| source == target)) {
+            bail!("{} is the database being read from; restore into a different workspace", target.display());
+        }
+    }
+    
+    // Merge with what the target already has rather than replacing it
+    let writable = ConnectionOptions { read_only: false, ..ConnectionOptions::from_config(config) };
+    let workspace = match &args.workspace_db {
+        Some(path) => Some(SqliteStore::open_with(&path.to_string_lossy(), "ItemTable", &writable).await?),
+        None => None,
+    };
+    let global = match &args.global_db {
+        Some(path) => Some(SqliteStore::open_with(&path.to_string_lossy(), &config.conversation_table, &writable).await?),
+        None => None,
+    };
+    let existing = match &workspace {
+        Some(store) if store.tables().await?.iter().any(|table| table == "ItemTable") => {
+            store
+                .get_keys(&[&config.composer_data_key, &config.prompts_key, &config.generations_key])
+                .await?
+        }
+        _ => Default::default(),
+    };
+    let mut existing_conversations = HashSet::new();
+    if let Some(store) = &global {
+        if store.tables().await?.contains(&config.conversation_table) {
+            for session in &selected {
+                if store.get_key(&format!("composerData:{}", session.composer_id)).await?.is_some() {
+                    existing_conversations.insert(session.composer_id.clone());
+                }
+            }
+        }
+    }
+    let plan = plan_restore(
+        config,
+        &selected,
         &generations,
         &prompts,
+        args.sessions.is_empty(),
+        &existing,
+        &existing_conversations,
     )?;
     
-    // Make sure the output directory exists before we try to write to it
-    // (nothing worse than a file write error because the directory doesn't exist)
-    let output_dir = cli.output_dir.unwrap_or(config.output_dir.clone());
-    fs::create_dir_all(&output_dir)?;
+    if let Some(path) = &args.export {
+        atomic::write_atomic(path, plan.export(config)?.as_bytes())?;
+        println!("Wrote {} sessions' records to {}", plan.restored.len(), path.display());
+        return Ok(());
+    }
+    if args.dry_run || workspace.is_none() {
+        print!("{}", plan.describe());
+        if workspace.is_none() {
+            println!("Pass --workspace-db to restore into a workspace, or --export to write the records to a file");
+        }
+        return Ok(());
+    }
     
-    // Finally, write our beautiful markdown file to disk
-    let output_file = cli.output_file.unwrap_or(config.output_filename.clone());
-    let output_path = Path::new(&output_dir).join(&output_file);
-    fs::write(&output_path, markdown_content)?;
+    // Keep a copy of each target as it was, in case Cursor doesn't take to the result
+    for target in args.workspace_db.iter().chain(&args.global_db) {
+        let copy = PathBuf::from(format!("{}.before-restore", target.display()));
+        fs::copy(target, &copy).with_context(|| format!("Could not back up {}", target.display()))?;
+        println!("Saved a copy of {} as {}", target.display(), copy.display());
+    }
+    if let Some(store) = &workspace {
+        store.put_records(&plan.workspace).await?;
+    }
+    match &global {
+        Some(store) => store.put_records(&plan.conversations).await?,
+        None if !plan.conversations.is_empty() => {
+            eprintln!("Warning: transcripts weren't restored - pass --global-db to restore them too");
+        }
+        None => {}
+    }
+    println!(
+        "Restored {} sessions ({} already there)",
+        plan.restored.len(),
+        plan.already_there.len()
+    );
+    Ok(())
+}
+
+/// Write a config file for one of Cursor's workspaces: the one given with
+/// --workspace, the one picked from a list, or (without a terminal to show
+/// the list on) the one that has the current directory open.
+fn init(cli: &Cli, storage: Option<&Path>, workspace: Option<&str>, force: bool, no_gitignore: bool) -> Result<()> {
+    let config_path = std::path::absolute(&cli.config)?;
+    let term = Term::stderr();
+    if config_path.exists() && !force {
+        let overwrite = term.is_term()
+            && Confirm::new()
+                .with_prompt(format!("{} already exists - overwrite it?", config_path.display()))
+                .default(false)
+                .interact_on(&term)?;
+        if !overwrite {
+            bail!("{} already exists; pass --force to overwrite it", config_path.display());
+        }
+    }
     
-    // Success! Let the user know we're done and where to find their file
-    println!("Chat history consolidated successfully!");
-    println!("Output file: {}", output_path.display());
+    let defaults = Config::default();
+    let storage = match storage {
+        Some(storage) => PathBuf::from(shellexpand::tilde(&storage.to_string_lossy()).to_string()),
+        None => init::cursor_user_dir()?,
+    };
+    let workspace_storage = storage.join("workspaceStorage");
+    let workspaces = init::find_workspaces(&workspace_storage, &defaults.db_filename)?;
+    if workspaces.is_empty() {
+        bail!("No Cursor workspaces with chat history in {}", workspace_storage.display());
+    }
+    let here = std::env::current_dir()?;
+    let chosen = match workspace {
+        Some(wanted) => {
+            let folder = std::path::absolute(shellexpand::tilde(wanted).to_string())?;
+            workspaces
+                .iter()
+                .find(|candidate| candidate.id == wanted || candidate.folder.as_deref() == Some(folder.as_path()))
+                .ok_or_else(|| anyhow!("No workspace in {} has the ID or folder {}", workspace_storage.display(), wanted))?
+        }
+        None if term.is_term() => {
+            let zone = DisplayZone::Local;
+            let items: Vec<String> = workspaces
+                .iter()
+                .map(|candidate| {
+                    let used = candidate
+                        .last_used
+                        .map(|time| zone.format(chrono::DateTime::<Utc>::from(time), "%Y-%m-%d"))
+                        .unwrap_or_default();
+                    format!("{} (last used {}, {})", candidate.label(), used, candidate.id)
+                })
+                .collect();
+            let current = workspaces.iter().position(|candidate| candidate.contains(&here)).unwrap_or(0);
+            let picked = Select::new()
+                .with_prompt("Which workspace is this project? (enter to pick, esc to cancel)")
+                .items(&items)
+                .default(current)
+                .max_length(20)
+                .interact_on_opt(&term)?;
+            let Some(picked) = picked else {
+                bail!("Cancelled - nothing was written");
+            };
+            &workspaces[picked]
+        }
+        None => workspaces.iter().find(|candidate| candidate.contains(&here)).ok_or_else(|| {
+            anyhow!(
+                "No Cursor workspace has {} open; pass --workspace with one of: {}",
+                here.display(),
+                workspaces.iter().take(5).map(|candidate| candidate.id.as_str()).collect::<Vec<_>>().join(", ")
+            )
+        })?,
+    };
+    
+    let folder = chosen.folder.clone().unwrap_or_else(|| here.clone());
+    let folder_text = folder.to_string_lossy().to_string();
+    let output_dir = cli.output_dir.clone().unwrap_or(defaults.output_dir);
+    let mut settings = init::InitSettings::default()
+        .set("SOURCE", "cursor")
+        .set("DB_PATH", init::tilde_path(&workspace_storage))
+        .set("GLOBAL_DB_PATH", init::tilde_path(&storage.join("globalStorage").join(&defaults.db_filename)))
+        .set("WORKSPACE_ID", &chosen.id)
+        .set("PROJECT_NAME", folder.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default())
+        .set("PROJECT_PATH", &folder_text);
+    if let Some(branch) = chat_history_consolidator::git::current_branch(&folder_text) {
+        settings = settings.set("PROJECT_BRANCH", branch);
+    }
+    settings = settings.set("OUTPUT_DIR", &output_dir);
+    let header = format!(
+        "Written by `chat-history-consolidator init` for {}\nAnything not set here has its default (see the README)",
+        chosen.label()
+    );
+    if let Some(parent) = config_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    atomic::write_atomic(&config_path, settings.to_env(&header).as_bytes())?;
+    println!("Wrote {} for workspace {} ({})", config_path.display(), chosen.id, chosen.label());
+    
+    // The lore is written next to the config file; keep it out of git
+    // unless it's somewhere else altogether
+    let config_dir = config_path.parent().unwrap_or(Path::new("."));
+    let inside = Path::new(&output_dir).is_relative() && !output_dir.starts_with("..");
+    if !no_gitignore && inside && init::add_to_gitignore(config_dir, &output_dir)? {
+        println!("Added {} to {}", output_dir, config_dir.join(".gitignore").display());
+    }
+    println!("Run chat-history-consolidator in {} to write the lore", config_dir.display());
+    Ok(())
+}
+
+/// Rename the settings in the config file to their LORE_ names, and say
+/// which ones the environment has under plain names.
+fn migrate_env(cli: &Cli, dry_run: bool) -> Result<()> {
+    let names = Config::setting_names();
+    let path = Path::new(&cli.config);
+    match fs::read_to_string(path) {
+        Ok(text) => {
+            let migration = namespace::migrate(&text, &names);
+            if migration.renamed.is_empty() {
+                println!("{} has no settings to rename", path.display());
+            } else {
+                for name in &migration.renamed {
+                    println!("{} -> {}{}", name, ENV_PREFIX, name);
+                }
+                if dry_run {
+                    println!("Would rename {} settings in {}", migration.renamed.len(), path.display());
+                } else {
+                    atomic::replace(path, migration.text.as_bytes(), true)?;
+                    println!(
+                        "Renamed {} settings in {} (the old file is {})",
+                        migration.renamed.len(),
+                        path.display(),
+                        atomic::backup_path(path).display()
+                    );
+                }
+            }
+        }
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => println!("There's no {} to migrate", path.display()),
+        Err(error) => return Err(error).with_context(|| format!("Could not read {}", path.display())),
+    }
+    
+    // The environment is the shell's to change, so only say what to rename
+    let from_env = |name: &str| std::env::var(name).ok();
+    let plain = namespace::unprefixed(&from_env, &names);
+    if !plain.is_empty() {
+        println!("Set in the environment without the prefix (rename them where they're set):");
+        for name in plain {
+            println!("  {} -> {}{}", name, ENV_PREFIX, name);
+        }
+    }
+    Ok(())
+}
+
+/// Load the config file and apply the command-line overrides on top.
+fn load_config(cli: &Cli) -> Result<Config> {
+    let defaults = cli.cargo.as_ref().map(CargoProject::settings).unwrap_or_default();
+    with_cli_settings(
+        cli,
+        Config::load_with_profile(&cli.config, cli.profile.as_deref(), &cli.settings.values, &defaults)?,
+    )
+}
+
+/// Apply the settings given on the command line to `config`, and check
+/// the result.
+fn with_cli_settings(cli: &Cli, mut config: Config) -> Result<Config> {
+    if let Some(format) = cli.format.clone() {
+        config.output_format = format;
+    }
+    if let Some(source) = cli.source.clone() {
+        config.source = source;
+    }
+    if let Some(timezone) = cli.timezone.clone() {
+        config.timezone = timezone;
+    }
+    if let Some(group_by) = cli.group_by.clone() {
+        config.group_by = group_by;
+    }
+    if let Some(sort) = cli.sort.clone() {
+        config.sort_by = sort;
+    }
+    if let Some(order) = cli.order.clone() {
+        config.sort_order = order;
+    }
+    if let Some(timeline) = cli.timeline.clone() {
+        config.timeline = timeline;
+    }
+    if let Some(graph) = cli.graph.clone() {
+        config.graph_format = graph;
+    }
+    if cli.file_lore {
+        config.file_lore = true;
+    }
+    if cli.collapse_transcripts {
+        config.collapse_transcripts = true;
+    }
+    if let Some(level) = cli.heading_level {
+        config.heading_level = level;
+    }
+    if cli.lore_index {
+        config.lore_index = true;
+    }
+    if cli.strict {
+        config.strict = true;
+    }
+    let append = |setting: &mut String, values: &[String]| {
+        let values = std::iter::once(&*setting).chain(values).filter(|value| !value.trim().is_empty());
+        *setting = values.cloned().collect::<Vec<_>>().join(",");
+    };
+    append(&mut config.exclude_sessions, &cli.exclude);
+    append(&mut config.include_tags, &cli.tags);
+    append(&mut config.exclude_tags, &cli.exclude_tag);
+    if cli.backup {
+        config.backup = true;
+    }
+    if let Some(state_dir) = cli.state_dir.clone() {
+        config.state_dir = state_dir;
+    }
+    if let Some(cache_dir) = cli.cache_dir.clone() {
+        config.cache_dir = cache_dir;
+    }
+    config.validate()?;
+    MarkdownGenerator::new(&config).check_sections()?;
+    Ok(config)
+}
+
+/// Consolidate every project in a batch manifest, one after another, and
+/// sum up how each went. A project failing doesn't stop the others unless
+/// `fail_fast` is set; `--report-json` gets every project's report.
+async fn batch(cli: &Cli, manifest: &Path, fail_fast: bool) -> Result<i32> {
+    if cli.output_dir.is_some() || cli.output_file.is_some() {
+        return Err(anyhow!("batch takes output directories from the manifest, not --output-dir or --output-file"))
+            .context(Failure::Config);
+    }
+    let progress_path = BatchProgress::path(&state_dirs::state_home().context(Failure::Config)?, manifest);
+    let manifest = BatchManifest::load(manifest).context(Failure::Config)?;
+    let configs = (|| -> Result<Vec<(String, Config)>> {
+        manifest
+            .configs(&cli.config, cli.profile.as_deref(), &cli.settings.values)?
+            .into_iter()
+            .map(|(name, config)| {
+                let config = with_cli_settings(cli, config).with_context(|| format!("Project '{}' is not set up right", name))?;
+                Ok((name, config))
+            })
+            .collect()
+    })()
+    .context(Failure::Config)?;
+    
+    let total = configs.len();
+    let zone = configs.first().map(|(_, config)| config.display_zone()).unwrap_or_default();
+    let terminal = configs.first().map(|(_, config)| config.terminal()).unwrap_or_else(Terminal::plain);
+    let mut batch = BatchReport::start();
     
+    // Which projects are done is saved after each one, for --resume
+    let mut progress = match (BatchProgress::load(&progress_path).context(Failure::Config)?, resume_mode(cli)) {
+        (Some(progress), ResumeMode::Resume) => progress,
+        (Some(progress), ResumeMode::Ask) => {
+            return Err(anyhow!(
+                "A batch started {} was interrupted; pass --resume to skip the projects it finished, or --restart to start over",
+                progress.started_at.format("%Y-%m-%d %H:%M UTC")
+            ))
+            .context(Failure::Config)
+        }
+        _ => BatchProgress::start(),
+    };
+    progress.save(&progress_path).context(Failure::Output)?;
+    let mut indexed: Vec<ProjectActivity> = progress.indexed.clone();
+    let mut failed: Vec<String> = Vec::new();
+    for (name, config) in configs {
+        if let Some(entry) = progress.finished.iter().find(|entry| entry.project == name) {
+            println!("[{}] already done", name);
+            batch.add(&name, entry.report.clone());
+            continue;
+        }
+        println!("[{}]", name);
+        let mut report = RunReport::start(&config.source);
+        let outcome = async {
+            let _lock = lock_run(cli, &config).await?;
+            consolidate(cli, &config, &mut report).await
+        }
+        .await;
+        let result = match outcome {
+            Ok(outcome) => {
+                if let Some(lore) = outcome.written.into_iter().next() {
+                    indexed.push(ProjectActivity { project: name.clone(), lore, sessions: outcome.activity });
+                }
+                Ok(())
+            }
+            Err(error) => {
+                eprintln!("Error: {:#}", error);
+                failed.push(name.clone());
+                Err(error)
+            }
+        };
+        report.finish(&result);
+        if result.is_ok() {
+            progress.finish(&name, &report, indexed.last().filter(|activity| activity.project == name));
+            if let Err(error) = progress.save(&progress_path) {
+                eprintln!("Warning: {:#}", error);
+            }
+        }
+        batch.add(&name, report);
+        if result.is_err() && fail_fast {
+            break;
+        }
+    }
+    
+    // One page to start from, with the busiest projects first
+    if let Some(path) = manifest.index_path().filter(|_| !indexed.is_empty()) {
+        let dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        let document = index_document(&indexed, &failed, &dir, &zone, Utc::now().timestamp_millis());
+        let written = (|| -> Result<()> {
+            fs::create_dir_all(&dir)?;
+            atomic::write_atomic(&path, OutputFormat::Markdown.render(&document)?.as_bytes())?;
+            Ok(())
+        })()
+        .with_context(|| format!("Could not write the index to {}", path.display()));
+        match written {
+            Ok(()) => batch.index = Some(path),
+            Err(error) => eprintln!("Warning: {:#}", error),
+        }
+    }
+    
+    let code = batch.finish(total - batch.projects.len());
+    if batch.skipped == 0 {
+        if let Err(error) = fs::remove_file(&progress_path) {
+            eprintln!("Warning: couldn't remove {}: {}", progress_path.display(), error);
+        }
+    }
+    print!("{}", batch.summary(&terminal));
+    if let Some(path) = &cli.report_json {
+        if let Err(error) = batch.write(path) {
+            eprintln!("Warning: {:#}", error);
+        }
+    }
+    Ok(code)
+}
+
+/// Run extractions on a schedule until interrupted. The config file is
+/// read again before every run, so changes take effect without a restart.
+/// Each run takes a lock in the state directory (so a manual run and a
+/// scheduled one never overlap) and is recorded in the run history there.
+async fn daemon(cli: &Cli, schedule: Option<String>) -> Result<()> {
+    loop {
+        let config = load_config(cli)?;
+        let expression = schedule.clone().unwrap_or(config.schedule.clone());
+        if expression.trim().is_empty() {
+            bail!("The daemon needs a schedule: set SCHEDULE or pass --schedule");
+        }
+        let zone = config.display_zone();
+        let now = Utc::now();
+        let next = Schedule::parse(&expression)?
+            .next_after(now, &zone)
+            .ok_or_else(|| anyhow!("The schedule '{}' never runs", expression))?;
+        println!("Next run at {}", zone.format(next, "%Y-%m-%d %H:%M %Z"));
+        tokio::time::sleep((next - now).to_std().unwrap_or_default()).await;
+        
+        let dirs = dirs(cli, &config)?;
+        fs::create_dir_all(&dirs.state)?;
+        let started_at = Utc::now();
+        let record = match RunLock::acquire(&dirs.state.join(LOCK_FILE))? {
+            None => {
+                eprintln!("Skipping this run - another one is still going");
+                RunRecord::skipped(started_at, "another run held the lock")
+            }
+            Some(_lock) => {
+                let mut report = RunReport::start(&config.source);
+                let (record, result) = match consolidate(cli, &config, &mut report).await {
+                    Ok(outcome) => (RunRecord::succeeded(started_at, outcome.sessions), Ok(())),
+                    Err(error) => {
+                        eprintln!("Scheduled run failed: {:#}", error);
+                        // Nobody's watching a daemon's terminal, so say so where people are
+                        if !config.notify_webhook.trim().is_empty() {
+                            let message = format!("Scheduled lore run for {} failed: {:#}", config.project_name, error);
+                            if let Err(error) = notify::send_message(config.notify_webhook.trim(), &message).await {
+                                eprintln!("Warning: couldn't report the failure: {:#}", error);
+                            }
+                        }
+                        (RunRecord::failed(started_at, &error), Err(error))
+                    }
+                };
+                // Each run's report replaces the previous one; the error's
+                // already been dealt with above
+                let _ = finish_report(cli, &mut report, result);
+                record
+            }
+        };
+        append_history(&dirs.state.join(RUN_HISTORY_FILE), &record)?;
+    }
+}
+
+/// Write a synthetic history into new Cursor-style databases under `dir`,
+/// and say how to point a run at them.
+async fn synthetic(config: &Config, dir: &Path, history: &SyntheticHistory) -> Result<()> {
+    let databases = history.write_databases(config, dir).await?;
+    
+    println!(
+        "Wrote {} sessions ({} messages) to {}",
+        history.sessions,
+        history.sessions * history.messages,
+        dir.display()
+    );
+    println!("Profile a run over them with:");
+    println!(
+        "  SOURCE=cursor DB_PATH={} WORKSPACE_ID=synthetic GLOBAL_DB_PATH={} chat-history-consolidator --profile-run",
+        databases.db_path.display(),
+        databases.global_db.display()
+    );
     Ok(())
+}
+
+/// Run a `snapshots` subcommand.
+fn snapshots(store: &SnapshotStore, action: &SnapshotAction, zone: &DisplayZone, terminal: &Terminal) -> Result<()> {
+    match action {
+        SnapshotAction::List => {
+            let entries = store.list()?;
+            if entries.is_empty() {
+                println!("No snapshots in {} yet (turn SNAPSHOTS on to keep them)", store.path().display());
+            }
+            let mut table = Table::new(&["id", "taken", "source", "sessions", "messages", "bytes"])
+                .align(3, Align::Right)
+                .align(4, Align::Right)
+                .align(5, Align::Right);
+            for entry in &entries {
+                table.row(vec![
+                    terminal.paint(Tone::Muted, entry.short_id()),
+                    zone.format(entry.taken_at, "%Y-%m-%d %H:%M"),
+                    entry.source.clone(),
+                    entry.sessions.to_string(),
+                    entry.messages.to_string(),
+                    entry.bytes.to_string(),
+                ]);
+            }
+            if !table.is_empty() {
+                print!("{}", table.render(terminal));
+            }
+        }
+        SnapshotAction::Show { id, json } => {
+            let entry = store.find(id)?;
+            let dataset = store.load(&entry)?;
+            if *json {
+                println!("{}", serde_json::to_string_pretty(&dataset)?);
+                return Ok(());
+            }
+            println!("Snapshot {}", entry.id);
+            println!("Taken:   {}", zone.format(entry.taken_at, "%Y-%m-%d %H:%M:%S %Z"));
+            println!("Source:  {}", entry.source);
+            println!(
+                "Holds:   {} sessions, {} generations, {} prompts",
+                entry.sessions,
+                dataset.generations.len(),
+                dataset.prompts.len()
+            );
+            for session in dataset.sessions.iter().flat_map(|data| &data.all_composers) {
+                let created = zone.format_millis(session.created_at, "%Y-%m-%d").unwrap_or_default();
+                println!("  {}  {} ({} messages)", created, session.name, session.messages.len());
+            }
+        }
+        SnapshotAction::Diff { from, to } => {
+            let older = store.load(&store.find(from)?)?;
+            let newer = store.load(&store.find(to)?)?;
+            for line in SnapshotDiff::between(&older, &newer).describe().lines() {
+                println!("{}", terminal.diff_line(line));
+            }
+        }
+        SnapshotAction::Prune { keep } => {
+            let pruned = store.prune(*keep)?;
+            println!("Removed {} runs and {} unused snapshots", pruned.runs, pruned.blobs);
+        }
+    }
+    Ok(())
+}
+
+/// Post a digest of the sessions no earlier digest mentioned to the
+/// NOTIFY_WEBHOOK, if one is set. The lore has already been written by
+/// now, so a failed post is only a warning - though it does make the run
+/// partial.
+async fn send_digest(
+    config: &Config,
+    sessions: &[ComposerData],
+    state_dir: &Path,
+    lore_url: Option<String>,
+    verbose: bool,
+    report: &mut RunReport,
+) {
+    if config.notify_webhook.trim().is_empty() {
+        return;
+    }
+    
+    let started = Instant::now();
+    let strings = Strings::for_locale(&config.locale).unwrap_or_default();
+    let state_path = state_dir.join(NOTIFY_STATE_FILE);
+    let result: Result<bool> = async {
+        let mut state = NotifyState::load(&state_path)?;
+        let links = lore_url
+            .into_iter()
+            .chain(Some(config.notify_lore_url.trim().to_string()).filter(|url| !url.is_empty()))
+            .map(|url| (strings.get("notify.read").to_string(), url))
+            .take(1)
+            .collect();
+        let digest = Digest::build(&config.project_name, sessions, &state, links);
+        if digest.is_empty() {
+            return Ok(false);
+        }
+        
+        notify::send(config.notify_webhook.trim(), &digest, &strings).await?;
+        fs::create_dir_all(state_dir)?;
+        state.announced.extend(digest.new_sessions.into_iter().map(|(id, _)| id));
+        state.save(&state_path)?;
+        Ok(true)
+    }
+    .await;
+    
+    match result {
+        Ok(true) => println!("Posted a digest of new sessions"),
+        Ok(false) if verbose => println!("No new sessions to post a digest about"),
+        Ok(false) => {}
+        Err(error) => {
+            eprintln!("Warning: couldn't post the digest: {:#}", error);
+            report.fail_after_output(format!("Couldn't post the digest: {:#}", error));
+        }
+    }
+    report.stage("notify", started);
 }
\ No newline at end of file
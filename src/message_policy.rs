@@ -0,0 +1,114 @@
+use anyhow::{anyhow, bail, Result};
+
+use crate::fences::{parse_fence, Fence};
+
+/// How many lines a message can have before `truncate` and `summarize`
+/// do anything, unless the policy says otherwise.
+pub const DEFAULT_MAX_LINES: usize = 40;
+
+/// What to do with a long message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MessagePolicy {
+    /// Show all of it
+    #[default]
+    Full,
+    /// Show the first lines, with the rest collapsed behind "more lines"
+    Truncate(usize),
+    /// Show a summary, with the message collapsed under it
+    Summarize(usize),
+}
+
+impl MessagePolicy {
+    /// A policy by name: `full`, `truncate`, `summarize`, or either of the
+    /// last two with the line limit after a colon, like `truncate:20`.
+    pub fn parse(spec: &str) -> Result<Self> {
+        let spec = spec.trim().to_lowercase();
+        let (name, lines) = match spec.split_once(':') {
+            Some((name, lines)) => {
+                let lines: usize = lines
+                    .trim()
+                    .parse()
+                    .map_err(|_| anyhow!("'{}' should give a number of lines after the colon", spec))?;
+                if lines == 0 {
+                    bail!("'{}' should keep at least one line", spec);
+                }
+                (name.trim(), Some(lines))
+            }
+            None => (spec.as_str(), None),
+        };
+        match (name, lines) {
+            ("full", None) => Ok(MessagePolicy::Full),
+            ("truncate", lines) => Ok(MessagePolicy::Truncate(lines.unwrap_or(DEFAULT_MAX_LINES))),
+            ("summarize", lines) => Ok(MessagePolicy::Summarize(lines.unwrap_or(DEFAULT_MAX_LINES))),
+            _ => bail!("Unknown message policy '{}' (expected full, truncate[:lines] or summarize[:lines])", spec),
+        }
+    }
+}
+
+/// The message policy for each section: one for all of them, and any
+/// sections that do it differently.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MessagePolicies {
+    /// What sections not named below do
+    pub default: MessagePolicy,
+    /// Sections with a policy of their own, by name
+    pub sections: Vec<(String, MessagePolicy)>,
+}
+
+impl MessagePolicies {
+    /// Parse a MESSAGE_POLICY setting like `truncate:40,current-session=full`:
+    /// a policy on its own is the default, and `section=policy` sets one
+    /// section's.
+    pub fn parse(spec: &str) -> Result<Self> {
+        let mut policies = MessagePolicies::default();
+        for entry in spec.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+            match entry.split_once('=') {
+                Some((section, policy)) => {
+                    let section = section.trim().to_lowercase();
+                    if section.is_empty() {
+                        bail!("Message policy '{}' should look like section=policy", entry);
+                    }
+                    policies.sections.retain(|(known, _)| *known != section);
+                    policies.sections.push((section, MessagePolicy::parse(policy)?));
+                }
+                None => policies.default = MessagePolicy::parse(entry)?,
+            }
+        }
+        Ok(policies)
+    }
+
+    /// The policy for the section called `name`.
+    pub fn for_section(&self, name: &str) -> MessagePolicy {
+        self.sections
+            .iter()
+            .find(|(section, _)| section.eq_ignore_ascii_case(name))
+            .map_or(self.default, |(_, policy)| *policy)
+    }
+}
+
+/// Split a message after its first `lines` lines, if it has more. A code
+/// fence open at the cut is closed at the end of the first part and opened
+/// again at the start of the rest, so both render as code. Returns the two
+/// parts and how many lines the second has.
+pub fn split_message_lines(text: &str, lines: usize) -> Option<(String, String, usize)> {
+    let all: Vec<&str> = text.trim().lines().collect();
+    if all.len() <= lines {
+        return None;
+    }
+
+    let mut fence: Option<Fence> = None;
+    for line in &all[..lines] {
+        match (&fence, parse_fence(line)) {
+            (Some(open), Some(found)) if found.closes(open) => fence = None,
+            (None, Some(found)) => fence = Some(found),
+            _ => {}
+        }
+    }
+    let mut head = all[..lines].join("\n");
+    let mut rest = all[lines..].join("\n");
+    if let Some(open) = fence {
+        head.push_str(&format!("\n{}", open.markers()));
+        rest = format!("{}{}\n{}", open.markers(), open.info, rest);
+    }
+    Some((head, rest, all.len() - lines))
+}
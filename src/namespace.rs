@@ -0,0 +1,66 @@
+/// What every setting can be prefixed with, in the environment and in
+/// config files, to keep it apart from other tools' variables:
+/// `LORE_OUTPUT_DIR` is `OUTPUT_DIR`, and wins over it.
+pub const ENV_PREFIX: &str = "LORE_";
+
+/// Look a setting up in `source` under its prefixed name first, then as it is.
+pub fn namespaced(source: &impl Fn(&str) -> Option<String>, name: &str) -> Option<String> {
+    source(&format!("{}{}", ENV_PREFIX, name)).or_else(|| source(name))
+}
+
+/// The settings of `names` that `source` has under both names, with
+/// different values - the prefixed one wins, which might not be what was
+/// meant.
+pub fn conflicts(source: &impl Fn(&str) -> Option<String>, names: &[String]) -> Vec<String> {
+    names
+        .iter()
+        .filter(|name| match (source(&format!("{}{}", ENV_PREFIX, name)), source(name)) {
+            (Some(prefixed), Some(plain)) => prefixed != plain,
+            _ => false,
+        })
+        .cloned()
+        .collect()
+}
+
+/// The settings of `names` that `source` has only under their plain names.
+pub fn unprefixed(source: &impl Fn(&str) -> Option<String>, names: &[String]) -> Vec<String> {
+    names
+        .iter()
+        .filter(|name| source(name).is_some() && source(&format!("{}{}", ENV_PREFIX, name)).is_none())
+        .cloned()
+        .collect()
+}
+
+/// A config file with its settings renamed to their prefixed names.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Migration {
+    /// The file's new text; comments, blank lines and profiles are kept
+    pub text: String,
+    /// The settings that were renamed, in the order they appear
+    pub renamed: Vec<String>,
+}
+
+/// Rename the settings of `names` in a config file's text to their
+/// prefixed names, `OUTPUT_DIR=.knowledge` becoming
+/// `LORE_OUTPUT_DIR=.knowledge` (and `export OUTPUT_DIR=...` keeping its
+/// `export`). Anything else in the file is left as it is.
+pub fn migrate(text: &str, names: &[String]) -> Migration {
+    let mut migration = Migration::default();
+    for line in text.split_inclusive('\n') {
+        let indent = &line[..line.len() - line.trim_start().len()];
+        let rest = &line[indent.len()..];
+        let (export, rest) = match rest.strip_prefix("export ") {
+            Some(rest) => ("export ", rest.trim_start()),
+            None => ("", rest),
+        };
+        let name_end = rest.find(|c: char| c == '=' || c.is_whitespace()).unwrap_or(rest.len());
+        let (name, after) = rest.split_at(name_end);
+        if after.trim_start().starts_with('=') && names.iter().any(|known| known == name) {
+            migration.text.push_str(&format!("{}{}{}{}{}", indent, export, ENV_PREFIX, name, after));
+            migration.renamed.push(name.to_string());
+        } else {
+            migration.text.push_str(line);
+        }
+    }
+    migration
+}
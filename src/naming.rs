@@ -0,0 +1,96 @@
+use anyhow::{anyhow, bail, Result};
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Utc};
+
+use crate::time::DisplayZone;
+
+/// The placeholders OUTPUT_DIR and OUTPUT_FILENAME can use.
+pub const PLACEHOLDERS: &[&str] = &["project", "branch", "source", "date", "date:<format>"];
+
+/// How `{date}` is written when no format is given.
+pub const DEFAULT_DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// What the placeholders in an output name stand for on one run.
+#[derive(Debug, Clone)]
+pub struct NameValues<'a> {
+    /// `{project}`: PROJECT_NAME
+    pub project: &'a str,
+    /// `{branch}`: PROJECT_BRANCH
+    pub branch: &'a str,
+    /// `{source}`: SOURCE
+    pub source: &'a str,
+    /// The zone `{date}` is written in
+    pub zone: DisplayZone,
+    /// When the run started; `{date}` is this, so every file a run writes
+    /// gets the same date even if it runs past midnight
+    pub now: DateTime<Utc>,
+}
+
+/// Whether `template` has any placeholders in it.
+pub fn has_placeholders(template: &str) -> bool {
+    template.replace("{{", "").replace("}}", "").contains('{')
+}
+
+/// Fill in the placeholders in an output file or directory name, like
+/// `chat-history-{project}-{date:%Y-%m}.md`. `{{` and `}}` stand for
+/// literal braces. Values are made safe for a file name (a branch called
+/// `feature/login` becomes `feature-login`), but a date format may use `/`
+/// on purpose, to put each month in a directory of its own.
+pub fn expand(template: &str, values: &NameValues) -> Result<String> {
+    let mut expanded = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find(['{', '}']) {
+        expanded.push_str(&rest[..start]);
+        let brace = rest[start..].chars().next().unwrap_or_default();
+        if rest[start + 1..].starts_with(brace) {
+            expanded.push(brace);
+            rest = &rest[start + 2..];
+            continue;
+        }
+        if brace == '}' {
+            bail!("Unmatched '}}' in '{}' (write '}}}}' for a literal brace)", template);
+        }
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| anyhow!("Unclosed '{{' in '{}' (write '{{{{' for a literal brace)", template))?;
+        expanded.push_str(&placeholder(&rest[start + 1..start + end], values)?);
+        rest = &rest[start + end + 1..];
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+/// The value of one placeholder (what's between the braces).
+fn placeholder(name: &str, values: &NameValues) -> Result<String> {
+    let (name, format) = match name.split_once(':') {
+        Some((name, format)) => (name.trim(), Some(format)),
+        None => (name.trim(), None),
+    };
+    match (name, format) {
+        ("project", None) => Ok(file_safe(values.project)),
+        ("branch", None) => Ok(file_safe(values.branch)),
+        ("source", None) => Ok(file_safe(values.source)),
+        ("date", format) => {
+            let format = format.unwrap_or(DEFAULT_DATE_FORMAT);
+            if format.is_empty() || StrftimeItems::new(format).any(|item| item == Item::Error) {
+                bail!("'{}' is not a date format (try %Y-%m-%d)", format);
+            }
+            Ok(values.zone.format(values.now, format))
+        }
+        _ => bail!("Unknown placeholder '{{{}}}' (expected {})", name, PLACEHOLDERS.join(", ")),
+    }
+}
+
+/// `value` with path separators and characters file systems reject
+/// turned into `-`.
+fn file_safe(value: &str) -> String {
+    let safe: String = value
+        .trim()
+        .chars()
+        .map(|c| if c.is_control() || "/\\:*?\"<>|".contains(c) { '-' } else { c })
+        .collect();
+    match safe.as_str() {
+        "" | "." | ".." => "-".to_string(),
+        _ => safe,
+    }
+}
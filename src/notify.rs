@@ -0,0 +1,205 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::cmp::Reverse;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+
+use crate::atomic::write_atomic;
+use crate::grouping::{group_by_key, session_topic};
+use crate::i18n::Strings;
+use crate::publish::check;
+use crate::{ChatSession, ComposerData};
+
+/// Where the list of announced sessions is kept, in the state directory.
+pub const NOTIFY_STATE_FILE: &str = ".lore-notify.json";
+
+/// How many new sessions a digest names before summing up the rest.
+const LISTED_SESSIONS: usize = 10;
+
+/// How many topics a digest mentions.
+const LISTED_TOPICS: usize = 3;
+
+/// Discord rejects messages longer than this.
+const DISCORD_LIMIT: usize = 2000;
+
+/// The chat services a digest can be posted to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WebhookKind {
+    /// A Slack incoming webhook (Mattermost and Rocket.Chat take the same payload)
+    Slack,
+    /// A Discord channel webhook
+    Discord,
+}
+
+impl WebhookKind {
+    /// Tell which service a webhook URL belongs to. Discord's are easy to
+    /// spot; anything else gets Slack's payload, which most other chat
+    /// tools accept too.
+    pub fn detect(url: &str) -> Self {
+        let host = url
+            .split("://")
+            .nth(1)
+            .and_then(|rest| rest.split('/').next())
+            .unwrap_or_default();
+        if host == "discord.com" || host.ends_with(".discord.com") || host == "discordapp.com" {
+            WebhookKind::Discord
+        } else {
+            WebhookKind::Slack
+        }
+    }
+}
+
+/// The sessions earlier digests already announced, so each digest only
+/// mentions what's new. Kept next to the output file.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NotifyState {
+    /// IDs of every session that's been in a digest
+    pub announced: BTreeSet<String>,
+}
+
+impl NotifyState {
+    /// Load the state, or start fresh if there isn't any yet.
+    pub fn load(path: &Path) -> Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(text) => serde_json::from_str(&text).with_context(|| format!("Could not read {}", path.display())),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(NotifyState::default()),
+            Err(error) => Err(error).with_context(|| format!("Could not read {}", path.display())),
+        }
+    }
+
+    /// Save the state for the next run.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        write_atomic(path, serde_json::to_string_pretty(self)?.as_bytes())
+    }
+}
+
+/// A short summary of what happened since the last digest: the new
+/// sessions, what they were mostly about, and where to read more.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Digest {
+    /// The project the sessions belong to
+    pub project: String,
+    /// The new sessions, newest first, as (ID, name)
+    pub new_sessions: Vec<(String, String)>,
+    /// The most common topics among the new sessions, with how many each
+    pub topics: Vec<(String, usize)>,
+    /// Links to the lore, as (label, URL)
+    pub links: Vec<(String, String)>,
+}
+
+impl Digest {
+    /// Put together a digest of the sessions `state` hasn't announced yet.
+    pub fn build(project: &str, sessions: &[ComposerData], state: &NotifyState, links: Vec<(String, String)>) -> Self {
+        let mut new: Vec<&ChatSession> = sessions
+            .iter()
+            .flat_map(|data| &data.all_composers)
+            .filter(|session| !state.announced.contains(&session.composer_id))
+            .collect();
+        new.sort_by_key(|session| Reverse(session.created_at));
+
+        let mut topics: Vec<(String, usize)> = group_by_key(new.clone(), |session| session_topic(session))
+            .into_iter()
+            .map(|(topic, members)| (topic, members.len()))
+            .collect();
+        topics.sort_by_key(|(_, count)| Reverse(*count));
+        topics.truncate(LISTED_TOPICS);
+
+        Digest {
+            project: project.to_string(),
+            new_sessions: new
+                .iter()
+                .map(|session| (session.composer_id.clone(), session.name.clone()))
+                .collect(),
+            topics,
+            links,
+        }
+    }
+
+    /// Whether there's anything to announce.
+    pub fn is_empty(&self) -> bool {
+        self.new_sessions.is_empty()
+    }
+
+    /// The digest as a message, in the service's flavour of markdown.
+    pub fn text(&self, kind: WebhookKind, strings: &Strings) -> String {
+        let bold = |text: &str| match kind {
+            WebhookKind::Slack => format!("*{}*", text),
+            WebhookKind::Discord => format!("**{}**", text),
+        };
+        let link = |label: &str, url: &str| match kind {
+            WebhookKind::Slack => format!("<{}|{}>", url, label),
+            WebhookKind::Discord => format!("[{}]({})", label, url),
+        };
+
+        let mut lines = vec![strings.format(
+            "notify.new_sessions",
+            &[
+                ("project", &bold(&self.project)),
+                ("sessions", &strings.count("count.sessions", self.new_sessions.len())),
+            ],
+        )];
+        for (_, name) in self.new_sessions.iter().take(LISTED_SESSIONS) {
+            lines.push(format!("• {}", name));
+        }
+        if self.new_sessions.len() > LISTED_SESSIONS {
+            let more = (self.new_sessions.len() - LISTED_SESSIONS).to_string();
+            lines.push(strings.format("notify.more", &[("count", &more)]));
+        }
+        if !self.topics.is_empty() {
+            let topics: Vec<String> = self
+                .topics
+                .iter()
+                .map(|(topic, count)| format!("{} ({})", topic, count))
+                .collect();
+            lines.push(format!("{}: {}", bold(strings.get("notify.topics")), topics.join(", ")));
+        }
+        for (label, url) in &self.links {
+            lines.push(link(label, url));
+        }
+        lines.join("\n")
+    }
+
+    /// The JSON body to post to the webhook.
+    pub fn payload(&self, kind: WebhookKind, strings: &Strings) -> Value {
+        let text = self.text(kind, strings);
+        match kind {
+            WebhookKind::Slack => json!({ "text": text }),
+            WebhookKind::Discord => {
+                let content: String = if text.chars().count() > DISCORD_LIMIT {
+                    text.chars().take(DISCORD_LIMIT - 1).chain(['…']).collect()
+                } else {
+                    text
+                };
+                json!({ "content": content })
+            }
+        }
+    }
+}
+
+/// Post a digest to a Slack or Discord webhook.
+pub async fn send(webhook: &str, digest: &Digest, strings: &Strings) -> Result<()> {
+    post(webhook, &digest.payload(WebhookKind::detect(webhook), strings)).await
+}
+
+/// Post a plain message (like a failed scheduled run) to a Slack or
+/// Discord webhook.
+pub async fn send_message(webhook: &str, text: &str) -> Result<()> {
+    let payload = match WebhookKind::detect(webhook) {
+        WebhookKind::Slack => json!({ "text": text }),
+        WebhookKind::Discord => json!({ "content": text.chars().take(DISCORD_LIMIT).collect::<String>() }),
+    };
+    post(webhook, &payload).await
+}
+
+async fn post(webhook: &str, payload: &Value) -> Result<()> {
+    let response = reqwest::Client::new()
+        .post(webhook)
+        .json(payload)
+        .send()
+        .await
+        .context("Could not reach the notification webhook")?;
+    check(response, "post to the notification webhook").await?;
+    Ok(())
+}
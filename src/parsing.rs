@@ -0,0 +1,156 @@
+use anyhow::{anyhow, Context, Result};
+use serde::de::{DeserializeOwned, Deserializer, SeqAccess, Visitor};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
+use std::io::{BufReader, Read};
+use std::marker::PhantomData;
+
+/// A record we had to leave out because it didn't have the shape we expected.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SkippedRecord {
+    /// What kind of record it was ("generation", "prompt", "session", ...)
+    pub kind: String,
+    /// Where it was: its position in the array, or its key for per-key records
+    pub location: String,
+    /// Why it didn't parse
+    pub error: String,
+}
+
+/// The records that parsed, plus the ones that didn't.
+#[derive(Debug)]
+pub struct Parsed<T> {
+    /// Everything that deserialized cleanly
+    pub records: Vec<T>,
+    /// Everything we skipped, and why
+    pub skipped: Vec<SkippedRecord>,
+}
+
+/// Parse a JSON array one element at a time.
+/// One odd record (a field Cursor renamed, a null where we expect text)
+/// shouldn't cost us the whole history, so bad elements are skipped and
+/// reported. The text still has to be a JSON array - if it isn't, the
+/// record is genuinely corrupt and that's an error.
+pub fn parse_array<T: DeserializeOwned>(json_str: &str, kind: &str) -> Result<Parsed<T>> {
+    parse_array_with(json_str, kind, |_| {})
+}
+
+/// Like `parse_array`, but lets the caller adjust each element first
+/// (for example to bring old field names up to date).
+pub fn parse_array_with<T: DeserializeOwned>(
+    json_str: &str,
+    kind: &str,
+    prepare: impl Fn(&mut Value),
+) -> Result<Parsed<T>> {
+    let value: Value = serde_json::from_str(json_str).with_context(|| format!("Invalid JSON in the {} records", kind))?;
+    match value {
+        Value::Array(mut items) => {
+            items.iter_mut().for_each(prepare);
+            Ok(parse_values(items, kind))
+        }
+        other => Err(anyhow!("Expected a list of {} records, found {}", kind, describe(&other))),
+    }
+}
+
+/// Deserialize already-parsed JSON values one by one, keeping the good ones.
+pub fn parse_values<T: DeserializeOwned>(items: Vec<Value>, kind: &str) -> Parsed<T> {
+    let mut parsed = Parsed {
+        records: Vec::new(),
+        skipped: Vec::new(),
+    };
+
+    for (index, item) in items.into_iter().enumerate() {
+        parsed.push(index, item, kind);
+    }
+
+    parsed
+}
+
+/// Like `parse_array_with`, but reading the JSON as it arrives: only one
+/// element is held as a `Value` at a time, so an array of hundreds of MB
+/// never has to be in memory as text or as one big `Value`.
+pub fn parse_array_reader<T: DeserializeOwned>(
+    reader: impl Read,
+    kind: &str,
+    prepare: impl Fn(&mut Value),
+) -> Result<Parsed<T>> {
+    let mut deserializer = serde_json::Deserializer::from_reader(BufReader::new(reader));
+    let visitor = ArrayVisitor { kind, prepare, records: PhantomData };
+    let parsed = deserializer
+        .deserialize_any(visitor)
+        .and_then(|parsed| deserializer.end().map(|()| parsed))
+        .with_context(|| format!("Invalid JSON in the {} records", kind))?;
+    Ok(parsed)
+}
+
+impl<T: DeserializeOwned> Parsed<T> {
+    /// Keep the element at `index` if it deserializes, or note why it didn't.
+    fn push(&mut self, index: usize, item: Value, kind: &str) {
+        match serde_json::from_value(item) {
+            Ok(record) => self.records.push(record),
+            Err(error) => self.skipped.push(SkippedRecord {
+                kind: kind.to_string(),
+                location: format!("#{}", index),
+                error: error.to_string(),
+            }),
+        }
+    }
+}
+
+/// Walks a JSON array for `parse_array_reader`, an element at a time.
+struct ArrayVisitor<'a, T, F> {
+    kind: &'a str,
+    prepare: F,
+    records: PhantomData<T>,
+}
+
+impl<'de, T: DeserializeOwned, F: Fn(&mut Value)> Visitor<'de> for ArrayVisitor<'_, T, F> {
+    type Value = Parsed<T>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "a list of {} records", self.kind)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut items: A) -> std::result::Result<Parsed<T>, A::Error> {
+        let mut parsed = Parsed { records: Vec::new(), skipped: Vec::new() };
+        let mut index = 0;
+        while let Some(mut item) = items.next_element::<Value>()? {
+            (self.prepare)(&mut item);
+            parsed.push(index, item, self.kind);
+            index += 1;
+        }
+        Ok(parsed)
+    }
+}
+
+/// A one-line summary of what was skipped, like
+/// "Skipped 3 malformed records (2 generation, 1 prompt)".
+pub fn summarize_skipped(skipped: &[SkippedRecord]) -> String {
+    let mut counts: Vec<(&str, usize)> = Vec::new();
+    for record in skipped {
+        match counts.iter_mut().find(|(kind, _)| *kind == record.kind) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((&record.kind, 1)),
+        }
+    }
+
+    let breakdown: Vec<String> = counts.iter().map(|(kind, count)| format!("{} {}", count, kind)).collect();
+    format!(
+        "Skipped {} malformed record{} ({})",
+        skipped.len(),
+        if skipped.len() == 1 { "" } else { "s" },
+        breakdown.join(", ")
+    )
+}
+
+/// Describe what kind of JSON value we got, for error messages.
+fn describe(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "a list",
+        Value::Object(_) => "an object",
+    }
+}
@@ -0,0 +1,429 @@
+use anyhow::{anyhow, bail, Context, Result};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::ffi::OsStr;
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::config::Config;
+use crate::document::{markdown_blocks, Block};
+use crate::importers::ImportedHistory;
+use crate::sections::{Section, SectionContext};
+use crate::timestamps::normalize_sessions;
+use crate::{ChatGeneration, ChatPrompt, ChatSession, ComposerData};
+
+/// The version of the plugin protocol this build speaks. A plugin answers
+/// the handshake with the version it speaks, and one that doesn't match is
+/// turned away before it's asked for anything.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// What a plugin's executable is called, before its name:
+/// `lore-plugin-jira` is the `jira` plugin.
+pub const PLUGIN_PREFIX: &str = "lore-plugin-";
+
+/// How SOURCE names a source plugin: `plugin:jira`.
+pub const PLUGIN_SOURCE_PREFIX: &str = "plugin:";
+
+/// What a plugin can do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Capability {
+    /// Import sessions from a chat tool the core doesn't know (SOURCE=plugin:<name>)
+    Source,
+    /// Turn the sessions into files of its own (RENDER_PLUGINS)
+    Renderer,
+    /// Add a section to the lore (ANALYZER_PLUGINS)
+    Analyzer,
+}
+
+impl Capability {
+    /// The capability's name, as the protocol spells it.
+    pub fn name(self) -> &'static str {
+        match self {
+            Capability::Source => "source",
+            Capability::Renderer => "renderer",
+            Capability::Analyzer => "analyzer",
+        }
+    }
+}
+
+/// A plugin's answer to the handshake: who it is and what it can do.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Handshake {
+    /// The protocol version the plugin speaks
+    pub protocol: u32,
+    /// The plugin's name
+    pub name: String,
+    /// The plugin's own version
+    #[serde(default)]
+    pub version: String,
+    /// What it can be asked to do
+    #[serde(default)]
+    pub capabilities: Vec<Capability>,
+    /// A line about what it's for
+    #[serde(default)]
+    pub description: String,
+}
+
+/// One file a renderer plugin made.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RenderedFile {
+    /// Where it goes, relative to the output directory
+    pub path: String,
+    /// What's in it
+    pub content: String,
+}
+
+/// An analyzer plugin's section.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+struct Analysis {
+    #[serde(default)]
+    title: String,
+    #[serde(default)]
+    markdown: String,
+}
+
+/// A source plugin's sessions.
+#[derive(Debug, Deserialize)]
+struct Import {
+    #[serde(default)]
+    sessions: Vec<ChatSession>,
+}
+
+/// A renderer plugin's files.
+#[derive(Debug, Deserialize)]
+struct Render {
+    #[serde(default)]
+    files: Vec<RenderedFile>,
+}
+
+/// A `lore-plugin-<name>` executable, which the tool talks to over its
+/// stdin and stdout, one JSON object per line.
+///
+/// Each use starts it afresh. The tool sends a handshake
+/// (`{"type":"handshake","protocol":1,...}`) and the plugin answers with
+/// a `Handshake`. Then comes one request - `import`, `render` or
+/// `analyze` - and stdin is closed; the plugin answers with one line and
+/// exits. An answer of `{"error":"..."}` fails the request with that
+/// message. Anything the plugin writes to stderr is passed through.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Plugin {
+    /// The plugin's name, from its executable's
+    pub name: String,
+    /// The executable
+    pub path: PathBuf,
+}
+
+impl Plugin {
+    /// The plugin at `path`, named after its executable.
+    pub fn at(path: &Path) -> Result<Self> {
+        let name = plugin_name(path).ok_or_else(|| anyhow!("{} isn't called {}<name>", path.display(), PLUGIN_PREFIX))?;
+        Ok(Plugin { name, path: path.to_path_buf() })
+    }
+
+    /// The plugin called `name`, from the first directory on PATH that
+    /// has it.
+    pub fn find(name: &str) -> Result<Self> {
+        validate_name(name)?;
+        discover()
+            .into_iter()
+            .find(|plugin| plugin.name == name)
+            .ok_or_else(|| anyhow!("No plugin called '{}' ({}{} isn't on PATH)", name, PLUGIN_PREFIX, name))
+    }
+
+    /// Start the plugin just to shake hands, to see what it is.
+    pub fn describe(&self) -> Result<Handshake> {
+        let mut child = self.spawn()?;
+        let mut stdout = BufReader::new(child.stdout.take().context("The plugin has no stdout")?);
+        let handshake = self.handshake(child.stdin.as_mut().context("The plugin has no stdin")?, &mut stdout);
+        // Closing stdin tells it there's nothing more to do
+        drop(child.stdin.take());
+        let _ = child.kill();
+        let _ = child.wait();
+        handshake
+    }
+
+    /// Import sessions with a source plugin, for SOURCE=plugin:<name>.
+    pub fn import(&self, config: &Config) -> Result<ImportedHistory> {
+        let request = json!({
+            "type": "import",
+            "project_name": config.project_name,
+            "project_path": config.project_path,
+        });
+        let mut import: Import = self.request(Capability::Source, &request)?;
+        normalize_sessions(&mut import.sessions, config.timestamp_unit());
+        Ok(ImportedHistory::from_sessions(import.sessions, &format!("{}{}", PLUGIN_SOURCE_PREFIX, self.name)))
+    }
+
+    /// Have a renderer plugin make files of the sessions, to go in the
+    /// output directory.
+    pub fn render(
+        &self,
+        config: &Config,
+        sessions: &[ComposerData],
+        generations: &[ChatGeneration],
+        prompts: &[ChatPrompt],
+    ) -> Result<Vec<RenderedFile>> {
+        let request = json!({
+            "type": "render",
+            "project_name": config.project_name,
+            "output_filename": config.output_filename,
+            "sessions": sessions,
+            "generations": generations,
+            "prompts": prompts,
+        });
+        let render: Render = self.request(Capability::Renderer, &request)?;
+        for file in &render.files {
+            if !is_relative_inside(&file.path) {
+                bail!("Plugin '{}' wanted to write {}, outside the output directory", self.name, file.path);
+            }
+        }
+        Ok(render.files)
+    }
+
+    /// Have an analyzer plugin write a section about the sessions: its
+    /// title and its blocks, or `None` if it had nothing to say.
+    pub fn analyze(
+        &self,
+        config: &Config,
+        sessions: &[ComposerData],
+        generations: &[ChatGeneration],
+        prompts: &[ChatPrompt],
+    ) -> Result<Option<(String, Vec<Block>)>> {
+        let request = json!({
+            "type": "analyze",
+            "project_name": config.project_name,
+            "locale": config.locale,
+            "sessions": sessions,
+            "generations": generations,
+            "prompts": prompts,
+        });
+        let analysis: Analysis = self.request(Capability::Analyzer, &request)?;
+        let blocks = markdown_blocks(&analysis.markdown);
+        if blocks.is_empty() {
+            return Ok(None);
+        }
+        let title = Some(analysis.title.trim()).filter(|title| !title.is_empty()).unwrap_or(&self.name).to_string();
+        Ok(Some((title, blocks)))
+    }
+
+    fn spawn(&self) -> Result<std::process::Child> {
+        Command::new(&self.path)
+            .env("LORE_PLUGIN_PROTOCOL", PROTOCOL_VERSION.to_string())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .with_context(|| format!("Couldn't start plugin '{}' ({})", self.name, self.path.display()))
+    }
+
+    fn handshake(&self, stdin: &mut impl Write, stdout: &mut impl BufRead) -> Result<Handshake> {
+        let hello = json!({
+            "type": "handshake",
+            "protocol": PROTOCOL_VERSION,
+            "client": env!("CARGO_PKG_NAME"),
+            "version": env!("CARGO_PKG_VERSION"),
+        });
+        writeln!(stdin, "{}", hello)
+            .and_then(|_| stdin.flush())
+            .with_context(|| format!("Plugin '{}' didn't take the handshake", self.name))?;
+        let mut line = String::new();
+        stdout
+            .read_line(&mut line)
+            .with_context(|| format!("Plugin '{}' didn't answer the handshake", self.name))?;
+        let handshake: Handshake = self.answer(&line).context("The handshake failed")?;
+        if handshake.protocol != PROTOCOL_VERSION {
+            bail!(
+                "Plugin '{}' speaks protocol version {}, but this version of the tool speaks {}",
+                self.name,
+                handshake.protocol,
+                PROTOCOL_VERSION
+            );
+        }
+        Ok(handshake)
+    }
+
+    /// Shake hands, check the plugin can do what's wanted, and send it
+    /// one request.
+    fn request<T: DeserializeOwned>(&self, capability: Capability, request: &Value) -> Result<T> {
+        let mut child = self.spawn()?;
+        let mut stdin = child.stdin.take().context("The plugin has no stdin")?;
+        let mut stdout = BufReader::new(child.stdout.take().context("The plugin has no stdout")?);
+
+        let handshake = match self.handshake(&mut stdin, &mut stdout) {
+            Ok(handshake) => handshake,
+            Err(error) => {
+                drop(stdin);
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(error);
+            }
+        };
+        if !handshake.capabilities.contains(&capability) {
+            drop(stdin);
+            let _ = child.kill();
+            let _ = child.wait();
+            bail!("Plugin '{}' doesn't offer the {} capability", self.name, capability.name());
+        }
+
+        // The request can be big, so it's written while the answer is read
+        let request = request.to_string();
+        let writer = std::thread::spawn(move || -> std::io::Result<()> {
+            writeln!(stdin, "{}", request)?;
+            stdin.flush()
+        });
+        let mut answer = String::new();
+        stdout
+            .read_to_string(&mut answer)
+            .with_context(|| format!("Couldn't read plugin '{}''s answer", self.name))?;
+        let status = child.wait()?;
+        let written = writer.join().map_err(|_| anyhow!("Couldn't send plugin '{}' its request", self.name))?;
+
+        let line = answer.lines().find(|line| !line.trim().is_empty()).unwrap_or_default();
+        if line.is_empty() {
+            written.with_context(|| format!("Couldn't send plugin '{}' its request", self.name))?;
+            bail!("Plugin '{}' gave no answer ({})", self.name, status);
+        }
+        self.answer(line)
+    }
+
+    /// Parse a line the plugin answered with, turning `{"error": ...}`
+    /// into an error.
+    fn answer<T: DeserializeOwned>(&self, line: &str) -> Result<T> {
+        let value: Value = serde_json::from_str(line.trim())
+            .with_context(|| format!("Plugin '{}' answered with something that isn't JSON", self.name))?;
+        if let Some(error) = value.get("error") {
+            let message = error.as_str().map(str::to_string).unwrap_or_else(|| error.to_string());
+            bail!("Plugin '{}' failed: {}", self.name, message);
+        }
+        serde_json::from_value(value).with_context(|| format!("Plugin '{}' answered with something unexpected", self.name))
+    }
+}
+
+/// An analyzer plugin's section, named after the plugin so SECTIONS can
+/// place it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PluginSection {
+    name: String,
+}
+
+impl PluginSection {
+    /// The section the analyzer plugin called `name` writes.
+    pub fn new(name: impl Into<String>) -> Self {
+        PluginSection { name: name.into() }
+    }
+}
+
+impl Section for PluginSection {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn render(&self, context: &SectionContext<'_>, emit: &mut dyn FnMut(Vec<Block>) -> Result<()>) -> Result<()> {
+        let config = context.config;
+        let plugin = Plugin::find(&self.name)?;
+        let Some((title, blocks)) = plugin.analyze(config, context.sessions, context.generations, context.prompts)? else {
+            return Ok(());
+        };
+
+        // A plugin's section is held to the same rules as the transcripts
+        let clean = |text: &str| config.sanitize_path(&context.generator.redact(text));
+        let mut section = vec![Block::heading(2, clean(&title))];
+        section.extend(blocks.into_iter().map(|block| match block {
+            Block::Heading { level, text, .. } => Block::heading(level.max(3), clean(&text)),
+            Block::Paragraph(text) => Block::Paragraph(clean(&text)),
+            Block::List { ordered, items } => Block::List { ordered, items: items.iter().map(|item| clean(item)).collect() },
+            Block::Code { language, text } => Block::Code { language, text: clean(&text) },
+            other => other,
+        }));
+        emit(section)
+    }
+}
+
+/// Every plugin on PATH, by name. Where two directories have one with the
+/// same name, the first wins, as it would for a command.
+pub fn discover() -> Vec<Plugin> {
+    discover_in(&std::env::var_os("PATH").unwrap_or_default())
+}
+
+/// Every plugin in a PATH-style list of directories, by name.
+pub fn discover_in(path: &OsStr) -> Vec<Plugin> {
+    let mut plugins: Vec<Plugin> = Vec::new();
+    for dir in std::env::split_paths(path) {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        let mut found: Vec<Plugin> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| is_executable(path))
+            .filter_map(|path| Plugin::at(&path).ok())
+            .collect();
+        found.sort_by(|a, b| a.name.cmp(&b.name));
+        for plugin in found {
+            if !plugins.iter().any(|known| known.name == plugin.name) {
+                plugins.push(plugin);
+            }
+        }
+    }
+    plugins.sort_by(|a, b| a.name.cmp(&b.name));
+    plugins
+}
+
+/// The source plugin SOURCE names, if it names one.
+pub fn source_plugin(source: &str) -> Option<&str> {
+    source.strip_prefix(PLUGIN_SOURCE_PREFIX)
+}
+
+/// Parse a list of plugin names like ANALYZER_PLUGINS, separated by commas.
+pub fn parse_names(list: &str) -> Result<Vec<String>> {
+    let mut names: Vec<String> = Vec::new();
+    for name in list.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+        validate_name(name)?;
+        if !names.iter().any(|known| known == name) {
+            names.push(name.to_string());
+        }
+    }
+    Ok(names)
+}
+
+/// Check a plugin name is one an executable could be called.
+pub fn validate_name(name: &str) -> Result<()> {
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+        bail!("'{}' can't be a plugin name (letters, digits, _ and - only)", name);
+    }
+    Ok(())
+}
+
+/// The plugin name in an executable's file name, if it's a plugin's.
+fn plugin_name(path: &Path) -> Option<String> {
+    let file_name = path.file_name()?.to_str()?;
+    let name = if cfg!(windows) {
+        Path::new(file_name).file_stem()?.to_str()?
+    } else {
+        file_name
+    };
+    let name = name.strip_prefix(PLUGIN_PREFIX)?;
+    validate_name(name).ok()?;
+    Some(name.to_string())
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    fs::metadata(path).is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    let extension = path.extension().and_then(OsStr::to_str).unwrap_or_default().to_lowercase();
+    path.is_file() && matches!(extension.as_str(), "exe" | "cmd" | "bat")
+}
+
+/// Whether a path a plugin gave stays inside the directory it's relative to.
+fn is_relative_inside(path: &str) -> bool {
+    let path = Path::new(path);
+    !path.as_os_str().is_empty() && path.components().all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+}
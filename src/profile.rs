@@ -0,0 +1,98 @@
+use anyhow::{bail, Result};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+
+/// What a profile's section header starts with: `[profile.work]`.
+pub const PROFILE_SECTION: &str = "profile.";
+
+/// A config file's settings: the ones at the top, which every run gets,
+/// and the named profiles after them, like
+///
+/// ```text
+/// PROJECT_NAME=api
+/// INCLUDE_SECRETS=false
+///
+/// [profile.work]
+/// WORKSPACE_ID=a29d4797...
+/// OUTPUT_DIR=~/work/lore
+///
+/// [profile.oss]
+/// SOURCE=continue
+/// REDACT_HOSTS=build-box
+/// ```
+///
+/// `--profile work` layers the work profile's settings over the top ones.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfigFile {
+    /// The settings before the first profile
+    pub base: HashMap<String, String>,
+    /// Each profile's settings, by name
+    pub profiles: BTreeMap<String, HashMap<String, String>>,
+}
+
+impl ConfigFile {
+    /// Read a config file. A missing (or unreadable) file has no settings,
+    /// so everything comes from the environment and the defaults.
+    pub fn read(path: &str) -> Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(text) => ConfigFile::parse(&text).map_err(|error| error.context(format!("{} is not a config file", path))),
+            Err(_) => Ok(ConfigFile::default()),
+        }
+    }
+
+    /// Parse a config file's text: `NAME=value` lines as in a `.env`
+    /// file, with `[profile.<name>]` starting each profile's settings.
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut file = ConfigFile::default();
+        let mut section: Option<String> = None;
+        let mut lines = String::new();
+        for line in text.lines() {
+            let trimmed = line.trim();
+            let Some(header) = trimmed.strip_prefix('[').and_then(|rest| rest.strip_suffix(']')) else {
+                lines.push_str(line);
+                lines.push('\n');
+                continue;
+            };
+            let name = match header.trim().strip_prefix(PROFILE_SECTION).map(str::trim) {
+                Some(name) if !name.is_empty() => name.to_string(),
+                _ => bail!("Unknown section '{}' (expected [{}<name>])", trimmed, PROFILE_SECTION),
+            };
+            file.add(section.replace(name), &std::mem::take(&mut lines));
+        }
+        file.add(section, &lines);
+        Ok(file)
+    }
+
+    /// Add the settings in `lines` to a profile's (or the top ones, for
+    /// `None`). Lines that aren't settings are skipped, as they always were.
+    fn add(&mut self, section: Option<String>, lines: &str) {
+        let settings = dotenvy::from_read_iter(lines.as_bytes()).filter_map(|entry| entry.ok());
+        match section {
+            Some(name) => self.profiles.entry(name).or_default().extend(settings),
+            None => self.base.extend(settings),
+        }
+    }
+
+    /// The settings a run with `profile` gets: the profile's over the top
+    /// ones. Fails if the file has no such profile.
+    pub fn settings(&self, profile: Option<&str>) -> Result<HashMap<String, String>> {
+        let mut settings = self.base.clone();
+        if let Some(name) = profile {
+            match self.profiles.get(name.trim()) {
+                Some(overrides) => settings.extend(overrides.clone()),
+                None if self.profiles.is_empty() => bail!("There's no profile '{}': the config file has no profiles", name.trim()),
+                None => bail!(
+                    "There's no profile '{}' (the config file has {})",
+                    name.trim(),
+                    self.profile_names().join(", ")
+                ),
+            }
+        }
+        Ok(settings)
+    }
+
+    /// The names of the file's profiles, in order.
+    pub fn profile_names(&self) -> Vec<&str> {
+        self.profiles.keys().map(String::as_str).collect()
+    }
+}
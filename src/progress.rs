@@ -0,0 +1,148 @@
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// What a long-running operation is busy with, for a progress bar's label.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Stage {
+    /// Reading each session's transcript out of the database; counted in
+    /// sessions
+    Transcripts,
+    /// Reading the generation and prompt lists; counted in lists
+    Records,
+    /// Rendering the sessions into the lore; counted in sessions
+    Rendering,
+    /// Building the lore index (LORE_INDEX); counted in sessions
+    Indexing,
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Stage::Transcripts => "Reading transcripts",
+            Stage::Records => "Reading generations and prompts",
+            Stage::Rendering => "Rendering sessions",
+            Stage::Indexing => "Indexing the lore",
+        })
+    }
+}
+
+/// Told how a long-running operation is getting on, to drive a progress
+/// bar in a GUI or TUI. Each stage is started, advanced any number of
+/// times, and finished, on whichever thread the operation runs on. All
+/// three do nothing unless implemented.
+pub trait ProgressReporter: Send + Sync {
+    /// `stage` started, with `total` steps to go if that's known.
+    fn start(&self, _stage: Stage, _total: Option<usize>) {}
+
+    /// `done` steps of `stage` are finished so far.
+    fn advance(&self, _stage: Stage, _done: usize) {}
+
+    /// `stage` is finished.
+    fn finish(&self, _stage: Stage) {}
+}
+
+/// Asks a long-running operation to stop. Clones share one flag, so keep
+/// one and hand another to the operation; cancelling it makes the
+/// operation give up with a `Cancelled` error at the next step, leaving
+/// nothing half-written behind.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    /// A token that hasn't been cancelled.
+    pub fn new() -> Self {
+        CancellationToken::default()
+    }
+
+    /// Ask whatever holds a clone of this token to stop.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    /// Whether `cancel` has been called on this token or a clone of it.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+/// The error an operation gives up with when its token is cancelled. Find
+/// it with `error.downcast_ref::<Cancelled>()` to tell a cancellation from
+/// a failure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// A reporter and a cancellation token, carried together through an
+/// operation. The default reports to nobody and is never cancelled.
+#[derive(Clone, Default)]
+pub struct Progress {
+    reporter: Option<Arc<dyn ProgressReporter>>,
+    cancellation: CancellationToken,
+}
+
+impl Progress {
+    /// Report to nobody, and never be cancelled.
+    pub fn new() -> Self {
+        Progress::default()
+    }
+
+    /// Report to `reporter`.
+    pub fn with_reporter(mut self, reporter: Arc<dyn ProgressReporter>) -> Self {
+        self.reporter = Some(reporter);
+        self
+    }
+
+    /// Stop when `token` is cancelled.
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = token;
+        self
+    }
+
+    /// Report that `stage` started.
+    pub fn start(&self, stage: Stage, total: Option<usize>) {
+        if let Some(reporter) = &self.reporter {
+            reporter.start(stage, total);
+        }
+    }
+
+    /// Report how much of `stage` is done.
+    pub fn advance(&self, stage: Stage, done: usize) {
+        if let Some(reporter) = &self.reporter {
+            reporter.advance(stage, done);
+        }
+    }
+
+    /// Report that `stage` is finished.
+    pub fn finish(&self, stage: Stage) {
+        if let Some(reporter) = &self.reporter {
+            reporter.finish(stage);
+        }
+    }
+
+    /// Give up with `Cancelled` if the token's been cancelled.
+    pub fn check(&self) -> Result<(), Cancelled> {
+        if self.cancellation.is_cancelled() {
+            return Err(Cancelled);
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Progress")
+            .field("reporting", &self.reporter.is_some())
+            .field("cancellation", &self.cancellation)
+            .finish()
+    }
+}
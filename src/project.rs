@@ -0,0 +1,515 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::git::current_branch;
+
+/// Directories that hold dependencies, build output or tooling rather
+/// than the project's own code.
+const SKIPPED_DIRS: &[&str] = &["target", "node_modules", "dist", "build", "out", "vendor", "__pycache__", "venv"];
+
+/// The manifests `summarize_project` reads, in the project's root.
+pub const MANIFESTS: &[&str] = &["Cargo.toml", "package.json", "pyproject.toml", "requirements.txt", "go.mod"];
+
+/// The most files looked at, so a huge checkout doesn't stall a run.
+const MAX_FILES: usize = 20_000;
+
+/// The most entries `project_tree` lists in one directory.
+const MAX_TREE_ENTRIES: usize = 20;
+
+/// Languages by file extension.
+const LANGUAGES: &[(&str, &str)] = &[
+    ("rs", "Rust"),
+    ("ts", "TypeScript"),
+    ("tsx", "TypeScript"),
+    ("js", "JavaScript"),
+    ("jsx", "JavaScript"),
+    ("mjs", "JavaScript"),
+    ("cjs", "JavaScript"),
+    ("py", "Python"),
+    ("go", "Go"),
+    ("java", "Java"),
+    ("kt", "Kotlin"),
+    ("swift", "Swift"),
+    ("c", "C"),
+    ("h", "C"),
+    ("cc", "C++"),
+    ("cpp", "C++"),
+    ("hpp", "C++"),
+    ("cs", "C#"),
+    ("rb", "Ruby"),
+    ("php", "PHP"),
+    ("scala", "Scala"),
+    ("ex", "Elixir"),
+    ("exs", "Elixir"),
+    ("sh", "Shell"),
+    ("sql", "SQL"),
+];
+
+/// The most dependencies a summary lists.
+const LISTED_DEPENDENCIES: usize = 10;
+
+/// Frameworks and notable libraries, by the dependency that brings them
+/// in. Go modules match by prefix, so `github.com/gin-gonic/gin/v2` is Gin too.
+const FRAMEWORKS: &[(&str, &str)] = &[
+    ("tokio", "Tokio"),
+    ("axum", "Axum"),
+    ("actix-web", "Actix Web"),
+    ("rocket", "Rocket"),
+    ("warp", "warp"),
+    ("tauri", "Tauri"),
+    ("bevy", "Bevy"),
+    ("leptos", "Leptos"),
+    ("yew", "Yew"),
+    ("diesel", "Diesel"),
+    ("sqlx", "SQLx"),
+    ("clap", "clap"),
+    ("react", "React"),
+    ("next", "Next.js"),
+    ("vue", "Vue"),
+    ("svelte", "Svelte"),
+    ("@angular/core", "Angular"),
+    ("express", "Express"),
+    ("fastify", "Fastify"),
+    ("@nestjs/core", "NestJS"),
+    ("electron", "Electron"),
+    ("@modelcontextprotocol/sdk", "MCP SDK"),
+    ("django", "Django"),
+    ("flask", "Flask"),
+    ("fastapi", "FastAPI"),
+    ("torch", "PyTorch"),
+    ("tensorflow", "TensorFlow"),
+    ("langchain", "LangChain"),
+    ("llama-index", "LlamaIndex"),
+    ("github.com/gin-gonic/gin", "Gin"),
+    ("github.com/labstack/echo", "Echo"),
+    ("github.com/gofiber/fiber", "Fiber"),
+    ("github.com/spf13/cobra", "Cobra"),
+    ("google.golang.org/grpc", "gRPC"),
+];
+
+/// What can be told about a project from its files alone.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ProjectSummary {
+    /// The description in its manifest (Cargo.toml, package.json or pyproject.toml)
+    pub description: Option<String>,
+    /// Languages and their share of the source files, biggest first
+    pub languages: Vec<(String, f64)>,
+    /// Frameworks and notable libraries among the dependencies
+    pub frameworks: Vec<String>,
+    /// The first few runtime dependencies in the manifests
+    pub dependencies: Vec<String>,
+    /// Where the programs start: binaries, library roots, scripts
+    pub entry_points: Vec<String>,
+}
+
+impl ProjectSummary {
+    /// Whether there's nothing to say.
+    pub fn is_empty(&self) -> bool {
+        self.description.is_none()
+            && self.languages.is_empty()
+            && self.frameworks.is_empty()
+            && self.dependencies.is_empty()
+            && self.entry_points.is_empty()
+    }
+}
+
+/// Look at the project in `root`: its manifests (Cargo.toml, package.json,
+/// pyproject.toml and go.mod) for a description, dependencies and entry
+/// points, and its source files for which languages it's in. A directory
+/// that isn't there gives an empty summary.
+pub fn summarize_project(root: &Path) -> ProjectSummary {
+    let description = manifest_description(root);
+    let (dependencies, entry_points) = manifest_contents(root);
+    let frameworks = unique(
+        dependencies
+            .iter()
+            .filter_map(|dependency| {
+                FRAMEWORKS
+                    .iter()
+                    .find(|(name, _)| dependency == name || (name.contains('/') && dependency.starts_with(name)))
+            })
+            .map(|(_, framework)| framework.to_string())
+            .collect(),
+    );
+    let mut dependencies = dependencies;
+    dependencies.truncate(LISTED_DEPENDENCIES);
+
+    let mut counts: HashMap<&'static str, usize> = HashMap::new();
+    for file in project_files(root) {
+        let extension = file.rsplit_once('.').map(|(_, extension)| extension.to_lowercase());
+        if let Some((_, language)) = LANGUAGES.iter().find(|(known, _)| Some(*known) == extension.as_deref()) {
+            *counts.entry(language).or_insert(0) += 1;
+        }
+    }
+
+    let total: usize = counts.values().sum();
+    let mut languages: Vec<(String, f64)> = counts
+        .into_iter()
+        .map(|(language, count)| (language.to_string(), count as f64 * 100.0 / total as f64))
+        .collect();
+    languages.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    ProjectSummary { description, languages, frameworks, dependencies, entry_points }
+}
+
+/// The project's files, as paths relative to `root` with `/` between
+/// directories, in order. Hidden and build directories are skipped, and
+/// only the first `MAX_FILES` are listed.
+pub fn project_files(root: &Path) -> Vec<String> {
+    let mut files = Vec::new();
+    collect_files(root, "", &mut files);
+    files
+}
+
+fn collect_files(dir: &Path, prefix: &str, files: &mut Vec<String>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    let mut entries: Vec<_> = entries.flatten().collect();
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        if files.len() >= MAX_FILES {
+            return;
+        }
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if name.starts_with('.') {
+            continue;
+        }
+        let Ok(kind) = entry.file_type() else {
+            continue;
+        };
+        if kind.is_dir() {
+            if !SKIPPED_DIRS.contains(&name.as_ref()) {
+                collect_files(&entry.path(), &format!("{}{}/", prefix, name), files);
+            }
+        } else if kind.is_file() {
+            files.push(format!("{}{}", prefix, name));
+        }
+    }
+}
+
+/// The directories and files in `root`, `depth` levels deep, drawn like
+/// `tree` does (without the root itself). Directories come first; hidden
+/// and build directories are left out. `None` if there's nothing there.
+pub fn project_tree(root: &Path, depth: usize) -> Option<String> {
+    let mut tree = String::new();
+    draw_tree(root, "", depth, &mut tree);
+    (!tree.is_empty()).then_some(tree)
+}
+
+fn draw_tree(dir: &Path, indent: &str, depth: usize, tree: &mut String) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    let mut entries: Vec<(bool, String)> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let is_dir = entry.file_type().ok()?.is_dir();
+            let skipped = name.starts_with('.') || (is_dir && SKIPPED_DIRS.contains(&name.as_str()));
+            (!skipped).then_some((!is_dir, name))
+        })
+        .collect();
+    entries.sort();
+    let hidden = entries.len().saturating_sub(MAX_TREE_ENTRIES);
+    entries.truncate(MAX_TREE_ENTRIES);
+
+    for (index, (is_file, name)) in entries.iter().enumerate() {
+        let last = index + 1 == entries.len() && hidden == 0;
+        let (branch, nested) = if last { ("└── ", "    ") } else { ("├── ", "│   ") };
+        if *is_file {
+            tree.push_str(&format!("{}{}{}\n", indent, branch, name));
+        } else {
+            tree.push_str(&format!("{}{}{}/\n", indent, branch, name));
+            if depth > 1 {
+                draw_tree(&dir.join(name), &format!("{}{}", indent, nested), depth - 1, tree);
+            }
+        }
+    }
+    if hidden > 0 {
+        tree.push_str(&format!("{}└── ... ({} more)\n", indent, hidden));
+    }
+}
+
+/// The Cargo package or workspace a directory is in, which is the project
+/// `cargo lore` writes lore for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CargoProject {
+    /// The workspace root, or the package's directory outside a workspace
+    pub root: PathBuf,
+    /// The root package's name, or the root directory's for a virtual workspace
+    pub name: String,
+    /// The git branch checked out there, if it's a repository
+    pub branch: Option<String>,
+}
+
+impl CargoProject {
+    /// Find the Cargo project `dir` is in, much the way cargo does: the
+    /// nearest Cargo.toml going up, or the closest one above it with a
+    /// `[workspace]` table. `None` outside a Cargo project.
+    pub fn find(dir: &Path) -> Option<Self> {
+        let manifests: Vec<(&Path, String)> = dir
+            .ancestors()
+            .filter_map(|dir| Some((dir, fs::read_to_string(dir.join("Cargo.toml")).ok()?)))
+            .collect();
+        let (root, text) = manifests
+            .iter()
+            .find(|(_, text)| {
+                text.lines()
+                    .map(str::trim)
+                    .any(|line| line == "[workspace]" || line.starts_with("[workspace."))
+            })
+            .or_else(|| manifests.first())?;
+        let name = toml_string(&toml_entries(text), &["package"], "name")
+            .or_else(|| root.file_name().map(|name| name.to_string_lossy().into_owned()))
+            .unwrap_or_default();
+        Some(CargoProject {
+            root: root.to_path_buf(),
+            name,
+            branch: current_branch(&root.to_string_lossy()),
+        })
+    }
+
+    /// PROJECT_PATH, PROJECT_NAME and PROJECT_BRANCH for this project, as
+    /// defaults for `Config::load_with_defaults`.
+    pub fn settings(&self) -> HashMap<String, String> {
+        let mut settings = HashMap::from([
+            ("PROJECT_PATH".to_string(), self.root.to_string_lossy().into_owned()),
+            ("PROJECT_NAME".to_string(), self.name.clone()),
+        ]);
+        if let Some(branch) = &self.branch {
+            settings.insert("PROJECT_BRANCH".to_string(), branch.clone());
+        }
+        settings
+    }
+}
+
+/// The description in the project's manifest, if it has one.
+fn manifest_description(root: &Path) -> Option<String> {
+    let read = |name: &str| fs::read_to_string(root.join(name)).ok();
+    let description = read("Cargo.toml")
+        .and_then(|text| toml_string(&toml_entries(&text), &["package"], "description"))
+        .or_else(|| {
+            let package: serde_json::Value = serde_json::from_str(&read("package.json")?).ok()?;
+            package["description"].as_str().map(str::to_string)
+        })
+        .or_else(|| {
+            read("pyproject.toml")
+                .and_then(|text| toml_string(&toml_entries(&text), &["project", "tool.poetry"], "description"))
+        })?;
+    let description = description.trim();
+    (!description.is_empty()).then(|| description.to_string())
+}
+
+/// The runtime dependencies every manifest lists, in order, and the
+/// entry points they (and the usual file layouts) point to.
+fn manifest_contents(root: &Path) -> (Vec<String>, Vec<String>) {
+    let read = |name: &str| fs::read_to_string(root.join(name)).ok();
+    let exists = |path: &str| root.join(path).is_file();
+    let mut dependencies: Vec<String> = Vec::new();
+    let mut entry_points: Vec<String> = Vec::new();
+
+    if let Some(text) = read("Cargo.toml") {
+        let entries = toml_entries(&text);
+        for (table, key, _) in &entries {
+            if table == "dependencies" {
+                dependencies.push(key.clone());
+            } else if let Some(name) = table.strip_prefix("dependencies.") {
+                dependencies.push(name.to_string());
+            }
+        }
+        entry_points.extend(toml_strings(&entries, "[bin]", "path"));
+        for path in ["src/main.rs", "src/lib.rs"] {
+            if exists(path) {
+                entry_points.push(path.to_string());
+            }
+        }
+        if let Ok(bins) = fs::read_dir(root.join("src/bin")) {
+            let mut bins: Vec<String> = bins
+                .flatten()
+                .map(|entry| format!("src/bin/{}", entry.file_name().to_string_lossy()))
+                .filter(|path| path.ends_with(".rs"))
+                .collect();
+            bins.sort();
+            entry_points.extend(bins);
+        }
+    }
+
+    if let Some(package) = read("package.json").and_then(|text| serde_json::from_str::<serde_json::Value>(&text).ok()) {
+        if let Some(names) = package["dependencies"].as_object() {
+            dependencies.extend(names.keys().cloned());
+        }
+        if let Some(main) = package["main"].as_str() {
+            entry_points.push(main.to_string());
+        }
+        match &package["bin"] {
+            serde_json::Value::String(bin) => entry_points.push(bin.clone()),
+            serde_json::Value::Object(bins) => entry_points.extend(bins.values().filter_map(|bin| bin.as_str().map(str::to_string))),
+            _ => {}
+        }
+    }
+
+    if let Some(text) = read("pyproject.toml") {
+        let entries = toml_entries(&text);
+        for (table, key, value) in &entries {
+            match (table.as_str(), key.as_str()) {
+                ("project", "dependencies") => {
+                    dependencies.extend(quoted(value).iter().map(|requirement| python_package(requirement)));
+                }
+                ("tool.poetry.dependencies", name) if name != "python" => dependencies.push(name.to_string()),
+                ("project.scripts" | "tool.poetry.scripts", name) => {
+                    if let Some(target) = quoted(value).first() {
+                        entry_points.push(format!("{} ({})", name, target));
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    if exists("pyproject.toml") || exists("requirements.txt") || exists("setup.py") {
+        for path in ["manage.py", "main.py", "app.py"] {
+            if exists(path) {
+                entry_points.push(path.to_string());
+            }
+        }
+    }
+    if let Some(text) = read("requirements.txt") {
+        dependencies.extend(
+            text.lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#') && !line.starts_with('-'))
+                .map(python_package),
+        );
+    }
+
+    if let Some(text) = read("go.mod") {
+        let mut in_block = false;
+        for line in text.lines().map(str::trim) {
+            let requirement = if in_block {
+                if line == ")" {
+                    in_block = false;
+                    continue;
+                }
+                line
+            } else if line == "require (" {
+                in_block = true;
+                continue;
+            } else if let Some(requirement) = line.strip_prefix("require ") {
+                requirement
+            } else {
+                continue;
+            };
+            if let Some(module) = requirement.split_whitespace().next().filter(|_| !requirement.ends_with("// indirect")) {
+                dependencies.push(module.to_string());
+            }
+        }
+        if exists("main.go") {
+            entry_points.push("main.go".to_string());
+        }
+        if let Ok(commands) = fs::read_dir(root.join("cmd")) {
+            let mut commands: Vec<String> = commands
+                .flatten()
+                .map(|entry| format!("cmd/{}/main.go", entry.file_name().to_string_lossy()))
+                .filter(|path| exists(path))
+                .collect();
+            commands.sort();
+            entry_points.extend(commands);
+        }
+    }
+
+    (unique(dependencies), unique(entry_points))
+}
+
+/// The items without repeats, first ones first.
+fn unique(items: Vec<String>) -> Vec<String> {
+    let mut unique: Vec<String> = Vec::new();
+    for item in items {
+        if !unique.contains(&item) {
+            unique.push(item);
+        }
+    }
+    unique
+}
+
+/// A Python requirement's package name, without versions, extras or markers.
+fn python_package(requirement: &str) -> String {
+    requirement
+        .split(|c: char| "<>=!~;[ (".contains(c))
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_lowercase()
+}
+
+/// The quoted strings in a TOML value (one string, or an array of them).
+fn quoted(value: &str) -> Vec<String> {
+    let mut strings = Vec::new();
+    let mut rest = value;
+    while let Some(start) = rest.find(['"', '\'']) {
+        let quote = rest[start..].chars().next().unwrap_or('"');
+        let Some(end) = rest[start + 1..].find(quote) else {
+            break;
+        };
+        strings.push(rest[start + 1..start + 1 + end].to_string());
+        rest = &rest[start + end + 2..];
+    }
+    strings
+}
+
+/// Every `key = value` in a TOML file, with the table it's in (`[[bin]]`
+/// tables come out as `[bin]`). Arrays spanning several lines are joined
+/// into one value. Enough TOML for a manifest, without pulling in a TOML
+/// parser.
+fn toml_entries(text: &str) -> Vec<(String, String, String)> {
+    let mut entries: Vec<(String, String, String)> = Vec::new();
+    let mut table = String::new();
+    let mut open: Option<(String, String)> = None;
+    for line in text.lines().map(str::trim) {
+        if let Some((key, value)) = &mut open {
+            value.push(' ');
+            value.push_str(line);
+            if line.starts_with(']') {
+                entries.push((table.clone(), std::mem::take(key), std::mem::take(value)));
+                open = None;
+            }
+            continue;
+        }
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+            table = name.trim().to_string();
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let (key, value) = (key.trim().trim_matches('"').to_string(), value.trim().to_string());
+        if value.starts_with('[') && value.matches('[').count() > value.matches(']').count() {
+            open = Some((key, value));
+        } else {
+            entries.push((table.clone(), key, value));
+        }
+    }
+    entries
+}
+
+/// The string value of `key` in the first of `tables` that has it.
+fn toml_string(entries: &[(String, String, String)], tables: &[&str], key: &str) -> Option<String> {
+    tables.iter().find_map(|wanted| {
+        entries
+            .iter()
+            .find(|(table, name, _)| table == wanted && name == key)
+            .and_then(|(_, _, value)| quoted(value).into_iter().next())
+    })
+}
+
+/// The string values of `key` in every `table` table, like each `[[bin]]`'s path.
+fn toml_strings(entries: &[(String, String, String)], table: &str, key: &str) -> Vec<String> {
+    entries
+        .iter()
+        .filter(|(found, name, _)| found == table && name == key)
+        .filter_map(|(_, _, value)| quoted(value).into_iter().next())
+        .collect()
+}
@@ -0,0 +1,154 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+
+use crate::config::Config;
+use crate::document::Block;
+use crate::workspace::TempWorkspace;
+
+pub mod confluence;
+pub mod git;
+pub mod notion;
+
+/// Where `publish` can send the lore.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PublishTarget {
+    /// A Confluence space, through its REST API
+    Confluence,
+    /// A Notion page, through the Notion API
+    Notion,
+    /// A git repository or GitHub wiki, committed to and pushed
+    Git,
+}
+
+impl PublishTarget {
+    /// The values `publish` accepts.
+    pub const NAMES: &'static [&'static str] = &["confluence", "notion", "git"];
+
+    /// Parse a `publish` target.
+    pub fn parse(name: &str) -> Result<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "confluence" => Ok(PublishTarget::Confluence),
+            "notion" => Ok(PublishTarget::Notion),
+            "git" => Ok(PublishTarget::Git),
+            other => Err(anyhow!(
+                "Unknown publish target '{}' (expected {})",
+                other,
+                PublishTarget::NAMES.join(", ")
+            )),
+        }
+    }
+
+    /// The target's name, for messages.
+    pub fn label(&self) -> &'static str {
+        match self {
+            PublishTarget::Confluence => "Confluence",
+            PublishTarget::Notion => "Notion",
+            PublishTarget::Git => "git",
+        }
+    }
+
+    /// Connect to the target with the credentials in the config. Anything
+    /// a publisher needs on disk (like a clone of the repository) goes in
+    /// the run's temp workspace.
+    pub fn publisher(&self, config: &Config, workspace: &TempWorkspace) -> Result<Box<dyn Publisher>> {
+        Ok(match self {
+            PublishTarget::Confluence => Box::new(confluence::ConfluencePublisher::new(config)?),
+            PublishTarget::Notion => Box::new(notion::NotionPublisher::new(config)?),
+            PublishTarget::Git => Box::new(git::GitPublisher::new(config, workspace.subdir("publish")?)?),
+        })
+    }
+}
+
+/// One wiki page's worth of lore.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Page {
+    /// What identifies the page between runs: the session ID for session
+    /// pages, so republishing updates a session's page instead of adding
+    /// a second one even if its title or number changed
+    pub key: String,
+    /// The page title
+    pub title: String,
+    /// The page content
+    pub blocks: Vec<Block>,
+}
+
+/// The lore split into pages: an overview with everything but the
+/// sessions, and one page per session underneath it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PublishPlan {
+    /// The overview page, which the session pages hang off
+    pub root: Page,
+    /// A page per session, in the configured session order
+    pub sessions: Vec<Page>,
+}
+
+impl PublishPlan {
+    /// What publishing this plan would do, for `--dry-run`.
+    pub fn describe(&self, target: PublishTarget) -> String {
+        let mut description = format!(
+            "Would publish {} pages to {}:\n  {} ({} blocks)\n",
+            self.sessions.len() + 1,
+            target.label(),
+            self.root.title,
+            self.root.blocks.len()
+        );
+        for page in &self.sessions {
+            description.push_str(&format!(
+                "    {} (session {}, {} blocks)\n",
+                page.title,
+                page.key,
+                page.blocks.len()
+            ));
+        }
+        description
+    }
+}
+
+/// What happened to a page when it was published.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PublishAction {
+    /// The page didn't exist yet
+    Created,
+    /// An earlier run's page was brought up to date
+    Updated,
+}
+
+/// One page that was published.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Published {
+    /// The page title
+    pub title: String,
+    /// Whether it was new
+    pub action: PublishAction,
+    /// Where to read it, if the service told us
+    pub url: Option<String>,
+}
+
+/// Something that can put a publish plan on a wiki. Publishing is
+/// idempotent: pages from an earlier run are found again (sessions by
+/// their session ID) and updated in place.
+#[async_trait]
+pub trait Publisher: Send + Sync {
+    /// Create or update every page in the plan.
+    async fn publish(&self, plan: &PublishPlan) -> Result<Vec<Published>>;
+}
+
+/// A setting a publisher can't work without.
+pub(crate) fn required<'a>(value: &'a str, name: &str, target: PublishTarget) -> Result<&'a str> {
+    if value.trim().is_empty() {
+        Err(anyhow!("{} needs to be set to publish to {}", name, target.label()))
+    } else {
+        Ok(value.trim())
+    }
+}
+
+/// Turn a failed HTTP response into an error with the service's own
+/// explanation in it.
+pub(crate) async fn check(response: reqwest::Response, action: &str) -> Result<reqwest::Response> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let body = response.text().await.unwrap_or_default();
+    Err(anyhow!("Couldn't {} ({}): {}", action, status, body.trim()))
+}
@@ -0,0 +1,285 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use serde_json::{json, Value};
+
+use crate::config::Config;
+use crate::document::{parse_inline, split_message, Block, Inline, MessagePart};
+use crate::publish::{check, required, Page, PublishAction, PublishPlan, PublishTarget, Published, Publisher};
+
+/// Publishes to Confluence (Cloud or Data Center) through the v1 REST API.
+/// Session pages carry a `lore-session-<id>` label, which is how later
+/// runs find them again.
+pub struct ConfluencePublisher {
+    client: reqwest::Client,
+    base_url: String,
+    space: String,
+    parent_id: Option<String>,
+    user: String,
+    token: String,
+}
+
+impl ConfluencePublisher {
+    /// Set up a publisher from the `CONFLUENCE_*` settings.
+    pub fn new(config: &Config) -> Result<Self> {
+        let target = PublishTarget::Confluence;
+        Ok(ConfluencePublisher {
+            client: reqwest::Client::new(),
+            base_url: required(&config.confluence_url, "CONFLUENCE_URL", target)?
+                .trim_end_matches('/')
+                .to_string(),
+            space: required(&config.confluence_space, "CONFLUENCE_SPACE", target)?.to_string(),
+            parent_id: Some(config.confluence_parent_id.trim().to_string()).filter(|id| !id.is_empty()),
+            user: required(&config.confluence_user, "CONFLUENCE_USER", target)?.to_string(),
+            token: required(&config.confluence_token, "CONFLUENCE_TOKEN", target)?.to_string(),
+        })
+    }
+
+    async fn get(&self, path: &str, query: &[(&str, &str)]) -> Result<Value> {
+        let response = self
+            .client
+            .get(format!("{}{}", self.base_url, path))
+            .basic_auth(&self.user, Some(&self.token))
+            .query(query)
+            .send()
+            .await?;
+        Ok(check(response, "read from Confluence").await?.json().await?)
+    }
+
+    /// The first page a content query found, as (id, version).
+    fn first_page(results: &Value) -> Option<(String, u64)> {
+        let page = results["results"].as_array()?.first()?;
+        Some((page["id"].as_str()?.to_string(), page["version"]["number"].as_u64().unwrap_or(1)))
+    }
+
+    /// The overview page, found by its title in the space.
+    async fn find_by_title(&self, title: &str) -> Result<Option<(String, u64)>> {
+        let results = self
+            .get(
+                "/rest/api/content",
+                &[("spaceKey", &self.space), ("title", title), ("type", "page"), ("expand", "version")],
+            )
+            .await?;
+        Ok(Self::first_page(&results))
+    }
+
+    /// A session page, found by its label.
+    async fn find_by_label(&self, label: &str) -> Result<Option<(String, u64)>> {
+        let cql = format!("space = \"{}\" and type = page and label = \"{}\"", self.space, label);
+        let results = self.get("/rest/api/content/search", &[("cql", &cql), ("expand", "version")]).await?;
+        Ok(Self::first_page(&results))
+    }
+
+    /// Create the page, or update it if it's already there.
+    async fn put_page(
+        &self,
+        page: &Page,
+        existing: Option<(String, u64)>,
+        parent_id: Option<&str>,
+        label: Option<&str>,
+    ) -> Result<(String, Published)> {
+        let mut body = json!({
+            "type": "page",
+            "title": page.title,
+            "space": { "key": self.space },
+            "body": { "storage": { "value": storage_format(&page.blocks), "representation": "storage" } },
+        });
+        if let Some(parent_id) = parent_id {
+            body["ancestors"] = json!([{ "id": parent_id }]);
+        }
+
+        let (request, action) = match &existing {
+            Some((id, version)) => {
+                body["id"] = json!(id);
+                body["version"] = json!({ "number": version + 1 });
+                (self.client.put(format!("{}/rest/api/content/{}", self.base_url, id)), PublishAction::Updated)
+            }
+            None => {
+                if let Some(label) = label {
+                    body["metadata"] = json!({ "labels": [{ "prefix": "global", "name": label }] });
+                }
+                (self.client.post(format!("{}/rest/api/content", self.base_url)), PublishAction::Created)
+            }
+        };
+        let response = request
+            .basic_auth(&self.user, Some(&self.token))
+            .json(&body)
+            .send()
+            .await?;
+        let saved: Value = check(response, &format!("publish '{}' to Confluence", page.title))
+            .await?
+            .json()
+            .await?;
+
+        let id = saved["id"]
+            .as_str()
+            .ok_or_else(|| anyhow!("Confluence didn't return an id for '{}'", page.title))?
+            .to_string();
+        let url = match (saved["_links"]["base"].as_str(), saved["_links"]["webui"].as_str()) {
+            (Some(base), Some(webui)) => Some(format!("{}{}", base, webui)),
+            _ => None,
+        };
+        Ok((
+            id,
+            Published {
+                title: page.title.clone(),
+                action,
+                url,
+            },
+        ))
+    }
+}
+
+#[async_trait]
+impl Publisher for ConfluencePublisher {
+    async fn publish(&self, plan: &PublishPlan) -> Result<Vec<Published>> {
+        let existing = self.find_by_title(&plan.root.title).await?;
+        let (root_id, root) = self
+            .put_page(&plan.root, existing, self.parent_id.as_deref(), None)
+            .await?;
+
+        let mut published = vec![root];
+        for page in &plan.sessions {
+            let label = session_label(&page.key);
+            let existing = self.find_by_label(&label).await?;
+            let (_, session) = self.put_page(page, existing, Some(&root_id), Some(&label)).await?;
+            published.push(session);
+        }
+        Ok(published)
+    }
+}
+
+/// The label that marks a session's page. Confluence labels are lowercase
+/// and can't contain spaces or most punctuation.
+pub fn session_label(session_id: &str) -> String {
+    let id: String = session_id
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '-' })
+        .collect();
+    format!("lore-session-{}", id)
+}
+
+/// Convert blocks to Confluence's storage format (XHTML with `ac:` macros).
+/// Code goes in the code macro and collapsed content in the expand macro;
+/// links to headings become plain text, since the headings they point at
+/// may be on another page now.
+pub fn storage_format(blocks: &[Block]) -> String {
+    blocks.iter().map(storage_block).collect()
+}
+
+fn storage_block(block: &Block) -> String {
+    match block {
+        Block::Heading {
+            level, text, properties, ..
+        } => {
+            let level = (*level).clamp(1, 6);
+            let mut heading = format!("<h{}>{}</h{}>", level, inline(text), level);
+            heading.push_str(&fields(properties));
+            heading
+        }
+        Block::Paragraph(text) => format!("<p>{}</p>", inline(text)),
+        Block::Fields(values) => fields(values),
+        Block::List { ordered, items } => {
+            let tag = if *ordered { "ol" } else { "ul" };
+            let items: String = items.iter().map(|item| format!("<li>{}</li>", inline(item))).collect();
+            format!("<{}>{}</{}>", tag, items, tag)
+        }
+        Block::Code { language, text } => code_macro(language, text),
+        Block::Details { summary, blocks } => format!(
+            "<ac:structured-macro ac:name=\"expand\"><ac:parameter ac:name=\"title\">{}</ac:parameter><ac:rich-text-body>{}</ac:rich-text-body></ac:structured-macro>",
+            escape(&plain(summary)),
+            storage_format(blocks)
+        ),
+        Block::Message { speaker, sent_at, text } => {
+            let mut message = match sent_at {
+                Some(sent_at) => format!("<p><strong>{}</strong> ({}):", escape(speaker), escape(sent_at)),
+                None => format!("<p><strong>{}</strong>:", escape(speaker)),
+            };
+            let mut open = true;
+            for part in split_message(text) {
+                match part {
+                    MessagePart::Prose(prose) => {
+                        if !open {
+                            message.push_str("<p>");
+                        }
+                        message.push_str(&format!(" {}</p>", prose_lines(&prose)));
+                    }
+                    MessagePart::Code { language, text } => {
+                        if open {
+                            message.push_str("</p>");
+                        }
+                        message.push_str(&code_macro(&language, &text));
+                    }
+                }
+                open = false;
+            }
+            if open {
+                message.push_str("</p>");
+            }
+            message
+        }
+        Block::Rule => "<hr />".to_string(),
+    }
+}
+
+/// `<strong>Label</strong>: value` lines.
+fn fields(values: &[(String, String)]) -> String {
+    values
+        .iter()
+        .map(|(label, value)| format!("<p><strong>{}</strong>: {}</p>", escape(label), inline(value)))
+        .collect()
+}
+
+/// Prose from a chat message, keeping its line breaks.
+fn prose_lines(prose: &str) -> String {
+    prose.lines().map(inline).collect::<Vec<_>>().join("<br />")
+}
+
+/// The code macro. The code sits in CDATA, so the only thing that needs
+/// escaping is a `]]>` inside it.
+fn code_macro(language: &str, text: &str) -> String {
+    let language = if language.is_empty() {
+        String::new()
+    } else {
+        format!("<ac:parameter ac:name=\"language\">{}</ac:parameter>", escape(language))
+    };
+    format!(
+        "<ac:structured-macro ac:name=\"code\">{}<ac:plain-text-body><![CDATA[{}]]></ac:plain-text-body></ac:structured-macro>",
+        language,
+        text.trim_end_matches('\n').replace("]]>", "]]]]><![CDATA[>")
+    )
+}
+
+/// Inline markup as XHTML.
+fn inline(text: &str) -> String {
+    parse_inline(text)
+        .into_iter()
+        .map(|span| match span {
+            Inline::Text(text) => escape(&text),
+            Inline::Bold(text) => format!("<strong>{}</strong>", escape(&text)),
+            Inline::Italic(text) => format!("<em>{}</em>", escape(&text)),
+            Inline::Code(text) => format!("<code>{}</code>", escape(&text)),
+            Inline::Link { text, target } if target.starts_with('#') => escape(&text),
+            Inline::Link { text, target } => format!("<a href=\"{}\">{}</a>", escape(&target), escape(&text)),
+        })
+        .collect()
+}
+
+/// Inline text with its markup stripped, for macro parameters.
+fn plain(text: &str) -> String {
+    parse_inline(text)
+        .into_iter()
+        .map(|span| match span {
+            Inline::Text(text) | Inline::Bold(text) | Inline::Italic(text) | Inline::Code(text) => text,
+            Inline::Link { text, .. } => text,
+        })
+        .collect()
+}
+
+/// Escape text for XHTML.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,315 @@
+use anyhow::{anyhow, bail, Context, Result};
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
+
+use crate::atomic::write_atomic;
+use crate::config::Config;
+use crate::document::Document;
+use crate::publish::{check, required, Page, PublishAction, PublishPlan, PublishTarget, Published, Publisher};
+use crate::render::OutputFormat;
+
+/// The overview page's file name, without its extension. GitHub wikis
+/// show `Home` as their front page.
+const HOME: &str = "Home";
+
+/// Commits the lore into a git repository - a GitHub wiki, or a docs
+/// repository - and pushes it, optionally through a pull request.
+/// Each page is a file in the configured output format whose first line
+/// is a comment recording its key, so a session's file is found (and
+/// renamed, if its title changed) on every later run.
+///
+/// It runs the `git` command rather than linking libgit2 (git2), so
+/// whatever already pushes to the repository - credential helpers, the
+/// SSH agent and `~/.ssh/config`, `insteadOf` rewrites - works as it is,
+/// and the build doesn't need OpenSSL and libssh2.
+pub struct GitPublisher {
+    repo: String,
+    branch: Option<String>,
+    dir: PathBuf,
+    format: OutputFormat,
+    message: String,
+    author: Option<(String, String)>,
+    open_pr: bool,
+    github_token: String,
+    project: String,
+    checkout: PathBuf,
+}
+
+impl GitPublisher {
+    /// Set up a publisher from the `GIT_*` settings. The repository is
+    /// cloned into `work_dir`, which should be somewhere temporary.
+    pub fn new(config: &Config, work_dir: PathBuf) -> Result<Self> {
+        let format = config.output_format();
+        if matches!(format, OutputFormat::Pdf | OutputFormat::Epub | OutputFormat::Csv | OutputFormat::Parquet) {
+            bail!("Publishing to git needs a text OUTPUT_FORMAT (markdown, org, asciidoc or typst)");
+        }
+        let author = Some(config.git_author.trim())
+            .filter(|author| !author.is_empty())
+            .map(parse_author)
+            .transpose()?;
+        if config.git_open_pr && config.github_token.trim().is_empty() {
+            bail!("GITHUB_TOKEN needs to be set to open pull requests");
+        }
+
+        Ok(GitPublisher {
+            repo: required(&config.git_publish_repo, "GIT_PUBLISH_REPO", PublishTarget::Git)?.to_string(),
+            branch: Some(config.git_publish_branch.trim().to_string()).filter(|branch| !branch.is_empty()),
+            dir: publish_dir(&config.git_publish_dir)?,
+            format,
+            message: config.git_commit_message.clone(),
+            author,
+            open_pr: config.git_open_pr,
+            github_token: config.github_token.trim().to_string(),
+            project: config.project_name.clone(),
+            checkout: work_dir.join("repository"),
+        })
+    }
+
+    /// Run git in the checkout and return what it printed.
+    fn git(&self, args: &[&str]) -> Result<String> {
+        let mut command = Command::new("git");
+        command.arg("-C").arg(&self.checkout);
+        if let Some((name, email)) = &self.author {
+            command.arg("-c").arg(format!("user.name={}", name));
+            command.arg("-c").arg(format!("user.email={}", email));
+        }
+        run(command.args(args), args.first().copied().unwrap_or("git"))
+    }
+
+    /// Write the pages into the checkout, and remove the ones for sessions
+    /// that are gone (excluded since, say). Returns what happened to each
+    /// page that was written.
+    fn write_pages(&self, plan: &PublishPlan) -> Result<Vec<Published>> {
+        let dir = self.checkout.join(&self.dir);
+        fs::create_dir_all(&dir).with_context(|| format!("Could not create {}", dir.display()))?;
+        let mut existing = self.existing_pages(&dir)?;
+
+        // Every page's file is worked out first: a renamed page's old file
+        // can be the one another page is written to in this run
+        let pages: Vec<(PathBuf, &Page)> = std::iter::once((HOME.to_string(), &plan.root))
+            .chain(plan.sessions.iter().map(|page| (file_stem(&page.title), page)))
+            .map(|(stem, page)| (dir.join(format!("{}.{}", stem, self.format.extension())), page))
+            .collect();
+        let mut targets: HashMap<&Path, &str> = HashMap::new();
+        for (path, page) in &pages {
+            if let Some(other) = targets.insert(path, &page.title) {
+                bail!("'{}' and '{}' would both be published as {}", other, page.title, path.display());
+            }
+        }
+
+        let mut published = Vec::new();
+        for (path, page) in &pages {
+            let action = match existing.remove(&page.key) {
+                Some(old) => {
+                    if old != *path && !targets.contains_key(old.as_path()) {
+                        fs::remove_file(&old)?;
+                    }
+                    PublishAction::Updated
+                }
+                None => PublishAction::Created,
+            };
+            write_atomic(path, self.page_text(page)?.as_bytes())?;
+            published.push(Published {
+                title: page.title.clone(),
+                action,
+                url: None,
+            });
+        }
+
+        // What's left belongs to sessions that aren't in the lore any more
+        for old in existing.into_values() {
+            if !targets.contains_key(old.as_path()) {
+                fs::remove_file(&old)?;
+            }
+        }
+        Ok(published)
+    }
+
+    /// Files an earlier run wrote, by page key.
+    fn existing_pages(&self, dir: &Path) -> Result<HashMap<String, PathBuf>> {
+        let mut pages = HashMap::new();
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if !path.is_file() || path.extension().is_none_or(|extension| extension != self.format.extension()) {
+                continue;
+            }
+            let text = fs::read_to_string(&path).unwrap_or_default();
+            if let Some(key) = text.lines().next().and_then(|line| self.key_from_marker(line)) {
+                pages.insert(key, path);
+            }
+        }
+        Ok(pages)
+    }
+
+    /// A page in the output format, with the marker line on top.
+    fn page_text(&self, page: &Page) -> Result<String> {
+        let document = Document {
+            blocks: page.blocks.clone(),
+            ..Document::default()
+        };
+        Ok(format!("{}\n{}", self.marker(&page.key), self.format.render(&document)?))
+    }
+
+    /// The comment that records a page's key, in the format's comment syntax.
+    fn marker(&self, key: &str) -> String {
+        match self.format {
+            OutputFormat::Org => format!("# lore-page: {}", key),
+            OutputFormat::AsciiDoc | OutputFormat::Typst => format!("// lore-page: {}", key),
+            _ => format!("<!-- lore-page: {} -->", key),
+        }
+    }
+
+    fn key_from_marker(&self, line: &str) -> Option<String> {
+        let key = match self.format {
+            OutputFormat::Org => line.strip_prefix("# lore-page: "),
+            OutputFormat::AsciiDoc | OutputFormat::Typst => line.strip_prefix("// lore-page: "),
+            _ => line.strip_prefix("<!-- lore-page: ")?.strip_suffix(" -->"),
+        };
+        key.map(str::to_string)
+    }
+
+    /// The commit message, with the placeholders filled in.
+    fn commit_message(&self, plan: &PublishPlan) -> String {
+        self.message
+            .replace("{project}", &self.project)
+            .replace("{sessions}", &plan.sessions.len().to_string())
+            .replace("{date}", &chrono::Utc::now().format("%Y-%m-%d").to_string())
+    }
+
+    /// Open a pull request from `head` into `base` on GitHub.
+    async fn open_pull_request(&self, head: &str, base: &str, title: &str) -> Result<String> {
+        let (owner, name) = github_repository(&self.repo)
+            .ok_or_else(|| anyhow!("Can only open pull requests on GitHub repositories, not {}", self.repo))?;
+        let response = reqwest::Client::new()
+            .post(format!("https://api.github.com/repos/{}/{}/pulls", owner, name))
+            .bearer_auth(&self.github_token)
+            .header("Accept", "application/vnd.github+json")
+            .header("User-Agent", "persistent-code-lore")
+            .json(&json!({
+                "title": title,
+                "head": head,
+                "base": base,
+                "body": "Lore regenerated from the latest chat history.",
+            }))
+            .send()
+            .await?;
+        let pull: Value = check(response, "open a pull request").await?.json().await?;
+        pull["html_url"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| anyhow!("GitHub didn't return a link to the pull request"))
+    }
+}
+
+#[async_trait]
+impl Publisher for GitPublisher {
+    async fn publish(&self, plan: &PublishPlan) -> Result<Vec<Published>> {
+        let mut clone = Command::new("git");
+        clone.args(["clone", "--depth", "1"]);
+        if let Some(branch) = &self.branch {
+            clone.args(["--branch", branch]);
+        }
+        // After `--`, a repository that looks like an option is still a repository
+        run(clone.arg("--").arg(&self.repo).arg(&self.checkout), "clone")?;
+
+        let mut published = self.write_pages(plan)?;
+        self.git(&["add", "--all", "--", "."])?;
+        if self.git(&["status", "--porcelain"])?.trim().is_empty() {
+            // Nothing changed since the last run, so there's nothing to push
+            return Ok(published);
+        }
+        let message = self.commit_message(plan);
+        self.git(&["commit", "--quiet", "--message", &message])?;
+
+        // An empty repository has no branch until this first commit
+        let base = match &self.branch {
+            Some(branch) => branch.clone(),
+            None => self.git(&["symbolic-ref", "--short", "HEAD"])?.trim().to_string(),
+        };
+        if self.open_pr {
+            let head = format!("lore/update-{}", chrono::Utc::now().format("%Y%m%d-%H%M%S"));
+            self.git(&["push", "--quiet", "origin", &format!("HEAD:refs/heads/{}", head)])?;
+            let title = message.lines().next().unwrap_or_default();
+            let url = self.open_pull_request(&head, &base, title).await?;
+            // The pull request is where the whole change can be read
+            if let Some(root) = published.first_mut() {
+                root.url = Some(url);
+            }
+        } else {
+            self.git(&["push", "--quiet", "origin", &format!("HEAD:refs/heads/{}", base)])?;
+        }
+        Ok(published)
+    }
+}
+
+/// Run a git command, turning a failure into an error with git's own message.
+pub(crate) fn run(command: &mut Command, what: &str) -> Result<String> {
+    let output = command.output().context("Could not run git - is it installed?")?;
+    if !output.status.success() {
+        bail!(
+            "git {} failed: {}",
+            what,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Split `Name <email>` into its parts.
+pub fn parse_author(author: &str) -> Result<(String, String)> {
+    let (name, rest) = author
+        .split_once('<')
+        .ok_or_else(|| anyhow!("'{}' should look like \"Name <email>\"", author))?;
+    let email = rest
+        .strip_suffix('>')
+        .ok_or_else(|| anyhow!("'{}' should look like \"Name <email>\"", author))?;
+    if name.trim().is_empty() || email.trim().is_empty() {
+        bail!("'{}' should look like \"Name <email>\"", author);
+    }
+    Ok((name.trim().to_string(), email.trim().to_string()))
+}
+
+/// The directory to publish into, which has to stay inside the repository.
+pub fn publish_dir(dir: &str) -> Result<PathBuf> {
+    let path = PathBuf::from(dir.trim());
+    if path
+        .components()
+        .any(|component| !matches!(component, Component::Normal(_) | Component::CurDir))
+    {
+        bail!("'{}' should be a relative path inside the repository", dir);
+    }
+    Ok(path)
+}
+
+/// A page file name from its title, the way GitHub wikis name pages:
+/// spaces become hyphens, and characters file systems (or the wiki)
+/// don't allow are dropped.
+pub fn file_stem(title: &str) -> String {
+    title
+        .trim()
+        .chars()
+        .filter_map(|c| match c {
+            ' ' => Some('-'),
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' | '#' | '%' => None,
+            c => Some(c),
+        })
+        .collect()
+}
+
+/// The owner and name of a GitHub repository, from its clone URL.
+fn github_repository(url: &str) -> Option<(String, String)> {
+    let path = url
+        .strip_prefix("https://github.com/")
+        .or_else(|| url.strip_prefix("git@github.com:"))
+        .or_else(|| url.strip_prefix("ssh://git@github.com/"))?;
+    let (owner, name) = path.trim_end_matches('/').split_once('/')?;
+    let name = name.strip_suffix(".git").unwrap_or(name);
+    if name.ends_with(".wiki") {
+        return None;
+    }
+    Some((owner.to_string(), name.to_string()))
+}
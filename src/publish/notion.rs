@@ -0,0 +1,393 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use serde_json::{json, Value};
+
+use crate::config::Config;
+use crate::document::{parse_inline, split_message, Block, Inline, MessagePart};
+use crate::publish::{check, required, Page, PublishAction, PublishPlan, PublishTarget, Published, Publisher};
+
+/// The Notion API version these requests are written against.
+const NOTION_VERSION: &str = "2022-06-28";
+
+/// Notion rejects text objects longer than this.
+const MAX_TEXT: usize = 2000;
+
+/// Notion takes at most this many blocks per request.
+const MAX_BLOCKS: usize = 100;
+
+/// Title of the database the session pages live in, under the overview.
+const SESSIONS_DATABASE: &str = "Sessions";
+
+/// The code languages Notion knows; anything else is shown as plain text.
+const LANGUAGES: &[&str] = &[
+    "bash", "c", "c#", "c++", "css", "diff", "docker", "go", "graphql", "html", "java", "javascript", "json",
+    "kotlin", "makefile", "markdown", "mermaid", "php", "powershell", "python", "ruby", "rust", "scss", "shell",
+    "sql", "swift", "typescript", "xml", "yaml",
+];
+
+/// Publishes to Notion. The overview becomes a page under
+/// `NOTION_PARENT_PAGE`, and sessions become pages in a "Sessions"
+/// database inside it, with a Session ID property that later runs
+/// query to find them again.
+pub struct NotionPublisher {
+    client: reqwest::Client,
+    token: String,
+    parent_page: String,
+}
+
+impl NotionPublisher {
+    /// Set up a publisher from the `NOTION_*` settings.
+    pub fn new(config: &Config) -> Result<Self> {
+        let target = PublishTarget::Notion;
+        Ok(NotionPublisher {
+            client: reqwest::Client::new(),
+            token: required(&config.notion_token, "NOTION_TOKEN", target)?.to_string(),
+            parent_page: required(&config.notion_parent_page, "NOTION_PARENT_PAGE", target)?.to_string(),
+        })
+    }
+
+    async fn send(&self, method: reqwest::Method, path: &str, body: Option<Value>) -> Result<Value> {
+        let mut request = self
+            .client
+            .request(method, format!("https://api.notion.com/v1{}", path))
+            .bearer_auth(&self.token)
+            .header("Notion-Version", NOTION_VERSION);
+        if let Some(body) = body {
+            request = request.json(&body);
+        }
+        let response = request.send().await?;
+        Ok(check(response, "talk to Notion").await?.json().await?)
+    }
+
+    /// Every child block of a page, following Notion's pagination.
+    async fn children(&self, block_id: &str) -> Result<Vec<Value>> {
+        let mut children = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let mut path = format!("/blocks/{}/children?page_size=100", block_id);
+            if let Some(cursor) = &cursor {
+                path.push_str(&format!("&start_cursor={}", cursor));
+            }
+            let page = self.send(reqwest::Method::GET, &path, None).await?;
+            children.extend(page["results"].as_array().cloned().unwrap_or_default());
+            match page["next_cursor"].as_str() {
+                Some(next) if page["has_more"].as_bool() == Some(true) => cursor = Some(next.to_string()),
+                _ => return Ok(children),
+            }
+        }
+    }
+
+    /// Add blocks to the end of a page, a request's worth at a time.
+    async fn append(&self, block_id: &str, blocks: &[Value]) -> Result<()> {
+        for chunk in blocks.chunks(MAX_BLOCKS) {
+            self.send(
+                reqwest::Method::PATCH,
+                &format!("/blocks/{}/children", block_id),
+                Some(json!({ "children": chunk })),
+            )
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// Swap a page's content for new blocks. Child pages and databases are
+    /// left alone, so clearing the overview doesn't take the sessions with it.
+    async fn replace_content(&self, page_id: &str, blocks: &[Value]) -> Result<()> {
+        for child in self.children(page_id).await? {
+            let kind = child["type"].as_str().unwrap_or_default();
+            if kind == "child_page" || kind == "child_database" {
+                continue;
+            }
+            if let Some(id) = child["id"].as_str() {
+                self.send(reqwest::Method::DELETE, &format!("/blocks/{}", id), None).await?;
+            }
+        }
+        self.append(page_id, blocks).await
+    }
+
+    /// Create a page with its first request's worth of blocks, then append the rest.
+    async fn create_page(&self, parent: Value, properties: Value, blocks: &[Value]) -> Result<Value> {
+        let (first, rest) = blocks.split_at(blocks.len().min(MAX_BLOCKS));
+        let page = self
+            .send(
+                reqwest::Method::POST,
+                "/pages",
+                Some(json!({ "parent": parent, "properties": properties, "children": first })),
+            )
+            .await?;
+        let id = page_id(&page)?;
+        self.append(&id, rest).await?;
+        Ok(page)
+    }
+
+    /// The overview page: found among the parent page's children by title,
+    /// or created there.
+    async fn publish_root(&self, root: &Page) -> Result<(String, Published)> {
+        let blocks = notion_blocks(&root.blocks);
+        let existing = self.children(&self.parent_page).await?.into_iter().find(|child| {
+            child["type"] == "child_page" && child["child_page"]["title"].as_str() == Some(root.title.as_str())
+        });
+
+        let (page, action) = match existing {
+            Some(child) => {
+                let id = page_id(&child)?;
+                self.replace_content(&id, &blocks).await?;
+                let page = self.send(reqwest::Method::GET, &format!("/pages/{}", id), None).await?;
+                (page, PublishAction::Updated)
+            }
+            None => {
+                let page = self
+                    .create_page(
+                        json!({ "page_id": self.parent_page }),
+                        json!({ "title": { "title": rich_text(&root.title) } }),
+                        &blocks,
+                    )
+                    .await?;
+                (page, PublishAction::Created)
+            }
+        };
+        Ok((page_id(&page)?, published(&root.title, action, &page)))
+    }
+
+    /// The sessions database inside the overview page, created the first time.
+    async fn sessions_database(&self, root_id: &str) -> Result<String> {
+        let existing = self.children(root_id).await?.into_iter().find(|child| {
+            child["type"] == "child_database" && child["child_database"]["title"].as_str() == Some(SESSIONS_DATABASE)
+        });
+        if let Some(database) = existing {
+            return page_id(&database);
+        }
+
+        let database = self
+            .send(
+                reqwest::Method::POST,
+                "/databases",
+                Some(json!({
+                    "parent": { "type": "page_id", "page_id": root_id },
+                    "title": rich_text(SESSIONS_DATABASE),
+                    "properties": {
+                        "Name": { "title": {} },
+                        "Session ID": { "rich_text": {} },
+                    },
+                })),
+            )
+            .await?;
+        page_id(&database)
+    }
+
+    /// A session's page: looked up in the database by session ID, then
+    /// updated, or created if this is the first time it's published.
+    async fn publish_session(&self, database_id: &str, page: &Page) -> Result<Published> {
+        let blocks = notion_blocks(&page.blocks);
+        let properties = json!({
+            "Name": { "title": rich_text(&page.title) },
+            "Session ID": { "rich_text": rich_text(&page.key) },
+        });
+        let query = json!({ "filter": { "property": "Session ID", "rich_text": { "equals": page.key } } });
+        let found = self
+            .send(reqwest::Method::POST, &format!("/databases/{}/query", database_id), Some(query))
+            .await?;
+
+        match found["results"].as_array().and_then(|results| results.first()) {
+            Some(existing) => {
+                let id = page_id(existing)?;
+                let updated = self
+                    .send(
+                        reqwest::Method::PATCH,
+                        &format!("/pages/{}", id),
+                        Some(json!({ "properties": properties })),
+                    )
+                    .await?;
+                self.replace_content(&id, &blocks).await?;
+                Ok(published(&page.title, PublishAction::Updated, &updated))
+            }
+            None => {
+                let created = self
+                    .create_page(json!({ "database_id": database_id }), properties, &blocks)
+                    .await?;
+                Ok(published(&page.title, PublishAction::Created, &created))
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Publisher for NotionPublisher {
+    async fn publish(&self, plan: &PublishPlan) -> Result<Vec<Published>> {
+        let (root_id, root) = self.publish_root(&plan.root).await?;
+        let mut results = vec![root];
+        if plan.sessions.is_empty() {
+            return Ok(results);
+        }
+
+        let database_id = self.sessions_database(&root_id).await?;
+        for page in &plan.sessions {
+            results.push(self.publish_session(&database_id, page).await?);
+        }
+        Ok(results)
+    }
+}
+
+fn page_id(object: &Value) -> Result<String> {
+    object["id"]
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| anyhow!("Notion returned an object without an id"))
+}
+
+fn published(title: &str, action: PublishAction, page: &Value) -> Published {
+    Published {
+        title: title.to_string(),
+        action,
+        url: page["url"].as_str().map(str::to_string),
+    }
+}
+
+/// Convert blocks to Notion block objects. Notion has three heading
+/// levels, so the title and sections share the top one and anything
+/// deeper than a subsection shares the third; collapsed content becomes
+/// a toggle.
+pub fn notion_blocks(blocks: &[Block]) -> Vec<Value> {
+    blocks.iter().flat_map(notion_block).collect()
+}
+
+fn notion_block(block: &Block) -> Vec<Value> {
+    match block {
+        Block::Heading {
+            level, text, properties, ..
+        } => {
+            let kind = format!("heading_{}", level.saturating_sub(1).clamp(1, 3));
+            let mut blocks = vec![json!({ "type": kind, (kind.clone()): { "rich_text": inline(text) } })];
+            blocks.extend(fields(properties));
+            blocks
+        }
+        Block::Paragraph(text) => vec![paragraph(inline(text))],
+        Block::Fields(values) => fields(values),
+        Block::List { ordered, items } => {
+            let kind = if *ordered { "numbered_list_item" } else { "bulleted_list_item" };
+            items
+                .iter()
+                .map(|item| json!({ "type": kind, kind: { "rich_text": inline(item) } }))
+                .collect()
+        }
+        Block::Code { language, text } => vec![code(language, text)],
+        Block::Details { summary, blocks } => vec![json!({
+            "type": "toggle",
+            "toggle": { "rich_text": inline(summary), "children": notion_blocks(blocks) },
+        })],
+        Block::Message { speaker, sent_at, text } => {
+            let mut label = match sent_at {
+                Some(sent_at) => vec![styled(speaker, "bold"), plain_text(&format!(" ({}):", sent_at))],
+                None => vec![styled(speaker, "bold"), plain_text(":")],
+            };
+            let mut blocks = Vec::new();
+            for part in split_message(text) {
+                match part {
+                    MessagePart::Prose(prose) => {
+                        let mut spans = std::mem::take(&mut label);
+                        if !spans.is_empty() {
+                            spans.push(plain_text(" "));
+                        }
+                        spans.extend(inline(&prose));
+                        blocks.push(paragraph(spans));
+                    }
+                    MessagePart::Code { language, text } => {
+                        if !label.is_empty() {
+                            blocks.push(paragraph(std::mem::take(&mut label)));
+                        }
+                        blocks.push(code(&language, &text));
+                    }
+                }
+            }
+            if !label.is_empty() {
+                blocks.push(paragraph(label));
+            }
+            blocks
+        }
+        Block::Rule => vec![json!({ "type": "divider", "divider": {} })],
+    }
+}
+
+fn paragraph(spans: Vec<Value>) -> Value {
+    json!({ "type": "paragraph", "paragraph": { "rich_text": spans } })
+}
+
+/// A paragraph per `Label: value`, with the label in bold.
+fn fields(values: &[(String, String)]) -> Vec<Value> {
+    values
+        .iter()
+        .map(|(label, value)| {
+            let mut spans = vec![styled(label, "bold"), plain_text(": ")];
+            spans.extend(inline(value));
+            paragraph(spans)
+        })
+        .collect()
+}
+
+fn code(language: &str, text: &str) -> Value {
+    let language = language.to_lowercase();
+    let language = match language.as_str() {
+        "sh" | "zsh" => "shell",
+        "js" => "javascript",
+        "ts" => "typescript",
+        "py" => "python",
+        "rs" => "rust",
+        "yml" => "yaml",
+        other if LANGUAGES.contains(&other) => other,
+        _ => "plain text",
+    };
+    json!({
+        "type": "code",
+        "code": { "language": language, "rich_text": chunks(text.trim_end_matches('\n'), json!({})) },
+    })
+}
+
+/// Inline markup as Notion rich text. Links to headings become plain
+/// text, since Notion can't link to a heading by its anchor.
+fn inline(text: &str) -> Vec<Value> {
+    parse_inline(text)
+        .into_iter()
+        .flat_map(|span| match span {
+            Inline::Text(text) => chunks(&text, json!({})),
+            Inline::Bold(text) => chunks(&text, json!({ "bold": true })),
+            Inline::Italic(text) => chunks(&text, json!({ "italic": true })),
+            Inline::Code(text) => chunks(&text, json!({ "code": true })),
+            Inline::Link { text, target } if target.starts_with("http://") || target.starts_with("https://") => {
+                let mut spans = chunks(&text, json!({}));
+                for span in &mut spans {
+                    span["text"]["link"] = json!({ "url": target });
+                }
+                spans
+            }
+            Inline::Link { text, .. } => chunks(&text, json!({})),
+        })
+        .collect()
+}
+
+fn plain_text(text: &str) -> Value {
+    json!({ "type": "text", "text": { "content": text } })
+}
+
+fn styled(text: &str, style: &str) -> Value {
+    json!({ "type": "text", "text": { "content": text }, "annotations": { style: true } })
+}
+
+fn rich_text(text: &str) -> Vec<Value> {
+    chunks(text, json!({}))
+}
+
+/// Text split into as many text objects as Notion's length limit needs,
+/// all with the same annotations.
+fn chunks(text: &str, annotations: Value) -> Vec<Value> {
+    let characters: Vec<char> = text.chars().collect();
+    characters
+        .chunks(MAX_TEXT)
+        .map(|chunk| {
+            let mut span = json!({ "type": "text", "text": { "content": chunk.iter().collect::<String>() } });
+            if annotations.as_object().is_some_and(|styles| !styles.is_empty()) {
+                span["annotations"] = annotations.clone();
+            }
+            span
+        })
+        .collect()
+}
@@ -0,0 +1,112 @@
+use serde_json::Value;
+use std::collections::{BTreeSet, HashMap};
+
+use crate::{ChatSession, ComposerData, FileReference};
+
+/// One file that came up in a session, with every line range that was attached.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiscussedFile {
+    /// The file's path, exactly as the source recorded it
+    pub path: String,
+    /// Distinct `(start, end)` line ranges that were attached, in order
+    pub line_ranges: Vec<(u32, u32)>,
+    /// How many times the file was referenced in the session
+    pub references: usize,
+}
+
+/// How much attention a file got across the whole history.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileActivity {
+    /// The file's path, exactly as the source recorded it
+    pub path: String,
+    /// How many different sessions referenced the file
+    pub sessions: usize,
+    /// How many references there were in total
+    pub references: usize,
+}
+
+/// Collect the files a single session referenced, in the order they first came up.
+pub fn files_discussed(session: &ChatSession) -> Vec<DiscussedFile> {
+    let mut files: Vec<DiscussedFile> = Vec::new();
+
+    for reference in session.messages.iter().flat_map(|message| &message.file_refs) {
+        let position = match files.iter().position(|file| file.path == reference.path) {
+            Some(position) => position,
+            None => {
+                files.push(DiscussedFile {
+                    path: reference.path.clone(),
+                    line_ranges: Vec::new(),
+                    references: 0,
+                });
+                files.len() - 1
+            }
+        };
+
+        let file = &mut files[position];
+        file.references += 1;
+        if let Some(start) = reference.start_line {
+            let range = (start, reference.end_line.unwrap_or(start));
+            if !file.line_ranges.contains(&range) {
+                file.line_ranges.push(range);
+            }
+        }
+    }
+
+    files
+}
+
+/// Rank every referenced file by how many sessions discussed it.
+/// Ties are broken by the total number of references, then by path so the
+/// ranking is stable from run to run.
+pub fn most_discussed_files(sessions: &[ComposerData]) -> Vec<FileActivity> {
+    let mut activity: HashMap<String, (BTreeSet<&str>, usize)> = HashMap::new();
+
+    for session in sessions.iter().flat_map(|data| &data.all_composers) {
+        for file in files_discussed(session) {
+            let entry = activity.entry(file.path).or_default();
+            entry.0.insert(&session.composer_id);
+            entry.1 += file.references;
+        }
+    }
+
+    let mut ranking: Vec<FileActivity> = activity
+        .into_iter()
+        .map(|(path, (sessions, references))| FileActivity {
+            path,
+            sessions: sessions.len(),
+            references,
+        })
+        .collect();
+    ranking.sort_by(|a, b| {
+        b.sessions
+            .cmp(&a.sessions)
+            .then(b.references.cmp(&a.references))
+            .then(a.path.cmp(&b.path))
+    });
+    ranking
+}
+
+/// Turn a VS Code-style URI into a plain path.
+/// Sources store these either as `{ "fsPath": ..., "path": ... }` objects
+/// or as `file://` URI strings.
+pub(crate) fn uri_to_path(uri: &Value) -> Option<String> {
+    match uri {
+        Value::String(text) => Some(text.strip_prefix("file://").unwrap_or(text).to_string()),
+        Value::Object(_) => uri["fsPath"]
+            .as_str()
+            .or_else(|| uri["path"].as_str())
+            .or_else(|| uri["value"].as_str().map(|value| value.strip_prefix("file://").unwrap_or(value)))
+            .map(str::to_string),
+        _ => None,
+    }
+    .filter(|path| !path.is_empty())
+}
+
+/// Build a reference to a whole file from its URI.
+pub(crate) fn whole_file(uri: &Value) -> Option<FileReference> {
+    Some(FileReference {
+        path: uri_to_path(uri)?,
+        start_line: None,
+        end_line: None,
+    })
+}
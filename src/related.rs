@@ -0,0 +1,122 @@
+use std::collections::{BTreeSet, HashMap};
+
+use crate::faq::{key_words, similarity};
+use crate::references::files_discussed;
+use crate::{ChatSession, MessageRole};
+
+/// How related two sessions have to be to be linked.
+const MIN_RELATEDNESS: f64 = 0.2;
+
+/// How much shared words, shared files and being close in time count
+/// towards two sessions being related.
+const WORDS_WEIGHT: f64 = 0.5;
+const FILES_WEIGHT: f64 = 0.35;
+const TIME_WEIGHT: f64 = 0.15;
+
+const DAY_MS: f64 = 86_400_000.0;
+
+/// What a session is about, going by its name and first question.
+pub fn topic_words(session: &ChatSession) -> BTreeSet<String> {
+    let question = session
+        .messages
+        .iter()
+        .find(|message| message.role == MessageRole::User)
+        .map(|message| message.text.as_str())
+        .unwrap_or_default();
+    key_words(&format!("{} {}", session.name, question))
+}
+
+/// What two sessions are compared by.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SessionProfile {
+    /// Its topic words (see `topic_words`)
+    pub words: BTreeSet<String>,
+    /// The files attached to its messages
+    pub files: BTreeSet<String>,
+    /// When it started (Unix milliseconds)
+    pub created_at: i64,
+}
+
+impl SessionProfile {
+    /// The profile of `session`.
+    pub fn of(session: &ChatSession) -> Self {
+        SessionProfile {
+            words: topic_words(session),
+            files: files_discussed(session).into_iter().map(|file| file.path).collect(),
+            created_at: session.created_at,
+        }
+    }
+}
+
+/// How related two sessions are, from 0 to 1: the words and files they
+/// share, out of all of either's, and how close together they started (a
+/// day apart counts half as much as the same day). Sessions that share
+/// neither words nor files aren't related, however close together they were.
+pub fn relatedness(a: &SessionProfile, b: &SessionProfile) -> f64 {
+    if a.words.is_disjoint(&b.words) && a.files.is_disjoint(&b.files) {
+        return 0.0;
+    }
+    let days = (a.created_at - b.created_at).unsigned_abs() as f64 / DAY_MS;
+    WORDS_WEIGHT * similarity(&a.words, &b.words)
+        + FILES_WEIGHT * similarity(&a.files, &b.files)
+        + TIME_WEIGHT / (1.0 + days)
+}
+
+/// The sessions related to each session, most related first, for the
+/// "Related sessions" list under each one (RELATED_SESSIONS).
+#[derive(Debug, Clone, Default)]
+pub struct RelatedSessions<'a> {
+    related: HashMap<&'a str, Vec<(usize, &'a ChatSession)>>,
+}
+
+impl<'a> RelatedSessions<'a> {
+    /// Find up to `limit` related sessions for each of the numbered
+    /// sessions. Only sessions with a word or file in common are compared,
+    /// so a long history doesn't mean comparing every pair.
+    pub fn find(numbered: &[(usize, &'a ChatSession)], limit: usize) -> Self {
+        let mut related = HashMap::new();
+        if limit == 0 {
+            return RelatedSessions { related };
+        }
+        let profiles: Vec<SessionProfile> = numbered.iter().map(|(_, session)| SessionProfile::of(session)).collect();
+
+        // Which sessions have each word or file
+        let mut having: HashMap<(bool, &str), Vec<usize>> = HashMap::new();
+        for (index, profile) in profiles.iter().enumerate() {
+            for word in &profile.words {
+                having.entry((false, word.as_str())).or_default().push(index);
+            }
+            for file in &profile.files {
+                having.entry((true, file.as_str())).or_default().push(index);
+            }
+        }
+
+        for (index, profile) in profiles.iter().enumerate() {
+            let candidates: BTreeSet<usize> = profile
+                .words
+                .iter()
+                .map(|word| (false, word.as_str()))
+                .chain(profile.files.iter().map(|file| (true, file.as_str())))
+                .flat_map(|key| having.get(&key).into_iter().flatten().copied())
+                .filter(|&other| other != index)
+                .collect();
+            let mut scored: Vec<(f64, usize)> = candidates
+                .into_iter()
+                .map(|other| (relatedness(profile, &profiles[other]), other))
+                .filter(|(score, _)| *score >= MIN_RELATEDNESS)
+                .collect();
+            // Most related first; ties go to the session that comes first
+            scored.sort_by(|a, b| b.0.total_cmp(&a.0).then(a.1.cmp(&b.1)));
+            if !scored.is_empty() {
+                let links = scored.into_iter().take(limit).map(|(_, other)| numbered[other]).collect();
+                related.insert(numbered[index].1.composer_id.as_str(), links);
+            }
+        }
+        RelatedSessions { related }
+    }
+
+    /// The sessions related to the session with this ID, with their numbers.
+    pub fn of(&self, session_id: &str) -> &[(usize, &'a ChatSession)] {
+        self.related.get(session_id).map(Vec::as_slice).unwrap_or_default()
+    }
+}
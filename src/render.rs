@@ -0,0 +1,276 @@
+use anyhow::{anyhow, Result};
+use std::io::Write;
+use std::path::Path;
+
+use crate::document::{Block, Document};
+use crate::versioning::{FORMAT_VERSION, VERSION_STAMP};
+
+pub mod asciidoc;
+pub mod epub;
+pub mod markdown;
+pub mod org;
+#[cfg(feature = "pdf")]
+pub mod pdf;
+pub mod typst;
+
+/// The formats the lore can be written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    /// GitHub-flavoured markdown (the default)
+    #[default]
+    Markdown,
+    /// Emacs org-mode, with properties drawers and timestamps on sessions
+    Org,
+    /// AsciiDoc, for Asciidoctor and Antora sites
+    AsciiDoc,
+    /// Typst markup, with a cover page and contents, for typesetting
+    Typst,
+    /// A typeset PDF, made from the Typst markup. Needs the `pdf` feature.
+    Pdf,
+    /// An EPUB book for e-readers, with a cover, contents, and a chapter
+    /// per month of sessions
+    Epub,
+    /// A CSV table with one row per message, for analytics
+    Csv,
+    /// The same table as Parquet. Needs the `parquet` feature.
+    Parquet,
+    /// The source of a Zola site: a page per session, tag and topic
+    /// pages, and an overview, in a directory of its own
+    Site,
+    /// The document tree itself as JSON, for the browser viewer to render
+    /// in any of the text formats (see `wasm`)
+    Json,
+}
+
+impl OutputFormat {
+    /// The values `--format` accepts.
+    pub const NAMES: &'static [&'static str] = &["markdown", "org", "asciidoc", "typst", "pdf", "epub", "csv", "parquet", "site", "json"];
+
+    /// Parse a `--format` / `OUTPUT_FORMAT` value.
+    pub fn parse(name: &str) -> Result<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "" | "markdown" | "md" => Ok(OutputFormat::Markdown),
+            "org" | "org-mode" => Ok(OutputFormat::Org),
+            "asciidoc" | "adoc" => Ok(OutputFormat::AsciiDoc),
+            "typst" | "typ" => Ok(OutputFormat::Typst),
+            "pdf" if cfg!(feature = "pdf") => Ok(OutputFormat::Pdf),
+            "pdf" => Err(anyhow!(
+                "PDF output isn't built in; reinstall with `cargo install --features pdf`, or use --format typst and run `typst compile` yourself"
+            )),
+            "epub" => Ok(OutputFormat::Epub),
+            "csv" => Ok(OutputFormat::Csv),
+            "site" | "zola" => Ok(OutputFormat::Site),
+            "json" => Ok(OutputFormat::Json),
+            "parquet" if cfg!(feature = "parquet") => Ok(OutputFormat::Parquet),
+            "parquet" => Err(anyhow!(
+                "Parquet output isn't built in; reinstall with `cargo install --features parquet`, or use --format csv"
+            )),
+            other => Err(anyhow!(
+                "Unknown output format '{}' (expected {})",
+                other,
+                OutputFormat::NAMES.join(", ")
+            )),
+        }
+    }
+
+    /// The usual file extension for this format.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Markdown => "md",
+            OutputFormat::Org => "org",
+            OutputFormat::AsciiDoc => "adoc",
+            OutputFormat::Typst => "typ",
+            OutputFormat::Pdf => "pdf",
+            OutputFormat::Epub => "epub",
+            OutputFormat::Csv => "csv",
+            OutputFormat::Parquet => "parquet",
+            OutputFormat::Site => "",
+            OutputFormat::Json => "json",
+        }
+    }
+
+    /// Adjust an output file name to this format: a `.md` name (like the
+    /// default) gets this format's extension instead, anything else is
+    /// left alone since the user picked it on purpose. A site is a
+    /// directory, so its name just loses the `.md`.
+    pub fn file_name(&self, file_name: &str) -> String {
+        let path = Path::new(file_name);
+        match path.extension() {
+            Some(extension) if extension == "md" => path.with_extension(self.extension()).to_string_lossy().to_string(),
+            _ => file_name.to_string(),
+        }
+    }
+
+    /// Whether this format is a table of messages rather than a document.
+    /// Tabular formats are written by `tabular`, not rendered from a `Document`.
+    pub fn is_tabular(&self) -> bool {
+        matches!(self, OutputFormat::Csv | OutputFormat::Parquet)
+    }
+
+    /// Write a document out in this format. PDF is binary, so for PDF
+    /// this gives the Typst markup it would be typeset from; use
+    /// `render_bytes` for the PDF itself. Tabular formats can't be made
+    /// from a document and give an error.
+    pub fn render(&self, document: &Document) -> Result<String> {
+        let stamped = |text: String| format!("{}\n{}", text, self.version_stamp().unwrap_or_default());
+        match self {
+            OutputFormat::Markdown => Ok(stamped(markdown::render(document))),
+            OutputFormat::Org => Ok(stamped(org::render(document))),
+            OutputFormat::AsciiDoc => Ok(stamped(asciidoc::render(document))),
+            OutputFormat::Typst | OutputFormat::Pdf => Ok(stamped(typst::render(document))),
+            OutputFormat::Json => Ok(format!("{}\n", serde_json::to_string(document)?)),
+            OutputFormat::Csv | OutputFormat::Parquet => Err(anyhow!(
+                "{} is a table of messages, not a document; build it with tabular::message_rows",
+                self.extension()
+            )),
+            OutputFormat::Epub => Err(anyhow!("An EPUB is a book of chapters, not one document; build it with generate_book")),
+            OutputFormat::Site => Err(anyhow!("A site is a tree of files, not a document; build it with generate_site")),
+        }
+    }
+
+    /// The comment a text format's lore ends with, saying which
+    /// `FORMAT_VERSION` it's in. JSON has the version in the document.
+    pub fn version_stamp(&self) -> Option<String> {
+        let comment = |open: &str, close: &str| Some(format!("{}{}: {}{}\n", open, VERSION_STAMP, FORMAT_VERSION, close));
+        match self {
+            OutputFormat::Markdown => comment("<!-- ", " -->"),
+            OutputFormat::Org => comment("# ", ""),
+            OutputFormat::AsciiDoc | OutputFormat::Typst | OutputFormat::Pdf => comment("// ", ""),
+            _ => None,
+        }
+    }
+
+    /// Write a document out in this format as the bytes of the file.
+    /// That's the UTF-8 text for everything but PDF, which is typeset here.
+    pub fn render_bytes(&self, document: &Document) -> Result<Vec<u8>> {
+        match self {
+            #[cfg(feature = "pdf")]
+            OutputFormat::Pdf => pdf::compile(&typst::render(document)),
+            #[cfg(not(feature = "pdf"))]
+            OutputFormat::Pdf => Err(anyhow!("PDF output isn't built in; rebuild with `--features pdf`")),
+            _ => Ok(self.render(document)?.into_bytes()),
+        }
+    }
+
+    /// Write a run of blocks in this format exactly as they come out in
+    /// the middle of a whole document, so a section's text can be looked
+    /// for in a file that was written earlier. Only text formats can.
+    pub fn render_blocks(&self, blocks: &[Block]) -> Result<String> {
+        if !matches!(self, OutputFormat::Markdown | OutputFormat::Org | OutputFormat::AsciiDoc | OutputFormat::Typst | OutputFormat::Json) {
+            return Err(anyhow!("{} isn't a text format", self.extension()));
+        }
+        let rendered = blocks.iter().map(|block| render_block(*self, block)).collect::<Result<Vec<_>>>()?;
+        Ok(rendered.join(if *self == OutputFormat::Json { "," } else { "\n" }))
+    }
+}
+
+/// One block in a text format, without what goes between blocks.
+fn render_block(format: OutputFormat, block: &Block) -> Result<String> {
+    Ok(match format {
+        OutputFormat::Org => org::render_block(block),
+        OutputFormat::AsciiDoc => asciidoc::render_block(block),
+        OutputFormat::Typst | OutputFormat::Pdf => typst::render_block(block),
+        OutputFormat::Json => serde_json::to_string(block)?,
+        _ => markdown::render_block(block),
+    })
+}
+
+/// Writes a document out in a text format a few blocks at a time, as it's
+/// being built, so neither the whole document nor the whole rendered text
+/// has to be held in memory at once. Writing every block of a document
+/// through one gives exactly what `OutputFormat::render` would.
+pub struct DocumentWriter<W: Write> {
+    format: OutputFormat,
+    sink: W,
+    /// Whether a block has been written since the last separator point
+    /// (for JSON, whether the list of blocks has been opened)
+    started: bool,
+    /// For Typst, the blocks before the first section, which go on the
+    /// cover page; `None` once the cover has been written
+    cover: Option<Vec<Block>>,
+}
+
+impl<W: Write> DocumentWriter<W> {
+    /// Write `format` to `sink`. PDF gives the Typst markup, as `render`
+    /// does; tabular formats aren't documents and give an error.
+    pub fn new(format: OutputFormat, sink: W) -> Result<Self> {
+        if format.is_tabular() {
+            return Err(anyhow!(
+                "{} is a table of messages, not a document; build it with tabular::message_rows",
+                format.extension()
+            ));
+        }
+        if format == OutputFormat::Epub {
+            return Err(anyhow!("An EPUB is a book of chapters, not one document; build it with generate_book"));
+        }
+        if format == OutputFormat::Site {
+            return Err(anyhow!("A site is a tree of files, not a document; build it with generate_site"));
+        }
+        let cover = matches!(format, OutputFormat::Typst | OutputFormat::Pdf).then(Vec::new);
+        Ok(DocumentWriter {
+            format,
+            sink,
+            started: false,
+            cover,
+        })
+    }
+
+    /// Write text as it is, ahead of the document - front matter, say.
+    pub fn write_raw(&mut self, text: &str) -> Result<()> {
+        self.sink.write_all(text.as_bytes())?;
+        Ok(())
+    }
+
+    /// Write the next blocks of the document.
+    pub fn write(&mut self, blocks: &[Block]) -> Result<()> {
+        for block in blocks {
+            if let Some(cover) = &mut self.cover {
+                if !matches!(block, Block::Heading { level, .. } if *level > 1) {
+                    cover.push(block.clone());
+                    continue;
+                }
+                self.write_cover()?;
+            }
+            match (self.format, self.started) {
+                (OutputFormat::Json, false) => self.json_start()?,
+                (OutputFormat::Json, true) => self.sink.write_all(b",")?,
+                (_, true) => self.sink.write_all(b"\n")?,
+                (_, false) => {}
+            }
+            self.sink.write_all(render_block(self.format, block)?.as_bytes())?;
+            self.started = true;
+        }
+        Ok(())
+    }
+
+    /// Finish the document and flush it, handing back the sink.
+    pub fn finish(mut self) -> Result<W> {
+        if self.cover.is_some() {
+            self.write_cover()?;
+        }
+        if self.format == OutputFormat::Json {
+            if !self.started {
+                self.json_start()?;
+            }
+            self.sink.write_all(b"]}\n")?;
+        }
+        if let Some(stamp) = self.format.version_stamp() {
+            write!(self.sink, "\n{}", stamp)?;
+        }
+        self.sink.flush()?;
+        Ok(self.sink)
+    }
+
+    /// Open a JSON document, up to its first block.
+    fn json_start(&mut self) -> Result<()> {
+        write!(self.sink, "{{\"format_version\":{},\"blocks\":[", FORMAT_VERSION)?;
+        Ok(())
+    }
+
+    fn write_cover(&mut self) -> Result<()> {
+        if let Some(cover) = self.cover.take() {
+            self.sink.write_all(typst::render_cover(&cover).as_bytes())?;
+        }
+        Ok(())
+    }
+}
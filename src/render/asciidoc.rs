@@ -0,0 +1,128 @@
+use crate::document::{parse_inline, split_message, Block, Document, Inline, MessagePart};
+use crate::render::markdown::with_newline;
+
+/// Render a document as AsciiDoc. Headings with ids get an anchor so the
+/// contents links resolve, and heading properties become a description list.
+pub fn render(document: &Document) -> String {
+    render_blocks(&document.blocks)
+}
+
+fn render_blocks(blocks: &[Block]) -> String {
+    blocks.iter().map(render_block).collect::<Vec<_>>().join("\n")
+}
+
+pub(crate) fn render_block(block: &Block) -> String {
+    match block {
+        Block::Heading {
+            level, text, id, properties, ..
+        } => {
+            let mut heading = String::new();
+            if let Some(id) = id {
+                heading.push_str(&format!("[[{}]]\n", id));
+            }
+            heading.push_str(&format!("{} {}\n", "=".repeat(*level), inline(text)));
+            if !properties.is_empty() {
+                heading.push('\n');
+                heading.push_str(&description_list(properties));
+            }
+            heading
+        }
+        Block::Paragraph(text) => format!("{}\n", prose(text)),
+        Block::Fields(values) => description_list(values),
+        Block::List { ordered, items } => items
+            .iter()
+            .map(|item| format!("{} {}\n", if *ordered { "." } else { "*" }, inline(item)))
+            .collect(),
+        Block::Code { language, text } => listing(language, text),
+        Block::Details { summary, blocks } => format!(
+            ".{}\n[%collapsible]\n====\n{}====\n",
+            inline(summary),
+            render_blocks(blocks)
+        ),
+        Block::Message { speaker, sent_at, text } => {
+            let mut message = match sent_at {
+                Some(sent_at) => format!("*{}* ({}):", speaker, sent_at),
+                None => format!("*{}*:", speaker),
+            };
+            for (i, part) in split_message(text).into_iter().enumerate() {
+                match part {
+                    MessagePart::Prose(text) if i == 0 => message.push_str(&format!(" {}\n", prose(&text))),
+                    MessagePart::Prose(text) => message.push_str(&format!("\n{}\n", prose(&text))),
+                    MessagePart::Code { language, text } => {
+                        if i == 0 {
+                            message.push('\n');
+                        }
+                        message.push('\n');
+                        message.push_str(&listing(&language, &text));
+                    }
+                }
+            }
+            if !message.ends_with('\n') {
+                message.push('\n');
+            }
+            message
+        }
+        Block::Rule => "'''\n".to_string(),
+    }
+}
+
+/// `Label:: value` lines.
+fn description_list(values: &[(String, String)]) -> String {
+    values
+        .iter()
+        .map(|(label, value)| format!("{}:: {}\n", label, inline(value)))
+        .collect()
+}
+
+/// A listing block, with a source language when there is one.
+fn listing(language: &str, text: &str) -> String {
+    let style = if language.is_empty() {
+        String::new()
+    } else {
+        format!("[source,{}]\n", language)
+    };
+    format!("{}----\n{}----\n", style, with_newline(text))
+}
+
+/// Text that may run over several lines, in AsciiDoc syntax. Chat content
+/// can't be allowed to start a heading, open a block (a `++++` passthrough
+/// block is raw HTML) or include a file, so lines that would are started
+/// with `{empty}`, which AsciiDoc turns into nothing.
+fn prose(text: &str) -> String {
+    inline(text)
+        .lines()
+        .map(|line| {
+            let trimmed = line.trim_end();
+            let first = trimmed.chars().next().unwrap_or(' ');
+            let delimiter = trimmed.len() >= 4 && "+-=*_./".contains(first) && trimmed.chars().all(|c| c == first);
+            let heading = first == '=' && trimmed.trim_start_matches('=').starts_with(' ');
+            let directive = ["include::", "ifdef::", "ifndef::", "ifeval::", "endif::", "|==="]
+                .iter()
+                .any(|directive| trimmed.starts_with(directive));
+            if delimiter || heading || directive {
+                format!("{{empty}}{}", line)
+            } else {
+                line.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Inline markup in AsciiDoc syntax.
+fn inline(text: &str) -> String {
+    parse_inline(text)
+        .into_iter()
+        .map(|span| match span {
+            // Passthroughs would let raw HTML into the output
+            Inline::Text(text) => text.replace("+++", "{plus}{plus}{plus}").replace("pass:", "pass{empty}:"),
+            Inline::Bold(text) => format!("*{}*", text),
+            Inline::Italic(text) => format!("_{}_", text),
+            Inline::Code(text) => format!("`+{}+`", text),
+            Inline::Link { text, target } => match target.strip_prefix('#') {
+                Some(id) => format!("<<{},{}>>", id, text),
+                None => format!("{}[{}]", target, text),
+            },
+        })
+        .collect()
+}
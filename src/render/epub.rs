@@ -0,0 +1,473 @@
+use anyhow::{bail, Result};
+use flate2::write::DeflateEncoder;
+use flate2::{Compression, Crc};
+use std::collections::HashMap;
+use std::io::Write;
+
+use crate::document::{heading_anchor, parse_inline, split_message, Block, Inline, MessagePart};
+
+/// An EPUB 3 book of the lore, for reading on an e-reader: a generated
+/// cover, a table of contents, and its chapters.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Book {
+    /// The book's title
+    pub title: String,
+    /// Who it's by - the project it's the lore of
+    pub author: String,
+    /// The language it's written in, like `en`
+    pub language: String,
+    /// What readers know the book by, so a new edition replaces the old
+    /// one in their library instead of sitting next to it
+    pub identifier: String,
+    /// When its content last changed, as `YYYY-MM-DDTHH:MM:SSZ`
+    pub modified: String,
+    /// Lines under the title on the cover (the date range, the session count)
+    pub cover_lines: Vec<String>,
+    /// The chapters, in reading order
+    pub chapters: Vec<Chapter>,
+}
+
+/// One chapter of a book: its title, and what's in it. The title is the
+/// chapter's only top-level heading; headings in `blocks` sit under it,
+/// and the level 2 ones are listed in the table of contents.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Chapter {
+    /// The chapter title
+    pub title: String,
+    /// Its content
+    pub blocks: Vec<Block>,
+}
+
+impl Book {
+    /// The files inside the EPUB, as (path, contents) pairs, in the order
+    /// they're packaged.
+    pub fn files(&self) -> Vec<(String, String)> {
+        // Links to a heading go to whichever chapter it ended up in
+        let mut anchors: HashMap<String, String> = HashMap::new();
+        for (index, chapter) in self.chapters.iter().enumerate() {
+            for (_, id, _) in headings(&chapter.blocks) {
+                anchors.entry(id).or_insert_with(|| chapter_file(index));
+            }
+        }
+
+        let mut files = vec![
+            ("mimetype".to_string(), "application/epub+zip".to_string()),
+            ("META-INF/container.xml".to_string(), CONTAINER.to_string()),
+            ("OEBPS/content.opf".to_string(), self.package_document()),
+            ("OEBPS/nav.xhtml".to_string(), self.navigation()),
+            ("OEBPS/toc.ncx".to_string(), self.ncx()),
+            ("OEBPS/style.css".to_string(), STYLE.to_string()),
+            ("OEBPS/cover.svg".to_string(), self.cover()),
+            (
+                "OEBPS/cover.xhtml".to_string(),
+                xhtml_page(&self.title, &self.language, "<div class=\"cover\"><img src=\"cover.svg\" alt=\"\"/></div>"),
+            ),
+        ];
+        for (index, chapter) in self.chapters.iter().enumerate() {
+            let body = format!(
+                "<section epub:type=\"chapter\">\n<h1>{}</h1>\n{}</section>",
+                escape(&chapter.title),
+                chapter.blocks.iter().map(|block| xhtml_block(block, &anchors)).collect::<String>()
+            );
+            files.push((format!("OEBPS/{}", chapter_file(index)), xhtml_page(&chapter.title, &self.language, &body)));
+        }
+        files
+    }
+
+    /// The book as the bytes of a `.epub` file.
+    pub fn package(&self) -> Result<Vec<u8>> {
+        let files: Vec<(String, Vec<u8>)> = self.files().into_iter().map(|(path, text)| (path, text.into_bytes())).collect();
+        zip(&files)
+    }
+
+    /// `content.opf`: the book's metadata, its files, and the reading order.
+    fn package_document(&self) -> String {
+        let mut manifest = String::from(concat!(
+            "    <item id=\"nav\" href=\"nav.xhtml\" media-type=\"application/xhtml+xml\" properties=\"nav\"/>\n",
+            "    <item id=\"ncx\" href=\"toc.ncx\" media-type=\"application/x-dtbncx+xml\"/>\n",
+            "    <item id=\"style\" href=\"style.css\" media-type=\"text/css\"/>\n",
+            "    <item id=\"cover-image\" href=\"cover.svg\" media-type=\"image/svg+xml\" properties=\"cover-image\"/>\n",
+            "    <item id=\"cover\" href=\"cover.xhtml\" media-type=\"application/xhtml+xml\"/>\n",
+        ));
+        let mut spine = String::from("    <itemref idref=\"cover\" linear=\"no\"/>\n    <itemref idref=\"nav\"/>\n");
+        for index in 0..self.chapters.len() {
+            manifest.push_str(&format!(
+                "    <item id=\"chapter-{}\" href=\"{}\" media-type=\"application/xhtml+xml\"/>\n",
+                index + 1,
+                chapter_file(index)
+            ));
+            spine.push_str(&format!("    <itemref idref=\"chapter-{}\"/>\n", index + 1));
+        }
+        format!(
+            concat!(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+                "<package xmlns=\"http://www.idpf.org/2007/opf\" version=\"3.0\" unique-identifier=\"book-id\" xml:lang=\"{language}\">\n",
+                "  <metadata xmlns:dc=\"http://purl.org/dc/elements/1.1/\">\n",
+                "    <dc:identifier id=\"book-id\">{identifier}</dc:identifier>\n",
+                "    <dc:title>{title}</dc:title>\n",
+                "    <dc:creator>{author}</dc:creator>\n",
+                "    <dc:language>{language}</dc:language>\n",
+                "    <meta property=\"dcterms:modified\">{modified}</meta>\n",
+                "    <meta name=\"cover\" content=\"cover-image\"/>\n",
+                "  </metadata>\n",
+                "  <manifest>\n{manifest}  </manifest>\n",
+                "  <spine toc=\"ncx\">\n{spine}  </spine>\n",
+                "</package>\n"
+            ),
+            language = escape(&self.language),
+            identifier = escape(&self.identifier),
+            title = escape(&self.title),
+            author = escape(&self.author),
+            modified = escape(&self.modified),
+            manifest = manifest,
+            spine = spine,
+        )
+    }
+
+    /// `nav.xhtml`: the table of contents, with each chapter's sections
+    /// (the overview's sections, a month's sessions) under it.
+    fn navigation(&self) -> String {
+        let mut list = String::from("<ol>\n");
+        for (index, chapter) in self.chapters.iter().enumerate() {
+            let file = chapter_file(index);
+            list.push_str(&format!("  <li><a href=\"{}\">{}</a>", file, escape(&chapter.title)));
+            let sections: Vec<String> = headings(&chapter.blocks)
+                .into_iter()
+                .filter(|(level, _, _)| *level == 2)
+                .map(|(_, id, text)| format!("      <li><a href=\"{}#{}\">{}</a></li>\n", file, escape(&id), escape(&plain(&text))))
+                .collect();
+            if !sections.is_empty() {
+                list.push_str(&format!("\n    <ol>\n{}    </ol>\n  ", sections.concat()));
+            }
+            list.push_str("</li>\n");
+        }
+        list.push_str("</ol>\n");
+        let body = format!("<nav epub:type=\"toc\" id=\"toc\">\n<h1>{}</h1>\n{}</nav>", escape(&self.title), list);
+        xhtml_page(&self.title, &self.language, &body)
+    }
+
+    /// `toc.ncx`: the chapters again, for readers that only know EPUB 2.
+    fn ncx(&self) -> String {
+        let points: String = self
+            .chapters
+            .iter()
+            .enumerate()
+            .map(|(index, chapter)| {
+                format!(
+                    "    <navPoint id=\"point-{0}\" playOrder=\"{0}\"><navLabel><text>{1}</text></navLabel><content src=\"{2}\"/></navPoint>\n",
+                    index + 1,
+                    escape(&chapter.title),
+                    chapter_file(index)
+                )
+            })
+            .collect();
+        format!(
+            concat!(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+                "<ncx xmlns=\"http://www.daisy.org/z3986/2005/ncx/\" version=\"2005-1\">\n",
+                "  <head><meta name=\"dtb:uid\" content=\"{}\"/></head>\n",
+                "  <docTitle><text>{}</text></docTitle>\n",
+                "  <navMap>\n{}  </navMap>\n",
+                "</ncx>\n"
+            ),
+            escape(&self.identifier),
+            escape(&self.title),
+            points
+        )
+    }
+
+    /// The cover: the title in large type, wrapped to fit, with the
+    /// author and the cover lines under it.
+    fn cover(&self) -> String {
+        let mut text = String::new();
+        let mut y = 420;
+        for line in wrap(&self.title, 22) {
+            text.push_str(&format!("  <text x=\"600\" y=\"{}\" class=\"title\">{}</text>\n", y, escape(&line)));
+            y += 110;
+        }
+        y += 60;
+        for (index, line) in std::iter::once(&self.author).chain(&self.cover_lines).enumerate() {
+            let class = if index == 0 { "author" } else { "line" };
+            text.push_str(&format!("  <text x=\"600\" y=\"{}\" class=\"{}\">{}</text>\n", y, class, escape(line)));
+            y += 70;
+        }
+        format!(
+            concat!(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+                "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 1200 1800\" width=\"1200\" height=\"1800\">\n",
+                "  <style>text {{ font-family: Georgia, serif; text-anchor: middle; fill: #f4f1ea; }} ",
+                ".title {{ font-size: 96px; font-weight: bold; }} .author {{ font-size: 56px; }} .line {{ font-size: 44px; fill: #c9c2b3; }}</style>\n",
+                "  <rect width=\"1200\" height=\"1800\" fill=\"#24313f\"/>\n",
+                "  <rect x=\"60\" y=\"60\" width=\"1080\" height=\"1680\" fill=\"none\" stroke=\"#c9a45c\" stroke-width=\"6\"/>\n",
+                "{}",
+                "</svg>\n"
+            ),
+            text
+        )
+    }
+}
+
+/// Where readers look first, to find the package document.
+const CONTAINER: &str = concat!(
+    "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+    "<container version=\"1.0\" xmlns=\"urn:oasis:names:tc:opendocument:xmlns:container\">\n",
+    "  <rootfiles>\n",
+    "    <rootfile full-path=\"OEBPS/content.opf\" media-type=\"application/oebps-package+xml\"/>\n",
+    "  </rootfiles>\n",
+    "</container>\n"
+);
+
+/// A plain style sheet; readers apply their own fonts and sizes on top.
+const STYLE: &str = concat!(
+    "body { line-height: 1.5; }\n",
+    "h1 { page-break-before: always; }\n",
+    "h2 { margin-top: 2em; }\n",
+    "pre { white-space: pre-wrap; font-size: 0.85em; background: #f3f3f3; padding: 0.5em; }\n",
+    ".message { margin: 0.8em 0; }\n",
+    ".speaker { margin-bottom: 0.2em; }\n",
+    ".summary { font-style: italic; }\n",
+    ".details { margin-left: 1em; }\n",
+    ".cover { text-align: center; }\n",
+    ".cover img { max-width: 100%; max-height: 100%; }\n",
+);
+
+/// The file the chapter at `index` goes in.
+fn chapter_file(index: usize) -> String {
+    format!("chapter-{:03}.xhtml", index + 1)
+}
+
+/// An XHTML page with the book's style sheet.
+fn xhtml_page(title: &str, language: &str, body: &str) -> String {
+    format!(
+        concat!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+            "<!DOCTYPE html>\n",
+            "<html xmlns=\"http://www.w3.org/1999/xhtml\" xmlns:epub=\"http://www.idpf.org/2007/ops\" xml:lang=\"{1}\" lang=\"{1}\">\n",
+            "<head>\n<meta charset=\"UTF-8\"/>\n<title>{0}</title>\n<link rel=\"stylesheet\" type=\"text/css\" href=\"style.css\"/>\n</head>\n",
+            "<body>\n{2}\n</body>\n</html>\n"
+        ),
+        escape(title),
+        escape(language),
+        body
+    )
+}
+
+/// The headings in `blocks`, collapsed ones included, as (level, id, text).
+fn headings(blocks: &[Block]) -> Vec<(usize, String, String)> {
+    let mut found = Vec::new();
+    for block in blocks {
+        match block {
+            Block::Heading { level, text, id, .. } => {
+                found.push((*level, id.clone().unwrap_or_else(|| heading_anchor(text)), text.clone()));
+            }
+            Block::Details { blocks, .. } => found.extend(headings(blocks)),
+            _ => {}
+        }
+    }
+    found
+}
+
+/// A block as XHTML. The chapter title is its `h1`, so headings start at
+/// `h2`. E-readers don't collapse anything, so collapsed content is shown
+/// in full under its summary.
+fn xhtml_block(block: &Block, anchors: &HashMap<String, String>) -> String {
+    match block {
+        Block::Heading {
+            level, text, id, properties, ..
+        } => {
+            let level = (*level).clamp(2, 6);
+            let id = id.clone().unwrap_or_else(|| heading_anchor(text));
+            format!("<h{0} id=\"{1}\">{2}</h{0}>\n{3}", level, escape(&id), inline(text, anchors), fields(properties, anchors))
+        }
+        Block::Paragraph(text) => format!("<p>{}</p>\n", inline(text, anchors)),
+        Block::Fields(values) => fields(values, anchors),
+        Block::List { ordered, items } => {
+            let tag = if *ordered { "ol" } else { "ul" };
+            let items: String = items.iter().map(|item| format!("<li>{}</li>\n", inline(item, anchors))).collect();
+            format!("<{0}>\n{1}</{0}>\n", tag, items)
+        }
+        Block::Code { language, text } => code(language, text),
+        Block::Details { summary, blocks } => format!(
+            "<div class=\"details\">\n<p class=\"summary\">{}</p>\n{}</div>\n",
+            inline(summary, anchors),
+            blocks.iter().map(|block| xhtml_block(block, anchors)).collect::<String>()
+        ),
+        Block::Message { speaker, sent_at, text } => {
+            let mut message = match sent_at {
+                Some(sent_at) => format!("<div class=\"message\">\n<p class=\"speaker\"><strong>{}</strong> ({})</p>\n", escape(speaker), escape(sent_at)),
+                None => format!("<div class=\"message\">\n<p class=\"speaker\"><strong>{}</strong></p>\n", escape(speaker)),
+            };
+            for part in split_message(text) {
+                match part {
+                    MessagePart::Prose(prose) => {
+                        for paragraph in prose.split("\n\n").map(str::trim).filter(|paragraph| !paragraph.is_empty()) {
+                            let lines: Vec<String> = paragraph.lines().map(|line| inline(line, anchors)).collect();
+                            message.push_str(&format!("<p>{}</p>\n", lines.join("<br/>")));
+                        }
+                    }
+                    MessagePart::Code { language, text } => message.push_str(&code(&language, &text)),
+                }
+            }
+            message.push_str("</div>\n");
+            message
+        }
+        Block::Rule => "<hr/>\n".to_string(),
+    }
+}
+
+/// `<strong>Label</strong>: value` lines.
+fn fields(values: &[(String, String)], anchors: &HashMap<String, String>) -> String {
+    values
+        .iter()
+        .map(|(label, value)| format!("<p><strong>{}</strong>: {}</p>\n", escape(label), inline(value, anchors)))
+        .collect()
+}
+
+/// A code block, with its language as a class for readers that highlight.
+fn code(language: &str, text: &str) -> String {
+    match language {
+        "" => format!("<pre><code>{}</code></pre>\n", escape(text.trim_end_matches('\n'))),
+        language => format!(
+            "<pre><code class=\"language-{}\">{}</code></pre>\n",
+            escape(language),
+            escape(text.trim_end_matches('\n'))
+        ),
+    }
+}
+
+/// Inline markup as XHTML. A link to a heading goes to the chapter it's
+/// in (or is plain text if the heading isn't in the book), and only web
+/// and mail links are kept, since nothing else opens from an e-reader.
+fn inline(text: &str, anchors: &HashMap<String, String>) -> String {
+    parse_inline(text)
+        .into_iter()
+        .map(|span| match span {
+            Inline::Text(text) => escape(&text),
+            Inline::Bold(text) => format!("<strong>{}</strong>", escape(&text)),
+            Inline::Italic(text) => format!("<em>{}</em>", escape(&text)),
+            Inline::Code(text) => format!("<code>{}</code>", escape(&text)),
+            Inline::Link { text, target } => match target.strip_prefix('#') {
+                Some(id) => match anchors.get(id) {
+                    Some(file) => format!("<a href=\"{}#{}\">{}</a>", file, escape(id), escape(&text)),
+                    None => escape(&text),
+                },
+                None if ["http://", "https://", "mailto:"].iter().any(|scheme| target.starts_with(scheme)) => {
+                    format!("<a href=\"{}\">{}</a>", escape(&target), escape(&text))
+                }
+                None => escape(&text),
+            },
+        })
+        .collect()
+}
+
+/// Inline text with its markup stripped, for the table of contents.
+fn plain(text: &str) -> String {
+    parse_inline(text)
+        .into_iter()
+        .map(|span| match span {
+            Inline::Text(text) | Inline::Bold(text) | Inline::Italic(text) | Inline::Code(text) => text,
+            Inline::Link { text, .. } => text,
+        })
+        .collect()
+}
+
+/// Escape text for XHTML, dropping the control characters XML can't hold
+/// at all (a reader refuses the whole chapter over one).
+fn escape(text: &str) -> String {
+    text.chars()
+        .filter(|c| !c.is_control() || matches!(c, '\n' | '\t' | '\r'))
+        .collect::<String>()
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Break text into lines of about `width` characters, at spaces.
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        if !line.is_empty() && line.chars().count() + 1 + word.chars().count() > width {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/// The DOS date every file is stamped with (1980-01-01), so the same lore
+/// always packages to the same bytes; the book's own date is in its metadata.
+const DOS_DATE: u16 = (1 << 5) | 1;
+
+/// Pack files into a zip archive the way EPUB wants it: the `mimetype`
+/// file first and uncompressed (readers sniff for it at a fixed offset),
+/// everything else deflated.
+fn zip(files: &[(String, Vec<u8>)]) -> Result<Vec<u8>> {
+    let mut archive: Vec<u8> = Vec::new();
+    let mut directory: Vec<u8> = Vec::new();
+    for (name, data) in files {
+        let stored = name == "mimetype";
+        let mut crc = Crc::new();
+        crc.update(data);
+        let body = if stored {
+            data.clone()
+        } else {
+            let mut encoder = DeflateEncoder::new(Vec::new(), Compression::best());
+            encoder.write_all(data)?;
+            encoder.finish()?
+        };
+        let (Ok(offset), Ok(compressed), Ok(size)) = (u32::try_from(archive.len()), u32::try_from(body.len()), u32::try_from(data.len())) else {
+            bail!("The book is too big for an EPUB (over 4 GB)");
+        };
+        let method: u16 = if stored { 0 } else { 8 };
+
+        // The fields the local header and the central directory share:
+        // version needed, flags (UTF-8 names), method, time, date, CRC, sizes, name length
+        let mut common: Vec<u8> = Vec::new();
+        for value in [20u16, 0x0800, method, 0, DOS_DATE] {
+            common.extend_from_slice(&value.to_le_bytes());
+        }
+        for value in [crc.sum(), compressed, size] {
+            common.extend_from_slice(&value.to_le_bytes());
+        }
+        common.extend_from_slice(&(name.len() as u16).to_le_bytes());
+
+        archive.extend_from_slice(&0x04034b50u32.to_le_bytes());
+        archive.extend_from_slice(&common);
+        archive.extend_from_slice(&0u16.to_le_bytes());
+        archive.extend_from_slice(name.as_bytes());
+        archive.extend_from_slice(&body);
+
+        directory.extend_from_slice(&0x02014b50u32.to_le_bytes());
+        directory.extend_from_slice(&20u16.to_le_bytes());
+        directory.extend_from_slice(&common);
+        // No extra field or comment, disk 0, no attributes
+        for value in [0u16, 0, 0, 0] {
+            directory.extend_from_slice(&value.to_le_bytes());
+        }
+        directory.extend_from_slice(&0u32.to_le_bytes());
+        directory.extend_from_slice(&offset.to_le_bytes());
+        directory.extend_from_slice(name.as_bytes());
+    }
+
+    let count = files.len() as u16;
+    let (Ok(start), Ok(length)) = (u32::try_from(archive.len()), u32::try_from(directory.len())) else {
+        bail!("The book is too big for an EPUB (over 4 GB)");
+    };
+    archive.extend_from_slice(&directory);
+    archive.extend_from_slice(&0x06054b50u32.to_le_bytes());
+    for value in [0u16, 0, count, count] {
+        archive.extend_from_slice(&value.to_le_bytes());
+    }
+    archive.extend_from_slice(&length.to_le_bytes());
+    archive.extend_from_slice(&start.to_le_bytes());
+    archive.extend_from_slice(&0u16.to_le_bytes());
+    Ok(archive)
+}
@@ -0,0 +1,216 @@
+use regex::Regex;
+use std::sync::OnceLock;
+
+use crate::document::{parse_inline, Block, Document, Inline};
+use crate::fences::{fence_for, parse_fence, Fence};
+use crate::redaction::REDACTED;
+
+/// Render a document as GitHub-flavoured markdown.
+pub fn render(document: &Document) -> String {
+    render_blocks(&document.blocks)
+}
+
+/// Render blocks one after another, with a blank line between each.
+fn render_blocks(blocks: &[Block]) -> String {
+    blocks.iter().map(render_block).collect::<Vec<_>>().join("\n")
+}
+
+pub(crate) fn render_block(block: &Block) -> String {
+    match block {
+        // GitHub makes its own anchors from the heading text, so ids need no markup
+        Block::Heading {
+            level, text, properties, ..
+        } => {
+            let mut heading = format!("{} {}\n", "#".repeat(*level), heading_text(text));
+            heading.push_str(&fields(properties));
+            heading
+        }
+        Block::Paragraph(text) => format!("{}\n", prose(text)),
+        Block::Fields(values) => fields(values),
+        Block::List { ordered, items } => items
+            .iter()
+            .enumerate()
+            .map(|(i, item)| match ordered {
+                true => format!("{}. {}\n", i + 1, line(item)),
+                false => format!("- {}\n", line(item)),
+            })
+            .collect(),
+        Block::Code { language, text } => {
+            let fence = fence_for(text);
+            format!("{}{}\n{}{}\n", fence, language, with_newline(text), fence)
+        }
+        // Markdown isn't rendered inside <summary>, so it gets HTML instead
+        Block::Details { summary, blocks } => format!(
+            "<details>\n<summary>{}</summary>\n\n{}\n</details>\n",
+            html_inline(summary),
+            render_blocks(blocks)
+        ),
+        Block::Message { speaker, sent_at, text } => match sent_at {
+            Some(sent_at) => format!("**{}** ({}):{}\n", line(speaker), line(sent_at), message_body(text)),
+            None => format!("**{}**:{}\n", line(speaker), message_body(text)),
+        },
+        Block::Rule => "---\n".to_string(),
+    }
+}
+
+/// `**Label**: value` lines.
+fn fields(values: &[(String, String)]) -> String {
+    values
+        .iter()
+        .map(|(label, value)| format!("**{}**: {}\n", line(label), line(value)))
+        .collect()
+}
+
+/// Inline markup as HTML, for the places markdown syntax isn't rendered.
+fn html_inline(text: &str) -> String {
+    parse_inline(text)
+        .into_iter()
+        .map(|span| match span {
+            Inline::Text(text) => html_escape(&text),
+            Inline::Bold(text) => format!("<b>{}</b>", html_escape(&text)),
+            Inline::Italic(text) => format!("<i>{}</i>", html_escape(&text)),
+            Inline::Code(text) => format!("<code>{}</code>", html_escape(&text)),
+            Inline::Link { text, target } => {
+                format!("<a href=\"{}\">{}</a>", html_escape(&safe_target(&target)), html_escape(&text))
+            }
+        })
+        .collect()
+}
+
+/// Code blocks need their closing fence on its own line.
+pub(crate) fn with_newline(text: &str) -> String {
+    if text.is_empty() || text.ends_with('\n') {
+        text.to_string()
+    } else {
+        format!("{}\n", text)
+    }
+}
+
+/// Text for HTML, with the characters that mean something there escaped.
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// A link target that can't run anything: `javascript:`, `vbscript:` and
+/// `data:` links go nowhere instead.
+fn safe_target(target: &str) -> String {
+    let scheme: String = target.trim_start().chars().take(11).collect::<String>().to_lowercase();
+    if ["javascript:", "vbscript:", "data:"].iter().any(|unsafe_scheme| scheme.starts_with(unsafe_scheme)) {
+        "#".to_string()
+    } else {
+        target.to_string()
+    }
+}
+
+// Chat content is markdown that goes into a markdown document, so what it
+// says can't be allowed to change the document around it. The functions
+// below keep its inline markup (bold, links, inline code) and its code
+// blocks, but escape what would start a heading, a rule or a block of HTML,
+// or leave a code block open past the end of the message.
+
+/// A heading's text: one line, with no HTML, and no trailing `#`s for
+/// markdown to take as the heading's closing sequence.
+fn heading_text(text: &str) -> String {
+    let text = line(text);
+    match text.strip_suffix('#') {
+        Some(rest) if rest.trim_end_matches('#').ends_with(' ') || rest.trim_end_matches('#').is_empty() => {
+            let hashes = text.len() - text.trim_end_matches('#').len();
+            format!("{}\\{}", &text[..text.len() - hashes], "#".repeat(hashes))
+        }
+        _ => text,
+    }
+}
+
+/// Text that has to stay on one line (a list item, a field): line breaks
+/// become spaces, and what would make it a heading or a rule is escaped.
+fn line(text: &str) -> String {
+    let joined = text.lines().map(str::trim).filter(|part| !part.is_empty()).collect::<Vec<_>>().join(" ");
+    escape_line(&joined)
+}
+
+/// Text that may run over several lines, like a message. Code blocks are
+/// left alone (one left open is closed), and every other line is escaped
+/// so it can't start a heading, a rule or an HTML block.
+fn prose(text: &str) -> String {
+    let mut out: Vec<String> = Vec::new();
+    let mut open: Option<Fence> = None;
+    for text_line in text.lines() {
+        match (&open, parse_fence(text_line)) {
+            (None, Some(fence)) => open = Some(fence),
+            (Some(fence), Some(found)) if found.closes(fence) => open = None,
+            (Some(_), _) => {}
+            (None, None) => {
+                out.push(escape_line(text_line));
+                continue;
+            }
+        }
+        out.push(text_line.to_string());
+    }
+    if let Some(fence) = open {
+        out.push(format!("{}{}", " ".repeat(fence.indent), fence.markers()));
+    }
+    out.join("\n")
+}
+
+/// A message's text, to follow its speaker: on the same line, unless it
+/// opens with a code block. A fence only counts at the start of a line, so
+/// one left after the speaker wouldn't open the block `prose` sees opening.
+fn message_body(text: &str) -> String {
+    let text = text.trim();
+    match text.lines().next().and_then(parse_fence) {
+        Some(_) => format!("\n{}", prose(text)),
+        None => format!(" {}", prose(text)),
+    }
+}
+
+/// One line of text outside code blocks, escaped: a leading `#` (a
+/// heading), a line of `-`, `=`, `*` or `_` (a rule, or the underline that
+/// makes the line before it a heading), HTML tags, and links that would
+/// run script.
+fn escape_line(text: &str) -> String {
+    static SCRIPT_LINK: OnceLock<Regex> = OnceLock::new();
+    let script_link =
+        SCRIPT_LINK.get_or_init(|| Regex::new(r"(?i)\]\(\s*(javascript|vbscript|data):").expect("valid regex"));
+
+    let trimmed = text.trim_start();
+    let indent = &text[..text.len() - trimmed.len()];
+    let hashes = trimmed.chars().take_while(|&c| c == '#').count();
+    let heading = (1..=6).contains(&hashes) && trimmed[hashes..].chars().next().is_none_or(|c| c == ' ' || c == '\t');
+    let marks: Vec<char> = trimmed.chars().filter(|c| !c.is_whitespace()).collect();
+    let rule = marks.first().is_some_and(|first| {
+        "-=*_".contains(*first) && marks.iter().all(|c| c == first) && (marks.len() >= 3 || "-=".contains(*first))
+    });
+    let escaped = if heading || rule { format!("{}\\{}", indent, trimmed) } else { text.to_string() };
+
+    let escaped = escape_tags(&escaped);
+    script_link.replace_all(&escaped, "](#").into_owned()
+}
+
+/// What the lore puts in place of secrets and paths. They look like tags
+/// but are left as they are.
+const PLACEHOLDERS: &[&str] = &[REDACTED, "<PROJECT_PATH>", "<DB_PATH>"];
+
+/// Escape the `<` of anything that looks like an HTML tag, comment or
+/// declaration, outside inline code (where it shows as it is anyway).
+fn escape_tags(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(at) = rest.find(['<', '`']) {
+        escaped.push_str(&rest[..at]);
+        rest = &rest[at..];
+        if rest.starts_with('`') {
+            // A code span runs to the next run of as many backticks
+            let ticks = rest.chars().take_while(|&c| c == '`').count();
+            let end = rest[ticks..].find(&"`".repeat(ticks)).map_or(ticks, |end| ticks + end + ticks);
+            escaped.push_str(&rest[..end]);
+            rest = &rest[end..];
+            continue;
+        }
+        let placeholder = PLACEHOLDERS.iter().any(|placeholder| rest.starts_with(placeholder));
+        let tag = !placeholder && rest[1..].chars().next().is_some_and(|c| c.is_ascii_alphabetic() || "/!?".contains(c));
+        escaped.push_str(if tag { "&lt;" } else { "<" });
+        rest = &rest[1..];
+    }
+    escaped.push_str(rest);
+    escaped
+}
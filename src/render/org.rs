@@ -0,0 +1,161 @@
+use crate::document::{parse_inline, split_message, Block, Document, Inline, MessagePart};
+use crate::render::markdown::with_newline;
+
+/// Render a document as Emacs org-mode. Headings with properties get a
+/// properties drawer (and a `CUSTOM_ID` when they have an id, so links to
+/// them work), and dated headings get an inactive timestamp.
+pub fn render(document: &Document) -> String {
+    render_blocks(&document.blocks)
+}
+
+fn render_blocks(blocks: &[Block]) -> String {
+    blocks.iter().map(render_block).collect::<Vec<_>>().join("\n")
+}
+
+pub(crate) fn render_block(block: &Block) -> String {
+    match block {
+        Block::Heading { level: 1, text, .. } => format!("#+TITLE: {}\n", plain(text)),
+        Block::Heading {
+            level,
+            text,
+            id,
+            timestamp,
+            properties,
+        } => {
+            let mut heading = format!("{} {}\n", "*".repeat(level - 1), inline(text));
+            if id.is_some() || !properties.is_empty() {
+                heading.push_str(":PROPERTIES:\n");
+                if let Some(id) = id {
+                    heading.push_str(&format!(":CUSTOM_ID: {}\n", id));
+                }
+                for (label, value) in properties {
+                    heading.push_str(&format!(":{}: {}\n", property_name(label), plain(value)));
+                }
+                heading.push_str(":END:\n");
+            }
+            if let Some(timestamp) = timestamp {
+                heading.push_str(&format!("{}\n", timestamp.format("[%Y-%m-%d %a %H:%M]")));
+            }
+            heading
+        }
+        Block::Paragraph(text) => format!("{}\n", prose(text)),
+        Block::Fields(values) => values
+            .iter()
+            .map(|(label, value)| format!("- {} :: {}\n", plain(label), inline(value)))
+            .collect(),
+        Block::List { ordered, items } => items
+            .iter()
+            .enumerate()
+            .map(|(i, item)| match ordered {
+                true => format!("{}. {}\n", i + 1, inline(item)),
+                false => format!("- {}\n", inline(item)),
+            })
+            .collect(),
+        Block::Code { language, text } => source_block(language, text),
+        Block::Details { summary, blocks } => format!(
+            "#+begin_details\n{}\n\n{}#+end_details\n",
+            inline(summary),
+            render_blocks(blocks)
+        ),
+        Block::Message { speaker, sent_at, text } => {
+            let mut message = match sent_at {
+                Some(sent_at) => format!("*{}* ({}):", speaker, sent_at),
+                None => format!("*{}*:", speaker),
+            };
+            for (i, part) in split_message(text).into_iter().enumerate() {
+                match part {
+                    MessagePart::Prose(text) if i == 0 => message.push_str(&format!(" {}\n", prose(&text))),
+                    MessagePart::Prose(text) => message.push_str(&format!("\n{}\n", prose(&text))),
+                    MessagePart::Code { language, text } => {
+                        if i == 0 {
+                            message.push('\n');
+                        }
+                        message.push('\n');
+                        message.push_str(&source_block(&language, &text));
+                    }
+                }
+            }
+            if !message.ends_with('\n') {
+                message.push('\n');
+            }
+            message
+        }
+        Block::Rule => "-----\n".to_string(),
+    }
+}
+
+/// A `#+begin_src` block, or `#+begin_example` when there's no language.
+/// Lines org would read as headings or keywords are escaped with a comma.
+fn source_block(language: &str, text: &str) -> String {
+    let escaped: String = with_newline(text)
+        .lines()
+        .map(|line| {
+            if line.starts_with('*') || line.starts_with("#+") || line.starts_with(",*") || line.starts_with(",#+") {
+                format!(",{}\n", line)
+            } else {
+                format!("{}\n", line)
+            }
+        })
+        .collect();
+    if language.is_empty() {
+        format!("#+begin_example\n{}#+end_example\n", escaped)
+    } else {
+        format!("#+begin_src {}\n{}#+end_src\n", language, escaped)
+    }
+}
+
+/// Inline markup in org syntax.
+fn inline(text: &str) -> String {
+    parse_inline(text)
+        .into_iter()
+        .map(|span| match span {
+            // `@@` starts an export snippet, which can hold raw HTML
+            Inline::Text(text) => text.replace("@@", "@\u{200B}@"),
+            Inline::Bold(text) => format!("*{}*", text),
+            Inline::Italic(text) => format!("/{}/", text),
+            Inline::Code(text) if text.contains('~') => format!("={}=", text),
+            Inline::Code(text) => format!("~{}~", text),
+            Inline::Link { text, target } => format!("[[{}][{}]]", target, text),
+        })
+        .collect()
+}
+
+/// Text that may run over several lines, in org syntax. Chat content
+/// can't be allowed to start headings or keywords (`#+begin_export html`),
+/// so lines that would are started with a zero-width space, the escape
+/// the org manual suggests.
+fn prose(text: &str) -> String {
+    inline(text)
+        .lines()
+        .map(|line| {
+            let stars = line.chars().take_while(|&c| c == '*').count();
+            let heading = stars > 0 && line[stars..].starts_with(' ');
+            if heading || line.trim_start().starts_with("#+") {
+                format!("\u{200B}{}", line)
+            } else {
+                line.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Inline text with the markup taken out, for property values and titles.
+fn plain(text: &str) -> String {
+    parse_inline(text)
+        .into_iter()
+        .map(|span| match span {
+            Inline::Text(text) | Inline::Bold(text) | Inline::Italic(text) | Inline::Code(text) => text,
+            Inline::Link { text, .. } => text,
+        })
+        .collect()
+}
+
+/// Turn a field label into a property name: "Session ID" becomes `SESSION_ID`.
+fn property_name(label: &str) -> String {
+    label
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join("_")
+        .to_uppercase()
+}
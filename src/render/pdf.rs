@@ -0,0 +1,83 @@
+use anyhow::{anyhow, Result};
+use chrono::Datelike;
+use typst::diag::{FileError, FileResult};
+use typst::foundations::{Bytes, Datetime};
+use typst::layout::PagedDocument;
+use typst::syntax::{FileId, Source, VirtualPath};
+use typst::text::{Font, FontBook};
+use typst::utils::LazyHash;
+use typst::{Library, World};
+
+/// Typeset Typst markup (as the `typst` renderer writes it) into a PDF,
+/// in-process. Fonts come embedded in the binary, so the result looks the
+/// same wherever it's built and nothing needs to be installed.
+pub fn compile(markup: &str) -> Result<Vec<u8>> {
+    let world = LoreWorld::new(markup);
+    let document: PagedDocument = typst::compile(&world).output.map_err(|errors| {
+        let messages: Vec<String> = errors.iter().map(|error| error.message.to_string()).collect();
+        anyhow!("Couldn't typeset the PDF: {}", messages.join("; "))
+    })?;
+    typst_pdf::pdf(&document, &typst_pdf::PdfOptions::default()).map_err(|errors| {
+        let messages: Vec<String> = errors.iter().map(|error| error.message.to_string()).collect();
+        anyhow!("Couldn't write the PDF: {}", messages.join("; "))
+    })
+}
+
+/// Everything the Typst compiler can see: the standard library, the
+/// embedded fonts, and the one source file we hand it. There are no other
+/// files - the lore doesn't include images or other documents.
+struct LoreWorld {
+    library: LazyHash<Library>,
+    book: LazyHash<FontBook>,
+    fonts: Vec<Font>,
+    source: Source,
+}
+
+impl LoreWorld {
+    fn new(markup: &str) -> Self {
+        let fonts: Vec<Font> = typst_assets::fonts()
+            .flat_map(|data| Font::iter(Bytes::new(data)))
+            .collect();
+        LoreWorld {
+            library: LazyHash::new(Library::default()),
+            book: LazyHash::new(FontBook::from_fonts(&fonts)),
+            fonts,
+            source: Source::new(FileId::new(None, VirtualPath::new("/lore.typ")), markup.to_string()),
+        }
+    }
+}
+
+impl World for LoreWorld {
+    fn library(&self) -> &LazyHash<Library> {
+        &self.library
+    }
+
+    fn book(&self) -> &LazyHash<FontBook> {
+        &self.book
+    }
+
+    fn main(&self) -> FileId {
+        self.source.id()
+    }
+
+    fn source(&self, id: FileId) -> FileResult<Source> {
+        if id == self.source.id() {
+            Ok(self.source.clone())
+        } else {
+            Err(FileError::NotFound(id.vpath().as_rootless_path().into()))
+        }
+    }
+
+    fn file(&self, id: FileId) -> FileResult<Bytes> {
+        Err(FileError::NotFound(id.vpath().as_rootless_path().into()))
+    }
+
+    fn font(&self, index: usize) -> Option<Font> {
+        self.fonts.get(index).cloned()
+    }
+
+    fn today(&self, offset: Option<i64>) -> Option<Datetime> {
+        let now = chrono::Utc::now() + chrono::Duration::hours(offset.unwrap_or(0));
+        Datetime::from_ymd(now.year(), now.month().try_into().ok()?, now.day().try_into().ok()?)
+    }
+}
@@ -0,0 +1,206 @@
+use crate::document::{parse_inline, split_message, Block, Document, Inline, MessagePart};
+
+/// Page setup and styling shared by every typeset lore: A4 pages with
+/// numbers, code in a shaded monospace box, and coloured links.
+const PREAMBLE: &str = r##"#set page(paper: "a4", margin: (x: 2.2cm, y: 2.5cm), numbering: "1")
+#set text(font: "Libertinus Serif", size: 10.5pt)
+#set par(justify: true)
+#show heading: set block(above: 1.4em, below: 0.8em)
+#show raw: set text(font: "DejaVu Sans Mono", size: 8.5pt)
+#show raw.where(block: true): block.with(fill: luma(245), inset: 8pt, radius: 3pt, width: 100%)
+#show link: set text(fill: rgb("#1a5fb4"))
+"##;
+
+/// Render a document as Typst markup, ready to be typeset into a PDF.
+/// The title and everything before the first section go on a cover page,
+/// followed by a table of contents on a page of its own.
+pub fn render(document: &Document) -> String {
+    let (cover, body) = split_cover(&document.blocks);
+    let mut out = render_cover(cover);
+    out.push_str(&render_blocks(body));
+    out
+}
+
+/// Everything that comes before the first section: the preamble, the
+/// cover page made from `cover`, and the table of contents.
+pub(crate) fn render_cover(cover: &[Block]) -> String {
+    let mut out = String::from(PREAMBLE);
+
+    let title = cover.iter().find_map(|block| match block {
+        Block::Heading { level: 1, text, .. } => Some(text.as_str()),
+        _ => None,
+    });
+    if let Some(title) = title {
+        out.push_str(&format!("#set document(title: {})\n", string(&plain(title))));
+    }
+    out.push('\n');
+
+    out.push_str("#page(numbering: none)[\n#align(center + horizon)[\n");
+    if let Some(title) = title {
+        out.push_str(&format!("#text(size: 26pt, weight: \"bold\")[{}]\n\n#v(2em)\n\n", inline(title)));
+    }
+    for block in cover.iter().filter(|block| !matches!(block, Block::Heading { level: 1, .. })) {
+        out.push_str(&render_block(block));
+        out.push('\n');
+    }
+    out.push_str("]\n]\n\n");
+
+    out.push_str("#outline(depth: 3)\n#pagebreak()\n\n");
+    out
+}
+
+/// The blocks up to the first section heading, and the rest.
+fn split_cover(blocks: &[Block]) -> (&[Block], &[Block]) {
+    let split = blocks
+        .iter()
+        .position(|block| matches!(block, Block::Heading { level, .. } if *level > 1))
+        .unwrap_or(blocks.len());
+    blocks.split_at(split)
+}
+
+fn render_blocks(blocks: &[Block]) -> String {
+    blocks.iter().map(render_block).collect::<Vec<_>>().join("\n")
+}
+
+pub(crate) fn render_block(block: &Block) -> String {
+    match block {
+        // The title is on the cover page, so sections start at `=`
+        Block::Heading {
+            level, text, id, properties, ..
+        } => {
+            let mut heading = format!("{} {}", "=".repeat(level.saturating_sub(1).max(1)), inline(text));
+            if let Some(id) = id {
+                heading.push_str(&format!(" <{}>", id));
+            }
+            heading.push('\n');
+            if !properties.is_empty() {
+                heading.push('\n');
+                heading.push_str(&term_list(properties));
+            }
+            heading
+        }
+        Block::Paragraph(text) => format!("{}\n", inline(text)),
+        Block::Fields(values) => term_list(values),
+        Block::List { ordered, items } => items
+            .iter()
+            .map(|item| format!("{} {}\n", if *ordered { "+" } else { "-" }, inline(item)))
+            .collect(),
+        Block::Code { language, text } => raw_block(language, text),
+        // Paper can't fold, so collapsed content is just set off in a box
+        Block::Details { summary, blocks } => format!(
+            "#block(stroke: luma(200), inset: 8pt, radius: 3pt, width: 100%)[\n#strong[{}]\n\n{}]\n",
+            inline(summary),
+            render_blocks(blocks)
+        ),
+        Block::Message { speaker, sent_at, text } => {
+            let mut message = match sent_at {
+                Some(sent_at) => format!("#strong[{}] ({}):", escape(speaker), escape(sent_at)),
+                None => format!("#strong[{}]:", escape(speaker)),
+            };
+            for (i, part) in split_message(text).into_iter().enumerate() {
+                match part {
+                    MessagePart::Prose(prose) if i == 0 => message.push_str(&format!(" {}\n", inline(&prose))),
+                    MessagePart::Prose(prose) => message.push_str(&format!("\n{}\n", inline(&prose))),
+                    MessagePart::Code { language, text } => {
+                        message.push_str("\n\n");
+                        message.push_str(&raw_block(&language, &text));
+                    }
+                }
+            }
+            if !message.ends_with('\n') {
+                message.push('\n');
+            }
+            message
+        }
+        Block::Rule => "#line(length: 100%, stroke: luma(200))\n".to_string(),
+    }
+}
+
+/// `/ Label: value` lines.
+fn term_list(values: &[(String, String)]) -> String {
+    values
+        .iter()
+        .map(|(label, value)| format!("/ {}: {}\n", escape(label), inline(value)))
+        .collect()
+}
+
+/// A code block. It goes through `raw()` with the code as a string, so
+/// nothing in the code (backticks included) can end the block early.
+fn raw_block(language: &str, text: &str) -> String {
+    if language.is_empty() {
+        format!("#raw(block: true, {})\n", string(text.trim_end_matches('\n')))
+    } else {
+        format!(
+            "#raw(block: true, lang: {}, {})\n",
+            string(language),
+            string(text.trim_end_matches('\n'))
+        )
+    }
+}
+
+/// Inline markup in Typst syntax. Styled runs use function calls rather
+/// than `*`/`_` shorthand so their contents can't unbalance anything.
+fn inline(text: &str) -> String {
+    parse_inline(text)
+        .into_iter()
+        .map(|span| match span {
+            Inline::Text(text) => escape(&text),
+            Inline::Bold(text) => format!("#strong[{}]", escape(&text)),
+            Inline::Italic(text) => format!("#emph[{}]", escape(&text)),
+            Inline::Code(text) => format!("#raw({})", string(&text)),
+            Inline::Link { text, target } => match target.strip_prefix('#') {
+                Some(id) => format!("#link(<{}>)[{}]", id, escape(&text)),
+                None => format!("#link({})[{}]", string(&target), escape(&text)),
+            },
+        })
+        .collect()
+}
+
+/// Inline text with its markup stripped, for places that take plain text
+/// like the PDF's title metadata.
+fn plain(text: &str) -> String {
+    parse_inline(text)
+        .into_iter()
+        .map(|span| match span {
+            Inline::Text(text) | Inline::Bold(text) | Inline::Italic(text) | Inline::Code(text) => text,
+            Inline::Link { text, .. } => text,
+        })
+        .collect()
+}
+
+/// Escape text so Typst reads it literally. Typst markup has a lot of
+/// shorthand (`*`, `_`, `#`, `@`, `<`, `//`, `-` lists, `=` headings, ...)
+/// and a backslash in front of any character makes it plain, so every
+/// character that could start something gets one. `(` is included because
+/// right after a `#strong[...]` it would continue the call.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(
+            c,
+            '\\' | '#' | '*' | '_' | '`' | '$' | '<' | '>' | '@' | '[' | ']' | '~' | '/' | '=' | '-' | '+' | '('
+        ) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// A Typst string literal.
+fn string(text: &str) -> String {
+    let mut literal = String::with_capacity(text.len() + 2);
+    literal.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => literal.push_str("\\\""),
+            '\\' => literal.push_str("\\\\"),
+            '\n' => literal.push_str("\\n"),
+            '\r' => literal.push_str("\\r"),
+            '\t' => literal.push_str("\\t"),
+            c => literal.push(c),
+        }
+    }
+    literal.push('"');
+    literal
+}
@@ -0,0 +1,319 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use crate::atomic::write_atomic;
+use crate::warnings::Warning;
+
+/// What went wrong, broadly, when a run fails. Attach one to an error with
+/// `.context(Failure::Source)` and the process exits with its code, so
+/// scripts can tell a missing database from a wiki that's down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Failure {
+    /// The command line or the config file is wrong
+    Config,
+    /// The chat history couldn't be read
+    Source,
+    /// The lore couldn't be rendered or written
+    Output,
+    /// Publishing the lore failed
+    Publish,
+    /// Another run holds the lock on this project
+    Locked,
+}
+
+impl Failure {
+    /// The exit code for this kind of failure.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Failure::Config => exit_code::CONFIG,
+            Failure::Source => exit_code::SOURCE,
+            Failure::Output => exit_code::OUTPUT,
+            Failure::Publish => exit_code::PUBLISH,
+            Failure::Locked => exit_code::LOCKED,
+        }
+    }
+
+    /// The kind of failure an error was marked with, if any.
+    pub fn of(error: &anyhow::Error) -> Option<Failure> {
+        error.downcast_ref::<Failure>().copied()
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Failure::Config => "Invalid configuration",
+            Failure::Source => "Could not read the chat history",
+            Failure::Output => "Could not write the lore",
+            Failure::Publish => "Could not publish the lore",
+            Failure::Locked => "Another run is in progress",
+        })
+    }
+}
+
+/// The process exit codes, which are part of the command-line interface:
+/// scripts depend on them, so they never change meaning.
+pub mod exit_code {
+    /// Everything worked
+    pub const SUCCESS: i32 = 0;
+    /// Something failed that doesn't fit a more specific code
+    pub const OTHER: i32 = 1;
+    /// The command line or config file is wrong (clap uses 2 for bad
+    /// arguments too)
+    pub const CONFIG: i32 = 2;
+    /// The chat history couldn't be read
+    pub const SOURCE: i32 = 3;
+    /// The lore couldn't be rendered or written
+    pub const OUTPUT: i32 = 4;
+    /// Publishing failed
+    pub const PUBLISH: i32 = 5;
+    /// The lore was written (or published), but a step after it - like
+    /// posting the digest - failed
+    pub const PARTIAL: i32 = 6;
+    /// `lint` found problems in the lore
+    pub const LINT: i32 = 7;
+    /// `batch` ran, but some projects failed (or were skipped after one did)
+    pub const BATCH: i32 = 8;
+    /// `verify` found the lore no longer matches the chat history
+    pub const VERIFY: i32 = 9;
+    /// Another run holds the lock on this project, and neither `--wait`
+    /// nor `--force` was given
+    pub const LOCKED: i32 = 10;
+    /// Interrupted with Ctrl-C
+    pub const INTERRUPTED: i32 = 130;
+}
+
+/// The exit code for a run's result.
+pub fn exit_code_for(result: &Result<()>) -> i32 {
+    match result {
+        Ok(()) => exit_code::SUCCESS,
+        Err(error) => Failure::of(error).map_or(exit_code::OTHER, |failure| failure.exit_code()),
+    }
+}
+
+/// How a run ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportStatus {
+    /// Everything worked (there may still be warnings)
+    Succeeded,
+    /// The lore was written but a later step failed
+    Partial,
+    /// The run failed
+    Failed,
+}
+
+/// How long one stage of a run took.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct StageTiming {
+    /// The stage: backup, extract, snapshot, render, write, publish or notify
+    pub stage: String,
+    /// How long it took, in milliseconds
+    pub millis: u64,
+}
+
+/// How much a run found.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct RunCounts {
+    /// Sessions extracted
+    pub sessions: usize,
+    /// Messages in those sessions
+    pub messages: usize,
+    /// Generations extracted
+    pub generations: usize,
+    /// Prompts extracted
+    pub prompts: usize,
+    /// Malformed records that were skipped
+    pub skipped_records: usize,
+    /// Secrets blanked out of the lore
+    pub redactions: usize,
+}
+
+/// A file the run wrote.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct OutputFile {
+    /// Where it is
+    pub path: PathBuf,
+    /// Its size in bytes
+    pub bytes: u64,
+    /// The SHA-256 of its contents
+    pub sha256: String,
+}
+
+/// A machine-readable account of one run, for `--report-json`: what it
+/// found, how long each stage took, what it wrote, and how it ended.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct RunReport {
+    /// The version of this program that ran
+    pub version: String,
+    /// Which source was read
+    pub source: String,
+    /// When the run started
+    pub started_at: DateTime<Utc>,
+    /// When it ended
+    pub finished_at: DateTime<Utc>,
+    /// How long it took, in milliseconds
+    pub duration_millis: u64,
+    /// How it ended
+    pub status: ReportStatus,
+    /// The exit code the process ended with
+    pub exit_code: i32,
+    /// What kind of failure ended it, if one did
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failure: Option<Failure>,
+    /// The error that ended it, if one did
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// How much it found
+    pub counts: RunCounts,
+    /// How long each stage took, in the order they ran
+    pub stages: Vec<StageTiming>,
+    /// Things that went wrong without stopping the run
+    pub warnings: Vec<String>,
+    /// What was wrong with the chat history itself, one entry per record:
+    /// records left out, timestamps missing or unreadable
+    #[serde(default)]
+    pub data_warnings: Vec<Warning>,
+    /// The files it wrote
+    pub outputs: Vec<OutputFile>,
+    /// Pages it published, by title
+    pub published: Vec<String>,
+    /// Whether it stopped after extracting, having found nothing changed
+    /// since the last run (SKIP_UNCHANGED)
+    #[serde(default)]
+    pub unchanged: bool,
+    /// The most memory the process held at once, in bytes, where the
+    /// platform reports it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub peak_memory_bytes: Option<u64>,
+    #[serde(skip)]
+    clock: Option<Instant>,
+    #[serde(skip)]
+    partial: bool,
+}
+
+impl RunReport {
+    /// Start the report for a run reading `source`.
+    pub fn start(source: &str) -> Self {
+        let now = Utc::now();
+        RunReport {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            source: source.to_string(),
+            started_at: now,
+            finished_at: now,
+            duration_millis: 0,
+            status: ReportStatus::Succeeded,
+            exit_code: exit_code::SUCCESS,
+            failure: None,
+            error: None,
+            counts: RunCounts::default(),
+            stages: Vec::new(),
+            warnings: Vec::new(),
+            data_warnings: Vec::new(),
+            outputs: Vec::new(),
+            published: Vec::new(),
+            unchanged: false,
+            peak_memory_bytes: None,
+            clock: Some(Instant::now()),
+            partial: false,
+        }
+    }
+
+    /// Record a stage that started at `started`.
+    pub fn stage(&mut self, stage: &str, started: Instant) {
+        self.stages.push(StageTiming {
+            stage: stage.to_string(),
+            millis: started.elapsed().as_millis() as u64,
+        });
+    }
+
+    /// Record something that went wrong without stopping the run.
+    pub fn warn(&mut self, warning: impl Into<String>) {
+        self.warnings.push(warning.into());
+    }
+
+    /// Record a step after the lore was written failing, which makes the
+    /// run partial.
+    pub fn fail_after_output(&mut self, warning: impl Into<String>) {
+        self.warn(warning);
+        self.partial = true;
+    }
+
+    /// Record a file the run wrote, with its size and checksum.
+    pub fn output(&mut self, path: &Path) -> Result<()> {
+        let data = fs::read(path).with_context(|| format!("Could not read back {}", path.display()))?;
+        self.outputs.push(OutputFile {
+            path: path.to_path_buf(),
+            bytes: data.len() as u64,
+            sha256: Sha256::digest(&data).iter().map(|byte| format!("{:02x}", byte)).collect(),
+        });
+        Ok(())
+    }
+
+    /// Close the report with the run's result, returning the exit code
+    /// the process should end with.
+    pub fn finish(&mut self, result: &Result<()>) -> i32 {
+        self.finished_at = Utc::now();
+        self.duration_millis = self.clock.map_or(0, |clock| clock.elapsed().as_millis() as u64);
+        self.exit_code = exit_code_for(result);
+        self.peak_memory_bytes = peak_memory();
+        match result {
+            Err(error) => {
+                self.status = ReportStatus::Failed;
+                self.failure = Failure::of(error);
+                self.error = Some(format!("{:#}", error));
+            }
+            Ok(()) if self.partial => {
+                self.status = ReportStatus::Partial;
+                self.exit_code = exit_code::PARTIAL;
+            }
+            Ok(()) => self.status = ReportStatus::Succeeded,
+        }
+        self.exit_code
+    }
+
+    /// The stage timings and peak memory as a table, for `--profile-run`.
+    pub fn profile(&self) -> String {
+        let mut profile = format!("{:<10} {:>10}\n", "stage", "ms");
+        for stage in &self.stages {
+            profile.push_str(&format!("{:<10} {:>10}\n", stage.stage, stage.millis));
+        }
+        profile.push_str(&format!("{:<10} {:>10}\n", "total", self.duration_millis));
+        match self.peak_memory_bytes {
+            Some(bytes) => profile.push_str(&format!("peak memory: {:.1} MiB\n", bytes as f64 / (1024.0 * 1024.0))),
+            None => profile.push_str("peak memory: not available on this platform\n"),
+        }
+        profile
+    }
+
+    /// Write the report as pretty JSON to `path`, or to stdout for `-`.
+    pub fn write(&self, path: &str) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)? + "\n";
+        if path == "-" {
+            print!("{}", json);
+            return Ok(());
+        }
+        if let Some(parent) = Path::new(path).parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        write_atomic(Path::new(path), json.as_bytes()).with_context(|| format!("Could not write the run report to {}", path))
+    }
+}
+
+/// The process's peak resident memory so far, in bytes. Only Linux makes
+/// this available without platform-specific calls (as `VmHWM` in
+/// `/proc/self/status`).
+pub fn peak_memory() -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmHWM:"))?;
+    let kilobytes: u64 = line.trim_start_matches("VmHWM:").trim().trim_end_matches("kB").trim().parse().ok()?;
+    Some(kilobytes * 1024)
+}
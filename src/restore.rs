@@ -0,0 +1,265 @@
+use anyhow::{bail, Context, Result};
+use serde_json::{json, Map, Value};
+use std::collections::{BTreeMap, HashSet};
+
+use crate::config::Config;
+use crate::{ChatGeneration, ChatMessage, ChatPrompt, ChatSession, ComposerData, MessageRole};
+
+/// Records to write back into Cursor's databases, by key: the session list,
+/// prompts and generations go in the workspace database's `ItemTable`, and
+/// each conversation in the global database's conversation table.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RestorePlan {
+    /// Records for the workspace `ItemTable`
+    pub workspace: BTreeMap<String, String>,
+    /// `composerData:<id>` records for the global conversation table
+    pub conversations: BTreeMap<String, String>,
+    /// The sessions being restored, as (ID, name)
+    pub restored: Vec<(String, String)>,
+    /// Sessions the target already has, which are left alone
+    pub already_there: Vec<(String, String)>,
+}
+
+impl RestorePlan {
+    /// What restoring would do, for `--dry-run`.
+    pub fn describe(&self) -> String {
+        let mut description = format!(
+            "Would restore {} sessions ({} transcripts) and update {} workspace records\n",
+            self.restored.len(),
+            self.conversations.len(),
+            self.workspace.len()
+        );
+        for (id, name) in &self.restored {
+            description.push_str(&format!("  + {} ({})\n", name, id));
+        }
+        for (id, name) in &self.already_there {
+            description.push_str(&format!("  = {} ({}) is already there\n", name, id));
+        }
+        description
+    }
+
+    /// The plan as one JSON document, `{"ItemTable": {...}, "<conversation
+    /// table>": {...}}`, for loading into a database by other means.
+    pub fn export(&self, config: &Config) -> Result<String> {
+        Ok(serde_json::to_string_pretty(&json!({
+            "ItemTable": self.workspace,
+            config.conversation_table.clone(): self.conversations,
+        }))?)
+    }
+}
+
+/// Pick the sessions to restore: the ones whose IDs start with one of
+/// `ids`, or every session if `ids` is empty.
+pub fn select_sessions<'a>(sessions: &'a [ComposerData], ids: &[String]) -> Result<Vec<&'a ChatSession>> {
+    let all: Vec<&ChatSession> = sessions.iter().flat_map(|data| &data.all_composers).collect();
+    if ids.is_empty() {
+        return Ok(all);
+    }
+    let mut selected = Vec::new();
+    for id in ids {
+        let matches: Vec<&ChatSession> = all.iter().copied().filter(|session| session.composer_id.starts_with(id.as_str())).collect();
+        match matches.len() {
+            0 => bail!("No session matches '{}'", id),
+            1 => selected.push(matches[0]),
+            count => bail!("'{}' matches {} sessions; use more of the ID", id, count),
+        }
+    }
+    Ok(selected)
+}
+
+/// Work out the records that put `sessions` into a Cursor workspace.
+/// `existing` holds the target's current session list, prompts and
+/// generations (by key, where it has them), which are added to rather than
+/// replaced; `existing_conversations` are the sessions whose transcripts
+/// the target already has. When only some sessions are restored, only the
+/// prompts and generations that belong to them go along.
+pub fn plan_restore(
+    config: &Config,
+    sessions: &[&ChatSession],
+    generations: &[ChatGeneration],
+    prompts: &[ChatPrompt],
+    all_sessions: bool,
+    existing: &BTreeMap<String, String>,
+    existing_conversations: &HashSet<String>,
+) -> Result<RestorePlan> {
+    let mut plan = RestorePlan::default();
+
+    // The session list, in whichever naming the target already uses
+    let mut composer_data: Value = match existing.get(&config.composer_data_key) {
+        Some(json_str) => serde_json::from_str(json_str).context("The target's session list isn't valid JSON")?,
+        None => json!({}),
+    };
+    let list_field = if composer_data.get("all_composers").is_some() { "all_composers" } else { "allComposers" };
+    let snake_case = list_field == "all_composers";
+    let list = composer_data
+        .as_object_mut()
+        .context("The target's session list isn't a JSON object")?
+        .entry(list_field)
+        .or_insert_with(|| json!([]));
+    let Value::Array(list) = list else {
+        bail!("The target's session list isn't a list");
+    };
+    let present: HashSet<String> = list
+        .iter()
+        .filter_map(|entry| entry.get("composerId").or_else(|| entry.get("composer_id")))
+        .filter_map(|id| id.as_str().map(str::to_string))
+        .collect();
+
+    let mut restored: Vec<&ChatSession> = Vec::new();
+    for session in sessions {
+        let entry = (session.composer_id.clone(), session.name.clone());
+        if present.contains(&session.composer_id) {
+            plan.already_there.push(entry);
+            continue;
+        }
+        list.push(session_header(session, snake_case));
+        plan.restored.push(entry);
+        restored.push(session);
+
+        let key = format!("composerData:{}", session.composer_id);
+        if !session.messages.is_empty() && !existing_conversations.contains(&session.composer_id) {
+            plan.conversations.insert(key, serde_json::to_string(&conversation(session))?);
+        }
+    }
+    if restored.is_empty() {
+        return Ok(plan);
+    }
+    plan.workspace.insert(config.composer_data_key.clone(), serde_json::to_string(&composer_data)?);
+
+    // Prompts and generations carry no session ID, so a partial restore
+    // takes the ones that show up in (or during) the restored sessions
+    let user_texts: HashSet<&str> = restored
+        .iter()
+        .flat_map(|session| &session.messages)
+        .filter(|message| message.role == MessageRole::User)
+        .map(|message| message.text.as_str())
+        .collect();
+    let during = |unix_ms: i64| {
+        restored
+            .iter()
+            .any(|session| unix_ms >= session.created_at && unix_ms <= session.last_updated_at.max(session.created_at))
+    };
+    let prompts: Vec<Value> = prompts
+        .iter()
+        .filter(|prompt| all_sessions || user_texts.contains(prompt.text.as_str()))
+        .map(|prompt| json!({ "text": prompt.text, "commandType": prompt.command_type }))
+        .collect();
+    let generations: Vec<Value> = generations
+        .iter()
+        .filter(|generation| all_sessions || during(generation.unix_ms))
+        .map(|generation| {
+            json!({
+                "unixMs": generation.unix_ms,
+                "generationUUID": generation.generation_uuid,
+                "type": generation.r#type,
+                "textDescription": generation.text_description,
+            })
+        })
+        .collect();
+
+    if let Some(merged) = append_new(existing.get(&config.prompts_key), prompts, |prompt| {
+        format!("{}\u{0}{}", prompt["text"], prompt["commandType"])
+    })? {
+        plan.workspace.insert(config.prompts_key.clone(), merged);
+    }
+    if let Some(merged) = append_new(existing.get(&config.generations_key), generations, |generation| {
+        generation["generationUUID"].to_string()
+    })? {
+        plan.workspace.insert(config.generations_key.clone(), merged);
+    }
+    Ok(plan)
+}
+
+/// Add `records` to an existing JSON list, skipping ones it already has
+/// (compared by `identity`, and by the `generationUUID`/`commandType`
+/// spelling of either naming). `None` if nothing was added.
+fn append_new(existing: Option<&String>, records: Vec<Value>, identity: impl Fn(&Value) -> String) -> Result<Option<String>> {
+    let mut list: Vec<Value> = match existing {
+        Some(json_str) => serde_json::from_str(json_str).context("A record in the target isn't a JSON list")?,
+        None => Vec::new(),
+    };
+    let mut seen: HashSet<String> = list.iter().map(|record| identity(&camel_case(record))).collect();
+    let before = list.len();
+    for record in records {
+        if seen.insert(identity(&record)) {
+            list.push(record);
+        }
+    }
+    if list.len() == before {
+        return Ok(None);
+    }
+    Ok(Some(serde_json::to_string(&list)?))
+}
+
+/// A record with its snake_case fields renamed the way Cursor spells them,
+/// so records from a snake_case export compare equal to ours.
+fn camel_case(record: &Value) -> Value {
+    let Value::Object(fields) = record else {
+        return record.clone();
+    };
+    let renamed: Map<String, Value> = fields
+        .iter()
+        .map(|(name, value)| {
+            let name = match name.as_str() {
+                "command_type" => "commandType",
+                "generation_uuid" => "generationUUID",
+                "unix_ms" => "unixMs",
+                "text_description" => "textDescription",
+                other => other,
+            };
+            (name.to_string(), value.clone())
+        })
+        .collect();
+    Value::Object(renamed)
+}
+
+/// A session's entry in the workspace session list.
+fn session_header(session: &ChatSession, snake_case: bool) -> Value {
+    let name = |camel: &'static str, snake: &'static str| if snake_case { snake } else { camel };
+    let mut header = Map::new();
+    header.insert("type".to_string(), json!(session.session_type));
+    header.insert(name("composerId", "composer_id").to_string(), json!(session.composer_id));
+    header.insert("name".to_string(), json!(session.name));
+    header.insert(name("lastUpdatedAt", "last_updated_at").to_string(), json!(session.last_updated_at));
+    header.insert(name("createdAt", "created_at").to_string(), json!(session.created_at));
+    header.insert(name("unifiedMode", "unified_mode").to_string(), json!(session.unified_mode));
+    header.insert(name("forceMode", "force_mode").to_string(), json!(session.force_mode));
+    header.insert(name("hasUnreadMessages", "has_unread_messages").to_string(), json!(false));
+    Value::Object(header)
+}
+
+/// A session's transcript in the older inline layout (the whole
+/// conversation under one key), which current Cursor versions still read.
+/// Text, timestamps, models and token counts make it across; tool calls,
+/// edits and attached files don't.
+fn conversation(session: &ChatSession) -> Value {
+    let bubbles: Vec<Value> = session.messages.iter().map(bubble).collect();
+    json!({
+        "composerId": session.composer_id,
+        "name": session.name,
+        "createdAt": session.created_at,
+        "lastUpdatedAt": session.last_updated_at,
+        "conversation": bubbles,
+    })
+}
+
+fn bubble(message: &ChatMessage) -> Value {
+    let mut bubble = json!({
+        "bubbleId": uuid::Uuid::new_v4().to_string(),
+        "type": match message.role {
+            MessageRole::User => 1,
+            MessageRole::Assistant => 2,
+        },
+        "text": message.text,
+    });
+    if let Some(timestamp) = message.timestamp {
+        bubble["timingInfo"] = json!({ "clientStartTime": timestamp });
+    }
+    if let Some(model) = &message.model {
+        bubble["modelInfo"] = json!({ "modelName": model });
+    }
+    if let Some(usage) = message.usage {
+        bubble["tokenCount"] = json!({ "inputTokens": usage.input_tokens, "outputTokens": usage.output_tokens });
+    }
+    bubble
+}
@@ -0,0 +1,180 @@
+use anyhow::{anyhow, bail, Context, Result};
+use regex::Regex;
+
+use crate::redaction::{redact_emails, redact_home_dirs, redact_secrets, Identities, REDACTED};
+
+/// The built-in detectors a `builtin:` rule can name.
+pub const BUILTINS: &[&str] = &["secrets", "emails", "home-dirs", "personal-info"];
+
+/// What a sanitizer rule looks for.
+#[derive(Debug, Clone)]
+pub enum RuleKind {
+    /// Text matching a regular expression
+    Regex(Regex),
+    /// This exact text
+    Literal(String),
+    /// Paths under this directory, written with `~` or without
+    PathPrefix(String),
+    /// Tokens, passwords and keys (`redaction::redact_secrets`)
+    Secrets,
+    /// Email addresses
+    Emails,
+    /// The user name in home directories
+    HomeDirs,
+    /// User names, host names, email addresses and home directories
+    PersonalInfo(Identities),
+}
+
+/// One step of the sanitizer: what to look for, and what to put in its place.
+#[derive(Debug, Clone)]
+pub struct SanitizeRule {
+    /// The rule as it's written in SANITIZE_RULES, to show which fired
+    pub label: String,
+    /// What it looks for
+    pub kind: RuleKind,
+    /// What replaces it (built-in detectors have placeholders of their own)
+    pub replacement: String,
+}
+
+impl SanitizeRule {
+    /// Replace text matching `pattern`; `$1` and the like refer to its groups.
+    pub fn regex(pattern: &str, replacement: &str) -> Result<Self> {
+        let regex = Regex::new(pattern).with_context(|| format!("'{}' is not a regular expression", pattern))?;
+        Ok(SanitizeRule {
+            label: format!("regex:{}={}", pattern, replacement),
+            kind: RuleKind::Regex(regex),
+            replacement: replacement.to_string(),
+        })
+    }
+
+    /// Replace `text` wherever it appears.
+    pub fn literal(text: &str, replacement: &str) -> Self {
+        SanitizeRule {
+            label: format!("literal:{}={}", text, replacement),
+            kind: RuleKind::Literal(text.to_string()),
+            replacement: replacement.to_string(),
+        }
+    }
+
+    /// Replace the directory `prefix` at the start of paths under it.
+    pub fn path_prefix(prefix: &str, replacement: &str) -> Self {
+        SanitizeRule {
+            label: format!("path:{}={}", prefix, replacement),
+            kind: RuleKind::PathPrefix(prefix.to_string()),
+            replacement: replacement.to_string(),
+        }
+    }
+
+    /// One of the built-in detectors, by name (see `BUILTINS`). The
+    /// `personal-info` detector looks for `identities`.
+    pub fn builtin(name: &str, identities: &Identities) -> Result<Self> {
+        let kind = match name.trim().to_lowercase().as_str() {
+            "secrets" => RuleKind::Secrets,
+            "emails" => RuleKind::Emails,
+            "home-dirs" => RuleKind::HomeDirs,
+            "personal-info" => RuleKind::PersonalInfo(identities.clone()),
+            other => bail!("Unknown built-in rule '{}' (expected {})", other, BUILTINS.join(", ")),
+        };
+        Ok(SanitizeRule {
+            label: format!("builtin:{}", name.trim().to_lowercase()),
+            kind,
+            replacement: String::new(),
+        })
+    }
+
+    /// Parse one rule, like `regex:ACME-[0-9]+=<TICKET>`,
+    /// `literal:Project Falcon=<CODENAME>`, `path:/srv/builds=<BUILDS>` or
+    /// `builtin:emails`. The replacement is whatever follows the last `=`
+    /// (`<REDACTED>` if there's no `=`), so a pattern with a `=` in it
+    /// needs a replacement after it.
+    pub fn parse(entry: &str, identities: &Identities) -> Result<Self> {
+        let (kind, value) = entry
+            .trim()
+            .split_once(':')
+            .ok_or_else(|| anyhow!("Sanitizer rule '{}' should look like kind:value=replacement", entry.trim()))?;
+        let kind = kind.trim().to_lowercase();
+        if kind == "builtin" {
+            if value.contains('=') {
+                bail!("Built-in rule '{}' has placeholders of its own and takes no replacement", value);
+            }
+            return SanitizeRule::builtin(value, identities);
+        }
+        let (value, replacement) = value.rsplit_once('=').unwrap_or((value, REDACTED));
+        if value.is_empty() {
+            bail!("Sanitizer rule '{}' has nothing to look for", entry.trim());
+        }
+        match kind.as_str() {
+            "regex" => SanitizeRule::regex(value, replacement),
+            "literal" => Ok(SanitizeRule::literal(value, replacement)),
+            "path" => Ok(SanitizeRule::path_prefix(value, replacement)),
+            other => bail!("Unknown kind of sanitizer rule '{}' (expected regex, literal, path or builtin)", other),
+        }
+    }
+
+    /// `text` with this rule applied.
+    pub fn apply(&self, text: &str) -> String {
+        match &self.kind {
+            RuleKind::Regex(regex) => regex.replace_all(text, self.replacement.as_str()).into_owned(),
+            RuleKind::Literal(literal) => text.replace(literal, &self.replacement),
+            RuleKind::PathPrefix(prefix) if prefix.is_empty() => text.to_string(),
+            RuleKind::PathPrefix(prefix) => {
+                // Whether or not the path has had its ~ expanded
+                let expanded = shellexpand::tilde(prefix).to_string();
+                text.replace(&expanded, &self.replacement).replace(prefix, &self.replacement)
+            }
+            RuleKind::Secrets => redact_secrets(text),
+            RuleKind::Emails => redact_emails(text),
+            RuleKind::HomeDirs => redact_home_dirs(text),
+            RuleKind::PersonalInfo(identities) => identities.scrub(text),
+        }
+    }
+}
+
+/// Parse SANITIZE_RULES: rules separated by `;` (see `SanitizeRule::parse`).
+pub fn parse_rules(spec: &str, identities: &Identities) -> Result<Vec<SanitizeRule>> {
+    spec.split(';')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| SanitizeRule::parse(entry, identities))
+        .collect()
+}
+
+/// A rule that changed the text, and what the text was after it.
+#[derive(Debug, Clone)]
+pub struct RuleHit<'a> {
+    /// The rule that fired
+    pub rule: &'a SanitizeRule,
+    /// The text once it had
+    pub after: String,
+}
+
+/// The rules everything written goes through, in order. `Config::sanitizer`
+/// puts together the built-in ones the INCLUDE_* settings ask for and
+/// SANITIZE_RULES.
+#[derive(Debug, Clone, Default)]
+pub struct Sanitizer {
+    /// The rules, applied first to last
+    pub rules: Vec<SanitizeRule>,
+}
+
+impl Sanitizer {
+    /// `text` with every rule applied.
+    pub fn apply(&self, text: &str) -> String {
+        self.rules.iter().fold(text.to_string(), |text, rule| rule.apply(&text))
+    }
+
+    /// The rules that change `text`, in order, with the text after each;
+    /// the last one's is what `apply` gives.
+    pub fn trace(&self, text: &str) -> Vec<RuleHit<'_>> {
+        let mut hits = Vec::new();
+        let mut current = text.to_string();
+        for rule in &self.rules {
+            let after = rule.apply(&current);
+            if after != current {
+                hits.push(RuleHit { rule, after: after.clone() });
+                current = after;
+            }
+        }
+        hits
+    }
+}
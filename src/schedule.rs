@@ -0,0 +1,158 @@
+use anyhow::{anyhow, bail, Result};
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime, Timelike, Utc};
+
+use crate::time::DisplayZone;
+
+/// How far ahead to look for the next run before deciding a schedule
+/// never fires (like `0 0 30 2 *`, February 30th).
+const SEARCH_YEARS: i32 = 5;
+
+/// A cron schedule: `minute hour day-of-month month day-of-week`, with
+/// the usual `*`, lists (`1,15`), ranges (`9-17`), steps (`*/15`), month
+/// and day names (`jan`, `mon-fri`), and the `@daily`-style shorthands.
+/// As in cron, when both the day of the month and the day of the week are
+/// restricted, a day matching either one counts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Schedule {
+    minutes: Vec<bool>,
+    hours: Vec<bool>,
+    days: Vec<bool>,
+    months: Vec<bool>,
+    weekdays: Vec<bool>,
+    any_day: bool,
+    any_weekday: bool,
+}
+
+impl Schedule {
+    /// Parse a cron expression.
+    pub fn parse(expression: &str) -> Result<Self> {
+        let expression = match expression.trim() {
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            "@monthly" => "0 0 1 * *",
+            "@weekly" => "0 0 * * 0",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@hourly" => "0 * * * *",
+            other => other,
+        };
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            bail!(
+                "'{}' should have five fields: minute hour day-of-month month day-of-week",
+                expression
+            );
+        };
+
+        let mut weekdays = field(weekday, 0, 7, WEEKDAYS)?;
+        // Both 0 and 7 mean Sunday
+        if weekdays[7] {
+            weekdays[0] = true;
+        }
+        weekdays.truncate(7);
+
+        Ok(Schedule {
+            minutes: field(minute, 0, 59, &[])?,
+            hours: field(hour, 0, 23, &[])?,
+            days: field(day, 1, 31, &[])?,
+            months: field(month, 1, 12, MONTHS)?,
+            weekdays,
+            any_day: day == "*",
+            any_weekday: weekday == "*",
+        })
+    }
+
+    /// The first time after `after` that the schedule fires, reading the
+    /// schedule in `zone`'s wall-clock time. Times skipped by a daylight
+    /// saving change don't fire. `None` if it never fires.
+    pub fn next_after(&self, after: DateTime<Utc>, zone: &DisplayZone) -> Option<DateTime<Utc>> {
+        let start = zone.local_millis(after.timestamp_millis())?;
+        let mut time = start.with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+        let give_up = start.with_year(start.year() + SEARCH_YEARS)?;
+
+        while time < give_up {
+            if !self.months[time.month() as usize] {
+                time = first_of_next_month(time.date())?;
+            } else if !self.day_matches(time.date()) {
+                time = (time.date() + Duration::days(1)).and_time(NaiveTime::MIN);
+            } else if !self.hours[time.hour() as usize] {
+                time = time.with_minute(0)? + Duration::hours(1);
+            } else if !self.minutes[time.minute() as usize] {
+                time += Duration::minutes(1);
+            } else {
+                match zone.to_utc(time) {
+                    Some(utc) if utc > after => return Some(utc),
+                    _ => time += Duration::minutes(1),
+                }
+            }
+        }
+        None
+    }
+
+    fn day_matches(&self, date: NaiveDate) -> bool {
+        let day = self.days[date.day() as usize];
+        let weekday = self.weekdays[date.weekday().num_days_from_sunday() as usize];
+        match (self.any_day, self.any_weekday) {
+            (false, false) => day || weekday,
+            _ => day && weekday,
+        }
+    }
+}
+
+const MONTHS: &[&str] = &["jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec"];
+const WEEKDAYS: &[&str] = &["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+/// Which values one cron field allows, indexed by value.
+fn field(spec: &str, min: u32, max: u32, names: &[&str]) -> Result<Vec<bool>> {
+    let mut allowed = vec![false; max as usize + 1];
+    for part in spec.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (
+                range,
+                step.parse::<u32>()
+                    .ok()
+                    .filter(|step| *step > 0)
+                    .ok_or_else(|| anyhow!("'{}' has a bad step", part))?,
+            ),
+            None => (part, 1),
+        };
+        let (first, last) = match range {
+            "*" => (min, max),
+            range => match range.split_once('-') {
+                Some((first, last)) => (value(first, min, names)?, value(last, min, names)?),
+                // `5/10` means every 10 starting at 5
+                None if step > 1 => (value(range, min, names)?, max),
+                None => {
+                    let value = value(range, min, names)?;
+                    (value, value)
+                }
+            },
+        };
+        if first < min || last > max || first > last {
+            bail!("'{}' is out of range ({}-{})", part, min, max);
+        }
+        for value in (first..=last).step_by(step as usize) {
+            allowed[value as usize] = true;
+        }
+    }
+    Ok(allowed)
+}
+
+/// One value in a cron field: a number, or a month or day name.
+fn value(text: &str, min: u32, names: &[&str]) -> Result<u32> {
+    if let Ok(number) = text.parse() {
+        return Ok(number);
+    }
+    names
+        .iter()
+        .position(|name| name.eq_ignore_ascii_case(text))
+        .map(|index| index as u32 + min)
+        .ok_or_else(|| anyhow!("'{}' isn't a number{}", text, if names.is_empty() { "" } else { " or name" }))
+}
+
+fn first_of_next_month(date: NaiveDate) -> Option<NaiveDateTime> {
+    let (year, month) = if date.month() == 12 {
+        (date.year() + 1, 1)
+    } else {
+        (date.year(), date.month() + 1)
+    };
+    Some(NaiveDate::from_ymd_opt(year, month, 1)?.and_time(NaiveTime::MIN))
+}
@@ -0,0 +1,376 @@
+use anyhow::{anyhow, bail, Result};
+use serde_json::Value;
+use std::fmt;
+
+#[cfg(feature = "native")]
+use crate::config::Config;
+#[cfg(feature = "native")]
+use crate::store::RecordStore;
+
+/// The kinds of record whose field names have changed between Cursor versions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordKind {
+    /// The composer data record that lists every session
+    ComposerData,
+    /// One session in that list
+    Session,
+    /// One AI generation
+    Generation,
+    /// One user prompt
+    Prompt,
+}
+
+impl RecordKind {
+    /// The names `FIELD_MAPPING` knows each kind by.
+    pub const NAMES: &'static [&'static str] = &["composer_data", "session", "generation", "prompt"];
+
+    /// Parse a record kind's name.
+    pub fn parse(name: &str) -> Result<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "composer_data" => Ok(RecordKind::ComposerData),
+            "session" => Ok(RecordKind::Session),
+            "generation" => Ok(RecordKind::Generation),
+            "prompt" => Ok(RecordKind::Prompt),
+            other => Err(anyhow!("Unknown record kind '{}' (expected {})", other, RecordKind::NAMES.join(", "))),
+        }
+    }
+}
+
+/// One entry of a field mapping: where in a record to find one of our
+/// model's fields.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MappedField {
+    /// Which kind of record it applies to
+    pub kind: RecordKind,
+    /// The field of ours to fill in (like `created_at`)
+    pub field: String,
+    /// A JSON pointer into the record (like `/meta/created`)
+    pub pointer: String,
+}
+
+/// Where to find our model's fields in records whose shape isn't one we
+/// know - a field renamed, or wrapped in another object - set with
+/// FIELD_MAPPING so a new Cursor version can be read without a new release.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FieldMapping {
+    /// The fields to fill in, in the order they're given
+    pub fields: Vec<MappedField>,
+}
+
+impl FieldMapping {
+    /// Parse a mapping like `session.created_at=/meta/created,composer_data.all_composers=/data/sessions`:
+    /// each entry is `kind.field=pointer`.
+    pub fn parse(spec: &str) -> Result<Self> {
+        let fields = spec
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| {
+                let (target, pointer) = entry
+                    .split_once('=')
+                    .ok_or_else(|| anyhow!("Field mapping '{}' should look like kind.field=/json/pointer", entry))?;
+                let (kind, field) = target
+                    .split_once('.')
+                    .ok_or_else(|| anyhow!("Field mapping '{}' should look like kind.field=/json/pointer", entry))?;
+                let (field, pointer) = (field.trim(), pointer.trim());
+                if field.is_empty() {
+                    bail!("Field mapping '{}' doesn't name a field", entry);
+                }
+                if !pointer.starts_with('/') {
+                    bail!("Field mapping '{}' needs a JSON pointer starting with '/'", entry);
+                }
+                Ok(MappedField { kind: RecordKind::parse(kind)?, field: field.to_string(), pointer: pointer.to_string() })
+            })
+            .collect::<Result<_>>()?;
+        Ok(FieldMapping { fields })
+    }
+
+    /// Whether there's nothing to map.
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    /// Fill in the mapped fields of a `kind` record from wherever their
+    /// pointers lead, replacing anything already there. A pointer that
+    /// leads nowhere leaves the field alone.
+    pub fn apply(&self, kind: RecordKind, record: &mut Value) {
+        let found: Vec<(String, Value)> = self
+            .fields
+            .iter()
+            .filter(|mapped| mapped.kind == kind)
+            .filter_map(|mapped| Some((mapped.field.clone(), record.pointer(&mapped.pointer)?.clone())))
+            .collect();
+        if let Value::Object(fields) = record {
+            fields.extend(found);
+        }
+    }
+}
+
+/// Field renames from the camelCase names Cursor writes to the names our
+/// models use. Older exports (and our fixtures) already use the new names,
+/// so a rename only happens when the old name is there and the new one isn't.
+const FIELD_RENAMES: &[(RecordKind, &str, &str)] = &[
+    (RecordKind::ComposerData, "allComposers", "all_composers"),
+    (RecordKind::Session, "composerId", "composer_id"),
+    (RecordKind::Session, "lastUpdatedAt", "last_updated_at"),
+    (RecordKind::Session, "createdAt", "created_at"),
+    (RecordKind::Session, "unifiedMode", "unified_mode"),
+    (RecordKind::Session, "forceMode", "force_mode"),
+    (RecordKind::Session, "hasUnreadMessages", "has_unread_messages"),
+    (RecordKind::Generation, "unixMs", "unix_ms"),
+    (RecordKind::Generation, "generationUUID", "generation_uuid"),
+    (RecordKind::Generation, "textDescription", "text_description"),
+    (RecordKind::Prompt, "commandType", "command_type"),
+];
+
+/// Bring one record up to the field names our models expect, starting
+/// with any that `mapping` says where to find. Sessions also get defaults
+/// for fields newer Cursor versions leave out (an untitled session simply
+/// has no `name`).
+pub fn migrate(kind: RecordKind, record: &mut Value, mapping: &FieldMapping) {
+    mapping.apply(kind, record);
+    let Value::Object(fields) = record else {
+        return;
+    };
+
+    for (_, from, to) in FIELD_RENAMES.iter().filter(|(rename_kind, _, _)| *rename_kind == kind) {
+        if !fields.contains_key(*to) {
+            if let Some(value) = fields.remove(*from) {
+                fields.insert(to.to_string(), value);
+            }
+        }
+    }
+
+    if kind == RecordKind::Session {
+        let defaults = [
+            ("type", Value::from("head")),
+            ("name", Value::from("Untitled session")),
+            ("unified_mode", Value::from("chat")),
+            ("force_mode", Value::from("")),
+            ("has_unread_messages", Value::from(false)),
+        ];
+        for (field, default) in defaults {
+            fields.entry(field).or_insert(default);
+        }
+    }
+}
+
+/// How a database names its fields.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldNaming {
+    /// `all_composers`, `unix_ms` - exports and older fixtures
+    SnakeCase,
+    /// `allComposers`, `unixMs` - what Cursor itself writes
+    CamelCase,
+    /// We couldn't find a record to tell from
+    Unknown,
+}
+
+/// Where a database keeps the messages of each conversation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConversationStorage {
+    /// The whole conversation inline under `composerData:<id>`
+    Inline,
+    /// Headers under `composerData:<id>`, each message under `bubbleId:<id>:<bubble>`
+    PerMessageKeys,
+    /// No conversation records found at all
+    Missing,
+}
+
+/// The Cursor storage layouts we know how to read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CursorSchema {
+    /// snake_case records, the shape this tool was first written against
+    SnakeCaseExport,
+    /// camelCase records with conversations stored inline (older Cursor versions)
+    InlineConversations,
+    /// camelCase records with one key per message (newer Cursor versions)
+    PerMessageKeys,
+    /// Something we haven't seen before
+    Unknown,
+}
+
+impl CursorSchema {
+    /// Work out the layout from what the fingerprint found.
+    pub fn detect(naming: FieldNaming, storage: ConversationStorage) -> Self {
+        match (naming, storage) {
+            (FieldNaming::SnakeCase, _) => CursorSchema::SnakeCaseExport,
+            (FieldNaming::CamelCase, ConversationStorage::PerMessageKeys) => CursorSchema::PerMessageKeys,
+            (FieldNaming::CamelCase, _) => CursorSchema::InlineConversations,
+            (FieldNaming::Unknown, _) => CursorSchema::Unknown,
+        }
+    }
+
+    /// A short description for reports.
+    pub fn describe(&self) -> &'static str {
+        match self {
+            CursorSchema::SnakeCaseExport => "snake_case export",
+            CursorSchema::InlineConversations => "older Cursor (inline conversations)",
+            CursorSchema::PerMessageKeys => "newer Cursor (one key per message)",
+            CursorSchema::Unknown => "unknown",
+        }
+    }
+}
+
+/// The shape of one top-level record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordShape {
+    /// The key the record lives under
+    pub key: String,
+    /// "missing", "list of 12", "object", ...
+    pub kind: String,
+    /// Field names of the record (or of its first element, for lists), sorted
+    pub fields: Vec<String>,
+}
+
+/// Everything we could tell about a database's layout, for bug reports.
+#[derive(Debug, Clone)]
+pub struct SchemaReport {
+    /// Where the main records came from
+    pub location: String,
+    /// Tables in the main database
+    pub tables: Vec<String>,
+    /// How many records the main table holds
+    pub item_count: i64,
+    /// The shapes of the records we read
+    pub records: Vec<RecordShape>,
+    /// How many conversation records there are under each known key prefix
+    pub conversation_keys: Vec<(String, i64)>,
+    /// Field names of a sample conversation record, if there is one
+    pub conversation_fields: Vec<String>,
+    /// How fields are named
+    pub naming: FieldNaming,
+    /// Where conversations live
+    pub storage: ConversationStorage,
+    /// The layout we think this is
+    pub schema: CursorSchema,
+}
+
+/// Key prefixes Cursor uses for per-conversation records.
+#[cfg(feature = "native")]
+const CONVERSATION_PREFIXES: &[&str] = &["composerData:", "bubbleId:", "codeBlockDiff:", "checkpointId:"];
+
+/// Fingerprint a Cursor database: which tables and key families exist, what
+/// the main records look like, and which storage layout that adds up to.
+/// Only samples records, so it stays quick on large global databases.
+#[cfg(feature = "native")]
+pub async fn fingerprint(
+    config: &Config,
+    store: &dyn RecordStore,
+    conversation_store: Option<&dyn RecordStore>,
+) -> Result<SchemaReport> {
+    let mut records = Vec::new();
+    for key in [&config.composer_data_key, &config.generations_key, &config.prompts_key] {
+        let value = store
+            .get_key(key)
+            .await?
+            .and_then(|json_str| serde_json::from_str::<Value>(&json_str).ok());
+        records.push((key.clone(), value));
+    }
+
+    let naming = match &records[0].1 {
+        Some(composer_data) if composer_data.get("all_composers").is_some() => FieldNaming::SnakeCase,
+        Some(composer_data) if composer_data.get("allComposers").is_some() => FieldNaming::CamelCase,
+        _ => FieldNaming::Unknown,
+    };
+
+    // Sample one session's conversation record to see how messages are stored
+    let conversation_store = conversation_store.unwrap_or(store);
+    let mut conversation_keys = Vec::new();
+    for prefix in CONVERSATION_PREFIXES {
+        conversation_keys.push((prefix.to_string(), conversation_store.count_prefix(prefix).await?));
+    }
+    let sample_id = records[0].1.as_ref().and_then(|composer_data| {
+        let sessions = composer_data.get("all_composers").or_else(|| composer_data.get("allComposers"))?;
+        let first = sessions.as_array()?.first()?;
+        first.get("composer_id").or_else(|| first.get("composerId"))?.as_str().map(str::to_string)
+    });
+    let sample = match sample_id {
+        Some(id) => conversation_store
+            .get_key(&format!("composerData:{}", id))
+            .await?
+            .and_then(|json_str| serde_json::from_str::<Value>(&json_str).ok()),
+        None => None,
+    };
+
+    let storage = match &sample {
+        Some(sample) if sample.get("fullConversationHeadersOnly").is_some() => ConversationStorage::PerMessageKeys,
+        Some(_) => ConversationStorage::Inline,
+        None => ConversationStorage::Missing,
+    };
+
+    Ok(SchemaReport {
+        location: config.sanitize_path(&store.location()),
+        tables: store.tables().await?,
+        item_count: store.count().await?,
+        records: records
+            .iter()
+            .map(|(key, value)| describe_record(key, value.as_ref()))
+            .collect(),
+        conversation_keys,
+        conversation_fields: sample.as_ref().map(field_names).unwrap_or_default(),
+        naming,
+        storage,
+        schema: CursorSchema::detect(naming, storage),
+    })
+}
+
+/// Describe the shape of one top-level record.
+#[cfg(feature = "native")]
+fn describe_record(key: &str, value: Option<&Value>) -> RecordShape {
+    let (kind, fields) = match value {
+        None => ("missing".to_string(), Vec::new()),
+        Some(Value::Array(items)) => (
+            format!("list of {}", items.len()),
+            items.first().map(field_names).unwrap_or_default(),
+        ),
+        Some(value @ Value::Object(_)) => ("object".to_string(), field_names(value)),
+        Some(_) => ("scalar".to_string(), Vec::new()),
+    };
+
+    RecordShape {
+        key: key.to_string(),
+        kind,
+        fields,
+    }
+}
+
+/// The sorted field names of a JSON object (nothing for other values).
+#[cfg(feature = "native")]
+fn field_names(value: &Value) -> Vec<String> {
+    let mut names: Vec<String> = value
+        .as_object()
+        .map(|fields| fields.keys().cloned().collect())
+        .unwrap_or_default();
+    names.sort();
+    names
+}
+
+impl fmt::Display for SchemaReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Schema report for {}", self.location)?;
+        writeln!(f, "Detected layout: {}", self.schema.describe())?;
+        writeln!(f, "Field naming: {:?}", self.naming)?;
+        writeln!(f, "Conversation storage: {:?}", self.storage)?;
+        writeln!(f, "Tables: {}", self.tables.join(", "))?;
+        writeln!(f, "Items: {}", self.item_count)?;
+
+        writeln!(f, "\nRecords:")?;
+        for record in &self.records {
+            writeln!(f, "  {} ({})", record.key, record.kind)?;
+            if !record.fields.is_empty() {
+                writeln!(f, "    fields: {}", record.fields.join(", "))?;
+            }
+        }
+
+        writeln!(f, "\nConversation keys:")?;
+        for (prefix, count) in &self.conversation_keys {
+            writeln!(f, "  {}* {}", prefix, count)?;
+        }
+        if !self.conversation_fields.is_empty() {
+            writeln!(f, "  sample fields: {}", self.conversation_fields.join(", "))?;
+        }
+
+        Ok(())
+    }
+}
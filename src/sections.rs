@@ -0,0 +1,175 @@
+use anyhow::{anyhow, bail, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::document::Block;
+use crate::generator::MarkdownGenerator;
+use crate::i18n::Strings;
+use crate::{ChatGeneration, ChatPrompt, ComposerData};
+
+pub mod custom;
+
+/// What a section has to work with.
+pub struct SectionContext<'a> {
+    /// The settings the lore is being generated with
+    pub config: &'a Config,
+    /// Titles and labels in the configured language
+    pub strings: &'a Strings,
+    /// The sessions
+    pub sessions: &'a [ComposerData],
+    /// The generations
+    pub generations: &'a [ChatGeneration],
+    /// The prompts
+    pub prompts: &'a [ChatPrompt],
+    /// Whether the historical sessions are part of this document (when
+    /// publishing, they get pages of their own instead)
+    pub include_sessions: bool,
+    /// The generator, for the built-in sections
+    pub(crate) generator: &'a MarkdownGenerator,
+}
+
+/// One section of the lore. The built-in ones are listed in
+/// `BUILTIN_SECTIONS`; more can be added with
+/// `MarkdownGenerator::with_section`.
+pub trait Section: Send + Sync {
+    /// The name SECTIONS knows it by, like `metadata`
+    fn name(&self) -> &str;
+
+    /// Render the section, handing its blocks to `emit` - all at once, or
+    /// a piece at a time for big sections so they can be streamed. A
+    /// section with nothing to say emits nothing.
+    fn render(&self, context: &SectionContext<'_>, emit: &mut dyn FnMut(Vec<Block>) -> Result<()>) -> Result<()>;
+}
+
+/// The built-in sections, in the order they're rendered unless SECTIONS
+/// says otherwise.
+pub const BUILTIN_SECTIONS: &[&str] = &[
+    "header",
+    "metadata",
+    "project-context",
+    "workspace",
+    "decisions",
+    "follow-ups",
+    "generations",
+    "faq",
+    "troubleshooting",
+    "sessions",
+    "tags",
+    "timeline",
+    "knowledge-graph",
+    "most-discussed-files",
+    "hotspots",
+    "commands",
+    "model-usage",
+    "current-session",
+    "topics",
+    "project-structure",
+    "key-features",
+    "git-status",
+    "data-sources",
+    "notes",
+    "warnings",
+    "footer",
+];
+
+/// The sections a generator knows, in their default order.
+#[derive(Default)]
+pub struct SectionRegistry {
+    sections: Vec<Box<dyn Section>>,
+}
+
+impl SectionRegistry {
+    /// Add a section. One with the same name as a section already there
+    /// takes its place; a new one goes before the footer.
+    pub fn register(&mut self, section: Box<dyn Section>) {
+        if let Some(index) = self.sections.iter().position(|known| known.name() == section.name()) {
+            self.sections[index] = section;
+        } else if let Some(index) = self.sections.iter().position(|known| known.name() == "footer") {
+            self.sections.insert(index, section);
+        } else {
+            self.sections.push(section);
+        }
+    }
+
+    /// Add a section at a particular place. One with the same name as a
+    /// section already there is moved there, or stays where it was if
+    /// the position is `Default`. An anchor that isn't registered is
+    /// treated as `Default`.
+    pub fn insert(&mut self, section: Box<dyn Section>, position: &SectionPosition) {
+        let replaced = self.sections.iter().position(|known| known.name() == section.name());
+        if let Some(index) = replaced {
+            self.sections.remove(index);
+        }
+        let anchor = |name: &str| self.sections.iter().position(|known| known.name() == name);
+        let index = match position {
+            SectionPosition::Before(name) => anchor(name),
+            SectionPosition::After(name) => anchor(name).map(|index| index + 1),
+            SectionPosition::Default => None,
+        };
+        match index.or(replaced) {
+            Some(index) => self.sections.insert(index, section),
+            None => self.register(section),
+        }
+    }
+
+    /// The section called `name`, if there is one.
+    pub fn get(&self, name: &str) -> Option<&dyn Section> {
+        self.sections.iter().find(|section| section.name() == name).map(|section| section.as_ref())
+    }
+
+    /// The names of every section, in their default order.
+    pub fn names(&self) -> Vec<&str> {
+        self.sections.iter().map(|section| section.name()).collect()
+    }
+
+    /// The sections to render, in order: the ones `order` names, or all
+    /// of them if it's empty.
+    pub fn pipeline(&self, order: &[String]) -> Result<Vec<&dyn Section>> {
+        if order.is_empty() {
+            return Ok(self.sections.iter().map(|section| section.as_ref()).collect());
+        }
+        let mut pipeline: Vec<&dyn Section> = Vec::new();
+        for name in order {
+            let section = self
+                .get(name)
+                .ok_or_else(|| anyhow!("Unknown section '{}' in SECTIONS (expected {})", name, self.names().join(", ")))?;
+            if pipeline.iter().any(|picked| picked.name() == name) {
+                bail!("Section '{}' is listed twice in SECTIONS", name);
+            }
+            pipeline.push(section);
+        }
+        Ok(pipeline)
+    }
+}
+
+/// Where a section goes in the default order.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SectionPosition {
+    /// Before the footer (or in place of the section it replaces)
+    #[default]
+    Default,
+    /// Right before the named section
+    Before(String),
+    /// Right after the named section
+    After(String),
+}
+
+impl SectionPosition {
+    /// Parse a position like `before:sessions` or `after:metadata`.
+    pub fn parse(value: &str) -> Result<Self> {
+        match value.trim().split_once(':') {
+            Some(("before", name)) if !name.trim().is_empty() => Ok(SectionPosition::Before(name.trim().to_lowercase())),
+            Some(("after", name)) if !name.trim().is_empty() => Ok(SectionPosition::After(name.trim().to_lowercase())),
+            _ => bail!("Unknown section position '{}' (expected before:<section> or after:<section>)", value),
+        }
+    }
+
+    /// The section it's placed next to, if any.
+    pub fn anchor(&self) -> Option<&str> {
+        match self {
+            SectionPosition::Before(name) | SectionPosition::After(name) => Some(name),
+            SectionPosition::Default => None,
+        }
+    }
+}
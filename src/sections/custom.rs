@@ -0,0 +1,160 @@
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::process::Command;
+
+use crate::document::{heading_anchor, markdown_blocks, Block};
+use crate::sections::{Section, SectionContext, SectionPosition};
+
+/// Where a custom section's content comes from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "value", rename_all = "snake_case")]
+pub enum CustomContent {
+    /// A file, relative to PROJECT_PATH. Markdown and text files become
+    /// paragraphs and lists; anything else is shown as code.
+    File(String),
+    /// Markdown written in the config, with `{project}`, `{branch}`,
+    /// `{sessions}` and `{date}` filled in and `\n` for line breaks
+    Template(String),
+    /// A shell command run in PROJECT_PATH, whose output is shown as code
+    Command(String),
+}
+
+/// A section of your own, from CUSTOM_SECTIONS.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CustomSection {
+    /// The name SECTIONS knows it by: its title as a heading anchor
+    pub name: String,
+    /// The section's heading
+    pub title: String,
+    /// What goes under the heading
+    pub content: CustomContent,
+    /// Where it goes in the default order
+    pub position: SectionPosition,
+}
+
+/// Parse a CUSTOM_SECTIONS spec like
+/// `Architecture=file:docs/architecture.md;Dependencies@after:project-structure=command:cargo tree --depth 1`.
+/// Entries are separated by `;` and look like `Title[@position]=kind:value`,
+/// where kind is `file`, `template` or `command` and position is
+/// `before:<section>` or `after:<section>`.
+pub fn parse_custom_sections(spec: &str) -> Result<Vec<CustomSection>> {
+    spec.split(';')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (heading, source) = entry
+                .split_once('=')
+                .ok_or_else(|| anyhow!("Custom section '{}' should look like Title=kind:value", entry))?;
+            let (title, position) = match heading.rsplit_once('@') {
+                Some((title, position)) if position.contains(':') => (title, SectionPosition::parse(position)?),
+                _ => (heading, SectionPosition::Default),
+            };
+            let title = title.trim();
+            let name = heading_anchor(title);
+            if name.is_empty() {
+                bail!("Custom section '{}' needs a title", entry);
+            }
+            let (kind, value) = source
+                .split_once(':')
+                .ok_or_else(|| anyhow!("Custom section '{}' should look like Title=kind:value", entry))?;
+            let value = value.trim().to_string();
+            if value.is_empty() {
+                bail!("Custom section '{}' has nothing after {}:", title, kind.trim());
+            }
+            let content = match kind.trim() {
+                "file" => CustomContent::File(value),
+                "template" => CustomContent::Template(value),
+                "command" => CustomContent::Command(value),
+                other => bail!("Unknown kind '{}' for custom section '{}' (expected file, template or command)", other, title),
+            };
+            Ok(CustomSection { name, title: title.to_string(), content, position })
+        })
+        .collect()
+}
+
+impl Section for CustomSection {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn render(&self, context: &SectionContext<'_>, emit: &mut dyn FnMut(Vec<Block>) -> Result<()>) -> Result<()> {
+        let config = context.config;
+        let blocks = match &self.content {
+            CustomContent::File(path) => {
+                let path = config.project_file(path);
+                let text = std::fs::read_to_string(&path)
+                    .with_context(|| format!("Couldn't read custom section '{}' from {}", self.title, path.display()))?;
+                match path.extension().and_then(|extension| extension.to_str()).unwrap_or_default() {
+                    "" | "md" | "markdown" | "txt" => markdown_blocks(&text),
+                    language => code_block(language, &text),
+                }
+            }
+            CustomContent::Template(template) => {
+                let sessions: usize = context.sessions.iter().map(|data| data.all_composers.len()).sum();
+                let text = template
+                    .replace("\\n", "\n")
+                    .replace("{project}", &config.project_name)
+                    .replace("{branch}", &config.project_branch)
+                    .replace("{sessions}", &sessions.to_string())
+                    .replace("{date}", &config.display_zone().format_now("%Y-%m-%d"));
+                markdown_blocks(&text)
+            }
+            CustomContent::Command(command) => code_block("", &run(command, &config.project_file(""), &self.title)?),
+        };
+        if blocks.is_empty() {
+            return Ok(());
+        }
+
+        // What's pulled in is treated like the transcripts: no secrets or home directories
+        let clean = |text: &str| config.sanitize_path(&context.generator.redact(text));
+        let mut section = vec![Block::heading(2, &self.title)];
+        section.extend(blocks.into_iter().map(|block| match block {
+            Block::Heading { level, text, .. } => Block::heading(level, clean(&text)),
+            Block::Paragraph(text) => Block::Paragraph(clean(&text)),
+            Block::List { ordered, items } => Block::List { ordered, items: items.iter().map(|item| clean(item)).collect() },
+            Block::Code { language, text } => Block::Code { language, text: clean(&text) },
+            other => other,
+        }));
+        emit(section)
+    }
+}
+
+/// Run a command through the shell and return what it printed.
+fn run(command: &str, dir: &Path, title: &str) -> Result<String> {
+    let mut shell = if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C");
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+    shell.arg(command);
+    if dir.is_dir() {
+        shell.current_dir(dir);
+    }
+    let output = shell
+        .output()
+        .with_context(|| format!("Couldn't run `{}` for custom section '{}'", command, title))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        bail!(
+            "`{}` failed for custom section '{}' ({}): {}",
+            command,
+            title,
+            output.status,
+            stderr.lines().next().unwrap_or_default()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Text shown as a code block, or nothing if it's blank.
+fn code_block(language: &str, text: &str) -> Vec<Block> {
+    if text.trim().is_empty() {
+        return Vec::new();
+    }
+    vec![Block::code(language, format!("{}\n", text.trim_end()))]
+}
@@ -0,0 +1,195 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashSet};
+use std::fs;
+use std::path::Path;
+
+use crate::atomic::write_atomic;
+use crate::config::Config;
+use crate::importers::ImportedHistory;
+use crate::tags::{parse_tags, SessionTags};
+use crate::{ChatSession, MessageRole};
+
+/// Which sessions make it into the lore, from INCLUDE_SESSIONS,
+/// EXCLUDE_SESSIONS, INCLUDE_TAGS and EXCLUDE_TAGS. A pattern matches a
+/// session with exactly that ID, or whose name it matches (ignoring case,
+/// with `*` for any run of characters and `?` for any one). Exclusions win
+/// over inclusions, so `scratch*` stays out even when everything else is
+/// included.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SessionFilter {
+    /// Only sessions matching one of these are kept; empty keeps them all
+    pub include: Vec<String>,
+    /// Sessions matching any of these are left out
+    pub exclude: Vec<String>,
+    /// Only sessions with one of these tags are kept; empty keeps them all
+    pub include_tags: Vec<String>,
+    /// Sessions with any of these tags are left out
+    pub exclude_tags: Vec<String>,
+    /// Which sessions have which tags, for the tag filters
+    pub tags: SessionTags,
+}
+
+impl SessionFilter {
+    /// The filter the config describes. Tag filters need the sessions'
+    /// tags too; see `with_tags`.
+    pub fn from_config(config: &Config) -> Self {
+        SessionFilter {
+            include: patterns(&config.include_sessions),
+            exclude: patterns(&config.exclude_sessions),
+            include_tags: parse_tags(&config.include_tags).unwrap_or_default(),
+            exclude_tags: parse_tags(&config.exclude_tags).unwrap_or_default(),
+            tags: SessionTags::default(),
+        }
+    }
+
+    /// Use `tags` to tell which sessions the tag filters match.
+    pub fn with_tags(mut self, tags: SessionTags) -> Self {
+        self.tags = tags;
+        self
+    }
+
+    /// Whether this filter keeps every session.
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty() && self.include_tags.is_empty() && self.exclude_tags.is_empty()
+    }
+
+    /// Whether `session` makes it into the lore.
+    pub fn keeps(&self, session: &ChatSession) -> bool {
+        let matches = |pattern: &String| pattern == &session.composer_id || wildcard_match(pattern, &session.name);
+        let id = &session.composer_id;
+        (self.include.is_empty() || self.include.iter().any(matches))
+            && !self.exclude.iter().any(matches)
+            && (self.include_tags.is_empty() || self.tags.has_any(id, &self.include_tags))
+            && !self.tags.has_any(id, &self.exclude_tags)
+    }
+
+    /// Drop the sessions this filter leaves out of `history`, returning
+    /// how many went. Prompts and generations carry no session ID, so the
+    /// ones that only belong to dropped sessions go too: prompts by their
+    /// text, generations by when they were made.
+    pub fn apply(&self, history: &mut ImportedHistory) -> usize {
+        if self.is_empty() {
+            return 0;
+        }
+        let mut dropped: Vec<ChatSession> = Vec::new();
+        for data in &mut history.sessions {
+            let (kept, left_out) = std::mem::take(&mut data.all_composers)
+                .into_iter()
+                .partition(|session| self.keeps(session));
+            data.all_composers = kept;
+            dropped.extend(left_out);
+        }
+        if dropped.is_empty() {
+            return 0;
+        }
+        let kept: Vec<&ChatSession> = history.sessions.iter().flat_map(|data| &data.all_composers).collect();
+
+        let user_texts = |sessions: &[&ChatSession]| -> HashSet<String> {
+            sessions
+                .iter()
+                .flat_map(|session| &session.messages)
+                .filter(|message| message.role == MessageRole::User)
+                .map(|message| message.text.clone())
+                .collect()
+        };
+        let kept_texts = user_texts(&kept);
+        let dropped_texts = user_texts(&dropped.iter().collect::<Vec<_>>());
+        history
+            .prompts
+            .retain(|prompt| kept_texts.contains(&prompt.text) || !dropped_texts.contains(&prompt.text));
+
+        let during = |sessions: &[&ChatSession], unix_ms: i64| {
+            sessions
+                .iter()
+                .any(|session| unix_ms >= session.created_at && unix_ms <= session.last_updated_at.max(session.created_at))
+        };
+        let dropped_refs: Vec<&ChatSession> = dropped.iter().collect();
+        history
+            .generations
+            .retain(|generation| during(&kept, generation.unix_ms) || !during(&dropped_refs, generation.unix_ms));
+
+        dropped.len()
+    }
+}
+
+/// The `--interactive` picks, in the state directory.
+pub const PICKER_STATE_FILE: &str = ".lore-picker.json";
+
+/// What was picked the last time sessions were chosen with
+/// `--interactive`. Only the sessions that were unticked are kept, so
+/// sessions that turn up later are in the lore until someone leaves them
+/// out. Every run honours the picks, not just interactive ones.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PickerState {
+    /// IDs of the sessions left out
+    pub left_out: BTreeSet<String>,
+}
+
+impl PickerState {
+    /// Load the picks, or start with none if nothing's been picked yet.
+    pub fn load(path: &Path) -> Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(text) => serde_json::from_str(&text).with_context(|| format!("Could not read {}", path.display())),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(PickerState::default()),
+            Err(error) => Err(error).with_context(|| format!("Could not read {}", path.display())),
+        }
+    }
+
+    /// Save the picks for the next run.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        write_atomic(path, serde_json::to_string_pretty(self)?.as_bytes())
+    }
+
+    /// Record a round of picking: of the sessions in `shown`, the ones in
+    /// `chosen` are in and the rest are out. Picks for sessions that
+    /// weren't shown are left as they were.
+    pub fn record(&mut self, shown: &[&ChatSession], chosen: &HashSet<String>) {
+        for session in shown {
+            if chosen.contains(&session.composer_id) {
+                self.left_out.remove(&session.composer_id);
+            } else {
+                self.left_out.insert(session.composer_id.clone());
+            }
+        }
+    }
+
+    /// The picks as a filter that leaves out the unticked sessions.
+    pub fn filter(&self) -> SessionFilter {
+        SessionFilter {
+            exclude: self.left_out.iter().cloned().collect(),
+            ..SessionFilter::default()
+        }
+    }
+}
+
+/// Split a comma-separated list of patterns.
+fn patterns(list: &str) -> Vec<String> {
+    list.split(',').map(str::trim).filter(|pattern| !pattern.is_empty()).map(str::to_string).collect()
+}
+
+/// Match `text` against a pattern with `*` and `?` wildcards, ignoring case.
+pub fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Where the last `*` was, and how much of the text it has taken so far
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            // Let the `*` take one more character and try again
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
@@ -0,0 +1,148 @@
+use crate::front_matter::quoted;
+
+/// The section the session pages go in, under `content/`.
+pub const SESSIONS_SECTION: &str = "sessions";
+
+/// The taxonomies the session pages are filed under.
+pub const TAXONOMIES: [&str; 2] = ["tags", "topics"];
+
+/// Labels the site's templates show, in the lore's language.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SiteLabels {
+    /// The site's title
+    pub title: String,
+    /// What the tags taxonomy is called
+    pub tags: String,
+    /// What the topics taxonomy is called
+    pub topics: String,
+    /// The heading over the latest sessions on the front page
+    pub recent: String,
+}
+
+/// Zola's `config.toml` for the site.
+pub fn config_toml(labels: &SiteLabels, language: &str) -> String {
+    let taxonomies: Vec<String> = TAXONOMIES.iter().map(|name| format!("    {{ name = \"{}\" }},", name)).collect();
+    format!(
+        "base_url = \"/\"\ntitle = {}\ndefault_language = {}\ncompile_sass = false\nbuild_search_index = false\ntaxonomies = [\n{}\n]\n\n[markdown]\nhighlight_code = true\n\n[extra]\ntags_title = {}\ntopics_title = {}\nrecent_title = {}\n",
+        quoted(&labels.title),
+        quoted(language),
+        taxonomies.join("\n"),
+        quoted(&labels.tags),
+        quoted(&labels.topics),
+        quoted(&labels.recent),
+    )
+}
+
+/// The front matter of a section's `_index.md`: its title, and its pages
+/// newest first.
+pub fn section_front_matter(title: &str) -> String {
+    format!("+++\ntitle = {}\nsort_by = \"date\"\n+++\n\n", quoted(title))
+}
+
+/// The templates Zola renders the site with: a plain layout, the front
+/// page, the sessions list, a session, and the tag and topic pages.
+pub const TEMPLATES: &[(&str, &str)] = &[
+    (
+        "base.html",
+        r#"<!DOCTYPE html>
+<html lang="{{ lang }}">
+<head>
+  <meta charset="utf-8">
+  <meta name="viewport" content="width=device-width, initial-scale=1">
+  <title>{% block title %}{{ config.title }}{% endblock title %}</title>
+  <style>
+    body { max-width: 52rem; margin: 2rem auto; padding: 0 1rem; font-family: system-ui, sans-serif; line-height: 1.55; }
+    nav a { margin-right: 1rem; }
+    pre { overflow-x: auto; padding: .75rem; }
+    .meta { color: #666; }
+  </style>
+</head>
+<body>
+  {% set sessions = get_section(path="sessions/_index.md", metadata_only=true) %}
+  <nav>
+    <a href="{{ get_url(path="@/_index.md") }}">{{ config.title }}</a>
+    <a href="{{ sessions.permalink }}">{{ sessions.title }}</a>
+    <a href="{{ get_url(path="tags/") }}">{{ config.extra.tags_title }}</a>
+    <a href="{{ get_url(path="topics/") }}">{{ config.extra.topics_title }}</a>
+  </nav>
+  <main>
+  {% block content %}{% endblock content %}
+  </main>
+</body>
+</html>
+"#,
+    ),
+    (
+        "index.html",
+        r#"{% extends "base.html" %}
+{% block content %}
+<h1>{{ section.title }}</h1>
+{% set sessions = get_section(path="sessions/_index.md") %}
+<h2>{{ config.extra.recent_title }}</h2>
+<ul>
+{% for page in sessions.pages | slice(end=10) %}
+  <li><a href="{{ page.permalink }}">{{ page.title }}</a> <span class="meta">{{ page.date | date(format="%Y-%m-%d") }}</span></li>
+{% endfor %}
+</ul>
+{{ section.content | safe }}
+{% endblock content %}
+"#,
+    ),
+    (
+        "section.html",
+        r#"{% extends "base.html" %}
+{% block title %}{{ section.title }} - {{ config.title }}{% endblock title %}
+{% block content %}
+<h1>{{ section.title }}</h1>
+<ul>
+{% for page in section.pages %}
+  <li><a href="{{ page.permalink }}">{{ page.title }}</a> <span class="meta">{{ page.date | date(format="%Y-%m-%d") }}</span></li>
+{% endfor %}
+</ul>
+{% endblock content %}
+"#,
+    ),
+    (
+        "page.html",
+        r#"{% extends "base.html" %}
+{% block title %}{{ page.title }} - {{ config.title }}{% endblock title %}
+{% block content %}
+<h1>{{ page.title }}</h1>
+<p class="meta">
+  {{ page.date | date(format="%Y-%m-%d %H:%M") }}
+  {% for name in ["tags", "topics"] %}{% if page.taxonomies[name] %}
+    | {% for term in page.taxonomies[name] %}<a href="{{ get_taxonomy_url(kind=name, name=term) }}">{{ term }}</a>{% if not loop.last %}, {% endif %}{% endfor %}
+  {% endif %}{% endfor %}
+</p>
+{{ page.content | safe }}
+{% endblock content %}
+"#,
+    ),
+    (
+        "taxonomy_list.html",
+        r#"{% extends "base.html" %}
+{% block content %}
+<h1>{% if taxonomy.name == "tags" %}{{ config.extra.tags_title }}{% else %}{{ config.extra.topics_title }}{% endif %}</h1>
+<ul>
+{% for term in terms %}
+  <li><a href="{{ term.permalink }}">{{ term.name }}</a> ({{ term.pages | length }})</li>
+{% endfor %}
+</ul>
+{% endblock content %}
+"#,
+    ),
+    (
+        "taxonomy_single.html",
+        r#"{% extends "base.html" %}
+{% block title %}{{ term.name }} - {{ config.title }}{% endblock title %}
+{% block content %}
+<h1>{{ term.name }}</h1>
+<ul>
+{% for page in term.pages %}
+  <li><a href="{{ page.permalink }}">{{ page.title }}</a> <span class="meta">{{ page.date | date(format="%Y-%m-%d") }}</span></li>
+{% endfor %}
+</ul>
+{% endblock content %}
+"#,
+    ),
+];
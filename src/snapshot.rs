@@ -0,0 +1,319 @@
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, Utc};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashSet};
+use std::fs::{self, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::atomic::write_atomic;
+use crate::versioning::{check_readable, FORMAT_VERSION};
+use crate::{ChatGeneration, ChatPrompt, ComposerData};
+
+/// Where snapshots are kept, in the state directory.
+pub const SNAPSHOT_DIR: &str = "snapshots";
+
+/// The list of runs, one JSON object per line, oldest first.
+const INDEX_FILE: &str = "index.jsonl";
+
+/// Everything a run extracted, as it's stored in a snapshot.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Dataset {
+    /// `FORMAT_VERSION` when it was saved (0 for datasets from before
+    /// it was recorded)
+    #[serde(default)]
+    pub format_version: u32,
+    /// Which source it came from
+    pub source: String,
+    /// The sessions, with their transcripts
+    pub sessions: Vec<ComposerData>,
+    /// The generations
+    pub generations: Vec<ChatGeneration>,
+    /// The prompts
+    pub prompts: Vec<ChatPrompt>,
+}
+
+/// The same thing borrowed, so saving doesn't need a copy of the data.
+#[derive(Serialize)]
+struct DatasetRef<'a> {
+    format_version: u32,
+    source: &'a str,
+    sessions: &'a [ComposerData],
+    generations: &'a [ChatGeneration],
+    prompts: &'a [ChatPrompt],
+}
+
+/// One run's snapshot in the index.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotEntry {
+    /// The SHA-256 of the dataset, which is also where its blob lives.
+    /// Runs that extracted exactly the same data share a blob.
+    pub id: String,
+    /// When the run took it
+    pub taken_at: DateTime<Utc>,
+    /// Which source it came from
+    pub source: String,
+    /// How many sessions it holds
+    pub sessions: usize,
+    /// How many messages those sessions hold between them
+    pub messages: usize,
+    /// The compressed blob's size in bytes
+    pub bytes: u64,
+}
+
+impl SnapshotEntry {
+    /// The first twelve characters of the ID, which is plenty to tell
+    /// snapshots apart.
+    pub fn short_id(&self) -> &str {
+        &self.id[..self.id.len().min(12)]
+    }
+}
+
+/// What `prune` removed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Pruned {
+    /// Runs dropped from the index
+    pub runs: usize,
+    /// Blobs no remaining run needed
+    pub blobs: usize,
+}
+
+/// A store of gzip-compressed, content-addressed snapshots of everything
+/// each run extracted - a history of the history that outlives whatever
+/// the chat application decides to keep. Blobs live under
+/// `objects/<first two hex digits>/<rest>.json.gz`, next to an index of
+/// the runs that took them.
+pub struct SnapshotStore {
+    dir: PathBuf,
+}
+
+impl SnapshotStore {
+    /// The store in a state directory (created when the first snapshot
+    /// is saved).
+    pub fn open(state_dir: &Path) -> Self {
+        SnapshotStore {
+            dir: state_dir.join(SNAPSHOT_DIR),
+        }
+    }
+
+    /// Where the store is.
+    pub fn path(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Save a run's data. Data identical to an earlier snapshot isn't
+    /// stored twice; the run is still added to the index.
+    pub fn save(
+        &self,
+        source: &str,
+        sessions: &[ComposerData],
+        generations: &[ChatGeneration],
+        prompts: &[ChatPrompt],
+    ) -> Result<SnapshotEntry> {
+        let json = serde_json::to_vec(&DatasetRef {
+            format_version: FORMAT_VERSION,
+            source,
+            sessions,
+            generations,
+            prompts,
+        })?;
+        let id = hex_digest(&json);
+
+        fs::create_dir_all(&self.dir).with_context(|| format!("Could not create {}", self.dir.display()))?;
+        // Snapshots hold every word of every chat, unredacted, so keep them
+        // out of any repository a STATE_DIR is pointed into
+        let gitignore = self.dir.join(".gitignore");
+        if !gitignore.exists() {
+            fs::write(&gitignore, "*\n")?;
+        }
+
+        let blob = self.blob_path(&id);
+        if !blob.exists() {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(&json)?;
+            let compressed = encoder.finish()?;
+            fs::create_dir_all(blob.parent().unwrap_or(&self.dir))?;
+            // Write then rename, so an interrupted run never leaves a blob
+            // whose contents don't match its name
+            write_atomic(&blob, &compressed)?;
+        }
+
+        let entry = SnapshotEntry {
+            id,
+            taken_at: Utc::now(),
+            source: source.to_string(),
+            sessions: sessions.iter().map(|data| data.all_composers.len()).sum(),
+            messages: sessions
+                .iter()
+                .flat_map(|data| &data.all_composers)
+                .map(|session| session.messages.len())
+                .sum(),
+            bytes: fs::metadata(&blob)?.len(),
+        };
+        let mut index = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.dir.join(INDEX_FILE))
+            .context("Could not open the snapshot index")?;
+        writeln!(index, "{}", serde_json::to_string(&entry)?)?;
+        Ok(entry)
+    }
+
+    /// Every run's snapshot, oldest first.
+    pub fn list(&self) -> Result<Vec<SnapshotEntry>> {
+        match fs::read_to_string(self.dir.join(INDEX_FILE)) {
+            Ok(text) => Ok(text.lines().filter_map(|line| serde_json::from_str(line).ok()).collect()),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(error) => Err(error).context("Could not read the snapshot index"),
+        }
+    }
+
+    /// Find a snapshot by ID, by a prefix of one (as long as it's
+    /// unambiguous), or by `latest`.
+    pub fn find(&self, id: &str) -> Result<SnapshotEntry> {
+        let entries = self.list()?;
+        if id == "latest" {
+            return entries.into_iter().last().ok_or_else(|| anyhow!("There are no snapshots yet"));
+        }
+        let mut matches: Vec<SnapshotEntry> = entries.into_iter().filter(|entry| entry.id.starts_with(id)).collect();
+        let distinct: HashSet<&str> = matches.iter().map(|entry| entry.id.as_str()).collect();
+        match distinct.len() {
+            0 => bail!("No snapshot matches '{}'", id),
+            1 => Ok(matches.pop().expect("there's a match")),
+            count => bail!("'{}' matches {} different snapshots; use more of the ID", id, count),
+        }
+    }
+
+    /// Read a snapshot's data back, checking it's what was saved.
+    pub fn load(&self, entry: &SnapshotEntry) -> Result<Dataset> {
+        let blob = self.blob_path(&entry.id);
+        let compressed = fs::read(&blob).with_context(|| format!("Snapshot {} is missing its data", entry.short_id()))?;
+        let mut json = Vec::new();
+        GzDecoder::new(compressed.as_slice())
+            .read_to_end(&mut json)
+            .with_context(|| format!("Snapshot {} is corrupt", entry.short_id()))?;
+        if hex_digest(&json) != entry.id {
+            bail!("Snapshot {} is corrupt (its contents don't match its ID)", entry.short_id());
+        }
+        let dataset: Dataset = serde_json::from_slice(&json)?;
+        check_readable(dataset.format_version, &format!("Snapshot {}", entry.short_id()))?;
+        Ok(dataset)
+    }
+
+    /// Keep the newest `keep` runs and delete the rest, along with any blob
+    /// none of the remaining runs point at.
+    pub fn prune(&self, keep: usize) -> Result<Pruned> {
+        let entries = self.list()?;
+        let removed = entries.len().saturating_sub(keep);
+        let kept = &entries[removed..];
+        let mut index = String::new();
+        for entry in kept {
+            index.push_str(&serde_json::to_string(entry)?);
+            index.push('\n');
+        }
+        if self.dir.exists() {
+            write_atomic(&self.dir.join(INDEX_FILE), index.as_bytes())?;
+        }
+
+        let wanted: HashSet<PathBuf> = kept.iter().map(|entry| self.blob_path(&entry.id)).collect();
+        let mut blobs = 0;
+        let objects = self.dir.join("objects");
+        for shard in fs::read_dir(&objects).into_iter().flatten().flatten() {
+            for blob in fs::read_dir(shard.path()).into_iter().flatten().flatten() {
+                if !wanted.contains(&blob.path()) {
+                    fs::remove_file(blob.path())?;
+                    blobs += 1;
+                }
+            }
+            // Only succeeds once the shard is empty, which is what we want
+            let _ = fs::remove_dir(shard.path());
+        }
+        Ok(Pruned { runs: removed, blobs })
+    }
+
+    fn blob_path(&self, id: &str) -> PathBuf {
+        let (shard, rest) = id.split_at(2.min(id.len()));
+        self.dir.join("objects").join(shard).join(format!("{}.json.gz", rest))
+    }
+}
+
+fn hex_digest(data: &[u8]) -> String {
+    Sha256::digest(data).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// How two snapshots' sessions differ.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SnapshotDiff {
+    /// Sessions only in the newer snapshot, as (ID, name)
+    pub added: Vec<(String, String)>,
+    /// Sessions only in the older one - usually ones the chat application
+    /// has since pruned, which the older snapshot still remembers
+    pub removed: Vec<(String, String)>,
+    /// Sessions in both whose messages changed, as (ID, name, messages
+    /// before, messages after)
+    pub changed: Vec<(String, String, usize, usize)>,
+}
+
+impl SnapshotDiff {
+    /// Compare an older snapshot with a newer one.
+    pub fn between(older: &Dataset, newer: &Dataset) -> Self {
+        let sessions = |dataset: &Dataset| -> BTreeMap<String, (String, usize, i64)> {
+            dataset
+                .sessions
+                .iter()
+                .flat_map(|data| &data.all_composers)
+                .map(|session| {
+                    (
+                        session.composer_id.clone(),
+                        (session.name.clone(), session.messages.len(), session.last_updated_at),
+                    )
+                })
+                .collect()
+        };
+        let (before, after) = (sessions(older), sessions(newer));
+
+        let mut diff = SnapshotDiff::default();
+        for (id, (name, messages, updated)) in &after {
+            match before.get(id) {
+                None => diff.added.push((id.clone(), name.clone())),
+                Some((_, old_messages, old_updated)) if old_messages != messages || old_updated != updated => {
+                    diff.changed.push((id.clone(), name.clone(), *old_messages, *messages));
+                }
+                Some(_) => {}
+            }
+        }
+        for (id, (name, _, _)) in &before {
+            if !after.contains_key(id) {
+                diff.removed.push((id.clone(), name.clone()));
+            }
+        }
+        diff
+    }
+
+    /// Whether the two snapshots have the same sessions.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    /// The difference as text, one session per line.
+    pub fn describe(&self) -> String {
+        let mut lines = Vec::new();
+        for (id, name) in &self.added {
+            lines.push(format!("+ {} ({})", name, id));
+        }
+        for (id, name) in &self.removed {
+            lines.push(format!("- {} ({})", name, id));
+        }
+        for (id, name, before, after) in &self.changed {
+            lines.push(format!("~ {} ({}): {} -> {} messages", name, id, before, after));
+        }
+        if lines.is_empty() {
+            lines.push("No differences".to_string());
+        }
+        lines.join("\n") + "\n"
+    }
+}
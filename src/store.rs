@@ -0,0 +1,686 @@
+use anyhow::{anyhow, Result};
+#[cfg(feature = "native")]
+use anyhow::Context;
+#[cfg(feature = "native")]
+use async_trait::async_trait;
+#[cfg(feature = "native")]
+use rusqlite::blob::Blob;
+#[cfg(feature = "native")]
+use rusqlite::{Connection, DatabaseName, OpenFlags, OptionalExtension};
+#[cfg(feature = "native")]
+use sqlx::sqlite::SqliteConnectOptions;
+#[cfg(feature = "native")]
+use sqlx::{Row, SqlitePool};
+#[cfg(feature = "native")]
+use std::collections::BTreeMap;
+#[cfg(feature = "native")]
+use std::fs;
+#[cfg(feature = "native")]
+use std::io::{Read, Seek, SeekFrom};
+#[cfg(feature = "native")]
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+#[cfg(feature = "native")]
+use tokio::sync::mpsc::Sender;
+
+use crate::config::Config;
+
+/// A read-only key/value view over wherever a chat application keeps its data.
+/// Cursor and VS Code keep JSON blobs in SQLite tables, Continue keeps one JSON
+/// file per session, and tests want everything in memory - but the extractors
+/// only ever need to look up a key or walk all keys with a given prefix.
+#[cfg(feature = "native")]
+#[async_trait]
+pub trait RecordStore: Send + Sync {
+    /// Fetch the raw value stored under `key`, or `None` if there isn't one.
+    async fn get_key(&self, key: &str) -> Result<Option<String>>;
+
+    /// Fetch several keys at once, returning the ones that exist.
+    /// Stores that can do this in one round-trip (like SQLite) override it;
+    /// everyone else just looks the keys up one by one.
+    async fn get_keys(&self, keys: &[&str]) -> Result<BTreeMap<String, String>> {
+        let mut records = BTreeMap::new();
+        for key in keys {
+            if let Some(value) = self.get_key(key).await? {
+                records.insert(key.to_string(), value);
+            }
+        }
+        Ok(records)
+    }
+
+    /// Like `get_keys`, but leaving out values bigger than `max_bytes`, so
+    /// they can be streamed with `stream_value` instead.
+    async fn get_keys_within(&self, keys: &[&str], max_bytes: u64) -> Result<BTreeMap<String, String>> {
+        let mut records = self.get_keys(keys).await?;
+        records.retain(|_, value| value.len() as u64 <= max_bytes);
+        Ok(records)
+    }
+
+    /// Fetch every `(key, value)` pair whose key starts with `prefix`, sorted by key.
+    async fn scan_prefix(&self, prefix: &str) -> Result<Vec<(String, String)>>;
+
+    /// How many records the store holds in total.
+    async fn count(&self) -> Result<i64>;
+
+    /// How many records have a key starting with `prefix`.
+    async fn count_prefix(&self, prefix: &str) -> Result<i64> {
+        Ok(self.scan_prefix(prefix).await?.len() as i64)
+    }
+
+    /// A human-readable description of where the records live (used in messages).
+    fn location(&self) -> String;
+
+    /// The names of the tables (or table-like groupings) backing this store.
+    async fn tables(&self) -> Result<Vec<String>> {
+        Ok(Vec::new())
+    }
+
+    /// How many bytes the value under `key` has, or `None` if there isn't one.
+    async fn value_size(&self, key: &str) -> Result<Option<u64>> {
+        Ok(self.get_key(key).await?.map(|value| value.len() as u64))
+    }
+
+    /// Up to `len` bytes of the value under `key`, starting at byte
+    /// `offset` (empty past the end). Stores that can read part of a value
+    /// without the rest (like SQLite) override it.
+    async fn read_range(&self, key: &str, offset: u64, len: u64) -> Result<Vec<u8>> {
+        let value = self.get_key(key).await?.unwrap_or_default();
+        Ok(byte_range(value.as_bytes(), offset, len).to_vec())
+    }
+
+    /// Send the value under `key` to `chunks`, up to `chunk_bytes` at a
+    /// time, stopping early if nobody's listening any more. By default
+    /// each chunk is a `read_range`; stores that can keep a value open
+    /// between chunks (like SQLite) override it.
+    async fn send_value(&self, key: &str, chunk_bytes: u64, chunks: Sender<Vec<u8>>) -> Result<()> {
+        let size = self.value_size(key).await?.unwrap_or(0);
+        let mut offset = 0;
+        while offset < size {
+            let chunk = self.read_range(key, offset, chunk_bytes.max(1)).await?;
+            if chunk.is_empty() {
+                break;
+            }
+            offset += chunk.len() as u64;
+            if chunks.send(chunk).await.is_err() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Like `get_key`, but treats a missing key as an error naming the store.
+    async fn require_key(&self, key: &str) -> Result<String> {
+        self.get_key(key)
+            .await?
+            .ok_or_else(|| anyhow!("Key '{}' not found in {}", key, self.location()))
+    }
+}
+
+/// Records stored in a SQLite key/value table, like Cursor's `ItemTable`.
+#[cfg(feature = "native")]
+pub struct SqliteStore {
+    /// Our connection to the SQLite database
+    pool: SqlitePool,
+    /// Which table holds the key/value pairs
+    table: String,
+    /// The database file we connected to
+    path: String,
+    /// How we connected, for the connections big values are read through
+    options: ConnectionOptions,
+}
+
+/// How we open SQLite databases that belong to another application.
+/// Cursor may be running (and writing) while we read, so by default we open
+/// read-only, wait a while when the database is locked, and retry a few
+/// times before giving up with an explanation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectionOptions {
+    /// Never write to the database (there's no reason we ever should)
+    pub read_only: bool,
+    /// Tell SQLite the file can't change underneath us, which skips locking
+    /// entirely. Only safe when the owning application is closed.
+    pub immutable: bool,
+    /// How long a single query waits for a lock before reporting "busy"
+    pub busy_timeout: Duration,
+    /// How many extra attempts to make when the database stays busy
+    pub retries: u32,
+    /// Create the database if it doesn't exist (only makes sense with
+    /// `read_only` off)
+    pub create: bool,
+}
+
+impl Default for ConnectionOptions {
+    fn default() -> Self {
+        ConnectionOptions {
+            read_only: true,
+            immutable: false,
+            busy_timeout: Duration::from_secs(5),
+            retries: 3,
+            create: false,
+        }
+    }
+}
+
+impl ConnectionOptions {
+    /// Take the connection settings from the configuration.
+    pub fn from_config(config: &Config) -> Self {
+        ConnectionOptions {
+            read_only: true,
+            immutable: config.db_immutable,
+            busy_timeout: Duration::from_millis(config.db_busy_timeout_ms),
+            retries: config.db_connect_retries,
+            create: false,
+        }
+    }
+}
+
+#[cfg(feature = "native")]
+impl SqliteStore {
+    /// Connect to the database at `path`, reading key/value pairs from `table`.
+    pub async fn open(path: &str, table: &str) -> Result<Self> {
+        SqliteStore::open_with(path, table, &ConnectionOptions::default()).await
+    }
+
+    /// Connect with explicit connection options.
+    /// If the database is locked we back off and try again a bounded number
+    /// of times; any other failure is reported straight away.
+    pub async fn open_with(path: &str, table: &str, options: &ConnectionOptions) -> Result<Self> {
+        validate_table(table)?;
+        let connect_options = SqliteConnectOptions::new()
+            .filename(path)
+            .read_only(options.read_only)
+            .immutable(options.immutable)
+            .create_if_missing(options.create)
+            .busy_timeout(options.busy_timeout);
+
+        let mut attempt = 0;
+        loop {
+            match connect_and_probe(&connect_options).await {
+                Ok(pool) => {
+                    return Ok(SqliteStore {
+                        pool,
+                        table: table.to_string(),
+                        path: path.to_string(),
+                        options: options.clone(),
+                    })
+                }
+                Err(error) if is_busy(&error) && attempt < options.retries => {
+                    // Give whoever holds the lock a moment, a little longer each time
+                    attempt += 1;
+                    tokio::time::sleep(Duration::from_millis(250 * 2u64.pow(attempt))).await;
+                }
+                Err(error) if is_busy(&error) => {
+                    return Err(anyhow!(
+                        "The database at {} is locked by another process (tried {} times). \
+                        Close Cursor and try again, or set DB_BUSY_TIMEOUT_MS to wait longer.",
+                        path,
+                        attempt + 1
+                    ));
+                }
+                Err(error) => {
+                    return Err(error).with_context(|| {
+                        format!(
+                            "Could not open the database at {} - check that the path is right \
+                            and readable (DB_PATH / WORKSPACE_ID)",
+                            path
+                        )
+                    });
+                }
+            }
+        }
+    }
+
+    /// A view of another key/value table in the same database.
+    /// The connection pool is shared, so this doesn't open the file again.
+    pub fn with_table(&self, table: &str) -> Result<SqliteStore> {
+        validate_table(table)?;
+        Ok(SqliteStore {
+            pool: self.pool.clone(),
+            table: table.to_string(),
+            path: self.path.clone(),
+            options: self.options.clone(),
+        })
+    }
+
+    /// Write records into the table (creating it if it's missing),
+    /// replacing any with the same keys, all in one transaction. Only for
+    /// stores opened with `read_only: false` - `restore` is the one thing
+    /// that ever writes to a chat application's database.
+    pub async fn put_records(&self, records: &BTreeMap<String, String>) -> Result<()> {
+        let mut transaction = self.pool.begin().await?;
+        let create = format!(
+            "CREATE TABLE IF NOT EXISTS \"{}\" (key TEXT UNIQUE ON CONFLICT REPLACE, value BLOB)",
+            self.table
+        );
+        sqlx::query(&create).execute(&mut *transaction).await?;
+        let insert = format!("INSERT OR REPLACE INTO \"{}\" (key, value) VALUES (?, ?)", self.table);
+        for (key, value) in records {
+            validate_key(key)?;
+            sqlx::query(&insert).bind(key).bind(value).execute(&mut *transaction).await?;
+        }
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    /// Open the value under `key` for SQLite's incremental blob I/O and
+    /// hand it to `read`, on a blocking thread. A blob handle reads the
+    /// pages asked for and no more; a query for the value, even for
+    /// `substr` of it, loads the whole value every time. Gives `None` if
+    /// there's no such key.
+    async fn with_blob<T, F>(&self, key: &str, read: F) -> Result<Option<T>>
+    where
+        T: Send + 'static,
+        F: FnOnce(Blob<'_>) -> Result<T> + Send + 'static,
+    {
+        validate_key(key)?;
+        let (path, table, key) = (self.path.clone(), self.table.clone(), key.to_string());
+        let options = self.options.clone();
+        tokio::task::spawn_blocking(move || {
+            // sqlx has no blob handles, so this is a connection of its own
+            let flags = OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_URI | OpenFlags::SQLITE_OPEN_NO_MUTEX;
+            let connection = Connection::open_with_flags(sqlite_uri(&path, options.immutable), flags)
+                .with_context(|| format!("Could not open the database at {}", path))?;
+            connection.busy_timeout(options.busy_timeout)?;
+            let query = format!("SELECT rowid FROM \"{}\" WHERE key = ?", table);
+            let Some(rowid) = connection.query_row(&query, [&key], |row| row.get::<_, i64>(0)).optional()? else {
+                return Ok(None);
+            };
+            let blob = connection.blob_open(DatabaseName::Main, &table, "value", rowid, true)?;
+            read(blob).map(Some)
+        })
+        .await
+        .context("Reading the database stopped unexpectedly")?
+    }
+}
+
+#[cfg(feature = "native")]
+#[async_trait]
+impl RecordStore for SqliteStore {
+    async fn get_key(&self, key: &str) -> Result<Option<String>> {
+        validate_key(key)?;
+        // Table names can't be bound, but we checked this one when the store was made
+        let query = format!("SELECT value FROM \"{}\" WHERE key = ?", self.table);
+
+        let row = sqlx::query(&query).bind(key).fetch_optional(&self.pool).await?;
+        Ok(row.map(|row| row.get(0)))
+    }
+
+    async fn get_keys(&self, keys: &[&str]) -> Result<BTreeMap<String, String>> {
+        if keys.is_empty() {
+            return Ok(BTreeMap::new());
+        }
+        for key in keys {
+            validate_key(key)?;
+        }
+        // One `IN (?, ?, ...)` query instead of a round-trip per key, which
+        // adds up quickly on network filesystems
+        let placeholders = vec!["?"; keys.len()].join(", ");
+        let query = format!(
+            "SELECT key, value FROM \"{}\" WHERE key IN ({})",
+            self.table, placeholders
+        );
+
+        let mut query = sqlx::query(&query);
+        for key in keys {
+            query = query.bind(*key);
+        }
+        let rows = query.fetch_all(&self.pool).await?;
+        Ok(rows.into_iter().map(|row| (row.get(0), row.get(1))).collect())
+    }
+
+    async fn get_keys_within(&self, keys: &[&str], max_bytes: u64) -> Result<BTreeMap<String, String>> {
+        if keys.is_empty() {
+            return Ok(BTreeMap::new());
+        }
+        for key in keys {
+            validate_key(key)?;
+        }
+        // Still one query, but the big values never leave the database
+        let placeholders = vec!["?"; keys.len()].join(", ");
+        let query = format!(
+            "SELECT key, value FROM \"{}\" WHERE key IN ({}) AND octet_length(value) <= ?",
+            self.table, placeholders
+        );
+
+        let mut query = sqlx::query(&query);
+        for key in keys {
+            query = query.bind(*key);
+        }
+        let rows = query.bind(max_bytes.min(i64::MAX as u64) as i64).fetch_all(&self.pool).await?;
+        Ok(rows.into_iter().map(|row| (row.get(0), row.get(1))).collect())
+    }
+
+    async fn scan_prefix(&self, prefix: &str) -> Result<Vec<(String, String)>> {
+        if !prefix.is_empty() {
+            validate_key(prefix)?;
+        }
+        // A plain string comparison rather than LIKE, so `_` and `%` in a
+        // prefix mean themselves and matching stays case-sensitive
+        let query = format!(
+            "SELECT key, value FROM \"{}\" WHERE substr(key, 1, length(?1)) = ?1 ORDER BY key",
+            self.table
+        );
+
+        let rows = sqlx::query(&query).bind(prefix).fetch_all(&self.pool).await?;
+        Ok(rows.into_iter().map(|row| (row.get(0), row.get(1))).collect())
+    }
+
+    async fn count(&self) -> Result<i64> {
+        let query = format!("SELECT COUNT(*) FROM \"{}\"", self.table);
+        let count: i64 = sqlx::query_scalar(&query).fetch_one(&self.pool).await?;
+        Ok(count)
+    }
+
+    async fn count_prefix(&self, prefix: &str) -> Result<i64> {
+        // Counting in SQL means we never pull the (often large) values over
+        let query = format!(
+            "SELECT COUNT(*) FROM \"{}\" WHERE substr(key, 1, length(?1)) = ?1",
+            self.table
+        );
+        let count: i64 = sqlx::query_scalar(&query).bind(prefix).fetch_one(&self.pool).await?;
+        Ok(count)
+    }
+
+    async fn value_size(&self, key: &str) -> Result<Option<u64>> {
+        validate_key(key)?;
+        // octet_length reads the size from the record without loading the value
+        let query = format!("SELECT octet_length(value) FROM \"{}\" WHERE key = ?", self.table);
+        let size: Option<Option<i64>> = sqlx::query_scalar(&query).bind(key).fetch_optional(&self.pool).await?;
+        Ok(size.map(|size| size.unwrap_or(0) as u64))
+    }
+
+    async fn read_range(&self, key: &str, offset: u64, len: u64) -> Result<Vec<u8>> {
+        let range = self.with_blob(key, move |mut blob| {
+            blob.seek(SeekFrom::Start(offset))?;
+            let mut range = Vec::new();
+            blob.take(len).read_to_end(&mut range)?;
+            Ok(range)
+        });
+        Ok(range.await?.unwrap_or_default())
+    }
+
+    async fn send_value(&self, key: &str, chunk_bytes: u64, chunks: Sender<Vec<u8>>) -> Result<()> {
+        // One blob handle for the whole value, read from start to end
+        let sent = self.with_blob(key, move |mut blob| {
+            loop {
+                let mut chunk = Vec::new();
+                (&mut blob).take(chunk_bytes.max(1)).read_to_end(&mut chunk)?;
+                if chunk.is_empty() || chunks.blocking_send(chunk).is_err() {
+                    return Ok(());
+                }
+            }
+        });
+        sent.await.map(|_| ())
+    }
+
+    fn location(&self) -> String {
+        self.path.clone()
+    }
+
+    async fn tables(&self) -> Result<Vec<String>> {
+        let tables_query = "SELECT name FROM sqlite_master WHERE type='table'";
+        let tables: Vec<String> = sqlx::query_scalar(tables_query)
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(tables)
+    }
+}
+
+/// How much of a big value is read at a time when it's streamed.
+#[cfg(feature = "native")]
+pub const CHUNK_BYTES: u64 = 4 * 1024 * 1024;
+
+/// Read the value under `key` a chunk at a time and hand it to `parse` as
+/// a reader, so a value of hundreds of MB never has to be held as one
+/// string. `parse` runs on a blocking thread while the chunks are fetched;
+/// at most a couple of chunks are in memory at once. Returns `None` if
+/// there's no such key.
+#[cfg(feature = "native")]
+pub async fn stream_value<T, F>(store: &dyn RecordStore, key: &str, chunk_bytes: u64, parse: F) -> Result<Option<T>>
+where
+    T: Send + 'static,
+    F: FnOnce(ChunkReader) -> Result<T> + Send + 'static,
+{
+    if store.value_size(key).await?.is_none() {
+        return Ok(None);
+    }
+    let (sender, receiver) = tokio::sync::mpsc::channel(2);
+    let parser = tokio::task::spawn_blocking(move || parse(ChunkReader::new(receiver)));
+    // The parser stops listening when it's hit an error; that's reported below
+    store.send_value(key, chunk_bytes, sender).await?;
+    let parsed = parser.await.context("The parser stopped unexpectedly")??;
+    Ok(Some(parsed))
+}
+
+/// The chunks of a streamed value, read as one stream of bytes.
+#[cfg(feature = "native")]
+pub struct ChunkReader {
+    /// Where the chunks arrive
+    receiver: tokio::sync::mpsc::Receiver<Vec<u8>>,
+    /// The chunk being read
+    chunk: Vec<u8>,
+    /// How much of it has been read
+    position: usize,
+}
+
+#[cfg(feature = "native")]
+impl ChunkReader {
+    fn new(receiver: tokio::sync::mpsc::Receiver<Vec<u8>>) -> Self {
+        ChunkReader { receiver, chunk: Vec::new(), position: 0 }
+    }
+}
+
+#[cfg(feature = "native")]
+impl std::io::Read for ChunkReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.position == self.chunk.len() {
+            match self.receiver.blocking_recv() {
+                Some(chunk) => {
+                    self.chunk = chunk;
+                    self.position = 0;
+                }
+                None => return Ok(0),
+            }
+        }
+        let count = buf.len().min(self.chunk.len() - self.position);
+        buf[..count].copy_from_slice(&self.chunk[self.position..self.position + count]);
+        self.position += count;
+        Ok(count)
+    }
+}
+
+/// The bytes of `value` from `offset`, up to `len` of them.
+#[cfg(feature = "native")]
+fn byte_range(value: &[u8], offset: u64, len: u64) -> &[u8] {
+    let start = (offset as usize).min(value.len());
+    let end = start.saturating_add(len as usize).min(value.len());
+    &value[start..end]
+}
+
+/// A SQLite `file:` URI for the database at `path`, opened without any
+/// locking with `immutable`.
+pub(crate) fn sqlite_uri(path: &str, immutable: bool) -> String {
+    let path = path.replace('%', "%25").replace('?', "%3f").replace('#', "%23");
+    format!("file:{}{}", path, if immutable { "?immutable=1" } else { "" })
+}
+
+/// Check that a record key is something a chat application could have written.
+/// Keys are bound as query parameters, so quotes and other punctuation are
+/// fine - but an empty key or one with control characters almost certainly
+/// means a typo in the configuration.
+pub fn validate_key(key: &str) -> Result<()> {
+    if key.is_empty() {
+        return Err(anyhow!("Record keys can't be empty"));
+    }
+    if key.chars().any(char::is_control) {
+        return Err(anyhow!("Record key {:?} contains control characters", key));
+    }
+    Ok(())
+}
+
+/// Check that a table name is a plain identifier (letters, digits and `_`).
+/// Unlike keys, table names have to be written into the SQL itself.
+pub fn validate_table(table: &str) -> Result<()> {
+    let mut chars = table.chars();
+    let starts_well = chars.next().is_some_and(|first| first.is_ascii_alphabetic() || first == '_');
+    if !starts_well || !chars.all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err(anyhow!(
+            "'{}' isn't a valid table name - use letters, digits and underscores only",
+            table
+        ));
+    }
+    Ok(())
+}
+
+/// Open a pool and run a trivial query, so lock problems show up now rather
+/// than halfway through an extraction.
+#[cfg(feature = "native")]
+async fn connect_and_probe(options: &SqliteConnectOptions) -> std::result::Result<SqlitePool, sqlx::Error> {
+    let pool = SqlitePool::connect_with(options.clone()).await?;
+    sqlx::query("SELECT COUNT(*) FROM sqlite_master").fetch_one(&pool).await?;
+    Ok(pool)
+}
+
+/// Whether an error means "someone else has the database locked".
+#[cfg(feature = "native")]
+fn is_busy(error: &sqlx::Error) -> bool {
+    match error {
+        // SQLITE_BUSY, SQLITE_LOCKED and their extended variants
+        sqlx::Error::Database(db_error) => {
+            matches!(db_error.code().as_deref(), Some("5" | "6" | "261" | "517"))
+                || db_error.message().contains("database is locked")
+        }
+        _ => false,
+    }
+}
+
+/// Records stored as a directory of JSON files, one record per file.
+/// The key is the file name without its `.json` extension.
+#[cfg(feature = "native")]
+pub struct JsonDirStore {
+    /// The directory holding the JSON files
+    dir: PathBuf,
+}
+
+#[cfg(feature = "native")]
+impl JsonDirStore {
+    /// Open a directory of JSON files, expanding a leading `~`.
+    pub fn open(dir: &str) -> Result<Self> {
+        let dir = PathBuf::from(shellexpand::tilde(dir).to_string());
+        if !dir.is_dir() {
+            return Err(anyhow!("{} is not a directory", dir.display()));
+        }
+        Ok(JsonDirStore { dir })
+    }
+
+    /// The directory this store reads from.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+}
+
+#[cfg(feature = "native")]
+#[async_trait]
+impl RecordStore for JsonDirStore {
+    async fn get_key(&self, key: &str) -> Result<Option<String>> {
+        let path = self.dir.join(format!("{}.json", key));
+        if !path.is_file() {
+            return Ok(None);
+        }
+        let value = fs::read_to_string(&path)
+            .with_context(|| format!("Could not read {}", path.display()))?;
+        Ok(Some(value))
+    }
+
+    async fn scan_prefix(&self, prefix: &str) -> Result<Vec<(String, String)>> {
+        let mut records = Vec::new();
+
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().is_none_or(|ext| ext != "json") {
+                continue;
+            }
+            let Some(key) = path.file_stem().map(|stem| stem.to_string_lossy().to_string()) else {
+                continue;
+            };
+            if key.starts_with(prefix) {
+                let value = fs::read_to_string(&path)
+                    .with_context(|| format!("Could not read {}", path.display()))?;
+                records.push((key, value));
+            }
+        }
+
+        records.sort();
+        Ok(records)
+    }
+
+    async fn count(&self) -> Result<i64> {
+        Ok(self.scan_prefix("").await?.len() as i64)
+    }
+
+    fn location(&self) -> String {
+        self.dir.to_string_lossy().to_string()
+    }
+
+    async fn tables(&self) -> Result<Vec<String>> {
+        Ok(vec![self.location()])
+    }
+}
+
+/// Records kept entirely in memory.
+/// Handy for tests and fixtures, and for single-file exports that we read up front.
+#[cfg(feature = "native")]
+#[derive(Debug, Default, Clone)]
+pub struct MemoryStore {
+    /// The records, kept sorted so prefix scans come back in key order
+    records: BTreeMap<String, String>,
+}
+
+#[cfg(feature = "native")]
+impl MemoryStore {
+    /// Create an empty store.
+    pub fn new() -> Self {
+        MemoryStore::default()
+    }
+
+    /// Add (or replace) a record, returning the store so calls can be chained.
+    pub fn with_record(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.insert(key, value);
+        self
+    }
+
+    /// Add (or replace) a record.
+    pub fn insert(&mut self, key: impl Into<String>, value: impl Into<String>) {
+        self.records.insert(key.into(), value.into());
+    }
+}
+
+#[cfg(feature = "native")]
+#[async_trait]
+impl RecordStore for MemoryStore {
+    async fn get_key(&self, key: &str) -> Result<Option<String>> {
+        Ok(self.records.get(key).cloned())
+    }
+
+    async fn scan_prefix(&self, prefix: &str) -> Result<Vec<(String, String)>> {
+        Ok(self
+            .records
+            .range(prefix.to_string()..)
+            .take_while(|(key, _)| key.starts_with(prefix))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect())
+    }
+
+    async fn count(&self) -> Result<i64> {
+        Ok(self.records.len() as i64)
+    }
+
+    async fn read_range(&self, key: &str, offset: u64, len: u64) -> Result<Vec<u8>> {
+        let value = self.records.get(key).map(String::as_bytes).unwrap_or_default();
+        Ok(byte_range(value, offset, len).to_vec())
+    }
+
+    fn location(&self) -> String {
+        "in-memory store".to_string()
+    }
+}
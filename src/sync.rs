@@ -0,0 +1,235 @@
+use anyhow::{anyhow, Context, Result};
+use chrono::Utc;
+use rusqlite::{Connection, DatabaseName, OpenFlags, OptionalExtension};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::config::Config;
+use crate::extractor::{
+    assemble_conversation, conversation_keys, merge_generations, merge_prompts, parse_session_list, warn_skipped,
+    workspace_json,
+};
+use crate::importers::ImportedHistory;
+use crate::parsing::{parse_array_reader, parse_array_with, Parsed};
+use crate::progress::{Progress, Stage};
+use crate::schema::{migrate, FieldMapping, RecordKind};
+use crate::store::{sqlite_uri, validate_table};
+use crate::timestamps::{normalize_generations, normalize_sessions, validate_history};
+use crate::workspace_info::{WorkspaceInfo, EDITOR_STATE_KEY, HISTORY_KEY};
+use crate::{ChatGeneration, ChatPrompt, ComposerData};
+
+/// A blocking version of `crate::ChatExtractor`, reading Cursor's
+/// databases with rusqlite, for tools that don't run an async runtime (a
+/// cargo subcommand, a pre-commit hook). It reads the same records and
+/// gives the same results; only the `sync` feature builds it.
+pub struct ChatExtractor {
+    /// The workspace's `ItemTable`
+    workspace: Table,
+    /// Where full conversations are kept: the global database's
+    /// conversation table, or the workspace's `ItemTable` without one
+    conversations: Option<Table>,
+    /// The global `ItemTable`, for prompts and generations that never made
+    /// it into the workspace database
+    global: Option<Table>,
+    /// Who to tell how the extraction's going, and whether to stop
+    progress: Progress,
+    /// What to look for
+    config: Config,
+}
+
+impl ChatExtractor {
+    /// Open the workspace database and, if it's there, the global one.
+    pub fn new(config: &Config) -> Result<Self> {
+        let workspace = Table::open(config, &config.database_path(), "ItemTable")?;
+        let global_path = config.global_database_path();
+        let (conversations, global) = if Path::new(&global_path).is_file() {
+            let global = config
+                .include_global_storage
+                .then(|| Table::open(config, &global_path, "ItemTable"))
+                .transpose()?;
+            (Some(Table::open(config, &global_path, &config.conversation_table)?), global)
+        } else {
+            (None, None)
+        };
+        Ok(ChatExtractor { workspace, conversations, global, progress: Progress::default(), config: config.clone() })
+    }
+
+    /// Report how the extraction is going, transcript by transcript, and
+    /// give up with `Cancelled` between transcripts if asked to.
+    pub fn with_progress(mut self, progress: Progress) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    /// Extract sessions (with their transcripts), generations and prompts,
+    /// with their timestamps checked as the async pipeline checks them: a
+    /// warning for each bad one in `warnings`, dealt with as
+    /// TIMESTAMP_POLICY says.
+    pub fn extract_all(&self) -> Result<ImportedHistory> {
+        let sessions = self.parse_sessions()?;
+        self.progress.start(Stage::Records, Some(2));
+        self.progress.check()?;
+        let mut generations = self.read_list(&self.config.generations_key, RecordKind::Generation, merge_generations)?;
+        normalize_generations(&mut generations.records, self.config.timestamp_unit());
+        self.progress.advance(Stage::Records, 1);
+        self.progress.check()?;
+        let prompts = self.read_list(&self.config.prompts_key, RecordKind::Prompt, merge_prompts)?;
+        self.progress.advance(Stage::Records, 2);
+        self.progress.finish(Stage::Records);
+        let mut skipped = sessions.skipped;
+        skipped.extend(generations.skipped);
+        skipped.extend(prompts.skipped);
+        let mut history = ImportedHistory {
+            sessions: sessions.records,
+            generations: generations.records,
+            prompts: prompts.records,
+            skipped,
+            warnings: Vec::new(),
+            workspace: Some(self.workspace_info()?).filter(|info| !info.is_empty()),
+        };
+        validate_history(&mut history, self.config.timestamp_handling(), Utc::now().timestamp_millis());
+        Ok(history)
+    }
+
+    /// What the workspace had open: the folder, editor tabs and history.
+    pub fn workspace_info(&self) -> Result<WorkspaceInfo> {
+        let workspace_json = workspace_json(&self.config);
+        let history = self.workspace.get_key(HISTORY_KEY)?;
+        let editor_state = self.workspace.get_key(EDITOR_STATE_KEY)?;
+        Ok(WorkspaceInfo::from_records(workspace_json.as_deref(), history.as_deref(), editor_state.as_deref()))
+    }
+
+    /// Extract the sessions, with their transcripts.
+    pub fn extract_sessions(&self) -> Result<Vec<ComposerData>> {
+        Ok(warn_skipped(self.parse_sessions()?))
+    }
+
+    /// Extract the generations, merged with global storage's.
+    pub fn extract_generations(&self) -> Result<Vec<ChatGeneration>> {
+        let mut parsed = self.read_list(&self.config.generations_key, RecordKind::Generation, merge_generations)?;
+        normalize_generations(&mut parsed.records, self.config.timestamp_unit());
+        Ok(warn_skipped(parsed))
+    }
+
+    /// Extract the prompts, merged with global storage's.
+    pub fn extract_prompts(&self) -> Result<Vec<ChatPrompt>> {
+        Ok(warn_skipped(self.read_list(&self.config.prompts_key, RecordKind::Prompt, merge_prompts)?))
+    }
+
+    fn parse_sessions(&self) -> Result<Parsed<ComposerData>> {
+        let json_str = self.workspace.require_key(&self.config.composer_data_key)?;
+        let Parsed { mut records, skipped } = parse_session_list(&json_str, &self.config.record_field_mapping())?;
+        let store = self.conversations.as_ref().unwrap_or(&self.workspace);
+        self.progress.start(Stage::Transcripts, Some(records.len()));
+        for (done, session) in records.iter_mut().enumerate() {
+            self.progress.check()?;
+            self.progress.advance(Stage::Transcripts, done);
+            let Some(json_str) = store.get_key(&format!("composerData:{}", session.composer_id))? else {
+                continue;
+            };
+            let composer: Value = serde_json::from_str(&json_str)?;
+            let mut found = HashMap::new();
+            for key in conversation_keys(&session.composer_id, &composer) {
+                if let Some(value) = store.get_key(&key)? {
+                    found.insert(key, value);
+                }
+            }
+            session.messages = assemble_conversation(&session.composer_id, &composer, &found)?;
+        }
+        self.progress.advance(Stage::Transcripts, records.len());
+        self.progress.finish(Stage::Transcripts);
+        normalize_sessions(&mut records, self.config.timestamp_unit());
+        Ok(Parsed { records: vec![ComposerData { all_composers: records }], skipped })
+    }
+
+    /// Parse a list of generations or prompts, merging in global
+    /// storage's if it has one.
+    fn read_list<T: DeserializeOwned>(
+        &self,
+        key: &str,
+        kind: RecordKind,
+        merge: fn(Vec<T>, Vec<T>) -> Vec<T>,
+    ) -> Result<Parsed<T>> {
+        let name = match kind {
+            RecordKind::Prompt => "prompt",
+            _ => "generation",
+        };
+        let limit = self.config.large_value_mb.saturating_mul(1024 * 1024);
+        let mapping = self.config.record_field_mapping();
+        let mut parsed = self
+            .workspace
+            .read_records(key, name, kind, &mapping, limit)?
+            .ok_or_else(|| anyhow!("Key '{}' not found in {}", key, self.workspace.path))?;
+        if let Some(global) = &self.global {
+            if let Some(global) = global.read_records(key, &format!("global {}", name), kind, &mapping, limit)? {
+                parsed.records = merge(parsed.records, global.records);
+                parsed.skipped.extend(global.skipped);
+            }
+        }
+        Ok(parsed)
+    }
+}
+
+/// A key/value table in a SQLite database, opened read-only.
+struct Table {
+    connection: Connection,
+    name: String,
+    path: String,
+}
+
+impl Table {
+    /// Open `table` in the database at `path`, the way `SqliteStore` does:
+    /// read-only, waiting DB_BUSY_TIMEOUT_MS for locks, and without any
+    /// locking at all with DB_IMMUTABLE.
+    fn open(config: &Config, path: &str, table: &str) -> Result<Self> {
+        validate_table(table)?;
+        let flags = OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_URI | OpenFlags::SQLITE_OPEN_NO_MUTEX;
+        let connection = Connection::open_with_flags(sqlite_uri(path, config.db_immutable), flags).with_context(|| {
+            format!(
+                "Could not open the database at {} - check that the path is right and readable (DB_PATH / WORKSPACE_ID)",
+                path
+            )
+        })?;
+        connection.busy_timeout(Duration::from_millis(config.db_busy_timeout_ms))?;
+        Ok(Table { connection, name: table.to_string(), path: path.to_string() })
+    }
+
+    fn get_key(&self, key: &str) -> Result<Option<String>> {
+        let query = format!("SELECT value FROM \"{}\" WHERE key = ?", self.name);
+        let value = self.connection.query_row(&query, [key], |row| row.get(0)).optional()?;
+        Ok(value)
+    }
+
+    fn require_key(&self, key: &str) -> Result<String> {
+        self.get_key(key)?
+            .ok_or_else(|| anyhow!("Key '{}' not found in {}", key, self.path))
+    }
+
+    /// Parse the list of records under `key`, reading it through SQLite's
+    /// incremental blob I/O when it's over `limit` bytes.
+    fn read_records<T: DeserializeOwned>(
+        &self,
+        key: &str,
+        kind: &str,
+        record_kind: RecordKind,
+        mapping: &FieldMapping,
+        limit: u64,
+    ) -> Result<Option<Parsed<T>>> {
+        let prepare = |record: &mut Value| migrate(record_kind, record, mapping);
+        let query = format!("SELECT rowid, octet_length(value) FROM \"{}\" WHERE key = ?", self.name);
+        let found: Option<(i64, Option<i64>)> =
+            self.connection.query_row(&query, [key], |row| Ok((row.get(0)?, row.get(1)?))).optional()?;
+        let Some((rowid, size)) = found else {
+            return Ok(None);
+        };
+        if size.unwrap_or(0) as u64 <= limit {
+            let json_str = self.require_key(key)?;
+            return parse_array_with(&json_str, kind, prepare).map(Some);
+        }
+        let blob = self.connection.blob_open(DatabaseName::Main, &self.name, "value", rowid, true)?;
+        parse_array_reader(blob, kind, prepare).map(Some)
+    }
+}
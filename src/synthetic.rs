@@ -0,0 +1,255 @@
+use anyhow::Result;
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::Config;
+use crate::restore::{plan_restore, RestorePlan};
+use crate::store::{ConnectionOptions, MemoryStore, SqliteStore};
+use crate::{ChatGeneration, ChatMessage, ChatPrompt, ChatSession, ComposerData, MessageRole, TokenUsage};
+
+/// When the first synthetic session starts (2025-01-06, a Monday).
+const EPOCH_MS: i64 = 1_736_150_400_000;
+
+const SUBJECTS: &[&str] = &[
+    "the parser", "the session list", "the config loader", "the markdown renderer", "the retry loop",
+    "the database connection", "the cache", "the CLI flags", "the error messages", "the test suite",
+];
+const VERBS: &[&str] = &[
+    "refactor", "speed up", "fix", "document", "simplify", "test", "rename", "split up", "harden", "profile",
+];
+const FILES: &[&str] = &[
+    "src/main.rs", "src/config.rs", "src/parser.rs", "src/render/markdown.rs", "src/store.rs",
+    "tests/integration_test.rs", "Cargo.toml", "README.md", "src/lib.rs", "src/cache.rs",
+];
+const MODELS: &[&str] = &["claude-3.5-sonnet", "gpt-4o", "cursor-small"];
+
+/// A made-up chat history of any size, in the shape Cursor stores, for
+/// benchmarks and `--profile-run`. The same sizes and seed always give
+/// the same history.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SyntheticHistory {
+    /// How many sessions
+    pub sessions: usize,
+    /// How many messages in each session
+    pub messages: usize,
+    /// Varies the text without changing the sizes
+    pub seed: u64,
+}
+
+impl SyntheticHistory {
+    /// A history of `sessions` sessions with `messages` messages each.
+    pub fn new(sessions: usize, messages: usize) -> Self {
+        SyntheticHistory { sessions, messages, seed: 0 }
+    }
+
+    /// The sessions, as an extraction would return them.
+    pub fn sessions(&self) -> Vec<ComposerData> {
+        let mut random = SplitMix64(self.seed);
+        let sessions = (0..self.sessions)
+            .map(|index| {
+                // A session every five hours, a message every 45 seconds
+                let created_at = EPOCH_MS + index as i64 * 5 * 3_600_000;
+                let subject = random.pick(SUBJECTS);
+                let messages = (0..self.messages)
+                    .map(|position| self.message(&mut random, subject, created_at + position as i64 * 45_000, position))
+                    .collect::<Vec<_>>();
+                ChatSession {
+                    session_type: "head".to_string(),
+                    composer_id: format!("{:08x}-0000-4000-8000-{:012x}", index, self.seed),
+                    name: format!("{} {} #{}", capitalize(random.pick(VERBS)), subject, index + 1),
+                    last_updated_at: created_at + self.messages as i64 * 45_000,
+                    created_at,
+                    unified_mode: "agent".to_string(),
+                    force_mode: "edit".to_string(),
+                    has_unread_messages: false,
+                    messages,
+                    extra: Default::default(),
+                }
+            })
+            .collect();
+        vec![ComposerData { all_composers: sessions }]
+    }
+
+    /// One generation per user message.
+    pub fn generations(&self) -> Vec<ChatGeneration> {
+        let mut generations = Vec::new();
+        for session in self.sessions().iter().flat_map(|data| &data.all_composers) {
+            for message in session.messages.iter().filter(|message| message.role == MessageRole::User) {
+                generations.push(ChatGeneration {
+                    unix_ms: message.timestamp.unwrap_or(session.created_at),
+                    generation_uuid: format!("{}-{}", session.composer_id, generations.len()),
+                    r#type: "composer".to_string(),
+                    text_description: first_line(&message.text),
+                    model: None,
+                    kind: None,
+                    extra: Default::default(),
+                });
+            }
+        }
+        generations
+    }
+
+    /// One prompt per user message.
+    pub fn prompts(&self) -> Vec<ChatPrompt> {
+        self.sessions()
+            .iter()
+            .flat_map(|data| &data.all_composers)
+            .flat_map(|session| &session.messages)
+            .filter(|message| message.role == MessageRole::User)
+            .map(|message| ChatPrompt {
+                text: message.text.clone(),
+                command_type: 4,
+            })
+            .collect()
+    }
+
+    /// The raw records Cursor would hold for this history: the workspace
+    /// records and the conversations, keyed the way `config` expects.
+    pub fn records(&self, config: &Config) -> Result<RestorePlan> {
+        let sessions = self.sessions();
+        let all: Vec<&ChatSession> = sessions.iter().flat_map(|data| &data.all_composers).collect();
+        plan_restore(config, &all, &self.generations(), &self.prompts(), true, &BTreeMap::new(), &HashSet::new())
+    }
+
+    /// The raw records in one in-memory store, ready for `ChatExtractor`.
+    pub fn memory_store(&self, config: &Config) -> Result<MemoryStore> {
+        let plan = self.records(config)?;
+        let mut store = MemoryStore::new();
+        for (key, value) in plan.workspace.into_iter().chain(plan.conversations) {
+            store.insert(key, value);
+        }
+        Ok(store)
+    }
+
+    /// Write the history into new Cursor databases under `dir`.
+    pub async fn write_databases(&self, config: &Config, dir: &Path) -> Result<CursorDatabases> {
+        CursorDatabases::write(config, &self.records(config)?, dir).await
+    }
+
+    fn message(&self, random: &mut SplitMix64, subject: &str, timestamp: i64, position: usize) -> ChatMessage {
+        let file = random.pick(FILES);
+        let mut message = if position.is_multiple_of(2) {
+            ChatMessage::new(
+                MessageRole::User,
+                format!(
+                    "Can you {} {} in `{}`? It's been {} since the last change.",
+                    random.pick(VERBS),
+                    subject,
+                    file,
+                    random.pick(&["slow", "flaky", "confusing", "wrong"])
+                ),
+            )
+        } else {
+            let mut text = format!("Sure. {} lives in `{}`, so I'll start there.\n\n", capitalize(subject), file);
+            for _ in 0..random.below(4) + 1 {
+                text.push_str(&format!(
+                    "Next I'll {} {} and check `{}` still agrees with it. ",
+                    random.pick(VERBS),
+                    random.pick(SUBJECTS),
+                    random.pick(FILES)
+                ));
+            }
+            text.push_str(&format!(
+                "\n\n```rust\nfn step_{}() -> Result<()> {{\n    let value = load({})?;\n    Ok(())\n}}\n```\n",
+                position,
+                random.below(1000)
+            ));
+            let mut message = ChatMessage::new(MessageRole::Assistant, text);
+            message.model = Some(random.pick(MODELS).to_string());
+            message.usage = Some(TokenUsage {
+                input_tokens: 500 + random.below(4000),
+                output_tokens: 100 + random.below(1500),
+            });
+            message
+        };
+        message.timestamp = Some(timestamp);
+        message
+    }
+}
+
+/// A pair of Cursor databases written from scratch: a workspace database
+/// under `workspaceStorage/synthetic/` and a global one under
+/// `globalStorage/`, laid out the way Cursor lays them out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CursorDatabases {
+    /// The `workspaceStorage` directory, for DB_PATH
+    pub db_path: PathBuf,
+    /// The workspace's directory name in it, for WORKSPACE_ID
+    pub workspace_id: String,
+    /// The global database, for GLOBAL_DB_PATH
+    pub global_db: PathBuf,
+}
+
+impl CursorDatabases {
+    /// Write `plan`'s records into new databases under `dir`: the
+    /// workspace records into the workspace database's `ItemTable`, the
+    /// conversations into the global database.
+    pub async fn write(config: &Config, plan: &RestorePlan, dir: &Path) -> Result<Self> {
+        let databases = CursorDatabases {
+            db_path: dir.join("workspaceStorage"),
+            workspace_id: "synthetic".to_string(),
+            global_db: dir.join("globalStorage").join("state.vscdb"),
+        };
+        let workspace_dir = databases.db_path.join(&databases.workspace_id);
+        fs::create_dir_all(&workspace_dir)?;
+        fs::create_dir_all(dir.join("globalStorage"))?;
+        let workspace_db = workspace_dir.join(&config.db_filename);
+
+        let options = ConnectionOptions { read_only: false, create: true, ..ConnectionOptions::default() };
+        SqliteStore::open_with(&workspace_db.to_string_lossy(), "ItemTable", &options)
+            .await?
+            .put_records(&plan.workspace)
+            .await?;
+        let global =
+            SqliteStore::open_with(&databases.global_db.to_string_lossy(), &config.conversation_table, &options).await?;
+        global.put_records(&plan.conversations).await?;
+        // Cursor's global database always has an ItemTable, even when it's empty
+        global.with_table("ItemTable")?.put_records(&BTreeMap::new()).await?;
+        Ok(databases)
+    }
+
+    /// `config`, reading Cursor history from these databases.
+    pub fn configure(&self, config: &Config) -> Config {
+        let mut config = config.clone();
+        config.source = "cursor".to_string();
+        config.db_path = self.db_path.to_string_lossy().to_string();
+        config.workspace_id = self.workspace_id.clone();
+        config.global_db_path = self.global_db.to_string_lossy().to_string();
+        config
+    }
+}
+
+/// A small, fast, seedable random number generator - plenty for made-up
+/// chat text, and it keeps a dependency out of the crate.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn below(&mut self, bound: u64) -> u64 {
+        self.next() % bound.max(1)
+    }
+
+    fn pick<'a>(&mut self, items: &[&'a str]) -> &'a str {
+        items[self.below(items.len() as u64) as usize]
+    }
+}
+
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+fn first_line(text: &str) -> String {
+    text.lines().next().unwrap_or_default().to_string()
+}
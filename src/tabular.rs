@@ -0,0 +1,184 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, SecondsFormat};
+use serde::Serialize;
+
+use crate::usage::estimate_tokens;
+use crate::{ChatGeneration, ChatPrompt, ComposerData, MessageRole};
+
+/// The columns of a tabular export, in order.
+pub const COLUMNS: &[&str] = &["timestamp", "session_id", "role", "command_type", "text_length", "token_estimate"];
+
+/// One message, flattened into a row for analysis in pandas, DuckDB and
+/// the like. Only the shape of the message is kept - not its text - so the
+/// export is safe to share even when the transcript isn't.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MessageRow {
+    /// When the message was sent (Unix timestamp in milliseconds), if known
+    pub timestamp: Option<i64>,
+    /// The session the message belongs to; empty for Cursor prompts and
+    /// generations, which the database doesn't tie to a session
+    pub session_id: String,
+    /// "user" or "assistant"
+    pub role: String,
+    /// The prompt's command type, for Cursor prompts
+    pub command_type: Option<i32>,
+    /// How many characters the message has
+    pub text_length: usize,
+    /// Tokens the message used: the recorded count where the source kept
+    /// one, otherwise estimated from the text length
+    pub token_estimate: u64,
+}
+
+/// Flatten everything we extracted into one row per message: transcript
+/// messages first (session by session), then Cursor's prompts and
+/// generations.
+pub fn message_rows(
+    sessions: &[ComposerData],
+    generations: &[ChatGeneration],
+    prompts: &[ChatPrompt],
+) -> Vec<MessageRow> {
+    let mut rows = Vec::new();
+
+    for session in sessions.iter().flat_map(|data| &data.all_composers) {
+        for message in &session.messages {
+            let (role, recorded) = match message.role {
+                MessageRole::User => ("user", message.usage.map(|usage| usage.input_tokens)),
+                MessageRole::Assistant => ("assistant", message.usage.map(|usage| usage.output_tokens)),
+            };
+            rows.push(MessageRow {
+                timestamp: message.timestamp,
+                session_id: session.composer_id.clone(),
+                role: role.to_string(),
+                command_type: None,
+                text_length: message.text.chars().count(),
+                token_estimate: recorded.unwrap_or_else(|| estimate_tokens(&message.text)),
+            });
+        }
+    }
+
+    for prompt in prompts {
+        rows.push(MessageRow {
+            timestamp: None,
+            session_id: String::new(),
+            role: "user".to_string(),
+            command_type: Some(prompt.command_type),
+            text_length: prompt.text.chars().count(),
+            token_estimate: estimate_tokens(&prompt.text),
+        });
+    }
+
+    for generation in generations {
+        rows.push(MessageRow {
+            timestamp: Some(generation.unix_ms),
+            session_id: String::new(),
+            role: "assistant".to_string(),
+            command_type: None,
+            text_length: generation.text_description.chars().count(),
+            token_estimate: estimate_tokens(&generation.text_description),
+        });
+    }
+
+    rows
+}
+
+/// Write the rows as CSV with a header line. Timestamps are RFC 3339 in
+/// UTC so pandas and DuckDB read them as timestamps; unknown values are
+/// left empty.
+pub fn to_csv(rows: &[MessageRow]) -> String {
+    let mut csv = COLUMNS.join(",");
+    csv.push('\n');
+    for row in rows {
+        let timestamp = row
+            .timestamp
+            .and_then(DateTime::from_timestamp_millis)
+            .map(|time| time.to_rfc3339_opts(SecondsFormat::Millis, true))
+            .unwrap_or_default();
+        let fields = [
+            timestamp,
+            csv_field(&row.session_id),
+            csv_field(&row.role),
+            row.command_type.map(|command_type| command_type.to_string()).unwrap_or_default(),
+            row.text_length.to_string(),
+            row.token_estimate.to_string(),
+        ];
+        csv.push_str(&fields.join(","));
+        csv.push('\n');
+    }
+    csv
+}
+
+/// Quote a CSV field if it needs it (RFC 4180).
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Write the rows as a Parquet file, with the timestamp as a proper
+/// millisecond UTC timestamp column. Needs the `parquet` feature.
+#[cfg(feature = "parquet")]
+pub fn to_parquet(rows: &[MessageRow]) -> Result<Vec<u8>> {
+    use parquet::data_type::{ByteArray, ByteArrayType, Int32Type, Int64Type};
+    use parquet::file::properties::WriterProperties;
+    use parquet::file::writer::SerializedFileWriter;
+    use parquet::schema::parser::parse_message_type;
+    use std::sync::Arc;
+
+    let schema = parse_message_type(
+        "message chat_messages {
+            optional int64 timestamp (TIMESTAMP(MILLIS,true));
+            required binary session_id (STRING);
+            required binary role (STRING);
+            optional int32 command_type;
+            required int64 text_length;
+            required int64 token_estimate;
+        }",
+    )?;
+
+    // Optional columns get a definition level per row (1 = present) and
+    // only the values that are there
+    let definition_levels = |present: &dyn Fn(&MessageRow) -> bool| -> Vec<i16> {
+        rows.iter().map(|row| i16::from(present(row))).collect()
+    };
+    let timestamps: Vec<i64> = rows.iter().filter_map(|row| row.timestamp).collect();
+    let timestamp_levels = definition_levels(&|row| row.timestamp.is_some());
+    let session_ids: Vec<ByteArray> = rows.iter().map(|row| ByteArray::from(row.session_id.as_str())).collect();
+    let roles: Vec<ByteArray> = rows.iter().map(|row| ByteArray::from(row.role.as_str())).collect();
+    let command_types: Vec<i32> = rows.iter().filter_map(|row| row.command_type).collect();
+    let command_type_levels = definition_levels(&|row| row.command_type.is_some());
+    let text_lengths: Vec<i64> = rows.iter().map(|row| row.text_length as i64).collect();
+    let token_estimates: Vec<i64> = rows.iter().map(|row| row.token_estimate as i64).collect();
+
+    let mut parquet = Vec::new();
+    let mut writer = SerializedFileWriter::new(
+        &mut parquet,
+        Arc::new(schema),
+        Arc::new(WriterProperties::builder().build()),
+    )?;
+    let mut row_group = writer.next_row_group()?;
+    let mut column = |write: &mut dyn FnMut(&mut parquet::file::writer::SerializedColumnWriter) -> parquet::errors::Result<usize>| -> Result<()> {
+        let mut column = row_group
+            .next_column()?
+            .ok_or_else(|| anyhow!("Parquet schema has fewer columns than the rows"))?;
+        write(&mut column)?;
+        column.close()?;
+        Ok(())
+    };
+    column(&mut |c| c.typed::<Int64Type>().write_batch(&timestamps, Some(&timestamp_levels), None))?;
+    column(&mut |c| c.typed::<ByteArrayType>().write_batch(&session_ids, None, None))?;
+    column(&mut |c| c.typed::<ByteArrayType>().write_batch(&roles, None, None))?;
+    column(&mut |c| c.typed::<Int32Type>().write_batch(&command_types, Some(&command_type_levels), None))?;
+    column(&mut |c| c.typed::<Int64Type>().write_batch(&text_lengths, None, None))?;
+    column(&mut |c| c.typed::<Int64Type>().write_batch(&token_estimates, None, None))?;
+    row_group.close()?;
+    writer.close()?;
+    Ok(parquet)
+}
+
+/// Without the `parquet` feature there's no Parquet writer.
+#[cfg(not(feature = "parquet"))]
+pub fn to_parquet(_rows: &[MessageRow]) -> Result<Vec<u8>> {
+    Err(anyhow!("Parquet output isn't built in; rebuild with `--features parquet`"))
+}
@@ -0,0 +1,137 @@
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+
+use crate::atomic::write_atomic;
+use crate::grouping::session_topic;
+use crate::{ChatSession, MessageRole};
+
+/// Where session tags are kept, in the state directory.
+pub const TAGS_FILE: &str = ".lore-tags.json";
+
+/// How many tags `suggest_tags` offers at most.
+const SUGGESTIONS: usize = 3;
+
+/// Tags suggested when a session's name or prompts mention one of the
+/// words. The first few follow the topics `session_topic` knows about.
+const SUGGESTED: &[(&str, &[&str])] = &[
+    ("orchestration", &["orchestrator", "orchestration", "delegate", "delegation"]),
+    ("rag", &["rag", "retrieval", "embedding", "embeddings", "vector"]),
+    ("agents", &["agentic", "agent", "agents", "tool call", "tools"]),
+    ("memory", &["memory", "persistence", "cache", "caching"]),
+    ("knowledge", &["history", "lore", "knowledge"]),
+    ("architecture", &["architecture", "design", "structure", "module", "modules"]),
+    ("auth", &["auth", "authentication", "login", "oauth", "token", "password"]),
+    ("testing", &["test", "tests", "testing", "flaky", "coverage"]),
+    ("performance", &["performance", "slow", "speed up", "profile", "latency", "benchmark"]),
+    ("bugfix", &["bug", "fix", "broken", "crash", "error"]),
+    ("refactoring", &["refactor", "refactoring", "rename", "split up", "simplify", "cleanup"]),
+    ("docs", &["docs", "documentation", "document", "readme"]),
+    ("config", &["config", "configuration", "settings", "env"]),
+    ("database", &["database", "sqlite", "sql", "migration", "schema"]),
+    ("ci", &["ci", "pipeline", "github actions", "workflow", "deploy"]),
+];
+
+/// Tags people gave sessions with the `tag` subcommand. They live in the
+/// state directory rather than the source, since Cursor has nowhere to
+/// keep them, and are matched to sessions by ID.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionTags {
+    /// Each tagged session's tags, by session ID
+    pub sessions: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl SessionTags {
+    /// Load the tags, or start with none if nothing's been tagged yet.
+    pub fn load(path: &Path) -> Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(text) => serde_json::from_str(&text).with_context(|| format!("Could not read {}", path.display())),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(SessionTags::default()),
+            Err(error) => Err(error).with_context(|| format!("Could not read {}", path.display())),
+        }
+    }
+
+    /// Save the tags.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        write_atomic(path, serde_json::to_string_pretty(self)?.as_bytes())
+    }
+
+    /// Give a session some more tags.
+    pub fn add(&mut self, session_id: &str, tags: &[String]) {
+        self.sessions.entry(session_id.to_string()).or_default().extend(tags.iter().cloned());
+    }
+
+    /// Take tags off a session, forgetting the session once it has none.
+    pub fn remove(&mut self, session_id: &str, tags: &[String]) {
+        if let Some(current) = self.sessions.get_mut(session_id) {
+            for tag in tags {
+                current.remove(tag);
+            }
+            if current.is_empty() {
+                self.sessions.remove(session_id);
+            }
+        }
+    }
+
+    /// A session's tags, in alphabetical order.
+    pub fn of(&self, session_id: &str) -> Vec<&str> {
+        self.sessions
+            .get(session_id)
+            .map(|tags| tags.iter().map(String::as_str).collect())
+            .unwrap_or_default()
+    }
+
+    /// Whether a session has any of `tags`.
+    pub fn has_any(&self, session_id: &str, tags: &[String]) -> bool {
+        self.sessions.get(session_id).is_some_and(|current| tags.iter().any(|tag| current.contains(tag)))
+    }
+
+    /// Whether any session has a tag.
+    pub fn is_empty(&self) -> bool {
+        self.sessions.is_empty()
+    }
+}
+
+/// Parse a comma-separated list of tags. Tags are lowercased, and spaces
+/// become dashes, so `Auth, Data Model` is `auth` and `data-model`.
+pub fn parse_tags(list: &str) -> Result<Vec<String>> {
+    let mut tags = Vec::new();
+    for tag in list.split(',').map(str::trim).filter(|tag| !tag.is_empty()) {
+        let tag = tag.to_lowercase().split_whitespace().collect::<Vec<_>>().join("-");
+        if !tag.chars().all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '.' | '/')) {
+            bail!("'{}' isn't a usable tag (use letters, digits, '-', '_', '.' and '/')", tag);
+        }
+        if !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    Ok(tags)
+}
+
+/// Tags a session probably deserves, going by the words in its name and
+/// its prompts (and the topic `session_topic` puts it under). The best
+/// matches come first.
+pub fn suggest_tags(session: &ChatSession) -> Vec<String> {
+    let mut text = format!("{} {}", session.name, session_topic(session)).to_lowercase();
+    for message in session.messages.iter().filter(|message| message.role == MessageRole::User) {
+        text.push(' ');
+        text.push_str(&message.text.to_lowercase());
+    }
+    let words: Vec<&str> = text.split(|c: char| !c.is_alphanumeric()).filter(|word| !word.is_empty()).collect();
+    let joined = format!(" {} ", words.join(" "));
+
+    let mut scored: Vec<(usize, usize, &str)> = SUGGESTED
+        .iter()
+        .enumerate()
+        .map(|(order, (tag, keywords))| {
+            let hits = keywords.iter().map(|keyword| joined.matches(&format!(" {} ", keyword)).count()).sum();
+            (hits, order, *tag)
+        })
+        .filter(|(hits, _, _)| *hits > 0)
+        .collect();
+    // Most mentioned first, in table order when it's a tie
+    scored.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+    scored.into_iter().take(SUGGESTIONS).map(|(_, _, tag)| tag.to_string()).collect()
+}
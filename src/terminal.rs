@@ -0,0 +1,262 @@
+use anyhow::{anyhow, Result};
+use dialoguer::console::{measure_text_width, truncate_str, Term};
+use std::io::IsTerminal;
+
+/// Whether terminal output is colored (COLOR, or `--color`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ColorChoice {
+    /// When writing to a terminal, unless NO_COLOR is set or TERM is "dumb"
+    #[default]
+    Auto,
+    /// Always, even into a pipe
+    Always,
+    /// Never
+    Never,
+}
+
+impl ColorChoice {
+    /// The values COLOR accepts.
+    pub const NAMES: &'static [&'static str] = &["auto", "always", "never"];
+
+    /// Parse a COLOR value.
+    pub fn parse(name: &str) -> Result<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "auto" | "" => Ok(ColorChoice::Auto),
+            "always" => Ok(ColorChoice::Always),
+            "never" => Ok(ColorChoice::Never),
+            other => Err(anyhow!("Unknown color choice '{}' (expected {})", other, ColorChoice::NAMES.join(", "))),
+        }
+    }
+
+    /// Whether to color output going to a terminal (or not), going by
+    /// `env` for NO_COLOR (see <https://no-color.org>) and TERM.
+    pub fn enabled(&self, is_terminal: bool, env: impl Fn(&str) -> Option<String>) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                is_terminal
+                    && env("NO_COLOR").is_none_or(|value| value.is_empty())
+                    && env("TERM").is_none_or(|term| term != "dumb")
+            }
+        }
+    }
+}
+
+/// What a piece of output is, which decides its color.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tone {
+    /// Table headers and titles
+    Heading,
+    /// Things that went well, and lines added in a diff
+    Success,
+    /// Things worth a look, and lines changed in a diff
+    Warning,
+    /// Things that failed, and lines removed in a diff
+    Error,
+    /// Details, like IDs and paths
+    Muted,
+}
+
+/// The colors of each tone, as ANSI SGR parameters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Theme {
+    /// The theme's name in COLOR_THEME
+    pub name: &'static str,
+    heading: &'static str,
+    success: &'static str,
+    warning: &'static str,
+    error: &'static str,
+    muted: &'static str,
+}
+
+impl Theme {
+    /// The themes COLOR_THEME can name.
+    pub const ALL: &'static [Theme] = &[
+        Theme { name: "default", heading: "1", success: "32", warning: "33", error: "31", muted: "2" },
+        // Bright colors, and no dimming, for dark terminals and tired eyes
+        Theme { name: "high-contrast", heading: "1;4", success: "1;92", warning: "1;93", error: "1;91", muted: "97" },
+    ];
+
+    /// Find a theme by name.
+    pub fn named(name: &str) -> Result<Self> {
+        let name = name.trim().to_lowercase();
+        Theme::ALL.iter().find(|theme| theme.name == name).copied().ok_or_else(|| {
+            let names: Vec<&str> = Theme::ALL.iter().map(|theme| theme.name).collect();
+            anyhow!("Unknown color theme '{}' (expected {})", name, names.join(", "))
+        })
+    }
+
+    fn code(&self, tone: Tone) -> &'static str {
+        match tone {
+            Tone::Heading => self.heading,
+            Tone::Success => self.success,
+            Tone::Warning => self.warning,
+            Tone::Error => self.error,
+            Tone::Muted => self.muted,
+        }
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::ALL[0]
+    }
+}
+
+/// Where the subcommands print their lists, tables and diffs: whether to
+/// color them, in which theme, and how wide the terminal is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Terminal {
+    /// Whether output is colored
+    pub colors: bool,
+    /// The colors to use
+    pub theme: Theme,
+    /// How many columns there are to fill; tables are cut to fit
+    pub width: usize,
+}
+
+impl Terminal {
+    /// Standard output, colored as `choice` and the environment say.
+    pub fn stdout(choice: ColorChoice, theme: Theme) -> Self {
+        let term = Term::stdout();
+        let is_terminal = std::io::stdout().is_terminal();
+        Terminal {
+            colors: choice.enabled(is_terminal, |name| std::env::var(name).ok()),
+            theme,
+            // Piped output isn't cut short
+            width: match term.size_checked() {
+                Some((_, columns)) if is_terminal => usize::from(columns),
+                _ => usize::MAX,
+            },
+        }
+    }
+
+    /// No colors and no width limit, for output that goes in files and tests.
+    pub fn plain() -> Self {
+        Terminal { colors: false, theme: Theme::default(), width: usize::MAX }
+    }
+
+    /// `text` in the tone's color, if output is colored.
+    pub fn paint(&self, tone: Tone, text: &str) -> String {
+        if self.colors && !text.is_empty() {
+            format!("\x1b[{}m{}\x1b[0m", self.theme.code(tone), text)
+        } else {
+            text.to_string()
+        }
+    }
+
+    /// A line of a diff, colored by its first character: `+` for added,
+    /// `-` for removed and `~` for changed.
+    pub fn diff_line(&self, line: &str) -> String {
+        match line.chars().next() {
+            Some('+') => self.paint(Tone::Success, line),
+            Some('-') => self.paint(Tone::Error, line),
+            Some('~') => self.paint(Tone::Warning, line),
+            _ => line.to_string(),
+        }
+    }
+}
+
+/// How a table column's cells line up.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Align {
+    /// Against the left edge, for text
+    #[default]
+    Left,
+    /// Against the right edge, for numbers
+    Right,
+}
+
+/// A table for the terminal: columns as wide as their widest cell, two
+/// spaces apart, with the last column cut short (ending in `…`) if the
+/// table would be wider than the terminal. Cells can be painted already;
+/// colors don't count towards widths.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Table {
+    header: Vec<String>,
+    align: Vec<Align>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    /// A table with these column headers.
+    pub fn new(header: &[&str]) -> Self {
+        Table {
+            header: header.iter().map(|cell| cell.to_string()).collect(),
+            align: vec![Align::Left; header.len()],
+            rows: Vec::new(),
+        }
+    }
+
+    /// Line up the cells of column `column` (from 0) this way.
+    pub fn align(mut self, column: usize, align: Align) -> Self {
+        if let Some(slot) = self.align.get_mut(column) {
+            *slot = align;
+        }
+        self
+    }
+
+    /// Add a row; missing cells are left empty.
+    pub fn row(&mut self, cells: Vec<String>) {
+        self.rows.push(cells);
+    }
+
+    /// Whether the table has no rows.
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// The table as lines for `terminal`, each ending in a newline.
+    pub fn render(&self, terminal: &Terminal) -> String {
+        let columns = self.header.len();
+        let cell = |row: &Vec<String>, column: usize| row.get(column).cloned().unwrap_or_default();
+        let mut widths: Vec<usize> = self.header.iter().map(|header| measure_text_width(header)).collect();
+        for row in &self.rows {
+            for (column, width) in widths.iter_mut().enumerate() {
+                *width = (*width).max(measure_text_width(&cell(row, column)));
+            }
+        }
+
+        let header = self.line(&self.header, &widths, terminal, |text| terminal.paint(Tone::Heading, text));
+        let mut table = header;
+        for row in &self.rows {
+            let cells: Vec<String> = (0..columns).map(|column| cell(row, column)).collect();
+            table.push_str(&self.line(&cells, &widths, terminal, str::to_string));
+        }
+        table
+    }
+
+    /// One line of the table, with `style` applied to each cell after
+    /// it's been padded and cut to size.
+    fn line(&self, cells: &[String], widths: &[usize], terminal: &Terminal, style: impl Fn(&str) -> String) -> String {
+        let mut line = String::new();
+        let mut used = 0;
+        for (column, text) in cells.iter().enumerate() {
+            let last = column + 1 == cells.len();
+            if column > 0 {
+                line.push_str("  ");
+                used += 2;
+            }
+            let width = widths[column];
+            let text = if last {
+                // The last column takes what's left, and isn't padded
+                let room = terminal.width.saturating_sub(used).max(1);
+                match self.align[column] {
+                    Align::Left => truncate_str(text, room, "…").into_owned(),
+                    Align::Right => format!("{}{}", " ".repeat(width.saturating_sub(measure_text_width(text))), text),
+                }
+            } else {
+                let padding = " ".repeat(width.saturating_sub(measure_text_width(text)));
+                match self.align[column] {
+                    Align::Left => format!("{}{}", text, padding),
+                    Align::Right => format!("{}{}", padding, text),
+                }
+            };
+            used += width;
+            line.push_str(&style(&text));
+        }
+        line.push('\n');
+        line
+    }
+}
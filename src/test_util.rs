@@ -0,0 +1,315 @@
+use anyhow::Result;
+use proptest::prelude::*;
+use serde_json::{json, Map, Value};
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+
+use crate::config::Config;
+use crate::importers::ImportedHistory;
+use crate::restore::{plan_restore, RestorePlan};
+use crate::store::MemoryStore;
+use crate::synthetic::{CursorDatabases, SyntheticHistory};
+use crate::{ChatGeneration, ChatMessage, ChatPrompt, ChatSession, ComposerData, MessageRole};
+
+/// When the first fixture session starts, unless it says otherwise
+/// (2025-03-03 09:00 UTC).
+pub const FIXTURE_EPOCH_MS: i64 = 1_740_992_400_000;
+
+/// A chat history put together by hand for a test, a session at a time,
+/// that can be handed to the generator as it is or written into the
+/// records and databases Cursor would keep it in. Everything about it is
+/// fixed, so rendering it gives the same text every time - what snapshot
+/// tests need.
+#[derive(Debug, Clone, Default)]
+pub struct FixtureHistory {
+    sessions: Vec<ChatSession>,
+    generations: Vec<ChatGeneration>,
+    prompts: Vec<ChatPrompt>,
+}
+
+impl FixtureHistory {
+    /// An empty history.
+    pub fn new() -> Self {
+        FixtureHistory::default()
+    }
+
+    /// A made-up history of `sessions` sessions with `messages` messages
+    /// each (see `SyntheticHistory`), to add to or test at scale.
+    pub fn synthetic(sessions: usize, messages: usize) -> Self {
+        let synthetic = SyntheticHistory::new(sessions, messages);
+        FixtureHistory {
+            sessions: synthetic.sessions().into_iter().flat_map(|data| data.all_composers).collect(),
+            generations: synthetic.generations(),
+            prompts: synthetic.prompts(),
+        }
+    }
+
+    /// Add a session called `name` with the given exchange, alternating
+    /// user and assistant messages a minute apart. It starts a day after
+    /// the previous session; each user message is also a prompt and each
+    /// answer a generation, as Cursor would record them.
+    pub fn session(self, id: &str, name: &str, exchange: &[&str]) -> Self {
+        let created_at = match self.sessions.last() {
+            Some(previous) => previous.created_at + 24 * 3_600_000,
+            None => FIXTURE_EPOCH_MS,
+        };
+        let messages = exchange
+            .iter()
+            .enumerate()
+            .map(|(position, text)| {
+                let role = if position % 2 == 0 { MessageRole::User } else { MessageRole::Assistant };
+                ChatMessage {
+                    timestamp: Some(created_at + position as i64 * 60_000),
+                    ..ChatMessage::new(role, *text)
+                }
+            })
+            .collect();
+        self.with_session(ChatSession {
+            session_type: "head".to_string(),
+            composer_id: id.to_string(),
+            name: name.to_string(),
+            last_updated_at: created_at + exchange.len() as i64 * 60_000,
+            created_at,
+            unified_mode: "agent".to_string(),
+            force_mode: "edit".to_string(),
+            has_unread_messages: false,
+            messages,
+            extra: Default::default(),
+        })
+    }
+
+    /// Add a session exactly as given, with a prompt for each of its user
+    /// messages and a generation for each of its answers.
+    pub fn with_session(mut self, session: ChatSession) -> Self {
+        for (position, message) in session.messages.iter().enumerate() {
+            match message.role {
+                MessageRole::User => self.prompts.push(ChatPrompt { text: message.text.clone(), command_type: 4 }),
+                MessageRole::Assistant => self.generations.push(ChatGeneration {
+                    unix_ms: message.timestamp.unwrap_or(session.created_at),
+                    generation_uuid: format!("{}-{}", session.composer_id, position),
+                    r#type: "composer".to_string(),
+                    text_description: message.text.clone(),
+                    model: message.model.clone(),
+                    kind: None,
+                    extra: Default::default(),
+                }),
+            }
+        }
+        self.sessions.push(session);
+        self
+    }
+
+    /// Add a generation that isn't part of any session's transcript.
+    pub fn with_generation(mut self, generation: ChatGeneration) -> Self {
+        self.generations.push(generation);
+        self
+    }
+
+    /// Add a prompt that isn't part of any session's transcript.
+    pub fn with_prompt(mut self, text: &str) -> Self {
+        self.prompts.push(ChatPrompt { text: text.to_string(), command_type: 4 });
+        self
+    }
+
+    /// The sessions, as an extraction would return them.
+    pub fn sessions(&self) -> Vec<ComposerData> {
+        vec![ComposerData { all_composers: self.sessions.clone() }]
+    }
+
+    /// The generations, oldest first.
+    pub fn generations(&self) -> Vec<ChatGeneration> {
+        let mut generations = self.generations.clone();
+        generations.sort_by_key(|generation| generation.unix_ms);
+        generations
+    }
+
+    /// The prompts, in the order they were added.
+    pub fn prompts(&self) -> Vec<ChatPrompt> {
+        self.prompts.clone()
+    }
+
+    /// Everything, as an importer would return it.
+    pub fn history(&self) -> ImportedHistory {
+        ImportedHistory {
+            sessions: self.sessions(),
+            generations: self.generations(),
+            prompts: self.prompts(),
+            ..ImportedHistory::default()
+        }
+    }
+
+    /// The raw records Cursor would hold for this history, keyed the way
+    /// `config` expects.
+    pub fn records(&self, config: &Config) -> Result<RestorePlan> {
+        let sessions: Vec<&ChatSession> = self.sessions.iter().collect();
+        let (generations, prompts) = (self.generations(), self.prompts());
+        plan_restore(config, &sessions, &generations, &prompts, true, &BTreeMap::new(), &HashSet::new())
+    }
+
+    /// The raw records in one in-memory store, ready for `ChatExtractor`.
+    pub fn memory_store(&self, config: &Config) -> Result<MemoryStore> {
+        let plan = self.records(config)?;
+        let mut store = MemoryStore::new();
+        for (key, value) in plan.workspace.into_iter().chain(plan.conversations) {
+            store.insert(key, value);
+        }
+        Ok(store)
+    }
+
+    /// Write the history into new `state.vscdb` databases under `dir`;
+    /// `CursorDatabases::configure` points a config at them.
+    pub async fn write_databases(&self, config: &Config, dir: &Path) -> Result<CursorDatabases> {
+        CursorDatabases::write(config, &self.records(config)?, dir).await
+    }
+}
+
+/// Text a chat message could hold, weighted towards what trips parsers and
+/// renderers up: unbalanced code fences, markdown and HTML, long runs of
+/// one character, control characters, and anything at all.
+pub fn chat_text() -> impl Strategy<Value = String> {
+    let fragment = prop_oneof![
+        Just("```".to_string()),
+        Just("```rust\n".to_string()),
+        Just("~~~".to_string()),
+        Just("\n".to_string()),
+        Just("[".to_string()),
+        Just("](".to_string()),
+        Just("<details>".to_string()),
+        Just("| a | b |\n".to_string()),
+        Just("TODO: ".to_string()),
+        Just("We decided to ".to_string()),
+        Just("`src/main.rs`".to_string()),
+        Just("\u{0}\u{1b}[31m\r".to_string()),
+        Just("\u{202e}\u{fe0f}\u{1f600}".to_string()),
+        "\\PC{0,40}",
+    ];
+    prop_oneof![
+        3 => prop::collection::vec(fragment, 0..40).prop_map(|fragments| fragments.concat()),
+        1 => any::<String>(),
+        1 => (prop_oneof![Just("`"), Just("["), Just("\n"), Just("*"), Just("#"), Just("-"), Just("a ")], 1..4000usize)
+            .prop_map(|(piece, count)| piece.repeat(count)),
+    ]
+}
+
+/// A timestamp as a source might write it: usually plausible milliseconds,
+/// sometimes seconds, zero, negative, far off, fractional, or a string.
+pub fn timestamp() -> impl Strategy<Value = Value> {
+    prop_oneof![
+        4 => (FIXTURE_EPOCH_MS - 1_000_000_000..FIXTURE_EPOCH_MS + 1_000_000_000).prop_map(Value::from),
+        1 => (FIXTURE_EPOCH_MS / 1000 - 1_000_000..FIXTURE_EPOCH_MS / 1000).prop_map(Value::from),
+        1 => prop_oneof![Just(0i64), Just(-1), Just(i64::MIN), Just(i64::MAX)].prop_map(Value::from),
+        1 => any::<f64>().prop_map(|value| json!(value)),
+        1 => "[0-9T:Z-]{0,30}".prop_map(Value::from),
+    ]
+}
+
+/// A session as Cursor lists it, with its fields named the way newer
+/// Cursor versions write them or the way exports do.
+pub fn session_json() -> BoxedStrategy<Value> {
+    (any::<bool>(), "[a-f0-9-]{1,36}", chat_text(), timestamp(), timestamp(), any::<bool>()).prop_map(
+        |(camel_case, id, name, created_at, last_updated_at, unread)| {
+            if camel_case {
+                json!({"type": "head", "composerId": id, "name": name, "createdAt": created_at,
+                    "lastUpdatedAt": last_updated_at, "unifiedMode": "agent", "forceMode": "edit",
+                    "hasUnreadMessages": unread})
+            } else {
+                json!({"type": "head", "composer_id": id, "name": name, "created_at": created_at,
+                    "last_updated_at": last_updated_at, "unified_mode": "agent", "force_mode": "edit",
+                    "has_unread_messages": unread})
+            }
+        },
+    )
+    .boxed()
+}
+
+/// Cursor's composer data: a list of sessions (some malformed, or not a
+/// list at all) under `allComposers`.
+pub fn composer_data_json() -> impl Strategy<Value = Value> {
+    top_level(record_list(session_json())).prop_map(|sessions| json!({"allComposers": sessions}))
+}
+
+/// A generation as Cursor records it.
+pub fn generation_json() -> BoxedStrategy<Value> {
+    (timestamp(), "[a-f0-9-]{1,36}", chat_text(), proptest::option::of("[a-z0-9.-]{1,20}")).prop_map(
+        |(unix_ms, uuid, text, model)| {
+            json!({"unixMs": unix_ms, "generationUUID": uuid, "type": "composer", "textDescription": text,
+                "modelName": model})
+        },
+    )
+    .boxed()
+}
+
+/// A prompt as Cursor records it.
+pub fn prompt_json() -> BoxedStrategy<Value> {
+    (chat_text(), any::<i32>()).prop_map(|(text, command_type)| json!({"text": text, "commandType": command_type}))
+        .boxed()
+}
+
+/// Any JSON value, a few levels deep.
+pub fn any_json() -> impl Strategy<Value = Value> {
+    let leaf = prop_oneof![
+        Just(Value::Null),
+        any::<bool>().prop_map(Value::from),
+        any::<i64>().prop_map(Value::from),
+        any::<f64>().prop_map(|value| json!(value)),
+        chat_text().prop_map(Value::from),
+    ];
+    leaf.prop_recursive(4, 32, 6, |inner| {
+        prop_oneof![
+            prop::collection::vec(inner.clone(), 0..6).prop_map(Value::from),
+            prop::collection::btree_map("[a-zA-Z_]{1,12}", inner, 0..6)
+                .prop_map(|fields| Value::Object(fields.into_iter().collect::<Map<_, _>>())),
+        ]
+    })
+}
+
+/// `record`, usually broken somehow: a field dropped, a field's value
+/// swapped for any other JSON, a field nobody knows added, or the whole
+/// record replaced with something that isn't one.
+pub fn malformed(record: impl Strategy<Value = Value>) -> impl Strategy<Value = Value> {
+    (record, 0..5u8, any::<prop::sample::Index>(), any_json()).prop_map(|(mut record, how, which, other)| {
+        let Some(fields) = record.as_object_mut() else {
+            return record;
+        };
+        let names: Vec<String> = fields.keys().cloned().collect();
+        let name = which.get(&names).clone();
+        match how {
+            0 => {
+                fields.remove(&name);
+            }
+            1 => {
+                fields.insert(name, other);
+            }
+            2 => {
+                fields.insert("unexpectedField".to_string(), other);
+            }
+            3 => return other,
+            _ => {}
+        }
+        record
+    })
+}
+
+/// What a key holds: usually `value`, sometimes something of another shape
+/// altogether - a list, a string, a number, a bool or null - as a database
+/// written by another version (or another tool) might.
+pub fn top_level(value: impl Strategy<Value = Value>) -> impl Strategy<Value = Value> {
+    prop_oneof![
+        4 => value,
+        1 => prop::collection::vec(any_json(), 0..4).prop_map(Value::from),
+        1 => chat_text().prop_map(Value::from),
+        1 => any::<i64>().prop_map(Value::from),
+        1 => any::<f64>().prop_map(|value| json!(value)),
+        1 => any::<bool>().prop_map(Value::from),
+        1 => Just(Value::Null),
+    ]
+}
+
+/// A list of records from `record`, some of them malformed.
+pub fn record_list<S>(record: S) -> impl Strategy<Value = Value>
+where
+    S: Strategy<Value = Value> + Clone,
+{
+    let item = prop_oneof![3 => record.clone(), 1 => malformed(record)];
+    prop::collection::vec(item, 0..12).prop_map(Value::from)
+}
@@ -0,0 +1,86 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use std::fmt;
+
+/// The time zone timestamps are shown in.
+/// Chat applications store UTC milliseconds, but the lore reads better in
+/// the time frame the work actually happened in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DisplayZone {
+    /// Coordinated Universal Time (the default)
+    #[default]
+    Utc,
+    /// Whatever zone the machine running the tool is set to
+    Local,
+    /// A named IANA zone like `Europe/Rome`
+    Named(Tz),
+}
+
+impl DisplayZone {
+    /// Parse a zone setting: `UTC`, `local`, or an IANA name.
+    pub fn parse(name: &str) -> Result<Self> {
+        match name.trim() {
+            "" => Ok(DisplayZone::Utc),
+            name if name.eq_ignore_ascii_case("utc") => Ok(DisplayZone::Utc),
+            name if name.eq_ignore_ascii_case("local") => Ok(DisplayZone::Local),
+            name => name.parse::<Tz>().map(DisplayZone::Named).map_err(|_| {
+                anyhow!(
+                    "Unknown time zone '{}' - use an IANA name like Europe/Rome, or UTC or local",
+                    name
+                )
+            }),
+        }
+    }
+
+    /// Format a Unix timestamp (milliseconds) in this zone.
+    /// Returns `None` for timestamps chrono can't represent.
+    pub fn format_millis(&self, millis: i64, format: &str) -> Option<String> {
+        DateTime::from_timestamp_millis(millis).map(|time| self.format(time, format))
+    }
+
+    /// The wall-clock time a Unix timestamp (milliseconds) shows in this zone.
+    pub fn local_millis(&self, millis: i64) -> Option<NaiveDateTime> {
+        let time = DateTime::from_timestamp_millis(millis)?;
+        Some(match self {
+            DisplayZone::Utc => time.naive_utc(),
+            DisplayZone::Local => time.with_timezone(&Local).naive_local(),
+            DisplayZone::Named(zone) => time.with_timezone(zone).naive_local(),
+        })
+    }
+
+    /// The UTC time a wall-clock time in this zone refers to. When clocks
+    /// go back and the time happens twice, this is the first one; when they
+    /// go forward and it never happens, `None`.
+    pub fn to_utc(&self, local: NaiveDateTime) -> Option<DateTime<Utc>> {
+        match self {
+            DisplayZone::Utc => Some(local.and_utc()),
+            DisplayZone::Local => Local.from_local_datetime(&local).earliest().map(|time| time.to_utc()),
+            DisplayZone::Named(zone) => zone.from_local_datetime(&local).earliest().map(|time| time.to_utc()),
+        }
+    }
+
+    /// Format the current time in this zone.
+    pub fn format_now(&self, format: &str) -> String {
+        self.format(Utc::now(), format)
+    }
+
+    /// Format a UTC time in this zone.
+    pub fn format(&self, time: DateTime<Utc>, format: &str) -> String {
+        match self {
+            DisplayZone::Utc => time.format(format).to_string(),
+            DisplayZone::Local => time.with_timezone(&Local).format(format).to_string(),
+            DisplayZone::Named(zone) => time.with_timezone(zone).format(format).to_string(),
+        }
+    }
+}
+
+impl fmt::Display for DisplayZone {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DisplayZone::Utc => write!(f, "UTC"),
+            DisplayZone::Local => write!(f, "local"),
+            DisplayZone::Named(zone) => write!(f, "{}", zone.name()),
+        }
+    }
+}
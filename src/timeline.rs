@@ -0,0 +1,162 @@
+use anyhow::{anyhow, Result};
+
+use crate::grouping::{group_by_key, GroupBy};
+use crate::i18n::Strings;
+use crate::time::DisplayZone;
+use crate::ChatSession;
+
+/// Which Mermaid diagram (if any) to draw of the development activity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TimelineStyle {
+    /// No diagram (the default)
+    #[default]
+    None,
+    /// A Mermaid `timeline`: one entry per week listing its sessions
+    Timeline,
+    /// A Mermaid `gantt` chart: one bar per session, one milestone per
+    /// week of commits
+    Gantt,
+}
+
+impl TimelineStyle {
+    /// The values `TIMELINE` accepts.
+    pub const NAMES: &'static [&'static str] = &["none", "timeline", "gantt"];
+
+    /// Parse a `--timeline` / `TIMELINE` value.
+    pub fn parse(name: &str) -> Result<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "" | "none" | "off" => Ok(TimelineStyle::None),
+            "timeline" => Ok(TimelineStyle::Timeline),
+            "gantt" => Ok(TimelineStyle::Gantt),
+            other => Err(anyhow!(
+                "Unknown timeline style '{}' (expected {})",
+                other,
+                TimelineStyle::NAMES.join(", ")
+            )),
+        }
+    }
+}
+
+/// One week of development: the sessions started in it and the commit
+/// times that landed in it.
+struct Week<'a> {
+    key: String,
+    sessions: Vec<(usize, &'a ChatSession)>,
+    commits: Vec<i64>,
+}
+
+/// Bucket sessions and commit times into ISO weeks, in date order.
+fn weeks<'a>(sessions: &[(usize, &'a ChatSession)], commit_times: &[i64], zone: &DisplayZone) -> Vec<Week<'a>> {
+    let mut weeks: Vec<Week> = group_by_key(sessions.to_vec(), |(_, session)| {
+        GroupBy::Week.date_key(session, zone).unwrap_or_default()
+    })
+    .into_iter()
+    .map(|(key, sessions)| Week {
+        key,
+        sessions,
+        commits: Vec::new(),
+    })
+    .collect();
+
+    for &time in commit_times {
+        let Some(key) = zone.format_millis(time, "%G-W%V") else {
+            continue;
+        };
+        match weeks.iter_mut().find(|week| week.key == key) {
+            Some(week) => week.commits.push(time),
+            None => weeks.push(Week {
+                key,
+                sessions: Vec::new(),
+                commits: vec![time],
+            }),
+        }
+    }
+
+    weeks.sort_by(|a, b| a.key.cmp(&b.key));
+    weeks
+}
+
+/// Draw the sessions (and the commits made around them) as the source of a
+/// Mermaid diagram, which GitHub and GitLab render inline. `sessions` carries the
+/// numbers the rest of the document uses.
+pub fn mermaid_diagram(
+    style: TimelineStyle,
+    sessions: &[(usize, &ChatSession)],
+    commit_times: &[i64],
+    zone: &DisplayZone,
+    strings: &Strings,
+) -> String {
+    let weeks = weeks(sessions, commit_times, zone);
+    if weeks.is_empty() {
+        return String::new();
+    }
+
+    let mut diagram = String::new();
+    match style {
+        TimelineStyle::None => return String::new(),
+        TimelineStyle::Timeline => {
+            diagram.push_str("timeline\n");
+            for week in &weeks {
+                let mut events: Vec<String> = week
+                    .sessions
+                    .iter()
+                    .map(|(number, session)| label(*number, &session.name, strings))
+                    .collect();
+                if !week.commits.is_empty() {
+                    events.push(strings.count("count.commits", week.commits.len()));
+                }
+                diagram.push_str(&format!("    {} : {}\n", week.key, events.join(" : ")));
+            }
+        }
+        TimelineStyle::Gantt => {
+            diagram.push_str("gantt\n    dateFormat YYYY-MM-DD HH:mm\n    axisFormat %b %d\n");
+            for week in &weeks {
+                diagram.push_str(&format!("    section {}\n", week.key));
+                for (number, session) in &week.sessions {
+                    let Some(start) = zone.format_millis(session.created_at, "%Y-%m-%d %H:%M") else {
+                        continue;
+                    };
+                    // Mermaid won't draw a zero-length bar, so short sessions get an hour
+                    let end = if session.last_updated_at > session.created_at + 60_000 {
+                        zone.format_millis(session.last_updated_at, "%Y-%m-%d %H:%M").unwrap_or_else(|| "1h".to_string())
+                    } else {
+                        "1h".to_string()
+                    };
+                    diagram.push_str(&format!(
+                        "    {} :s{}, {}, {}\n",
+                        label(*number, &session.name, strings),
+                        number,
+                        start,
+                        end
+                    ));
+                }
+                if let Some(last) = week.commits.iter().max() {
+                    let when = zone.format_millis(*last, "%Y-%m-%d %H:%M").unwrap_or_default();
+                    diagram.push_str(&format!(
+                        "    {} :milestone, {}, 0d\n",
+                        strings.count("count.commits", week.commits.len()),
+                        when
+                    ));
+                }
+            }
+        }
+    }
+    diagram
+}
+
+/// A short, Mermaid-safe label for a session. Colons, semicolons and
+/// hashes mean something to Mermaid, so they're dropped, and long names
+/// are cut so the diagram stays readable.
+fn label(number: usize, name: &str, strings: &Strings) -> String {
+    let cleaned: String = name
+        .chars()
+        .map(|c| if matches!(c, ':' | ';' | '#' | '\n' | '\r') { ' ' } else { c })
+        .collect();
+    let cleaned = cleaned.split_whitespace().collect::<Vec<_>>().join(" ");
+    let short: String = if cleaned.chars().count() > 40 {
+        format!("{}…", cleaned.chars().take(39).collect::<String>())
+    } else {
+        cleaned
+    };
+    format!("{} {} - {}", strings.get("sessions.session"), number, short)
+}
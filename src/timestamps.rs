@@ -0,0 +1,279 @@
+use anyhow::{anyhow, Result};
+use chrono::DateTime;
+use serde_json::Value;
+
+use crate::importers::ImportedHistory;
+use crate::warnings::{Warning, WarningKind};
+use crate::{ChatGeneration, ChatSession};
+
+/// The earliest a timestamp in a chat history can plausibly be
+/// (2000-01-01): anything before it is a missing value that came out as
+/// zero, or seconds read as milliseconds.
+pub const EARLIEST_MILLIS: i64 = 946_684_800_000;
+
+/// How far past the current time a timestamp can be before it's treated as
+/// wrong rather than a clock that's a little fast (a day).
+pub const FUTURE_SLACK_MILLIS: i64 = 24 * 60 * 60 * 1000;
+
+/// What to do with a record whose timestamp is zero, negative, or
+/// implausibly far in the past or future.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TimestampPolicy {
+    /// Keep the record as it is, with a warning (the default)
+    #[default]
+    Warn,
+    /// Leave the record out, with a warning
+    Skip,
+    /// Keep the record with the timestamp moved to the nearest plausible
+    /// time (for a session, its other timestamp if that one's fine), with
+    /// a warning
+    Clamp,
+}
+
+impl TimestampPolicy {
+    /// The values `TIMESTAMP_POLICY` accepts.
+    pub const NAMES: &'static [&'static str] = &["warn", "skip", "clamp"];
+
+    /// Parse a `TIMESTAMP_POLICY` value.
+    pub fn parse(name: &str) -> Result<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "" | "warn" => Ok(TimestampPolicy::Warn),
+            "skip" => Ok(TimestampPolicy::Skip),
+            "clamp" => Ok(TimestampPolicy::Clamp),
+            other => Err(anyhow!(
+                "Unknown timestamp policy '{}' (expected {})",
+                other,
+                TimestampPolicy::NAMES.join(", ")
+            )),
+        }
+    }
+}
+
+/// Below this, an autodetected timestamp is taken to be in seconds: as
+/// milliseconds it would be before March 1973, and as seconds it reaches
+/// past the year 5000.
+pub const SECONDS_CUTOFF: i64 = 100_000_000_000;
+
+/// What unit a source's numeric timestamps are in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TimeUnit {
+    /// Work it out for each value from its size (the default)
+    #[default]
+    Auto,
+    /// Unix seconds
+    Seconds,
+    /// Unix milliseconds
+    Milliseconds,
+}
+
+impl TimeUnit {
+    /// The units `TIMESTAMP_UNITS` accepts.
+    pub const NAMES: &'static [&'static str] = &["auto", "seconds", "milliseconds"];
+
+    /// Parse a unit name; `s` and `ms` will do too.
+    pub fn parse(name: &str) -> Result<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "" | "auto" => Ok(TimeUnit::Auto),
+            "s" | "seconds" => Ok(TimeUnit::Seconds),
+            "ms" | "milliseconds" => Ok(TimeUnit::Milliseconds),
+            other => Err(anyhow!("Unknown timestamp unit '{}' (expected {})", other, TimeUnit::NAMES.join(", "))),
+        }
+    }
+}
+
+/// Parse a `TIMESTAMP_UNITS` spec like `cody=seconds,plugin:mine=ms`:
+/// each entry is `source=unit`, or just a unit for every source not named.
+pub fn parse_time_units(spec: &str) -> Result<Vec<(Option<String>, TimeUnit)>> {
+    spec.split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| match entry.split_once('=') {
+            Some((source, unit)) => Ok((Some(source.trim().to_string()), TimeUnit::parse(unit)?)),
+            None => Ok((None, TimeUnit::parse(entry)?)),
+        })
+        .collect()
+}
+
+/// The unit `spec` (a `TIMESTAMP_UNITS` value) gives for `source`:
+/// its own entry, then the entry for every source, then milliseconds for
+/// Cursor (which never writes anything else) and autodetection for the rest.
+pub fn time_unit_for(spec: &str, source: &str) -> TimeUnit {
+    let units = parse_time_units(spec).unwrap_or_default();
+    units
+        .iter()
+        .find(|(named, _)| named.as_deref() == Some(source))
+        .or_else(|| units.iter().find(|(named, _)| named.is_none()))
+        .map(|(_, unit)| *unit)
+        .unwrap_or(if source == "cursor" { TimeUnit::Milliseconds } else { TimeUnit::Auto })
+}
+
+/// Turn a raw timestamp in `unit` into Unix milliseconds.
+pub fn to_millis(raw: i64, unit: TimeUnit) -> i64 {
+    match unit {
+        TimeUnit::Seconds => raw.saturating_mul(1000),
+        TimeUnit::Auto if raw.abs() < SECONDS_CUTOFF => raw.saturating_mul(1000),
+        _ => raw,
+    }
+}
+
+/// Turn a timestamp that may be a number (whole or fractional), a numeric
+/// string, or an RFC 3339 date string into Unix milliseconds, reading
+/// numbers as `unit`.
+pub fn parse_millis(value: &Value, unit: TimeUnit) -> Option<i64> {
+    match value {
+        Value::Number(number) => match number.as_i64() {
+            Some(raw) => Some(to_millis(raw, unit)),
+            None => number.as_f64().and_then(|raw| float_millis(raw, unit)),
+        },
+        Value::String(text) => match text.trim().parse::<i64>() {
+            Ok(raw) => Some(to_millis(raw, unit)),
+            Err(_) => text
+                .trim()
+                .parse::<f64>()
+                .ok()
+                .and_then(|raw| float_millis(raw, unit))
+                .or_else(|| DateTime::parse_from_rfc3339(text).ok().map(|date| date.timestamp_millis())),
+        },
+        _ => None,
+    }
+}
+
+fn float_millis(raw: f64, unit: TimeUnit) -> Option<i64> {
+    if !raw.is_finite() {
+        return None;
+    }
+    let seconds = match unit {
+        TimeUnit::Seconds => true,
+        TimeUnit::Milliseconds => false,
+        TimeUnit::Auto => raw.abs() < SECONDS_CUTOFF as f64,
+    };
+    Some(if seconds { (raw * 1000.0).round() } else { raw.round() } as i64)
+}
+
+/// Put the sessions' timestamps, and their messages', into milliseconds
+/// for a source whose timestamps are in `unit`.
+pub fn normalize_sessions(sessions: &mut [ChatSession], unit: TimeUnit) {
+    if unit == TimeUnit::Milliseconds {
+        return;
+    }
+    for session in sessions {
+        session.created_at = to_millis(session.created_at, unit);
+        session.last_updated_at = to_millis(session.last_updated_at, unit);
+        for message in &mut session.messages {
+            message.timestamp = message.timestamp.map(|raw| to_millis(raw, unit));
+        }
+    }
+}
+
+/// Put the generations' timestamps into milliseconds for a source whose
+/// timestamps are in `unit`.
+pub fn normalize_generations(generations: &mut [ChatGeneration], unit: TimeUnit) {
+    if unit == TimeUnit::Milliseconds {
+        return;
+    }
+    for generation in generations {
+        generation.unix_ms = to_millis(generation.unix_ms, unit);
+    }
+}
+
+/// What's wrong with `millis` as the time of something in a chat history
+/// read at `now` (both Unix milliseconds), or `None` if it's plausible.
+pub fn problem(millis: i64, now: i64) -> Option<&'static str> {
+    if millis == 0 {
+        Some("is missing (zero)")
+    } else if millis < 0 {
+        Some("is negative")
+    } else if millis < EARLIEST_MILLIS {
+        Some("is before 2000")
+    } else if millis > now.saturating_add(FUTURE_SLACK_MILLIS) {
+        Some("is in the future")
+    } else {
+        None
+    }
+}
+
+/// The plausible time nearest to `millis`: `fallback` (if that's
+/// plausible itself) or 2000-01-01 for one that's too early, and `now`
+/// for one in the future.
+pub fn clamp(millis: i64, fallback: Option<i64>, now: i64) -> i64 {
+    if millis > now {
+        return now;
+    }
+    fallback.filter(|fallback| problem(*fallback, now).is_none()).unwrap_or(EARLIEST_MILLIS)
+}
+
+/// Check the sessions' `created_at` and `last_updated_at` and the
+/// generations' `unix_ms` in `history` as of `now`, dealing with each bad
+/// one as `policy` says and adding a warning about it to
+/// `history.warnings`. Returns how many records were left out.
+pub fn validate_history(history: &mut ImportedHistory, policy: TimestampPolicy, now: i64) -> usize {
+    let mut warnings = Vec::new();
+    let mut left_out = 0;
+
+    for data in &mut history.sessions {
+        data.all_composers.retain_mut(|session| {
+            let location = format!("session {}", session.composer_id);
+            let (created, updated) = (problem(session.created_at, now), problem(session.last_updated_at, now));
+            for (field, value, found) in [
+                ("created_at", session.created_at, created),
+                ("last_updated_at", session.last_updated_at, updated),
+            ] {
+                if let Some(found) = found {
+                    warnings.push(timestamp_warning(&location, field, value, found, policy));
+                }
+            }
+            match policy {
+                _ if created.is_none() && updated.is_none() => true,
+                TimestampPolicy::Warn => true,
+                TimestampPolicy::Skip => {
+                    left_out += 1;
+                    false
+                }
+                TimestampPolicy::Clamp => {
+                    if created.is_some() {
+                        session.created_at = clamp(session.created_at, Some(session.last_updated_at), now);
+                    }
+                    if updated.is_some() {
+                        session.last_updated_at = clamp(session.last_updated_at, Some(session.created_at), now);
+                    }
+                    true
+                }
+            }
+        });
+    }
+
+    history.generations.retain_mut(|generation| {
+        let Some(found) = problem(generation.unix_ms, now) else {
+            return true;
+        };
+        let location = format!("generation {}", generation.generation_uuid);
+        warnings.push(timestamp_warning(&location, "unix_ms", generation.unix_ms, found, policy));
+        match policy {
+            TimestampPolicy::Warn => true,
+            TimestampPolicy::Skip => {
+                left_out += 1;
+                false
+            }
+            TimestampPolicy::Clamp => {
+                generation.unix_ms = clamp(generation.unix_ms, None, now);
+                true
+            }
+        }
+    });
+
+    history.warnings.extend(warnings);
+    left_out
+}
+
+fn timestamp_warning(location: &str, field: &str, value: i64, found: &str, policy: TimestampPolicy) -> Warning {
+    let kind = if value == 0 { WarningKind::MissingTimestamp } else { WarningKind::InvalidTimestamp };
+    let shown = DateTime::from_timestamp_millis(value)
+        .map(|time| format!(" ({})", time.format("%Y-%m-%d")))
+        .unwrap_or_default();
+    let action = match policy {
+        TimestampPolicy::Warn => "kept as it is",
+        TimestampPolicy::Skip => "left out",
+        TimestampPolicy::Clamp => "moved to the nearest plausible time",
+    };
+    Warning::new(kind, location, format!("{} {}{} {}; {}", field, value, shown, found, action))
+}
@@ -0,0 +1,65 @@
+use serde_json::Value;
+
+use crate::{ChatSession, ToolCall};
+
+/// Build a `ToolCall` from a tool name and its arguments.
+/// Arguments arrive either as a JSON string or as an already-parsed object;
+/// either way we keep them as a string and dig out the shell command if the
+/// tool was a terminal one.
+pub(crate) fn tool_call(name: &str, arguments: &Value, status: Option<&str>) -> ToolCall {
+    let parsed = match arguments {
+        Value::String(raw) => serde_json::from_str(raw).unwrap_or(Value::Null),
+        other => other.clone(),
+    };
+    let arguments = match arguments {
+        Value::String(raw) => raw.clone(),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    };
+
+    // Any tool that takes a `command` argument ran something in a shell,
+    // whatever the particular source decided to call it
+    let command = parsed["command"]
+        .as_str()
+        .or_else(|| parsed["cmd"].as_str().filter(|_| is_terminal_tool(name)))
+        .map(str::to_string);
+
+    ToolCall {
+        name: name.to_string(),
+        arguments,
+        command,
+        status: status.map(str::to_string),
+    }
+}
+
+/// Whether a tool name looks like one that runs shell commands.
+fn is_terminal_tool(name: &str) -> bool {
+    let name = name.to_lowercase();
+    name.contains("terminal") || name.contains("shell") || name.contains("bash")
+}
+
+/// Every shell command the AI ran during a session, in the order it ran them.
+pub fn commands_executed(session: &ChatSession) -> Vec<&str> {
+    session
+        .messages
+        .iter()
+        .flat_map(|message| &message.tool_calls)
+        .filter_map(|call| call.command.as_deref())
+        .collect()
+}
+
+/// How many times each tool was used in a session, most used first.
+pub fn tool_usage(session: &ChatSession) -> Vec<(&str, usize)> {
+    let mut usage: Vec<(&str, usize)> = Vec::new();
+
+    for call in session.messages.iter().flat_map(|message| &message.tool_calls) {
+        match usage.iter_mut().find(|(name, _)| *name == call.name) {
+            Some((_, count)) => *count += 1,
+            None => usage.push((&call.name, 1)),
+        }
+    }
+
+    // Stable sort keeps first-used order among tools with the same count
+    usage.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+    usage
+}
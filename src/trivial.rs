@@ -0,0 +1,103 @@
+use std::collections::BTreeSet;
+
+use crate::config::Config;
+use crate::importers::ImportedHistory;
+use crate::related::topic_words;
+use crate::selection::SessionFilter;
+use crate::ChatSession;
+
+/// Keeping the lore to the conversations that went somewhere, from
+/// MIN_SESSION_MESSAGES and MERGE_SESSIONS_WITHIN: sessions with hardly
+/// anything in them are hidden, and a run of quick sessions on the same
+/// thing (a question, a new chat to rephrase it, another to follow up) is
+/// rendered as one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TrivialSessions {
+    /// Sessions with fewer messages than this are hidden; 0 keeps them all
+    pub min_messages: usize,
+    /// How soon after a session's last message the next one has to start to
+    /// be merged into it (milliseconds); 0 never merges
+    pub merge_within_ms: i64,
+}
+
+/// What `TrivialSessions::apply` did to a history.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TrivialOutcome {
+    /// How many sessions were hidden
+    pub hidden: usize,
+    /// How many sessions were merged into the one before them
+    pub merged: usize,
+}
+
+impl TrivialSessions {
+    /// The thresholds the config sets.
+    pub fn from_config(config: &Config) -> Self {
+        TrivialSessions {
+            min_messages: config.min_session_messages,
+            merge_within_ms: i64::try_from(config.merge_sessions_within.saturating_mul(60_000)).unwrap_or(i64::MAX),
+        }
+    }
+
+    /// Whether this leaves every session as it is.
+    pub fn is_empty(&self) -> bool {
+        self.min_messages == 0 && self.merge_within_ms == 0
+    }
+
+    /// Merge the quick sessions in `history`, then hide the ones that are
+    /// still too short - so a string of one-question sessions that add up
+    /// to a conversation survives. A merged session keeps the first one's
+    /// ID and name, and gets the messages of the rest in turn.
+    pub fn apply(&self, history: &mut ImportedHistory) -> TrivialOutcome {
+        let mut outcome = TrivialOutcome::default();
+        if self.merge_within_ms > 0 {
+            for data in &mut history.sessions {
+                let before = data.all_composers.len();
+                data.all_composers = self.merge(std::mem::take(&mut data.all_composers));
+                outcome.merged += before - data.all_composers.len();
+            }
+        }
+        if self.min_messages > 0 {
+            let short: Vec<String> = history
+                .sessions
+                .iter()
+                .flat_map(|data| &data.all_composers)
+                .filter(|session| session.messages.len() < self.min_messages)
+                .map(|session| session.composer_id.clone())
+                .collect();
+            // Their prompts and generations go with them, as with EXCLUDE_SESSIONS
+            let filter = SessionFilter {
+                exclude: short,
+                ..SessionFilter::default()
+            };
+            outcome.hidden = filter.apply(history);
+        }
+        outcome
+    }
+
+    /// Merge each session that follows closely on the one before it (by
+    /// creation time) and shares words with it. Sessions stay where they
+    /// were in `sessions`, so SORT_BY=source still means the source's order.
+    fn merge(&self, sessions: Vec<ChatSession>) -> Vec<ChatSession> {
+        let mut sessions: Vec<(usize, ChatSession)> = sessions.into_iter().enumerate().collect();
+        sessions.sort_by_key(|(_, session)| session.created_at);
+
+        // Each merged session, with the words of the last session merged into it
+        let mut merged: Vec<(usize, ChatSession, BTreeSet<String>)> = Vec::new();
+        for (position, session) in sessions {
+            let words = topic_words(&session);
+            if let Some((_, previous, previous_words)) = merged.last_mut() {
+                let gap = session.created_at - previous.last_updated_at.max(previous.created_at);
+                if gap <= self.merge_within_ms && !previous_words.is_disjoint(&words) {
+                    previous.last_updated_at = previous.last_updated_at.max(session.last_updated_at);
+                    previous.has_unread_messages |= session.has_unread_messages;
+                    previous.messages.extend(session.messages);
+                    *previous_words = words;
+                    continue;
+                }
+            }
+            merged.push((position, session, words));
+        }
+        merged.sort_by_key(|(position, _, _)| *position);
+        merged.into_iter().map(|(_, session, _)| session).collect()
+    }
+}
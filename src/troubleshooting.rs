@@ -0,0 +1,127 @@
+use regex::Regex;
+use std::sync::OnceLock;
+
+use crate::decisions::excerpt;
+use crate::faq::{is_rejection, summary};
+use crate::{ChatSession, MessageRole};
+
+/// An error someone pasted into a chat, with the fix that was taken.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TroubleshootingEntry {
+    /// The error's telling line, as it was first pasted
+    pub signature: String,
+    /// The first sentences of the fix
+    pub resolution: String,
+    /// Files the fix changed, if the assistant edited any
+    pub files: Vec<String>,
+    /// IDs of the sessions the error came up in, first one first
+    pub sessions: Vec<String>,
+    /// When it was first pasted (Unix milliseconds)
+    pub timestamp: i64,
+}
+
+/// Find the exchanges where the user pasted an error or a stack trace and
+/// the assistant's reply fixed it, in the order the errors first came up.
+/// A reply counts as the fix unless the user's next message pushes back on
+/// it ("no", "still"...) or pastes the same error again. The same error in
+/// several sessions (the same line, give or take numbers, quoted values and
+/// paths) is one entry, with the first fix.
+pub fn troubleshooting_log(sessions: &[&ChatSession]) -> Vec<TroubleshootingEntry> {
+    let mut entries: Vec<(String, TroubleshootingEntry)> = Vec::new();
+    for session in sessions {
+        for (index, message) in session.messages.iter().enumerate() {
+            if message.role != MessageRole::User {
+                continue;
+            }
+            let Some(signature) = error_signature(&message.text) else {
+                continue;
+            };
+            let key = signature_key(&signature);
+            if let Some((_, entry)) = entries.iter_mut().find(|(known, _)| *known == key) {
+                if !entry.sessions.contains(&session.composer_id) {
+                    entry.sessions.push(session.composer_id.clone());
+                }
+                continue;
+            }
+
+            let Some(reply) = session.messages.get(index + 1).filter(|reply| reply.role == MessageRole::Assistant) else {
+                continue;
+            };
+            let pushed_back = session.messages.get(index + 2).is_some_and(|next| {
+                next.role == MessageRole::User
+                    && (is_rejection(&next.text)
+                        || error_signature(&next.text).is_some_and(|again| signature_key(&again) == key))
+            });
+            if pushed_back {
+                continue;
+            }
+            let Some(resolution) = summary(&reply.text) else {
+                continue;
+            };
+            let mut files: Vec<String> = Vec::new();
+            for edit in &reply.edits {
+                if !files.contains(&edit.path) {
+                    files.push(edit.path.clone());
+                }
+            }
+
+            let entry = TroubleshootingEntry {
+                signature,
+                resolution,
+                files,
+                sessions: vec![session.composer_id.clone()],
+                timestamp: message.timestamp.unwrap_or(session.created_at),
+            };
+            entries.push((key, entry));
+        }
+    }
+    entries.into_iter().map(|(_, entry)| entry).collect()
+}
+
+/// The line that says what went wrong in a pasted error, if the text has
+/// one: a compiler error (`error[E0308]: mismatched types`), an exception
+/// (`TypeError: x is undefined` - the last one in a Python traceback, which
+/// is where the exception is), a panic, or a line starting `fatal:`,
+/// `ERROR` or `FAILED`. Code fences are looked in too, since that's where
+/// errors usually get pasted.
+pub fn error_signature(text: &str) -> Option<String> {
+    static COMPILER: OnceLock<Regex> = OnceLock::new();
+    static EXCEPTION: OnceLock<Regex> = OnceLock::new();
+    static OTHER: OnceLock<Regex> = OnceLock::new();
+    let compiler = COMPILER.get_or_init(|| Regex::new(r"^error(\[[A-Z]*\d+\])?: \S").expect("valid regex"));
+    let exception = EXCEPTION.get_or_init(|| {
+        Regex::new(r"^(Uncaught |Unhandled )?([\w.$]+\.)?[A-Z]\w*(Error|Exception)(: |$)").expect("valid regex")
+    });
+    let other = OTHER.get_or_init(|| {
+        Regex::new(r"^(thread '.*' panicked at|panic: |fatal: |FATAL|ERROR\b|FAILED\b|Error: )").expect("valid regex")
+    });
+
+    let lines: Vec<&str> = text.lines().map(str::trim).filter(|line| !line.is_empty()).collect();
+    let traceback = lines.iter().any(|line| line.starts_with("Traceback (most recent call last)"));
+    let found = lines
+        .iter()
+        .find(|line| compiler.is_match(line))
+        .or_else(|| {
+            let mut exceptions = lines.iter().filter(|line| exception.is_match(line));
+            if traceback { exceptions.next_back() } else { exceptions.next() }
+        })
+        .or_else(|| lines.iter().find(|line| other.is_match(line)))?;
+    // A panic's message is on the line after it in newer Rust
+    let line = match lines.iter().position(|line| line == found) {
+        Some(at) if found.starts_with("thread '") && found.ends_with(':') => {
+            format!("{} {}", found, lines.get(at + 1).unwrap_or(&""))
+        }
+        _ => found.to_string(),
+    };
+    Some(excerpt(&line.replace('`', "'")))
+}
+
+/// What two pastes of the same error have in common: the signature
+/// lowercased, with numbers, quoted values and paths taken out.
+fn signature_key(signature: &str) -> String {
+    static VARYING: OnceLock<Regex> = OnceLock::new();
+    let varying = VARYING.get_or_init(|| {
+        Regex::new(r#"'[^']*'|"[^"]*"|\S*[/\\]\S*|0x[0-9a-fA-F]+|\d+"#).expect("valid regex")
+    });
+    varying.replace_all(&signature.to_lowercase(), "_").split_whitespace().collect::<Vec<_>>().join(" ")
+}
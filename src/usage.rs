@@ -0,0 +1,181 @@
+use anyhow::{anyhow, Context, Result};
+use chrono::DateTime;
+use serde::{Deserialize, Serialize};
+
+use crate::{ChatSession, ComposerData, MessageRole};
+
+/// What a model costs, in US dollars per million tokens.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelPrice {
+    /// The model name (or name prefix) this price applies to
+    pub model: String,
+    /// Dollars per million input tokens
+    pub input_per_million: f64,
+    /// Dollars per million output tokens
+    pub output_per_million: f64,
+}
+
+/// Token and cost totals for one model.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ModelUsage {
+    /// The model name ("unknown" when the source didn't record one)
+    pub model: String,
+    /// How many AI responses the model produced
+    pub responses: usize,
+    /// Input tokens (recorded where available, estimated otherwise)
+    pub input_tokens: u64,
+    /// Output tokens (recorded where available, estimated otherwise)
+    pub output_tokens: u64,
+    /// Whether any of the token counts had to be estimated from text length
+    pub estimated: bool,
+    /// Estimated spend in dollars, if we know the model's price
+    pub cost: Option<f64>,
+}
+
+/// Parse a pricing spec like `gpt-4o=2.5/10,claude-3.5-sonnet=3/15`.
+/// Each entry is `model=input/output`, in dollars per million tokens.
+pub fn parse_pricing(spec: &str) -> Result<Vec<ModelPrice>> {
+    spec.split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (model, prices) = entry
+                .split_once('=')
+                .ok_or_else(|| anyhow!("Model price '{}' should look like model=input/output", entry))?;
+            let (input, output) = prices
+                .split_once('/')
+                .ok_or_else(|| anyhow!("Model price '{}' should look like model=input/output", entry))?;
+
+            Ok(ModelPrice {
+                model: model.trim().to_string(),
+                input_per_million: input.trim().parse().with_context(|| format!("Bad input price in '{}'", entry))?,
+                output_per_million: output.trim().parse().with_context(|| format!("Bad output price in '{}'", entry))?,
+            })
+        })
+        .collect()
+}
+
+/// Find the price for a model. Prices match by prefix (so `claude-3.5-sonnet`
+/// covers `claude-3.5-sonnet-20241022`), and the most specific match wins.
+pub fn price_for<'a>(pricing: &'a [ModelPrice], model: &str) -> Option<&'a ModelPrice> {
+    let model = model.to_lowercase();
+    pricing
+        .iter()
+        .filter(|price| model.starts_with(&price.model.to_lowercase()))
+        .max_by_key(|price| price.model.len())
+}
+
+/// A rough token estimate for text we don't have real counts for.
+/// Four characters per token is the usual rule of thumb for English and code.
+pub fn estimate_tokens(text: &str) -> u64 {
+    (text.chars().count() as u64).div_ceil(4)
+}
+
+/// Work out which models answered in a session and roughly what that cost.
+/// Each AI response is charged for the user messages since the previous
+/// response (as input) and its own text (as output), unless the source
+/// recorded real token counts.
+pub fn session_usage(session: &ChatSession, pricing: &[ModelPrice]) -> Vec<ModelUsage> {
+    let mut usage = Vec::new();
+    let mut pending_input = 0;
+
+    for message in &session.messages {
+        match message.role {
+            MessageRole::User => pending_input += estimate_tokens(&message.text),
+            MessageRole::Assistant => {
+                let (input_tokens, output_tokens, estimated) = match message.usage {
+                    Some(recorded) => (recorded.input_tokens, recorded.output_tokens, false),
+                    None => (pending_input, estimate_tokens(&message.text), true),
+                };
+                pending_input = 0;
+
+                merge_usage(
+                    &mut usage,
+                    ModelUsage {
+                        model: message.model.clone().unwrap_or_else(|| "unknown".to_string()),
+                        responses: 1,
+                        input_tokens,
+                        output_tokens,
+                        estimated,
+                        cost: None,
+                    },
+                );
+            }
+        }
+    }
+
+    price_usage(&mut usage, pricing);
+    usage
+}
+
+/// Usage across all sessions, grouped by model.
+pub fn total_usage(sessions: &[ComposerData], pricing: &[ModelPrice]) -> Vec<ModelUsage> {
+    let mut usage = Vec::new();
+    for session in sessions.iter().flat_map(|data| &data.all_composers) {
+        for model_usage in session_usage(session, pricing) {
+            merge_usage(&mut usage, model_usage);
+        }
+    }
+    price_usage(&mut usage, pricing);
+    usage
+}
+
+/// Usage grouped by the month each session started in (`YYYY-MM`), oldest first.
+pub fn monthly_usage(sessions: &[ComposerData], pricing: &[ModelPrice]) -> Vec<(String, Vec<ModelUsage>)> {
+    let mut months: Vec<(String, Vec<ModelUsage>)> = Vec::new();
+
+    for session in sessions.iter().flat_map(|data| &data.all_composers) {
+        let Some(created_at) = DateTime::from_timestamp_millis(session.created_at) else {
+            continue;
+        };
+        let month = created_at.format("%Y-%m").to_string();
+
+        let position = match months.iter().position(|(existing, _)| *existing == month) {
+            Some(position) => position,
+            None => {
+                months.push((month, Vec::new()));
+                months.len() - 1
+            }
+        };
+        for model_usage in session_usage(session, pricing) {
+            merge_usage(&mut months[position].1, model_usage);
+        }
+    }
+
+    for (_, usage) in &mut months {
+        price_usage(usage, pricing);
+    }
+    months.retain(|(_, usage)| !usage.is_empty());
+    months.sort_by(|a, b| a.0.cmp(&b.0));
+    months
+}
+
+/// Add up the estimated spend, if any of the models had a price.
+pub fn total_cost(usage: &[ModelUsage]) -> Option<f64> {
+    usage.iter().filter_map(|model_usage| model_usage.cost).reduce(|a, b| a + b)
+}
+
+/// Fold one model's usage into a list, combining it with any existing entry.
+fn merge_usage(usage: &mut Vec<ModelUsage>, addition: ModelUsage) {
+    match usage.iter_mut().find(|existing| existing.model == addition.model) {
+        Some(existing) => {
+            existing.responses += addition.responses;
+            existing.input_tokens += addition.input_tokens;
+            existing.output_tokens += addition.output_tokens;
+            existing.estimated |= addition.estimated;
+        }
+        None => usage.push(addition),
+    }
+}
+
+/// Fill in costs from the price list, and sort busiest model first.
+fn price_usage(usage: &mut [ModelUsage], pricing: &[ModelPrice]) {
+    for model_usage in usage.iter_mut() {
+        model_usage.cost = price_for(pricing, &model_usage.model).map(|price| {
+            (model_usage.input_tokens as f64 * price.input_per_million
+                + model_usage.output_tokens as f64 * price.output_per_million)
+                / 1_000_000.0
+        });
+    }
+    usage.sort_by(|a, b| b.responses.cmp(&a.responses).then(a.model.cmp(&b.model)));
+}
@@ -0,0 +1,94 @@
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+
+use crate::document::{Block, Document};
+use crate::i18n::Strings;
+use crate::render::OutputFormat;
+
+/// One session `verify` looked for in the lore.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionCheck {
+    /// The session's ID
+    pub id: String,
+    /// Its heading, like "Session 3: Fix login bug"
+    pub title: String,
+    /// The SHA-256 of the session as it renders from the database now
+    pub hash: String,
+}
+
+/// How lore written earlier compares with what the database holds now.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Verification {
+    /// Every session the database has, in the order the lore lists them
+    pub sessions: Vec<SessionCheck>,
+    /// Sessions the lore doesn't mention at all
+    pub missing: Vec<SessionCheck>,
+    /// Sessions the lore has, but not as they render now: their
+    /// transcript (or anything else about them) has changed since
+    pub drifted: Vec<SessionCheck>,
+    /// The SHA-256 of the lore file itself
+    pub lore_hash: String,
+}
+
+impl Verification {
+    /// Whether the lore still has every session, just as it renders now.
+    pub fn is_faithful(&self) -> bool {
+        self.missing.is_empty() && self.drifted.is_empty()
+    }
+}
+
+/// Check that `lore` - a file written earlier in `format` - still holds
+/// every session of `document`, which was built from the database just
+/// now with the same configuration. Each session is rendered on its own
+/// and hashed; a session whose ID isn't in the lore is missing, and one
+/// whose ID is there but whose rendering isn't has drifted. Sections that
+/// change every run, like the metadata, aren't compared.
+pub fn verify_lore(lore: &str, document: &Document, format: OutputFormat, strings: &Strings) -> Result<Verification> {
+    let mut verification = Verification {
+        lore_hash: sha256_hex(lore.as_bytes()),
+        ..Verification::default()
+    };
+    for (id, blocks) in session_blocks(&document.blocks, strings.get("sessions.session_id")) {
+        let rendered = format.render_blocks(blocks)?;
+        let title = match blocks.first() {
+            Some(Block::Heading { text, .. }) => text.clone(),
+            _ => String::new(),
+        };
+        let check = SessionCheck {
+            id: id.to_string(),
+            title,
+            hash: sha256_hex(rendered.as_bytes()),
+        };
+        if !lore.contains(id) {
+            verification.missing.push(check.clone());
+        } else if !lore.contains(&rendered) {
+            verification.drifted.push(check.clone());
+        }
+        verification.sessions.push(check);
+    }
+    Ok(verification)
+}
+
+/// Each session in `blocks` with its ID: from its heading, which has the
+/// ID among its properties, down to the next heading as high up.
+fn session_blocks<'a>(blocks: &'a [Block], id_label: &str) -> Vec<(&'a str, &'a [Block])> {
+    let mut sessions = Vec::new();
+    for (start, block) in blocks.iter().enumerate() {
+        let Block::Heading { level, properties, .. } = block else {
+            continue;
+        };
+        let Some((_, id)) = properties.iter().find(|(label, _)| label == id_label) else {
+            continue;
+        };
+        let end = blocks[start + 1..]
+            .iter()
+            .position(|block| matches!(block, Block::Heading { level: next, .. } if next <= level))
+            .map_or(blocks.len(), |offset| start + 1 + offset);
+        sessions.push((id.as_str(), &blocks[start..end]));
+    }
+    sessions
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
@@ -0,0 +1,250 @@
+use anyhow::{bail, Context, Result};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use regex::Regex;
+use serde_json::Value;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::sync::OnceLock;
+
+use crate::document::{heading_anchor, Document};
+use crate::i18n::{available_locales, Strings};
+use crate::render::OutputFormat;
+use crate::snapshot::Dataset;
+
+/// The version of the structure of what the tool writes: the lore in each
+/// text format (stamped in a comment at the end), JSON documents and
+/// extracted datasets (in their `format_version`). It goes up whenever a
+/// change would trip up something reading older files, with a step in
+/// `migrate` to bring those up to date. Files written before versions
+/// were stamped are version 0.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// The name in a text format's stamp: `<!-- lore-format: 1 -->`.
+pub const VERSION_STAMP: &str = "lore-format";
+
+/// Fail for something written by a newer version of the tool, whose
+/// structure this one can't know about.
+pub fn check_readable(version: u32, what: &str) -> Result<()> {
+    if version > FORMAT_VERSION {
+        bail!(
+            "{} is in format version {}, but this version of the tool only knows up to {}; upgrade it to read the file",
+            what,
+            version,
+            FORMAT_VERSION
+        );
+    }
+    Ok(())
+}
+
+/// The version a text file's stamp (its last line) says it's in, if it has one.
+pub fn stamped_version(text: &str) -> Option<u32> {
+    static STAMP: OnceLock<Regex> = OnceLock::new();
+    let stamp = STAMP.get_or_init(|| {
+        Regex::new(&format!(r"^(?:<!--|#|//)\s*{}:\s*(\d+)\s*(?:-->)?$", regex::escape(VERSION_STAMP))).unwrap()
+    });
+    let last = text.lines().rev().find(|line| !line.trim().is_empty())?;
+    stamp.captures(last.trim()).and_then(|captures| captures[1].parse().ok())
+}
+
+/// What sort of file `migrate` upgraded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArtifactKind {
+    /// Markdown lore
+    Markdown,
+    /// A document written with `--format json`
+    Document,
+    /// An extracted dataset (`snapshots show --json`), compressed or not
+    Dataset,
+}
+
+impl ArtifactKind {
+    /// What to call it in messages.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ArtifactKind::Markdown => "markdown lore",
+            ArtifactKind::Document => "JSON document",
+            ArtifactKind::Dataset => "dataset",
+        }
+    }
+}
+
+/// A file brought up to `FORMAT_VERSION` by `migrate`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Upgrade {
+    /// What the file is
+    pub kind: ArtifactKind,
+    /// The version it was in
+    pub from: u32,
+    /// What changed, one line per change
+    pub changes: Vec<String>,
+    /// The file's new contents
+    pub contents: Vec<u8>,
+}
+
+/// Bring a file written by an older version of the tool up to
+/// `FORMAT_VERSION`: markdown lore (`.md`), JSON documents and datasets
+/// (`.json`), and compressed datasets (`.json.gz`). Gives `None` for a
+/// file that's already current, or isn't something the tool wrote, and
+/// fails for one written by a newer version.
+pub fn migrate(path: &Path, bytes: &[u8]) -> Result<Option<Upgrade>> {
+    let name = path.file_name().map(|name| name.to_string_lossy().to_lowercase()).unwrap_or_default();
+    let what = path.display().to_string();
+    if name.ends_with(".md") || name.ends_with(".markdown") {
+        let Ok(text) = std::str::from_utf8(bytes) else {
+            return Ok(None);
+        };
+        return migrate_markdown(text, &what);
+    }
+    let compressed = name.ends_with(".json.gz");
+    if !compressed && !name.ends_with(".json") {
+        return Ok(None);
+    }
+    let json = if compressed {
+        let mut json = Vec::new();
+        GzDecoder::new(bytes).read_to_end(&mut json).with_context(|| format!("{} isn't a valid gzip file", what))?;
+        json
+    } else {
+        bytes.to_vec()
+    };
+    // Other JSON - the lore index, a graph - has versions of its own, or none
+    let Ok(Value::Object(object)) = serde_json::from_slice::<Value>(&json) else {
+        return Ok(None);
+    };
+    let from = object.get("format_version").and_then(Value::as_u64).map_or(0, |version| version as u32);
+    check_readable(from, &what)?;
+    if from == FORMAT_VERSION {
+        return Ok(None);
+    }
+    let recorded = format!("recorded format version {}", FORMAT_VERSION);
+    if object.contains_key("blocks") && !compressed {
+        // Version 0 documents have the same blocks; only the version is new
+        let mut document: Document = serde_json::from_value(Value::Object(object)).with_context(|| format!("{} isn't a lore document", what))?;
+        document.format_version = FORMAT_VERSION;
+        let contents = OutputFormat::Json.render(&document)?.into_bytes();
+        return Ok(Some(Upgrade { kind: ArtifactKind::Document, from, changes: vec![recorded], contents }));
+    }
+    if object.contains_key("sessions") && object.contains_key("prompts") {
+        let mut dataset: Dataset = serde_json::from_value(Value::Object(object)).with_context(|| format!("{} isn't an extracted dataset", what))?;
+        dataset.format_version = FORMAT_VERSION;
+        let mut contents = serde_json::to_vec_pretty(&dataset)?;
+        contents.push(b'\n');
+        if compressed {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(&contents)?;
+            contents = encoder.finish()?;
+        }
+        return Ok(Some(Upgrade { kind: ArtifactKind::Dataset, from, changes: vec![recorded], contents }));
+    }
+    Ok(None)
+}
+
+/// Upgrade markdown lore, one version at a time, and stamp it.
+fn migrate_markdown(text: &str, what: &str) -> Result<Option<Upgrade>> {
+    let Some(strings) = lore_locale(text) else {
+        return Ok(None);
+    };
+    let from = stamped_version(text).unwrap_or(0);
+    check_readable(from, what)?;
+    if from == FORMAT_VERSION {
+        return Ok(None);
+    }
+
+    let mut text = text.to_string();
+    let mut changes = Vec::new();
+    if from < 1 {
+        if let Some((upgraded, sessions)) = add_contents(&text, &strings) {
+            text = upgraded;
+            changes.push(format!("added the contents list of {} sessions", sessions));
+        }
+    }
+
+    // Replace the old stamp, or start one after a blank line, as the renderer does
+    if stamped_version(&text).is_some() {
+        let end = text.trim_end().rfind('\n').map_or(0, |newline| newline + 1);
+        text.truncate(end);
+    } else if !text.is_empty() {
+        if !text.ends_with('\n') {
+            text.push('\n');
+        }
+        text.push('\n');
+    }
+    text.push_str(&OutputFormat::Markdown.version_stamp().unwrap_or_default());
+    changes.push(format!("stamped format version {}", FORMAT_VERSION));
+    Ok(Some(Upgrade { kind: ArtifactKind::Markdown, from, changes, contents: text.into_bytes() }))
+}
+
+/// The locale markdown lore was written in, going by its title; `None`
+/// for markdown the tool didn't write.
+fn lore_locale(text: &str) -> Option<Strings> {
+    let titles: Vec<&str> = headings(text).filter(|(level, _, _)| *level == 1).map(|(_, title, _)| title).collect();
+    available_locales()
+        .into_iter()
+        .filter_map(|locale| Strings::for_locale(locale).ok())
+        .find(|strings| titles.contains(&strings.get("header.title")))
+}
+
+/// The headings in markdown, outside code fences: their level, their
+/// text and the line they're on.
+fn headings(text: &str) -> impl Iterator<Item = (usize, &str, usize)> {
+    let mut fence: Option<(char, usize)> = None;
+    text.lines().enumerate().filter_map(move |(number, line)| {
+        let trimmed = line.trim();
+        let marker = trimmed.chars().next().filter(|c| *c == '`' || *c == '~');
+        if let Some(c) = marker {
+            let run = trimmed.chars().take_while(|d| *d == c).count();
+            match fence {
+                None if run >= 3 => fence = Some((c, run)),
+                Some((open, length)) if c == open && run >= length && trimmed.chars().all(|d| d == c) => fence = None,
+                _ => {}
+            }
+            return None;
+        }
+        if fence.is_some() {
+            return None;
+        }
+        let level = line.chars().take_while(|c| *c == '#').count();
+        let title = line[level..].strip_prefix(' ')?;
+        (1..=6).contains(&level).then_some((level, title.trim(), number))
+    })
+}
+
+/// Version 1: lore written before the sessions were sorted has no linked
+/// contents list at the top of Historical Chat Sessions. Build one from
+/// the session headings, returning the new text and how many sessions it
+/// lists, or `None` if there's nothing to add.
+fn add_contents(text: &str, strings: &Strings) -> Option<(String, usize)> {
+    let all: Vec<(usize, &str, usize)> = headings(text).collect();
+    let start = all.iter().position(|(_, title, _)| *title == strings.get("sessions.title"))?;
+    let (level, _, line) = all[start];
+    let session_prefix = format!("{} ", strings.get("sessions.session"));
+    let titles: Vec<&str> = all[start + 1..]
+        .iter()
+        .take_while(|(deeper, _, _)| *deeper > level)
+        .map(|(_, title, _)| *title)
+        .filter(|title| {
+            title.strip_prefix(&session_prefix).and_then(|rest| rest.split_once(':')).is_some_and(|(number, _)| number.parse::<usize>().is_ok())
+        })
+        .collect();
+    let lines: Vec<&str> = text.lines().collect();
+    let contents_label = format!("**{}**", strings.get("sessions.contents"));
+    let already = lines[line + 1..].iter().find(|next| !next.trim().is_empty()).is_some_and(|next| next.trim() == contents_label);
+    if titles.is_empty() || already {
+        return None;
+    }
+
+    let mut inserted = vec![String::new(), contents_label, String::new()];
+    inserted.extend(titles.iter().map(|title| format!("- [{}](#{})", title, heading_anchor(title))));
+    let mut upgraded: Vec<String> = lines[..=line].iter().map(|line| line.to_string()).collect();
+    upgraded.extend(inserted);
+    if lines.get(line + 1).is_some_and(|next| !next.trim().is_empty()) {
+        upgraded.push(String::new());
+    }
+    upgraded.extend(lines[line + 1..].iter().map(|line| line.to_string()));
+    let mut upgraded = upgraded.join("\n");
+    if text.ends_with('\n') {
+        upgraded.push('\n');
+    }
+    Some((upgraded, titles.len()))
+}
@@ -0,0 +1,104 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::Mutex;
+
+use crate::parsing::SkippedRecord;
+
+/// What kind of problem a warning is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum WarningKind {
+    /// A record didn't have the shape we expected and was left out
+    SkippedRecord,
+    /// A record had no usable timestamp, so one was made up for it
+    MissingTimestamp,
+    /// A timestamp was there but couldn't be shown as a date, or was
+    /// implausibly far in the past or future
+    InvalidTimestamp,
+}
+
+impl fmt::Display for WarningKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            WarningKind::SkippedRecord => "skipped record",
+            WarningKind::MissingTimestamp => "missing timestamp",
+            WarningKind::InvalidTimestamp => "invalid timestamp",
+        })
+    }
+}
+
+/// Something about the chat history that wasn't right, and what was done
+/// about it instead of failing: a record left out, a date guessed. These
+/// go in the run report, and with INCLUDE_WARNINGS in an appendix to the
+/// lore, so readers know which parts to take with a pinch of salt.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+pub struct Warning {
+    /// What kind of problem it is
+    pub kind: WarningKind,
+    /// Which record: a session ID, a key, or a position in a list
+    pub location: String,
+    /// What was wrong, and what was done about it
+    pub message: String,
+}
+
+impl Warning {
+    /// A warning about the record at `location`.
+    pub fn new(kind: WarningKind, location: impl Into<String>, message: impl Into<String>) -> Self {
+        Warning { kind, location: location.into(), message: message.into() }
+    }
+}
+
+impl From<&SkippedRecord> for Warning {
+    fn from(record: &SkippedRecord) -> Self {
+        Warning::new(WarningKind::SkippedRecord, format!("{} {}", record.kind, record.location), record.error.clone())
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} in {}: {}", self.kind, self.location, self.message)
+    }
+}
+
+/// Warnings gathered while the lore is built, from whichever thread finds
+/// them. The same warning is only kept once, since a session can be
+/// rendered more than once in a run (for the lore and its index, say).
+#[derive(Debug, Default)]
+pub struct Warnings {
+    found: Mutex<Vec<Warning>>,
+}
+
+impl Warnings {
+    /// Start with the warnings in `found`.
+    pub fn from_vec(found: Vec<Warning>) -> Self {
+        let warnings = Warnings::default();
+        warnings.extend(found);
+        warnings
+    }
+
+    /// Add a warning, unless it's already there.
+    pub fn push(&self, warning: Warning) {
+        let mut found = self.found.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if !found.contains(&warning) {
+            found.push(warning);
+        }
+    }
+
+    /// Add several.
+    pub fn extend(&self, warnings: impl IntoIterator<Item = Warning>) {
+        for warning in warnings {
+            self.push(warning);
+        }
+    }
+
+    /// Everything found so far, in the order it was found.
+    pub fn to_vec(&self) -> Vec<Warning> {
+        self.found.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).clone()
+    }
+
+    /// Whether nothing's been found.
+    pub fn is_empty(&self) -> bool {
+        self.found.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).is_empty()
+    }
+}
@@ -0,0 +1,74 @@
+//! JavaScript bindings for a lore viewer in the browser. A document written
+//! with `--format json` can be rendered again client-side in any of the
+//! text formats, and text can be run through the same secret redaction
+//! the CLI uses, without a server.
+//!
+//! Build with `--no-default-features --features wasm --target
+//! wasm32-unknown-unknown` and hand the result to `wasm-bindgen`.
+
+use wasm_bindgen::prelude::*;
+
+use crate::document::{Block, Document};
+use crate::redaction::redact_secrets;
+use crate::render::OutputFormat;
+use crate::versioning::check_readable;
+
+/// Render a document written with `--format json` as `format`: markdown,
+/// org, asciidoc, typst, or json again.
+#[wasm_bindgen(js_name = renderDocument)]
+pub fn render_document(json: &str, format: &str) -> Result<String, JsError> {
+    let format = OutputFormat::parse(format).map_err(|error| JsError::new(&error.to_string()))?;
+    let document: Document = serde_json::from_str(json)?;
+    check_readable(document.format_version, "The document").map_err(|error| JsError::new(&error.to_string()))?;
+    format.render(&document).map_err(|error| JsError::new(&error.to_string()))
+}
+
+/// Redact a document written with `--format json`, in case it was
+/// exported with REDACT_SECRETS turned off, and give it back as JSON.
+#[wasm_bindgen(js_name = redactDocument)]
+pub fn redact_document(json: &str) -> Result<String, JsError> {
+    let mut document: Document = serde_json::from_str(json)?;
+    for block in &mut document.blocks {
+        redact_block(block);
+    }
+    Ok(serde_json::to_string(&document)?)
+}
+
+/// Replace anything in `text` that looks like a secret with a placeholder.
+#[wasm_bindgen(js_name = redactSecrets)]
+pub fn redact_secrets_in(text: &str) -> String {
+    redact_secrets(text)
+}
+
+/// The formats `renderDocument` can render.
+#[wasm_bindgen(js_name = documentFormats)]
+pub fn document_formats() -> Vec<String> {
+    ["markdown", "org", "asciidoc", "typst", "json"].iter().map(|name| name.to_string()).collect()
+}
+
+fn redact_block(block: &mut Block) {
+    match block {
+        Block::Heading { text, properties, .. } => {
+            *text = redact_secrets(text);
+            for (_, value) in properties {
+                *value = redact_secrets(value);
+            }
+        }
+        Block::Paragraph(text) | Block::Code { text, .. } | Block::Message { text, .. } => *text = redact_secrets(text),
+        Block::Fields(fields) => {
+            for (_, value) in fields {
+                *value = redact_secrets(value);
+            }
+        }
+        Block::List { items, .. } => {
+            for item in items {
+                *item = redact_secrets(item);
+            }
+        }
+        Block::Details { summary, blocks } => {
+            *summary = redact_secrets(summary);
+            blocks.iter_mut().for_each(redact_block);
+        }
+        Block::Rule => {}
+    }
+}
@@ -0,0 +1,97 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// How old a leftover workspace from a crashed run has to be before we sweep it up.
+const STALE_AFTER: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// A scratch directory that lives exactly as long as one run.
+/// Everything temporary - database snapshots, partially rendered output,
+/// downloaded files - goes in here instead of being scattered around, and
+/// the whole directory disappears when the workspace is dropped. Because
+/// cleanup happens on drop, it also happens when a run is cancelled and its
+/// future is dropped mid-flight.
+#[derive(Debug)]
+pub struct TempWorkspace {
+    /// The root of this run's scratch space
+    root: PathBuf,
+    /// Leave everything on disk afterwards (handy for debugging)
+    keep: bool,
+}
+
+impl TempWorkspace {
+    /// Create a fresh workspace under the system temp directory.
+    /// The directory name starts with `app_name` so leftovers are easy to spot.
+    pub fn create(app_name: &str, keep: bool) -> Result<Self> {
+        TempWorkspace::create_in(&std::env::temp_dir(), app_name, keep)
+    }
+
+    /// Create a fresh workspace under `parent` instead of the system temp directory.
+    pub fn create_in(parent: &Path, app_name: &str, keep: bool) -> Result<Self> {
+        let prefix = format!("{}-run-", app_name);
+
+        // Runs killed with SIGKILL never get to clean up, so tidy their leftovers
+        sweep_stale(parent, &prefix);
+
+        let root = parent.join(format!("{}{}", prefix, uuid::Uuid::new_v4()));
+        fs::create_dir_all(&root)
+            .with_context(|| format!("Could not create temp workspace {}", root.display()))?;
+
+        Ok(TempWorkspace { root, keep })
+    }
+
+    /// The root directory of the workspace.
+    pub fn path(&self) -> &Path {
+        &self.root
+    }
+
+    /// Whether the workspace will be left on disk when the run ends.
+    pub fn is_kept(&self) -> bool {
+        self.keep
+    }
+
+    /// Get (and create if needed) a named area of the workspace,
+    /// like `snapshots` or `renders`.
+    pub fn subdir(&self, name: &str) -> Result<PathBuf> {
+        let dir = self.root.join(name);
+        fs::create_dir_all(&dir)
+            .with_context(|| format!("Could not create temp directory {}", dir.display()))?;
+        Ok(dir)
+    }
+
+    /// A path for a temporary file inside a named area of the workspace.
+    pub fn file(&self, area: &str, name: &str) -> Result<PathBuf> {
+        Ok(self.subdir(area)?.join(name))
+    }
+}
+
+impl Drop for TempWorkspace {
+    fn drop(&mut self) {
+        if !self.keep {
+            // Nothing useful to do if this fails - we're on the way out anyway
+            let _ = fs::remove_dir_all(&self.root);
+        }
+    }
+}
+
+/// Remove workspaces with our prefix that haven't been touched in a day.
+fn sweep_stale(parent: &Path, prefix: &str) {
+    let Ok(entries) = fs::read_dir(parent) else {
+        return;
+    };
+
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let is_ours = entry.file_name().to_string_lossy().starts_with(prefix);
+        let is_stale = entry
+            .metadata()
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+            .is_some_and(|age| age > STALE_AFTER);
+
+        if is_ours && is_stale {
+            let _ = fs::remove_dir_all(entry.path());
+        }
+    }
+}
@@ -0,0 +1,129 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use crate::init::file_uri_path;
+
+/// The `ItemTable` key with the editor history: files opened in the
+/// workspace, most recent first.
+pub const HISTORY_KEY: &str = "history.entries";
+
+/// The `ItemTable` key with the editor layout: the groups of tabs that
+/// were open, and which one had focus.
+pub const EDITOR_STATE_KEY: &str = "memento/workbench.parts.editor";
+
+/// The most recently opened files kept.
+const MAX_RECENT_FILES: usize = 50;
+
+/// What a Cursor workspace had open around the time of the chats: the
+/// folder from workspace.json, the editor tabs the window was left with,
+/// and the files opened recently. It's how the workspace looks now (Cursor
+/// doesn't keep it per session), which still says which part of the
+/// codebase the chats were about.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WorkspaceInfo {
+    /// What the workspace has open, as Cursor wrote it in workspace.json:
+    /// a folder, a `.code-workspace` file, or a remote URI
+    pub folder_uri: Option<String>,
+    /// The local folder, when `folder_uri` is one
+    pub folder: Option<String>,
+    /// The files open in editor tabs, in the order they were opened
+    pub open_files: Vec<String>,
+    /// Files opened recently, most recent first
+    pub recent_files: Vec<String>,
+}
+
+impl WorkspaceInfo {
+    /// Put together what the workspace's records say: the workspace.json
+    /// file and the values under `HISTORY_KEY` and `EDITOR_STATE_KEY`, each
+    /// if there is one. Anything that doesn't parse is passed over.
+    pub fn from_records(workspace_json: Option<&str>, history: Option<&str>, editor_state: Option<&str>) -> Self {
+        let folder_uri = workspace_json
+            .and_then(|text| serde_json::from_str::<Value>(text).ok())
+            .and_then(|json| {
+                ["folder", "workspace"]
+                    .iter()
+                    .find_map(|key| json.get(key).and_then(Value::as_str).map(str::to_string))
+            });
+        let folder = folder_uri
+            .as_deref()
+            .filter(|uri| !uri.ends_with(".code-workspace"))
+            .and_then(file_uri_path)
+            .map(|path| path.to_string_lossy().into_owned());
+
+        // Each entry is {"editor": {"resource": "file:///..."}}
+        let recent_files = history
+            .and_then(|text| serde_json::from_str::<Vec<Value>>(text).ok())
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|entry| entry.pointer("/editor/resource").and_then(Value::as_str))
+            .filter_map(file_uri_path)
+            .map(|path| path.to_string_lossy().into_owned())
+            .fold(Vec::new(), unique)
+            .into_iter()
+            .take(MAX_RECENT_FILES)
+            .collect();
+
+        // The layout nests each tab's state as a JSON string inside JSON, so
+        // rather than follow the grid, pick out the file URIs wherever they are
+        static FILE_URI: OnceLock<Regex> = OnceLock::new();
+        let file_uri = FILE_URI.get_or_init(|| Regex::new(r#"file:///[^"\\\s]+"#).expect("valid regex"));
+        let open_files = editor_state
+            .map(|text| {
+                file_uri
+                    .find_iter(text)
+                    .filter_map(|found| file_uri_path(found.as_str()))
+                    .map(|path| path.to_string_lossy().into_owned())
+                    .fold(Vec::new(), unique)
+            })
+            .unwrap_or_default();
+
+        WorkspaceInfo { folder_uri, folder, open_files, recent_files }
+    }
+
+    /// Whether there's nothing to say.
+    pub fn is_empty(&self) -> bool {
+        self.folder_uri.is_none() && self.open_files.is_empty() && self.recent_files.is_empty()
+    }
+
+    /// A file's path relative to the workspace folder, if it's in it.
+    pub fn relative<'a>(&self, file: &'a str) -> Option<&'a str> {
+        let folder = self.folder.as_deref()?;
+        let relative = Path::new(file).strip_prefix(folder).ok()?.to_str()?;
+        (!relative.is_empty()).then_some(relative)
+    }
+
+    /// The top-level directories of the folder the recent files are in,
+    /// with how many are in each, busiest first: the part of the codebase
+    /// the work was in. Files at the top level count as `.`.
+    pub fn areas(&self) -> Vec<(String, usize)> {
+        let mut areas: Vec<(String, usize)> = Vec::new();
+        for file in &self.recent_files {
+            let Some(relative) = self.relative(file) else {
+                continue;
+            };
+            let mut components = Path::new(relative).components();
+            let top = components.next().map(|dir| dir.as_os_str().to_string_lossy().into_owned());
+            let area = match (top, components.next()) {
+                (Some(dir), Some(_)) => format!("{}/", dir),
+                _ => ".".to_string(),
+            };
+            match areas.iter_mut().find(|(known, _)| *known == area) {
+                Some((_, count)) => *count += 1,
+                None => areas.push((area, 1)),
+            }
+        }
+        areas.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+        areas
+    }
+}
+
+/// Add `item` to `list` unless it's already there.
+fn unique(mut list: Vec<String>, item: String) -> Vec<String> {
+    if !list.contains(&item) {
+        list.push(item);
+    }
+    list
+}